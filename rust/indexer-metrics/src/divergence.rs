@@ -0,0 +1,299 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Periodically samples a transaction from the fullnode, derives a few facts from it and
+//! compares them to what the processors wrote to Postgres (queried through Hasura). This is
+//! meant to surface silent parsing bugs rather than lag, so only versions that are well
+//! behind the ledger tip are sampled.
+
+use crate::{
+    metrics::{DIVERGENCE_DETECTED_COUNT, TASK_FAILURE_COUNT},
+    util::{deserialize_from_string, get_url_with_timeout, post_url_with_timeout},
+};
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::time::Duration;
+
+const QUERY_TIMEOUT_MS: u64 = 2000;
+
+/// Facts that can be compared between the fullnode and Postgres.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DivergenceCheck {
+    /// Number of events emitted by the transaction, checked against `events`.
+    EventCount,
+    /// Sender of a user transaction, checked against `user_transactions`.
+    Sender,
+}
+
+impl DivergenceCheck {
+    pub fn table_name(&self) -> &'static str {
+        match self {
+            DivergenceCheck::EventCount => "events",
+            DivergenceCheck::Sender => "user_transactions",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DivergenceCheckConfig {
+    /// How often a version is sampled and checked.
+    #[serde(default = "DivergenceCheckConfig::default_sample_interval_secs")]
+    pub sample_interval_secs: u64,
+    /// How far behind the ledger tip to sample, so processors have had time to catch up.
+    #[serde(default = "DivergenceCheckConfig::default_version_lag")]
+    pub version_lag: u64,
+    #[serde(default = "DivergenceCheckConfig::default_checks")]
+    pub checks: Vec<DivergenceCheck>,
+}
+
+impl DivergenceCheckConfig {
+    pub const fn default_sample_interval_secs() -> u64 {
+        60
+    }
+
+    pub const fn default_version_lag() -> u64 {
+        10_000
+    }
+
+    pub fn default_checks() -> Vec<DivergenceCheck> {
+        vec![DivergenceCheck::EventCount, DivergenceCheck::Sender]
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct LedgerInfoResponse {
+    #[serde(deserialize_with = "deserialize_from_string")]
+    ledger_version: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct FullnodeTransaction {
+    #[serde(rename = "type")]
+    transaction_type: String,
+    sender: Option<String>,
+    #[serde(default)]
+    events: Vec<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct UserTransactionSender {
+    sender: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct EventsAggregateCount {
+    count: i64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct EventsAggregate {
+    aggregate: EventsAggregateCount,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TransactionFactsResponseInner {
+    user_transactions: Vec<UserTransactionSender>,
+    events_aggregate: EventsAggregate,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct TransactionFactsResponse {
+    data: TransactionFactsResponseInner,
+}
+
+/// Facts derived independently from a single transaction.
+#[derive(Debug, Default, PartialEq)]
+struct TransactionFacts {
+    sender: Option<String>,
+    event_count: i64,
+}
+
+impl From<FullnodeTransaction> for TransactionFacts {
+    fn from(txn: FullnodeTransaction) -> Self {
+        Self {
+            sender: (txn.transaction_type == "user_transaction")
+                .then_some(txn.sender)
+                .flatten()
+                .map(|s| standardize_address(&s)),
+            event_count: txn.events.len() as i64,
+        }
+    }
+}
+
+impl From<TransactionFactsResponse> for TransactionFacts {
+    fn from(resp: TransactionFactsResponse) -> Self {
+        Self {
+            sender: resp
+                .data
+                .user_transactions
+                .into_iter()
+                .next()
+                .map(|t| standardize_address(&t.sender)),
+            event_count: resp.data.events_aggregate.aggregate.count,
+        }
+    }
+}
+
+/// The fullnode may shorten special addresses while the indexer always pads to 64 hex chars.
+fn standardize_address(address: &str) -> String {
+    let trimmed = address
+        .trim_start_matches("0x")
+        .trim_start_matches('0')
+        .to_lowercase();
+    format!("0x{:0>64}", trimmed)
+}
+
+/// Returns the checks whose facts differ between the fullnode and Postgres.
+fn find_divergences(
+    checks: &[DivergenceCheck],
+    fullnode: &TransactionFacts,
+    postgres: &TransactionFacts,
+) -> Vec<DivergenceCheck> {
+    checks
+        .iter()
+        .filter(|check| match check {
+            DivergenceCheck::EventCount => fullnode.event_count != postgres.event_count,
+            DivergenceCheck::Sender => fullnode.sender != postgres.sender,
+        })
+        .copied()
+        .collect()
+}
+
+async fn fetch_fullnode_facts(
+    client: &Client,
+    fullnode_url: &str,
+    version: u64,
+) -> Result<TransactionFacts> {
+    let txn_url = format!("{}/transactions/by_version/{}", fullnode_url, version);
+    let txn = get_url_with_timeout(client, &txn_url, QUERY_TIMEOUT_MS)
+        .await
+        .context("Transaction request timed out")??
+        .json::<FullnodeTransaction>()
+        .await?;
+    Ok(txn.into())
+}
+
+async fn fetch_postgres_facts(
+    client: &Client,
+    hasura_url: &str,
+    version: u64,
+) -> Result<TransactionFacts> {
+    let data = serde_json::json!({
+        "query": r#"
+            query TransactionFacts($version: bigint!) {
+                user_transactions(where: {version: {_eq: $version}}) {
+                    sender
+                }
+                events_aggregate(where: {transaction_version: {_eq: $version}}) {
+                    aggregate {
+                        count
+                    }
+                }
+            }
+        "#,
+        "variables": { "version": version },
+    });
+    let resp = post_url_with_timeout(client, hasura_url, data, QUERY_TIMEOUT_MS)
+        .await
+        .context("Hasura request timed out")??
+        .json::<TransactionFactsResponse>()
+        .await?;
+    Ok(resp.into())
+}
+
+async fn check_divergence_once(
+    client: &Client,
+    config: &DivergenceCheckConfig,
+    fullnode_url: &str,
+    hasura_url: &str,
+    chain_name: &str,
+) -> Result<()> {
+    let ledger_info = get_url_with_timeout(client, fullnode_url, QUERY_TIMEOUT_MS)
+        .await
+        .context("Ledger info request timed out")??
+        .json::<LedgerInfoResponse>()
+        .await?;
+    let version = ledger_info
+        .ledger_version
+        .saturating_sub(config.version_lag);
+
+    let fullnode_facts = fetch_fullnode_facts(client, fullnode_url, version).await?;
+    let postgres_facts = fetch_postgres_facts(client, hasura_url, version).await?;
+
+    for check in find_divergences(&config.checks, &fullnode_facts, &postgres_facts) {
+        tracing::error!(
+            version = version,
+            check = ?check,
+            fullnode = ?fullnode_facts,
+            postgres = ?postgres_facts,
+            "Divergence detected between fullnode and Postgres"
+        );
+        DIVERGENCE_DETECTED_COUNT
+            .with_label_values(&[check.table_name(), chain_name])
+            .inc();
+    }
+    Ok(())
+}
+
+pub async fn start_divergence_check(
+    client: Client,
+    config: DivergenceCheckConfig,
+    fullnode_url: String,
+    hasura_url: String,
+    chain_name: String,
+) {
+    let fullnode_url = fullnode_url.trim_end_matches('/').to_string();
+    loop {
+        if let Err(err) =
+            check_divergence_once(&client, &config, &fullnode_url, &hasura_url, &chain_name).await
+        {
+            tracing::error!(error = ?err, "Divergence check failed");
+            TASK_FAILURE_COUNT
+                .with_label_values(&["divergence_check", &chain_name])
+                .inc();
+        }
+        tokio::time::sleep(Duration::from_secs(config.sample_interval_secs)).await;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_address() {
+        assert_eq!(
+            standardize_address("0x1"),
+            standardize_address(&format!("0x{:0>64}", "1"))
+        );
+        assert_eq!(standardize_address("0xABC"), format!("0x{:0>64}", "abc"));
+    }
+
+    #[test]
+    fn test_find_divergences() {
+        let fullnode_txn: FullnodeTransaction = serde_json::from_str(
+            r#"{"type": "user_transaction", "sender": "0xa", "events": [{}, {}]}"#,
+        )
+        .unwrap();
+        let postgres_resp: TransactionFactsResponse = serde_json::from_str(
+            r#"
+            {
+                "data": {
+                    "user_transactions": [{"sender": "0x000000000000000000000000000000000000000000000000000000000000000a"}],
+                    "events_aggregate": {"aggregate": {"count": 1}}
+                }
+            }
+            "#,
+        )
+        .unwrap();
+        let divergences = find_divergences(
+            &DivergenceCheckConfig::default_checks(),
+            &fullnode_txn.into(),
+            &postgres_resp.into(),
+        );
+        assert_eq!(divergences, vec![DivergenceCheck::EventCount]);
+    }
+}
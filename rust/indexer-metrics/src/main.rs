@@ -1,17 +1,22 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use chrono::NaiveDateTime;
 use clap::Parser;
 use indexer_metrics::{
+    divergence::{start_divergence_check, DivergenceCheckConfig},
     metrics::{
         HASURA_API_LATEST_TRANSACTION_LATENCY_IN_SECS, HASURA_API_LATEST_TRANSACTION_TIMESTAMP,
         HASURA_API_LATEST_VERSION, HASURA_API_LATEST_VERSION_TIMESTAMP, PFN_LEDGER_TIMESTAMP,
         PFN_LEDGER_VERSION, TASK_FAILURE_COUNT,
     },
-    util::{deserialize_from_string, fetch_processor_status_with_timeout, get_url_with_timeout},
+    util::{
+        deserialize_from_string, fetch_processor_status_with_timeout, get_url_with_timeout,
+        HttpClientConfig,
+    },
 };
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use server_framework::{RunnableConfig, ServerArgs};
 use tokio::time::Duration;
@@ -52,24 +57,59 @@ pub struct PostProcessorConfig {
     pub hasura_graphql_endpoint: Option<String>,
     pub fullnode_rest_api_endpoint: Option<String>,
     pub chain_name: String,
+    /// Requires both the Hasura and fullnode endpoints.
+    #[serde(default)]
+    pub divergence_check: Option<DivergenceCheckConfig>,
+    /// Proxy and TLS settings for all requests.
+    #[serde(default)]
+    pub http_client: HttpClientConfig,
+}
+
+impl PostProcessorConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.divergence_check.is_some()
+            && (self.hasura_graphql_endpoint.is_none() || self.fullnode_rest_api_endpoint.is_none())
+        {
+            bail!(
+                "divergence_check requires both hasura_graphql_endpoint and fullnode_rest_api_endpoint"
+            );
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
 impl RunnableConfig for PostProcessorConfig {
     async fn run(&self) -> Result<()> {
+        self.validate()?;
         let mut tasks = vec![];
         let hasura_graphql_endpoint = self.hasura_graphql_endpoint.clone();
         let fullnode_rest_api_endpoint = self.fullnode_rest_api_endpoint.clone();
         let chain_name = self.chain_name.clone();
+        let client = self.http_client.build_client()?;
 
+        if let (Some(config), Some(hasura), Some(fullnode)) = (
+            self.divergence_check.clone(),
+            hasura_graphql_endpoint.clone(),
+            fullnode_rest_api_endpoint.clone(),
+        ) {
+            tasks.push(tokio::spawn(start_divergence_check(
+                client.clone(),
+                config,
+                fullnode,
+                hasura,
+                chain_name.clone(),
+            )));
+        }
         if let Some(endpoint) = hasura_graphql_endpoint {
             tasks.push(tokio::spawn(start_processor_status_fetch(
+                client.clone(),
                 endpoint,
                 chain_name.clone(),
             )));
         }
         if let Some(fullnode) = fullnode_rest_api_endpoint {
-            tasks.push(tokio::spawn(start_fn_fetch(fullnode, chain_name)));
+            tasks.push(tokio::spawn(start_fn_fetch(client, fullnode, chain_name)));
         }
 
         let _ = futures::future::join_all(tasks).await;
@@ -89,9 +129,9 @@ async fn main() -> Result<()> {
         .await
 }
 
-async fn start_fn_fetch(url: String, chain_name: String) {
+async fn start_fn_fetch(client: Client, url: String, chain_name: String) {
     loop {
-        let result = get_url_with_timeout(&url, QUERY_TIMEOUT_MS).await;
+        let result = get_url_with_timeout(&client, &url, QUERY_TIMEOUT_MS).await;
         let time_now = tokio::time::Instant::now();
 
         // Handle the result
@@ -136,9 +176,9 @@ async fn start_fn_fetch(url: String, chain_name: String) {
     }
 }
 
-async fn start_processor_status_fetch(url: String, chain_name: String) {
+async fn start_processor_status_fetch(client: Client, url: String, chain_name: String) {
     loop {
-        let result = fetch_processor_status_with_timeout(&url, QUERY_TIMEOUT_MS).await;
+        let result = fetch_processor_status_with_timeout(&client, &url, QUERY_TIMEOUT_MS).await;
         let time_now = tokio::time::Instant::now();
 
         // Handle the result
@@ -272,4 +312,19 @@ mod test {
             .last_transaction_timestamp
             .is_none());
     }
+
+    #[test]
+    fn test_divergence_check_requires_both_endpoints() {
+        let config: PostProcessorConfig = serde_json::from_str(
+            r#"{"fullnode_rest_api_endpoint": "http://localhost:8080", "chain_name": "testnet", "divergence_check": {}}"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_err());
+
+        let config: PostProcessorConfig = serde_json::from_str(
+            r#"{"hasura_graphql_endpoint": "http://localhost:8090", "fullnode_rest_api_endpoint": "http://localhost:8080", "chain_name": "testnet", "divergence_check": {}}"#,
+        )
+        .unwrap();
+        assert!(config.validate().is_ok());
+    }
 }
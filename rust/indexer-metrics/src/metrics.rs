@@ -17,6 +17,16 @@ pub static TASK_FAILURE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+/// Number of sampled transactions whose facts in Postgres did not match the fullnode.
+pub static DIVERGENCE_DETECTED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "indexer_metrics_divergence_detected",
+        "Number of mismatches between Postgres and the fullnode found by the divergence check",
+        &["table_name", "chain_name"],
+    )
+    .unwrap()
+});
+
 pub static HASURA_API_LATEST_VERSION: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
         "indexer_metrics_hasura_latest_version",
@@ -1,9 +1,10 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use reqwest::Client;
-use serde::{Deserialize, Deserializer};
-use std::{str::FromStr, time::Duration};
+use anyhow::Context;
+use reqwest::{Certificate, Client, Proxy};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::{path::PathBuf, str::FromStr, time::Duration};
 use tokio::time::{error::Elapsed, timeout};
 
 /// Deserialize from string to type T
@@ -19,7 +20,48 @@ where
     s.parse::<T>().map_err(D::Error::custom)
 }
 
+/// How the HTTP client used for all fetches is built.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HttpClientConfig {
+    /// Proxy all requests, HTTP and HTTPS, go through.
+    #[serde(default)]
+    pub proxy_url: Option<String>,
+    /// PEM files with root certificates to trust on top of the system ones, e.g. for a proxy
+    /// that terminates TLS with its own CA.
+    #[serde(default)]
+    pub root_certificate_paths: Vec<PathBuf>,
+    /// DANGER: accept any TLS certificate, including expired, self-signed and ones for the
+    /// wrong host. Only meant for internal testing, never set this in production.
+    #[serde(default)]
+    pub danger_accept_invalid_certs: bool,
+}
+
+impl HttpClientConfig {
+    pub fn build_client(&self) -> anyhow::Result<Client> {
+        let mut builder = Client::builder();
+        if let Some(proxy_url) = &self.proxy_url {
+            builder = builder.proxy(
+                Proxy::all(proxy_url).with_context(|| format!("Invalid proxy URL {proxy_url}"))?,
+            );
+        }
+        for path in &self.root_certificate_paths {
+            let pem = std::fs::read(path)
+                .with_context(|| format!("Failed to read root certificate {}", path.display()))?;
+            let certificate = Certificate::from_pem(&pem)
+                .with_context(|| format!("Invalid root certificate {}", path.display()))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+        if self.danger_accept_invalid_certs {
+            tracing::warn!("TLS certificate verification is disabled, only use this for testing");
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        builder.build().context("Failed to build HTTP client")
+    }
+}
+
 pub async fn fetch_processor_status_with_timeout(
+    client: &Client,
     url: &str,
     timeout_ms: u64,
 ) -> Result<Result<reqwest::Response, reqwest::Error>, Elapsed> {
@@ -35,16 +77,15 @@ pub async fn fetch_processor_status_with_timeout(
             }
         "#
     });
-    post_url_with_timeout(url, data, timeout_ms).await
+    post_url_with_timeout(client, url, data, timeout_ms).await
 }
 
-async fn post_url_with_timeout(
+pub async fn post_url_with_timeout(
+    client: &Client,
     url: &str,
     data: serde_json::Value,
     timeout_ms: u64,
 ) -> Result<Result<reqwest::Response, reqwest::Error>, Elapsed> {
-    let client = Client::new();
-
     // Set the timeout duration
     let timeout_duration = Duration::from_millis(timeout_ms);
 
@@ -53,14 +94,66 @@ async fn post_url_with_timeout(
 }
 
 pub async fn get_url_with_timeout(
+    client: &Client,
     url: &str,
     timeout_ms: u64,
 ) -> Result<Result<reqwest::Response, reqwest::Error>, Elapsed> {
-    let client = Client::new();
-
     // Set the timeout duration
     let timeout_duration = Duration::from_millis(timeout_ms);
 
     // Use tokio::time::timeout to set a timeout for the request
     timeout(timeout_duration, client.get(url).send()).await
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    #[tokio::test]
+    async fn test_requests_go_through_proxy() {
+        let proxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let config = HttpClientConfig {
+            proxy_url: Some(format!("http://{}", proxy.local_addr().unwrap())),
+            ..HttpClientConfig::default()
+        };
+        let client = config.build_client().unwrap();
+
+        let proxy_task = tokio::spawn(async move {
+            let (mut stream, _) = proxy.accept().await.unwrap();
+            let mut buf = vec![0; 1024];
+            let n = stream.read(&mut buf).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).to_string()
+        });
+        let response = get_url_with_timeout(&client, "http://fullnode.invalid/v1", 5000)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(response.status().is_success());
+        // A proxied request carries the absolute URL of the target
+        let request = proxy_task.await.unwrap();
+        assert!(request.starts_with("GET http://fullnode.invalid/v1 HTTP/1.1"));
+    }
+
+    #[test]
+    fn test_invalid_client_config_is_an_error() {
+        let config = HttpClientConfig {
+            proxy_url: Some("not a url".to_string()),
+            ..HttpClientConfig::default()
+        };
+        assert!(config.build_client().is_err());
+
+        let config = HttpClientConfig {
+            root_certificate_paths: vec![PathBuf::from("/nonexistent/ca.pem")],
+            ..HttpClientConfig::default()
+        };
+        assert!(config.build_client().is_err());
+    }
+}
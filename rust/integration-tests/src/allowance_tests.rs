@@ -0,0 +1,91 @@
+use crate::{run_permutation_test, test_transactions::TransactionBuilder, PermutationTest};
+use aptos_protos::transaction::v1::Transaction;
+use bigdecimal::BigDecimal;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use processor::{
+    db::postgres::models::allowance_models::allowances::UNLIMITED_ALLOWANCE,
+    processors::{allowance_processor::AllowanceProcessorConfig, ProcessorConfig},
+    schema::current_allowances::dsl::*,
+};
+use std::str::FromStr;
+
+const OWNER: &str = "0x0000000000000000000000000000000000000000000000000000000000000a11";
+const SPENDER: &str = "0x0000000000000000000000000000000000000000000000000000000000000b0b";
+const FA: &str = "0x000000000000000000000000000000000000000000000000000000000000000a";
+
+/// A transaction emitting an allowance event of `struct_name` for the fungible asset `FA`.
+fn allowance_transaction(version: u64, struct_name: &str, allowance: &str) -> Transaction {
+    TransactionBuilder::user(version)
+        .sender(OWNER)
+        .event(
+            &format!("0xcafe::allowance::{}", struct_name),
+            serde_json::json!({
+                "owner": OWNER,
+                "spender": SPENDER,
+                "amount": allowance,
+                "metadata": {"inner": FA},
+            }),
+        )
+        .build()
+}
+
+async fn run_allowance_test(
+    transactions: &[Transaction],
+    expected_amount: BigDecimal,
+    expected_is_unlimited: bool,
+) {
+    // Out of order, a spend can be processed before the approval it consumes
+    let strategy = PermutationTest::new(11, vec!["allowance_activities", "current_allowances"]);
+    let num_transactions = transactions.len() as i64;
+
+    assert!(run_permutation_test(
+        transactions,
+        ProcessorConfig::AllowanceProcessor(AllowanceProcessorConfig {
+            contract_addresses: vec!["0xcafe".to_string()],
+        }),
+        strategy,
+        move |conn, _version| {
+            let rows = current_allowances
+                .filter(owner_address.eq(OWNER))
+                .filter(spender_address.eq(SPENDER))
+                .filter(asset_type.eq(FA))
+                .select((amount, is_unlimited, num_activities))
+                .load::<(BigDecimal, bool, i64)>(conn)?;
+            assert_eq!(
+                rows,
+                [(
+                    expected_amount.clone(),
+                    expected_is_unlimited,
+                    num_transactions
+                )]
+            );
+            Ok(())
+        }
+    )
+    .await
+    .is_ok());
+}
+
+#[tokio::test]
+async fn test_transfer_from_decrements_allowance() {
+    let transactions = [
+        allowance_transaction(1, "Approval", "100"),
+        allowance_transaction(2, "TransferFrom", "30"),
+    ];
+    run_allowance_test(&transactions, BigDecimal::from(70), false).await;
+}
+
+#[tokio::test]
+async fn test_unlimited_allowance_is_not_decremented() {
+    let unlimited = UNLIMITED_ALLOWANCE.to_string();
+    let transactions = [
+        allowance_transaction(1, "Approval", &unlimited),
+        allowance_transaction(2, "TransferFrom", "30"),
+    ];
+    run_allowance_test(
+        &transactions,
+        BigDecimal::from_str(&unlimited).unwrap(),
+        true,
+    )
+    .await;
+}
@@ -0,0 +1,91 @@
+//! Compares a table over a version range in two processor databases and reports the rows that
+//! differ. `inserted_at` is ignored.
+//!
+//! cargo run -p integration-tests --bin compare_databases -- \
+//!     --old-db-url postgres://... --new-db-url postgres://... \
+//!     --table events --start-version 0 --end-version 1000000
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use diesel::{pg::PgConnection, Connection};
+use integration_tests::db_compare::{diff_rows, load_rows, primary_key_columns};
+
+#[derive(Parser)]
+struct Args {
+    #[clap(long)]
+    old_db_url: String,
+    #[clap(long)]
+    new_db_url: String,
+    #[clap(long)]
+    table: String,
+    /// Inclusive.
+    #[clap(long)]
+    start_version: i64,
+    /// Inclusive.
+    #[clap(long)]
+    end_version: i64,
+    /// Column the version range applies to, e.g. `last_transaction_version` for current tables.
+    #[clap(long, default_value = "transaction_version")]
+    version_column: String,
+    /// Stop after this many differences.
+    #[clap(long, default_value_t = 10)]
+    max_differences: usize,
+    /// Versions read from each database at a time.
+    #[clap(long, default_value_t = 10_000)]
+    versions_per_page: i64,
+}
+
+fn connect(db_url: &str) -> Result<PgConnection> {
+    PgConnection::establish(db_url).with_context(|| format!("Error connecting to {}", db_url))
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let mut old_conn = connect(&args.old_db_url)?;
+    let mut new_conn = connect(&args.new_db_url)?;
+    let primary_key = primary_key_columns(&mut old_conn, &args.table)?;
+    if primary_key_columns(&mut new_conn, &args.table)? != primary_key {
+        bail!("Table {} has different primary keys", args.table);
+    }
+
+    let mut num_differences = 0;
+    let mut start_version = args.start_version;
+    while start_version <= args.end_version && num_differences < args.max_differences {
+        let end_version = (start_version + args.versions_per_page.max(1)).min(args.end_version + 1);
+        let load = |conn: &mut PgConnection| {
+            load_rows(
+                conn,
+                &args.table,
+                &args.version_column,
+                &primary_key,
+                start_version,
+                end_version,
+            )
+        };
+        let differences = diff_rows(load(&mut old_conn)?, load(&mut new_conn)?, &primary_key);
+        for difference in differences
+            .into_iter()
+            .take(args.max_differences - num_differences)
+        {
+            println!("{}", difference);
+            num_differences += 1;
+        }
+        start_version = end_version;
+    }
+
+    if num_differences >= args.max_differences {
+        bail!(
+            "{} differs, stopped after {} differences",
+            args.table,
+            num_differences
+        );
+    }
+    if num_differences > 0 {
+        bail!("{} differs in {} rows", args.table, num_differences);
+    }
+    println!(
+        "{} matches for versions {} to {}",
+        args.table, args.start_version, args.end_version
+    );
+    Ok(())
+}
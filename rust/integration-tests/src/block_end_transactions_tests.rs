@@ -0,0 +1,39 @@
+use crate::{
+    test_transactions::CAPTURED_BLOCK_EPILOGUE, ScenarioTest, TestContext, TestProcessorConfig,
+    TestType,
+};
+use chrono::DateTime;
+use diesel::{QueryDsl, RunQueryDsl};
+use processor::{processors::ProcessorConfig, schema::block_end_transactions::dsl::*};
+
+#[tokio::test]
+async fn test_block_epilogue_is_recorded() {
+    let test_context = TestContext::new(&[CAPTURED_BLOCK_EPILOGUE]).await.unwrap();
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::DefaultProcessor(Default::default()),
+    };
+
+    assert!(test_context
+        .run(
+            processor_config,
+            TestType::Scenario(ScenarioTest),
+            |conn, _| {
+                let rows = block_end_transactions
+                    .select((version, block_height, epoch, transaction_type, timestamp))
+                    .load::<(i64, i64, i64, String, chrono::NaiveDateTime)>(conn)?;
+                let expected = (
+                    3,
+                    1,
+                    2,
+                    "TRANSACTION_TYPE_BLOCK_EPILOGUE".to_string(),
+                    DateTime::from_timestamp(1_700_000_001, 812_345_000)
+                        .unwrap()
+                        .naive_utc(),
+                );
+                assert_eq!(rows, [expected]);
+                Ok(())
+            },
+        )
+        .await
+        .is_ok());
+}
@@ -0,0 +1,66 @@
+use crate::{test_transactions::TransactionBuilder, TestContext};
+use aptos_protos::transaction::v1::Transaction;
+use bigdecimal::BigDecimal;
+use diesel::{pg::PgConnection, Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use processor::{
+    processors::{ProcessorConfig, ProcessorTrait},
+    schema::block_gas_stats,
+    utils::database::{new_db_pool, DbConnectionConfig},
+    worker::build_processor_for_testing,
+};
+use std::sync::Arc;
+
+const BLOCK_HEIGHT: u64 = 5;
+
+fn user_transaction(version: u64) -> Transaction {
+    TransactionBuilder::user(version)
+        .block_height(BLOCK_HEIGHT)
+        .gas(10, 100)
+        .build()
+}
+
+#[tokio::test]
+async fn test_block_split_across_batches_out_of_order() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let processor =
+        build_processor_for_testing(ProcessorConfig::BlockGasStatsProcessor, db_pool).unwrap();
+
+    // Block 5 is versions 10 to 15, processed in three batches, the last first and the middle
+    // one twice
+    let block_metadata = TransactionBuilder::block_metadata(10)
+        .block_height(BLOCK_HEIGHT)
+        .build();
+    let batches = [
+        vec![user_transaction(14), user_transaction(15)],
+        vec![block_metadata, user_transaction(11)],
+        vec![user_transaction(12), user_transaction(13)],
+        vec![user_transaction(12), user_transaction(13)],
+    ];
+    for batch in batches {
+        let (start_version, end_version) = (batch[0].version, batch[1].version);
+        processor
+            .process_transactions(Arc::new(batch), start_version, end_version, None)
+            .await
+            .unwrap();
+    }
+
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    let (first_version, last_version, transaction_count, total_gas_used) = block_gas_stats::table
+        .filter(block_gas_stats::block_height.eq(BLOCK_HEIGHT as i64))
+        .select((
+            block_gas_stats::first_version,
+            block_gas_stats::last_version,
+            block_gas_stats::transaction_count,
+            block_gas_stats::total_gas_used,
+        ))
+        .first::<(i64, i64, i64, BigDecimal)>(&mut conn)
+        .unwrap();
+    assert_eq!((first_version, last_version), (10, 15));
+    assert_eq!(transaction_count, 5);
+    assert_eq!(total_gas_used, BigDecimal::from(50));
+}
@@ -0,0 +1,82 @@
+use crate::{
+    test_transactions::transaction_with_events, ScenarioTest, TestContext, TestProcessorConfig,
+    TestType,
+};
+use clickhouse::{Client, Row};
+use processor::{
+    processors::{events_processor::EventsProcessorConfig, ProcessorConfig},
+    utils::clickhouse_sink::ClickhouseConfig,
+};
+use serde::Deserialize;
+use testcontainers::{
+    core::{IntoContainerPort, WaitFor},
+    runners::AsyncRunner,
+    GenericImage,
+};
+
+#[derive(Debug, Deserialize, PartialEq, Row)]
+struct EventRow {
+    transaction_version: i64,
+    event_index: i64,
+    #[serde(rename = "type")]
+    type_: String,
+}
+
+/// Events are written to ClickHouse too, and processing the same versions again doesn't leave
+/// duplicates once merged.
+#[tokio::test]
+async fn test_events_written_to_clickhouse() {
+    let clickhouse_container = GenericImage::new("clickhouse/clickhouse-server", "24.8")
+        .with_exposed_port(8123.tcp())
+        .with_wait_for(WaitFor::message_on_stderr("Ready for connections"))
+        .start()
+        .await
+        .expect("ClickHouse started");
+    let host = clickhouse_container.get_host().await.unwrap();
+    let port = clickhouse_container.get_host_port_ipv4(8123).await.unwrap();
+    let url = format!("http://{host}:{port}");
+
+    let test_context = TestContext::from_transactions(&[transaction_with_events(1, 3)])
+        .await
+        .unwrap();
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::EventsProcessor(EventsProcessorConfig {
+            clickhouse: Some(ClickhouseConfig {
+                url: url.parse().unwrap(),
+                database: ClickhouseConfig::default_database(),
+                user: None,
+                password: None,
+                max_retries: ClickhouseConfig::default_max_retries(),
+                initial_retry_delay_ms: ClickhouseConfig::default_initial_retry_delay_ms(),
+                retry_jitter: Default::default(),
+            }),
+            ..Default::default()
+        }),
+    };
+    // As if the processor restarted before committing progress
+    for _ in 0..2 {
+        assert!(test_context
+            .run(
+                processor_config.clone(),
+                TestType::Scenario(ScenarioTest),
+                |_conn, _version| Ok(())
+            )
+            .await
+            .is_ok());
+    }
+
+    let client = Client::default().with_url(url);
+    let rows = client
+        .query("SELECT ?fields FROM events FINAL ORDER BY transaction_version, event_index")
+        .fetch_all::<EventRow>()
+        .await
+        .unwrap();
+    let expected = (0..3)
+        .map(|i| EventRow {
+            transaction_version: 1,
+            event_index: i,
+            type_: "0x1::coin::DepositEvent".to_string(),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(rows, expected);
+}
@@ -0,0 +1,57 @@
+use crate::{
+    test_transactions::TransactionBuilder, ScenarioTest, TestContext, TestProcessorConfig, TestType,
+};
+use diesel::{QueryDsl, RunQueryDsl};
+use processor::{
+    processors::{fungible_asset_processor::FungibleAssetProcessorConfig, ProcessorConfig},
+    schema::coin_to_fa_mapping::dsl::*,
+};
+
+const MOON_COIN: &str =
+    "0x66c34778730acbb120cefa57a3d98fd21e0c8b3a51e9baee530088b2e444e94c::moon_coin::MoonCoin";
+const MOON_COIN_FA: &str = "0xf772c28c069aa7e4417d85d771957eb3c5c11b5bf90b1965cda23b899ebc0384";
+
+#[tokio::test]
+async fn test_pair_creation_establishes_mapping() {
+    // Migrating MoonCoin creates its paired fungible asset
+    let pair_creation = TransactionBuilder::user(1)
+        .event(
+            "0x1::coin::PairCreation",
+            serde_json::json!({
+                "coin_type": {
+                    "account_address": "0x66c34778730acbb120cefa57a3d98fd21e0c8b3a51e9baee530088b2e444e94c",
+                    // "moon_coin" and "MoonCoin" hex encoded
+                    "module_name": "0x6d6f6f6e5f636f696e",
+                    "struct_name": "0x4d6f6f6e436f696e",
+                },
+                "fungible_asset_metadata_address": MOON_COIN_FA,
+            }),
+        )
+        .build();
+    let test_context = TestContext::from_transactions(&[pair_creation])
+        .await
+        .unwrap();
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::FungibleAssetProcessor(FungibleAssetProcessorConfig::default()),
+    };
+
+    assert!(test_context
+        .run(
+            processor_config,
+            TestType::Scenario(ScenarioTest),
+            |conn, version| {
+                let rows = coin_to_fa_mapping
+                    .select((coin_type, fa_metadata_address, transaction_version))
+                    .load::<(String, String, i64)>(conn)?;
+                let expected = (
+                    MOON_COIN.to_string(),
+                    MOON_COIN_FA.to_string(),
+                    version.parse::<i64>()?,
+                );
+                assert_eq!(rows, [expected]);
+                Ok(())
+            },
+        )
+        .await
+        .is_ok());
+}
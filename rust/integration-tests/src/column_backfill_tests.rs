@@ -0,0 +1,88 @@
+use crate::{test_transactions::transaction_with_events, TestContext};
+use diesel::{
+    pg::PgConnection, sql_query, sql_types::Text, Connection, ExpressionMethods, QueryDsl,
+    RunQueryDsl,
+};
+use processor::{
+    processors::events_processor::indexed_type_backfill,
+    schema::events::dsl::*,
+    utils::database::{new_db_pool, DbConnectionConfig},
+};
+
+type EventRow = (
+    i64,
+    i64,
+    i64,
+    String,
+    serde_json::Value,
+    chrono::NaiveDateTime,
+);
+
+fn load_events(conn: &mut PgConnection) -> Vec<(EventRow, String)> {
+    events
+        .order((transaction_version, event_index))
+        .select((
+            (
+                transaction_version,
+                event_index,
+                sequence_number,
+                type_,
+                data,
+                inserted_at,
+            ),
+            indexed_type,
+        ))
+        .load(conn)
+        .unwrap()
+}
+
+/// Events written before `indexed_type` was, so it's still the column's default.
+#[tokio::test]
+async fn test_backfill_only_changes_the_column() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    // The events of version 1, and one of version 2, which isn't backfilled
+    for (version, index, event_indexed_type) in [
+        (1, 0, ""),
+        (1, 1, "0x1::coin::DepositEvent"),
+        (1, 2, ""),
+        (2, 0, ""),
+    ] {
+        sql_query(format!(
+            "INSERT INTO events (sequence_number, creation_number, account_address, \
+             transaction_version, transaction_block_height, type, data, event_index, \
+             indexed_type) VALUES ({index}, 0, '0x1', {version}, {version}, \
+             '0x1::coin::DepositEvent', '{{\"amount\":\"{index}\"}}', {index}, $1)"
+        ))
+        .bind::<Text, _>(event_indexed_type)
+        .execute(&mut conn)
+        .unwrap();
+    }
+    let before = load_events(&mut conn);
+
+    let num_rows_updated = indexed_type_backfill()
+        .backfill_batch(db_pool, &[transaction_with_events(1, 3)])
+        .await
+        .unwrap();
+    // The one already set is left alone
+    assert_eq!(num_rows_updated, 2);
+
+    let after = load_events(&mut conn);
+    assert_eq!(after.len(), before.len());
+    for ((row_before, indexed_type_before), (row_after, indexed_type_after)) in
+        before.into_iter().zip(after)
+    {
+        assert_eq!(row_before, row_after);
+        let expected = if row_after.0 == 1 {
+            "0x1::coin::DepositEvent"
+        } else {
+            indexed_type_before.as_str()
+        };
+        assert_eq!(indexed_type_after, expected);
+    }
+}
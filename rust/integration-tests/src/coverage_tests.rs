@@ -0,0 +1,86 @@
+use crate::TestContext;
+use diesel::{
+    pg::PgConnection,
+    sql_query,
+    sql_types::{Int8, Text},
+    Connection, RunQueryDsl,
+};
+use processor::utils::{
+    coverage::{get_coverage, VersionRange},
+    database::{new_db_pool, DbConnectionConfig},
+};
+
+const PROCESSOR_NAME: &str = "events_processor";
+
+fn insert_events(conn: &mut PgConnection, first_version: i64, last_version: i64) {
+    // Two events per version, so versions have to be counted once each
+    sql_query(
+        "INSERT INTO events (sequence_number, creation_number, account_address, \
+         transaction_version, transaction_block_height, type, data, event_index, indexed_type) \
+         SELECT 0, 0, '0x1', version, version, '0x1::test::Event', '{}', event_index, \
+         '0x1::test::Event' \
+         FROM generate_series($1::bigint, $2::bigint) AS version, \
+         generate_series(0, 1) AS event_index",
+    )
+    .bind::<Int8, _>(first_version)
+    .bind::<Int8, _>(last_version)
+    .execute(conn)
+    .unwrap();
+}
+
+fn range(start_version: i64, end_version: i64) -> VersionRange {
+    VersionRange {
+        start_version,
+        end_version,
+    }
+}
+
+#[tokio::test]
+async fn test_coverage_with_hole() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    // Versions 5 to 7 are missing, as after a backfill that skipped them
+    insert_events(&mut conn, 1, 4);
+    insert_events(&mut conn, 8, 10);
+    // Version 14 is written but not yet committed as processed
+    insert_events(&mut conn, 14, 14);
+    sql_query("INSERT INTO processor_status (processor, last_success_version) VALUES ($1, 12)")
+        .bind::<Text, _>(PROCESSOR_NAME)
+        .execute(&mut conn)
+        .unwrap();
+
+    let coverage = get_coverage(db_pool.clone(), PROCESSOR_NAME, "events", 0, 100)
+        .await
+        .unwrap();
+    assert_eq!(coverage.last_success_version, 12);
+    assert_eq!(coverage.end_version, 12);
+    assert_eq!(coverage.covered, [range(1, 4), range(8, 10)]);
+    assert_eq!(coverage.holes, [range(0, 0), range(5, 7), range(11, 12)]);
+
+    // Only the requested versions are scanned
+    let coverage = get_coverage(db_pool.clone(), PROCESSOR_NAME, "events", 3, 8)
+        .await
+        .unwrap();
+    assert_eq!(coverage.covered, [range(3, 4), range(8, 8)]);
+    assert_eq!(coverage.holes, [range(5, 7)]);
+    assert!(
+        get_coverage(db_pool.clone(), PROCESSOR_NAME, "events", 0, i64::MAX)
+            .await
+            .is_err()
+    );
+
+    // Only tables with a known version column can be scanned
+    assert!(
+        get_coverage(db_pool.clone(), PROCESSOR_NAME, "processor_status", 0, 100)
+            .await
+            .is_err()
+    );
+    assert!(get_coverage(db_pool, "missing_processor", "events", 0, 100)
+        .await
+        .is_err());
+}
@@ -0,0 +1,44 @@
+use crate::{run_permutation_test, test_transactions::TransactionBuilder, PermutationTest};
+use diesel::{QueryDsl, RunQueryDsl};
+use processor::{
+    processors::{default_processor::DefaultProcessorConfig, ProcessorConfig},
+    schema::current_move_resources::dsl::*,
+};
+
+/// Whatever order the updates of a resource are processed in, the latest one is kept.
+#[tokio::test]
+async fn test_current_move_resource_keeps_latest_version() {
+    let transactions = [(1, 100), (2, 50), (3, 30)].map(|(version, value)| {
+        TransactionBuilder::user(version)
+            .write_resource(
+                "0xa",
+                "0x1::coin::CoinStore",
+                serde_json::json!({"coin": {"value": value.to_string()}}),
+            )
+            .build()
+    });
+    let config = ProcessorConfig::DefaultProcessor(DefaultProcessorConfig {
+        current_move_resources: true,
+    });
+    let strategy = PermutationTest {
+        num_permutations: 6,
+        ..PermutationTest::new(7, vec!["current_move_resources"])
+    };
+
+    assert!(
+        run_permutation_test(&transactions, config, strategy, |conn, _version| {
+            let rows = current_move_resources
+                .select((last_transaction_version, data))
+                .load::<(i64, Option<serde_json::Value>)>(conn)?;
+            assert_eq!(rows.len(), 1);
+            assert_eq!(rows[0].0, 3);
+            assert_eq!(
+                rows[0].1,
+                Some(serde_json::json!({"coin": {"value": "30"}}))
+            );
+            Ok(())
+        })
+        .await
+        .is_ok()
+    );
+}
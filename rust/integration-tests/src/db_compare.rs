@@ -0,0 +1,209 @@
+//! Row level comparison of a table in two processor databases, e.g. the outputs of a processor
+//! before and after a refactor. Unlike the diff tests, neither side is a golden file.
+
+use crate::{diff_test_helper::remove_inserted_at, JsonRow};
+use anyhow::{bail, Context};
+use diesel::{pg::PgConnection, sql_query, sql_types::Text, QueryableByName, RunQueryDsl};
+use serde_json::Value;
+use std::{collections::HashMap, fmt};
+
+/// A row that differs between the databases.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RowDifference {
+    /// In the old database only.
+    Missing { key: Vec<Value> },
+    /// In the new database only.
+    Extra { key: Vec<Value> },
+    /// In both, with different values for `fields`.
+    Changed {
+        key: Vec<Value>,
+        fields: Vec<String>,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl fmt::Display for RowDifference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RowDifference::Missing { key } => write!(f, "{:?}: missing in new database", key),
+            RowDifference::Extra { key } => write!(f, "{:?}: only in new database", key),
+            RowDifference::Changed {
+                key,
+                fields,
+                old,
+                new,
+            } => write!(
+                f,
+                "{:?}: {} differ\n  old: {}\n  new: {}",
+                key,
+                fields.join(", "),
+                old,
+                new
+            ),
+        }
+    }
+}
+
+#[derive(QueryableByName)]
+struct ColumnName {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+/// Only plain identifiers are accepted since table and column names are put into the queries.
+fn check_identifier(identifier: &str) -> anyhow::Result<()> {
+    if identifier.is_empty()
+        || !identifier
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        bail!("Invalid identifier {:?}", identifier);
+    }
+    Ok(())
+}
+
+/// Primary key columns of the table, in key order.
+pub fn primary_key_columns(conn: &mut PgConnection, table: &str) -> anyhow::Result<Vec<String>> {
+    check_identifier(table)?;
+    let query = format!(
+        "SELECT a.attname::text AS name
+        FROM pg_index i
+        JOIN pg_attribute a ON a.attrelid = i.indrelid AND a.attnum = ANY(i.indkey)
+        WHERE i.indrelid = '{}'::regclass AND i.indisprimary
+        ORDER BY array_position(i.indkey::int2[], a.attnum)",
+        table
+    );
+    let columns = sql_query(query)
+        .load::<ColumnName>(conn)
+        .with_context(|| format!("Failed to read the primary key of {}", table))?
+        .into_iter()
+        .map(|column| column.name)
+        .collect::<Vec<_>>();
+    if columns.is_empty() {
+        bail!("Table {} has no primary key", table);
+    }
+    Ok(columns)
+}
+
+/// Rows with versions in `[start_version, end_version)` in primary key order, without the
+/// columns that differ between runs.
+pub fn load_rows(
+    conn: &mut PgConnection,
+    table: &str,
+    version_column: &str,
+    primary_key: &[String],
+    start_version: i64,
+    end_version: i64,
+) -> anyhow::Result<Vec<Value>> {
+    check_identifier(table)?;
+    check_identifier(version_column)?;
+    let query = format!(
+        "SELECT row_to_json(t)::text AS row FROM {table} t WHERE {version_column} >= {start_version} AND {version_column} < {end_version} ORDER BY {}",
+        primary_key.join(", ")
+    );
+    let rows = sql_query(query)
+        .load::<JsonRow>(conn)
+        .with_context(|| format!("Failed to read table {}", table))?
+        .into_iter()
+        .map(|json_row| serde_json::from_str(&json_row.row))
+        .collect::<Result<Vec<Value>, _>>()?;
+    let mut rows = Value::Array(rows);
+    remove_inserted_at(&mut rows);
+    match rows {
+        Value::Array(rows) => Ok(rows),
+        _ => unreachable!(),
+    }
+}
+
+fn row_key(row: &Value, primary_key: &[String]) -> Vec<Value> {
+    primary_key
+        .iter()
+        .map(|column| row.get(column).cloned().unwrap_or(Value::Null))
+        .collect()
+}
+
+/// Differences between the same rows of both databases, in the primary key order of the old
+/// rows, followed by the rows only in the new database.
+pub fn diff_rows(old: Vec<Value>, new: Vec<Value>, primary_key: &[String]) -> Vec<RowDifference> {
+    let mut new_order = vec![];
+    let mut new_by_key = HashMap::new();
+    for row in new {
+        let key = row_key(&row, primary_key);
+        new_order.push(key.clone());
+        new_by_key.insert(key.iter().map(Value::to_string).collect::<Vec<_>>(), row);
+    }
+    let mut differences = vec![];
+    for old_row in old {
+        let key = row_key(&old_row, primary_key);
+        let key_str = key.iter().map(Value::to_string).collect::<Vec<_>>();
+        let Some(new_row) = new_by_key.remove(&key_str) else {
+            differences.push(RowDifference::Missing { key });
+            continue;
+        };
+        let empty = serde_json::Map::new();
+        let old_fields = old_row.as_object().unwrap_or(&empty);
+        let new_fields = new_row.as_object().unwrap_or(&empty);
+        let mut fields = old_fields
+            .keys()
+            .chain(new_fields.keys())
+            .filter(|field| old_fields.get(*field) != new_fields.get(*field))
+            .cloned()
+            .collect::<Vec<_>>();
+        fields.sort();
+        fields.dedup();
+        if !fields.is_empty() {
+            differences.push(RowDifference::Changed {
+                key,
+                fields,
+                old: old_row,
+                new: new_row,
+            });
+        }
+    }
+    for key in new_order {
+        let key_str = key.iter().map(Value::to_string).collect::<Vec<_>>();
+        if new_by_key.contains_key(&key_str) {
+            differences.push(RowDifference::Extra { key });
+        }
+    }
+    differences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_rows() {
+        let primary_key = vec!["transaction_version".to_string(), "event_index".to_string()];
+        let old = vec![
+            json!({"transaction_version": 1, "event_index": 0, "type_": "0x1::a::B"}),
+            json!({"transaction_version": 1, "event_index": 1, "type_": "0x1::a::B"}),
+            json!({"transaction_version": 2, "event_index": 0, "type_": "0x1::a::B"}),
+        ];
+        let new = vec![
+            json!({"transaction_version": 1, "event_index": 0, "type_": "0x1::a::B"}),
+            json!({"transaction_version": 2, "event_index": 0, "type_": "0x1::a::C"}),
+            json!({"transaction_version": 3, "event_index": 0, "type_": "0x1::a::B"}),
+        ];
+        assert_eq!(
+            diff_rows(old, new, &primary_key),
+            vec![
+                RowDifference::Missing {
+                    key: vec![json!(1), json!(1)]
+                },
+                RowDifference::Changed {
+                    key: vec![json!(2), json!(0)],
+                    fields: vec!["type_".to_string()],
+                    old: json!({"transaction_version": 2, "event_index": 0, "type_": "0x1::a::B"}),
+                    new: json!({"transaction_version": 2, "event_index": 0, "type_": "0x1::a::C"}),
+                },
+                RowDifference::Extra {
+                    key: vec![json!(3), json!(0)]
+                },
+            ]
+        );
+    }
+}
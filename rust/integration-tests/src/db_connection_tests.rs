@@ -0,0 +1,35 @@
+use crate::TestContext;
+use diesel::sql_query;
+use diesel_async::RunQueryDsl;
+use processor::utils::database::{new_db_pool, DbConnectionConfig};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_statement_timeout_aborts_slow_statement() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let connection_config = DbConnectionConfig {
+        statement_timeout_ms: 200,
+        ..DbConnectionConfig::default()
+    };
+    let db_pool = new_db_pool(&db_url, Some(1), &connection_config)
+        .await
+        .unwrap();
+    let mut conn = db_pool.get().await.unwrap();
+
+    let res = tokio::time::timeout(
+        Duration::from_secs(10),
+        sql_query("SELECT pg_sleep(30)").execute(&mut conn),
+    )
+    .await
+    .expect("The statement should be aborted by the timeout rather than hang");
+    let err = res.unwrap_err();
+    assert!(
+        err.to_string().contains("statement timeout"),
+        "Unexpected error: {}",
+        err
+    );
+
+    // The connection is still usable afterwards
+    sql_query("SELECT 1").execute(&mut conn).await.unwrap();
+}
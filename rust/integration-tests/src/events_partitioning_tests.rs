@@ -0,0 +1,111 @@
+use crate::{test_transactions::transaction_with_events, TestContext};
+use diesel::{
+    pg::PgConnection,
+    sql_query,
+    sql_types::{Int8, Text},
+    Connection, QueryableByName, RunQueryDsl,
+};
+use processor::{
+    processors::{
+        events_processor::{partition_events_table, EventsProcessorConfig},
+        ProcessorConfig, ProcessorTrait,
+    },
+    utils::database::{new_db_pool, DbConnectionConfig},
+    worker::build_processor_for_testing,
+};
+use std::sync::Arc;
+
+#[derive(QueryableByName)]
+struct EventPartition {
+    #[diesel(sql_type = Text)]
+    partition: String,
+    #[diesel(sql_type = Int8)]
+    transaction_version: i64,
+}
+
+#[derive(QueryableByName)]
+struct Name {
+    #[diesel(sql_type = Text)]
+    name: String,
+}
+
+fn names(conn: &mut PgConnection, query: &str) -> Vec<String> {
+    sql_query(query)
+        .load::<Name>(conn)
+        .unwrap()
+        .into_iter()
+        .map(|row| row.name)
+        .collect()
+}
+
+fn indexes_and_views(conn: &mut PgConnection) -> (Vec<String>, Vec<String>) {
+    (
+        names(
+            conn,
+            "SELECT indexname AS name FROM pg_indexes WHERE tablename = 'events' ORDER BY 1",
+        ),
+        names(
+            conn,
+            "SELECT DISTINCT v.relname::TEXT AS name FROM pg_depend d \
+             JOIN pg_rewrite r ON r.oid = d.objid \
+             JOIN pg_class v ON v.oid = r.ev_class \
+             WHERE d.refobjid = 'events'::regclass AND v.relkind = 'v' ORDER BY 1",
+        ),
+    )
+}
+
+#[tokio::test]
+async fn test_events_spanning_partition_boundary() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    let (unpartitioned_indexes, unpartitioned_views) = indexes_and_views(&mut conn);
+
+    partition_events_table(db_pool.clone()).await.unwrap();
+    // The table is rebuilt from the catalog, so nothing the migrations created is lost
+    assert_eq!(
+        indexes_and_views(&mut conn),
+        (unpartitioned_indexes, unpartitioned_views)
+    );
+    // Already partitioned, so this does nothing
+    partition_events_table(db_pool.clone()).await.unwrap();
+
+    let processor = build_processor_for_testing(
+        ProcessorConfig::EventsProcessor(EventsProcessorConfig {
+            partition_interval: Some(10),
+            ..Default::default()
+        }),
+        db_pool,
+    )
+    .unwrap();
+    let transactions = [9, 10, 25]
+        .into_iter()
+        .map(|version| transaction_with_events(version, 2))
+        .collect::<Vec<_>>();
+    processor
+        .process_transactions(Arc::new(transactions), 9, 25, None)
+        .await
+        .unwrap();
+
+    let rows = sql_query(
+        "SELECT tableoid::regclass::TEXT AS partition, transaction_version FROM events \
+         ORDER BY transaction_version, event_index",
+    )
+    .load::<EventPartition>(&mut conn)
+    .unwrap()
+    .into_iter()
+    .map(|row| (row.partition, row.transaction_version))
+    .collect::<Vec<_>>();
+    assert_eq!(rows, vec![
+        ("events_0".to_string(), 9),
+        ("events_0".to_string(), 9),
+        ("events_10".to_string(), 10),
+        ("events_10".to_string(), 10),
+        ("events_20".to_string(), 25),
+        ("events_20".to_string(), 25),
+    ]);
+}
@@ -0,0 +1,49 @@
+use crate::{
+    test_transactions::CAPTURED_FAILED_COIN_TRANSFER, ScenarioTest, TestContext,
+    TestProcessorConfig, TestType,
+};
+use bigdecimal::BigDecimal;
+use diesel::{QueryDsl, RunQueryDsl};
+use processor::{processors::ProcessorConfig, schema::user_transactions::dsl::*};
+
+const INSUFFICIENT_BALANCE: &str = "Move abort in 0x1::coin: EINSUFFICIENT_BALANCE(0x10006): \
+                                    Not enough coins to complete transaction";
+
+#[tokio::test]
+async fn test_failed_transaction_vm_status() {
+    let test_context = TestContext::new(&[CAPTURED_FAILED_COIN_TRANSFER])
+        .await
+        .unwrap();
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::UserTransactionProcessor(Default::default()),
+    };
+
+    assert!(test_context
+        .run(
+            processor_config,
+            TestType::Scenario(ScenarioTest),
+            |conn, _| {
+                let rows = user_transactions
+                    .select((success, vm_status, abort_code, abort_location))
+                    .load::<(
+                        Option<bool>,
+                        Option<String>,
+                        Option<BigDecimal>,
+                        Option<String>,
+                    )>(conn)?;
+                let expected = (
+                    Some(false),
+                    Some(INSUFFICIENT_BALANCE.to_string()),
+                    Some(BigDecimal::from(0x10006)),
+                    Some(
+                        "0x0000000000000000000000000000000000000000000000000000000000000001::coin"
+                            .to_string(),
+                    ),
+                );
+                assert_eq!(rows, [expected]);
+                Ok(())
+            },
+        )
+        .await
+        .is_ok());
+}
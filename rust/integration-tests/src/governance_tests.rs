@@ -0,0 +1,156 @@
+use crate::{
+    run_permutation_test,
+    test_transactions::{TransactionBuilder, BASE_TIMESTAMP_SECS},
+    PermutationTest,
+};
+use aptos_protos::transaction::v1::Transaction;
+use bigdecimal::BigDecimal;
+use diesel::{
+    sql_query,
+    sql_types::{Int8, Numeric, Text},
+    QueryableByName, RunQueryDsl,
+};
+use processor::processors::ProcessorConfig;
+
+const PROPOSER: &str = "0x0000000000000000000000000000000000000000000000000000000000000a11";
+const VOTER_A: &str = "0x00000000000000000000000000000000000000000000000000000000000000aa";
+const VOTER_B: &str = "0x00000000000000000000000000000000000000000000000000000000000000bb";
+// Year 2096, so still open when the test runs
+const FUTURE_EXPIRATION_SECS: u64 = 4_000_000_000;
+
+fn create_proposal(version: u64, proposal_id: u64, expiration_secs: u64) -> Transaction {
+    TransactionBuilder::user(version)
+        .event(
+            "0x1::voting::CreateProposal",
+            serde_json::json!({
+                "proposal_id": proposal_id.to_string(),
+                "early_resolution_vote_threshold": {"vec": ["300"]},
+                "execution_hash": "0x1234",
+                "expiration_secs": expiration_secs.to_string(),
+                "metadata": {"data": []},
+                "min_vote_threshold": "200",
+            }),
+        )
+        .event(
+            "0x1::aptos_governance::CreateProposal",
+            serde_json::json!({
+                "proposer": PROPOSER,
+                "stake_pool": PROPOSER,
+                "proposal_id": proposal_id.to_string(),
+                "execution_hash": "0x1234",
+                "proposal_metadata": {"data": []},
+            }),
+        )
+        .build()
+}
+
+fn vote(
+    version: u64,
+    proposal_id: u64,
+    voter: &str,
+    num_votes: u64,
+    should_pass: bool,
+) -> Transaction {
+    TransactionBuilder::user(version)
+        .event(
+            "0x1::voting::Vote",
+            serde_json::json!({
+                "proposal_id": proposal_id.to_string(),
+                "num_votes": num_votes.to_string(),
+            }),
+        )
+        .event(
+            "0x1::aptos_governance::Vote",
+            serde_json::json!({
+                "proposal_id": proposal_id.to_string(),
+                "voter": voter,
+                "stake_pool": voter,
+                "num_votes": num_votes.to_string(),
+                "should_pass": should_pass,
+            }),
+        )
+        .build()
+}
+
+fn resolve(version: u64, proposal_id: u64, yes_votes: u64, no_votes: u64) -> Transaction {
+    TransactionBuilder::user(version)
+        .event(
+            "0x1::voting::ResolveProposal",
+            serde_json::json!({
+                "proposal_id": proposal_id.to_string(),
+                "yes_votes": yes_votes.to_string(),
+                "no_votes": no_votes.to_string(),
+                "resolved_early": true,
+            }),
+        )
+        // The module event is only governance's alongside a write of its forum
+        .write_resource(
+            "0x1",
+            "0x1::voting::VotingForum<0x1::governance_proposal::GovernanceProposal>",
+            serde_json::json!({"next_proposal_id": (proposal_id + 1).to_string()}),
+        )
+        .build()
+}
+
+#[derive(Debug, PartialEq, QueryableByName)]
+struct ProposalOutcome {
+    #[diesel(sql_type = Int8)]
+    proposal_id: i64,
+    #[diesel(sql_type = Text)]
+    outcome: String,
+    #[diesel(sql_type = Numeric)]
+    yes_votes: BigDecimal,
+    #[diesel(sql_type = Numeric)]
+    no_votes: BigDecimal,
+}
+
+/// Proposal 1 is voted on and resolved, proposal 2 fails to pass and expires, and proposal 3 is
+/// still open.
+#[tokio::test]
+async fn test_proposal_lifecycle() {
+    let transactions = [
+        create_proposal(1, 1, FUTURE_EXPIRATION_SECS),
+        vote(2, 1, VOTER_A, 300, true),
+        vote(3, 1, VOTER_B, 100, false),
+        resolve(4, 1, 300, 100),
+        create_proposal(5, 2, BASE_TIMESTAMP_SECS as u64 + 100),
+        vote(6, 2, VOTER_A, 50, false),
+        create_proposal(7, 3, FUTURE_EXPIRATION_SECS),
+    ];
+    // Out of order, the resolution can be processed before the proposal it resolves
+    let strategy = PermutationTest::new(17, vec![
+        "governance_proposals",
+        "governance_votes",
+        "governance_proposal_resolutions",
+    ]);
+
+    assert!(run_permutation_test(
+        &transactions,
+        ProcessorConfig::GovernanceProcessor,
+        strategy,
+        |conn, _version| {
+            let outcomes = sql_query(
+                "SELECT proposal_id, outcome, yes_votes, no_votes \
+                 FROM governance_proposal_outcomes ORDER BY proposal_id",
+            )
+            .load::<ProposalOutcome>(conn)?;
+            let expected = [
+                (1, "resolved", 300, 100),
+                (2, "expired", 0, 50),
+                (3, "voting", 0, 0),
+            ]
+            .map(
+                |(proposal_id, outcome, yes_votes, no_votes)| ProposalOutcome {
+                    proposal_id,
+                    outcome: outcome.to_string(),
+                    yes_votes: BigDecimal::from(yes_votes),
+                    no_votes: BigDecimal::from(no_votes),
+                },
+            );
+            assert_eq!(outcomes, expected);
+            Ok(())
+        }
+    )
+    .await
+    .is_ok());
+}
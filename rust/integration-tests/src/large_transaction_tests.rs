@@ -0,0 +1,45 @@
+use crate::{
+    test_transactions::transaction_with_events, ScenarioTest, TestContext, TestProcessorConfig,
+    TestType,
+};
+use diesel::{
+    dsl::{count_star, max},
+    ExpressionMethods, QueryDsl, RunQueryDsl,
+};
+use processor::{
+    processors::{events_processor::EventsProcessorConfig, ProcessorConfig},
+    schema::events::dsl::*,
+};
+
+const NUM_EVENTS: usize = 50_000;
+
+#[tokio::test]
+async fn test_transaction_with_more_events_than_max_events_per_insert() {
+    let test_context = TestContext::from_transactions(&[transaction_with_events(1, NUM_EVENTS)])
+        .await
+        .unwrap();
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::EventsProcessor(EventsProcessorConfig {
+            max_events_per_insert: Some(10_000),
+            ..Default::default()
+        }),
+    };
+
+    assert!(test_context
+        .run(
+            processor_config,
+            TestType::Scenario(ScenarioTest),
+            |conn, version| {
+                let version = version.parse::<i64>()?;
+                let (count, max_index) = events
+                    .filter(transaction_version.eq(version))
+                    .select((count_star(), max(event_index)))
+                    .first::<(i64, Option<i64>)>(conn)?;
+                assert_eq!(count, NUM_EVENTS as i64);
+                assert_eq!(max_index, Some(NUM_EVENTS as i64 - 1));
+                Ok(())
+            },
+        )
+        .await
+        .is_ok());
+}
@@ -1,32 +1,115 @@
 use anyhow::Context;
 use aptos_protos::transaction::v1::Transaction;
-use diesel::{pg::PgConnection, sql_query, Connection, RunQueryDsl};
+use diesel::{
+    pg::PgConnection, sql_query, sql_types::Text, Connection, QueryableByName, RunQueryDsl,
+};
 use processor::{
     processors::{ProcessorConfig, ProcessorTrait},
-    utils::database::{new_db_pool, run_pending_migrations},
+    utils::database::{new_db_pool, run_pending_migrations, DbConnectionConfig},
     worker::build_processor_for_testing,
 };
 use testcontainers::{
     core::{IntoContainerPort, WaitFor},
     runners::AsyncRunner,
-    ContainerAsync, GenericImage, ImageExt,
+    ContainerAsync, ContainerRequest, GenericImage, ImageExt,
 };
 
+#[cfg(test)]
+mod allowance_tests;
+#[cfg(test)]
+mod block_end_transactions_tests;
+#[cfg(test)]
+mod block_gas_stats_tests;
+#[cfg(test)]
+mod clickhouse_tests;
+#[cfg(test)]
+mod coin_to_fa_mapping_tests;
+#[cfg(test)]
+mod collection_stats_tests;
+#[cfg(test)]
+mod column_backfill_tests;
+#[cfg(test)]
+mod coverage_tests;
+#[cfg(test)]
+mod current_move_resources_tests;
+pub mod db_compare;
+#[cfg(test)]
+mod db_connection_tests;
 mod diff_test_helper;
+#[cfg(test)]
+mod events_partitioning_tests;
+#[cfg(test)]
+mod failed_transaction_tests;
+#[cfg(test)]
+mod governance_tests;
+#[cfg(test)]
+mod large_transaction_tests;
+#[cfg(test)]
+mod marketplace_activities_tests;
 mod models;
+#[cfg(test)]
+mod outbox_tests;
+#[cfg(test)]
+mod progress_lease_tests;
+#[cfg(test)]
+mod replay_tests;
+#[cfg(test)]
+mod retention_tests;
 mod sanity_test;
+#[cfg(test)]
+mod schema_drift_tests;
+#[cfg(test)]
+mod schema_version_tests;
 mod sdk_tests;
+#[cfg(test)]
+mod test_transactions;
+#[cfg(test)]
+mod typed_events_tests;
+#[cfg(test)]
+mod user_transaction_tests;
+#[cfg(test)]
+mod write_ahead_log_tests;
 
-use std::time::Duration;
-use tokio::time::sleep; // You can use tokio's async sleep for delay
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{sync::OnceCell, time::sleep}; // You can use tokio's async sleep for delay
 
 const MAX_RETRIES: u32 = 3;
 const RETRY_DELAY: Duration = Duration::from_secs(2);
 
+/// The shared container and the URL of its default database. Only the URL is used after
+/// startup, so it doesn't matter that the test runtime that started the container is gone.
+static SHARED_POSTGRES: OnceCell<(ContainerAsync<GenericImage>, String)> = OnceCell::const_new();
+static SHARED_POSTGRES_DATABASE_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Where a `TestContext` gets its Postgres from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PostgresMode {
+    /// A new container for every context.
+    #[default]
+    PerTest,
+    /// One container for the whole test binary, started by the first context that asks for it.
+    /// Every context gets its own database in it, so tests running in parallel don't see each
+    /// other's data, and the schema is still dropped and recreated before every run. The
+    /// container is never stopped by the tests since it lives in a static; it's named
+    /// `indexer-integration-tests-postgres-<pid>` to make it easy to clean up.
+    Shared,
+}
+
 /// The test context struct holds the test name and the transaction batches.
 pub struct TestContext {
     pub transaction_batches: Vec<Transaction>,
-    postgres_container: ContainerAsync<GenericImage>,
+    // Only held so the container stops with the context. None in shared mode, where the
+    // container is kept in `SHARED_POSTGRES`
+    _postgres_container: Option<ContainerAsync<GenericImage>>,
+    db_url: String,
 }
 
 #[derive(Debug, Clone)]
@@ -34,9 +117,33 @@ pub struct TestProcessorConfig {
     pub config: ProcessorConfig,
 }
 
+fn postgres_image() -> ContainerRequest<GenericImage> {
+    GenericImage::new("postgres", "14")
+        .with_exposed_port(5432.tcp())
+        .with_wait_for(WaitFor::message_on_stderr(
+            "database system is ready to accept connections",
+        ))
+        .with_env_var("POSTGRES_DB", "postgres")
+        .with_env_var("POSTGRES_USER", "postgres")
+        .with_env_var("POSTGRES_PASSWORD", "postgres")
+}
+
+async fn container_db_url(container: &ContainerAsync<GenericImage>, database: &str) -> String {
+    let host = container.get_host().await.unwrap();
+    let port = container.get_host_port_ipv4(5432).await.unwrap();
+    format!("postgres://postgres:postgres@{host}:{port}/{database}")
+}
+
 impl TestContext {
     // TODO: move this to builder pattern to allow chaining.
     pub async fn new(txn_bytes: &[&[u8]]) -> anyhow::Result<Self> {
+        Self::new_with_postgres_mode(txn_bytes, PostgresMode::PerTest).await
+    }
+
+    pub async fn new_with_postgres_mode(
+        txn_bytes: &[&[u8]],
+        postgres_mode: PostgresMode,
+    ) -> anyhow::Result<Self> {
         let transaction_batches = txn_bytes
             .iter()
             .map(|txn| {
@@ -44,21 +151,65 @@ impl TestContext {
                 txn
             })
             .collect::<Vec<Transaction>>();
-        let postgres_container = GenericImage::new("postgres", "14")
-            .with_exposed_port(5432.tcp())
-            .with_wait_for(WaitFor::message_on_stderr(
-                "database system is ready to accept connections",
-            ))
-            .with_env_var("POSTGRES_DB", "postgres")
-            .with_env_var("POSTGRES_USER", "postgres")
-            .with_env_var("POSTGRES_PASSWORD", "postgres")
-            .start()
-            .await
-            .expect("Redis started");
-        Ok(TestContext {
-            transaction_batches,
-            postgres_container,
-        })
+        Self::with_transactions(transaction_batches, postgres_mode).await
+    }
+
+    pub async fn from_transactions(transactions: &[Transaction]) -> anyhow::Result<Self> {
+        Self::with_transactions(transactions.to_vec(), PostgresMode::PerTest).await
+    }
+
+    async fn with_transactions(
+        transaction_batches: Vec<Transaction>,
+        postgres_mode: PostgresMode,
+    ) -> anyhow::Result<Self> {
+        match postgres_mode {
+            PostgresMode::PerTest => {
+                let postgres_container = postgres_image().start().await.expect("Postgres started");
+                let db_url = container_db_url(&postgres_container, "postgres").await;
+                Ok(TestContext {
+                    transaction_batches,
+                    _postgres_container: Some(postgres_container),
+                    db_url,
+                })
+            },
+            PostgresMode::Shared => Ok(TestContext {
+                transaction_batches,
+                _postgres_container: None,
+                db_url: Self::create_shared_database().await?,
+            }),
+        }
+    }
+
+    /// Creates a new database in the shared container, starting the container if needed, and
+    /// returns its URL.
+    async fn create_shared_database() -> anyhow::Result<String> {
+        let (_, default_db_url) = SHARED_POSTGRES
+            .get_or_init(|| async {
+                let container = postgres_image()
+                    .with_container_name(format!(
+                        "indexer-integration-tests-postgres-{}",
+                        std::process::id()
+                    ))
+                    .start()
+                    .await
+                    .expect("Shared Postgres started");
+                let db_url = container_db_url(&container, "postgres").await;
+                (container, db_url)
+            })
+            .await;
+        let database = format!(
+            "test_{}",
+            SHARED_POSTGRES_DATABASE_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut conn = PgConnection::establish(default_db_url)
+            .with_context(|| format!("Error connecting to {}", default_db_url))?;
+        sql_query(format!("CREATE DATABASE {database};"))
+            .execute(&mut conn)
+            .with_context(|| format!("Error creating database {}", database))?;
+        let (base_url, _) = default_db_url
+            .rsplit_once('/')
+            .context("Shared Postgres URL has no database")?;
+        Ok(format!("{base_url}/{database}"))
     }
 
     async fn create_schema(&self) -> anyhow::Result<()> {
@@ -77,17 +228,12 @@ impl TestContext {
     }
 
     pub async fn get_db_url(&self) -> String {
-        let host = self.postgres_container.get_host().await.unwrap();
-        let port = self
-            .postgres_container
-            .get_host_port_ipv4(5432)
-            .await
-            .unwrap();
-        format!("postgres://postgres:postgres@{host}:{port}/postgres")
+        self.db_url.clone()
     }
 
     // The `run` function takes a closure that is executed after the test context is created.
-    // The closure is executed multiple times with different permutations of the transactions.
+    // With `TestType::Permutation`, the closure is executed multiple times with different
+    // permutations of the transactions.
     // For example:
     //   test.run(async move | context | {
     //       // Runs after every permutatation
@@ -109,84 +255,44 @@ impl TestContext {
     where
         F: Fn(&mut PgConnection, &str) -> anyhow::Result<()> + Send + Sync + 'static,
     {
+        if let TestType::Permutation(strategy) = &test_type {
+            return self
+                .run_permutations(processor_config, strategy, &test_type, &verification_f)
+                .await;
+        }
+
         let transactions = self.transaction_batches.clone();
         let db_url = self.get_db_url().await;
         let mut conn = PgConnection::establish(&db_url)
             .with_context(|| format!("Error connecting to {}", db_url))?;
-        let db_pool = new_db_pool(&db_url, None).await.unwrap();
+        let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+            .await
+            .unwrap();
 
         self.create_schema().await?;
         let processor =
-            build_processor_for_testing(processor_config.config.clone(), db_pool.clone());
+            build_processor_for_testing(processor_config.config.clone(), db_pool.clone())?;
 
         let mut last_version = None;
 
         for txn in transactions.iter() {
             let version = txn.version;
             processor
-                .process_transactions(vec![txn.clone()], version, version, None)
+                .process_transactions(Arc::new(vec![txn.clone()]), version, version, None)
                 .await?;
 
             last_version = Some(version);
 
             // For DiffTest, run verification after each transaction with retry logic
             if matches!(test_type, TestType::Diff(_)) {
-                let mut attempts = 0;
-                loop {
-                    attempts += 1;
-                    match test_type.run_verification(
-                        &mut conn,
-                        &version.to_string(),
-                        &verification_f,
-                    ) {
-                        Ok(_) => break,
-                        Err(e) if attempts < MAX_RETRIES => {
-                            eprintln!(
-                                "Verification failed on attempt {}. Retrying... Error: {:?}",
-                                attempts, e
-                            );
-                            sleep(RETRY_DELAY).await;
-                        },
-                        Err(e) => {
-                            return Err(anyhow::anyhow!(
-                                "Verification failed after {} attempts: {:?}",
-                                MAX_RETRIES,
-                                e
-                            ));
-                        },
-                    }
-                }
+                verify_with_retries(&test_type, &mut conn, version, &verification_f).await?;
             }
         }
 
         // For ScenarioTest, use the last transaction version if needed with retry logic
         if matches!(test_type, TestType::Scenario(_)) {
             if let Some(last_version) = last_version {
-                let mut attempts = 0;
-                loop {
-                    attempts += 1;
-                    match test_type.run_verification(
-                        &mut conn,
-                        &last_version.to_string(),
-                        &verification_f,
-                    ) {
-                        Ok(_) => break,
-                        Err(e) if attempts < MAX_RETRIES => {
-                            eprintln!(
-                                "Verification failed on attempt {}. Retrying... Error: {:?}",
-                                attempts, e
-                            );
-                            sleep(RETRY_DELAY).await;
-                        },
-                        Err(e) => {
-                            return Err(anyhow::anyhow!(
-                                "Verification failed after {} attempts: {:?}",
-                                MAX_RETRIES,
-                                e
-                            ));
-                        },
-                    }
-                }
+                verify_with_retries(&test_type, &mut conn, last_version, &verification_f).await?;
             } else {
                 return Err(anyhow::anyhow!(
                     "No transactions found to get the last version"
@@ -196,6 +302,116 @@ impl TestContext {
 
         Ok(())
     }
+
+    /// Processes the transactions once in their original order and then in shuffled orders,
+    /// starting from an empty database each time. After every run, the verification closure must
+    /// pass against the highest version and the compared tables must match the first run.
+    async fn run_permutations(
+        &self,
+        processor_config: TestProcessorConfig,
+        strategy: &PermutationTest,
+        test_type: &TestType,
+        verification_f: &dyn Fn(&mut PgConnection, &str) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let last_version = self
+            .transaction_batches
+            .iter()
+            .map(|txn| txn.version)
+            .max()
+            .context("No transactions found to get the last version")?;
+        let db_url = self.get_db_url().await;
+        let mut rng = StdRng::seed_from_u64(strategy.seed);
+        let mut expected_snapshot = None;
+
+        for permutation in 0..strategy.num_permutations {
+            let mut transactions = self.transaction_batches.clone();
+            // The first run keeps the original order as the baseline
+            if permutation > 0 {
+                transactions.shuffle(&mut rng);
+            }
+            let order = transactions.iter().map(|t| t.version).collect::<Vec<_>>();
+
+            self.create_schema().await?;
+            // New connections every run so nothing cached survives the schema being recreated
+            let mut conn = PgConnection::establish(&db_url)
+                .with_context(|| format!("Error connecting to {}", db_url))?;
+            let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+                .await
+                .unwrap();
+            let processor =
+                build_processor_for_testing(processor_config.config.clone(), db_pool.clone())?;
+
+            for txn in transactions {
+                let version = txn.version;
+                processor
+                    .process_transactions(Arc::new(vec![txn]), version, version, None)
+                    .await?;
+            }
+
+            verify_with_retries(test_type, &mut conn, last_version, verification_f)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Permutation {} (seed {}, order {:?}) failed verification",
+                        permutation, strategy.seed, order
+                    )
+                })?;
+
+            let snapshot = strategy.snapshot(&mut conn)?;
+            match &expected_snapshot {
+                None => expected_snapshot = Some(snapshot),
+                Some(expected) => {
+                    if let Some(table) = strategy
+                        .tables
+                        .iter()
+                        .find(|table| expected.get(**table) != snapshot.get(**table))
+                    {
+                        anyhow::bail!(
+                            "Table {} diverged in permutation {} (seed {}, order {:?}).\nExpected: {:#?}\nActual: {:#?}",
+                            table,
+                            permutation,
+                            strategy.seed,
+                            order,
+                            expected.get(*table),
+                            snapshot.get(*table)
+                        );
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs the verification, retrying a few times before giving up.
+async fn verify_with_retries(
+    test_type: &TestType,
+    conn: &mut PgConnection,
+    version: u64,
+    verification_f: &dyn Fn(&mut PgConnection, &str) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match test_type.run_verification(conn, &version.to_string(), verification_f) {
+            Ok(_) => return Ok(()),
+            Err(e) if attempts < MAX_RETRIES => {
+                eprintln!(
+                    "Verification failed on attempt {}. Retrying... Error: {:?}",
+                    attempts, e
+                );
+                sleep(RETRY_DELAY).await;
+            },
+            Err(e) => {
+                return Err(anyhow::anyhow!(
+                    "Verification failed after {} attempts: {:?}",
+                    MAX_RETRIES,
+                    e
+                ));
+            },
+        }
+    }
 }
 
 trait TestStrategy {
@@ -233,9 +449,95 @@ impl TestStrategy for ScenarioTest {
     }
 }
 
+/// Runs the transactions in several randomized orders to catch order dependent upserts, e.g. in
+/// "current" tables during out of order backfills. The seed makes the orders reproducible.
+pub struct PermutationTest {
+    /// Number of runs, including the first one in the original order
+    pub num_permutations: usize,
+    pub seed: u64,
+    /// Tables whose final contents must be identical across runs
+    pub tables: Vec<&'static str>,
+}
+
+impl PermutationTest {
+    const DEFAULT_NUM_PERMUTATIONS: usize = 4;
+
+    pub fn new(seed: u64, tables: Vec<&'static str>) -> Self {
+        Self {
+            num_permutations: Self::DEFAULT_NUM_PERMUTATIONS,
+            seed,
+            tables,
+        }
+    }
+}
+
+/// Processes `transactions` with the processor of `config` as a `PermutationTest`, each run
+/// against a fresh database, see `TestContext::run`.
+pub async fn run_permutation_test<F>(
+    transactions: &[Transaction],
+    config: ProcessorConfig,
+    strategy: PermutationTest,
+    verification_f: F,
+) -> anyhow::Result<()>
+where
+    F: Fn(&mut PgConnection, &str) -> anyhow::Result<()> + Send + Sync + 'static,
+{
+    TestContext::from_transactions(transactions)
+        .await?
+        .run(
+            TestProcessorConfig { config },
+            TestType::Permutation(strategy),
+            verification_f,
+        )
+        .await
+}
+
+#[derive(QueryableByName)]
+struct JsonRow {
+    #[diesel(sql_type = Text)]
+    row: String,
+}
+
+impl PermutationTest {
+    /// Sorted rows of every compared table. `inserted_at` is dropped since it differs per run.
+    fn snapshot(&self, conn: &mut PgConnection) -> anyhow::Result<BTreeMap<String, Vec<String>>> {
+        let mut snapshot = BTreeMap::new();
+        for table in &self.tables {
+            let query = format!("SELECT row_to_json(t)::text AS row FROM {} t", table);
+            let mut rows = sql_query(query)
+                .load::<JsonRow>(conn)
+                .with_context(|| format!("Failed to read table {}", table))?
+                .into_iter()
+                .map(|json_row| {
+                    let mut value: serde_json::Value = serde_json::from_str(&json_row.row)?;
+                    if let Some(object) = value.as_object_mut() {
+                        object.remove("inserted_at");
+                    }
+                    Ok(value.to_string())
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            rows.sort();
+            snapshot.insert(table.to_string(), rows);
+        }
+        Ok(snapshot)
+    }
+}
+
+impl TestStrategy for PermutationTest {
+    fn verify(
+        &self,
+        conn: &mut PgConnection,
+        version: &str,
+        verification_f: &dyn Fn(&mut PgConnection, &str) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        verification_f(conn, version)
+    }
+}
+
 pub enum TestType {
     Diff(DiffTest),
     Scenario(ScenarioTest),
+    Permutation(PermutationTest),
 }
 
 impl TestType {
@@ -248,6 +550,7 @@ impl TestType {
         match self {
             TestType::Diff(strategy) => strategy.verify(conn, version, verification_f),
             TestType::Scenario(strategy) => strategy.verify(conn, version, verification_f),
+            TestType::Permutation(strategy) => strategy.verify(conn, version, verification_f),
         }
     }
 }
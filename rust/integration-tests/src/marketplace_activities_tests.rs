@@ -0,0 +1,82 @@
+use crate::{
+    test_transactions::{CAPTURED_MARKETPLACE_LISTING, CAPTURED_MARKETPLACE_SALE},
+    ScenarioTest, TestContext, TestProcessorConfig, TestType,
+};
+use bigdecimal::BigDecimal;
+use diesel::{QueryDsl, RunQueryDsl};
+use processor::{
+    processors::{token_v2_processor::TokenV2ProcessorConfig, ProcessorConfig},
+    schema::marketplace_activities::dsl::*,
+};
+
+const SELLER: &str = "0xf0e1d2c3b4a5968778695a4b3c2d1e0ff0e1d2c3b4a5968778695a4b3c2d1e0f";
+const BUYER: &str = "0x1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d";
+const TOKEN: &str = "0x7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a29180f";
+
+/// A token v2 listed on and then bought through the example marketplace.
+#[tokio::test]
+async fn test_listing_and_sale() {
+    let test_context = TestContext::new(&[CAPTURED_MARKETPLACE_LISTING, CAPTURED_MARKETPLACE_SALE])
+        .await
+        .unwrap();
+    let token_v2_processor_config: TokenV2ProcessorConfig =
+        serde_json::from_value(serde_json::json!({
+            "marketplaces": [{
+                "name": "example_v2_marketplace",
+                "contract_address": "0x6de37368e31dff4580b211295198159ee6f98b42ffa93c5683bb955ca1be67e0",
+            }],
+        }))
+        .unwrap();
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::TokenV2Processor(token_v2_processor_config),
+    };
+
+    assert!(test_context
+        .run(
+            processor_config,
+            TestType::Scenario(ScenarioTest),
+            |conn, _| {
+                let rows = marketplace_activities
+                    .order(transaction_version)
+                    .select((
+                        transaction_version,
+                        activity_type,
+                        token_data_id,
+                        price,
+                        buyer,
+                        seller,
+                    ))
+                    .load::<(
+                        i64,
+                        String,
+                        Option<String>,
+                        BigDecimal,
+                        Option<String>,
+                        Option<String>,
+                    )>(conn)?;
+                let price_octas = BigDecimal::from(150_000_000);
+                let expected = [
+                    (
+                        10,
+                        "listing_placed".to_string(),
+                        Some(TOKEN.to_string()),
+                        price_octas.clone(),
+                        None,
+                        Some(SELLER.to_string()),
+                    ),
+                    (
+                        20,
+                        "listing_filled".to_string(),
+                        Some(TOKEN.to_string()),
+                        price_octas,
+                        Some(BUYER.to_string()),
+                        Some(SELLER.to_string()),
+                    ),
+                ];
+                assert_eq!(rows, expected);
+                Ok(())
+            },
+        )
+        .await
+        .is_ok());
+}
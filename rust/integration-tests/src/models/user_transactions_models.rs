@@ -51,4 +51,9 @@ pub struct UserTransaction {
     pub entry_function_contract_address: Option<String>,
     pub entry_function_module_name: Option<String>,
     pub entry_function_function_name: Option<String>,
+    pub script_payload: Option<serde_json::Value>,
+    pub success: Option<bool>,
+    pub vm_status: Option<String>,
+    pub abort_code: Option<BigDecimal>,
+    pub abort_location: Option<String>,
 }
@@ -0,0 +1,56 @@
+use crate::{
+    test_transactions::transaction_with_events, ScenarioTest, TestContext, TestProcessorConfig,
+    TestType,
+};
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use processor::{
+    processors::{events_processor::EventsProcessorConfig, ProcessorConfig},
+    schema::{events, outbox},
+};
+
+/// Every event gets an outbox row with its key and contents.
+#[tokio::test]
+async fn test_events_outbox_rows_match_event_rows() {
+    let test_context = TestContext::from_transactions(&[transaction_with_events(1, 3)])
+        .await
+        .unwrap();
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::EventsProcessor(EventsProcessorConfig {
+            outbox: true,
+            ..Default::default()
+        }),
+    };
+
+    assert!(test_context
+        .run(
+            processor_config,
+            TestType::Scenario(ScenarioTest),
+            |conn, version| {
+                let version = version.parse::<i64>()?;
+                let event_rows = events::table
+                    .filter(events::transaction_version.eq(version))
+                    .order(events::event_index)
+                    .select((events::event_index, events::data))
+                    .load::<(i64, serde_json::Value)>(conn)?;
+                let outbox_rows = outbox::table
+                    .filter(outbox::table_name.eq("events"))
+                    .order(outbox::id)
+                    .select((outbox::operation, outbox::row_key, outbox::payload))
+                    .load::<(String, serde_json::Value, serde_json::Value)>(conn)?;
+                assert_eq!(event_rows.len(), 3);
+                assert_eq!(outbox_rows.len(), event_rows.len());
+                for ((event_index, data), (operation, row_key, payload)) in
+                    event_rows.iter().zip(&outbox_rows)
+                {
+                    assert_eq!(operation, "insert");
+                    assert_eq!(row_key["transaction_version"], version);
+                    assert_eq!(&row_key["event_index"], event_index);
+                    assert_eq!(&payload["data"], data);
+                    assert_eq!(payload["event_index"], *event_index);
+                }
+                Ok(())
+            },
+        )
+        .await
+        .is_ok());
+}
@@ -0,0 +1,65 @@
+use crate::TestContext;
+use diesel::{
+    pg::PgConnection, sql_query, Connection, ExpressionMethods, OptionalExtension, QueryDsl,
+    RunQueryDsl,
+};
+use processor::{
+    processors::{ProcessorConfig, ProcessorTrait},
+    schema::processor_status,
+    utils::{
+        database::{new_db_pool, DbConnectionConfig},
+        progress_lease::{LeasedProgressStorage, PostgresLeaseStore, ProgressLeaseConfig},
+    },
+    worker::build_processor_for_testing,
+};
+
+#[tokio::test]
+async fn test_progress_not_committed_after_lease_taken_over() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let processor = build_processor_for_testing(
+        ProcessorConfig::DefaultProcessor(Default::default()),
+        db_pool.clone(),
+    )
+    .unwrap();
+    let leased_progress = LeasedProgressStorage::new(
+        Box::new(PostgresLeaseStore::new(db_pool, processor.name())),
+        &ProgressLeaseConfig {
+            holder_id: "primary".to_string(),
+            lease_duration_secs: 3600,
+            renew_interval_secs: 10,
+        },
+    );
+    leased_progress.acquire().await.unwrap();
+
+    processor
+        .update_last_processed_version(10, None, Some(&leased_progress))
+        .await
+        .unwrap();
+
+    // The standby takes the lease over while the primary still thinks it holds it, e.g. after
+    // the primary was paused for longer than the lease
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    sql_query(
+        "UPDATE processor_leases SET holder_id = 'standby', fencing_token = fencing_token + 1",
+    )
+    .execute(&mut conn)
+    .unwrap();
+    assert!(leased_progress.is_held());
+    assert!(processor
+        .update_last_processed_version(20, None, Some(&leased_progress))
+        .await
+        .is_err());
+
+    let last_success_version = processor_status::table
+        .select(processor_status::last_success_version)
+        .filter(processor_status::processor.eq(processor.name()))
+        .first::<i64>(&mut conn)
+        .optional()
+        .unwrap();
+    assert_eq!(last_success_version, Some(10));
+}
@@ -0,0 +1,125 @@
+use crate::{
+    test_transactions::transaction_with_events, ScenarioTest, TestContext, TestProcessorConfig,
+    TestType,
+};
+use aptos_protos::transaction::v1::Transaction;
+use diesel::{pg::PgConnection, Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use processor::{
+    grpc_stream::TransactionsPBResponse,
+    processors::{events_processor::EventsProcessorConfig, ProcessorConfig},
+    replay::{ReplayQueue, ReplayState, ReplayStream},
+    schema::events,
+    utils::{
+        channel_byte_limiter::ChannelByteLimiter,
+        database::{new_db_pool, DbConnectionConfig},
+    },
+    worker::build_processor_for_testing,
+};
+use std::sync::Arc;
+
+/// Every row of `events`, in order.
+fn load_events(conn: &mut PgConnection) -> anyhow::Result<Vec<(i64, i64, serde_json::Value)>> {
+    Ok(events::table
+        .order((events::transaction_version, events::event_index))
+        .select((
+            events::transaction_version,
+            events::event_index,
+            events::data,
+        ))
+        .load(conn)?)
+}
+
+/// Streams the given transactions of a range one per batch, as the GRPC fetcher would.
+fn fetch_from(transactions: Vec<Transaction>) -> impl Fn(u64, u64) -> ReplayStream {
+    move |start_version, end_version| {
+        let (sender, receiver) = kanal::unbounded_async();
+        for txn in &transactions {
+            if (start_version..=end_version).contains(&txn.version) {
+                let transactions_pb = TransactionsPBResponse {
+                    transactions: vec![txn.clone()],
+                    chain_id: 1,
+                    start_version: txn.version,
+                    end_version: txn.version,
+                    start_txn_timestamp: txn.timestamp.clone(),
+                    end_txn_timestamp: txn.timestamp.clone(),
+                    size_in_bytes: 0,
+                    extensions: Arc::default(),
+                };
+                sender.as_sync().send(Arc::new(transactions_pb)).unwrap();
+            }
+        }
+        // Closes the channel once the batches have been received
+        drop(sender);
+        ReplayStream {
+            receiver,
+            channel_byte_limiter: Arc::new(ChannelByteLimiter::new("test".to_string(), None)),
+            fetcher_task: tokio::spawn(async {}),
+        }
+    }
+}
+
+/// Reprocessing part of what was already processed leaves the rows as they were.
+#[tokio::test]
+async fn test_replay_reprocesses_range_idempotently() {
+    let transactions: Vec<Transaction> = (1..=3)
+        .map(|version| transaction_with_events(version, 2))
+        .collect();
+    let test_context = TestContext::from_transactions(&transactions).await.unwrap();
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::EventsProcessor(EventsProcessorConfig::default()),
+    };
+
+    assert!(test_context
+        .run(
+            processor_config.clone(),
+            TestType::Scenario(ScenarioTest),
+            |conn, _| {
+                assert_eq!(load_events(conn)?.len(), 6);
+                Ok(())
+            },
+        )
+        .await
+        .is_ok());
+    let db_url = test_context.get_db_url().await;
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    let events_before = load_events(&mut conn).unwrap();
+
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let processor = build_processor_for_testing(processor_config.config, db_pool).unwrap();
+    let fetch = fetch_from(transactions);
+    let replay_queue = ReplayQueue::default();
+    replay_queue.enqueue(2, 3).unwrap();
+    let replay = replay_queue.run_next(&processor, 1, &fetch).await;
+    assert_eq!(replay.state, ReplayState::Done);
+    assert_eq!(replay.num_transactions_processed, 2);
+    assert_eq!(load_events(&mut conn).unwrap(), events_before);
+
+    // A deleted row is written again
+    diesel::delete(events::table.filter(events::transaction_version.eq(3)))
+        .execute(&mut conn)
+        .unwrap();
+    replay_queue.enqueue(3, 3).unwrap();
+    let replay = replay_queue.run_next(&processor, 1, &fetch).await;
+    assert_eq!(replay.state, ReplayState::Done);
+    assert_eq!(load_events(&mut conn).unwrap(), events_before);
+
+    // The stream ending early fails the replay
+    replay_queue.enqueue(3, 5).unwrap();
+    let replay = replay_queue.run_next(&processor, 1, &fetch).await;
+    assert_eq!(replay.state, ReplayState::Failed);
+    assert_eq!(
+        replay.error.as_deref(),
+        Some("Stream ended before version 4")
+    );
+    let states: Vec<ReplayState> = replay_queue
+        .replays()
+        .iter()
+        .map(|replay| replay.state)
+        .collect();
+    assert_eq!(
+        states,
+        [ReplayState::Done, ReplayState::Done, ReplayState::Failed]
+    );
+}
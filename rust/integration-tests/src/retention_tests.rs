@@ -0,0 +1,122 @@
+use crate::TestContext;
+use ahash::AHashMap;
+use diesel::{
+    pg::PgConnection,
+    sql_query,
+    sql_types::{Int8, Text},
+    Connection, QueryableByName, RunQueryDsl,
+};
+use processor::utils::{
+    database::{new_db_pool, DbConnectionConfig},
+    retention::{prune, Retention, RetentionConfig},
+};
+
+const PROCESSOR_NAME: &str = "events_processor";
+
+#[derive(QueryableByName)]
+struct Version {
+    #[diesel(sql_type = Int8)]
+    transaction_version: i64,
+}
+
+fn insert_events(conn: &mut PgConnection, first_version: i64, last_version: i64) {
+    // Two events per version, so a batch deletes more rows than versions
+    sql_query(
+        "INSERT INTO events (sequence_number, creation_number, account_address, \
+         transaction_version, transaction_block_height, type, data, event_index, indexed_type) \
+         SELECT 0, 0, '0x1', version, version, '0x1::test::Event', '{}', event_index, \
+         '0x1::test::Event' \
+         FROM generate_series($1::bigint, $2::bigint) AS version, \
+         generate_series(0, 1) AS event_index",
+    )
+    .bind::<Int8, _>(first_version)
+    .bind::<Int8, _>(last_version)
+    .execute(conn)
+    .unwrap();
+}
+
+fn remaining_versions(conn: &mut PgConnection) -> Vec<i64> {
+    sql_query("SELECT DISTINCT transaction_version FROM events ORDER BY transaction_version")
+        .load::<Version>(conn)
+        .unwrap()
+        .into_iter()
+        .map(|row| row.transaction_version)
+        .collect()
+}
+
+fn retention_config(retention: Retention) -> RetentionConfig {
+    RetentionConfig {
+        tables: AHashMap::from_iter([("events".to_string(), retention)]),
+        interval_secs: RetentionConfig::default_interval_secs(),
+        batch_versions: 3,
+    }
+}
+
+#[tokio::test]
+async fn test_prune_by_versions() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    insert_events(&mut conn, 1, 12);
+    // Versions 11 and 12 are written but not yet committed as processed
+    sql_query("INSERT INTO processor_status (processor, last_success_version) VALUES ($1, 10)")
+        .bind::<Text, _>(PROCESSOR_NAME)
+        .execute(&mut conn)
+        .unwrap();
+
+    let config = retention_config(Retention::Versions(4));
+    prune(db_pool.clone(), PROCESSOR_NAME, &config)
+        .await
+        .unwrap();
+    assert_eq!(remaining_versions(&mut conn), (7..=12).collect::<Vec<_>>());
+
+    // Nothing more to prune until the processor makes progress
+    prune(db_pool, PROCESSOR_NAME, &config).await.unwrap();
+    assert_eq!(remaining_versions(&mut conn), (7..=12).collect::<Vec<_>>());
+}
+
+#[tokio::test]
+async fn test_prune_by_days() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    insert_events(&mut conn, 1, 10);
+    // Blocks start at versions 1 and 4, 100 days ago, and at 8, 80 days ago
+    sql_query(
+        "INSERT INTO block_metadata_transactions (version, block_height, id, round, epoch, \
+         previous_block_votes_bitvec, proposer, failed_proposer_indices, timestamp) \
+         VALUES \
+         (1, 1, '0x1', 1, 1, '[]', '0x1', '[]', NOW() - INTERVAL '100 days'), \
+         (4, 2, '0x2', 2, 1, '[]', '0x1', '[]', NOW() - INTERVAL '100 days'), \
+         (8, 3, '0x3', 3, 1, '[]', '0x1', '[]', NOW() - INTERVAL '80 days')",
+    )
+    .execute(&mut conn)
+    .unwrap();
+
+    prune(
+        db_pool.clone(),
+        PROCESSOR_NAME,
+        &retention_config(Retention::Days(90)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(remaining_versions(&mut conn), (8..=10).collect::<Vec<_>>());
+
+    // No block that recent, so there's no boundary and nothing is pruned
+    prune(
+        db_pool,
+        PROCESSOR_NAME,
+        &retention_config(Retention::Days(30)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(remaining_versions(&mut conn), (8..=10).collect::<Vec<_>>());
+}
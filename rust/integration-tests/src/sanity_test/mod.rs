@@ -13,6 +13,23 @@ use sdk_processor::processors::{
 use serde_json::Value;
 use std::collections::HashMap;
 
+/// Number of processors the sanity tests run at once, from `DIFF_TEST_CONCURRENCY`, to match the
+/// cores and memory CI has. Defaults to 1, one processor after the other.
+///
+/// Each processor gets its own database in the shared Postgres container and its own mock GRPC
+/// server, so they can't see each other's rows. What they do share is process wide: the metrics
+/// are `Lazy` statics registered once per process, so running processors together only adds to
+/// the same counters, which none of the tests read. Anything a test asserts on has to come from
+/// the processor's database instead.
+#[allow(dead_code)]
+pub fn diff_test_concurrency() -> usize {
+    std::env::var("DIFF_TEST_CONCURRENCY")
+        .ok()
+        .and_then(|concurrency| concurrency.parse().ok())
+        .filter(|&concurrency| concurrency > 0)
+        .unwrap_or(1)
+}
+
 /// Wrapper for the different processors to run the tests
 #[allow(dead_code)]
 pub enum ProcessorWrapper {
@@ -13,26 +13,28 @@ mod tests {
             token_v2_processor::load_data as load_token_v2_data,
             user_transaction_processor::load_data as load_ut_data,
         },
-        sanity_test::ProcessorWrapper,
+        sanity_test::{diff_test_concurrency, ProcessorWrapper},
         sdk_tests::{
             account_transaction_processor_tests::setup_acc_txn_processor_config,
             ans_processor_tests::setup_ans_processor_config,
             default_processor_tests::setup_default_processor_config,
             events_processor_tests::setup_events_processor_config,
             fungible_asset_processor_tests::setup_fa_processor_config,
-            objects_processor_tests::setup_objects_processor_config, setup_test_environment,
+            objects_processor_tests::setup_objects_processor_config,
             stake_processor_tests::setup_stake_processor_config,
             token_v2_processor_tests::setup_token_v2_processor_config,
             user_transaction_processor_tests::setup_user_txn_processor_config,
         },
+        TestContext,
     };
     use aptos_indexer_test_transactions::{
         ALL_IMPORTED_MAINNET_TXNS, ALL_IMPORTED_TESTNET_TXNS, ALL_SCRIPTED_TRANSACTIONS,
     };
     use aptos_indexer_testing_framework::{
-        cli_parser::get_test_config, database::TestDatabase, sdk_test_context::SdkTestContext,
+        cli_parser::get_test_config, sdk_test_context::SdkTestContext,
     };
     use diesel::pg::PgConnection;
+    use futures::{stream, StreamExt};
     use sdk_processor::processors::{
         account_transactions_processor::AccountTransactionsProcessor, ans_processor::AnsProcessor,
         default_processor::DefaultProcessor, events_processor::EventsProcessor,
@@ -45,157 +47,162 @@ mod tests {
 
     const DEFAULT_OUTPUT_FOLDER: &str = "expected_db_output_files";
 
-    #[tokio::test]
+    const PROCESSOR_NAMES: [&str; 9] = [
+        "events_processor",
+        "fungible_asset_processor",
+        "ans_processor",
+        "default_processor",
+        "objects_processor",
+        "stake_processor",
+        "user_transactions_processor",
+        "token_v2_processor",
+        "account_transactions_processor",
+    ];
+
+    #[tokio::test(flavor = "multi_thread")]
     async fn test_all_testnet_txns_for_all_processors() {
         let (diff_flag, custom_output_path) = get_test_config();
         let output_path = custom_output_path
             .unwrap_or_else(|| DEFAULT_OUTPUT_FOLDER.to_string() + "/imported_testnet_txns");
 
-        let (db, test_context) = setup_test_environment(ALL_IMPORTED_TESTNET_TXNS).await;
-        let db_url = db.get_db_url();
-
-        run_processors_with_test_context(test_context, db_url, diff_flag, output_path).await;
+        run_all_processors(ALL_IMPORTED_TESTNET_TXNS, diff_flag, output_path).await;
     }
 
-    #[tokio::test]
+    #[tokio::test(flavor = "multi_thread")]
     async fn test_all_mainnet_txns_for_all_processors() {
         let (diff_flag, custom_output_path) = get_test_config();
         let output_path = custom_output_path
             .unwrap_or_else(|| DEFAULT_OUTPUT_FOLDER.to_string() + "/imported_testnet_txns");
 
-        let (db, test_context) = setup_test_environment(ALL_IMPORTED_MAINNET_TXNS).await;
-        let db_url = db.get_db_url();
-
-        run_processors_with_test_context(test_context, db_url, diff_flag, output_path).await;
+        run_all_processors(ALL_IMPORTED_MAINNET_TXNS, diff_flag, output_path).await;
     }
 
-    #[tokio::test]
+    #[tokio::test(flavor = "multi_thread")]
     async fn test_all_scripted_txns_for_all_processors() {
         let (diff_flag, custom_output_path) = get_test_config();
         let output_path = custom_output_path
             .unwrap_or_else(|| DEFAULT_OUTPUT_FOLDER.to_string() + "/imported_testnet_txns");
 
-        let (db, test_context) = setup_test_environment(ALL_SCRIPTED_TRANSACTIONS).await;
-        let db_url = db.get_db_url();
+        run_all_processors(ALL_SCRIPTED_TRANSACTIONS, diff_flag, output_path).await;
+    }
+
+    /// Runs every processor over the transactions, `diff_test_concurrency` of them at a time, and
+    /// fails with all the processors that failed.
+    async fn run_all_processors(transactions: &[&[u8]], diff_flag: bool, output_path: String) {
+        let results: Vec<(&str, anyhow::Result<()>)> = stream::iter(PROCESSOR_NAMES)
+            .map(|processor_name| {
+                let output_path = output_path.clone();
+                async move {
+                    let res =
+                        run_processor(processor_name, transactions, diff_flag, output_path).await;
+                    (processor_name, res)
+                }
+            })
+            .buffer_unordered(diff_test_concurrency())
+            .collect()
+            .await;
 
-        run_processors_with_test_context(test_context, db_url, diff_flag, output_path).await;
+        let mut failed = vec![];
+        for (processor_name, res) in results {
+            match res {
+                Ok(_) => {
+                    println!("Processor ran successfully for {}", processor_name);
+                },
+                Err(e) => {
+                    eprintln!(
+                        "Error running processor test for {} with error: {:?}",
+                        processor_name, e
+                    );
+                    failed.push(processor_name);
+                },
+            }
+        }
+        assert!(failed.is_empty(), "Processor tests failed for {:?}", failed);
     }
 
-    async fn run_processors_with_test_context(
-        mut test_context: SdkTestContext,
-        db_url: String,
+    /// Runs one processor against a database of its own in the shared container, streaming the
+    /// transactions from a mock GRPC server of its own.
+    async fn run_processor(
+        processor_name: &str,
+        transactions: &[&[u8]],
         diff_flag: bool,
         output_path: String,
-    ) {
-        let processors_map: HashMap<String, ProcessorWrapper> = [
-            (
-                "events_processor".to_string(),
-                ProcessorWrapper::EventsProcessor(
-                    EventsProcessor::new(setup_events_processor_config(&test_context, &db_url).0)
-                        .await
-                        .expect("Failed to create EventsProcessor"),
-                ),
+    ) -> anyhow::Result<()> {
+        let db_url = TestContext::create_shared_database().await?;
+        let mut test_context = SdkTestContext::new(transactions);
+        if test_context.init_mock_grpc().await.is_err() {
+            anyhow::bail!("Failed to initialize mock grpc");
+        }
+        let processor = build_processor(processor_name, &test_context, &db_url).await;
+        processor
+            .run(
+                &mut test_context,
+                get_db_values_fn_for_processor(processor_name),
+                db_url,
+                diff_flag,
+                output_path,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn build_processor(
+        processor_name: &str,
+        test_context: &SdkTestContext,
+        db_url: &str,
+    ) -> ProcessorWrapper {
+        let db_url = db_url.to_string();
+        match processor_name {
+            "events_processor" => ProcessorWrapper::EventsProcessor(
+                EventsProcessor::new(setup_events_processor_config(test_context, &db_url).0)
+                    .await
+                    .expect("Failed to create EventsProcessor"),
             ),
-            (
-                "fungible_asset_processor".to_string(),
-                ProcessorWrapper::FungibleAssetProcessor(
-                    FungibleAssetProcessor::new(
-                        setup_fa_processor_config(&test_context, &db_url).0,
-                    )
+            "fungible_asset_processor" => ProcessorWrapper::FungibleAssetProcessor(
+                FungibleAssetProcessor::new(setup_fa_processor_config(test_context, &db_url).0)
                     .await
                     .expect("Failed to create FungibleAssetProcessor"),
-                ),
             ),
-            (
-                "ans_processor".to_string(),
-                ProcessorWrapper::AnsProcessor(
-                    AnsProcessor::new(setup_ans_processor_config(&test_context, &db_url).0)
-                        .await
-                        .expect("Failed to create AnsProcessor"),
-                ),
-            ),
-            (
-                "default_processor".to_string(),
-                ProcessorWrapper::DefaultProcessor(
-                    DefaultProcessor::new(setup_default_processor_config(&test_context, &db_url).0)
-                        .await
-                        .expect("Failed to create DefaultProcessor"),
-                ),
-            ),
-            (
-                "objects_processor".to_string(),
-                ProcessorWrapper::ObjectsProcessor(
-                    ObjectsProcessor::new(setup_objects_processor_config(&test_context, &db_url).0)
-                        .await
-                        .expect("Failed to create ObjectsProcessor"),
-                ),
+            "ans_processor" => ProcessorWrapper::AnsProcessor(
+                AnsProcessor::new(setup_ans_processor_config(test_context, &db_url).0)
+                    .await
+                    .expect("Failed to create AnsProcessor"),
             ),
-            (
-                "stake_processor".to_string(),
-                ProcessorWrapper::StakeProcessor(
-                    StakeProcessor::new(setup_stake_processor_config(&test_context, &db_url).0)
-                        .await
-                        .expect("Failed to create StakeProcessor"),
-                ),
+            "default_processor" => ProcessorWrapper::DefaultProcessor(
+                DefaultProcessor::new(setup_default_processor_config(test_context, &db_url).0)
+                    .await
+                    .expect("Failed to create DefaultProcessor"),
             ),
-            (
-                "user_transactions_processor".to_string(),
-                ProcessorWrapper::UserTransactionProcessor(
-                    UserTransactionProcessor::new(
-                        setup_user_txn_processor_config(&test_context, &db_url).0,
-                    )
+            "objects_processor" => ProcessorWrapper::ObjectsProcessor(
+                ObjectsProcessor::new(setup_objects_processor_config(test_context, &db_url).0)
                     .await
-                    .expect("Failed to create UserTransactionProcessor"),
-                ),
+                    .expect("Failed to create ObjectsProcessor"),
             ),
-            (
-                "token_v2_processor".to_string(),
-                ProcessorWrapper::TokenV2Processor(
-                    TokenV2Processor::new(
-                        setup_token_v2_processor_config(&test_context, &db_url).0,
-                    )
+            "stake_processor" => ProcessorWrapper::StakeProcessor(
+                StakeProcessor::new(setup_stake_processor_config(test_context, &db_url).0)
                     .await
-                    .expect("Failed to create TokenV2Processor"),
-                ),
+                    .expect("Failed to create StakeProcessor"),
             ),
-            (
-                "account_transactions_processor".to_string(),
-                ProcessorWrapper::AccountTransactionsProcessor(
-                    AccountTransactionsProcessor::new(
-                        setup_acc_txn_processor_config(&test_context, &db_url).0,
-                    )
+            "user_transactions_processor" => ProcessorWrapper::UserTransactionProcessor(
+                UserTransactionProcessor::new(
+                    setup_user_txn_processor_config(test_context, &db_url).0,
+                )
+                .await
+                .expect("Failed to create UserTransactionProcessor"),
+            ),
+            "token_v2_processor" => ProcessorWrapper::TokenV2Processor(
+                TokenV2Processor::new(setup_token_v2_processor_config(test_context, &db_url).0)
                     .await
-                    .expect("Failed to create AccountTransactionProcessor"),
-                ),
+                    .expect("Failed to create TokenV2Processor"),
             ),
-        ]
-        .into_iter()
-        .collect();
-
-        // Loop through all processors and run tests
-        for (processor_name, processor) in processors_map {
-            let db_values_fn = get_db_values_fn_for_processor(&processor_name);
-
-            match processor
-                .run(
-                    &mut test_context,
-                    db_values_fn,
-                    db_url.clone(),
-                    diff_flag,
-                    output_path.clone(),
+            "account_transactions_processor" => ProcessorWrapper::AccountTransactionsProcessor(
+                AccountTransactionsProcessor::new(
+                    setup_acc_txn_processor_config(test_context, &db_url).0,
                 )
                 .await
-            {
-                Ok(_) => {
-                    println!("Processor ran successfully for {}", processor_name);
-                },
-                Err(e) => {
-                    panic!(
-                        "Error running processor test for {} with error: {}",
-                        processor_name, e
-                    );
-                },
-            }
+                .expect("Failed to create AccountTransactionProcessor"),
+            ),
+            _ => panic!("Unknown processor: {}", processor_name),
         }
     }
 
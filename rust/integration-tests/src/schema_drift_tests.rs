@@ -0,0 +1,34 @@
+use crate::TestContext;
+use diesel::{pg::PgConnection, sql_query, Connection, RunQueryDsl};
+use processor::{
+    schema::events,
+    utils::{
+        database::{new_db_pool, DbConnectionConfig},
+        schema_drift::{check_schema_drift, SchemaDrift},
+    },
+};
+
+#[tokio::test]
+async fn test_missing_events_column_is_detected() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    check_schema_drift::<events::table>(db_pool.clone())
+        .await
+        .unwrap();
+
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    sql_query("ALTER TABLE events DROP COLUMN indexed_type")
+        .execute(&mut conn)
+        .unwrap();
+    let err = check_schema_drift::<events::table>(db_pool)
+        .await
+        .unwrap_err();
+    let drift = err.downcast_ref::<SchemaDrift>().unwrap();
+    assert_eq!(drift.table_name, "events");
+    assert_eq!(drift.missing_in_db, ["indexed_type"]);
+    assert!(drift.missing_in_model.is_empty());
+}
@@ -0,0 +1,66 @@
+use crate::TestContext;
+use processor::utils::{
+    database::{new_db_pool, DbConnectionConfig},
+    schema_version::{check_schema_version, SchemaVersion, SchemaVersionCheck},
+};
+
+const PROCESSOR_NAME: &str = "token_v2_processor";
+const V1: SchemaVersion = SchemaVersion {
+    version: 1,
+    migration: "v1",
+};
+const V2: SchemaVersion = SchemaVersion {
+    version: 2,
+    migration: "v2",
+};
+
+#[tokio::test]
+async fn test_schema_version_guard() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+
+    let applied = vec!["v1".to_string()];
+    assert_eq!(
+        check_schema_version(db_pool.clone(), PROCESSOR_NAME, V1, &applied)
+            .await
+            .unwrap(),
+        SchemaVersionCheck::FirstStart
+    );
+    assert_eq!(
+        check_schema_version(db_pool.clone(), PROCESSOR_NAME, V1, &applied)
+            .await
+            .unwrap(),
+        SchemaVersionCheck::Matching
+    );
+
+    // A newer binary only starts once its migration has run, and then records its version
+    assert!(
+        check_schema_version(db_pool.clone(), PROCESSOR_NAME, V2, &applied)
+            .await
+            .is_err()
+    );
+    let applied = vec!["v1".to_string(), "v2".to_string()];
+    assert_eq!(
+        check_schema_version(db_pool.clone(), PROCESSOR_NAME, V2, &applied)
+            .await
+            .unwrap(),
+        SchemaVersionCheck::Upgrade { from: 1 }
+    );
+
+    // Rolling back to the older binary is refused, and leaves the recorded version alone
+    assert!(
+        check_schema_version(db_pool.clone(), PROCESSOR_NAME, V1, &applied)
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        check_schema_version(db_pool, PROCESSOR_NAME, V2, &applied)
+            .await
+            .unwrap(),
+        SchemaVersionCheck::Matching
+    );
+}
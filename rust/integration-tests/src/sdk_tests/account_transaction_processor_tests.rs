@@ -32,6 +32,7 @@ pub fn setup_acc_txn_processor_config(
             transaction_stream_config,
             db_config,
             backfill_config: None,
+            rayon_num_threads: None,
         },
         processor_name,
     )
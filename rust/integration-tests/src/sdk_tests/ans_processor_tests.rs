@@ -44,6 +44,7 @@ pub fn setup_ans_processor_config(
             transaction_stream_config,
             db_config,
             backfill_config: None,
+            rayon_num_threads: None,
         },
         processor_name,
     )
@@ -32,6 +32,7 @@ pub fn setup_events_processor_config(
             transaction_stream_config,
             db_config,
             backfill_config: None,
+            rayon_num_threads: None,
         },
         processor_name,
     )
@@ -60,7 +61,8 @@ mod tests {
         IMPORTED_TESTNET_TXNS_5992795934_FA_ACTIVITIES,
     };
     use aptos_indexer_testing_framework::{cli_parser::get_test_config, database::TestDatabase};
-    use aptos_protos::transaction::v1::Transaction;
+    use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+    use processor::utils::util::standardize_address;
     use sdk_processor::processors::events_processor::EventsProcessor;
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
@@ -210,6 +212,59 @@ mod tests {
         }
     }
 
+    /// Account-scoped (v1) events carry the account address in their key, which is what the
+    /// account address index on events is built on.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn devnet_events_processor_populates_account_address() {
+        let txn_bytes = IMPORTED_DEVNET_TXNS_78753811_COIN_TRANSFER_WITH_V2_EVENTS;
+        let txn: Transaction =
+            serde_json::from_slice(txn_bytes).expect("Failed to deserialize transaction");
+        let expected_addresses: Vec<String> = match txn.txn_data.as_ref().unwrap() {
+            TxnData::User(inner) => inner
+                .events
+                .iter()
+                .map(|event| {
+                    standardize_address(event.key.as_ref().unwrap().account_address.as_str())
+                })
+                .collect(),
+            _ => panic!("Expected a user transaction"),
+        };
+        assert!(expected_addresses
+            .iter()
+            .any(|address| address != &standardize_address("0x0")));
+
+        let (diff_flag, custom_output_path) = get_test_config();
+        let output_path = custom_output_path
+            .unwrap_or_else(|| format!("{}/imported_devnet_txns", DEFAULT_OUTPUT_FOLDER));
+        let (db, mut test_context) = setup_test_environment(&[txn_bytes]).await;
+        let db_url = db.get_db_url();
+        let (indexer_processor_config, _processor_name) =
+            setup_events_processor_config(&test_context, &db_url);
+        let events_processor = EventsProcessor::new(indexer_processor_config)
+            .await
+            .expect("Failed to create EventsProcessor");
+
+        let db_value = run_processor_test(
+            &mut test_context,
+            events_processor,
+            load_data,
+            db_url,
+            diff_flag,
+            output_path,
+            Some("coin_event_v2".to_string()),
+        )
+        .await
+        .expect("Failed to run processor");
+
+        let actual_addresses: Vec<String> = db_value["events"]
+            .as_array()
+            .expect("Expected events to be an array")
+            .iter()
+            .map(|event| event["account_address"].as_str().unwrap().to_string())
+            .collect();
+        assert_eq!(actual_addresses, expected_addresses);
+    }
+
     async fn process_single_devnet_event_txn(txn: &[u8], test_case_name: Option<String>) {
         process_single_event_txn(txn, test_case_name, "imported_devnet_txns").await
     }
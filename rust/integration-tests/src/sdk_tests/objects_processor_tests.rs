@@ -43,6 +43,7 @@ pub fn setup_objects_processor_config(
             transaction_stream_config,
             db_config,
             backfill_config: None,
+            rayon_num_threads: None,
         },
         processor_name,
     )
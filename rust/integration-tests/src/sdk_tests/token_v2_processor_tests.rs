@@ -42,6 +42,7 @@ pub fn setup_token_v2_processor_config(
             transaction_stream_config,
             db_config,
             backfill_config: None,
+            rayon_num_threads: None,
         },
         processor_name,
     )
@@ -33,6 +33,7 @@ pub fn setup_user_txn_processor_config(
             transaction_stream_config,
             db_config,
             backfill_config: None,
+            rayon_num_threads: None,
         },
         processor_name,
     )
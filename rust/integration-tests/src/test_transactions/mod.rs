@@ -0,0 +1,200 @@
+//! Transactions for the integration tests. `TransactionBuilder` makes the small transactions a
+//! test needs to exercise one code path, and the `CAPTURED_` constants are whole transactions as
+//! the transaction stream serves them, in the same JSON format as `aptos-indexer-test-transactions`.
+
+use aptos_protos::{
+    transaction::v1::{
+        signature::Signature as SignatureEnum,
+        transaction::{TransactionType, TxnData},
+        write_set_change::{Change, Type as WriteSetChangeType},
+        BlockMetadataTransaction, Ed25519Signature, Event, EventKey, MoveStructTag, Signature,
+        Transaction, TransactionInfo, UserTransaction, UserTransactionRequest, WriteResource,
+        WriteSetChange,
+    },
+    util::timestamp::Timestamp,
+};
+
+/// A block epilogue, which closes blocks since it replaced the state checkpoint.
+pub const CAPTURED_BLOCK_EPILOGUE: &[u8] = include_bytes!("block_epilogue.json");
+/// A coin transfer that aborted with `EINSUFFICIENT_BALANCE`, so only the gas was charged.
+pub const CAPTURED_FAILED_COIN_TRANSFER: &[u8] = include_bytes!("failed_coin_transfer.json");
+
+/// A token v2 listed for 1.5 APT on the example marketplace at
+/// 0x6de37368e31dff4580b211295198159ee6f98b42ffa93c5683bb955ca1be67e0.
+pub const CAPTURED_MARKETPLACE_LISTING: &[u8] = include_bytes!("marketplace_listing.json");
+/// The purchase of `CAPTURED_MARKETPLACE_LISTING`.
+pub const CAPTURED_MARKETPLACE_SALE: &[u8] = include_bytes!("marketplace_sale.json");
+
+/// Timestamp of version 0, every version is a second later.
+pub const BASE_TIMESTAMP_SECS: i64 = 1_700_000_000;
+
+/// Builds a transaction with everything the processors expect of one coming from the stream:
+/// info with a hash unique to the version, and for user transactions a signed request. Block
+/// height and timestamp follow the version unless set.
+pub struct TransactionBuilder {
+    transaction: Transaction,
+}
+
+impl TransactionBuilder {
+    /// A successful user transaction sent by 0x1, without events or changes.
+    pub fn user(version: u64) -> Self {
+        let request = UserTransactionRequest {
+            sender: "0x1".to_string(),
+            expiration_timestamp_secs: Some(Timestamp {
+                seconds: BASE_TIMESTAMP_SECS + version as i64 + 60,
+                nanos: 0,
+            }),
+            signature: Some(Signature {
+                signature: Some(SignatureEnum::Ed25519(Ed25519Signature::default())),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        Self::new(
+            version,
+            TransactionType::User,
+            TxnData::User(UserTransaction {
+                request: Some(request),
+                events: vec![],
+            }),
+        )
+    }
+
+    pub fn block_metadata(version: u64) -> Self {
+        Self::new(
+            version,
+            TransactionType::BlockMetadata,
+            TxnData::BlockMetadata(BlockMetadataTransaction::default()),
+        )
+    }
+
+    fn new(version: u64, r#type: TransactionType, txn_data: TxnData) -> Self {
+        Self {
+            transaction: Transaction {
+                version,
+                block_height: version,
+                timestamp: Some(Timestamp {
+                    seconds: BASE_TIMESTAMP_SECS + version as i64,
+                    nanos: 0,
+                }),
+                r#type: r#type as i32,
+                info: Some(TransactionInfo {
+                    hash: version.to_be_bytes().repeat(4),
+                    success: true,
+                    vm_status: "Executed successfully".to_string(),
+                    ..Default::default()
+                }),
+                txn_data: Some(txn_data),
+                ..Default::default()
+            },
+        }
+    }
+
+    pub fn block_height(mut self, block_height: u64) -> Self {
+        self.transaction.block_height = block_height;
+        self
+    }
+
+    pub fn timestamp_secs(mut self, seconds: i64) -> Self {
+        self.transaction.timestamp = Some(Timestamp { seconds, nanos: 0 });
+        self
+    }
+
+    pub fn sender(mut self, sender: &str) -> Self {
+        self.request().sender = sender.to_string();
+        self
+    }
+
+    pub fn gas(mut self, gas_used: u64, gas_unit_price: u64) -> Self {
+        self.info().gas_used = gas_used;
+        self.request().gas_unit_price = gas_unit_price;
+        self
+    }
+
+    /// Adds a module event, which is emitted by 0x0.
+    pub fn event(self, type_str: &str, data: serde_json::Value) -> Self {
+        self.handle_event("0x0", type_str, data)
+    }
+
+    /// Adds an event emitted through an event handle of `account_address`, as v1 events are.
+    pub fn handle_event(
+        mut self,
+        account_address: &str,
+        type_str: &str,
+        data: serde_json::Value,
+    ) -> Self {
+        let events = &mut self.user_transaction().events;
+        let sequence_number = events.len() as u64;
+        events.push(Event {
+            key: Some(EventKey {
+                creation_number: 0,
+                account_address: account_address.to_string(),
+            }),
+            sequence_number,
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Adds a write of the resource `type_str`, e.g. `0x1::coin::CoinStore`, at `address`.
+    pub fn write_resource(
+        mut self,
+        address: &str,
+        type_str: &str,
+        data: serde_json::Value,
+    ) -> Self {
+        let mut parts = type_str.splitn(3, "::");
+        let r#type = MoveStructTag {
+            address: parts.next().unwrap_or_default().to_string(),
+            module: parts.next().unwrap_or_default().to_string(),
+            name: parts.next().unwrap_or_default().to_string(),
+            generic_type_params: vec![],
+        };
+        let write_resource = WriteResource {
+            address: address.to_string(),
+            state_key_hash: format!("{}{}", address, type_str).into_bytes(),
+            r#type: Some(r#type),
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+        };
+        self.info().changes.push(WriteSetChange {
+            r#type: WriteSetChangeType::WriteResource as i32,
+            change: Some(Change::WriteResource(write_resource)),
+        });
+        self
+    }
+
+    pub fn build(self) -> Transaction {
+        self.transaction
+    }
+
+    fn info(&mut self) -> &mut TransactionInfo {
+        self.transaction.info.as_mut().unwrap()
+    }
+
+    fn user_transaction(&mut self) -> &mut UserTransaction {
+        match self.transaction.txn_data.as_mut() {
+            Some(TxnData::User(user_transaction)) => user_transaction,
+            _ => panic!("Only user transactions have a request and events"),
+        }
+    }
+
+    fn request(&mut self) -> &mut UserTransactionRequest {
+        self.user_transaction().request.as_mut().unwrap()
+    }
+}
+
+/// A user transaction emitting `num_events` deposit events into 0x1.
+pub fn transaction_with_events(version: u64, num_events: usize) -> Transaction {
+    (0..num_events)
+        .fold(TransactionBuilder::user(version), |builder, i| {
+            builder.handle_event(
+                "0x1",
+                "0x1::coin::DepositEvent",
+                serde_json::json!({"amount": i.to_string()}),
+            )
+        })
+        .build()
+}
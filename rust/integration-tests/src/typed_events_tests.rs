@@ -0,0 +1,58 @@
+use crate::{
+    test_transactions::transaction_with_events, ScenarioTest, TestContext, TestProcessorConfig,
+    TestType,
+};
+use bigdecimal::BigDecimal;
+use diesel::{ExpressionMethods, QueryDsl, RunQueryDsl};
+use processor::{
+    db::postgres::models::events_models::typed_events::{
+        TypedEventFields, EVENT_ACCOUNT_ADDRESS_FIELD,
+    },
+    processors::{events_processor::EventsProcessorConfig, ProcessorConfig},
+    schema::typed_events::dsl::*,
+    utils::util::standardize_address,
+};
+
+/// Coin deposits get their amount and receiver in typed columns.
+#[tokio::test]
+async fn test_typed_columns_of_coin_deposits() {
+    let test_context = TestContext::from_transactions(&[transaction_with_events(1, 3)])
+        .await
+        .unwrap();
+    let deposit_fields = TypedEventFields {
+        amount: Some("amount".to_string()),
+        to_address: Some(EVENT_ACCOUNT_ADDRESS_FIELD.to_string()),
+        ..Default::default()
+    };
+    let processor_config = TestProcessorConfig {
+        config: ProcessorConfig::EventsProcessor(EventsProcessorConfig {
+            typed_events: [("0x1::coin::DepositEvent".to_string(), deposit_fields)]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        }),
+    };
+
+    assert!(test_context
+        .run(
+            processor_config,
+            TestType::Scenario(ScenarioTest),
+            |conn, version| {
+                let rows = typed_events
+                    .filter(transaction_version.eq(version.parse::<i64>()?))
+                    .order(event_index)
+                    .select((type_, amount, from_address, to_address))
+                    .load::<(String, Option<BigDecimal>, Option<String>, Option<String>)>(conn)?;
+                assert_eq!(rows.len(), 3);
+                for (i, (event_type, event_amount, from, to)) in rows.into_iter().enumerate() {
+                    assert_eq!(event_type, "0x1::coin::DepositEvent");
+                    assert_eq!(event_amount, Some(BigDecimal::from(i as u64)));
+                    assert_eq!(from, None);
+                    assert_eq!(to, Some(standardize_address("0x1")));
+                }
+                Ok(())
+            },
+        )
+        .await
+        .is_ok());
+}
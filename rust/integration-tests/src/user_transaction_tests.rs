@@ -0,0 +1,77 @@
+use crate::{test_transactions::TransactionBuilder, TestContext};
+use aptos_protos::transaction::v1::Transaction;
+use diesel::{pg::PgConnection, Connection, QueryDsl, RunQueryDsl};
+use processor::{
+    gap_detectors::ProcessingResult,
+    processors::{
+        user_transaction_processor::UserTransactionProcessorConfig, ProcessorConfig, ProcessorTrait,
+    },
+    schema::{signatures, transactions, user_transactions},
+    utils::database::{new_db_pool, DbConnectionConfig},
+    worker::build_processor_for_testing,
+};
+use std::sync::Arc;
+
+const NUM_TRANSACTIONS: u64 = 500;
+
+fn db_insertion_duration_in_secs(result: ProcessingResult) -> f64 {
+    match result {
+        ProcessingResult::DefaultProcessingResult(result) => result.db_insertion_duration_in_secs,
+        ProcessingResult::ParquetProcessingResult(_) => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn test_reprocessing_indexed_range_skips_inserts() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_url = test_context.get_db_url().await;
+    let db_pool = new_db_pool(&db_url, None, &DbConnectionConfig::default())
+        .await
+        .unwrap();
+    let processor = build_processor_for_testing(
+        ProcessorConfig::UserTransactionProcessor(UserTransactionProcessorConfig {
+            skip_existing_versions: true,
+        }),
+        db_pool,
+    )
+    .unwrap();
+    let transactions: Arc<Vec<Transaction>> = Arc::new(
+        (1..=NUM_TRANSACTIONS)
+            .map(|version| TransactionBuilder::user(version).build())
+            .collect(),
+    );
+
+    let first_run = processor
+        .process_transactions(transactions.clone(), 1, NUM_TRANSACTIONS, None)
+        .await
+        .unwrap();
+    let reprocessing_run = processor
+        .process_transactions(transactions, 1, NUM_TRANSACTIONS, None)
+        .await
+        .unwrap();
+    let (first_run_secs, reprocessing_secs) = (
+        db_insertion_duration_in_secs(first_run),
+        db_insertion_duration_in_secs(reprocessing_run),
+    );
+    println!(
+        "Inserting {} versions took {:.3}s, reprocessing them took {:.3}s ({:.1}x faster)",
+        NUM_TRANSACTIONS,
+        first_run_secs,
+        reprocessing_secs,
+        first_run_secs / reprocessing_secs
+    );
+
+    let mut conn = PgConnection::establish(&db_url).unwrap();
+    for count in [
+        transactions::table.count().get_result::<i64>(&mut conn),
+        user_transactions::table
+            .count()
+            .get_result::<i64>(&mut conn),
+        signatures::table.count().get_result::<i64>(&mut conn),
+    ] {
+        assert_eq!(count.unwrap(), NUM_TRANSACTIONS as i64);
+    }
+    // Reprocessing only reads which versions are written
+    assert!(reprocessing_secs < first_run_secs);
+}
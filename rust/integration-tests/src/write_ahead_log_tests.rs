@@ -0,0 +1,58 @@
+use crate::TestContext;
+use processor::utils::{
+    database::{new_db_pool, DbConnectionConfig},
+    write_ahead_log::{PostgresWalStore, WriteAheadLog},
+};
+
+const PROCESSOR_NAME: &str = "events_processor";
+
+/// A restart after a crash resumes at the start of the batch being written, and after the last
+/// batch written once it's done, whatever progress was committed.
+#[tokio::test]
+async fn test_recover_from_postgres_after_crash() {
+    let test_context = TestContext::new(&[]).await.unwrap();
+    test_context.create_schema().await.unwrap();
+    let db_pool = new_db_pool(
+        &test_context.get_db_url().await,
+        None,
+        &DbConnectionConfig::default(),
+    )
+    .await
+    .unwrap();
+    let store = PostgresWalStore::new(db_pool.clone(), PROCESSOR_NAME);
+    assert_eq!(
+        WriteAheadLog::recover(&store, PROCESSOR_NAME)
+            .await
+            .unwrap(),
+        None
+    );
+
+    let wal = WriteAheadLog::new(
+        Box::new(PostgresWalStore::new(db_pool.clone(), PROCESSOR_NAME)),
+        100,
+    );
+    wal.begin(100, 199).await.unwrap();
+    wal.complete(100, 199).await.unwrap();
+    wal.begin(200, 299).await.unwrap();
+    drop(wal);
+    assert_eq!(
+        WriteAheadLog::recover(&store, PROCESSOR_NAME)
+            .await
+            .unwrap(),
+        Some(200)
+    );
+
+    let wal = WriteAheadLog::new(
+        Box::new(PostgresWalStore::new(db_pool.clone(), PROCESSOR_NAME)),
+        200,
+    );
+    wal.begin(200, 299).await.unwrap();
+    wal.complete(200, 299).await.unwrap();
+    drop(wal);
+    assert_eq!(
+        WriteAheadLog::recover(&store, PROCESSOR_NAME)
+            .await
+            .unwrap(),
+        Some(300)
+    );
+}
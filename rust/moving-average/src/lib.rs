@@ -5,12 +5,18 @@
 
 use std::collections::VecDeque;
 
+/// Smallest elapsed time the average is computed over, so values ticked within the same
+/// millisecond don't divide by zero.
+pub const DEFAULT_MIN_ELAPSED_MILLIS: u64 = 1;
+
 // TPS data
 pub struct MovingAverage {
     window_millis: u64,
     // (timestamp_millis, value)
     values: VecDeque<(u64, u64)>,
     sum: u64,
+    min_elapsed_millis: u64,
+    max_avg: Option<f64>,
 }
 
 impl MovingAverage {
@@ -22,9 +28,19 @@ impl MovingAverage {
             window_millis,
             values: queue,
             sum: 0,
+            min_elapsed_millis: DEFAULT_MIN_ELAPSED_MILLIS,
+            max_avg: None,
         }
     }
 
+    /// Computes the average over at least `min_elapsed_millis`, since a burst of values in a
+    /// few milliseconds gives an absurd rate, and caps it at `max_avg`.
+    pub fn with_limits(mut self, min_elapsed_millis: u64, max_avg: Option<f64>) -> Self {
+        self.min_elapsed_millis = min_elapsed_millis.max(1);
+        self.max_avg = max_avg;
+        self
+    }
+
     pub fn tick_now(&mut self, value: u64) {
         let now = chrono::Utc::now().naive_utc().and_utc().timestamp_millis() as u64;
         self.tick(now, value);
@@ -51,11 +67,19 @@ impl MovingAverage {
 
     // Only be called after tick_now/tick is called.
     pub fn avg(&self) -> f64 {
+        self.clamped_avg().0
+    }
+
+    /// The average, and whether it was capped at `max_avg`.
+    pub fn clamped_avg(&self) -> (f64, bool) {
         if self.values.len() < 2 {
-            0.0
-        } else {
-            let elapsed = self.values.back().unwrap().0 - self.values.front().unwrap().0;
-            (self.sum * 1000) as f64 / elapsed as f64
+            return (0.0, false);
+        }
+        let elapsed = self.values.back().unwrap().0 - self.values.front().unwrap().0;
+        let avg = (self.sum * 1000) as f64 / elapsed.max(self.min_elapsed_millis) as f64;
+        match self.max_avg {
+            Some(max_avg) if avg > max_avg => (max_avg, true),
+            _ => (avg, false),
         }
     }
 
@@ -81,4 +105,18 @@ mod test {
         let avg = ma.avg();
         assert!(avg >= 99.0, "Average is too low: {}", avg);
     }
+
+    #[test]
+    fn test_zero_elapsed_time() {
+        let mut ma = MovingAverage::new(10_000);
+        let start = ma.values.front().unwrap().0;
+        // Ticked in the same millisecond as the start of the window
+        assert_eq!(ma.tick(start, 1_000), 1_000_000.0);
+
+        let mut ma = MovingAverage::new(10_000).with_limits(100, Some(5_000.0));
+        let start = ma.values.front().unwrap().0;
+        assert_eq!(ma.tick(start, 100), 1_000.0);
+        ma.tick(start, 900);
+        assert_eq!(ma.clamped_avg(), (5_000.0, true));
+    }
 }
@@ -12,6 +12,7 @@ CREATE TABLE `{}`
   generic_type_params STRING,
   data STRING,
   state_key_hash STRING,
+  is_resource_group_member BOOL,
   
   bq_inserted_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP(),
   PRIMARY KEY(txn_version, write_set_change_index) NOT ENFORCED
@@ -30,7 +30,8 @@ THEN
     is_deleted,
     generic_type_params,
     data,
-    state_key_hash
+    state_key_hash,
+    is_resource_group_member
   )
   VALUES (
     staging.txn_version,
@@ -44,6 +45,7 @@ THEN
     staging.is_deleted,
     staging.generic_type_params,
     staging.data,
-    staging.state_key_hash
+    staging.state_key_hash,
+    staging.is_resource_group_member
   );
   
\ No newline at end of file
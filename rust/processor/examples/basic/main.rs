@@ -0,0 +1,238 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Registers a processor that isn't part of this crate and runs it like a built-in one, with a
+//! decoder for a network whose transactions carry an extra field. Events it fails to parse can be
+//! routed to `failed_events` instead of failing the batch.
+
+use ahash::AHashMap;
+use anyhow::Result;
+use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+use async_trait::async_trait;
+use clap::Parser;
+use processor::{
+    gap_detectors::ProcessingResult,
+    processors::{
+        custom_processor::{register_processor_factory, CustomProcessorArgs, ProcessorFactory},
+        DefaultProcessingResult, ProcessorTrait,
+    },
+    utils::{
+        database::ArcDbPool,
+        failed_events::{insert_failed_events, FailedEventCollector},
+        transaction_decoder::{
+            register_transaction_decoder, DecodedTransaction, TransactionDecoder,
+            TransactionExtensions,
+        },
+        util::deserialize_from_string,
+    },
+    IndexerGrpcProcessorConfig,
+};
+use prost::Message;
+use serde::Deserialize;
+use server_framework::ServerArgs;
+use std::{
+    any::Any,
+    collections::BTreeSet,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+/// Field the network appends to `Transaction`. The processors only see the standard message,
+/// which doesn't know about it.
+#[derive(Clone, PartialEq, Message)]
+struct TransactionExtension {
+    #[prost(string, tag = "1000")]
+    tenant: String,
+}
+
+/// Decodes the standard transaction, and the extension from the same bytes
+struct TenantDecoder;
+
+impl TransactionDecoder for TenantDecoder {
+    fn decode(&self, bytes: &[u8]) -> Result<DecodedTransaction> {
+        let transaction = Transaction::decode(bytes)?;
+        let extension = TransactionExtension::decode(bytes)?;
+        Ok(DecodedTransaction {
+            transaction,
+            extension: (!extension.tenant.is_empty())
+                .then(|| Arc::new(extension) as Arc<dyn Any + Send + Sync>),
+        })
+    }
+}
+
+const FEE_STATEMENT_TYPE: &str = "0x1::transaction_fee::FeeStatement";
+
+#[derive(Deserialize)]
+struct FeeStatement {
+    #[serde(deserialize_with = "deserialize_from_string")]
+    total_charge_gas_units: u64,
+}
+
+/// Logs how many user transactions it has seen and the gas they were charged. It writes nothing
+/// but progress, and the fee statements it fails to parse if `dead_letter_failed_events` is set.
+struct UserTransactionCounter {
+    connection_pool: ArcDbPool,
+    per_table_chunk_sizes: AHashMap<String, usize>,
+    log_every: u64,
+    dead_letter_failed_events: bool,
+    count: AtomicU64,
+    gas_units: AtomicU64,
+}
+
+impl Debug for UserTransactionCounter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "UserTransactionCounter {{ log_every: {} }}",
+            self.log_every
+        )
+    }
+}
+
+#[async_trait]
+impl ProcessorTrait for UserTransactionCounter {
+    fn name(&self) -> &'static str {
+        "user_transaction_counter"
+    }
+
+    async fn process_transactions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        start_version: u64,
+        end_version: u64,
+        db_chain_id: Option<u64>,
+    ) -> Result<ProcessingResult> {
+        self.process_transactions_with_extensions(
+            transactions,
+            Arc::default(),
+            start_version,
+            end_version,
+            db_chain_id,
+        )
+        .await
+    }
+
+    async fn process_transactions_with_extensions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        extensions: Arc<TransactionExtensions>,
+        start_version: u64,
+        end_version: u64,
+        _: Option<u64>,
+    ) -> Result<ProcessingResult> {
+        let tenants = transactions
+            .iter()
+            .filter_map(|txn| {
+                extensions
+                    .get(&txn.version)?
+                    .downcast_ref::<TransactionExtension>()
+            })
+            .map(|extension| extension.tenant.as_str())
+            .collect::<BTreeSet<_>>();
+        let mut failed_events =
+            FailedEventCollector::new(self.name(), self.dead_letter_failed_events);
+        let mut num_user_transactions = 0;
+        let mut gas_units = 0;
+        for txn in transactions.iter() {
+            let Some(TxnData::User(user_txn)) = &txn.txn_data else {
+                continue;
+            };
+            num_user_transactions += 1;
+            for (index, event) in user_txn.events.iter().enumerate() {
+                let fee_statement =
+                    failed_events.parse(event, txn.version as i64, index as i64, |event| {
+                        if event.type_str != FEE_STATEMENT_TYPE {
+                            return Ok(None);
+                        }
+                        Ok(Some(serde_json::from_str::<FeeStatement>(&event.data)?))
+                    })?;
+                if let Some(fee_statement) = fee_statement {
+                    gas_units += fee_statement.total_charge_gas_units;
+                }
+            }
+        }
+        insert_failed_events(
+            self.connection_pool.clone(),
+            &failed_events.into_failed_events(),
+            &self.per_table_chunk_sizes,
+        )
+        .await?;
+
+        let before = self
+            .count
+            .fetch_add(num_user_transactions, Ordering::Relaxed);
+        let after = before + num_user_transactions;
+        let total_gas_units = self.gas_units.fetch_add(gas_units, Ordering::Relaxed) + gas_units;
+        if after / self.log_every > before / self.log_every {
+            tracing::info!(
+                end_version,
+                count = after,
+                total_gas_units,
+                ?tenants,
+                "Counted user transactions"
+            );
+        }
+
+        Ok(ProcessingResult::DefaultProcessingResult(
+            DefaultProcessingResult {
+                start_version,
+                end_version,
+                processing_duration_in_secs: 0.0,
+                db_insertion_duration_in_secs: 0.0,
+                last_transaction_timestamp: transactions.last().unwrap().timestamp,
+                num_events: None,
+                num_write_set_changes: None,
+            },
+        ))
+    }
+
+    fn connection_pool(&self) -> &ArcDbPool {
+        &self.connection_pool
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserTransactionCounterParams {
+    log_every: u64,
+    #[serde(default)]
+    dead_letter_failed_events: bool,
+}
+
+struct UserTransactionCounterFactory;
+
+impl ProcessorFactory for UserTransactionCounterFactory {
+    fn build(
+        &self,
+        params: &serde_json::Value,
+        args: CustomProcessorArgs,
+    ) -> Result<Box<dyn ProcessorTrait>> {
+        let params: UserTransactionCounterParams = serde_json::from_value(params.clone())?;
+        Ok(Box::new(UserTransactionCounter {
+            connection_pool: args.db_pool,
+            per_table_chunk_sizes: args.per_table_chunk_sizes,
+            log_every: params.log_every.max(1),
+            dead_letter_failed_events: params.dead_letter_failed_events,
+            count: AtomicU64::new(0),
+            gas_units: AtomicU64::new(0),
+        }))
+    }
+}
+
+fn main() -> Result<()> {
+    register_processor_factory("user_transaction_counter", UserTransactionCounterFactory);
+    register_transaction_decoder(TenantDecoder);
+
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(async {
+            let args = ServerArgs::parse();
+            args.run::<IndexerGrpcProcessorConfig>(tokio::runtime::Handle::current())
+                .await
+        })
+}
@@ -1,33 +1,104 @@
-use crate::{bq_analytics::ParquetProcessorError, utils::counters::PARQUET_BUFFER_SIZE};
+use crate::{
+    bq_analytics::ParquetProcessorError,
+    utils::{
+        counters::PARQUET_BUFFER_SIZE,
+        retry::{Backoff, Jitter},
+    },
+};
 use anyhow::{Context, Result};
 use chrono::{Datelike, Timelike};
 use google_cloud_storage::{
     client::Client as GCSClient,
-    http::objects::upload::{Media, UploadObjectRequest, UploadType},
+    http::{
+        objects::{
+            get::GetObjectRequest,
+            list::ListObjectsRequest,
+            upload::{Media, UploadObjectRequest, UploadType},
+        },
+        Error as StorageError,
+    },
 };
 use hyper::{body::HttpBody, Body};
+use serde::{Deserialize, Serialize};
 use std::path::{Path, PathBuf};
-use tokio::time::{sleep, timeout, Duration};
+use tokio::time::{timeout, Duration};
 use tracing::{debug, error, info};
 
-const MAX_RETRIES: usize = 3;
-const INITIAL_DELAY_MS: u64 = 500;
-const TIMEOUT_SECONDS: u64 = 300;
-pub async fn upload_parquet_to_gcs(
-    client: &GCSClient,
-    buffer: Vec<u8>,
-    table_name: &str,
-    bucket_name: &str,
-    bucket_root: &Path,
-    processor_name: String,
-) -> Result<(), ParquetProcessorError> {
-    if buffer.is_empty() {
-        error!("The file is empty and has no data to upload.",);
-        return Err(ParquetProcessorError::Other(
-            "The file is empty and has no data to upload.".to_string(),
-        ));
+/// Timeout and retry settings for a single Parquet file upload. The delay between retries doubles
+/// after each attempt, with `retry_jitter` applied.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GcsUploadConfig {
+    #[serde(default = "GcsUploadConfig::default_upload_timeout_secs")]
+    pub upload_timeout_secs: u64,
+    #[serde(default = "GcsUploadConfig::default_max_retries")]
+    pub max_retries: usize,
+    #[serde(default = "GcsUploadConfig::default_initial_retry_delay_ms")]
+    pub initial_retry_delay_ms: u64,
+    #[serde(default)]
+    pub retry_jitter: Jitter,
+    /// How long the handler gets on shutdown to upload what it has buffered. Structs it doesn't
+    /// get to upload are processed again after a restart.
+    #[serde(default = "GcsUploadConfig::default_shutdown_flush_timeout_secs")]
+    pub shutdown_flush_timeout_secs: u64,
+    /// Checks each file's structs against how many the processor made per version and its row
+    /// count against the structs written to it before uploading it, and the uploaded object's
+    /// size against the file's after, failing the upload on a mismatch.
+    #[serde(default)]
+    pub verify_uploads: bool,
+}
+
+impl GcsUploadConfig {
+    pub const fn default_upload_timeout_secs() -> u64 {
+        300
+    }
+
+    pub const fn default_max_retries() -> usize {
+        3
+    }
+
+    pub const fn default_initial_retry_delay_ms() -> u64 {
+        500
+    }
+
+    pub const fn default_shutdown_flush_timeout_secs() -> u64 {
+        60
     }
+}
+
+impl Default for GcsUploadConfig {
+    fn default() -> Self {
+        Self {
+            upload_timeout_secs: Self::default_upload_timeout_secs(),
+            max_retries: Self::default_max_retries(),
+            initial_retry_delay_ms: Self::default_initial_retry_delay_ms(),
+            retry_jitter: Jitter::default(),
+            shutdown_flush_timeout_secs: Self::default_shutdown_flush_timeout_secs(),
+            verify_uploads: false,
+        }
+    }
+}
 
+/// Where to look for Parquet files already written for a backfill, to resume after them instead
+/// of relying on DB progress.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParquetResumeConfig {
+    pub bucket_name: String,
+    pub bucket_root: String,
+    /// Tables the processor writes. It resumes at the lowest version any of them is missing, so
+    /// none is left with a gap.
+    pub table_names: Vec<String>,
+}
+
+/// Path of the Parquet file for a table's versions `start_version..=end_version`, under the
+/// current month's directory
+pub fn new_parquet_file_path(
+    bucket_root: &Path,
+    table_name: &str,
+    start_version: i64,
+    end_version: i64,
+) -> PathBuf {
     let now = chrono::Utc::now();
     let start_of_month = now
         .with_day(1)
@@ -41,10 +112,154 @@ pub async fn upload_parquet_to_gcs(
         .with_nanosecond(0)
         .unwrap();
     let highwater_s = start_of_month.timestamp_millis();
-    let highwater_ms = now.timestamp_millis();
-    let counter = 0; // THIS NEED TO BE REPLACED OR REIMPLEMENTED WITH AN ACTUAL LOGIC TO ENSURE FILE UNIQUENESS.
-    let object_name: PathBuf =
-        generate_parquet_file_path(bucket_root, table_name, highwater_s, highwater_ms, counter);
+    generate_parquet_file_path(
+        bucket_root,
+        table_name,
+        highwater_s,
+        start_version,
+        end_version,
+    )
+}
+
+/// The version range of a Parquet file written by `new_parquet_file_path`, from its name.
+pub fn parse_parquet_file_versions(object_name: &str) -> Option<(u64, u64)> {
+    let file_stem = object_name.rsplit('/').next()?.strip_suffix(".parquet")?;
+    let (start_version, end_version) = file_stem.split_once('_')?;
+    let (start_version, end_version) = (start_version.parse().ok()?, end_version.parse().ok()?);
+    // Files from before versions were in the name are `<timestamp ms>_0.parquet`
+    (start_version <= end_version).then_some((start_version, end_version))
+}
+
+/// The version after the files that cover `starting_version` onwards without a gap, or None if
+/// none starts at or before it. Files past a gap are ignored, so whatever is missing is written
+/// again. Names that don't parse are ignored.
+pub fn resume_version_from_object_names<'a>(
+    object_names: impl IntoIterator<Item = &'a str>,
+    starting_version: u64,
+) -> Option<u64> {
+    let mut versions: Vec<_> = object_names
+        .into_iter()
+        .filter_map(parse_parquet_file_versions)
+        .collect();
+    versions.sort_unstable();
+    let mut resume_version = None;
+    for (start_version, end_version) in versions {
+        let next_version = resume_version.unwrap_or(starting_version);
+        if start_version > next_version {
+            break;
+        }
+        if end_version >= next_version {
+            resume_version = Some(end_version + 1);
+        }
+    }
+    resume_version
+}
+
+/// Lists every object under the table's directory in the bucket.
+pub async fn list_parquet_file_names(
+    client: &GCSClient,
+    bucket_name: &str,
+    bucket_root: &str,
+    table_name: &str,
+) -> Result<Vec<String>, ParquetProcessorError> {
+    let prefix = Path::new(bucket_root)
+        .join(table_name)
+        .to_string_lossy()
+        .into_owned();
+    let mut object_names = vec![];
+    let mut page_token = None;
+    loop {
+        let request = ListObjectsRequest {
+            bucket: bucket_name.to_string(),
+            prefix: Some(format!("{}/", prefix)),
+            page_token,
+            ..Default::default()
+        };
+        let response = client
+            .list_objects(&request)
+            .await
+            .map_err(ParquetProcessorError::StorageError)?;
+        object_names.extend(
+            response
+                .items
+                .unwrap_or_default()
+                .into_iter()
+                .map(|o| o.name),
+        );
+        match response.next_page_token {
+            Some(next_page_token) => page_token = Some(next_page_token),
+            None => return Ok(object_names),
+        }
+    }
+}
+
+/// The version to resume a backfill from, based on the Parquet files already in GCS. None if no
+/// table has files from `starting_version` on, in which case the processor starts from there.
+pub async fn get_resume_version_from_gcs(
+    client: &GCSClient,
+    resume_config: &ParquetResumeConfig,
+    starting_version: u64,
+) -> Result<Option<u64>, ParquetProcessorError> {
+    let mut resume_version: Option<u64> = None;
+    for table_name in &resume_config.table_names {
+        let object_names = list_parquet_file_names(
+            client,
+            &resume_config.bucket_name,
+            &resume_config.bucket_root,
+            table_name,
+        )
+        .await?;
+        let table_resume_version = resume_version_from_object_names(
+            object_names.iter().map(String::as_str),
+            starting_version,
+        );
+        info!(
+            table_name = table_name,
+            num_files = object_names.len(),
+            resume_version = table_resume_version,
+            "Found Parquet files in GCS",
+        );
+        // A table without files yet is skipped. It may just have had nothing to write
+        if let Some(table_resume_version) = table_resume_version {
+            resume_version =
+                Some(resume_version.map_or(table_resume_version, |v| v.min(table_resume_version)));
+        }
+    }
+    Ok(resume_version)
+}
+
+pub async fn parquet_file_exists(
+    client: &GCSClient,
+    bucket_name: &str,
+    object_name: &str,
+) -> Result<bool, ParquetProcessorError> {
+    let request = GetObjectRequest {
+        bucket: bucket_name.to_string(),
+        object: object_name.to_string(),
+        ..Default::default()
+    };
+    match client.get_object(&request).await {
+        Ok(_) => Ok(true),
+        Err(StorageError::Response(e)) if e.code == 404 => Ok(false),
+        Err(e) => Err(ParquetProcessorError::StorageError(e)),
+    }
+}
+
+pub async fn upload_parquet_to_gcs(
+    client: &GCSClient,
+    buffer: Vec<u8>,
+    table_name: &str,
+    bucket_name: &str,
+    object_name: &Path,
+    processor_name: String,
+    upload_config: GcsUploadConfig,
+) -> Result<(), ParquetProcessorError> {
+    if buffer.is_empty() {
+        error!("The file is empty and has no data to upload.",);
+        return Err(ParquetProcessorError::Other(
+            "The file is empty and has no data to upload.".to_string(),
+        ));
+    }
 
     let file_name = object_name.to_str().unwrap().to_owned();
     let upload_type: UploadType = UploadType::Simple(Media::new(file_name.clone()));
@@ -55,7 +270,10 @@ pub async fn upload_parquet_to_gcs(
     };
 
     let mut retry_count = 0;
-    let mut delay = INITIAL_DELAY_MS;
+    let mut backoff = Backoff::new(
+        upload_config.initial_retry_delay_ms,
+        upload_config.retry_jitter,
+    );
 
     loop {
         let data = Body::from(buffer.clone());
@@ -66,13 +284,26 @@ pub async fn upload_parquet_to_gcs(
             .set(size as i64);
 
         let upload_result = timeout(
-            Duration::from_secs(TIMEOUT_SECONDS),
+            Duration::from_secs(upload_config.upload_timeout_secs),
             client.upload_object(&upload_request, data, &upload_type),
         )
         .await;
 
         match upload_result {
             Ok(Ok(result)) => {
+                if upload_config.verify_uploads && result.size != size as i64 {
+                    error!(
+                        table_name = table_name,
+                        file_name = result.name,
+                        size_in_bytes = size,
+                        uploaded_size_in_bytes = result.size,
+                        "Uploaded file size doesn't match",
+                    );
+                    return Err(ParquetProcessorError::Other(format!(
+                        "Uploaded {} is {} bytes instead of {}",
+                        result.name, result.size, size
+                    )));
+                }
                 info!(
                     table_name = table_name,
                     file_name = result.name,
@@ -81,22 +312,43 @@ pub async fn upload_parquet_to_gcs(
                 return Ok(());
             },
             Ok(Err(e)) => {
-                error!("Failed to upload file to GCS: {}", e);
-                if retry_count >= MAX_RETRIES {
-                    return Err(ParquetProcessorError::StorageError(e));
+                error!(
+                    table_name = table_name,
+                    size_in_bytes = size,
+                    retry_count = retry_count,
+                    "Failed to upload file to GCS: {}",
+                    e
+                );
+                if retry_count >= upload_config.max_retries {
+                    return Err(ParquetProcessorError::UploadFailed {
+                        table_name: table_name.to_string(),
+                        size_in_bytes: size,
+                        attempts: retry_count + 1,
+                        source: Box::new(ParquetProcessorError::StorageError(e)),
+                    });
                 }
             },
             Err(e) => {
-                error!("Upload timed out: {}", e);
-                if retry_count >= MAX_RETRIES {
-                    return Err(ParquetProcessorError::TimeoutError(e));
+                error!(
+                    table_name = table_name,
+                    size_in_bytes = size,
+                    retry_count = retry_count,
+                    "Upload timed out: {}",
+                    e
+                );
+                if retry_count >= upload_config.max_retries {
+                    return Err(ParquetProcessorError::UploadFailed {
+                        table_name: table_name.to_string(),
+                        size_in_bytes: size,
+                        attempts: retry_count + 1,
+                        source: Box::new(ParquetProcessorError::TimeoutError(e)),
+                    });
                 }
             },
         }
 
         retry_count += 1;
-        sleep(Duration::from_millis(delay)).await;
-        delay *= 2;
+        backoff.sleep().await;
         debug!("Retrying upload operation. Retry count: {}", retry_count);
     }
 }
@@ -105,11 +357,72 @@ fn generate_parquet_file_path(
     gcs_bucket_root: &Path,
     table: &str,
     highwater_s: i64,
-    highwater_ms: i64,
-    counter: u32,
+    start_version: i64,
+    end_version: i64,
 ) -> PathBuf {
     gcs_bucket_root.join(format!(
         "{}/{}/{}_{}.parquet",
-        table, highwater_s, highwater_ms, counter
+        table, highwater_s, start_version, end_version
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume_version_from_object_names() {
+        let object_names = [
+            "root/transactions/1719792000000/0_999.parquet",
+            "root/transactions/1719792000000/1000_1999.parquet",
+            "root/transactions/1722470400000/2000_2499.parquet",
+            // Written before versions were in the name
+            "root/transactions/1719792000000/1720000000000_0.parquet",
+            "root/transactions/1719792000000/_SUCCESS",
+        ];
+        assert_eq!(
+            parse_parquet_file_versions(object_names[2]),
+            Some((2000, 2499))
+        );
+        assert_eq!(parse_parquet_file_versions(object_names[3]), None);
+        assert_eq!(
+            resume_version_from_object_names(object_names, 0),
+            Some(2500)
+        );
+        assert_eq!(
+            resume_version_from_object_names(object_names, 1500),
+            Some(2500)
+        );
+        assert_eq!(resume_version_from_object_names(object_names, 2500), None);
+        assert_eq!(resume_version_from_object_names([], 0), None);
+    }
+
+    #[test]
+    fn test_resume_version_stops_at_gap() {
+        let object_names = [
+            "root/transactions/1719792000000/0_999.parquet",
+            // 1000 to 1999 never made it
+            "root/transactions/1719792000000/2000_2999.parquet",
+            "root/transactions/1719792000000/3000_3999.parquet",
+        ];
+        assert_eq!(
+            resume_version_from_object_names(object_names, 0),
+            Some(1000)
+        );
+        // Nothing covers the starting version
+        assert_eq!(
+            resume_version_from_object_names(object_names[1..].iter().copied(), 0),
+            None
+        );
+        // Files that overlap, e.g. rewritten with another batch size, still count
+        let object_names = [
+            "root/transactions/1719792000000/0_999.parquet",
+            "root/transactions/1719792000000/500_1499.parquet",
+            "root/transactions/1719792000000/1000_1199.parquet",
+        ];
+        assert_eq!(
+            resume_version_from_object_names(object_names, 0),
+            Some(1500)
+        );
+    }
+}
@@ -1,21 +1,37 @@
 use super::ParquetProcessingResult;
 use crate::{
-    bq_analytics::gcs_handler::upload_parquet_to_gcs,
+    bq_analytics::{
+        gcs_handler::{
+            new_parquet_file_path, parquet_file_exists, upload_parquet_to_gcs, GcsUploadConfig,
+        },
+        parquet_checkpoint::{reconcile_checkpoints, UploadedRange},
+    },
+    db::postgres::models::parquet_upload_checkpoint::{
+        ParquetUploadCheckpoint, CHECKPOINT_STATUS_UPLOADING,
+    },
     gap_detectors::ProcessingResult,
     utils::{
         counters::{PARQUET_HANDLER_CURRENT_BUFFER_SIZE, PARQUET_STRUCT_SIZE},
+        database::ArcDbPool,
         util::naive_datetime_to_timestamp,
     },
 };
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use allocative::Allocative;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use google_cloud_storage::client::Client as GCSClient;
 use parquet::{
-    file::{properties::WriterProperties, writer::SerializedFileWriter},
+    basic::{Compression, GzipLevel, ZstdLevel},
+    file::{
+        footer::{decode_footer, decode_metadata},
+        properties::WriterProperties,
+        writer::SerializedFileWriter,
+        FOOTER_SIZE,
+    },
     record::RecordWriter,
     schema::types::Type,
 };
+use serde::{Deserialize, Serialize};
 use std::{path::PathBuf, sync::Arc, time::Instant};
 use tokio::time::Duration;
 use tracing::{debug, error, info};
@@ -23,6 +39,22 @@ use tracing::{debug, error, info};
 #[derive(Debug, Default, Clone)]
 pub struct ParquetDataGeneric<ParquetType> {
     pub data: Vec<ParquetType>,
+    /// How many structs of `data` the processor made for each version, which uploads are checked
+    /// against with `verify_uploads`
+    pub transaction_version_to_struct_count: AHashMap<i64, i64>,
+}
+
+/// Adds up the struct counts of a processor's tables, which is what the gap detector waits for.
+pub fn merge_struct_counts<'a>(
+    counts: impl IntoIterator<Item = &'a AHashMap<i64, i64>>,
+) -> AHashMap<i64, i64> {
+    let mut transaction_version_to_struct_count = AHashMap::new();
+    for (version, count) in counts.into_iter().flatten() {
+        *transaction_version_to_struct_count
+            .entry(*version)
+            .or_insert(0) += count;
+    }
+    transaction_version_to_struct_count
 }
 
 pub trait NamedTable {
@@ -65,6 +97,8 @@ where
     pub buffer: Vec<ParquetType>,
     pub buffer_size_bytes: usize,
 
+    /// Structs the processor sent per version that haven't been uploaded yet, with
+    /// `verify_uploads`
     pub transaction_version_to_struct_count: AHashMap<i64, i64>,
     pub bucket_name: String,
     pub bucket_root: String,
@@ -73,24 +107,130 @@ where
     pub max_buffer_size: usize,
     pub last_upload_time: Instant,
     pub processor_name: String,
+    pub gcs_upload_config: GcsUploadConfig,
+    pub checkpoint_pool: ArcDbPool,
+    /// Structs replayed after a restart that were already uploaded before it
+    pub uploaded_range: Option<UploadedRange>,
+    pub compression: Compression,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum ParquetCodec {
+    Uncompressed,
+    Snappy,
+    Gzip,
+    #[default]
+    Lz4,
+    Zstd,
 }
 
-fn create_new_writer(schema: Arc<Type>) -> Result<SerializedFileWriter<Vec<u8>>> {
+/// Compression of the Parquet files written. LZ4 by default, ZSTD makes much smaller files for
+/// BigQuery loads at a bit more CPU.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParquetCompressionConfig {
+    #[serde(default)]
+    pub codec: ParquetCodec,
+    /// 1 to 22 for ZSTD, 0 to 10 for GZIP, the codec's default if not set. SNAPPY and LZ4 don't
+    /// take one.
+    #[serde(default)]
+    pub level: Option<u32>,
+}
+
+impl ParquetCompressionConfig {
+    /// The compression to write with, failing if the level doesn't fit the codec.
+    pub fn compression(&self) -> Result<Compression> {
+        let compression = match (self.codec, self.level) {
+            (ParquetCodec::Zstd, None) => Compression::ZSTD(ZstdLevel::default()),
+            (ParquetCodec::Zstd, Some(level)) => Compression::ZSTD(
+                ZstdLevel::try_new(i32::try_from(level).unwrap_or(i32::MAX))
+                    .context("Invalid ZSTD compression level")?,
+            ),
+            (ParquetCodec::Gzip, None) => Compression::GZIP(GzipLevel::default()),
+            (ParquetCodec::Gzip, Some(level)) => Compression::GZIP(
+                GzipLevel::try_new(level).context("Invalid GZIP compression level")?,
+            ),
+            (codec, Some(_)) => bail!("{:?} compression doesn't take a level", codec),
+            (ParquetCodec::Uncompressed, None) => Compression::UNCOMPRESSED,
+            (ParquetCodec::Snappy, None) => Compression::SNAPPY,
+            (ParquetCodec::Lz4, None) => Compression::LZ4,
+        };
+        Ok(compression)
+    }
+}
+
+pub fn create_new_writer(
+    schema: Arc<Type>,
+    compression: Compression,
+) -> Result<SerializedFileWriter<Vec<u8>>> {
     let props = WriterProperties::builder()
-        .set_compression(parquet::basic::Compression::LZ4)
+        .set_compression(compression)
         .build();
     let props_arc = Arc::new(props);
 
     SerializedFileWriter::new(Vec::new(), schema, props_arc).context("Failed to create new writer")
 }
 
+/// Takes the structs per version of a file ending at `end_version` off what the processor sent.
+/// Fails if a version has more structs than the processor counted, or fewer when no more can
+/// follow, since only the last version of a file can continue in the next one.
+fn reconcile_struct_counts(
+    transaction_version_to_struct_count: &mut AHashMap<i64, i64>,
+    file_struct_counts: &AHashMap<i64, i64>,
+    end_version: i64,
+) -> Result<()> {
+    for (version, count) in file_struct_counts {
+        *transaction_version_to_struct_count
+            .entry(*version)
+            .or_insert(0) -= count;
+    }
+    let mut missing_structs = transaction_version_to_struct_count
+        .iter()
+        .filter(|(version, missing)| **missing < 0 || (**missing > 0 && **version < end_version))
+        .map(|(version, missing)| (*version, *missing))
+        .collect::<Vec<_>>();
+    transaction_version_to_struct_count.retain(|_, missing| *missing != 0);
+    if !missing_structs.is_empty() {
+        missing_structs.sort();
+        bail!(
+            "Structs missing per version, negative if there are more than the processor counted: \
+             {:?}",
+            missing_structs
+        );
+    }
+    Ok(())
+}
+
+/// Checks that the Parquet `file` has `expected_row_count` rows, going by its footer.
+fn verify_row_count(file: &[u8], expected_row_count: i64) -> Result<()> {
+    let footer_start = file
+        .len()
+        .checked_sub(FOOTER_SIZE)
+        .context("Parquet file is shorter than its footer")?;
+    let footer: &[u8; FOOTER_SIZE] = file[footer_start..].try_into()?;
+    let metadata_start = footer_start
+        .checked_sub(decode_footer(footer)?)
+        .context("Parquet file is shorter than its metadata")?;
+    let metadata = decode_metadata(&file[metadata_start..footer_start])?;
+    let row_count = metadata.file_metadata().num_rows();
+    if row_count != expected_row_count {
+        bail!(
+            "Parquet file has {} rows, expected {}",
+            row_count,
+            expected_row_count
+        );
+    }
+    Ok(())
+}
+
 impl<ParquetType> ParquetHandler<ParquetType>
 where
     ParquetType: Allocative + GetTimeStamp + HasVersion + HasParquetSchema + 'static + NamedTable,
     for<'a> &'a [ParquetType]: RecordWriter<ParquetType>,
 {
     fn create_new_writer(&self) -> Result<SerializedFileWriter<Vec<u8>>> {
-        create_new_writer(self.schema.clone())
+        create_new_writer(self.schema.clone(), self.compression)
     }
 
     fn close_writer(&mut self) -> Result<SerializedFileWriter<Vec<u8>>> {
@@ -99,6 +239,7 @@ where
         Ok(old_writer)
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         bucket_name: String,
         bucket_root: String,
@@ -107,9 +248,12 @@ where
         upload_interval: Duration,
         max_buffer_size: usize,
         processor_name: String,
+        gcs_upload_config: GcsUploadConfig,
+        checkpoint_pool: ArcDbPool,
+        compression: Compression,
     ) -> Result<Self> {
         // had to append unique id to avoid concurrent write issues
-        let writer = create_new_writer(schema.clone())?;
+        let writer = create_new_writer(schema.clone(), compression)?;
 
         Ok(Self {
             writer,
@@ -124,15 +268,127 @@ where
             max_buffer_size,
             last_upload_time: Instant::now(),
             processor_name,
+            gcs_upload_config,
+            checkpoint_pool,
+            uploaded_range: None,
+            compression,
         })
     }
 
+    /// Resolves the uploads a previous run left uncommitted by checking whether their files made
+    /// it to GCS, and picks up the last committed upload so already uploaded structs get dropped.
+    pub async fn recover_checkpoints(&mut self, gcs_client: &GCSClient) -> Result<()> {
+        let checkpoints = {
+            let mut conn = self.checkpoint_pool.get().await?;
+            ParquetUploadCheckpoint::get_by_table(
+                &self.processor_name,
+                ParquetType::TABLE_NAME,
+                &mut conn,
+            )
+            .await?
+        };
+        let mut uploaded_files = AHashSet::new();
+        for checkpoint in checkpoints.iter().filter(|c| !c.is_committed()) {
+            if parquet_file_exists(gcs_client, &self.bucket_name, &checkpoint.file_path).await? {
+                uploaded_files.insert(checkpoint.file_path.clone());
+            }
+        }
+
+        let recovery = reconcile_checkpoints(checkpoints, &uploaded_files);
+        for mut checkpoint in recovery.to_commit {
+            checkpoint
+                .mark_committed(self.checkpoint_pool.clone())
+                .await?;
+        }
+        for checkpoint in recovery.to_delete {
+            checkpoint.delete(self.checkpoint_pool.clone()).await?;
+        }
+        if let Some(last_committed) = recovery.last_committed {
+            info!(
+                table_name = ParquetType::TABLE_NAME,
+                end_version = last_committed.end_version,
+                "[Parquet Handler] Recovered last committed upload checkpoint",
+            );
+            self.uploaded_range = Some(UploadedRange::new(&last_committed));
+        }
+        Ok(())
+    }
+
+    /// Drops structs that were already uploaded before a restart. They are acknowledged to the
+    /// gap detector right away, as if they had just been uploaded.
+    async fn drop_uploaded_structs(
+        &mut self,
+        parquet_structs: Vec<ParquetType>,
+    ) -> Result<Vec<ParquetType>> {
+        let Some(uploaded_range) = self.uploaded_range.as_mut() else {
+            return Ok(parquet_structs);
+        };
+
+        let mut uploaded = vec![];
+        let mut remaining = vec![];
+        for parquet_struct in parquet_structs {
+            if uploaded_range.contains(parquet_struct.version()) {
+                uploaded.push(parquet_struct);
+            } else {
+                remaining.push(parquet_struct);
+            }
+        }
+        if remaining
+            .last()
+            .is_some_and(|last| uploaded_range.is_passed(last.version()))
+        {
+            self.uploaded_range = None;
+        }
+
+        if let (Some(first), Some(last)) = (uploaded.first(), uploaded.last()) {
+            debug!(
+                table_name = ParquetType::TABLE_NAME,
+                num_structs = uploaded.len(),
+                "Dropping structs that were already uploaded.",
+            );
+            let uploaded_struct_counts = build_parquet_processed_transactions(&uploaded);
+            if self.gcs_upload_config.verify_uploads {
+                for (version, count) in &uploaded_struct_counts {
+                    *self
+                        .transaction_version_to_struct_count
+                        .entry(*version)
+                        .or_insert(0) -= count;
+                }
+                self.transaction_version_to_struct_count
+                    .retain(|_, count| *count != 0);
+            }
+            let parquet_processing_result = ParquetProcessingResult {
+                start_version: first.version(),
+                end_version: last.version(),
+                last_transaction_timestamp: Some(naive_datetime_to_timestamp(last.get_timestamp())),
+                txn_version_to_struct_count: None,
+                parquet_processed_structs: Some(uploaded_struct_counts),
+                table_name: ParquetType::TABLE_NAME.to_string(),
+            };
+            self.gap_detector_sender
+                .send(ProcessingResult::ParquetProcessingResult(
+                    parquet_processing_result,
+                ))
+                .await
+                .context("Failed to send dropped structs to gap detector")?;
+        }
+        Ok(remaining)
+    }
+
     pub async fn handle(
         &mut self,
         gcs_client: &GCSClient,
         changes: ParquetDataGeneric<ParquetType>,
     ) -> Result<()> {
-        let parquet_structs = changes.data;
+        if self.gcs_upload_config.verify_uploads {
+            for (version, count) in changes.transaction_version_to_struct_count {
+                *self
+                    .transaction_version_to_struct_count
+                    .entry(version)
+                    .or_insert(0) += count;
+            }
+        }
+        let parquet_structs = self.drop_uploaded_structs(changes.data).await?;
         let processor_name = self.processor_name.clone();
 
         if self.last_upload_time.elapsed() >= self.upload_interval {
@@ -178,6 +434,16 @@ where
         Ok(())
     }
 
+    /// Uploads whatever is buffered, returning the number of structs uploaded. Unlike the
+    /// periodic upload, nothing is sent to the gap detector when the buffer is empty.
+    pub async fn flush(&mut self, gcs_client: &GCSClient) -> Result<usize> {
+        let num_structs = self.buffer.len();
+        if num_structs > 0 {
+            self.upload_buffer(gcs_client).await?;
+        }
+        Ok(num_structs)
+    }
+
     async fn upload_buffer(&mut self, gcs_client: &GCSClient) -> Result<()> {
         // This is to cover the case when interval duration has passed but buffer is empty
         if self.buffer.is_empty() {
@@ -214,7 +480,26 @@ where
         let last_transaction_timestamp = naive_datetime_to_timestamp(last.get_timestamp());
 
         let parquet_processed_transactions = build_parquet_processed_transactions(&self.buffer);
+        let end_version_struct_count = parquet_processed_transactions
+            .get(&end_version)
+            .copied()
+            .unwrap_or_default();
         let struct_buffer = std::mem::take(&mut self.buffer);
+        if self.gcs_upload_config.verify_uploads {
+            reconcile_struct_counts(
+                &mut self.transaction_version_to_struct_count,
+                &parquet_processed_transactions,
+                end_version,
+            )
+            .with_context(|| {
+                format!(
+                    "{} structs for versions {} to {} don't match what the processor sent",
+                    ParquetType::TABLE_NAME,
+                    start_version,
+                    end_version
+                )
+            })?;
+        }
 
         let mut row_group_writer = self
             .writer
@@ -233,19 +518,56 @@ where
         let upload_buffer = old_writer
             .into_inner()
             .context("Failed to get inner buffer")?;
+        if self.gcs_upload_config.verify_uploads {
+            verify_row_count(&upload_buffer, struct_buffer.len() as i64).with_context(|| {
+                format!(
+                    "Failed to verify {} file for versions {} to {}",
+                    ParquetType::TABLE_NAME,
+                    start_version,
+                    end_version
+                )
+            })?;
+        }
 
         let bucket_root = PathBuf::from(&self.bucket_root);
+        let object_name = new_parquet_file_path(
+            &bucket_root,
+            ParquetType::TABLE_NAME,
+            start_version,
+            end_version,
+        );
+
+        // Recorded before the upload so a crash mid-upload can be reconciled against GCS
+        let mut checkpoint = ParquetUploadCheckpoint {
+            processor: self.processor_name.clone(),
+            table_name: ParquetType::TABLE_NAME.to_string(),
+            start_version,
+            end_version,
+            end_version_struct_count,
+            file_path: object_name.to_string_lossy().into_owned(),
+            status: CHECKPOINT_STATUS_UPLOADING.to_string(),
+        };
+        checkpoint
+            .mark_uploading(self.checkpoint_pool.clone())
+            .await
+            .context("Failed to record upload checkpoint")?;
 
         upload_parquet_to_gcs(
             gcs_client,
             upload_buffer,
             ParquetType::TABLE_NAME,
             &self.bucket_name,
-            &bucket_root,
+            &object_name,
             self.processor_name.clone(),
+            self.gcs_upload_config,
         )
         .await?;
 
+        checkpoint
+            .mark_committed(self.checkpoint_pool.clone())
+            .await
+            .context("Failed to commit upload checkpoint")?;
+
         self.buffer_size_bytes = 0;
 
         let parquet_processing_result = ParquetProcessingResult {
@@ -288,3 +610,154 @@ fn build_parquet_processed_transactions<ParquetType: NamedTable + HasVersion>(
     }
     txn_version_to_struct_count_for_gap_detector
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        db::parquet::models::transaction_metadata_model::parquet_write_set_size_info::WriteSetSize,
+        utils::database::MyDbConnection,
+    };
+    use diesel_async::pooled_connection::{bb8::Pool, AsyncDieselConnectionManager};
+    use google_cloud_storage::client::ClientConfig as GcsClientConfig;
+    use parquet::{
+        file::reader::{FileReader, SerializedFileReader},
+        record::RowAccessor,
+    };
+
+    fn compression_config(json: &str) -> Result<Compression> {
+        serde_json::from_str::<ParquetCompressionConfig>(json)?.compression()
+    }
+
+    #[test]
+    fn test_compression_config() {
+        assert_eq!(compression_config("{}").unwrap(), Compression::LZ4);
+        assert_eq!(
+            compression_config(r#"{"codec": "SNAPPY"}"#).unwrap(),
+            Compression::SNAPPY
+        );
+        assert_eq!(
+            compression_config(r#"{"codec": "ZSTD", "level": 9}"#).unwrap(),
+            Compression::ZSTD(ZstdLevel::try_new(9).unwrap())
+        );
+        assert!(compression_config(r#"{"codec": "BROTLI"}"#).is_err());
+        assert!(compression_config(r#"{"codec": "ZSTD", "level": 23}"#).is_err());
+        assert!(compression_config(r#"{"codec": "GZIP", "level": 11}"#).is_err());
+        assert!(compression_config(r#"{"codec": "SNAPPY", "level": 1}"#).is_err());
+    }
+
+    fn write_set_sizes(num_structs: i64) -> Vec<WriteSetSize> {
+        (0..num_structs)
+            .map(|txn_version| WriteSetSize {
+                txn_version,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    fn write_file(structs: &[WriteSetSize], compression: Compression) -> Vec<u8> {
+        let mut writer = create_new_writer(WriteSetSize::schema(), compression).unwrap();
+        let mut row_group_writer = writer.next_row_group().unwrap();
+        structs.write_to_row_group(&mut row_group_writer).unwrap();
+        row_group_writer.close().unwrap();
+        writer.into_inner().unwrap()
+    }
+
+    fn struct_counts(counts: &[(i64, i64)]) -> AHashMap<i64, i64> {
+        counts.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_verify_row_count() {
+        let file = write_file(&write_set_sizes(10), Compression::LZ4);
+        assert!(verify_row_count(&file, 10).is_ok());
+        assert!(verify_row_count(&file, 9).is_err());
+        // A truncated file
+        assert!(verify_row_count(&file[..file.len() / 2], 10).is_err());
+    }
+
+    #[test]
+    fn test_reconcile_struct_counts() {
+        let mut counts = struct_counts(&[(1, 2), (2, 3)]);
+        // The rest of version 2 can still come in the next file
+        reconcile_struct_counts(&mut counts, &struct_counts(&[(1, 2), (2, 1)]), 2).unwrap();
+        assert_eq!(counts, struct_counts(&[(2, 2)]));
+        reconcile_struct_counts(&mut counts, &struct_counts(&[(2, 2)]), 2).unwrap();
+        assert!(counts.is_empty());
+
+        // Version 1 can't continue in a file ending at version 2
+        let mut counts = struct_counts(&[(1, 2), (2, 1)]);
+        assert!(
+            reconcile_struct_counts(&mut counts, &struct_counts(&[(1, 1), (2, 1)]), 2).is_err()
+        );
+        // More structs than the processor counted
+        let mut counts = struct_counts(&[(1, 1)]);
+        assert!(reconcile_struct_counts(&mut counts, &struct_counts(&[(1, 2)]), 1).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upload_fails_on_processor_undercount() {
+        let (gap_detector_sender, _gap_detector_receiver) = kanal::bounded_async(1);
+        // Never connected to, the upload fails before the checkpoint is recorded
+        let checkpoint_pool = Arc::new(Pool::builder().build_unchecked(
+            AsyncDieselConnectionManager::<MyDbConnection>::new("postgres://localhost/unused"),
+        ));
+        let mut handler = ParquetHandler::<WriteSetSize>::new(
+            "bucket".to_string(),
+            "root".to_string(),
+            gap_detector_sender,
+            WriteSetSize::schema(),
+            Duration::from_secs(3600),
+            usize::MAX,
+            "test_processor".to_string(),
+            GcsUploadConfig {
+                verify_uploads: true,
+                ..Default::default()
+            },
+            checkpoint_pool,
+            Compression::LZ4,
+        )
+        .unwrap();
+        let gcs_client = GCSClient::new(GcsClientConfig::default());
+
+        // The processor made 2 structs of version 3 but only sent 1 of them
+        let mut data = write_set_sizes(5);
+        data.retain(|write_set_size| write_set_size.txn_version >= 2);
+        handler
+            .handle(
+                &gcs_client,
+                ParquetDataGeneric {
+                    data,
+                    transaction_version_to_struct_count: struct_counts(&[(2, 1), (3, 2), (4, 1)]),
+                },
+            )
+            .await
+            .unwrap();
+        let err = handler.flush(&gcs_client).await.unwrap_err();
+        assert!(format!("{:#}", err).contains("[(3, 1)]"));
+    }
+
+    #[test]
+    fn test_write_with_zstd() {
+        let compression = compression_config(r#"{"codec": "ZSTD", "level": 3}"#).unwrap();
+        let file = write_file(&write_set_sizes(100), compression);
+
+        let path = std::env::temp_dir().join(format!("zstd_{}.parquet", std::process::id()));
+        std::fs::write(&path, file).unwrap();
+        let reader = SerializedFileReader::new(std::fs::File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let metadata = reader.metadata();
+        assert_eq!(metadata.file_metadata().num_rows(), 100);
+        assert!(matches!(
+            metadata.row_group(0).column(0).compression(),
+            Compression::ZSTD(_)
+        ));
+        let txn_versions = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| row.unwrap().get_long(0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(txn_versions, (0..100).collect::<Vec<_>>());
+    }
+}
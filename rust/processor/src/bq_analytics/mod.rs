@@ -1,21 +1,31 @@
 pub mod gcs_handler;
 pub mod generic_parquet_processor;
+pub mod parquet_checkpoint;
 
 use crate::{
-    bq_analytics::generic_parquet_processor::{
-        GetTimeStamp, HasParquetSchema, HasVersion, NamedTable, ParquetDataGeneric,
-        ParquetHandler as GenericParquetHandler,
+    bq_analytics::{
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{
+            GetTimeStamp, HasParquetSchema, HasVersion, NamedTable, ParquetCompressionConfig,
+            ParquetDataGeneric, ParquetHandler as GenericParquetHandler,
+        },
     },
     gap_detectors::ProcessingResult,
+    utils::{
+        database::ArcDbPool,
+        shutdown::{self, ShutdownSignal},
+    },
     worker::PROCESSOR_SERVICE_TYPE,
 };
 use ahash::AHashMap;
 use allocative::Allocative;
+use anyhow::Context;
+use async_trait::async_trait;
 use google_cloud_storage::{
     client::{Client as GCSClient, ClientConfig as GcsClientConfig},
     http::Error as StorageError,
 };
-use kanal::AsyncSender;
+use kanal::{AsyncReceiver, AsyncSender};
 use parquet::record::RecordWriter;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -23,7 +33,7 @@ use std::{
     sync::Arc,
 };
 use tokio::{io, time::Duration};
-use tracing::{debug, error, info};
+use tracing::{error, info, warn};
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct ParquetProcessingResult {
@@ -42,6 +52,13 @@ pub enum ParquetProcessorError {
     StorageError(StorageError),
     TimeoutError(tokio::time::error::Elapsed),
     IoError(io::Error),
+    /// Upload of a single file still failing once retries are exhausted
+    UploadFailed {
+        table_name: String,
+        size_in_bytes: u64,
+        attempts: usize,
+        source: Box<ParquetProcessorError>,
+    },
     Other(String),
 }
 
@@ -52,6 +69,7 @@ impl std::error::Error for ParquetProcessorError {
             ParquetProcessorError::StorageError(ref err) => Some(err),
             ParquetProcessorError::TimeoutError(ref err) => Some(err),
             ParquetProcessorError::IoError(ref err) => Some(err),
+            ParquetProcessorError::UploadFailed { ref source, .. } => Some(source.as_ref()),
             ParquetProcessorError::Other(_) => None,
         }
     }
@@ -64,6 +82,16 @@ impl Display for ParquetProcessorError {
             ParquetProcessorError::StorageError(ref err) => write!(f, "Storage error: {}", err),
             ParquetProcessorError::TimeoutError(ref err) => write!(f, "Timeout error: {}", err),
             ParquetProcessorError::IoError(ref err) => write!(f, "IO error: {}", err),
+            ParquetProcessorError::UploadFailed {
+                ref table_name,
+                size_in_bytes,
+                attempts,
+                ref source,
+            } => write!(
+                f,
+                "Failed to upload {} byte parquet file for table {} after {} attempts: {}",
+                size_in_bytes, table_name, attempts, source
+            ),
             ParquetProcessorError::Other(ref desc) => write!(f, "Error: {}", desc),
         }
     }
@@ -87,6 +115,7 @@ impl From<parquet::errors::ParquetError> for ParquetProcessorError {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn create_parquet_handler_loop<ParquetType>(
     new_gap_detector_sender: AsyncSender<ProcessingResult>,
     processor_name: &str,
@@ -95,6 +124,9 @@ pub fn create_parquet_handler_loop<ParquetType>(
     parquet_handler_response_channel_size: usize,
     max_buffer_size: usize,
     upload_interval: Duration,
+    gcs_upload_config: GcsUploadConfig,
+    checkpoint_pool: ArcDbPool,
+    compression: ParquetCompressionConfig,
 ) -> AsyncSender<ParquetDataGeneric<ParquetType>>
 where
     ParquetType: GetTimeStamp
@@ -112,9 +144,17 @@ where
         parquet_handler_response_channel_size,
     );
 
-    debug!(
+    info!(
         processor_name = processor_name.clone(),
         service_type = PROCESSOR_SERVICE_TYPE,
+        table_name = ParquetType::TABLE_NAME,
+        upload_timeout_secs = gcs_upload_config.upload_timeout_secs,
+        max_retries = gcs_upload_config.max_retries,
+        initial_retry_delay_ms = gcs_upload_config.initial_retry_delay_ms,
+        retry_jitter = ?gcs_upload_config.retry_jitter,
+        shutdown_flush_timeout_secs = gcs_upload_config.shutdown_flush_timeout_secs,
+        verify_uploads = gcs_upload_config.verify_uploads,
+        compression = ?compression,
         "[Parquet Handler] Starting parquet handler loop",
     );
 
@@ -126,51 +166,291 @@ where
         upload_interval,
         max_buffer_size,
         processor_name.clone(),
+        gcs_upload_config,
+        checkpoint_pool,
+        compression
+            .compression()
+            .expect("Invalid Parquet compression"),
     )
     .expect("Failed to create parquet manager");
 
-    tokio::spawn(async move {
-        let gcs_config = GcsClientConfig::default()
-            .with_auth()
-            .await
-            .expect("Failed to create GCS client config");
-        let gcs_client = Arc::new(GCSClient::new(gcs_config));
-
-        loop {
-            match parquet_receiver.recv().await {
-                Ok(txn_pb_res) => {
-                    let result = parquet_handler.handle(&gcs_client, txn_pb_res).await;
-
-                    match result {
-                        Ok(_) => {
-                            info!(
-                                processor_name = processor_name.clone(),
-                                service_type = PROCESSOR_SERVICE_TYPE,
-                                "[Parquet Handler] Successfully processed structs to buffer",
-                            );
-                        },
-                        Err(e) => {
-                            error!(
-                                processor_name = processor_name.clone(),
-                                service_type = PROCESSOR_SERVICE_TYPE,
-                                "[Parquet Handler] Error processing parquet files: {:?}",
-                                e
-                            );
-                            panic!("Error processing parquet files: {:?}", e);
-                        },
-                    }
-                },
-                Err(e) => {
-                    error!(
-                        processor_name = processor_name.clone(),
-                        service_type = PROCESSOR_SERVICE_TYPE,
-                        "[Parquet Handler] Error receiving parquet files: {:?}",
-                        e
-                    );
-                },
-            }
-        }
+    let flush_timeout = Duration::from_secs(gcs_upload_config.shutdown_flush_timeout_secs);
+    let handler_task = tokio::spawn(async move {
+        let gcs_client = match connect_and_recover(&mut parquet_handler).await {
+            Ok(gcs_client) => gcs_client,
+            Err(e) => {
+                error!(
+                    processor_name = processor_name,
+                    service_type = PROCESSOR_SERVICE_TYPE,
+                    table_name = ParquetType::TABLE_NAME,
+                    error = ?e,
+                    "[Parquet Handler] Error starting parquet handler",
+                );
+                panic!("Error starting parquet handler: {:?}", e);
+            },
+        };
+
+        run_parquet_handler_loop(
+            &processor_name,
+            parquet_receiver,
+            GcsParquetHandler {
+                parquet_handler,
+                gcs_client,
+            },
+            shutdown::shutdown_signal(),
+            flush_timeout,
+        )
+        .await;
     });
+    shutdown::register_flush_task(handler_task);
 
     parquet_sender
 }
+
+/// Creates the GCS client and resolves the uploads a previous run left uncommitted, before the
+/// handler takes any structs.
+async fn connect_and_recover<ParquetType>(
+    parquet_handler: &mut GenericParquetHandler<ParquetType>,
+) -> anyhow::Result<Arc<GCSClient>>
+where
+    ParquetType: Allocative + GetTimeStamp + HasVersion + HasParquetSchema + 'static + NamedTable,
+    for<'a> &'a [ParquetType]: RecordWriter<ParquetType>,
+{
+    let gcs_config = GcsClientConfig::default()
+        .with_auth()
+        .await
+        .context("Failed to create GCS client config")?;
+    let gcs_client = Arc::new(GCSClient::new(gcs_config));
+    parquet_handler
+        .recover_checkpoints(&gcs_client)
+        .await
+        .context("Failed to recover parquet upload checkpoints")?;
+    Ok(gcs_client)
+}
+
+/// The part of a Parquet handler the handler loop drives
+#[async_trait]
+pub trait BufferedParquetHandler<ParquetData: Send>: Send {
+    const TABLE_NAME: &'static str;
+
+    async fn handle(&mut self, data: ParquetData) -> anyhow::Result<()>;
+
+    /// Uploads whatever is buffered, returning the number of structs uploaded
+    async fn flush(&mut self) -> anyhow::Result<usize>;
+}
+
+struct GcsParquetHandler<ParquetType>
+where
+    ParquetType: NamedTable + HasVersion + HasParquetSchema + 'static + Allocative,
+    for<'a> &'a [ParquetType]: RecordWriter<ParquetType>,
+{
+    parquet_handler: GenericParquetHandler<ParquetType>,
+    gcs_client: Arc<GCSClient>,
+}
+
+#[async_trait]
+impl<ParquetType> BufferedParquetHandler<ParquetDataGeneric<ParquetType>>
+    for GcsParquetHandler<ParquetType>
+where
+    ParquetType: GetTimeStamp
+        + HasVersion
+        + HasParquetSchema
+        + NamedTable
+        + Send
+        + Sync
+        + 'static
+        + Allocative,
+    for<'a> &'a [ParquetType]: RecordWriter<ParquetType>,
+{
+    const TABLE_NAME: &'static str = ParquetType::TABLE_NAME;
+
+    async fn handle(&mut self, data: ParquetDataGeneric<ParquetType>) -> anyhow::Result<()> {
+        self.parquet_handler.handle(&self.gcs_client, data).await
+    }
+
+    async fn flush(&mut self) -> anyhow::Result<usize> {
+        self.parquet_handler.flush(&self.gcs_client).await
+    }
+}
+
+/// Hands the structs received to the handler until `shutdown` fires or every sender is gone.
+/// Then it takes the structs still queued and uploads all it has buffered, giving up after
+/// `flush_timeout`. Only uploaded structs are reported to the gap detector, so progress never
+/// covers ones that didn't make it.
+pub async fn run_parquet_handler_loop<ParquetData, Handler>(
+    processor_name: &str,
+    parquet_receiver: AsyncReceiver<ParquetData>,
+    mut handler: Handler,
+    mut shutdown: ShutdownSignal,
+    flush_timeout: Duration,
+) where
+    ParquetData: Send,
+    Handler: BufferedParquetHandler<ParquetData>,
+{
+    loop {
+        let received = tokio::select! {
+            received = parquet_receiver.recv() => received,
+            _ = shutdown.triggered() => break,
+        };
+        match received {
+            Ok(txn_pb_res) => {
+                let result = handler.handle(txn_pb_res).await;
+
+                match result {
+                    Ok(_) => {
+                        info!(
+                            processor_name = processor_name,
+                            service_type = PROCESSOR_SERVICE_TYPE,
+                            "[Parquet Handler] Successfully processed structs to buffer",
+                        );
+                    },
+                    Err(e) => {
+                        error!(
+                            processor_name = processor_name,
+                            service_type = PROCESSOR_SERVICE_TYPE,
+                            "[Parquet Handler] Error processing parquet files: {:?}",
+                            e
+                        );
+                        panic!("Error processing parquet files: {:?}", e);
+                    },
+                }
+            },
+            Err(e) => {
+                info!(
+                    processor_name = processor_name,
+                    service_type = PROCESSOR_SERVICE_TYPE,
+                    table_name = Handler::TABLE_NAME,
+                    error = ?e,
+                    "[Parquet Handler] Parquet channel has been closed",
+                );
+                break;
+            },
+        }
+    }
+
+    info!(
+        processor_name = processor_name,
+        service_type = PROCESSOR_SERVICE_TYPE,
+        table_name = Handler::TABLE_NAME,
+        flush_timeout_secs = flush_timeout.as_secs_f64(),
+        "[Parquet Handler] Flushing buffer before exiting",
+    );
+    let flush = async {
+        while let Ok(Some(txn_pb_res)) = parquet_receiver.try_recv() {
+            handler.handle(txn_pb_res).await?;
+        }
+        handler.flush().await
+    };
+    match tokio::time::timeout(flush_timeout, flush).await {
+        Ok(Ok(num_structs)) => {
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                table_name = Handler::TABLE_NAME,
+                num_structs,
+                "[Parquet Handler] Flushed buffer",
+            );
+        },
+        Ok(Err(e)) => {
+            error!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                table_name = Handler::TABLE_NAME,
+                error = ?e,
+                "[Parquet Handler] Failed to flush buffer, the structs not uploaded will be \
+                 processed again after restart",
+            );
+        },
+        Err(_) => {
+            warn!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                table_name = Handler::TABLE_NAME,
+                flush_timeout_secs = flush_timeout.as_secs_f64(),
+                "[Parquet Handler] Timed out flushing buffer, the structs not uploaded will be \
+                 processed again after restart",
+            );
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::shutdown::ShutdownTrigger;
+    use std::sync::Mutex;
+
+    /// Buffers the numbers it's handed until flushed, then records them as uploaded
+    #[derive(Default)]
+    struct MockHandler {
+        buffer: Vec<u64>,
+        uploaded: Arc<Mutex<Vec<u64>>>,
+        flush_delay: Duration,
+    }
+
+    #[async_trait]
+    impl BufferedParquetHandler<Vec<u64>> for MockHandler {
+        const TABLE_NAME: &'static str = "mock";
+
+        async fn handle(&mut self, data: Vec<u64>) -> anyhow::Result<()> {
+            self.buffer.extend(data);
+            Ok(())
+        }
+
+        async fn flush(&mut self) -> anyhow::Result<usize> {
+            tokio::time::sleep(self.flush_delay).await;
+            let buffer = std::mem::take(&mut self.buffer);
+            let num_structs = buffer.len();
+            self.uploaded.lock().unwrap().extend(buffer);
+            Ok(num_structs)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_partial_buffer_uploaded_on_shutdown() {
+        let (sender, receiver) = kanal::bounded_async(10);
+        let uploaded = Arc::new(Mutex::new(vec![]));
+        let handler = MockHandler {
+            uploaded: uploaded.clone(),
+            ..Default::default()
+        };
+        let shutdown = ShutdownTrigger::new();
+        let handler_task = tokio::spawn(run_parquet_handler_loop(
+            "test",
+            receiver,
+            handler,
+            shutdown.signal(),
+            Duration::from_secs(10),
+        ));
+
+        sender.send(vec![1, 2]).await.unwrap();
+        sender.send(vec![3]).await.unwrap();
+        shutdown.trigger();
+        handler_task.await.unwrap();
+        assert_eq!(*uploaded.lock().unwrap(), vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_flush_on_shutdown_times_out() {
+        let (sender, receiver) = kanal::bounded_async(10);
+        let uploaded = Arc::new(Mutex::new(vec![]));
+        let handler = MockHandler {
+            uploaded: uploaded.clone(),
+            flush_delay: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let shutdown = ShutdownTrigger::new();
+        sender.send(vec![1]).await.unwrap();
+        shutdown.trigger();
+
+        run_parquet_handler_loop(
+            "test",
+            receiver,
+            handler,
+            shutdown.signal(),
+            Duration::from_millis(10),
+        )
+        .await;
+        assert!(uploaded.lock().unwrap().is_empty());
+    }
+}
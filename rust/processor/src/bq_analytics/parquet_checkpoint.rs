@@ -0,0 +1,136 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Two-phase checkpoints that make Parquet uploads effectively exactly-once. Processing resumes
+//! from the slowest table's progress, so on restart every other table replays structs it already
+//! uploaded; the latest committed checkpoint tells the handler which ones to drop.
+
+use crate::db::postgres::models::parquet_upload_checkpoint::{
+    ParquetUploadCheckpoint, CHECKPOINT_STATUS_COMMITTED,
+};
+use ahash::AHashSet;
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CheckpointRecovery {
+    /// Uploads that were interrupted after the file landed in GCS
+    pub to_commit: Vec<ParquetUploadCheckpoint>,
+    /// Uploads that never made it to GCS, the structs will be uploaded again
+    pub to_delete: Vec<ParquetUploadCheckpoint>,
+    pub last_committed: Option<ParquetUploadCheckpoint>,
+}
+
+/// Resolves the checkpoints of a table left behind by a previous run. `uploaded_files` are the
+/// files of uncommitted checkpoints that turned out to exist in GCS.
+pub fn reconcile_checkpoints(
+    checkpoints: Vec<ParquetUploadCheckpoint>,
+    uploaded_files: &AHashSet<String>,
+) -> CheckpointRecovery {
+    let mut recovery = CheckpointRecovery::default();
+    for mut checkpoint in checkpoints {
+        if !checkpoint.is_committed() {
+            if !uploaded_files.contains(&checkpoint.file_path) {
+                recovery.to_delete.push(checkpoint);
+                continue;
+            }
+            checkpoint.status = CHECKPOINT_STATUS_COMMITTED.to_string();
+            recovery.to_commit.push(checkpoint.clone());
+        }
+        let is_latest = recovery
+            .last_committed
+            .as_ref()
+            .is_none_or(|last| checkpoint.end_version >= last.end_version);
+        if is_latest {
+            recovery.last_committed = Some(checkpoint);
+        }
+    }
+    recovery
+}
+
+/// Everything up to and including the last committed checkpoint. Files of a table are uploaded in
+/// order, so all versions before its end version are fully uploaded, while the end version itself
+/// may continue in the next file.
+#[derive(Debug)]
+pub struct UploadedRange {
+    end_version: i64,
+    remaining_end_version_structs: i64,
+}
+
+impl UploadedRange {
+    pub fn new(checkpoint: &ParquetUploadCheckpoint) -> Self {
+        Self {
+            end_version: checkpoint.end_version,
+            remaining_end_version_structs: checkpoint.end_version_struct_count,
+        }
+    }
+
+    /// Whether the struct was already uploaded. Structs must be passed in in processing order.
+    pub fn contains(&mut self, version: i64) -> bool {
+        if version < self.end_version {
+            return true;
+        }
+        if version == self.end_version && self.remaining_end_version_structs > 0 {
+            self.remaining_end_version_structs -= 1;
+            return true;
+        }
+        false
+    }
+
+    /// Once processing is past the range there is nothing left to drop
+    pub fn is_passed(&self, version: i64) -> bool {
+        version > self.end_version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::postgres::models::parquet_upload_checkpoint::CHECKPOINT_STATUS_UPLOADING;
+
+    fn checkpoint(
+        start_version: i64,
+        end_version: i64,
+        end_version_struct_count: i64,
+        status: &str,
+    ) -> ParquetUploadCheckpoint {
+        ParquetUploadCheckpoint {
+            processor: "parquet_default_processor".to_string(),
+            table_name: "transactions".to_string(),
+            start_version,
+            end_version,
+            end_version_struct_count,
+            file_path: format!("transactions/{}.parquet", start_version),
+            status: status.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_recover_after_crash_between_upload_and_commit() {
+        let committed = checkpoint(0, 99, 1, CHECKPOINT_STATUS_COMMITTED);
+        // Crashed after this file was uploaded but before it was marked committed
+        let uploaded = checkpoint(99, 150, 2, CHECKPOINT_STATUS_UPLOADING);
+        // Crashed before this file made it to GCS
+        let lost = checkpoint(151, 200, 1, CHECKPOINT_STATUS_UPLOADING);
+        let uploaded_files = [uploaded.file_path.clone()].into_iter().collect();
+
+        let recovery = reconcile_checkpoints(
+            vec![committed, uploaded.clone(), lost.clone()],
+            &uploaded_files,
+        );
+        let mut expected_commit = uploaded;
+        expected_commit.status = CHECKPOINT_STATUS_COMMITTED.to_string();
+        assert_eq!(recovery.to_commit, vec![expected_commit.clone()]);
+        assert_eq!(recovery.to_delete, vec![lost]);
+        assert_eq!(recovery.last_committed, Some(expected_commit));
+
+        // Processing restarts from the last version the gap detector saw complete, replaying
+        // 3 structs for version 150 of which the first 2 were in the uploaded file
+        let mut range = UploadedRange::new(recovery.last_committed.as_ref().unwrap());
+        let replayed = [120, 149, 150, 150, 150, 151];
+        let new_structs = replayed
+            .into_iter()
+            .filter(|version| !range.contains(*version))
+            .collect::<Vec<_>>();
+        assert_eq!(new_structs, vec![150, 151]);
+        assert!(range.is_passed(151));
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Filling in one column of rows already written, e.g. one added since they were, without
+//! reprocessing the range. The transactions of the range are streamed like for a replay, and a
+//! closure computes just the column of the rows they wrote, which is then set with `UPDATE`s
+//! matching the rows by their keys. Rows that aren't in the table are skipped rather than
+//! inserted, and the other columns, `inserted_at` included, are left as they are.
+
+use crate::{
+    replay::ReplayStream,
+    utils::database::{table_name_in_db, ArcDbPool},
+};
+use anyhow::{Context, Result};
+use aptos_protos::transaction::v1::Transaction;
+use diesel::{sql_query, sql_types::Jsonb};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::info;
+
+/// Column backfills that can be run with `column_backfill`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColumnBackfillTarget {
+    /// `events.indexed_type`, see `events_processor::indexed_type_backfill`
+    EventsIndexedType,
+}
+
+/// Sets `column` of the rows of `table` to what `compute` returns for them. `compute` returns a
+/// row for each row to update, serialized with a field per key column and one for `column`.
+pub struct ColumnBackfill<F> {
+    table: &'static str,
+    /// Columns identifying a row, with their SQL types
+    key_columns: &'static [(&'static str, &'static str)],
+    /// Column to backfill, with its SQL type
+    column: (&'static str, &'static str),
+    compute: F,
+}
+
+impl<F> ColumnBackfill<F> {
+    pub fn new(
+        table: &'static str,
+        key_columns: &'static [(&'static str, &'static str)],
+        column: (&'static str, &'static str),
+        compute: F,
+    ) -> Self {
+        Self {
+            table,
+            key_columns,
+            column,
+            compute,
+        }
+    }
+
+    /// Updates the rows from the JSON array bound to `$1`, only where the column changes.
+    fn update_query(&self) -> String {
+        let column = self.column.0;
+        let record_columns = self
+            .key_columns
+            .iter()
+            .chain(std::iter::once(&self.column))
+            .map(|(name, sql_type)| format!("\"{name}\" {sql_type}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        let key_matches = self
+            .key_columns
+            .iter()
+            .map(|(name, _)| format!("t.\"{name}\" = u.\"{name}\""))
+            .collect::<Vec<_>>()
+            .join(" AND ");
+        format!(
+            "UPDATE \"{table}\" AS t SET \"{column}\" = u.\"{column}\" \
+             FROM jsonb_to_recordset($1) AS u({record_columns}) \
+             WHERE {key_matches} AND t.\"{column}\" IS DISTINCT FROM u.\"{column}\"",
+            table = table_name_in_db(self.table),
+        )
+    }
+
+    /// Backfills the column of the rows written by `transactions`, returning how many changed.
+    pub async fn backfill_batch<R>(
+        &self,
+        pool: ArcDbPool,
+        transactions: &[Transaction],
+    ) -> Result<usize>
+    where
+        F: Fn(&[Transaction]) -> Vec<R>,
+        R: Serialize,
+    {
+        let rows = (self.compute)(transactions);
+        if rows.is_empty() {
+            return Ok(0);
+        }
+        let mut conn = pool.get().await?;
+        sql_query(self.update_query())
+            .bind::<Jsonb, _>(serde_json::to_value(rows)?)
+            .execute(&mut conn)
+            .await
+            .with_context(|| format!("Failed to backfill {}.{}", self.table, self.column.0))
+    }
+
+    /// Backfills the column over `[start_version, end_version]`, as streamed by `stream`.
+    /// Returns how many rows changed.
+    pub async fn run<R>(
+        &self,
+        pool: ArcDbPool,
+        stream: ReplayStream,
+        start_version: u64,
+        end_version: u64,
+    ) -> Result<u64>
+    where
+        F: Fn(&[Transaction]) -> Vec<R>,
+        R: Serialize,
+    {
+        info!(
+            table = self.table,
+            column = self.column.0,
+            start_version,
+            end_version,
+            "[Backfill] Backfilling column"
+        );
+        let mut next_version = start_version;
+        let mut num_rows_updated = 0;
+        let res = loop {
+            let Ok(transactions_pb) = stream.receiver.recv().await else {
+                if next_version <= end_version {
+                    break Err(anyhow::anyhow!(
+                        "Stream ended before version {}",
+                        next_version
+                    ));
+                }
+                break Ok(num_rows_updated);
+            };
+            stream
+                .channel_byte_limiter
+                .release(transactions_pb.size_in_bytes);
+            let transactions_pb = Arc::unwrap_or_clone(transactions_pb);
+            match self
+                .backfill_batch(pool.clone(), &transactions_pb.transactions)
+                .await
+            {
+                Ok(num_rows) => num_rows_updated += num_rows as u64,
+                Err(e) => break Err(e),
+            }
+            next_version = transactions_pb.end_version + 1;
+        };
+        stream.fetcher_task.abort();
+        let num_rows_updated = res?;
+        info!(
+            table = self.table,
+            column = self.column.0,
+            start_version,
+            end_version,
+            num_rows_updated,
+            "[Backfill] Finished backfilling column"
+        );
+        Ok(num_rows_updated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_update_query() {
+        let backfill = ColumnBackfill::new(
+            "events",
+            &[("transaction_version", "BIGINT"), ("event_index", "BIGINT")],
+            ("indexed_type", "VARCHAR(300)"),
+            |_: &[Transaction]| Vec::<()>::new(),
+        );
+        assert_eq!(
+            backfill.update_query(),
+            "UPDATE \"events\" AS t SET \"indexed_type\" = u.\"indexed_type\" FROM \
+             jsonb_to_recordset($1) AS u(\"transaction_version\" BIGINT, \"event_index\" BIGINT, \
+             \"indexed_type\" VARCHAR(300)) WHERE t.\"transaction_version\" = \
+             u.\"transaction_version\" AND t.\"event_index\" = u.\"event_index\" AND \
+             t.\"indexed_type\" IS DISTINCT FROM u.\"indexed_type\""
+        );
+    }
+}
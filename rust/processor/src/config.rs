@@ -2,14 +2,40 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
-    gap_detectors::DEFAULT_GAP_DETECTION_BATCH_SIZE, processors::ProcessorConfig,
-    transaction_filter::TransactionFilter, worker::Worker,
+    bq_analytics::gcs_handler::ParquetResumeConfig,
+    column_backfill::ColumnBackfillTarget,
+    gap_detectors::{DEFAULT_GAP_DETECTION_BATCH_SIZE, DEFAULT_PROGRESS_COMMIT_INTERVAL_SECS},
+    grpc_stream::get_chain_id,
+    parallel_fetch::ParallelFetchConfig,
+    parquet_file_stream::ParquetFileSourceConfig,
+    processors::ProcessorConfig,
+    self_test::SelfTestConfig,
+    transaction_filter::TransactionFilter,
+    utils::{
+        counters::set_metrics_prefix,
+        database::{
+            set_slow_query_threshold, set_table_prefix, ConflictStrategy, DbConnectionConfig,
+            DEFAULT_SLOW_QUERY_THRESHOLD_MS,
+        },
+        db_circuit_breaker::{set_db_circuit_breaker, DbCircuitBreakerConfig},
+        error_budget::ErrorBudgetConfig,
+        live_status::TpsReportingConfig,
+        progress_lease::ProgressLeaseConfig,
+        retention::RetentionConfig,
+        timestamp_to_version::{get_chain_tip_version, resolve_starting_version},
+        tip_lag::{TipLag, TipLagThrottle},
+        transaction_tee::transaction_tee,
+        util::{default_missing_timestamp_sentinel, set_missing_timestamp_sentinel},
+    },
+    worker::{OnChainMismatch, Worker, BUFFER_SIZE},
 };
 use ahash::AHashMap;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 use server_framework::RunnableConfig;
-use std::{collections::HashSet, time::Duration};
+use std::{collections::HashSet, path::PathBuf, time::Duration};
+use tracing::info;
 use url::Url;
 
 pub const QUERY_DEFAULT_RETRIES: u32 = 5;
@@ -20,6 +46,9 @@ pub const QUERY_DEFAULT_RETRY_DELAY_MS: u64 = 500;
 pub struct IndexerGrpcProcessorConfig {
     pub processor_config: ProcessorConfig,
     pub postgres_connection_string: String,
+    // Optional read replica for read-only queries. Writes always go to the primary
+    #[serde(default)]
+    pub read_replica_connection_string: Option<String>,
     // TODO: Add TLS support.
     pub indexer_grpc_data_service_address: Url,
     #[serde(flatten)]
@@ -27,6 +56,16 @@ pub struct IndexerGrpcProcessorConfig {
     pub auth_token: String,
     // Version to start indexing from
     pub starting_version: Option<u64>,
+    // Start from the first version at or after this time (RFC3339) instead of a version.
+    // Resolved through `fullnode_rest_api_url`
+    #[serde(default)]
+    pub starting_timestamp: Option<DateTime<Utc>>,
+    // Start from the latest version on chain, resolved through `fullnode_rest_api_url`, instead
+    // of where the processor left off. For processors that don't need history
+    #[serde(default)]
+    pub start_from_tip: bool,
+    #[serde(default)]
+    pub fullnode_rest_api_url: Option<Url>,
     // Version to end indexing at
     pub ending_version: Option<u64>,
     // Number of tasks waiting to pull transaction batches from the channel and process them
@@ -39,25 +78,175 @@ pub struct IndexerGrpcProcessorConfig {
     // Maximum number of batches "missing" before we assume we have an issue with gaps and abort
     #[serde(default = "IndexerGrpcProcessorConfig::default_gap_detection_batch_size")]
     pub parquet_gap_detection_batch_size: u64,
+    // Persist the gap detector's pending gaps to the DB so they survive restarts
+    #[serde(default)]
+    pub persist_gap_detector_state: bool,
+    // Seconds between writes of the last processed version to `processor_status`
+    #[serde(default = "IndexerGrpcProcessorConfig::default_progress_commit_interval_secs")]
+    pub progress_commit_interval_secs: u64,
+    // Also write it once this many versions have been processed since the last write
+    #[serde(default)]
+    pub progress_commit_interval_versions: Option<u64>,
     // Number of protobuff transactions to send per chunk to the processor tasks
     #[serde(default = "IndexerGrpcProcessorConfig::default_pb_channel_txn_chunk_size")]
     pub pb_channel_txn_chunk_size: usize,
     // Number of rows to insert, per chunk, for each DB table. Default per table is ~32,768 (2**16/2)
     #[serde(default = "AHashMap::new")]
     pub per_table_chunk_sizes: AHashMap<String, usize>,
+    // ON CONFLICT behavior for each DB table, `do_nothing` or `do_update`. Only tables with
+    // both queries honor it; events and user_transactions default to `do_nothing`
+    #[serde(default = "AHashMap::new")]
+    pub per_table_conflict_strategies: AHashMap<String, ConflictStrategy>,
     pub enable_verbose_logging: Option<bool>,
+    // Log any single DB statement that takes longer than this, with its table and row count
+    #[serde(default = "IndexerGrpcProcessorConfig::default_slow_query_threshold_ms")]
+    pub slow_query_threshold_ms: u64,
+    // Pause DB writes with backoff after consecutive failed inserts, instead of failing the batch
+    #[serde(default)]
+    pub db_circuit_breaker: Option<DbCircuitBreakerConfig>,
+    // TCP keepalive and session timeouts set on every DB connection
+    #[serde(default)]
+    pub db_connection: DbConnectionConfig,
 
     #[serde(default = "IndexerGrpcProcessorConfig::default_grpc_response_item_timeout_in_secs")]
     pub grpc_response_item_timeout_in_secs: u64,
 
     #[serde(default)]
     pub transaction_filter: TransactionFilter,
+    // Only update latency gauges and histograms every Nth batch. Counters are always exact.
+    #[serde(default = "IndexerGrpcProcessorConfig::default_metrics_sample_rate")]
+    pub metrics_sample_rate: u64,
+    // Namespace prepended to all metric names, e.g. `dapp_a` gives `dapp_a_indexer_processor_*`
+    #[serde(default)]
+    pub metrics_prefix: Option<String>,
+    // Maximum bytes of transactions buffered between the fetcher and processor tasks. When
+    // reached, the fetcher stops pulling from the stream until the buffer drains
+    #[serde(default)]
+    pub max_buffered_transaction_bytes: Option<u64>,
+    // Maximum bytes of transactions being processed at once across the processor tasks. A task
+    // waits for room before processing its batch
+    #[serde(default)]
+    pub max_in_flight_processing_bytes: Option<u64>,
+    // Read transactions from local Parquet files instead of the GRPC stream
+    #[serde(default)]
+    pub parquet_file_source: Option<ParquetFileSourceConfig>,
     // String vector for deprecated tables to skip db writes
     #[serde(default)]
     pub deprecated_tables: HashSet<String>,
+    // What to do if the DB has data from a different chain: panic, halt or error
+    #[serde(default)]
+    pub on_chain_mismatch: OnChainMismatch,
+    // Other processors to run in this process off the same stream, each with its own progress
+    #[serde(default)]
+    pub multiplexed_processor_configs: Vec<ProcessorConfig>,
+    // Number of batches each multiplexed processor can buffer. Bounds how far ahead of the
+    // slowest processor the others can get
+    #[serde(default = "IndexerGrpcProcessorConfig::default_multiplexed_buffer_size")]
+    pub multiplexed_buffer_size: usize,
+    // Resume a Parquet backfill after the highest version already written to GCS
+    #[serde(default)]
+    pub parquet_resume: Option<ParquetResumeConfig>,
+    // Compare parsing of known transactions against golden files before starting
+    #[serde(default)]
+    pub self_test: Option<SelfTestConfig>,
+    // Floor on the time TPS is computed over and ceiling on the reported TPS
+    #[serde(default)]
+    pub tps_reporting: TpsReportingConfig,
+    // Seconds between heartbeats reporting the stream tip, even when no transactions arrive
+    #[serde(default)]
+    pub heartbeat_interval_secs: Option<u64>,
+    // Prepended to the names of the tables written, e.g. `dappa_` writes `dappa_events`. Only
+    // supported by the events and user transaction processors
+    #[serde(default)]
+    pub table_prefix: Option<String>,
+    // Hold a lease on the processor's progress, so a hot standby with the same config but another
+    // holder ID only takes over once this process stops renewing it
+    #[serde(default)]
+    pub progress_lease: Option<ProgressLeaseConfig>,
+    // Drop the parts of each transaction the processors don't read as soon as it's fetched from
+    // GRPC, to save memory. Only the events processor reads less than the whole transaction
+    #[serde(default)]
+    pub strip_unused_transaction_fields: bool,
+    // Batches taking at least this long are attached to the batch processing time histogram as
+    // OpenMetrics exemplars
+    #[serde(default)]
+    pub slow_batch_exemplar_threshold_secs: Option<f64>,
+    // Serve `/replay` on the health check port, to reprocess a version range on demand without
+    // touching the processor's progress
+    #[serde(default)]
+    pub enable_replays: bool,
+    // Write a JSON report of the versions processed, rows written per table and any skipped
+    // ranges here once the run reaches `ending_version`
+    #[serde(default)]
+    pub progress_report_path: Option<PathBuf>,
+    // Fill in the block height of transactions the stream didn't set one for from the most
+    // recent block metadata transaction before them
+    #[serde(default)]
+    pub compute_block_heights: bool,
+    // Check that each user transaction's signing public keys hash to its sender's authentication
+    // key and count the ones that don't. Off by default as it hashes every sender's public keys
+    #[serde(default)]
+    pub check_authentication_keys: bool,
+    // Periodically delete the rows of the given tables that are older than their retention
+    #[serde(default)]
+    pub retention: Option<RetentionConfig>,
+    // Write batches strictly in version order, logging each one before it's written, so a restart
+    // resumes exactly where writing stopped
+    #[serde(default)]
+    pub write_ahead_log: bool,
+    // Threads to parse transactions in parallel on, instead of one per core, to cap the CPU a
+    // processor takes on a shared host
+    #[serde(default)]
+    pub rayon_num_threads: Option<usize>,
+    // Fail a batch whose processing takes longer than this, rather than stalling on it forever
+    #[serde(default)]
+    pub batch_processing_timeout_secs: Option<u64>,
+    // Stay this far behind the chain tip, holding the stream back when it gets closer
+    #[serde(default)]
+    pub tip_lag: Option<TipLag>,
+    // Fetch a bounded range over several GRPC streams at once, merged back in version order
+    #[serde(default)]
+    pub parallel_fetch: Option<ParallelFetchConfig>,
+    // Retry failed batches instead of panicking, until too many fail within a rolling window
+    #[serde(default)]
+    pub error_budget: Option<ErrorBudgetConfig>,
+    // Instead of processing, backfill one column of rows already written over
+    // [starting_version, ending_version], see `column_backfill`
+    #[serde(default)]
+    pub column_backfill: Option<ColumnBackfillTarget>,
+    // Stored as the timestamp of transactions without one, i.e. genesis, see
+    // `default_missing_timestamp_sentinel`
+    #[serde(default = "default_missing_timestamp_sentinel")]
+    pub missing_timestamp_sentinel: NaiveDateTime,
 }
 
 impl IndexerGrpcProcessorConfig {
+    /// Prefixed tables are only written through `execute_with_better_error`, so processors that
+    /// read back their own tables through the generated schema can't be prefixed.
+    fn check_table_prefix_supported(&self) -> Result<()> {
+        let processor_configs =
+            std::iter::once(&self.processor_config).chain(&self.multiplexed_processor_configs);
+        for processor_config in processor_configs {
+            match processor_config {
+                ProcessorConfig::EventsProcessor(config) if config.partition_interval.is_some() => {
+                    bail!("table_prefix doesn't support a partitioned events table")
+                },
+                ProcessorConfig::UserTransactionProcessor(config)
+                    if config.skip_existing_versions =>
+                {
+                    bail!("skip_existing_versions reads transactions, which can't be prefixed")
+                },
+                ProcessorConfig::EventsProcessor(_)
+                | ProcessorConfig::UserTransactionProcessor(_) => {},
+                _ => bail!(
+                    "table_prefix isn't supported by {}",
+                    processor_config.name()
+                ),
+            }
+        }
+        Ok(())
+    }
+
     pub const fn default_gap_detection_batch_size() -> u64 {
         DEFAULT_GAP_DETECTION_BATCH_SIZE
     }
@@ -76,6 +265,23 @@ impl IndexerGrpcProcessorConfig {
         100_000
     }
 
+    /// Update sampled metrics on every batch by default.
+    pub const fn default_metrics_sample_rate() -> u64 {
+        1
+    }
+
+    pub const fn default_slow_query_threshold_ms() -> u64 {
+        DEFAULT_SLOW_QUERY_THRESHOLD_MS
+    }
+
+    pub const fn default_progress_commit_interval_secs() -> u64 {
+        DEFAULT_PROGRESS_COMMIT_INTERVAL_SECS
+    }
+
+    pub const fn default_multiplexed_buffer_size() -> usize {
+        BUFFER_SIZE
+    }
+
     /// Default timeout for grpc response item in seconds. Defaults to 60 seconds.
     pub const fn default_grpc_response_item_timeout_in_secs() -> u64 {
         60
@@ -85,29 +291,176 @@ impl IndexerGrpcProcessorConfig {
 #[async_trait::async_trait]
 impl RunnableConfig for IndexerGrpcProcessorConfig {
     async fn run(&self) -> Result<()> {
+        set_metrics_prefix(self.metrics_prefix.clone())?;
+        if self.table_prefix.is_some() {
+            self.check_table_prefix_supported()?;
+        }
+        set_table_prefix(self.table_prefix.clone())?;
+        self.transaction_filter.validate()?;
+        if let Some(retention) = &self.retention {
+            retention.validate()?;
+        }
+        if let Some(parallel_fetch) = &self.parallel_fetch {
+            parallel_fetch.validate()?;
+            if self.ending_version.is_none()
+                || self.parquet_file_source.is_some()
+                || self.compute_block_heights
+            {
+                bail!(
+                    "parallel_fetch requires ending_version, and isn't supported with \
+                     parquet_file_source or compute_block_heights"
+                );
+            }
+            // The tee is registered before the server starts, so it's known by now
+            if transaction_tee().is_some() {
+                bail!("parallel_fetch isn't supported with a transaction tee registered");
+            }
+        }
+        if let Some(error_budget) = &self.error_budget {
+            error_budget.validate()?;
+        }
+        if self.column_backfill.is_some()
+            && ((self.starting_version.is_none() && self.starting_timestamp.is_none())
+                || self.ending_version.is_none())
+        {
+            bail!("column_backfill requires a starting version or timestamp, and ending_version");
+        }
+        if let Some(compression) = self.processor_config.parquet_compression() {
+            compression.compression()?;
+        }
+        // Replays are processed without a gap detector, which Parquet output, including a
+        // `parquet_sink` next to Postgres, needs to report its progress
+        if self.enable_replays && self.processor_config.is_parquet_processor() {
+            bail!("Replays are not supported by Parquet processors or with parquet_sink");
+        }
+        if self.write_ahead_log
+            && (self.processor_config.is_parquet_processor()
+                || !self.multiplexed_processor_configs.is_empty()
+                || self.enable_replays)
+        {
+            bail!(
+                "write_ahead_log isn't supported by Parquet processors, with multiplexing or with \
+                 replays"
+            );
+        }
+        if let Some(self_test) = &self.self_test {
+            self_test
+                .run(&self.processor_config)
+                .context("Self test failed, refusing to start")?;
+        }
+        set_slow_query_threshold(Duration::from_millis(self.slow_query_threshold_ms));
+        set_missing_timestamp_sentinel(self.missing_timestamp_sentinel)?;
+        if let Some(db_circuit_breaker) = &self.db_circuit_breaker {
+            set_db_circuit_breaker(db_circuit_breaker.clone());
+        }
+        if self.start_from_tip
+            && (self.starting_version.is_some()
+                || self.starting_timestamp.is_some()
+                || self.parquet_resume.is_some())
+        {
+            bail!(
+                "start_from_tip can't be combined with starting_version, starting_timestamp or \
+                 parquet_resume"
+            );
+        }
+        let starting_version = match self.starting_timestamp {
+            None if self.start_from_tip => {
+                let fullnode_rest_api_url = self
+                    .fullnode_rest_api_url
+                    .as_ref()
+                    .context("start_from_tip requires fullnode_rest_api_url")?;
+                // The tip is only a version of the stream's chain if the fullnode is on it too
+                let chain_id = match &self.parquet_file_source {
+                    Some(parquet_file_source) => parquet_file_source.chain_id,
+                    None => {
+                        get_chain_id(
+                            self.indexer_grpc_data_service_address.clone(),
+                            self.grpc_http2_config.grpc_http2_ping_interval_in_secs(),
+                            self.grpc_http2_config.grpc_http2_ping_timeout_in_secs(),
+                            self.grpc_http2_config.grpc_connection_timeout_secs(),
+                            self.auth_token.clone(),
+                            self.processor_config.name().to_string(),
+                        )
+                        .await
+                    },
+                };
+                let tip_version = get_chain_tip_version(fullnode_rest_api_url, chain_id).await?;
+                info!(
+                    processor_name = self.processor_config.name(),
+                    tip_version = tip_version,
+                    "[Parser] Starting from the chain tip, ignoring the stored progress",
+                );
+                Some(tip_version)
+            },
+            Some(starting_timestamp) => {
+                if self.starting_version.is_some() {
+                    bail!("Only one of starting_version and starting_timestamp can be set");
+                }
+                let fullnode_rest_api_url = self
+                    .fullnode_rest_api_url
+                    .as_ref()
+                    .context("starting_timestamp requires fullnode_rest_api_url")?;
+                Some(resolve_starting_version(fullnode_rest_api_url, starting_timestamp).await?)
+            },
+            None => self.starting_version,
+        };
+        let tip_lag = self
+            .tip_lag
+            .map(|tip_lag| TipLagThrottle::new(tip_lag, self.fullnode_rest_api_url.clone()))
+            .transpose()?;
         let mut worker = Worker::new(
             self.processor_config.clone(),
             self.postgres_connection_string.clone(),
+            self.read_replica_connection_string.clone(),
             self.indexer_grpc_data_service_address.clone(),
             self.grpc_http2_config.clone(),
             self.auth_token.clone(),
-            self.starting_version,
+            starting_version,
             self.ending_version,
             self.number_concurrent_processing_tasks,
             self.db_pool_size,
             self.gap_detection_batch_size,
             self.parquet_gap_detection_batch_size,
+            self.persist_gap_detector_state,
+            self.progress_commit_interval_secs,
+            self.progress_commit_interval_versions,
             self.pb_channel_txn_chunk_size,
             self.per_table_chunk_sizes.clone(),
+            self.per_table_conflict_strategies.clone(),
             self.enable_verbose_logging,
             self.transaction_filter.clone(),
             self.grpc_response_item_timeout_in_secs,
             self.deprecated_tables.clone(),
+            self.metrics_sample_rate,
+            self.max_buffered_transaction_bytes,
+            self.parquet_file_source.clone(),
+            self.on_chain_mismatch,
+            self.multiplexed_processor_configs.clone(),
+            self.multiplexed_buffer_size,
+            self.parquet_resume.clone(),
+            self.tps_reporting.clone(),
+            self.heartbeat_interval_secs,
+            self.max_in_flight_processing_bytes,
+            self.progress_lease.clone(),
+            self.strip_unused_transaction_fields,
+            self.slow_batch_exemplar_threshold_secs,
+            self.enable_replays,
+            self.db_connection.clone(),
+            self.progress_report_path.clone(),
+            self.compute_block_heights,
+            self.check_authentication_keys,
+            self.retention.clone(),
+            self.write_ahead_log,
+            self.batch_processing_timeout_secs,
+            tip_lag,
+            self.parallel_fetch.clone(),
+            self.error_budget.clone(),
+            self.column_backfill,
+            self.rayon_num_threads,
         )
         .await
         .context("Failed to build worker")?;
-        worker.run().await;
-        Ok(())
+        worker.run().await
     }
 
     fn get_server_name(&self) -> String {
@@ -120,6 +473,18 @@ impl RunnableConfig for IndexerGrpcProcessorConfig {
             .unwrap_or("unknown");
         before_underscore[..before_underscore.len().min(12)].to_string()
     }
+
+    /// Set once for the whole process by `run`, so several processors in one process must
+    /// agree on them
+    fn process_wide_settings(&self) -> serde_json::Value {
+        serde_json::json!({
+            "metrics_prefix": self.metrics_prefix,
+            "table_prefix": self.table_prefix,
+            "slow_query_threshold_ms": self.slow_query_threshold_ms,
+            "db_circuit_breaker": self.db_circuit_breaker,
+            "missing_timestamp_sentinel": self.missing_timestamp_sentinel,
+        })
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -0,0 +1,30 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Analytics copies of processor tables in ClickHouse. Rows are appended and never updated, so
+//! each table is a `ReplacingMergeTree` ordered by the Postgres primary key: rows written again
+//! when a version is reprocessed replace the earlier ones, keeping the highest `VERSION_COLUMN`
+//! and the last inserted among equal ones. Merges run in the background, so reads that must not
+//! see duplicates use `FINAL`.
+
+pub mod models;
+
+pub trait ClickhouseTable: clickhouse::Row + serde::Serialize + Send + Sync {
+    const TABLE_NAME: &'static str;
+    /// Column definitions, in the order of the struct's fields.
+    const COLUMNS: &'static str;
+    /// Columns rows are deduplicated on, usually the Postgres primary key.
+    const ORDER_BY: &'static str;
+    /// Column deciding which of the duplicates is kept.
+    const VERSION_COLUMN: &'static str;
+
+    fn create_table_query() -> String {
+        format!(
+            "CREATE TABLE IF NOT EXISTS {} ({}) ENGINE = ReplacingMergeTree({}) ORDER BY ({})",
+            Self::TABLE_NAME,
+            Self::COLUMNS,
+            Self::VERSION_COLUMN,
+            Self::ORDER_BY,
+        )
+    }
+}
@@ -0,0 +1,47 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::db::{clickhouse::ClickhouseTable, postgres::models::events_models::events::EventModel};
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Row, Serialize)]
+pub struct ClickhouseEvent {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub sequence_number: i64,
+    pub creation_number: i64,
+    pub account_address: String,
+    pub transaction_block_height: i64,
+    #[serde(rename = "type")]
+    pub type_: String,
+    /// JSON text, since ClickHouse's JSON type is still experimental in older servers
+    pub data: String,
+    pub indexed_type: String,
+}
+
+impl ClickhouseTable for ClickhouseEvent {
+    const TABLE_NAME: &'static str = "events";
+    const COLUMNS: &'static str = "transaction_version Int64, event_index Int64, \
+        sequence_number Int64, creation_number Int64, account_address String, \
+        transaction_block_height Int64, type LowCardinality(String), data String, \
+        indexed_type LowCardinality(String)";
+    const ORDER_BY: &'static str = "transaction_version, event_index";
+    const VERSION_COLUMN: &'static str = "transaction_version";
+}
+
+impl From<&EventModel> for ClickhouseEvent {
+    fn from(event: &EventModel) -> Self {
+        Self {
+            transaction_version: event.transaction_version,
+            event_index: event.event_index,
+            sequence_number: event.sequence_number,
+            creation_number: event.creation_number,
+            account_address: event.account_address.clone(),
+            transaction_block_height: event.transaction_block_height,
+            type_: event.type_.clone(),
+            data: event.data.to_string(),
+            indexed_type: event.indexed_type.clone(),
+        }
+    }
+}
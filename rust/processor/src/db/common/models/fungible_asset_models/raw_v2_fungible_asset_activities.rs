@@ -68,9 +68,12 @@ impl RawFungibleAssetActivity {
         object_aggregated_data_mapping: &ObjectAggregatedDataMapping,
     ) -> anyhow::Result<Option<Self>> {
         let event_type = event.type_str.clone();
-        if let Some(fa_event) =
-            &FungibleAssetEvent::from_event(event_type.as_str(), &event.data, txn_version)?
-        {
+        if let Some(fa_event) = &FungibleAssetEvent::from_event(
+            event_type.as_str(),
+            &event.data,
+            txn_version,
+            event_index,
+        )? {
             let (storage_id, is_frozen, amount) = match fa_event {
                 FungibleAssetEvent::WithdrawEvent(inner) => (
                     standardize_address(&event.key.as_ref().unwrap().account_address),
@@ -7,7 +7,7 @@ use crate::{
     db::common::models::stake_models::stake_utils::StakeEvent,
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        util::{parse_timestamp, standardize_address, u64_to_bigdecimal},
+        util::{parse_transaction_timestamp, standardize_address, u64_to_bigdecimal},
     },
 };
 use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
@@ -53,7 +53,8 @@ impl RawDelegatedStakingActivity {
             TxnData::Validator(txn) => &txn.events,
             _ => return Ok(delegator_activities),
         };
-        let block_timestamp = parse_timestamp(transaction.timestamp.as_ref().unwrap(), txn_version);
+        let block_timestamp =
+            parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version);
         for (index, event) in events.iter().enumerate() {
             let event_index = index as i64;
             if let Some(staking_event) =
@@ -15,7 +15,7 @@ use crate::{
     schema::current_delegator_balances,
     utils::{
         database::DbPoolConnection,
-        util::{parse_timestamp, standardize_address},
+        util::{parse_transaction_timestamp, standardize_address},
     },
 };
 use ahash::AHashMap;
@@ -445,7 +445,8 @@ impl RawCurrentDelegatorBalance {
         let mut current_delegator_balances: RawCurrentDelegatorBalanceMap = AHashMap::new();
         let mut delegator_balances = vec![];
         let txn_version = transaction.version as i64;
-        let txn_timestamp = parse_timestamp(transaction.timestamp.as_ref().unwrap(), txn_version);
+        let txn_timestamp =
+            parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version);
 
         let changes = &transaction.info.as_ref().unwrap().changes;
         // Do a first pass to get the mapping of active_share table handles to staking pool resource        let txn_version = transaction.version as i64;
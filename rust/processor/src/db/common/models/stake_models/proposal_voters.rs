@@ -9,7 +9,7 @@ use crate::{
     schema::proposal_votes,
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        util::{parse_timestamp, standardize_address},
+        util::{parse_transaction_timestamp, standardize_address},
     },
 };
 use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
@@ -63,8 +63,8 @@ impl RawProposalVote {
                         staking_pool_address: standardize_address(&ev.stake_pool),
                         num_votes: ev.num_votes.clone(),
                         should_pass: ev.should_pass,
-                        transaction_timestamp: parse_timestamp(
-                            transaction.timestamp.as_ref().unwrap(),
+                        transaction_timestamp: parse_transaction_timestamp(
+                            transaction.timestamp.as_ref(),
                             txn_version,
                         ),
                     });
@@ -6,7 +6,7 @@
 
 use crate::{
     db::common::models::stake_models::stake_utils::StakeResource,
-    utils::util::{parse_timestamp, standardize_address},
+    utils::util::{parse_transaction_timestamp, standardize_address},
 };
 use ahash::AHashMap;
 use aptos_protos::transaction::v1::{write_set_change::Change, Transaction};
@@ -30,11 +30,8 @@ impl RawCurrentStakingPoolVoter {
         let mut staking_pool_voters = AHashMap::new();
 
         let txn_version = transaction.version as i64;
-        let timestamp = transaction
-            .timestamp
-            .as_ref()
-            .expect("Transaction timestamp doesn't exist!");
-        let block_timestamp = parse_timestamp(timestamp, txn_version);
+        let block_timestamp =
+            parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version);
         for wsc in &transaction.info.as_ref().unwrap().changes {
             if let Change::WriteResource(write_resource) = wsc.change.as_ref().unwrap() {
                 if let Some(StakeResource::StakePool(inner)) =
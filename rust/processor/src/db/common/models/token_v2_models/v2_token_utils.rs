@@ -232,6 +232,10 @@ impl Mint {
     pub fn get_token_address(&self) -> String {
         standardize_address(&self.token)
     }
+
+    pub fn get_collection_address(&self) -> String {
+        standardize_address(&self.collection)
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
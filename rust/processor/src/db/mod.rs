@@ -1,3 +1,4 @@
+pub mod clickhouse;
 pub mod common;
 pub mod parquet;
 pub mod postgres;
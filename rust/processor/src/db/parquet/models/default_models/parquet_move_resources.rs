@@ -5,12 +5,13 @@
 
 use crate::{
     bq_analytics::generic_parquet_processor::{GetTimeStamp, HasVersion, NamedTable},
-    utils::util::standardize_address,
+    utils::util::{hex_to_raw_bytes, sha3_256, standardize_address},
 };
 use allocative_derive::Allocative;
 use anyhow::{Context, Result};
 use aptos_protos::transaction::v1::{
-    DeleteResource, MoveStructTag as MoveStructTagPB, WriteResource,
+    move_type::Content, DeleteResource, MoveStructTag as MoveStructTagPB, MoveType, MoveTypes,
+    WriteResource,
 };
 use field_count::FieldCount;
 use parquet_derive::ParquetRecordWriter;
@@ -33,6 +34,9 @@ pub struct MoveResource {
     pub generic_type_params: Option<String>,
     pub data: Option<String>,
     pub state_key_hash: String,
+    /// Set for the members of a resource group. They're written under the group's
+    /// `state_key_hash`, so that's what ties them together.
+    pub is_resource_group_member: bool,
 }
 
 impl NamedTable for MoveResource {
@@ -89,6 +93,11 @@ impl MoveResource {
                 hex::encode(write_resource.state_key_hash.as_slice()).as_str(),
             ),
             block_timestamp,
+            is_resource_group_member: is_resource_group_member(
+                &write_resource.address,
+                move_struct_tag,
+                &write_resource.state_key_hash,
+            ),
         };
         Ok(Some(move_resource))
     }
@@ -120,11 +129,101 @@ impl MoveResource {
                 hex::encode(delete_resource.state_key_hash.as_slice()).as_str(),
             ),
             block_timestamp,
+            is_resource_group_member: is_resource_group_member(
+                &delete_resource.address,
+                move_struct_tag,
+                &delete_resource.state_key_hash,
+            ),
         };
         Ok(Some(move_resource))
     }
 }
 
+/// Tags of `StateKeyInner::AccessPath` and `Path::Resource` in state keys
+const ACCESS_PATH_STATE_KEY_TAG: u8 = 0;
+const RESOURCE_PATH_TAG: u8 = 1;
+
+/// The stream expands a resource group write into a write per member resource, all under the
+/// group's state key. A resource written under any key but its own is therefore a group member,
+/// even if it's the only one in the group. False if the key can't be worked out.
+fn is_resource_group_member(
+    address: &str,
+    struct_tag: &MoveStructTagPB,
+    state_key_hash: &[u8],
+) -> bool {
+    resource_state_key_hash(address, struct_tag)
+        .is_some_and(|own_hash| own_hash.as_slice() != state_key_hash)
+}
+
+/// Hash of the state key of `struct_tag` stored on its own at `address`: SHA3-256 of the
+/// `StateKey` hasher seed followed by the key's encoding, as the node computes it. None if a
+/// generic type parameter can't be encoded.
+fn resource_state_key_hash(address: &str, struct_tag: &MoveStructTagPB) -> Option<[u8; 32]> {
+    let mut path = vec![RESOURCE_PATH_TAG];
+    encode_struct_tag(struct_tag, &mut path)?;
+    let mut buffer = sha3_256(b"APTOS::StateKey").to_vec();
+    buffer.push(ACCESS_PATH_STATE_KEY_TAG);
+    buffer.extend(hex_to_raw_bytes(&standardize_address(address)).ok()?);
+    encode_bytes(&path, &mut buffer);
+    Some(sha3_256(&buffer))
+}
+
+fn encode_uleb128(mut value: usize, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    encode_uleb128(bytes.len(), out);
+    out.extend_from_slice(bytes);
+}
+
+/// BCS of the `StructTag`
+fn encode_struct_tag(struct_tag: &MoveStructTagPB, out: &mut Vec<u8>) -> Option<()> {
+    out.extend(hex_to_raw_bytes(&standardize_address(&struct_tag.address)).ok()?);
+    encode_bytes(struct_tag.module.as_bytes(), out);
+    encode_bytes(struct_tag.name.as_bytes(), out);
+    encode_uleb128(struct_tag.generic_type_params.len(), out);
+    for type_param in &struct_tag.generic_type_params {
+        encode_type_tag(type_param, out)?;
+    }
+    Some(())
+}
+
+/// BCS of the `TypeTag`
+fn encode_type_tag(move_type: &MoveType, out: &mut Vec<u8>) -> Option<()> {
+    let variant = MoveTypes::try_from(move_type.r#type).ok()?;
+    let tag = match (variant, move_type.content.as_ref()) {
+        (MoveTypes::Bool, _) => 0,
+        (MoveTypes::U8, _) => 1,
+        (MoveTypes::U64, _) => 2,
+        (MoveTypes::U128, _) => 3,
+        (MoveTypes::Address, _) => 4,
+        (MoveTypes::Signer, _) => 5,
+        (MoveTypes::Vector, Some(Content::Vector(inner))) => {
+            out.push(6);
+            return encode_type_tag(inner, out);
+        },
+        (MoveTypes::Struct, Some(Content::Struct(inner))) => {
+            out.push(7);
+            return encode_struct_tag(inner, out);
+        },
+        (MoveTypes::U16, _) => 8,
+        (MoveTypes::U32, _) => 9,
+        (MoveTypes::U256, _) => 10,
+        _ => return None,
+    };
+    out.push(tag);
+    Some(())
+}
+
 pub fn convert_move_struct_tag(struct_tag: &MoveStructTagPB) -> MoveStructTag {
     MoveStructTag {
         resource_address: standardize_address(struct_tag.address.as_str()),
@@ -142,3 +241,115 @@ pub fn convert_move_struct_tag(struct_tag: &MoveStructTagPB) -> MoveStructTag {
             .unwrap_or(None),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN_ADDRESS: &str =
+        "0x1910ed3769bf094c43cf0c81470265304014c4ae7cbb4991fcc194f2c42bb4b4";
+
+    fn write_resource(
+        address: &str,
+        type_str: &str,
+        state_key_hash: &[u8],
+        data: &str,
+    ) -> WriteResource {
+        let (type_address, module, name) = {
+            let mut parts = type_str.splitn(3, "::");
+            (
+                parts.next().unwrap().to_string(),
+                parts.next().unwrap().to_string(),
+                parts.next().unwrap().to_string(),
+            )
+        };
+        WriteResource {
+            address: address.to_string(),
+            state_key_hash: state_key_hash.to_vec(),
+            r#type: Some(MoveStructTagPB {
+                address: type_address,
+                module,
+                name,
+                generic_type_params: vec![],
+            }),
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resource_group_members() {
+        // A token v2 mutation on mainnet writing the token object's `0x1::object::ObjectGroup`,
+        // which only holds a changed `TokenRefs`, next to the sender's account
+        let object_group_hash =
+            hex_to_raw_bytes("0x46062b050b32127b0939f63ea81c9d1529131721f32b43afbf08ff72b9ad1766")
+                .unwrap();
+        let account_hash =
+            hex_to_raw_bytes("0x09360d36baef62992617174a7da719980fe22baff404b4045c652166afde1fd9")
+                .unwrap();
+        let coin_store_hash =
+            hex_to_raw_bytes("0x7ebb67042ac5d85c2c317f369f5b598b2c6b8b3b34e5e3c00855199f61dfbfea")
+                .unwrap();
+        let mut coin_store = write_resource(
+            "0xa11",
+            "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>",
+            &coin_store_hash,
+            r#"{"coin":{"value":"100"},"frozen":false}"#,
+        );
+        coin_store.r#type = Some(MoveStructTagPB {
+            address: "0x1".to_string(),
+            module: "coin".to_string(),
+            name: "CoinStore".to_string(),
+            generic_type_params: vec![MoveType {
+                r#type: MoveTypes::Struct as i32,
+                content: Some(Content::Struct(MoveStructTagPB {
+                    address: "0x1".to_string(),
+                    module: "aptos_coin".to_string(),
+                    name: "AptosCoin".to_string(),
+                    generic_type_params: vec![],
+                })),
+            }],
+        });
+        let write_resources = [
+            write_resource(
+                TOKEN_ADDRESS,
+                "0xa8a3cdff3068ee47cb0419cbd93ad1f71bdabb50431fc0f5b971a00c613b13d2::token_components::TokenRefs",
+                &object_group_hash,
+                r#"{"burn_ref":{"vec":[]},"extend_ref":{"vec":[]},"mutator_ref":{"vec":[]},"property_mutator_ref":{"vec":[]},"transfer_ref":{"vec":[]}}"#,
+            ),
+            write_resource(
+                "0xa11",
+                "0x1::account::Account",
+                &account_hash,
+                r#"{"sequence_number":"3"}"#,
+            ),
+            coin_store,
+        ];
+        let block_timestamp = chrono::NaiveDateTime::default();
+        let move_resources = write_resources
+            .iter()
+            .enumerate()
+            .map(|(index, write_resource)| {
+                MoveResource::from_write_resource(
+                    write_resource,
+                    index as i64,
+                    967255533,
+                    10,
+                    block_timestamp,
+                )
+                .unwrap()
+                .unwrap()
+            })
+            .collect::<Vec<_>>();
+
+        // The only member of its group is still flagged
+        assert!(move_resources[0].is_resource_group_member);
+        assert_eq!(
+            move_resources[0].data.as_deref(),
+            Some(write_resources[0].data.as_str())
+        );
+        // Standalone resources are under their own keys, generic ones included
+        assert!(!move_resources[1].is_resource_group_member);
+        assert!(!move_resources[2].is_resource_group_member);
+    }
+}
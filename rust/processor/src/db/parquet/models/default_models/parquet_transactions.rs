@@ -10,7 +10,10 @@ use crate::{
     bq_analytics::generic_parquet_processor::{GetTimeStamp, HasVersion, NamedTable},
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        util::{get_clean_payload, get_clean_writeset, get_payload_type, standardize_address},
+        util::{
+            get_clean_payload, get_clean_writeset, get_payload_type, parse_transaction_timestamp,
+            standardize_address,
+        },
     },
 };
 use ahash::AHashMap;
@@ -19,6 +22,7 @@ use aptos_protos::transaction::v1::{
     transaction::{TransactionType, TxnData},
     Transaction as TransactionPB, TransactionInfo, TransactionSizeInfo,
 };
+use chrono::SubsecRound;
 use field_count::FieldCount;
 use parquet_derive::ParquetRecordWriter;
 use serde::{Deserialize, Serialize};
@@ -46,6 +50,8 @@ pub struct Transaction {
     pub txn_total_bytes: i64,
     #[allocative(skip)]
     pub block_timestamp: chrono::NaiveDateTime,
+    /// See `transaction_content_hash`. Only set if `compute_content_hash` is enabled.
+    pub content_hash: Option<String>,
 }
 
 impl NamedTable for Transaction {
@@ -138,6 +144,7 @@ impl Transaction {
             txn_total_bytes: txn_size_info
                 .map_or(0, |size_info| size_info.transaction_bytes as i64),
             block_timestamp,
+            content_hash: None,
         }
     }
 
@@ -174,13 +181,10 @@ impl Transaction {
             .expect("Transaction type doesn't exist!")
             .as_str_name()
             .to_string();
-        let timestamp = transaction
-            .timestamp
-            .as_ref()
-            .expect("Transaction timestamp doesn't exist!");
-        #[allow(deprecated)]
-        let block_timestamp = chrono::NaiveDateTime::from_timestamp_opt(timestamp.seconds, 0)
-            .expect("Txn Timestamp is invalid!");
+        // Stored to the second
+        let block_timestamp =
+            parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version)
+                .trunc_subsecs(0);
 
         let txn_size_info = transaction.size_info.as_ref();
 
@@ -339,9 +343,12 @@ impl Transaction {
         }
     }
 
+    /// Counts the transactions and write set changes made per version into
+    /// `txn_version_to_struct_count` and `wsc_version_to_struct_count`.
     pub fn from_transactions(
         transactions: &[TransactionPB],
-        transaction_version_to_struct_count: &mut AHashMap<i64, i64>,
+        txn_version_to_struct_count: &mut AHashMap<i64, i64>,
+        wsc_version_to_struct_count: &mut AHashMap<i64, i64>,
     ) -> (
         Vec<Self>,
         Vec<WriteSetChangeModel>,
@@ -354,15 +361,13 @@ impl Transaction {
         for txn in transactions {
             let (txn, mut wsc_list, mut wsc_detail_list) = Self::from_transaction(txn);
             txns.push(txn.clone());
-            // TODO: Remove once fully migrated
-            transaction_version_to_struct_count
+            txn_version_to_struct_count
                 .entry(txn.txn_version)
                 .and_modify(|e| *e += 1)
                 .or_insert(1);
 
-            // TODO: Remove once fully migrated
             if !wsc_list.is_empty() {
-                transaction_version_to_struct_count
+                wsc_version_to_struct_count
                     .entry(txn.txn_version)
                     .and_modify(|e| *e += wsc_list.len() as i64)
                     .or_insert(wsc_list.len() as i64);
@@ -0,0 +1,8 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS gap_detector_status (
+    processor VARCHAR(100) NOT NULL,
+    next_version_to_process BIGINT NOT NULL,
+    pending_batches JSONB NOT NULL,
+    last_updated TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (processor)
+);
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- Supports "events for account X" queries ordered by most recent first.
+-- Built concurrently, outside of a transaction (see metadata.toml), so writes to events carry on
+-- while it builds. Postgres runs the statements of a multi-statement migration in one implicit
+-- transaction, which CONCURRENTLY isn't allowed in, so each index change is its own migration.
+CREATE INDEX CONCURRENTLY IF NOT EXISTS ev_addr_version_index ON events (
+  account_address,
+  transaction_version DESC,
+  event_index DESC
+);
@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+CREATE INDEX CONCURRENTLY IF NOT EXISTS ev_addr_type_index ON events (account_address);
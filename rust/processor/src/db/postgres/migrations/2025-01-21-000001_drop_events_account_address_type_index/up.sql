@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- ev_addr_version_index starts with account_address, so it serves everything this index did.
+DROP INDEX CONCURRENTLY IF EXISTS ev_addr_type_index;
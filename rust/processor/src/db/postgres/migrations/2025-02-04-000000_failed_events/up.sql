@@ -0,0 +1,13 @@
+-- Your SQL goes here
+-- Dead-letter table for events that a processor failed to parse
+CREATE TABLE IF NOT EXISTS failed_events (
+  processor VARCHAR(100) NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  type TEXT NOT NULL,
+  -- Kept as text since the payload may not be valid JSON
+  data TEXT NOT NULL,
+  error TEXT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (processor, transaction_version, event_index)
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS current_account_sequence_numbers;
+DROP TABLE IF EXISTS account_sequence_numbers;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Sequence number history of every user transaction sender
+CREATE TABLE IF NOT EXISTS account_sequence_numbers (
+  transaction_version BIGINT NOT NULL PRIMARY KEY,
+  account_address VARCHAR(66) NOT NULL,
+  sequence_number BIGINT NOT NULL,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS asn_addr_seq_index ON account_sequence_numbers (account_address, sequence_number);
+-- Latest sequence number of every account
+CREATE TABLE IF NOT EXISTS current_account_sequence_numbers (
+  account_address VARCHAR(66) NOT NULL PRIMARY KEY,
+  sequence_number BIGINT NOT NULL,
+  last_transaction_version BIGINT NOT NULL,
+  last_transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS casn_insat_index ON current_account_sequence_numbers (inserted_at);
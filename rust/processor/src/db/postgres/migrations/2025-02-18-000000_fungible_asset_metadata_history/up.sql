@@ -0,0 +1,15 @@
+-- Your SQL goes here
+-- Append-only history of fungible asset metadata, one row per version where a field changed
+CREATE TABLE IF NOT EXISTS fungible_asset_metadata_history (
+  asset_type VARCHAR(1000) NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  name VARCHAR(32) NOT NULL,
+  symbol VARCHAR(10) NOT NULL,
+  decimals INT NOT NULL,
+  icon_uri VARCHAR(512),
+  project_uri VARCHAR(512),
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (asset_type, transaction_version)
+);
+CREATE INDEX IF NOT EXISTS famh_insat_index ON fungible_asset_metadata_history (inserted_at);
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE IF NOT EXISTS parquet_upload_checkpoints (
+    processor VARCHAR(100) NOT NULL,
+    table_name VARCHAR(100) NOT NULL,
+    start_version BIGINT NOT NULL,
+    end_version BIGINT NOT NULL,
+    -- number of rows for end_version in the file, since a version can be split across files
+    end_version_struct_count BIGINT NOT NULL,
+    file_path VARCHAR(1000) NOT NULL,
+    -- 'uploading' before the upload starts, 'committed' once it's confirmed
+    status VARCHAR(20) NOT NULL,
+    last_updated TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (processor, table_name, start_version)
+);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+-- Partitions that were already created are left as is.
+DROP FUNCTION IF EXISTS create_events_partition(BIGINT, BIGINT);
+DROP FUNCTION IF EXISTS partition_events_table();
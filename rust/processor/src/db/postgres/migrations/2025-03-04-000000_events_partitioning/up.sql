@@ -0,0 +1,77 @@
+-- Your SQL goes here
+-- Optional range partitioning of events by transaction_version. Nothing changes until the
+-- events processor is configured with a partition_interval, which calls these on startup.
+
+-- Recreates events as a partitioned table. Only an empty table is converted; a populated one
+-- has to be migrated by hand since copying billions of rows can't happen on startup. Columns,
+-- constraints, indexes and the views on events are all read from the catalog, so the partitioned
+-- table matches whatever migrations ran before.
+CREATE OR REPLACE FUNCTION partition_events_table() RETURNS VOID AS $$
+DECLARE
+    view_names TEXT[];
+    view_defs TEXT[];
+    constraint_names TEXT[];
+    constraint_defs TEXT[];
+    index_defs TEXT[];
+BEGIN
+    IF EXISTS (SELECT 1 FROM pg_partitioned_table WHERE partrelid = 'events'::regclass) THEN
+        RETURN;
+    END IF;
+    IF EXISTS (SELECT 1 FROM events LIMIT 1) THEN
+        RAISE EXCEPTION 'events already has rows, it has to be partitioned manually';
+    END IF;
+
+    SELECT COALESCE(array_agg(c.relname::TEXT ORDER BY c.oid), '{}'),
+        COALESCE(array_agg(rtrim(pg_get_viewdef(c.oid), ';') ORDER BY c.oid), '{}')
+    INTO view_names, view_defs
+    FROM pg_class c
+    WHERE c.relkind = 'v' AND c.oid IN (
+        SELECT r.ev_class FROM pg_depend d
+        JOIN pg_rewrite r ON r.oid = d.objid
+        WHERE d.classid = 'pg_rewrite'::regclass AND d.refobjid = 'events'::regclass
+    );
+    -- Unique constraints on a partitioned table must include the partition key, which the
+    -- primary key (transaction_version, event_index) does
+    SELECT COALESCE(array_agg(conname::TEXT ORDER BY oid), '{}'),
+        COALESCE(array_agg(pg_get_constraintdef(oid) ORDER BY oid), '{}')
+    INTO constraint_names, constraint_defs
+    FROM pg_constraint
+    WHERE conrelid = 'events'::regclass AND contype IN ('p', 'u', 'f', 'c');
+    SELECT COALESCE(array_agg(pg_get_indexdef(i.indexrelid) ORDER BY i.indexrelid), '{}')
+    INTO index_defs
+    FROM pg_index i
+    WHERE i.indrelid = 'events'::regclass
+        AND NOT EXISTS (SELECT 1 FROM pg_constraint c WHERE c.conindid = i.indexrelid);
+
+    -- In reverse order of creation, in case a view reads from another
+    FOR i IN REVERSE COALESCE(array_length(view_names, 1), 0)..1 LOOP
+        EXECUTE format('DROP VIEW %I', view_names[i]);
+    END LOOP;
+    ALTER TABLE events RENAME TO events_unpartitioned;
+    CREATE TABLE events (
+        LIKE events_unpartitioned INCLUDING DEFAULTS INCLUDING GENERATED INCLUDING COMMENTS
+    ) PARTITION BY RANGE (transaction_version);
+    DROP TABLE events_unpartitioned;
+
+    FOR i IN 1..COALESCE(array_length(constraint_names, 1), 0) LOOP
+        EXECUTE format('ALTER TABLE events ADD CONSTRAINT %I %s', constraint_names[i], constraint_defs[i]);
+    END LOOP;
+    -- The index definitions name the table, which is now the partitioned one
+    FOR i IN 1..COALESCE(array_length(index_defs, 1), 0) LOOP
+        EXECUTE index_defs[i];
+    END LOOP;
+    FOR i IN 1..COALESCE(array_length(view_names, 1), 0) LOOP
+        EXECUTE format('CREATE VIEW %I AS %s', view_names[i], view_defs[i]);
+    END LOOP;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Creates the partition for [partition_start, partition_end) if it doesn't exist yet. Processor
+-- tasks call this concurrently, so creation is serialized with an advisory lock.
+CREATE OR REPLACE FUNCTION create_events_partition(partition_start BIGINT, partition_end BIGINT) RETURNS VOID AS $$
+BEGIN
+    PERFORM pg_advisory_xact_lock(hashtext('create_events_partition'));
+    EXECUTE format('CREATE TABLE IF NOT EXISTS %I PARTITION OF events FOR VALUES FROM (%s) TO (%s)',
+                   'events_' || partition_start, partition_start, partition_end);
+END;
+$$ LANGUAGE plpgsql;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- The transaction closing each block: a state checkpoint, or a block epilogue on newer chain
+-- versions. Together with block_metadata_transactions this covers both ends of every block.
+CREATE TABLE IF NOT EXISTS block_end_transactions (
+  version BIGINT UNIQUE PRIMARY KEY NOT NULL,
+  block_height BIGINT UNIQUE NOT NULL,
+  epoch BIGINT NOT NULL,
+  transaction_type VARCHAR(50) NOT NULL,
+  "timestamp" TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS bet_insat_index ON block_end_transactions (inserted_at);
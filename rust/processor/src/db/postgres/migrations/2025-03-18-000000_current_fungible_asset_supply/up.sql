@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- Latest total supply of each fungible asset, from its Supply or ConcurrentSupply resource.
+-- Only written when the fungible asset processor reconciles supply.
+CREATE TABLE IF NOT EXISTS current_fungible_asset_supply (
+  asset_type VARCHAR(1000) PRIMARY KEY NOT NULL,
+  supply NUMERIC NOT NULL,
+  last_transaction_version BIGINT NOT NULL,
+  last_transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS cfas_insat_index ON current_fungible_asset_supply (inserted_at);
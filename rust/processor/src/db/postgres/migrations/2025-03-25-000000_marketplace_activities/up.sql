@@ -0,0 +1,29 @@
+-- Your SQL goes here
+-- Listing, offer and sale events of the configured NFT marketplaces, normalized across marketplaces.
+-- Only written when the token v2 processor has marketplaces configured.
+CREATE TABLE IF NOT EXISTS marketplace_activities (
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  marketplace VARCHAR(100) NOT NULL,
+  contract_address VARCHAR(66) NOT NULL,
+  activity_type VARCHAR(50) NOT NULL,
+  offer_or_listing_id VARCHAR(66) NOT NULL,
+  collection_id VARCHAR(66) NOT NULL,
+  creator_address VARCHAR(66) NOT NULL,
+  collection_name VARCHAR(128) NOT NULL,
+  token_data_id VARCHAR(66),
+  token_name VARCHAR(128),
+  token_standard VARCHAR(10) NOT NULL,
+  price NUMERIC NOT NULL,
+  token_amount NUMERIC NOT NULL,
+  buyer VARCHAR(66),
+  seller VARCHAR(66),
+  event_type TEXT NOT NULL,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (transaction_version, event_index)
+);
+CREATE INDEX IF NOT EXISTS ma_collection_id_index ON marketplace_activities (collection_id);
+CREATE INDEX IF NOT EXISTS ma_token_data_id_index ON marketplace_activities (token_data_id);
+CREATE INDEX IF NOT EXISTS ma_offer_or_listing_id_index ON marketplace_activities (offer_or_listing_id);
+CREATE INDEX IF NOT EXISTS ma_insat_index ON marketplace_activities (inserted_at);
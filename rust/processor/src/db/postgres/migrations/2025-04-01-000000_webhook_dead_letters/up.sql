@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Dead-letter table for events that couldn't be delivered to the events processor's webhook
+CREATE TABLE IF NOT EXISTS webhook_dead_letters (
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  url TEXT NOT NULL,
+  -- The CloudEvents envelope that was sent
+  cloud_event JSONB NOT NULL,
+  error TEXT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (transaction_version, event_index)
+);
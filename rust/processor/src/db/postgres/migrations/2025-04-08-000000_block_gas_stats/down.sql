@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS block_gas_stats_parts;
+DROP TABLE IF EXISTS block_gas_stats;
@@ -0,0 +1,29 @@
+-- Your SQL goes here
+-- Gas totals of the user transactions of each block
+CREATE TABLE IF NOT EXISTS block_gas_stats (
+  block_height BIGINT NOT NULL PRIMARY KEY,
+  first_version BIGINT NOT NULL,
+  last_version BIGINT NOT NULL,
+  block_timestamp TIMESTAMP,
+  transaction_count BIGINT NOT NULL,
+  total_gas_used NUMERIC NOT NULL,
+  total_fee_octas NUMERIC NOT NULL,
+  total_gas_unit_price NUMERIC NOT NULL,
+  avg_gas_unit_price NUMERIC GENERATED ALWAYS AS (
+    CASE
+      WHEN transaction_count = 0 THEN NULL
+      ELSE total_gas_unit_price / transaction_count
+    END
+  ) STORED,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS bgs_block_timestamp_index ON block_gas_stats (block_timestamp);
+-- The parts of each block already added to block_gas_stats. Batches don't line up with blocks,
+-- so a block is added up from the part in each batch, and a part is only added once
+CREATE TABLE IF NOT EXISTS block_gas_stats_parts (
+  block_height BIGINT NOT NULL,
+  first_version BIGINT NOT NULL,
+  last_version BIGINT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (block_height, first_version)
+);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- Lease on each processor's progress, held by one of the processes running it
+CREATE TABLE IF NOT EXISTS processor_leases (
+  processor VARCHAR(100) NOT NULL PRIMARY KEY,
+  holder_id VARCHAR(100) NOT NULL,
+  fencing_token BIGINT NOT NULL,
+  expires_at TIMESTAMP NOT NULL,
+  last_updated TIMESTAMP NOT NULL DEFAULT NOW()
+);
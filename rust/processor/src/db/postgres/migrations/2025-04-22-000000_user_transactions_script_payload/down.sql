@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE user_transactions
+DROP COLUMN IF EXISTS script_payload;
@@ -0,0 +1,5 @@
+-- Your SQL goes here
+-- The script and arguments of script transactions, e.g. governance proposals, in the same
+-- format as script payloads in transactions.payload. NULL for other payloads.
+ALTER TABLE user_transactions
+ADD COLUMN IF NOT EXISTS script_payload JSONB;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- Change data capture outbox. Rows are written in the same DB transaction as the rows they
+-- describe, so a CDC connector such as Debezium can stream them without logical decoding of
+-- the domain tables.
+CREATE TABLE IF NOT EXISTS outbox (
+  id BIGSERIAL PRIMARY KEY,
+  -- Table the row was written to
+  table_name VARCHAR(100) NOT NULL,
+  operation VARCHAR(10) NOT NULL,
+  -- Primary key of the row, e.g. {"transaction_version": 1, "event_index": 0}
+  row_key JSONB NOT NULL,
+  payload JSONB NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  -- Reprocessing a row doesn't add another outbox row
+  UNIQUE (table_name, row_key)
+);
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+-- Latest state of every resource, maintained by the default processor
+CREATE TABLE IF NOT EXISTS current_move_resources (
+  address VARCHAR(66) NOT NULL,
+  -- Hash of the type for pk since the type is unbounded
+  resource_type_hash VARCHAR(64) NOT NULL,
+  resource_type TEXT NOT NULL,
+  module TEXT NOT NULL,
+  name TEXT NOT NULL,
+  generic_type_params JSONB,
+  -- Null once the resource is deleted
+  data JSONB,
+  is_deleted BOOLEAN NOT NULL,
+  state_key_hash VARCHAR(66) NOT NULL,
+  last_transaction_version BIGINT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (address, resource_type_hash)
+);
+CREATE INDEX IF NOT EXISTS cmr_insat_index ON current_move_resources (inserted_at);
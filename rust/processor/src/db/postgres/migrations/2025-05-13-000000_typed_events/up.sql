@@ -0,0 +1,14 @@
+-- Your SQL goes here
+-- Fields of events of configured types, pulled out of the JSON data into typed columns
+CREATE TABLE IF NOT EXISTS typed_events (
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  type TEXT NOT NULL,
+  amount NUMERIC,
+  from_address VARCHAR(66),
+  to_address VARCHAR(66),
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (transaction_version, event_index)
+);
+CREATE INDEX IF NOT EXISTS te_from_addr_index ON typed_events (from_address);
+CREATE INDEX IF NOT EXISTS te_to_addr_index ON typed_events (to_address);
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+-- Coins and the fungible assets they were paired with by the coin to fungible asset migration.
+-- Assets that only exist as a coin or only as a fungible asset have no row.
+CREATE TABLE IF NOT EXISTS coin_to_fa_mapping (
+  coin_type_hash VARCHAR(64) PRIMARY KEY NOT NULL,
+  coin_type VARCHAR(5000) NOT NULL,
+  fa_metadata_address VARCHAR(66) NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS ctfm_fa_metadata_address_index ON coin_to_fa_mapping (fa_metadata_address);
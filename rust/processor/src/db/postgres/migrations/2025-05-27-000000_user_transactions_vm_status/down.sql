@@ -0,0 +1,7 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS ut_abort_location_index;
+ALTER TABLE user_transactions
+DROP COLUMN IF EXISTS success,
+DROP COLUMN IF EXISTS vm_status,
+DROP COLUMN IF EXISTS abort_code,
+DROP COLUMN IF EXISTS abort_location;
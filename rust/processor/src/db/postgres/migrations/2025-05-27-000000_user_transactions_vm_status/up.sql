@@ -0,0 +1,11 @@
+-- Your SQL goes here
+-- How each user transaction ended. abort_code and abort_location are parsed from vm_status for
+-- failed transactions: the module and code of a Move abort, or the function of an execution
+-- failure. NULL for rows written before these columns existed.
+ALTER TABLE user_transactions
+ADD COLUMN IF NOT EXISTS success BOOLEAN,
+ADD COLUMN IF NOT EXISTS vm_status TEXT,
+ADD COLUMN IF NOT EXISTS abort_code NUMERIC,
+ADD COLUMN IF NOT EXISTS abort_location VARCHAR(1000);
+CREATE INDEX IF NOT EXISTS ut_abort_location_index ON user_transactions (abort_location)
+WHERE abort_location IS NOT NULL;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS current_allowances;
+DROP TABLE IF EXISTS allowance_activities;
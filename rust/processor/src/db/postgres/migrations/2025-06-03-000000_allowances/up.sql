@@ -0,0 +1,39 @@
+-- Your SQL goes here
+-- Every approval and every spend of an allowance, from the `Approval` and `TransferFrom` events.
+CREATE TABLE IF NOT EXISTS allowance_activities (
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  owner_address VARCHAR(66) NOT NULL,
+  spender_address VARCHAR(66) NOT NULL,
+  -- Fungible asset metadata address, or coin type
+  asset_type VARCHAR(1000) NOT NULL,
+  -- approval or spend
+  activity_type VARCHAR(50) NOT NULL,
+  amount NUMERIC NOT NULL,
+  is_unlimited BOOLEAN NOT NULL,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (transaction_version, event_index)
+);
+CREATE INDEX IF NOT EXISTS aa_owner_spender_asset_index ON allowance_activities (
+  owner_address,
+  spender_address,
+  asset_type
+);
+-- What each spender may still spend of each owner's asset: the latest approval less what was
+-- spent since. Unlimited approvals keep their amount.
+CREATE TABLE IF NOT EXISTS current_allowances (
+  owner_address VARCHAR(66) NOT NULL,
+  spender_address VARCHAR(66) NOT NULL,
+  asset_type VARCHAR(1000) NOT NULL,
+  amount NUMERIC NOT NULL,
+  is_unlimited BOOLEAN NOT NULL,
+  approval_transaction_version BIGINT NOT NULL,
+  last_transaction_version BIGINT NOT NULL,
+  last_transaction_timestamp TIMESTAMP NOT NULL,
+  -- Activities the row was computed from, so a stale recomputation can't overwrite a newer one
+  num_activities BIGINT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (owner_address, spender_address, asset_type)
+);
+CREATE INDEX IF NOT EXISTS ca_spender_address_index ON current_allowances (spender_address);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS asn_block_height_index;
+ALTER TABLE account_sequence_numbers
+DROP COLUMN IF EXISTS block_height;
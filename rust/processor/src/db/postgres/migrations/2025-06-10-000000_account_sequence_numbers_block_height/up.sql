@@ -0,0 +1,6 @@
+-- Your SQL goes here
+-- Height of the block each transaction is in, so block-scoped queries don't need to join
+-- block_metadata_transactions. NULL for rows written before this column existed.
+ALTER TABLE account_sequence_numbers
+ADD COLUMN IF NOT EXISTS block_height BIGINT;
+CREATE INDEX IF NOT EXISTS asn_block_height_index ON account_sequence_numbers (block_height);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP VIEW IF EXISTS governance_proposal_outcomes;
+DROP TABLE IF EXISTS governance_proposal_resolutions;
+DROP TABLE IF EXISTS governance_votes;
+DROP TABLE IF EXISTS governance_proposals;
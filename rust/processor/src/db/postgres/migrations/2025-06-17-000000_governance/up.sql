@@ -0,0 +1,71 @@
+-- Your SQL goes here
+-- Governance proposals as created, from the `CreateProposal` events of `aptos_governance` and
+-- `voting` in the same transaction.
+CREATE TABLE IF NOT EXISTS governance_proposals (
+  proposal_id BIGINT NOT NULL,
+  proposer_address VARCHAR(66) NOT NULL,
+  stake_pool_address VARCHAR(66) NOT NULL,
+  execution_hash VARCHAR(200) NOT NULL,
+  metadata_location TEXT,
+  metadata_hash TEXT,
+  min_vote_threshold NUMERIC NOT NULL,
+  early_resolution_vote_threshold NUMERIC,
+  expiration_timestamp TIMESTAMP NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (proposal_id)
+);
+CREATE INDEX IF NOT EXISTS gp_proposer_address_index ON governance_proposals (proposer_address);
+-- Every vote on a governance proposal
+CREATE TABLE IF NOT EXISTS governance_votes (
+  transaction_version BIGINT NOT NULL,
+  event_index BIGINT NOT NULL,
+  proposal_id BIGINT NOT NULL,
+  voter_address VARCHAR(66) NOT NULL,
+  stake_pool_address VARCHAR(66) NOT NULL,
+  num_votes NUMERIC NOT NULL,
+  should_pass BOOLEAN NOT NULL,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (transaction_version, event_index)
+);
+CREATE INDEX IF NOT EXISTS gv_proposal_id_index ON governance_votes (proposal_id);
+CREATE INDEX IF NOT EXISTS gv_voter_address_index ON governance_votes (voter_address);
+-- Final tally of resolved proposals. Multi-step proposals are resolved once per step, the row
+-- is the last one.
+CREATE TABLE IF NOT EXISTS governance_proposal_resolutions (
+  proposal_id BIGINT NOT NULL,
+  yes_votes NUMERIC NOT NULL,
+  no_votes NUMERIC NOT NULL,
+  resolved_early BOOLEAN NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  transaction_timestamp TIMESTAMP NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (proposal_id)
+);
+-- Where each proposal stands. Only resolving emits an event, so a proposal whose voting ended
+-- without it, whether it failed or passed and was never executed, is `expired`. One that
+-- passed can still be resolved later, and then becomes `resolved`.
+CREATE OR REPLACE VIEW governance_proposal_outcomes AS
+SELECT
+  p.proposal_id,
+  CASE
+    WHEN r.proposal_id IS NOT NULL THEN 'resolved'
+    WHEN p.expiration_timestamp <= NOW() THEN 'expired'
+    ELSE 'voting'
+  END AS outcome,
+  COALESCE(v.yes_votes, 0) AS yes_votes,
+  COALESCE(v.no_votes, 0) AS no_votes,
+  r.resolved_early,
+  r.transaction_version AS resolution_transaction_version
+FROM governance_proposals p
+LEFT JOIN governance_proposal_resolutions r ON r.proposal_id = p.proposal_id
+LEFT JOIN (
+  SELECT
+    proposal_id,
+    SUM(num_votes) FILTER (WHERE should_pass) AS yes_votes,
+    SUM(num_votes) FILTER (WHERE NOT should_pass) AS no_votes
+  FROM governance_votes
+  GROUP BY proposal_id
+) v ON v.proposal_id = p.proposal_id;
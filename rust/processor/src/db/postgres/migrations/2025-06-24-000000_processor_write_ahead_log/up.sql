@@ -0,0 +1,10 @@
+-- Your SQL goes here
+-- The batch each processor is writing, or last wrote, with `write_ahead_log`
+CREATE TABLE IF NOT EXISTS processor_write_ahead_log (
+  processor VARCHAR(100) NOT NULL PRIMARY KEY,
+  start_version BIGINT NOT NULL,
+  end_version BIGINT NOT NULL,
+  -- pending while the batch is being written, written once it's done
+  state VARCHAR(10) NOT NULL,
+  last_updated TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS collection_stats;
+DROP TABLE IF EXISTS collection_stat_changes;
@@ -0,0 +1,25 @@
+-- Your SQL goes here
+-- Mints, burns and transfers of each collection per transaction. Only written when the token v2
+-- processor has collection_stats set.
+CREATE TABLE IF NOT EXISTS collection_stat_changes (
+  collection_id VARCHAR(66) NOT NULL,
+  transaction_version BIGINT NOT NULL,
+  mints NUMERIC NOT NULL,
+  burns NUMERIC NOT NULL,
+  transfers BIGINT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (collection_id, transaction_version)
+);
+-- Totals of collection_stat_changes per collection
+CREATE TABLE IF NOT EXISTS collection_stats (
+  collection_id VARCHAR(66) NOT NULL,
+  -- Minted less burned
+  current_supply NUMERIC NOT NULL,
+  total_mints NUMERIC NOT NULL,
+  total_burns NUMERIC NOT NULL,
+  total_transfers BIGINT NOT NULL,
+  last_activity_version BIGINT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (collection_id)
+);
+CREATE INDEX IF NOT EXISTS cs_current_supply_index ON collection_stats (current_supply);
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- Schema version of the tables each processor writes, checked at startup so a binary can't run
+-- against tables older or newer than its models
+CREATE TABLE IF NOT EXISTS processor_schema_versions (
+  processor VARCHAR(100) NOT NULL,
+  schema_version BIGINT NOT NULL,
+  last_updated TIMESTAMP NOT NULL DEFAULT NOW(),
+  PRIMARY KEY (processor)
+);
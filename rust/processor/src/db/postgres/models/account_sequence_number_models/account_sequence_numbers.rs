@@ -0,0 +1,192 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use crate::{
+    schema::{account_sequence_numbers, current_account_sequence_numbers},
+    utils::util::{parse_transaction_timestamp, standardize_address},
+};
+use ahash::AHashMap;
+use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+pub type CurrentAccountSequenceNumberPK = String;
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(transaction_version))]
+#[diesel(table_name = account_sequence_numbers)]
+pub struct AccountSequenceNumber {
+    pub transaction_version: i64,
+    pub account_address: String,
+    pub sequence_number: i64,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+    pub block_height: i64,
+}
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(account_address))]
+#[diesel(table_name = current_account_sequence_numbers)]
+pub struct CurrentAccountSequenceNumber {
+    pub account_address: String,
+    pub sequence_number: i64,
+    pub last_transaction_version: i64,
+    pub last_transaction_timestamp: chrono::NaiveDateTime,
+}
+
+impl AccountSequenceNumber {
+    /// Only user transactions have a sender and sequence number
+    pub fn from_transaction(txn: &Transaction) -> Option<Self> {
+        let txn_version = txn.version as i64;
+        if let Some(TxnData::User(user_txn)) = txn.txn_data.as_ref() {
+            let user_request = user_txn.request.as_ref()?;
+            return Some(Self {
+                transaction_version: txn_version,
+                account_address: standardize_address(&user_request.sender),
+                sequence_number: user_request.sequence_number as i64,
+                transaction_timestamp: parse_transaction_timestamp(
+                    txn.timestamp.as_ref(),
+                    txn_version,
+                ),
+                block_height: txn.block_height as i64,
+            });
+        }
+        None
+    }
+}
+
+impl CurrentAccountSequenceNumber {
+    /// Keeps the latest sequence number per account, sorted by account so that concurrent upserts
+    /// lock rows in the same order.
+    pub fn from_history(history: &[AccountSequenceNumber]) -> Vec<Self> {
+        let mut current: AHashMap<CurrentAccountSequenceNumberPK, Self> = AHashMap::new();
+        for item in history {
+            let is_newer = current
+                .get(&item.account_address)
+                .is_none_or(|c| c.last_transaction_version < item.transaction_version);
+            if is_newer {
+                current.insert(item.account_address.clone(), Self {
+                    account_address: item.account_address.clone(),
+                    sequence_number: item.sequence_number,
+                    last_transaction_version: item.transaction_version,
+                    last_transaction_timestamp: item.transaction_timestamp,
+                });
+            }
+        }
+        let mut current = current.into_values().collect::<Vec<_>>();
+        current.sort_by(|a, b| a.account_address.cmp(&b.account_address));
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::block_height::BlockHeightTracker;
+    use aptos_protos::{
+        transaction::v1::{
+            transaction::TransactionType, BlockMetadataTransaction, Event, UserTransaction,
+            UserTransactionRequest,
+        },
+        util::timestamp::Timestamp,
+    };
+
+    fn user_txn(version: u64, sender: &str, sequence_number: u64) -> Transaction {
+        Transaction {
+            version,
+            r#type: TransactionType::User as i32,
+            timestamp: Some(Timestamp {
+                seconds: version as i64,
+                nanos: 0,
+            }),
+            txn_data: Some(TxnData::User(UserTransaction {
+                request: Some(UserTransactionRequest {
+                    sender: sender.to_string(),
+                    sequence_number,
+                    ..UserTransactionRequest::default()
+                }),
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn test_sequence_numbers_from_same_sender() {
+        let transactions = vec![
+            user_txn(100, "0xa", 0),
+            user_txn(101, "0xb", 7),
+            Transaction {
+                version: 102,
+                r#type: TransactionType::BlockMetadata as i32,
+                ..Transaction::default()
+            },
+            user_txn(103, "0xa", 1),
+            user_txn(104, "0xa", 2),
+        ];
+        let history = transactions
+            .iter()
+            .filter_map(AccountSequenceNumber::from_transaction)
+            .collect::<Vec<_>>();
+        assert_eq!(history.len(), 4);
+        let sender_a = standardize_address("0xa");
+        assert_eq!(
+            history
+                .iter()
+                .filter(|h| h.account_address == sender_a)
+                .map(|h| (h.sequence_number, h.transaction_version))
+                .collect::<Vec<_>>(),
+            vec![(0, 100), (1, 103), (2, 104)]
+        );
+
+        let current = CurrentAccountSequenceNumber::from_history(&history);
+        assert_eq!(current.len(), 2);
+        let current_a = current
+            .iter()
+            .find(|c| c.account_address == sender_a)
+            .unwrap();
+        assert_eq!(current_a.sequence_number, 2);
+        assert_eq!(current_a.last_transaction_version, 104);
+        assert_eq!(
+            current_a.last_transaction_timestamp,
+            history.last().unwrap().transaction_timestamp
+        );
+    }
+
+    #[test]
+    fn test_block_heights_across_block_boundary() {
+        let new_block = |version: u64, height: u64| Transaction {
+            version,
+            r#type: TransactionType::BlockMetadata as i32,
+            txn_data: Some(TxnData::BlockMetadata(BlockMetadataTransaction {
+                events: vec![Event {
+                    type_str: "0x1::block::NewBlockEvent".to_string(),
+                    data: format!(r#"{{"height":"{}"}}"#, height),
+                    ..Event::default()
+                }],
+                ..BlockMetadataTransaction::default()
+            })),
+            ..Transaction::default()
+        };
+        let mut transactions = vec![
+            new_block(100, 8),
+            user_txn(101, "0xa", 0),
+            user_txn(102, "0xb", 0),
+            new_block(103, 9),
+            user_txn(104, "0xa", 1),
+        ];
+        let mut block_heights = BlockHeightTracker::default();
+        for txn in transactions.iter_mut() {
+            block_heights.set_block_height(txn);
+        }
+        let history = transactions
+            .iter()
+            .filter_map(AccountSequenceNumber::from_transaction)
+            .map(|h| (h.transaction_version, h.block_height))
+            .collect::<Vec<_>>();
+        assert_eq!(history, vec![(101, 8), (102, 8), (104, 9)]);
+    }
+}
@@ -0,0 +1,352 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+
+//! Allowances let a spender move up to an approved amount of an owner's asset. The framework
+//! has no allowances of its own, so these follow the events ERC-20 style modules emit:
+//! `Approval { owner, spender, amount }` when the owner sets an allowance and
+//! `TransferFrom { owner, spender, amount }` when the spender uses it. The asset is the
+//! `metadata` object of the event for fungible assets, or the type argument of the event for
+//! coins, e.g. `0xcafe::allowance::Approval<0x1::aptos_coin::AptosCoin>`. Any module can emit
+//! events with these names, so only those of the configured contract addresses are recorded.
+
+use crate::{
+    db::common::models::token_v2_models::v2_token_utils::ResourceReference,
+    schema::allowance_activities,
+    utils::{
+        database::{execute_with_better_error, ArcDbPool},
+        util::{
+            deserialize_from_string, parse_transaction_timestamp, standardize_address,
+            u64_to_bigdecimal,
+        },
+    },
+};
+use ahash::AHashSet;
+use aptos_protos::transaction::v1::{transaction::TxnData, Event, Transaction};
+use bigdecimal::BigDecimal;
+use diesel::{
+    sql_query,
+    sql_types::{Array, Text},
+};
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// Approving `MAX_U64` approves spending without limit, so spends don't decrement it.
+pub const UNLIMITED_ALLOWANCE: u64 = u64::MAX;
+
+pub const APPROVAL: &str = "approval";
+pub const SPEND: &str = "spend";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct AllowanceEvent {
+    owner: String,
+    spender: String,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    amount: BigDecimal,
+    metadata: Option<ResourceReference>,
+}
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, PartialEq, Serialize)]
+#[diesel(primary_key(transaction_version, event_index))]
+#[diesel(table_name = allowance_activities)]
+pub struct AllowanceActivity {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub owner_address: String,
+    pub spender_address: String,
+    pub asset_type: String,
+    pub activity_type: String,
+    pub amount: BigDecimal,
+    pub is_unlimited: bool,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+}
+
+/// Activity type and coin type, if any, of an `Approval` or `TransferFrom` event type of a
+/// module published at one of `contract_addresses`, which must be standardized.
+fn parse_event_type<'a>(
+    type_str: &'a str,
+    contract_addresses: &AHashSet<String>,
+) -> Option<(&'static str, Option<&'a str>)> {
+    let (struct_path, type_arg) = match type_str.split_once('<') {
+        Some((struct_path, rest)) => (struct_path, Some(rest.strip_suffix('>')?)),
+        None => (type_str, None),
+    };
+    let mut parts = struct_path.splitn(3, "::");
+    let (Some(address), Some(_module), Some(struct_name)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+    if !contract_addresses.contains(&standardize_address(address)) {
+        return None;
+    }
+    let activity_type = match struct_name {
+        "Approval" => APPROVAL,
+        "TransferFrom" => SPEND,
+        _ => return None,
+    };
+    Some((activity_type, type_arg))
+}
+
+impl AllowanceActivity {
+    pub fn from_event(
+        event: &Event,
+        transaction_version: i64,
+        event_index: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
+        contract_addresses: &AHashSet<String>,
+    ) -> Option<Self> {
+        let (activity_type, coin_type) = parse_event_type(&event.type_str, contract_addresses)?;
+        // Other events can share the name, so anything without the fields is skipped
+        let inner: AllowanceEvent = serde_json::from_str(&event.data).ok()?;
+        let asset_type = match (&inner.metadata, coin_type) {
+            (Some(metadata), _) => metadata.get_reference_address(),
+            (None, Some(coin_type)) => coin_type.to_string(),
+            (None, None) => return None,
+        };
+        let is_unlimited =
+            activity_type == APPROVAL && inner.amount >= u64_to_bigdecimal(UNLIMITED_ALLOWANCE);
+        Some(Self {
+            transaction_version,
+            event_index,
+            owner_address: standardize_address(&inner.owner),
+            spender_address: standardize_address(&inner.spender),
+            asset_type,
+            activity_type: activity_type.to_string(),
+            amount: inner.amount,
+            is_unlimited,
+            transaction_timestamp,
+        })
+    }
+
+    /// `contract_addresses` must be standardized.
+    pub fn from_transactions(
+        transactions: &[Transaction],
+        contract_addresses: &AHashSet<String>,
+    ) -> Vec<Self> {
+        let mut activities = vec![];
+        for txn in transactions {
+            let txn_version = txn.version as i64;
+            let default = vec![];
+            let events = match txn.txn_data.as_ref() {
+                Some(TxnData::User(inner)) => &inner.events,
+                _ => &default,
+            };
+            if events.is_empty() {
+                continue;
+            }
+            let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
+            activities.extend(events.iter().enumerate().filter_map(|(index, event)| {
+                Self::from_event(
+                    event,
+                    txn_version,
+                    index as i64,
+                    txn_timestamp,
+                    contract_addresses,
+                )
+            }));
+        }
+        activities
+    }
+}
+
+/// Owners, spenders and assets whose current allowance changed, sorted and without duplicates
+/// so concurrent refreshes lock rows in the same order.
+pub fn allowance_keys(activities: &[AllowanceActivity]) -> Vec<(String, String, String)> {
+    let mut keys = activities
+        .iter()
+        .map(|activity| {
+            (
+                activity.owner_address.clone(),
+                activity.spender_address.clone(),
+                activity.asset_type.clone(),
+            )
+        })
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
+/// Recomputes `current_allowances` from all of `allowance_activities` rather than applying the
+/// batch to the current amounts, since batches are processed concurrently and out of order, and
+/// a spend only decrements the approval before it. `num_activities` only grows, so a
+/// recomputation that missed activities committed concurrently loses to one that saw them.
+const REFRESH_CURRENT_ALLOWANCES_QUERY: &str = "
+WITH keys AS (
+    SELECT * FROM UNNEST($1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[])
+        AS keys (owner_address, spender_address, asset_type)
+),
+activities AS (
+    SELECT a.* FROM allowance_activities a
+    JOIN keys USING (owner_address, spender_address, asset_type)
+),
+latest_approvals AS (
+    SELECT DISTINCT ON (owner_address, spender_address, asset_type) *
+    FROM activities
+    WHERE activity_type = 'approval'
+    ORDER BY owner_address, spender_address, asset_type, transaction_version DESC, event_index DESC
+),
+totals AS (
+    SELECT owner_address, spender_address, asset_type,
+        MAX(transaction_version) AS last_transaction_version,
+        MAX(transaction_timestamp) AS last_transaction_timestamp,
+        COUNT(*) AS num_activities
+    FROM activities
+    GROUP BY owner_address, spender_address, asset_type
+)
+INSERT INTO current_allowances AS c (
+    owner_address, spender_address, asset_type, amount, is_unlimited,
+    approval_transaction_version, last_transaction_version, last_transaction_timestamp,
+    num_activities
+)
+SELECT
+    l.owner_address, l.spender_address, l.asset_type,
+    CASE WHEN l.is_unlimited THEN l.amount ELSE GREATEST(l.amount - COALESCE((
+        SELECT SUM(s.amount) FROM activities s
+        WHERE s.owner_address = l.owner_address
+            AND s.spender_address = l.spender_address
+            AND s.asset_type = l.asset_type
+            AND s.activity_type = 'spend'
+            AND (s.transaction_version, s.event_index) > (l.transaction_version, l.event_index)
+    ), 0), 0) END,
+    l.is_unlimited, l.transaction_version, t.last_transaction_version,
+    t.last_transaction_timestamp, t.num_activities
+FROM latest_approvals l
+JOIN totals t USING (owner_address, spender_address, asset_type)
+ORDER BY l.owner_address, l.spender_address, l.asset_type
+ON CONFLICT (owner_address, spender_address, asset_type) DO UPDATE SET
+    amount = EXCLUDED.amount,
+    is_unlimited = EXCLUDED.is_unlimited,
+    approval_transaction_version = EXCLUDED.approval_transaction_version,
+    last_transaction_version = EXCLUDED.last_transaction_version,
+    last_transaction_timestamp = EXCLUDED.last_transaction_timestamp,
+    num_activities = EXCLUDED.num_activities,
+    inserted_at = NOW()
+WHERE (c.last_transaction_version, c.num_activities)
+    <= (EXCLUDED.last_transaction_version, EXCLUDED.num_activities)
+";
+
+/// Refreshes the current allowances of `keys` once their activities are written. Spends without
+/// an approval indexed before them, e.g. when starting after the approval, leave no row.
+pub async fn refresh_current_allowances(
+    pool: ArcDbPool,
+    keys: &[(String, String, String)],
+) -> diesel::QueryResult<usize> {
+    if keys.is_empty() {
+        return Ok(0);
+    }
+    let (owners, (spenders, assets)): (Vec<String>, (Vec<String>, Vec<String>)) = keys
+        .iter()
+        .cloned()
+        .map(|(owner, spender, asset)| (owner, (spender, asset)))
+        .unzip();
+    let query = sql_query(REFRESH_CURRENT_ALLOWANCES_QUERY)
+        .bind::<Array<Text>, _>(owners)
+        .bind::<Array<Text>, _>(spenders)
+        .bind::<Array<Text>, _>(assets);
+    execute_with_better_error(pool, query, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OWNER: &str = "0x0000000000000000000000000000000000000000000000000000000000000a11";
+    const SPENDER: &str = "0x0000000000000000000000000000000000000000000000000000000000000b0b";
+    const FA: &str = "0x000000000000000000000000000000000000000000000000000000000000000a";
+
+    fn event(type_str: &str, data: serde_json::Value) -> Event {
+        Event {
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    fn activity(event: &Event) -> Option<AllowanceActivity> {
+        let contract_addresses = AHashSet::from([standardize_address("0xcafe")]);
+        AllowanceActivity::from_event(
+            event,
+            1,
+            0,
+            chrono::NaiveDateTime::default(),
+            &contract_addresses,
+        )
+    }
+
+    #[test]
+    fn test_fungible_asset_approval() {
+        let approval = activity(&event(
+            "0xcafe::allowance::Approval",
+            serde_json::json!({
+                "owner": "0xa11",
+                "spender": "0xb0b",
+                "amount": "100",
+                "metadata": {"inner": "0xa"},
+            }),
+        ))
+        .unwrap();
+        assert_eq!(approval.owner_address, OWNER);
+        assert_eq!(approval.spender_address, SPENDER);
+        assert_eq!(approval.asset_type, FA);
+        assert_eq!(approval.activity_type, APPROVAL);
+        assert_eq!(approval.amount, BigDecimal::from(100));
+        assert!(!approval.is_unlimited);
+    }
+
+    #[test]
+    fn test_coin_spend_and_unlimited_approval() {
+        let data = serde_json::json!({"owner": "0xa11", "spender": "0xb0b", "amount": "5"});
+        let spend = activity(&event(
+            "0xcafe::allowance::TransferFrom<0x1::aptos_coin::AptosCoin>",
+            data,
+        ))
+        .unwrap();
+        assert_eq!(spend.asset_type, "0x1::aptos_coin::AptosCoin");
+        assert_eq!(spend.activity_type, SPEND);
+
+        let data = serde_json::json!({
+            "owner": "0xa11",
+            "spender": "0xb0b",
+            "amount": UNLIMITED_ALLOWANCE.to_string(),
+        });
+        let approval = activity(&event(
+            "0xcafe::allowance::Approval<0x1::aptos_coin::AptosCoin>",
+            data,
+        ))
+        .unwrap();
+        assert!(approval.is_unlimited);
+    }
+
+    #[test]
+    fn test_other_events_are_skipped() {
+        let data = serde_json::json!({"owner": "0xa11", "spender": "0xb0b", "amount": "5"});
+        // No asset
+        assert_eq!(activity(&event("0xcafe::allowance::Approval", data)), None);
+        // Another event with the same name
+        let data = serde_json::json!({"approved": true});
+        assert_eq!(
+            activity(&event(
+                "0xcafe::voting::Approval<0x1::string::String>",
+                data
+            )),
+            None
+        );
+        let data = serde_json::json!({"owner": "0xa11", "spender": "0xb0b", "amount": "5"});
+        assert_eq!(
+            activity(&event("0xcafe::allowance::Transfer", data.clone())),
+            None
+        );
+        // Same event from a contract that isn't configured
+        assert_eq!(
+            activity(&event(
+                "0xbad::allowance::TransferFrom<0x1::aptos_coin::AptosCoin>",
+                data
+            )),
+            None
+        );
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use crate::{
+    schema::block_gas_stats,
+    utils::{
+        database::DbPoolConnection,
+        util::{parse_timestamp, parse_transaction_timestamp, u64_to_bigdecimal},
+    },
+};
+use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+use bigdecimal::{BigDecimal, Zero};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::RunQueryDsl;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// Gas totals of the user transactions of a block, or of the part of a block in one batch.
+/// Batches don't line up with blocks, so the parts of a block are added up on insert, and
+/// `block_gas_stats_parts` keeps track of the parts already added.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, PartialEq, Serialize)]
+#[diesel(primary_key(block_height))]
+#[diesel(table_name = block_gas_stats)]
+pub struct BlockGasStats {
+    pub block_height: i64,
+    pub first_version: i64,
+    pub last_version: i64,
+    /// Timestamp of the block metadata transaction, None if the block began in an earlier batch
+    pub block_timestamp: Option<chrono::NaiveDateTime>,
+    pub transaction_count: i64,
+    pub total_gas_used: BigDecimal,
+    /// Sum of gas used times gas unit price, in octas
+    pub total_fee_octas: BigDecimal,
+    /// Sum of the gas unit prices, for `avg_gas_unit_price`
+    pub total_gas_unit_price: BigDecimal,
+}
+
+impl BlockGasStats {
+    fn new(block_height: i64, version: i64) -> Self {
+        Self {
+            block_height,
+            first_version: version,
+            last_version: version,
+            block_timestamp: None,
+            transaction_count: 0,
+            total_gas_used: BigDecimal::zero(),
+            total_fee_octas: BigDecimal::zero(),
+            total_gas_unit_price: BigDecimal::zero(),
+        }
+    }
+
+    /// Stats of each block in the batch, in block order. A block starts at its block metadata
+    /// transaction, so the first entry has no `block_timestamp` if the batch begins mid-block.
+    pub fn from_transactions(transactions: &[Transaction]) -> Vec<Self> {
+        let mut blocks: Vec<Self> = vec![];
+        for txn in transactions {
+            let txn_version = txn.version as i64;
+            let block_height = txn.block_height as i64;
+            let block = match blocks.last_mut() {
+                Some(block) if block.block_height == block_height => block,
+                _ => {
+                    blocks.push(Self::new(block_height, txn_version));
+                    blocks.last_mut().unwrap()
+                },
+            };
+            block.last_version = txn_version;
+            match txn.txn_data.as_ref() {
+                Some(TxnData::BlockMetadata(_)) => {
+                    block.block_timestamp = Some(parse_transaction_timestamp(
+                        txn.timestamp.as_ref(),
+                        txn_version,
+                    ));
+                },
+                Some(TxnData::User(user_txn)) => {
+                    let gas_used = u64_to_bigdecimal(txn.info.as_ref().unwrap().gas_used);
+                    let gas_unit_price =
+                        u64_to_bigdecimal(user_txn.request.as_ref().unwrap().gas_unit_price);
+                    block.transaction_count += 1;
+                    block.total_fee_octas += &gas_used * &gas_unit_price;
+                    block.total_gas_used += gas_used;
+                    block.total_gas_unit_price += gas_unit_price;
+                },
+                _ => {},
+            }
+        }
+        blocks
+    }
+
+    /// Whether the block metadata transaction is part of these stats.
+    pub fn has_block_start(&self) -> bool {
+        self.block_timestamp.is_some()
+    }
+
+    pub async fn exists(
+        block_height: i64,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<bool> {
+        block_gas_stats::table
+            .filter(block_gas_stats::block_height.eq(block_height))
+            .select(block_gas_stats::block_height)
+            .first::<i64>(conn)
+            .await
+            .optional()
+            .map(|row| row.is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::{
+        transaction::v1::{
+            transaction::TransactionType, BlockMetadataTransaction, TransactionInfo,
+            UserTransaction, UserTransactionRequest,
+        },
+        util::timestamp::Timestamp,
+    };
+
+    fn txn(
+        version: u64,
+        block_height: u64,
+        txn_data: Option<TxnData>,
+        gas_used: u64,
+    ) -> Transaction {
+        Transaction {
+            version,
+            block_height,
+            timestamp: Some(Timestamp {
+                seconds: version as i64,
+                nanos: 0,
+            }),
+            info: Some(TransactionInfo {
+                gas_used,
+                ..TransactionInfo::default()
+            }),
+            txn_data,
+            ..Transaction::default()
+        }
+    }
+
+    fn block_metadata(version: u64, block_height: u64) -> Transaction {
+        Transaction {
+            r#type: TransactionType::BlockMetadata as i32,
+            ..txn(
+                version,
+                block_height,
+                Some(TxnData::BlockMetadata(BlockMetadataTransaction::default())),
+                0,
+            )
+        }
+    }
+
+    fn user(version: u64, block_height: u64, gas_used: u64, gas_unit_price: u64) -> Transaction {
+        let user_txn = UserTransaction {
+            request: Some(UserTransactionRequest {
+                gas_unit_price,
+                ..UserTransactionRequest::default()
+            }),
+            ..UserTransaction::default()
+        };
+        Transaction {
+            r#type: TransactionType::User as i32,
+            ..txn(
+                version,
+                block_height,
+                Some(TxnData::User(user_txn)),
+                gas_used,
+            )
+        }
+    }
+
+    fn state_checkpoint(version: u64, block_height: u64) -> Transaction {
+        Transaction {
+            r#type: TransactionType::StateCheckpoint as i32,
+            ..txn(version, block_height, None, 0)
+        }
+    }
+
+    #[test]
+    fn test_stats_of_complete_blocks() {
+        let transactions = vec![
+            // The end of block 9, which started before the batch
+            user(99, 9, 7, 100),
+            state_checkpoint(100, 9),
+            block_metadata(101, 10),
+            user(102, 10, 10, 100),
+            user(103, 10, 20, 150),
+            state_checkpoint(104, 10),
+            block_metadata(105, 11),
+            user(106, 11, 5, 200),
+            state_checkpoint(107, 11),
+        ];
+        let stats = BlockGasStats::from_transactions(&transactions);
+        assert_eq!(stats.len(), 3);
+        assert!(!stats[0].has_block_start());
+        assert_eq!(stats[0].transaction_count, 1);
+
+        let block_timestamp = parse_timestamp(transactions[2].timestamp.as_ref().unwrap(), 101);
+        let expected = BlockGasStats {
+            block_height: 10,
+            first_version: 101,
+            last_version: 104,
+            block_timestamp: Some(block_timestamp),
+            transaction_count: 2,
+            total_gas_used: 30.into(),
+            total_fee_octas: 4000.into(),
+            total_gas_unit_price: 250.into(),
+        };
+        assert_eq!(stats[1], expected);
+        assert_eq!(stats[2].block_height, 11);
+        assert_eq!((stats[2].first_version, stats[2].last_version), (105, 107));
+        assert_eq!(stats[2].transaction_count, 1);
+        assert_eq!(stats[2].total_gas_used, 5.into());
+        assert_eq!(stats[2].total_fee_octas, 1000.into());
+    }
+}
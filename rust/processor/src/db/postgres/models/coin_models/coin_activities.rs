@@ -25,8 +25,8 @@ use crate::{
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
         util::{
-            get_entry_function_from_user_request, standardize_address, u64_to_bigdecimal,
-            APTOS_COIN_TYPE_STR,
+            get_entry_function_from_user_request, parse_transaction_timestamp, standardize_address,
+            u64_to_bigdecimal, APTOS_COIN_TYPE_STR,
         },
     },
 };
@@ -36,7 +36,7 @@ use aptos_protos::transaction::v1::{
     Transaction as TransactionPB, TransactionInfo, UserTransactionRequest,
 };
 use bigdecimal::{BigDecimal, Zero};
-use chrono::NaiveDateTime;
+use chrono::SubsecRound;
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
 
@@ -119,14 +119,10 @@ impl CoinActivity {
             .info
             .as_ref()
             .expect("Transaction info doesn't exist!");
-        let txn_timestamp = transaction
-            .timestamp
-            .as_ref()
-            .expect("Transaction timestamp doesn't exist!")
-            .seconds;
-        #[allow(deprecated)]
+        // Stored to the second
         let txn_timestamp =
-            NaiveDateTime::from_timestamp_opt(txn_timestamp, 0).expect("Txn Timestamp is invalid!");
+            parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version)
+                .trunc_subsecs(0);
 
         // Handling gas first
         let mut entry_function_id_str = None;
@@ -0,0 +1,108 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use crate::{schema::block_end_transactions, utils::util::parse_transaction_timestamp};
+use aptos_protos::transaction::v1::{transaction::TxnData, Transaction, TransactionType};
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// The transaction closing a block. Before block epilogues were introduced every block ended
+/// with a state checkpoint, which carries no data of its own beyond where it sits in the chain.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(version))]
+#[diesel(table_name = block_end_transactions)]
+pub struct BlockEndTransaction {
+    pub version: i64,
+    pub block_height: i64,
+    pub epoch: i64,
+    pub transaction_type: String,
+    pub timestamp: chrono::NaiveDateTime,
+}
+
+impl BlockEndTransaction {
+    /// Returns `None` for anything but state checkpoint and block epilogue transactions.
+    pub fn from_transaction(transaction: &Transaction) -> Option<Self> {
+        match transaction.txn_data.as_ref()? {
+            TxnData::StateCheckpoint(_) | TxnData::BlockEpilogue(_) => {},
+            TxnData::BlockMetadata(_)
+            | TxnData::Genesis(_)
+            | TxnData::User(_)
+            | TxnData::Validator(_) => return None,
+        }
+        let version = transaction.version as i64;
+        Some(Self {
+            version,
+            block_height: transaction.block_height as i64,
+            epoch: transaction.epoch as i64,
+            transaction_type: TransactionType::try_from(transaction.r#type)
+                .expect("Transaction type doesn't exist!")
+                .as_str_name()
+                .to_string(),
+            timestamp: parse_transaction_timestamp(transaction.timestamp.as_ref(), version),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::util::parse_timestamp;
+    use aptos_protos::{
+        transaction::v1::{BlockEpilogueTransaction, TransactionInfo, UserTransaction},
+        util::timestamp::Timestamp,
+    };
+
+    // Block epilogue as it comes off the stream, trimmed to the fields the processor reads
+    fn block_epilogue_transaction() -> Transaction {
+        Transaction {
+            version: 2_175_000_015,
+            block_height: 283_000_100,
+            epoch: 9_512,
+            timestamp: Some(Timestamp {
+                seconds: 1_736_900_000,
+                nanos: 123_456_000,
+            }),
+            r#type: TransactionType::BlockEpilogue as i32,
+            info: Some(TransactionInfo {
+                success: true,
+                vm_status: "Executed successfully".to_string(),
+                ..Default::default()
+            }),
+            txn_data: Some(TxnData::BlockEpilogue(BlockEpilogueTransaction::default())),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_block_epilogue_transaction() {
+        let bet = BlockEndTransaction::from_transaction(&block_epilogue_transaction()).unwrap();
+        assert_eq!(bet.version, 2_175_000_015);
+        assert_eq!(bet.block_height, 283_000_100);
+        assert_eq!(bet.epoch, 9_512);
+        assert_eq!(bet.transaction_type, "TRANSACTION_TYPE_BLOCK_EPILOGUE");
+        assert_eq!(
+            bet.timestamp,
+            parse_timestamp(
+                &Timestamp {
+                    seconds: 1_736_900_000,
+                    nanos: 123_456_000,
+                },
+                2_175_000_015
+            )
+        );
+    }
+
+    #[test]
+    fn test_other_transactions_are_skipped() {
+        let user_transaction = Transaction {
+            r#type: TransactionType::User as i32,
+            txn_data: Some(TxnData::User(UserTransaction::default())),
+            ..block_epilogue_transaction()
+        };
+        assert!(BlockEndTransaction::from_transaction(&user_transaction).is_none());
+    }
+}
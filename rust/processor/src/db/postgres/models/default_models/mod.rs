@@ -1,6 +1,8 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod block_end_transactions;
 pub mod block_metadata_transactions;
 pub mod move_resources;
 pub mod move_tables;
+pub mod transactions;
@@ -3,10 +3,15 @@
 
 #![allow(clippy::extra_unused_lifetimes)]
 
-use crate::{schema::move_resources, utils::util::standardize_address};
+use crate::{
+    schema::{current_move_resources, move_resources},
+    utils::util::{hash_str, standardize_address},
+};
+use ahash::AHashMap;
 use anyhow::{Context, Result};
 use aptos_protos::transaction::v1::{
-    DeleteResource, MoveStructTag as MoveStructTagPB, WriteResource,
+    write_set_change::Change as WriteSetChangeEnum, DeleteResource,
+    MoveStructTag as MoveStructTagPB, Transaction, WriteResource,
 };
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
@@ -28,6 +33,23 @@ pub struct MoveResource {
     pub state_key_hash: String,
 }
 
+/// Latest state of a resource. Deleted resources are kept with `is_deleted` set and no data.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(address, resource_type_hash))]
+#[diesel(table_name = current_move_resources)]
+pub struct CurrentMoveResource {
+    pub address: String,
+    pub resource_type_hash: String,
+    pub resource_type: String,
+    pub module: String,
+    pub name: String,
+    pub generic_type_params: Option<serde_json::Value>,
+    pub data: Option<serde_json::Value>,
+    pub is_deleted: bool,
+    pub state_key_hash: String,
+    pub last_transaction_version: i64,
+}
+
 pub struct MoveStructTag {
     address: String,
     pub module: String,
@@ -137,8 +159,149 @@ impl MoveResource {
     }
 }
 
+impl CurrentMoveResource {
+    pub fn from_move_resource(move_resource: MoveResource) -> Self {
+        Self {
+            address: move_resource.address,
+            resource_type_hash: hash_str(&move_resource.type_),
+            resource_type: move_resource.type_,
+            module: move_resource.module,
+            name: move_resource.name,
+            generic_type_params: move_resource.generic_type_params,
+            data: move_resource.data,
+            is_deleted: move_resource.is_deleted,
+            state_key_hash: move_resource.state_key_hash,
+            last_transaction_version: move_resource.transaction_version,
+        }
+    }
+
+    /// Latest state in the batch of each resource written or deleted, sorted by pk to avoid
+    /// deadlocks between concurrent inserts.
+    pub fn from_transactions(transactions: &[Transaction]) -> Vec<Self> {
+        let mut current_move_resources = AHashMap::new();
+        for transaction in transactions {
+            let Some(transaction_info) = transaction.info.as_ref() else {
+                continue;
+            };
+            let version = transaction.version as i64;
+            let block_height = transaction.block_height as i64;
+            for (index, wsc) in transaction_info.changes.iter().enumerate() {
+                let move_resource = match wsc.change.as_ref() {
+                    Some(WriteSetChangeEnum::WriteResource(inner)) => {
+                        MoveResource::from_write_resource(
+                            inner,
+                            index as i64,
+                            version,
+                            block_height,
+                        )
+                    },
+                    Some(WriteSetChangeEnum::DeleteResource(inner)) => {
+                        MoveResource::from_delete_resource(
+                            inner,
+                            index as i64,
+                            version,
+                            block_height,
+                        )
+                    },
+                    _ => continue,
+                };
+                let current_move_resource = Self::from_move_resource(move_resource);
+                current_move_resources.insert(
+                    (
+                        current_move_resource.address.clone(),
+                        current_move_resource.resource_type_hash.clone(),
+                    ),
+                    current_move_resource,
+                );
+            }
+        }
+        let mut current_move_resources =
+            current_move_resources.into_values().collect::<Vec<Self>>();
+        current_move_resources.sort_by(|a, b| {
+            (&a.address, &a.resource_type_hash).cmp(&(&b.address, &b.resource_type_hash))
+        });
+        current_move_resources
+    }
+}
+
 impl MoveStructTag {
     pub fn get_address(&self) -> String {
         standardize_address(self.address.as_str())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{TransactionInfo, WriteSetChange};
+
+    const COIN_STORE: &str = "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>";
+
+    fn coin_store_tag() -> MoveStructTagPB {
+        MoveStructTagPB {
+            address: "0x1".to_string(),
+            module: "coin".to_string(),
+            name: "CoinStore".to_string(),
+            generic_type_params: vec![],
+        }
+    }
+
+    fn transaction(version: u64, change: WriteSetChangeEnum) -> Transaction {
+        let change = WriteSetChange {
+            change: Some(change),
+            ..WriteSetChange::default()
+        };
+        Transaction {
+            version,
+            info: Some(TransactionInfo {
+                changes: vec![change],
+                ..TransactionInfo::default()
+            }),
+            ..Transaction::default()
+        }
+    }
+
+    fn write_coin_store(version: u64, value: u64) -> Transaction {
+        let write_resource = WriteResource {
+            address: "0xa".to_string(),
+            state_key_hash: vec![1; 32],
+            r#type: Some(coin_store_tag()),
+            type_str: COIN_STORE.to_string(),
+            data: format!(r#"{{"coin":{{"value":"{}"}}}}"#, value),
+        };
+        transaction(version, WriteSetChangeEnum::WriteResource(write_resource))
+    }
+
+    #[test]
+    fn test_latest_write_of_a_resource_wins() {
+        let transactions = vec![write_coin_store(1, 100), write_coin_store(2, 50)];
+        let current_move_resources = CurrentMoveResource::from_transactions(&transactions);
+        assert_eq!(current_move_resources.len(), 1);
+        let current = &current_move_resources[0];
+        assert_eq!(current.address, standardize_address("0xa"));
+        assert_eq!(current.resource_type, COIN_STORE);
+        assert_eq!(current.resource_type_hash, hash_str(COIN_STORE));
+        assert_eq!(current.last_transaction_version, 2);
+        assert_eq!(
+            current.data,
+            Some(serde_json::json!({"coin": {"value": "50"}}))
+        );
+        assert!(!current.is_deleted);
+
+        let delete_resource = DeleteResource {
+            address: "0xa".to_string(),
+            state_key_hash: vec![1; 32],
+            r#type: Some(coin_store_tag()),
+            type_str: COIN_STORE.to_string(),
+        };
+        let transactions = vec![
+            write_coin_store(3, 10),
+            transaction(4, WriteSetChangeEnum::DeleteResource(delete_resource)),
+        ];
+        let current_move_resources = CurrentMoveResource::from_transactions(&transactions);
+        assert_eq!(current_move_resources.len(), 1);
+        assert!(current_move_resources[0].is_deleted);
+        assert_eq!(current_move_resources[0].data, None);
+        assert_eq!(current_move_resources[0].last_transaction_version, 4);
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use crate::{
+    schema::transactions,
+    utils::{
+        counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
+        database::DbPoolConnection,
+        util::{
+            get_clean_payload, get_clean_writeset, get_payload_type, standardize_address,
+            u64_to_bigdecimal,
+        },
+    },
+};
+use ahash::AHashSet;
+use aptos_protos::transaction::v1::{
+    transaction::{TransactionType, TxnData},
+    Transaction as TransactionPB,
+};
+use bigdecimal::BigDecimal;
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(version))]
+#[diesel(table_name = transactions)]
+pub struct Transaction {
+    pub version: i64,
+    pub block_height: i64,
+    pub hash: String,
+    pub type_: String,
+    pub payload: Option<serde_json::Value>,
+    pub state_change_hash: String,
+    pub event_root_hash: String,
+    pub state_checkpoint_hash: Option<String>,
+    pub gas_used: BigDecimal,
+    pub success: bool,
+    pub vm_status: String,
+    pub accumulator_root_hash: String,
+    pub num_events: i64,
+    pub num_write_set_changes: i64,
+    pub epoch: i64,
+    pub payload_type: Option<String>,
+}
+
+impl Transaction {
+    /// Returns `None` if the transaction has no info, which the stream always sends.
+    pub fn from_transaction(transaction: &TransactionPB) -> Option<Self> {
+        let version = transaction.version as i64;
+        let info = transaction.info.as_ref()?;
+        let (payload, payload_type, num_events) = match transaction.txn_data.as_ref() {
+            Some(TxnData::User(user_txn)) => {
+                let payload = user_txn
+                    .request
+                    .as_ref()
+                    .and_then(|request| request.payload.as_ref());
+                (
+                    payload.and_then(|payload| get_clean_payload(payload, version)),
+                    payload.map(get_payload_type),
+                    user_txn.events.len(),
+                )
+            },
+            Some(TxnData::Genesis(genesis_txn)) => (
+                genesis_txn
+                    .payload
+                    .as_ref()
+                    .and_then(|payload| get_clean_writeset(payload, version)),
+                None,
+                genesis_txn.events.len(),
+            ),
+            Some(TxnData::BlockMetadata(block_metadata_txn)) => {
+                (None, None, block_metadata_txn.events.len())
+            },
+            Some(TxnData::Validator(validator_txn)) => (None, None, validator_txn.events.len()),
+            Some(TxnData::StateCheckpoint(_)) | Some(TxnData::BlockEpilogue(_)) => (None, None, 0),
+            None => {
+                PROCESSOR_UNKNOWN_TYPE_COUNT
+                    .with_label_values(&["Transaction"])
+                    .inc();
+                tracing::warn!(
+                    transaction_version = version,
+                    "Transaction data doesn't exist",
+                );
+                (None, None, 0)
+            },
+        };
+        Some(Self {
+            version,
+            block_height: transaction.block_height as i64,
+            hash: standardize_address(hex::encode(info.hash.as_slice()).as_str()),
+            type_: TransactionType::try_from(transaction.r#type)
+                .expect("Transaction type doesn't exist!")
+                .as_str_name()
+                .to_string(),
+            payload,
+            state_change_hash: standardize_address(
+                hex::encode(info.state_change_hash.as_slice()).as_str(),
+            ),
+            event_root_hash: standardize_address(
+                hex::encode(info.event_root_hash.as_slice()).as_str(),
+            ),
+            state_checkpoint_hash: info
+                .state_checkpoint_hash
+                .as_ref()
+                .map(|hash| standardize_address(hex::encode(hash).as_str())),
+            gas_used: u64_to_bigdecimal(info.gas_used),
+            success: info.success,
+            vm_status: info.vm_status.clone(),
+            accumulator_root_hash: standardize_address(
+                hex::encode(info.accumulator_root_hash.as_slice()).as_str(),
+            ),
+            num_events: num_events as i64,
+            num_write_set_changes: info.changes.len() as i64,
+            epoch: transaction.epoch as i64,
+            payload_type,
+        })
+    }
+
+    /// Versions between `start_version` and `end_version`, inclusive, that are already written.
+    pub async fn existing_versions(
+        start_version: i64,
+        end_version: i64,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<AHashSet<i64>> {
+        let versions = transactions::table
+            .select(transactions::version)
+            .filter(transactions::version.between(start_version, end_version))
+            .load::<i64>(conn)
+            .await?;
+        Ok(versions.into_iter().collect())
+    }
+}
@@ -6,6 +6,7 @@
 use crate::{
     db::common::models::event_models::raw_events::{EventConvertible, RawEvent},
     schema::events,
+    utils::util::ParseContext,
 };
 use aptos_protos::transaction::v1::Event as EventPB;
 use field_count::FieldCount;
@@ -73,7 +74,9 @@ impl EventConvertible for Event {
             transaction_version: raw.transaction_version,
             transaction_block_height: raw.transaction_block_height,
             type_: raw.type_.clone(),
-            data: serde_json::from_str(&raw.data).unwrap(),
+            data: serde_json::from_str(&raw.data)
+                .parse_context(raw.transaction_version, raw.event_index, &raw.type_)
+                .unwrap(),
             event_index: raw.event_index,
             indexed_type: raw.indexed_type.clone(),
         }
@@ -0,0 +1,135 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use super::events::Event;
+use crate::{schema::typed_events, utils::util::standardize_address};
+use bigdecimal::BigDecimal;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::str::FromStr;
+
+/// Refers to the address of the event's handle rather than a field of its data, e.g. the
+/// receiver of a `0x1::coin::DepositEvent`.
+pub const EVENT_ACCOUNT_ADDRESS_FIELD: &str = "$account_address";
+
+/// Where the typed columns of an event type come from. Each is a field of the event data, with
+/// dots for nested fields, e.g. `coin.value`, or `$account_address`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct TypedEventFields {
+    #[serde(default)]
+    pub amount: Option<String>,
+    #[serde(default)]
+    pub from_address: Option<String>,
+    #[serde(default)]
+    pub to_address: Option<String>,
+}
+
+/// Fields of an event pulled out of its JSON data into typed columns, so they can be queried
+/// without JSON extraction.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, PartialEq, Serialize)]
+#[diesel(primary_key(transaction_version, event_index))]
+#[diesel(table_name = typed_events)]
+pub struct TypedEvent {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub type_: String,
+    pub amount: Option<BigDecimal>,
+    pub from_address: Option<String>,
+    pub to_address: Option<String>,
+}
+
+impl TypedEvent {
+    pub fn from_event(event: &Event, fields: &TypedEventFields) -> Self {
+        let field =
+            |path: &Option<String>| path.as_deref().and_then(|path| event_field(event, path));
+        Self {
+            transaction_version: event.transaction_version,
+            event_index: event.event_index,
+            type_: event.type_.clone(),
+            amount: field(&fields.amount).and_then(|value| match value {
+                Value::String(amount) => BigDecimal::from_str(&amount).ok(),
+                Value::Number(amount) => BigDecimal::from_str(&amount.to_string()).ok(),
+                _ => None,
+            }),
+            from_address: field(&fields.from_address)
+                .and_then(|value| value.as_str().map(standardize_address)),
+            to_address: field(&fields.to_address)
+                .and_then(|value| value.as_str().map(standardize_address)),
+        }
+    }
+}
+
+/// None if the event doesn't have the field.
+fn event_field(event: &Event, path: &str) -> Option<Value> {
+    if path == EVENT_ACCOUNT_ADDRESS_FIELD {
+        return Some(Value::String(event.account_address.clone()));
+    }
+    path.split('.')
+        .try_fold(&event.data, |value, key| value.get(key))
+        .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(type_: &str, data: Value) -> Event {
+        Event {
+            sequence_number: 0,
+            creation_number: 0,
+            account_address: standardize_address("0xa"),
+            transaction_version: 1,
+            transaction_block_height: 0,
+            type_: type_.to_string(),
+            data,
+            event_index: 0,
+            indexed_type: type_.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_coin_deposit_and_withdraw_fields() {
+        let data = serde_json::json!({"amount": "1000"});
+        let deposit = event("0x1::coin::DepositEvent", data);
+        let deposit_fields = TypedEventFields {
+            amount: Some("amount".to_string()),
+            to_address: Some(EVENT_ACCOUNT_ADDRESS_FIELD.to_string()),
+            ..Default::default()
+        };
+        let typed = TypedEvent::from_event(&deposit, &deposit_fields);
+        assert_eq!(typed.amount, Some(BigDecimal::from(1000)));
+        assert_eq!(typed.to_address, Some(standardize_address("0xa")));
+        assert_eq!(typed.from_address, None);
+
+        let data = serde_json::json!({
+            "account": "0xb",
+            "amount": "25",
+            "coin_type": "0x1::aptos_coin::AptosCoin",
+        });
+        let withdraw = event("0x1::coin::CoinWithdraw", data);
+        let withdraw_fields = TypedEventFields {
+            amount: Some("amount".to_string()),
+            from_address: Some("account".to_string()),
+            ..Default::default()
+        };
+        let typed = TypedEvent::from_event(&withdraw, &withdraw_fields);
+        assert_eq!(typed.amount, Some(BigDecimal::from(25)));
+        assert_eq!(typed.from_address, Some(standardize_address("0xb")));
+
+        // Missing or unparseable fields are left empty
+        let data = serde_json::json!({"amount": {"value": "25"}});
+        let odd = event("0x1::coin::CoinWithdraw", data);
+        let typed = TypedEvent::from_event(&odd, &withdraw_fields);
+        assert_eq!((typed.amount, typed.from_address), (None, None));
+        let nested_fields = TypedEventFields {
+            amount: Some("amount.value".to_string()),
+            ..Default::default()
+        };
+        let typed = TypedEvent::from_event(&odd, &nested_fields);
+        assert_eq!(typed.amount, Some(BigDecimal::from(25)));
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::schema::failed_events;
+use aptos_protos::transaction::v1::Event as EventPB;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// An event that a processor failed to parse, kept with its raw payload so it can be inspected
+/// and reprocessed later instead of failing the whole batch.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(processor, transaction_version, event_index))]
+#[diesel(table_name = failed_events)]
+pub struct FailedEvent {
+    pub processor: String,
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub type_: String,
+    pub data: String,
+    pub error: String,
+}
+
+impl FailedEvent {
+    pub fn from_event(
+        processor: &str,
+        event: &EventPB,
+        transaction_version: i64,
+        event_index: i64,
+        error: &anyhow::Error,
+    ) -> Self {
+        Self {
+            processor: processor.to_string(),
+            transaction_version,
+            event_index,
+            type_: event.type_str.clone(),
+            data: event.data.clone(),
+            error: format!("{:#}", error),
+        }
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+
+use super::v2_fungible_asset_utils::{PairCreationEvent, PairedCoinType};
+use crate::{
+    db::postgres::models::resources::FromWriteResource,
+    schema::coin_to_fa_mapping,
+    utils::util::{hash_str, parse_transaction_timestamp, standardize_address, ParseContext},
+};
+use ahash::AHashMap;
+use aptos_protos::transaction::v1::{transaction::TxnData, write_set_change::Change, Transaction};
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// A coin and the fungible asset it was paired with by the coin to fungible asset migration.
+/// Coins that were never paired and fungible assets that didn't start out as a coin have no
+/// mapping, so queries spanning both should outer join on it.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, PartialEq, Serialize)]
+#[diesel(primary_key(coin_type_hash))]
+#[diesel(table_name = coin_to_fa_mapping)]
+pub struct CoinToFaMapping {
+    pub coin_type_hash: String,
+    pub coin_type: String,
+    pub fa_metadata_address: String,
+    pub transaction_version: i64,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+}
+
+impl CoinToFaMapping {
+    /// Pairings seen in the batch, from the `PairCreation` event and from `PairedCoinType` being
+    /// written to the metadata object. A pairing never changes, so only the first sighting of
+    /// each coin is kept. Fails if a pairing can't be parsed, rather than dropping it.
+    pub fn from_transactions(transactions: &[Transaction]) -> anyhow::Result<Vec<Self>> {
+        let mut mappings: AHashMap<String, Self> = AHashMap::new();
+        for txn in transactions {
+            let txn_version = txn.version as i64;
+            let Some(info) = txn.info.as_ref() else {
+                continue;
+            };
+            let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
+            let default = vec![];
+            let events = match txn.txn_data.as_ref() {
+                Some(TxnData::BlockMetadata(inner)) => &inner.events,
+                Some(TxnData::Genesis(inner)) => &inner.events,
+                Some(TxnData::User(inner)) => &inner.events,
+                Some(TxnData::Validator(inner)) => &inner.events,
+                _ => &default,
+            };
+            let mut pairings = vec![];
+            for (index, event) in events.iter().enumerate() {
+                if let Some(inner) = PairCreationEvent::from_event(
+                    &event.type_str,
+                    &event.data,
+                    txn_version,
+                    index as i64,
+                )? {
+                    pairings.push((
+                        inner.coin_type.to_string(),
+                        inner.fungible_asset_metadata_address,
+                    ));
+                }
+            }
+            for (index, wsc) in info.changes.iter().enumerate() {
+                let Some(Change::WriteResource(write_resource)) = wsc.change.as_ref() else {
+                    continue;
+                };
+                if let Some(inner) = PairedCoinType::from_write_resource(write_resource)
+                    .parse_context(txn_version, index as i64, &write_resource.type_str)?
+                {
+                    pairings.push((inner.coin_type.to_string(), write_resource.address.clone()));
+                }
+            }
+            for (coin_type, fa_metadata_address) in pairings {
+                mappings.entry(coin_type.clone()).or_insert_with(|| Self {
+                    coin_type_hash: hash_str(&coin_type),
+                    coin_type,
+                    fa_metadata_address: standardize_address(&fa_metadata_address),
+                    transaction_version: txn_version,
+                    transaction_timestamp: txn_timestamp,
+                });
+            }
+        }
+        let mut mappings = mappings.into_values().collect::<Vec<_>>();
+        // Sorted to avoid postgres deadlocks
+        mappings.sort_by(|a, b| a.coin_type_hash.cmp(&b.coin_type_hash));
+        Ok(mappings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::{
+        transaction::v1::{
+            Event, MoveStructTag, TransactionInfo, UserTransaction, WriteResource, WriteSetChange,
+        },
+        util::timestamp::Timestamp,
+    };
+
+    const MOON_COIN: &str =
+        "0x66c34778730acbb120cefa57a3d98fd21e0c8b3a51e9baee530088b2e444e94c::moon_coin::MoonCoin";
+    const MOON_COIN_FA: &str = "0xf772c28c069aa7e4417d85d771957eb3c5c11b5bf90b1965cda23b899ebc0384";
+
+    fn transaction(version: u64, events: Vec<Event>, changes: Vec<WriteSetChange>) -> Transaction {
+        let user_transaction = UserTransaction {
+            events,
+            ..UserTransaction::default()
+        };
+        Transaction {
+            version,
+            timestamp: Some(Timestamp::default()),
+            info: Some(TransactionInfo {
+                changes,
+                ..TransactionInfo::default()
+            }),
+            txn_data: Some(TxnData::User(user_transaction)),
+            ..Transaction::default()
+        }
+    }
+
+    fn event(type_str: &str, data: serde_json::Value) -> Event {
+        Event {
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    fn write_resource(
+        address: &str,
+        module: &str,
+        name: &str,
+        data: serde_json::Value,
+    ) -> WriteSetChange {
+        let struct_tag = MoveStructTag {
+            address: "0x1".to_string(),
+            module: module.to_string(),
+            name: name.to_string(),
+            generic_type_params: vec![],
+        };
+        let write_resource = WriteResource {
+            address: address.to_string(),
+            type_str: format!("0x1::{}::{}", module, name),
+            r#type: Some(struct_tag),
+            data: data.to_string(),
+            ..WriteResource::default()
+        };
+        WriteSetChange {
+            change: Some(Change::WriteResource(write_resource)),
+            ..WriteSetChange::default()
+        }
+    }
+
+    /// TypeInfo as it appears in event and resource data, with hex encoded names.
+    fn type_info(account_address: &str, module_name: &str, struct_name: &str) -> serde_json::Value {
+        serde_json::json!({
+            "account_address": account_address,
+            "module_name": format!("0x{}", hex::encode(module_name)),
+            "struct_name": format!("0x{}", hex::encode(struct_name)),
+        })
+    }
+
+    #[test]
+    fn test_mappings_from_pair_creation_and_paired_coin_type() {
+        let moon_coin_type = type_info(
+            "0x66c34778730acbb120cefa57a3d98fd21e0c8b3a51e9baee530088b2e444e94c",
+            "moon_coin",
+            "MoonCoin",
+        );
+        let data = serde_json::json!({
+            "coin_type": moon_coin_type,
+            "fungible_asset_metadata_address": MOON_COIN_FA,
+        });
+        let pair_creation = event("0x1::coin::PairCreation", data);
+        // Only a coin, no pairing
+        let data = serde_json::json!({"amount": "1"});
+        let deposit = event("0x1::coin::DepositEvent", data);
+        let data = serde_json::json!({"type": type_info("0x1", "aptos_coin", "AptosCoin")});
+        let apt_paired = write_resource("0xa", "coin", "PairedCoinType", data);
+        // Only a fungible asset, no pairing
+        let data = serde_json::json!({"name": "FA", "symbol": "FA"});
+        let fa_only = write_resource("0xb", "fungible_asset", "Metadata", data);
+        // Seen again in a later transaction, the first sighting is kept
+        let data = serde_json::json!({"type": moon_coin_type});
+        let moon_paired = write_resource(MOON_COIN_FA, "coin", "PairedCoinType", data);
+        let transactions = [
+            transaction(1, vec![pair_creation, deposit], vec![apt_paired, fa_only]),
+            transaction(2, vec![], vec![moon_paired]),
+        ];
+
+        let mut mappings = CoinToFaMapping::from_transactions(&transactions)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.coin_type, m.fa_metadata_address, m.transaction_version))
+            .collect::<Vec<_>>();
+        mappings.sort();
+        let expected = [
+            (
+                "0x1::aptos_coin::AptosCoin".to_string(),
+                standardize_address("0xa"),
+                1,
+            ),
+            (MOON_COIN.to_string(), MOON_COIN_FA.to_string(), 1),
+        ];
+        assert_eq!(mappings, expected);
+    }
+
+    #[test]
+    fn test_malformed_pairing_fails_the_batch() {
+        let data = serde_json::json!({"coin_type": "not a type info"});
+        let pair_creation = event("0x1::coin::PairCreation", data);
+        let transactions = [transaction(1, vec![pair_creation], vec![])];
+
+        assert!(CoinToFaMapping::from_transactions(&transactions).is_err());
+    }
+}
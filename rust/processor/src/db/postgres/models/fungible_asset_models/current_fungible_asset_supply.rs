@@ -0,0 +1,272 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use crate::{
+    db::{
+        common::models::fungible_asset_models::raw_v2_fungible_asset_activities::RawFungibleAssetActivity,
+        postgres::models::resources::{FromWriteResource, V2FungibleAssetResource},
+    },
+    schema::current_fungible_asset_supply,
+    utils::{
+        database::DbPoolConnection,
+        util::{parse_transaction_timestamp, standardize_address, ParseContext},
+    },
+};
+use ahash::AHashMap;
+use aptos_protos::transaction::v1::{write_set_change::Change, Transaction};
+use bigdecimal::{BigDecimal, Zero};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+const DEPOSIT_EVENT_TYPES: [&str; 2] = [
+    "0x1::fungible_asset::Deposit",
+    "0x1::fungible_asset::DepositEvent",
+];
+const WITHDRAW_EVENT_TYPES: [&str; 2] = [
+    "0x1::fungible_asset::Withdraw",
+    "0x1::fungible_asset::WithdrawEvent",
+];
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(asset_type))]
+#[diesel(table_name = current_fungible_asset_supply)]
+pub struct CurrentFungibleAssetSupply {
+    pub asset_type: String,
+    pub supply: BigDecimal,
+    pub last_transaction_version: i64,
+    pub last_transaction_timestamp: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Identifiable, Queryable)]
+#[diesel(primary_key(asset_type))]
+#[diesel(table_name = current_fungible_asset_supply)]
+pub struct CurrentFungibleAssetSupplyQuery {
+    pub asset_type: String,
+    pub supply: BigDecimal,
+    pub last_transaction_version: i64,
+    pub last_transaction_timestamp: chrono::NaiveDateTime,
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
+/// A supply change that doesn't match the deposits and withdrawals of the asset since its
+/// previous known supply.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SupplyMismatch {
+    pub asset_type: String,
+    pub transaction_version: i64,
+    pub expected_supply: BigDecimal,
+    pub supply: BigDecimal,
+}
+
+impl CurrentFungibleAssetSupply {
+    /// Every supply write in the batch, in version order. `Supply` and `ConcurrentSupply` live
+    /// in the metadata object, so the resource address is the asset type.
+    pub fn from_transactions(transactions: &[Transaction]) -> Vec<Self> {
+        let mut supply_writes = vec![];
+        for txn in transactions {
+            let txn_version = txn.version as i64;
+            let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
+            let Some(info) = txn.info.as_ref() else {
+                continue;
+            };
+            for (index, wsc) in info.changes.iter().enumerate() {
+                let Some(Change::WriteResource(write_resource)) = wsc.change.as_ref() else {
+                    continue;
+                };
+                let resource = V2FungibleAssetResource::from_write_resource(write_resource)
+                    .parse_context(txn_version, index as i64, &write_resource.type_str)
+                    .unwrap();
+                let supply = match resource {
+                    Some(V2FungibleAssetResource::FungibleAssetSupply(inner)) => inner.current,
+                    Some(V2FungibleAssetResource::ConcurrentFungibleAssetSupply(inner)) => {
+                        inner.current.value
+                    },
+                    _ => continue,
+                };
+                supply_writes.push(Self {
+                    asset_type: standardize_address(&write_resource.address.to_string()),
+                    supply,
+                    last_transaction_version: txn_version,
+                    last_transaction_timestamp: txn_timestamp,
+                });
+            }
+        }
+        supply_writes
+    }
+
+    /// Net change an activity makes to the total amount held in stores of its asset.
+    fn balance_delta(activity: &RawFungibleAssetActivity) -> Option<(&str, BigDecimal)> {
+        let asset_type = activity.asset_type.as_deref()?;
+        let amount = activity.amount.clone()?;
+        let event_type = activity.event_type.as_str();
+        if DEPOSIT_EVENT_TYPES.contains(&event_type) {
+            Some((asset_type, amount))
+        } else if WITHDRAW_EVENT_TYPES.contains(&event_type) {
+            Some((asset_type, -amount))
+        } else {
+            None
+        }
+    }
+
+    /// Checks each supply write against the deposits and withdrawals of its asset since the
+    /// previous known supply, either earlier in the batch or from `previous` (the supply stored
+    /// before the batch). A mint deposits into a store and a burn withdraws from one, so the
+    /// change in supply should equal the net balance change. Assets with no known previous
+    /// supply only set the baseline.
+    pub fn reconcile(
+        supply_writes: &[Self],
+        activities: &[RawFungibleAssetActivity],
+        mut previous: AHashMap<String, BigDecimal>,
+    ) -> Vec<SupplyMismatch> {
+        let mut deltas: AHashMap<&str, BigDecimal> = AHashMap::new();
+        let mut activities = activities.iter().peekable();
+        let mut mismatches = vec![];
+        for write in supply_writes {
+            while let Some(activity) =
+                activities.next_if(|a| a.transaction_version <= write.last_transaction_version)
+            {
+                if let Some((asset_type, delta)) = Self::balance_delta(activity) {
+                    *deltas.entry(asset_type).or_insert_with(BigDecimal::zero) += delta;
+                }
+            }
+            let delta = deltas
+                .remove(write.asset_type.as_str())
+                .unwrap_or_else(BigDecimal::zero);
+            if let Some(previous_supply) = previous.get(&write.asset_type) {
+                let expected_supply = previous_supply + delta;
+                if expected_supply != write.supply {
+                    mismatches.push(SupplyMismatch {
+                        asset_type: write.asset_type.clone(),
+                        transaction_version: write.last_transaction_version,
+                        expected_supply,
+                        supply: write.supply.clone(),
+                    });
+                }
+            }
+            previous.insert(write.asset_type.clone(), write.supply.clone());
+        }
+        mismatches
+    }
+
+    /// Latest supply of each asset, for the current table.
+    pub fn latest_by_asset_type(supply_writes: Vec<Self>) -> Vec<Self> {
+        let mut latest: AHashMap<String, Self> = AHashMap::new();
+        for write in supply_writes {
+            latest.insert(write.asset_type.clone(), write);
+        }
+        let mut latest = latest.into_values().collect::<Vec<_>>();
+        // Sort by PK
+        latest.sort_by(|a, b| a.asset_type.cmp(&b.asset_type));
+        latest
+    }
+
+    /// Stored supply of each asset, if it was last written before `version`. If a later batch
+    /// has already been written by a concurrent task the asset is skipped, and if an earlier one
+    /// hasn't been written yet the baseline is stale, so only sequential processing checks every
+    /// supply change exactly.
+    pub async fn get_before_version(
+        asset_types: &[String],
+        version: i64,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<AHashMap<String, BigDecimal>> {
+        let rows = current_fungible_asset_supply::table
+            .filter(current_fungible_asset_supply::asset_type.eq_any(asset_types))
+            .filter(current_fungible_asset_supply::last_transaction_version.lt(version))
+            .load::<CurrentFungibleAssetSupplyQuery>(conn)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.asset_type, row.supply))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    const ASSET_TYPE: &str = "0xa";
+
+    fn activity(version: i64, event_type: &str, amount: u64) -> RawFungibleAssetActivity {
+        RawFungibleAssetActivity {
+            transaction_version: version,
+            event_index: 0,
+            owner_address: Some("0x1".to_string()),
+            storage_id: "0x2".to_string(),
+            asset_type: Some(ASSET_TYPE.to_string()),
+            is_frozen: None,
+            amount: Some(amount.into()),
+            event_type: event_type.to_string(),
+            is_gas_fee: false,
+            gas_fee_payer_address: None,
+            is_transaction_success: true,
+            entry_function_id_str: None,
+            block_height: 0,
+            token_standard: "v2".to_string(),
+            transaction_timestamp: NaiveDateTime::default(),
+            storage_refund_amount: BigDecimal::zero(),
+        }
+    }
+
+    fn supply_write(version: i64, supply: u64) -> CurrentFungibleAssetSupply {
+        CurrentFungibleAssetSupply {
+            asset_type: ASSET_TYPE.to_string(),
+            supply: supply.into(),
+            last_transaction_version: version,
+            last_transaction_timestamp: NaiveDateTime::default(),
+        }
+    }
+
+    fn previous_supply(supply: u64) -> AHashMap<String, BigDecimal> {
+        [(ASSET_TYPE.to_string(), supply.into())]
+            .into_iter()
+            .collect()
+    }
+
+    #[test]
+    fn test_mint_transfer_burn_is_consistent() {
+        let activities = vec![
+            // Mint 100
+            activity(10, "0x1::fungible_asset::Deposit", 100),
+            // Transfer 30, supply doesn't change
+            activity(11, "0x1::fungible_asset::Withdraw", 30),
+            activity(11, "0x1::fungible_asset::Deposit", 30),
+            // Burn 50
+            activity(12, "0x1::fungible_asset::Withdraw", 50),
+        ];
+        let supply_writes = vec![supply_write(10, 1100), supply_write(12, 1050)];
+        let mismatches = CurrentFungibleAssetSupply::reconcile(
+            &supply_writes,
+            &activities,
+            previous_supply(1000),
+        );
+        assert!(mismatches.is_empty());
+    }
+
+    #[test]
+    fn test_mint_without_deposit_is_flagged() {
+        let activities = vec![activity(12, "0x1::fungible_asset::Withdraw", 50)];
+        let supply_writes = vec![supply_write(10, 1100), supply_write(12, 1050)];
+        let mismatches = CurrentFungibleAssetSupply::reconcile(
+            &supply_writes,
+            &activities,
+            previous_supply(1000),
+        );
+        assert_eq!(
+            mismatches,
+            vec![SupplyMismatch {
+                asset_type: ASSET_TYPE.to_string(),
+                transaction_version: 10,
+                expected_supply: 1000.into(),
+                supply: 1100.into(),
+            }]
+        );
+    }
+}
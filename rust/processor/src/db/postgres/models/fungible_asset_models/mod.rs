@@ -1,10 +1,13 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod coin_to_fa_mapping;
+pub mod current_fungible_asset_supply;
 pub mod v2_fungible_asset_activities;
 pub mod v2_fungible_asset_balances;
 pub mod v2_fungible_asset_utils;
 pub mod v2_fungible_metadata;
+pub mod v2_fungible_metadata_history;
 
 // parquet models
 pub mod parquet_coin_supply;
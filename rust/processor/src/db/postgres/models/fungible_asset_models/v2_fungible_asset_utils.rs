@@ -7,11 +7,11 @@
 use crate::{
     db::{
         common::models::token_v2_models::v2_token_utils::ResourceReference,
-        postgres::models::token_models::token_utils::URI_LENGTH,
+        postgres::models::token_models::token_utils::{TypeInfo, URI_LENGTH},
     },
-    utils::util::{deserialize_from_string, truncate_str, Aggregator},
+    utils::util::{deserialize_from_string, truncate_str, Aggregator, ParseContext},
 };
-use anyhow::{Context, Result};
+use anyhow::Result;
 use aptos_protos::transaction::v1::WriteResource;
 use bigdecimal::BigDecimal;
 use field_count::FieldCount;
@@ -151,6 +151,21 @@ impl TryFrom<&WriteResource> for ConcurrentFungibleAssetBalance {
     }
 }
 
+/// In the metadata object of a fungible asset that was created as the pair of a coin.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PairedCoinType {
+    #[serde(rename = "type")]
+    pub coin_type: TypeInfo,
+}
+
+impl TryFrom<&WriteResource> for PairedCoinType {
+    type Error = anyhow::Error;
+
+    fn try_from(write_resource: &WriteResource) -> anyhow::Result<Self> {
+        serde_json::from_str(write_resource.data.as_str()).map_err(anyhow::Error::msg)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DepositEvent {
     #[serde(deserialize_with = "deserialize_from_string")]
@@ -188,6 +203,29 @@ pub struct FrozenEventV2 {
     pub frozen: bool,
 }
 
+/// Emitted once per coin, when its paired fungible asset is created during the migration.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PairCreationEvent {
+    pub coin_type: TypeInfo,
+    pub fungible_asset_metadata_address: String,
+}
+
+impl PairCreationEvent {
+    pub fn from_event(
+        data_type: &str,
+        data: &str,
+        txn_version: i64,
+        event_index: i64,
+    ) -> Result<Option<Self>> {
+        if data_type != "0x1::coin::PairCreation" {
+            return Ok(None);
+        }
+        serde_json::from_str(data)
+            .map(Some)
+            .parse_context(txn_version, event_index, data_type)
+    }
+}
+
 pub enum FungibleAssetEvent {
     DepositEvent(DepositEvent),
     WithdrawEvent(WithdrawEvent),
@@ -198,7 +236,12 @@ pub enum FungibleAssetEvent {
 }
 
 impl FungibleAssetEvent {
-    pub fn from_event(data_type: &str, data: &str, txn_version: i64) -> Result<Option<Self>> {
+    pub fn from_event(
+        data_type: &str,
+        data: &str,
+        txn_version: i64,
+        event_index: i64,
+    ) -> Result<Option<Self>> {
         match data_type {
             "0x1::fungible_asset::DepositEvent" => {
                 serde_json::from_str(data).map(|inner| Some(Self::DepositEvent(inner)))
@@ -220,10 +263,7 @@ impl FungibleAssetEvent {
             },
             _ => Ok(None),
         }
-        .context(format!(
-            "version {} failed! failed to parse type {}, data {:?}",
-            txn_version, data_type, data
-        ))
+        .parse_context(txn_version, event_index, data_type)
     }
 }
 
@@ -0,0 +1,198 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use crate::{
+    db::common::models::fungible_asset_models::raw_v2_fungible_metadata::RawFungibleAssetMetadataModel,
+    schema::fungible_asset_metadata_history, utils::database::DbPoolConnection,
+};
+use ahash::AHashMap;
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+// PK of fungible_asset_metadata_history, i.e. (asset_type, transaction_version)
+pub type FungibleAssetMetadataHistoryPK = (String, i64);
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(asset_type, transaction_version))]
+#[diesel(table_name = fungible_asset_metadata_history)]
+pub struct FungibleAssetMetadataHistory {
+    pub asset_type: String,
+    pub transaction_version: i64,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: i32,
+    pub icon_uri: Option<String>,
+    pub project_uri: Option<String>,
+}
+
+#[derive(Debug, Identifiable, Queryable)]
+#[diesel(primary_key(asset_type, transaction_version))]
+#[diesel(table_name = fungible_asset_metadata_history)]
+pub struct FungibleAssetMetadataHistoryQuery {
+    pub asset_type: String,
+    pub transaction_version: i64,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: i32,
+    pub icon_uri: Option<String>,
+    pub project_uri: Option<String>,
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
+impl From<&RawFungibleAssetMetadataModel> for FungibleAssetMetadataHistory {
+    fn from(raw: &RawFungibleAssetMetadataModel) -> Self {
+        Self {
+            asset_type: raw.asset_type.clone(),
+            transaction_version: raw.last_transaction_version,
+            transaction_timestamp: raw.last_transaction_timestamp,
+            name: raw.name.clone(),
+            symbol: raw.symbol.clone(),
+            decimals: raw.decimals,
+            icon_uri: raw.icon_uri.clone(),
+            project_uri: raw.project_uri.clone(),
+        }
+    }
+}
+
+impl From<FungibleAssetMetadataHistoryQuery> for FungibleAssetMetadataHistory {
+    fn from(query: FungibleAssetMetadataHistoryQuery) -> Self {
+        Self {
+            asset_type: query.asset_type,
+            transaction_version: query.transaction_version,
+            transaction_timestamp: query.transaction_timestamp,
+            name: query.name,
+            symbol: query.symbol,
+            decimals: query.decimals,
+            icon_uri: query.icon_uri,
+            project_uri: query.project_uri,
+        }
+    }
+}
+
+impl FungibleAssetMetadataHistory {
+    /// Only the user facing fields are tracked. Supply and the v1 aggregator change on every
+    /// mint and burn, which would otherwise make nearly every write look like a change.
+    fn has_same_metadata(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.symbol == other.symbol
+            && self.decimals == other.decimals
+            && self.icon_uri == other.icon_uri
+            && self.project_uri == other.project_uri
+    }
+
+    /// Turns metadata writes, in version order, into history rows. A write only produces a row if
+    /// it differs from the previous known metadata of the asset, either earlier in the batch or
+    /// from `previous` (the latest history row before the batch).
+    pub fn from_writes(
+        writes: &[RawFungibleAssetMetadataModel],
+        mut previous: AHashMap<String, Self>,
+    ) -> Vec<Self> {
+        let mut history = vec![];
+        for write in writes {
+            let item = Self::from(write);
+            if previous
+                .get(&item.asset_type)
+                .is_some_and(|prev| prev.has_same_metadata(&item))
+            {
+                continue;
+            }
+            previous.insert(item.asset_type.clone(), item.clone());
+            history.push(item);
+        }
+        history
+    }
+
+    /// Latest history row of each asset before `version`. Rows from batches that are still being
+    /// written by a concurrent task are not visible, in which case an unchanged write could be
+    /// recorded once more.
+    pub async fn get_latest_before_version(
+        asset_types: &[String],
+        version: i64,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<AHashMap<String, Self>> {
+        let rows = fungible_asset_metadata_history::table
+            .filter(fungible_asset_metadata_history::asset_type.eq_any(asset_types))
+            .filter(fungible_asset_metadata_history::transaction_version.lt(version))
+            .distinct_on(fungible_asset_metadata_history::asset_type)
+            .order((
+                fungible_asset_metadata_history::asset_type,
+                fungible_asset_metadata_history::transaction_version.desc(),
+            ))
+            .load::<FungibleAssetMetadataHistoryQuery>(conn)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.asset_type.clone(), Self::from(row)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+
+    fn metadata_write(version: i64, symbol: &str, supply: u64) -> RawFungibleAssetMetadataModel {
+        RawFungibleAssetMetadataModel {
+            asset_type: "0x1::aptos_coin::AptosCoin".to_string(),
+            creator_address: "0x1".to_string(),
+            name: "Aptos Coin".to_string(),
+            symbol: symbol.to_string(),
+            decimals: 8,
+            icon_uri: None,
+            project_uri: None,
+            last_transaction_version: version,
+            last_transaction_timestamp: NaiveDateTime::default(),
+            supply_aggregator_table_handle_v1: None,
+            supply_aggregator_table_key_v1: None,
+            token_standard: "v1".to_string(),
+            is_token_v2: None,
+            supply_v2: Some(supply.into()),
+            maximum_v2: None,
+        }
+    }
+
+    #[test]
+    fn test_symbol_change_recorded_and_rewrites_skipped() {
+        let writes = vec![
+            metadata_write(10, "APT", 100),
+            // Supply only, not a metadata change
+            metadata_write(11, "APT", 200),
+            metadata_write(12, "APT2", 200),
+            metadata_write(13, "APT2", 300),
+            metadata_write(14, "APT", 300),
+        ];
+        let history = FungibleAssetMetadataHistory::from_writes(&writes, AHashMap::new());
+        assert_eq!(
+            history
+                .iter()
+                .map(|h| (h.transaction_version, h.symbol.as_str()))
+                .collect::<Vec<_>>(),
+            vec![(10, "APT"), (12, "APT2"), (14, "APT")]
+        );
+    }
+
+    #[test]
+    fn test_unchanged_from_previous_batch_skipped() {
+        let previous_row = FungibleAssetMetadataHistory::from(&metadata_write(5, "APT", 100));
+        let previous = [(previous_row.asset_type.clone(), previous_row)]
+            .into_iter()
+            .collect();
+        let writes = vec![
+            metadata_write(10, "APT", 200),
+            metadata_write(11, "USD", 200),
+        ];
+        let history = FungibleAssetMetadataHistory::from_writes(&writes, previous);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].transaction_version, 11);
+        assert_eq!(history[0].symbol, "USD");
+    }
+}
@@ -0,0 +1,42 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::{
+    schema::gap_detector_status,
+    utils::database::{processor_status_key, DbPoolConnection},
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::RunQueryDsl;
+
+#[derive(AsChangeset, Debug, Insertable)]
+#[diesel(table_name = gap_detector_status)]
+/// Snapshot of the default gap detector so that pending gaps survive a restart
+pub struct GapDetectorStatus {
+    pub processor: String,
+    pub next_version_to_process: i64,
+    pub pending_batches: serde_json::Value,
+}
+
+#[derive(AsChangeset, Debug, Queryable)]
+#[diesel(table_name = gap_detector_status)]
+pub struct GapDetectorStatusQuery {
+    pub processor: String,
+    pub next_version_to_process: i64,
+    pub pending_batches: serde_json::Value,
+    pub last_updated: chrono::NaiveDateTime,
+}
+
+impl GapDetectorStatusQuery {
+    pub async fn get_by_processor(
+        processor_name: &str,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<Option<Self>> {
+        gap_detector_status::table
+            .filter(gap_detector_status::processor.eq(processor_status_key(processor_name)))
+            .first::<Self>(conn)
+            .await
+            .optional()
+    }
+}
@@ -0,0 +1,407 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+
+//! On-chain governance runs on `0x1::voting`, with `0x1::aptos_governance` on top adding the
+//! proposer, the stake pools voting and the proposal metadata. A proposal's creation emits a
+//! `CreateProposal` event from each module, each vote an `aptos_governance::Vote`, and resolving
+//! a proposal that passed a `voting::ResolveProposal` with the final tally. Proposals that don't
+//! pass, or pass but never get resolved, emit nothing more: they simply expire, which
+//! `governance_proposal_outcomes` works out from the expiration time.
+
+use crate::{
+    db::common::models::stake_models::stake_utils::GovernanceVoteEvent,
+    schema::{governance_proposal_resolutions, governance_proposals, governance_votes},
+    utils::util::{
+        deserialize_from_string, hex_to_raw_bytes, parse_timestamp_secs,
+        parse_transaction_timestamp, standardize_address,
+    },
+};
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use aptos_protos::transaction::v1::{
+    transaction::TxnData, write_set_change::Change as WriteSetChange, Event, Transaction,
+};
+use bigdecimal::BigDecimal;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// Where `aptos_governance` keeps its voting forum, so where `voting` events about governance
+/// proposals come from
+const GOVERNANCE_FORUM_ADDRESS: &str = "0x1";
+const GOVERNANCE_FORUM_TYPE: &str =
+    "0x1::voting::VotingForum<0x1::governance_proposal::GovernanceProposal>";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct SimpleMapEntry {
+    key: String,
+    value: String,
+}
+
+/// A `SimpleMap<String, vector<u8>>` as in the event data
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct ProposalMetadata {
+    data: Vec<SimpleMapEntry>,
+}
+
+impl ProposalMetadata {
+    fn get_string(&self, key: &str) -> Option<String> {
+        let entry = self.data.iter().find(|entry| entry.key == key)?;
+        let bytes = hex_to_raw_bytes(&entry.value).ok()?;
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct OptionalThreshold {
+    vec: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct GovernanceCreateProposalEvent {
+    proposer: String,
+    stake_pool: String,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    proposal_id: u64,
+    execution_hash: String,
+    #[serde(default)]
+    proposal_metadata: ProposalMetadata,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct VotingCreateProposalEvent {
+    #[serde(deserialize_with = "deserialize_from_string")]
+    proposal_id: u64,
+    early_resolution_vote_threshold: OptionalThreshold,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    expiration_secs: u64,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    min_vote_threshold: BigDecimal,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct ResolveProposalEvent {
+    #[serde(deserialize_with = "deserialize_from_string")]
+    proposal_id: u64,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    yes_votes: BigDecimal,
+    #[serde(deserialize_with = "deserialize_from_string")]
+    no_votes: BigDecimal,
+    resolved_early: bool,
+}
+
+enum GovernanceEvent {
+    CreateProposal(GovernanceCreateProposalEvent),
+    VotingCreateProposal(VotingCreateProposalEvent),
+    Vote(GovernanceVoteEvent),
+    ResolveProposal(ResolveProposalEvent),
+}
+
+impl GovernanceEvent {
+    fn from_event(
+        event: &Event,
+        txn_version: i64,
+        writes_governance_forum: bool,
+    ) -> Result<Option<Self>> {
+        let data = event.data.as_str();
+        match event.type_str.as_str() {
+            "0x1::aptos_governance::CreateProposalEvent"
+            | "0x1::aptos_governance::CreateProposal" => {
+                serde_json::from_str(data).map(|inner| Some(Self::CreateProposal(inner)))
+            },
+            "0x1::voting::CreateProposalEvent" | "0x1::voting::CreateProposal" => {
+                serde_json::from_str(data).map(|inner| Some(Self::VotingCreateProposal(inner)))
+            },
+            "0x1::aptos_governance::VoteEvent" | "0x1::aptos_governance::Vote" => {
+                serde_json::from_str(data).map(|inner| Some(Self::Vote(inner)))
+            },
+            "0x1::voting::ResolveProposal"
+                if is_from_governance_forum(event, writes_governance_forum) =>
+            {
+                serde_json::from_str(data).map(|inner| Some(Self::ResolveProposal(inner)))
+            },
+            _ => Ok(None),
+        }
+        .context(format!(
+            "version {} failed! failed to parse type {}, data {:?}",
+            txn_version, event.type_str, data
+        ))
+    }
+}
+
+/// Any module can run a forum of its own on `voting`, numbering its proposals from 0 like
+/// governance does. Handle events name the account of their forum. Module events don't, so those
+/// are only governance's if the transaction writes governance's forum, as resolving updates it.
+fn is_from_governance_forum(event: &Event, writes_governance_forum: bool) -> bool {
+    let forum_address = event
+        .key
+        .as_ref()
+        .map(|key| standardize_address(&key.account_address));
+    match forum_address {
+        Some(forum_address) if forum_address != standardize_address("0x0") => {
+            forum_address == standardize_address(GOVERNANCE_FORUM_ADDRESS)
+        },
+        _ => writes_governance_forum,
+    }
+}
+
+fn writes_governance_forum(txn: &Transaction) -> bool {
+    let Some(info) = txn.info.as_ref() else {
+        return false;
+    };
+    info.changes
+        .iter()
+        .any(|change| match change.change.as_ref() {
+            Some(WriteSetChange::WriteResource(resource)) => {
+                resource.type_str == GOVERNANCE_FORUM_TYPE
+                    && standardize_address(&resource.address)
+                        == standardize_address(GOVERNANCE_FORUM_ADDRESS)
+            },
+            _ => false,
+        })
+}
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, PartialEq, Serialize)]
+#[diesel(primary_key(proposal_id))]
+#[diesel(table_name = governance_proposals)]
+pub struct GovernanceProposal {
+    pub proposal_id: i64,
+    pub proposer_address: String,
+    pub stake_pool_address: String,
+    pub execution_hash: String,
+    pub metadata_location: Option<String>,
+    pub metadata_hash: Option<String>,
+    pub min_vote_threshold: BigDecimal,
+    pub early_resolution_vote_threshold: Option<BigDecimal>,
+    pub expiration_timestamp: chrono::NaiveDateTime,
+    pub transaction_version: i64,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, PartialEq, Serialize)]
+#[diesel(primary_key(transaction_version, event_index))]
+#[diesel(table_name = governance_votes)]
+pub struct GovernanceVote {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub proposal_id: i64,
+    pub voter_address: String,
+    pub stake_pool_address: String,
+    pub num_votes: BigDecimal,
+    pub should_pass: bool,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+}
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, PartialEq, Serialize)]
+#[diesel(primary_key(proposal_id))]
+#[diesel(table_name = governance_proposal_resolutions)]
+pub struct GovernanceProposalResolution {
+    pub proposal_id: i64,
+    pub yes_votes: BigDecimal,
+    pub no_votes: BigDecimal,
+    pub resolved_early: bool,
+    pub transaction_version: i64,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+pub struct GovernanceChanges {
+    pub proposals: Vec<GovernanceProposal>,
+    pub votes: Vec<GovernanceVote>,
+    pub resolutions: Vec<GovernanceProposalResolution>,
+}
+
+impl GovernanceChanges {
+    pub fn from_transactions(transactions: &[Transaction]) -> Result<Self> {
+        let mut changes = Self::default();
+        for txn in transactions {
+            let txn_version = txn.version as i64;
+            let events = match txn.txn_data.as_ref() {
+                Some(TxnData::User(inner)) => &inner.events,
+                _ => continue,
+            };
+            if events.is_empty() {
+                continue;
+            }
+            let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
+            changes.add_events(
+                events,
+                txn_version,
+                txn_timestamp,
+                writes_governance_forum(txn),
+            )?;
+        }
+        Ok(changes)
+    }
+
+    fn add_events(
+        &mut self,
+        events: &[Event],
+        txn_version: i64,
+        txn_timestamp: chrono::NaiveDateTime,
+        writes_governance_forum: bool,
+    ) -> Result<()> {
+        // Both halves of a proposal's creation are in the same transaction
+        let mut governance_creations = AHashMap::new();
+        let mut voting_creations = vec![];
+        for (index, event) in events.iter().enumerate() {
+            match GovernanceEvent::from_event(event, txn_version, writes_governance_forum)? {
+                Some(GovernanceEvent::CreateProposal(inner)) => {
+                    governance_creations.insert(inner.proposal_id, inner);
+                },
+                Some(GovernanceEvent::VotingCreateProposal(inner)) => voting_creations.push(inner),
+                Some(GovernanceEvent::Vote(inner)) => self.votes.push(GovernanceVote {
+                    transaction_version: txn_version,
+                    event_index: index as i64,
+                    proposal_id: inner.proposal_id as i64,
+                    voter_address: standardize_address(&inner.voter),
+                    stake_pool_address: standardize_address(&inner.stake_pool),
+                    num_votes: inner.num_votes,
+                    should_pass: inner.should_pass,
+                    transaction_timestamp: txn_timestamp,
+                }),
+                Some(GovernanceEvent::ResolveProposal(inner)) => {
+                    self.resolutions.push(GovernanceProposalResolution {
+                        proposal_id: inner.proposal_id as i64,
+                        yes_votes: inner.yes_votes,
+                        no_votes: inner.no_votes,
+                        resolved_early: inner.resolved_early,
+                        transaction_version: txn_version,
+                        transaction_timestamp: txn_timestamp,
+                    })
+                },
+                None => {},
+            }
+        }
+        // A `voting` proposal without an `aptos_governance` one is from another forum
+        for voting in voting_creations {
+            let Some(governance) = governance_creations.remove(&voting.proposal_id) else {
+                continue;
+            };
+            self.proposals.push(GovernanceProposal {
+                proposal_id: voting.proposal_id as i64,
+                proposer_address: standardize_address(&governance.proposer),
+                stake_pool_address: standardize_address(&governance.stake_pool),
+                execution_hash: governance.execution_hash,
+                metadata_location: governance.proposal_metadata.get_string("metadata_location"),
+                metadata_hash: governance.proposal_metadata.get_string("metadata_hash"),
+                min_vote_threshold: voting.min_vote_threshold,
+                early_resolution_vote_threshold: voting
+                    .early_resolution_vote_threshold
+                    .vec
+                    .first()
+                    .and_then(|threshold| threshold.parse().ok()),
+                expiration_timestamp: parse_timestamp_secs(voting.expiration_secs, txn_version),
+                transaction_version: txn_version,
+                transaction_timestamp: txn_timestamp,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::EventKey;
+
+    const PROPOSER: &str = "0x0000000000000000000000000000000000000000000000000000000000000a11";
+
+    fn event(type_str: &str, data: serde_json::Value) -> Event {
+        Event {
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    fn create_proposal_events(proposal_id: u64) -> Vec<Event> {
+        vec![
+            event(
+                "0x1::voting::CreateProposal",
+                serde_json::json!({
+                    "proposal_id": proposal_id.to_string(),
+                    "early_resolution_vote_threshold": {"vec": ["1000"]},
+                    "execution_hash": "0x1234",
+                    "expiration_secs": "1700000000",
+                    "metadata": {"data": []},
+                    "min_vote_threshold": "400",
+                }),
+            ),
+            event(
+                "0x1::aptos_governance::CreateProposal",
+                serde_json::json!({
+                    "proposer": "0xa11",
+                    "stake_pool": "0xa11",
+                    "proposal_id": proposal_id.to_string(),
+                    "execution_hash": "0x1234",
+                    "proposal_metadata": {"data": [
+                        // "https://example.com"
+                        {"key": "metadata_location", "value": "0x68747470733a2f2f6578616d706c652e636f6d"},
+                        {"key": "metadata_hash", "value": "0x616263"},
+                    ]},
+                }),
+            ),
+        ]
+    }
+
+    fn changes(events: &[Event], writes_governance_forum: bool) -> GovernanceChanges {
+        let mut changes = GovernanceChanges::default();
+        changes
+            .add_events(
+                events,
+                1,
+                chrono::NaiveDateTime::default(),
+                writes_governance_forum,
+            )
+            .unwrap();
+        changes
+    }
+
+    #[test]
+    fn test_create_proposal() {
+        let changes = changes(&create_proposal_events(7), true);
+        let proposal = &changes.proposals[0];
+        assert_eq!(proposal.proposal_id, 7);
+        assert_eq!(proposal.proposer_address, PROPOSER);
+        assert_eq!(
+            proposal.metadata_location.as_deref(),
+            Some("https://example.com")
+        );
+        assert_eq!(proposal.metadata_hash.as_deref(), Some("abc"));
+        assert_eq!(proposal.min_vote_threshold, BigDecimal::from(400));
+        assert_eq!(
+            proposal.early_resolution_vote_threshold,
+            Some(BigDecimal::from(1000))
+        );
+        assert_eq!(
+            proposal.expiration_timestamp,
+            parse_timestamp_secs(1_700_000_000, 1)
+        );
+    }
+
+    #[test]
+    fn test_other_forums_are_skipped() {
+        // A `voting` proposal of another forum
+        let events = &create_proposal_events(7)[..1];
+        assert!(changes(events, true).proposals.is_empty());
+
+        let data = serde_json::json!({
+            "proposal_id": "7",
+            "yes_votes": "500",
+            "no_votes": "0",
+            "resolved_early": false,
+        });
+        let mut resolution = event("0x1::voting::ResolveProposal", data);
+        assert_eq!(changes(&[resolution.clone()], true).resolutions.len(), 1);
+        // A module event of a transaction that doesn't touch governance's forum
+        assert!(changes(&[resolution.clone()], false).resolutions.is_empty());
+        resolution.key = Some(EventKey {
+            creation_number: 2,
+            account_address: "0xcafe".to_string(),
+        });
+        assert!(changes(&[resolution], true).resolutions.is_empty());
+    }
+}
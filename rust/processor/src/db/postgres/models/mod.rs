@@ -1,14 +1,23 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod account_sequence_number_models;
 pub mod account_transaction_models;
+pub mod allowance_models;
 pub mod ans_models;
+pub mod block_gas_stats_models;
 pub mod coin_models;
 pub mod default_models;
 pub mod events_models;
+pub mod failed_events;
 pub mod fungible_asset_models;
+pub mod gap_detector_status;
+pub mod governance_models;
 pub mod ledger_info;
 pub mod object_models;
+pub mod outbox;
+pub mod parquet_upload_checkpoint;
+pub mod processor_schema_version;
 pub mod processor_status;
 pub mod property_map;
 pub mod resources;
@@ -17,3 +26,4 @@ pub mod token_models;
 pub mod token_v2_models;
 pub mod transaction_metadata_model;
 pub mod user_transactions_models;
+pub mod webhook_dead_letters;
@@ -0,0 +1,31 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::schema::outbox;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// A change data capture record of a row written by a processor, see
+/// `execute_in_chunks_with_outbox`.
+#[derive(Clone, Debug, Deserialize, FieldCount, Insertable, PartialEq, Serialize)]
+#[diesel(table_name = outbox)]
+pub struct Outbox {
+    pub table_name: String,
+    pub operation: String,
+    pub row_key: serde_json::Value,
+    pub payload: serde_json::Value,
+}
+
+impl Outbox {
+    /// Record of inserting `row` into `table_name`.
+    pub fn insert<T: Serialize>(table_name: &str, row_key: serde_json::Value, row: &T) -> Self {
+        Self {
+            table_name: table_name.to_string(),
+            operation: "insert".to_string(),
+            row_key,
+            payload: serde_json::to_value(row).expect("Row should serialize to JSON"),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::{
+    schema::parquet_upload_checkpoints,
+    utils::database::{execute_with_better_error, ArcDbPool, DbPoolConnection},
+};
+use diesel::{pg::upsert::excluded, ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+
+pub const CHECKPOINT_STATUS_UPLOADING: &str = "uploading";
+pub const CHECKPOINT_STATUS_COMMITTED: &str = "committed";
+
+#[derive(AsChangeset, Clone, Debug, Insertable, PartialEq, Eq)]
+#[diesel(table_name = parquet_upload_checkpoints)]
+/// A single Parquet file upload for a table. It's recorded as uploading before the upload starts and
+/// marked committed once it's done, so a crash in between can be reconciled on startup.
+pub struct ParquetUploadCheckpoint {
+    pub processor: String,
+    pub table_name: String,
+    pub start_version: i64,
+    pub end_version: i64,
+    pub end_version_struct_count: i64,
+    pub file_path: String,
+    pub status: String,
+}
+
+#[derive(Debug, Queryable)]
+#[diesel(table_name = parquet_upload_checkpoints)]
+pub struct ParquetUploadCheckpointQuery {
+    pub processor: String,
+    pub table_name: String,
+    pub start_version: i64,
+    pub end_version: i64,
+    pub end_version_struct_count: i64,
+    pub file_path: String,
+    pub status: String,
+    pub last_updated: chrono::NaiveDateTime,
+}
+
+impl From<ParquetUploadCheckpointQuery> for ParquetUploadCheckpoint {
+    fn from(query: ParquetUploadCheckpointQuery) -> Self {
+        Self {
+            processor: query.processor,
+            table_name: query.table_name,
+            start_version: query.start_version,
+            end_version: query.end_version,
+            end_version_struct_count: query.end_version_struct_count,
+            file_path: query.file_path,
+            status: query.status,
+        }
+    }
+}
+
+impl ParquetUploadCheckpoint {
+    pub fn is_committed(&self) -> bool {
+        self.status == CHECKPOINT_STATUS_COMMITTED
+    }
+
+    pub async fn mark_uploading(&mut self, pool: ArcDbPool) -> diesel::QueryResult<()> {
+        self.status = CHECKPOINT_STATUS_UPLOADING.to_string();
+        self.upsert(pool).await
+    }
+
+    /// Marks the upload as committed and drops older committed checkpoints of the table, since
+    /// only the latest one is needed on recovery.
+    pub async fn mark_committed(&mut self, pool: ArcDbPool) -> diesel::QueryResult<()> {
+        self.status = CHECKPOINT_STATUS_COMMITTED.to_string();
+        self.upsert(pool.clone()).await?;
+        execute_with_better_error(
+            pool,
+            diesel::delete(
+                parquet_upload_checkpoints::table
+                    .filter(parquet_upload_checkpoints::processor.eq(self.processor.clone()))
+                    .filter(parquet_upload_checkpoints::table_name.eq(self.table_name.clone()))
+                    .filter(parquet_upload_checkpoints::start_version.lt(self.start_version))
+                    .filter(parquet_upload_checkpoints::status.eq(CHECKPOINT_STATUS_COMMITTED)),
+            ),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(&self, pool: ArcDbPool) -> diesel::QueryResult<()> {
+        execute_with_better_error(
+            pool,
+            diesel::delete(
+                parquet_upload_checkpoints::table
+                    .filter(parquet_upload_checkpoints::processor.eq(self.processor.clone()))
+                    .filter(parquet_upload_checkpoints::table_name.eq(self.table_name.clone()))
+                    .filter(parquet_upload_checkpoints::start_version.eq(self.start_version)),
+            ),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn upsert(&self, pool: ArcDbPool) -> diesel::QueryResult<()> {
+        execute_with_better_error(
+            pool,
+            diesel::insert_into(parquet_upload_checkpoints::table)
+                .values(self)
+                .on_conflict((
+                    parquet_upload_checkpoints::processor,
+                    parquet_upload_checkpoints::table_name,
+                    parquet_upload_checkpoints::start_version,
+                ))
+                .do_update()
+                .set((
+                    parquet_upload_checkpoints::end_version
+                        .eq(excluded(parquet_upload_checkpoints::end_version)),
+                    parquet_upload_checkpoints::end_version_struct_count.eq(excluded(
+                        parquet_upload_checkpoints::end_version_struct_count,
+                    )),
+                    parquet_upload_checkpoints::file_path
+                        .eq(excluded(parquet_upload_checkpoints::file_path)),
+                    parquet_upload_checkpoints::status
+                        .eq(excluded(parquet_upload_checkpoints::status)),
+                    parquet_upload_checkpoints::last_updated
+                        .eq(excluded(parquet_upload_checkpoints::last_updated)),
+                )),
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_by_table(
+        processor_name: &str,
+        table_name: &str,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<Vec<Self>> {
+        let rows = parquet_upload_checkpoints::table
+            .filter(parquet_upload_checkpoints::processor.eq(processor_name))
+            .filter(parquet_upload_checkpoints::table_name.eq(table_name))
+            .order(parquet_upload_checkpoints::start_version.asc())
+            .load::<ParquetUploadCheckpointQuery>(conn)
+            .await?;
+        Ok(rows.into_iter().map(Self::from).collect())
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::{
+    schema::processor_schema_versions,
+    utils::database::{processor_status_key, DbPoolConnection},
+};
+use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
+use diesel_async::RunQueryDsl;
+
+#[derive(AsChangeset, Debug, Insertable)]
+#[diesel(table_name = processor_schema_versions)]
+/// Schema version of the tables the processor last started against
+pub struct ProcessorSchemaVersion {
+    pub processor: String,
+    pub schema_version: i64,
+}
+
+impl ProcessorSchemaVersion {
+    pub async fn get_by_processor(
+        processor_name: &str,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<Option<i64>> {
+        processor_schema_versions::table
+            .filter(processor_schema_versions::processor.eq(processor_status_key(processor_name)))
+            .select(processor_schema_versions::schema_version)
+            .first::<i64>(conn)
+            .await
+            .optional()
+    }
+
+    pub async fn upsert(
+        processor_name: &str,
+        schema_version: i64,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<usize> {
+        diesel::insert_into(processor_schema_versions::table)
+            .values(Self {
+                processor: processor_status_key(processor_name),
+                schema_version,
+            })
+            .on_conflict(processor_schema_versions::processor)
+            .do_update()
+            .set((
+                processor_schema_versions::schema_version.eq(schema_version),
+                processor_schema_versions::last_updated.eq(diesel::dsl::now),
+            ))
+            .execute(conn)
+            .await
+    }
+}
@@ -3,7 +3,10 @@
 
 #![allow(clippy::extra_unused_lifetimes)]
 
-use crate::{schema::processor_status, utils::database::DbPoolConnection};
+use crate::{
+    schema::processor_status,
+    utils::database::{processor_status_key, DbPoolConnection},
+};
 use diesel::{ExpressionMethods, OptionalExtension, QueryDsl};
 use diesel_async::RunQueryDsl;
 
@@ -32,7 +35,7 @@ impl ProcessorStatusQuery {
         conn: &mut DbPoolConnection<'_>,
     ) -> diesel::QueryResult<Option<Self>> {
         processor_status::table
-            .filter(processor_status::processor.eq(processor_name))
+            .filter(processor_status::processor.eq(processor_status_key(processor_name)))
             .first::<Self>(conn)
             .await
             .optional()
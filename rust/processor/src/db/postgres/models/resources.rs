@@ -13,7 +13,7 @@ use crate::db::{
         default_models::move_resources::MoveResource,
         fungible_asset_models::v2_fungible_asset_utils::{
             ConcurrentFungibleAssetBalance, ConcurrentFungibleAssetSupply, FungibleAssetMetadata,
-            FungibleAssetStore, FungibleAssetSupply,
+            FungibleAssetStore, FungibleAssetSupply, PairedCoinType,
         },
     },
 };
@@ -33,6 +33,7 @@ pub const TYPE_FUNGIBLE_ASSET_METADATA: &str = formatcp!("{COIN_ADDR}::fungible_
 pub const TYPE_FUNGIBLE_ASSET_STORE: &str = formatcp!("{COIN_ADDR}::fungible_asset::FungibleStore");
 pub const TYPE_CONCURRENT_FUNGIBLE_ASSET_BALANCE: &str =
     formatcp!("{COIN_ADDR}::fungible_asset::ConcurrentFungibleBalance");
+pub const TYPE_PAIRED_COIN_TYPE: &str = formatcp!("{COIN_ADDR}::coin::PairedCoinType");
 
 pub const TYPE_OBJECT_CORE: &str = formatcp!("{COIN_ADDR}::object::ObjectCore");
 pub const TYPE_UNTRANSFERABLE: &str = formatcp!("{COIN_ADDR}::object::Untransferable");
@@ -105,6 +106,12 @@ impl Resource for FungibleAssetSupply {
     }
 }
 
+impl Resource for PairedCoinType {
+    fn type_str() -> &'static str {
+        TYPE_PAIRED_COIN_TYPE
+    }
+}
+
 impl V2FungibleAssetResource {
     pub fn from_write_resource(write_resource: &WriteResource) -> Result<Option<Self>> {
         let type_str = MoveResource::get_outer_type_from_write_resource(write_resource);
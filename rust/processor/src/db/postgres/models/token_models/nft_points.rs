@@ -10,7 +10,7 @@ use crate::{
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
         util::{
-            get_clean_payload, get_entry_function_from_user_request, parse_timestamp,
+            get_clean_payload, get_entry_function_from_user_request, parse_transaction_timestamp,
             standardize_address,
         },
     },
@@ -52,10 +52,6 @@ impl NftPoints {
             },
         };
         let version = transaction.version as i64;
-        let timestamp = transaction
-            .timestamp
-            .as_ref()
-            .expect("Transaction timestamp doesn't exist!");
         let transaction_info = transaction
             .info
             .as_ref()
@@ -122,7 +118,8 @@ impl NftPoints {
                         );
                         panic!()
                     });
-                    let transaction_timestamp = parse_timestamp(timestamp, version);
+                    let transaction_timestamp =
+                        parse_transaction_timestamp(transaction.timestamp.as_ref(), version);
                     return Some(Self {
                         transaction_version: version,
                         owner_address,
@@ -10,7 +10,7 @@ use crate::{
     schema::token_activities,
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        util::{parse_timestamp, standardize_address},
+        util::{parse_transaction_timestamp, standardize_address},
     },
 };
 use aptos_protos::transaction::v1::{transaction::TxnData, Event, Transaction};
@@ -89,7 +89,7 @@ impl TokenActivity {
                         event,
                         &token_event,
                         txn_version,
-                        parse_timestamp(transaction.timestamp.as_ref().unwrap(), txn_version),
+                        parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version),
                         index as i64,
                     ))
                 }
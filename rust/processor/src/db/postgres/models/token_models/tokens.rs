@@ -17,7 +17,7 @@ use crate::{
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
         database::DbPoolConnection,
-        util::{ensure_not_negative, parse_timestamp, standardize_address},
+        util::{ensure_not_negative, parse_transaction_timestamp, standardize_address},
     },
 };
 use ahash::AHashMap;
@@ -121,7 +121,7 @@ impl Token {
 
             let txn_version = transaction.version as i64;
             let txn_timestamp =
-                parse_timestamp(transaction.timestamp.as_ref().unwrap(), txn_version);
+                parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version);
             let transaction_info = transaction
                 .info
                 .as_ref()
@@ -0,0 +1,104 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::counters::COLLECTION_CREATOR_CACHE_LOOKUP_COUNT;
+use lru::LruCache;
+use once_cell::sync::OnceCell;
+use std::{future::Future, num::NonZeroUsize, sync::Mutex};
+
+static COLLECTION_CREATOR_CACHE: OnceCell<CollectionCreatorCache> = OnceCell::new();
+
+/// Bounded LRU cache of v1 collection creators keyed by the collection's table handle, so hot
+/// collections don't need a `current_collections_v2` lookup for every write. Entries are
+/// replaced whenever the collection's metadata shows up in the stream.
+pub struct CollectionCreatorCache {
+    cache: Mutex<LruCache<String, String>>,
+}
+
+impl CollectionCreatorCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            cache: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    /// Sets up the cache shared by all processing tasks. Tasks each build their own processor, so
+    /// only the first call creates it. A capacity of 0 disables the cache.
+    pub fn init_global(capacity: usize) {
+        if let Some(capacity) = NonZeroUsize::new(capacity) {
+            COLLECTION_CREATOR_CACHE.get_or_init(|| Self::new(capacity));
+        }
+    }
+
+    pub fn global() -> Option<&'static Self> {
+        COLLECTION_CREATOR_CACHE.get()
+    }
+
+    pub fn get(&self, table_handle: &str) -> Option<String> {
+        let creator_address = self.cache.lock().unwrap().get(table_handle).cloned();
+        let result = if creator_address.is_some() {
+            "hit"
+        } else {
+            "miss"
+        };
+        COLLECTION_CREATOR_CACHE_LOOKUP_COUNT
+            .with_label_values(&[result])
+            .inc();
+        creator_address
+    }
+
+    pub fn insert(&self, table_handle: String, creator_address: String) {
+        self.cache
+            .lock()
+            .unwrap()
+            .put(table_handle, creator_address);
+    }
+
+    /// Returns the cached creator, or fetches and caches it. The lock isn't held while fetching.
+    pub async fn get_or_try_fetch<F, Fut>(
+        &self,
+        table_handle: &str,
+        fetch: F,
+    ) -> anyhow::Result<String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = anyhow::Result<String>>,
+    {
+        if let Some(creator_address) = self.get(table_handle) {
+            return Ok(creator_address);
+        }
+        let creator_address = fetch().await?;
+        self.insert(table_handle.to_string(), creator_address.clone());
+        Ok(creator_address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[tokio::test]
+    async fn test_cache_hit_skips_lookup() {
+        let cache = CollectionCreatorCache::new(NonZeroUsize::new(2).unwrap());
+        let lookups = AtomicUsize::new(0);
+        let fetch = || async {
+            lookups.fetch_add(1, Ordering::SeqCst);
+            Ok("0x1".to_string())
+        };
+
+        assert_eq!(cache.get_or_try_fetch("0xa", fetch).await.unwrap(), "0x1");
+        assert_eq!(cache.get_or_try_fetch("0xa", fetch).await.unwrap(), "0x1");
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        // Metadata seen in the stream replaces the cached entry
+        cache.insert("0xa".to_string(), "0x2".to_string());
+        assert_eq!(cache.get_or_try_fetch("0xa", fetch).await.unwrap(), "0x2");
+        assert_eq!(lookups.load(Ordering::SeqCst), 1);
+
+        // Least recently used entry is evicted
+        cache.insert("0xb".to_string(), "0x3".to_string());
+        cache.insert("0xc".to_string(), "0x4".to_string());
+        assert_eq!(cache.get("0xa"), None);
+    }
+}
@@ -0,0 +1,320 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+
+//! Per collection supply and activity counts in `collection_stats`, kept when the token v2
+//! processor has `collection_stats` set. Each transaction's mints, burns and transfers are summed
+//! per collection into `collection_stat_changes`, keyed by version, and the stats of the
+//! collections a batch touched are then recomputed from those rows. Reprocessing a version writes
+//! the same change again rather than adding it twice, so the stats stay exact in any order.
+
+use crate::{
+    db::{
+        common::models::token_v2_models::v2_token_utils::V2TokenEvent,
+        postgres::models::token_models::token_utils::TokenEvent,
+    },
+    schema::collection_stat_changes,
+    utils::{
+        database::{execute_with_better_error, ArcDbPool, DbPoolConnection},
+        util::standardize_address,
+    },
+};
+use ahash::{AHashMap, AHashSet};
+use aptos_protos::transaction::v1::{transaction::TxnData, Event, Transaction};
+use bigdecimal::{BigDecimal, One, Zero};
+use diesel::{
+    sql_query,
+    sql_types::{Array, Text},
+    QueryableByName,
+};
+use diesel_async::RunQueryDsl;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// Token data id to collection id, for resolving the collection of transferred tokens
+pub type TokenCollections = AHashMap<String, String>;
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(collection_id, transaction_version))]
+#[diesel(table_name = collection_stat_changes)]
+pub struct CollectionStatChange {
+    pub collection_id: String,
+    pub transaction_version: i64,
+    pub mints: BigDecimal,
+    pub burns: BigDecimal,
+    pub transfers: i64,
+}
+
+enum CollectionActivity {
+    Mint(BigDecimal),
+    Burn(BigDecimal),
+    Transfer,
+}
+
+impl CollectionStatChange {
+    /// Tokens transferred in `transactions`, whose collections have to be looked up since a
+    /// transfer event doesn't name it.
+    pub fn get_transferred_tokens(transactions: &[Transaction]) -> AHashSet<String> {
+        transactions
+            .iter()
+            .filter_map(|txn| match txn.txn_data.as_ref() {
+                Some(TxnData::User(user_txn)) => Some((txn.version as i64, &user_txn.events)),
+                _ => None,
+            })
+            .flat_map(|(txn_version, events)| {
+                events.iter().filter_map(move |event| {
+                    match V2TokenEvent::from_event(&event.type_str, &event.data, txn_version) {
+                        Ok(Some(V2TokenEvent::TransferEvent(inner))) => {
+                            Some(inner.get_object_address())
+                        },
+                        _ => None,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    /// The changes of each collection in `txn`. Transfers of objects not in `token_collections`
+    /// aren't tokens, or are tokens the processor hasn't indexed, and are left out.
+    pub fn from_transaction(txn: &Transaction, token_collections: &TokenCollections) -> Vec<Self> {
+        let Some(TxnData::User(user_txn)) = txn.txn_data.as_ref() else {
+            return vec![];
+        };
+        let txn_version = txn.version as i64;
+        let mut changes: AHashMap<String, Self> = AHashMap::new();
+        for event in &user_txn.events {
+            let (collection_id, activity) =
+                match Self::from_event(event, txn_version, token_collections) {
+                    Ok(Some(activity)) => activity,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        tracing::warn!(
+                            transaction_version = txn_version,
+                            event_type = %event.type_str,
+                            error = ?e,
+                            "Failed to parse token event for collection stats",
+                        );
+                        continue;
+                    },
+                };
+            let change = changes
+                .entry(collection_id.clone())
+                .or_insert_with(|| Self {
+                    collection_id,
+                    transaction_version: txn_version,
+                    mints: BigDecimal::zero(),
+                    burns: BigDecimal::zero(),
+                    transfers: 0,
+                });
+            match activity {
+                CollectionActivity::Mint(amount) => change.mints += amount,
+                CollectionActivity::Burn(amount) => change.burns += amount,
+                CollectionActivity::Transfer => change.transfers += 1,
+            }
+        }
+        let mut changes = changes.into_values().collect::<Vec<_>>();
+        changes.sort_by(|a, b| a.collection_id.cmp(&b.collection_id));
+        changes
+    }
+
+    fn from_event(
+        event: &Event,
+        txn_version: i64,
+        token_collections: &TokenCollections,
+    ) -> anyhow::Result<Option<(String, CollectionActivity)>> {
+        // The older v2 mint and burn events are emitted on the collection's event handle
+        let event_account_address = || {
+            event
+                .key
+                .as_ref()
+                .map(|key| standardize_address(&key.account_address))
+        };
+        if let Some(token_event) =
+            V2TokenEvent::from_event(&event.type_str, &event.data, txn_version)?
+        {
+            let activity = match token_event {
+                V2TokenEvent::Mint(inner) => Some((
+                    inner.get_collection_address(),
+                    CollectionActivity::Mint(BigDecimal::one()),
+                )),
+                V2TokenEvent::MintEvent(_) => event_account_address()
+                    .map(|collection| (collection, CollectionActivity::Mint(BigDecimal::one()))),
+                V2TokenEvent::Burn(inner) => Some((
+                    inner.get_collection_address(),
+                    CollectionActivity::Burn(BigDecimal::one()),
+                )),
+                V2TokenEvent::BurnEvent(_) => event_account_address()
+                    .map(|collection| (collection, CollectionActivity::Burn(BigDecimal::one()))),
+                V2TokenEvent::TransferEvent(inner) => token_collections
+                    .get(&inner.get_object_address())
+                    .map(|collection| (collection.clone(), CollectionActivity::Transfer)),
+                _ => None,
+            };
+            return Ok(activity);
+        }
+        // Token v1 transfers are withdraw and deposit pairs, so only mints and burns are counted
+        let activity = match TokenEvent::from_event(&event.type_str, &event.data, txn_version)? {
+            Some(TokenEvent::MintTokenEvent(inner)) => Some((
+                inner.id.get_collection_id(),
+                CollectionActivity::Mint(inner.amount),
+            )),
+            Some(TokenEvent::Mint(inner)) => Some((
+                inner.id.get_collection_id(),
+                CollectionActivity::Mint(inner.amount),
+            )),
+            Some(TokenEvent::BurnTokenEvent(inner)) => Some((
+                inner.id.token_data_id.get_collection_id(),
+                CollectionActivity::Burn(inner.amount),
+            )),
+            Some(TokenEvent::Burn(inner)) => Some((
+                inner.id.token_data_id.get_collection_id(),
+                CollectionActivity::Burn(inner.amount),
+            )),
+            _ => None,
+        };
+        Ok(activity)
+    }
+}
+
+#[derive(QueryableByName)]
+struct TokenCollection {
+    #[diesel(sql_type = Text)]
+    token_data_id: String,
+    #[diesel(sql_type = Text)]
+    collection_id: String,
+}
+
+/// Looks up the collections of tokens indexed in earlier batches.
+pub async fn get_token_collections(
+    conn: &mut DbPoolConnection<'_>,
+    token_data_ids: Vec<String>,
+) -> anyhow::Result<TokenCollections> {
+    if token_data_ids.is_empty() {
+        return Ok(TokenCollections::new());
+    }
+    let rows: Vec<TokenCollection> = sql_query(
+        "SELECT token_data_id, collection_id FROM current_token_datas_v2 \
+         WHERE token_data_id = ANY($1)",
+    )
+    .bind::<Array<Text>, _>(token_data_ids)
+    .get_results(conn)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| (row.token_data_id, row.collection_id))
+        .collect())
+}
+
+const REFRESH_COLLECTION_STATS_QUERY: &str = "
+INSERT INTO collection_stats AS c (
+    collection_id, current_supply, total_mints, total_burns, total_transfers,
+    last_activity_version
+)
+SELECT collection_id, SUM(mints) - SUM(burns), SUM(mints), SUM(burns), SUM(transfers),
+    MAX(transaction_version)
+FROM collection_stat_changes
+WHERE collection_id = ANY($1)
+GROUP BY collection_id
+ORDER BY collection_id
+ON CONFLICT (collection_id) DO UPDATE SET
+    current_supply = EXCLUDED.current_supply,
+    total_mints = EXCLUDED.total_mints,
+    total_burns = EXCLUDED.total_burns,
+    total_transfers = EXCLUDED.total_transfers,
+    last_activity_version = EXCLUDED.last_activity_version,
+    inserted_at = NOW()
+WHERE (c.last_activity_version, c.total_mints + c.total_burns + c.total_transfers)
+    <= (EXCLUDED.last_activity_version,
+        EXCLUDED.total_mints + EXCLUDED.total_burns + EXCLUDED.total_transfers)
+";
+
+/// Recomputes the stats of the collections in `changes` once the changes are written.
+pub async fn refresh_collection_stats(
+    pool: ArcDbPool,
+    changes: &[CollectionStatChange],
+) -> diesel::QueryResult<usize> {
+    let mut collection_ids = changes
+        .iter()
+        .map(|change| change.collection_id.clone())
+        .collect::<Vec<_>>();
+    if collection_ids.is_empty() {
+        return Ok(0);
+    }
+    collection_ids.sort();
+    collection_ids.dedup();
+    let query = sql_query(REFRESH_COLLECTION_STATS_QUERY).bind::<Array<Text>, _>(collection_ids);
+    execute_with_better_error(pool, query, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{EventKey, UserTransaction};
+
+    const COLLECTION: &str = "0x000000000000000000000000000000000000000000000000000000000000c011";
+    const TOKEN: &str = "0x00000000000000000000000000000000000000000000000000000000000070c1";
+    const OWNER: &str = "0x0000000000000000000000000000000000000000000000000000000000000a11";
+
+    fn event(type_str: &str, data: serde_json::Value) -> Event {
+        Event {
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    fn user_txn(version: u64, events: Vec<Event>) -> Transaction {
+        Transaction {
+            version,
+            txn_data: Some(TxnData::User(UserTransaction {
+                events,
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn test_token_v2_changes() {
+        let mint = event(
+            "0x4::collection::Mint",
+            serde_json::json!({"collection": COLLECTION, "index": {"value": "1"}, "token": TOKEN}),
+        );
+        let transfer = event(
+            "0x1::object::Transfer",
+            serde_json::json!({"from": OWNER, "to": COLLECTION, "object": TOKEN}),
+        );
+        // The older burn event names the collection by the handle it's emitted on
+        let burn = Event {
+            key: Some(EventKey {
+                account_address: COLLECTION.to_string(),
+                ..EventKey::default()
+            }),
+            ..event(
+                "0x4::collection::BurnEvent",
+                serde_json::json!({"index": "1", "token": TOKEN}),
+            )
+        };
+        let txn = user_txn(10, vec![mint, transfer.clone(), burn]);
+
+        // Without the token's collection, the transfer is left out
+        let changes = CollectionStatChange::from_transaction(&txn, &TokenCollections::new());
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].collection_id, COLLECTION);
+        assert_eq!(changes[0].transaction_version, 10);
+        assert_eq!(changes[0].mints, BigDecimal::one());
+        assert_eq!(changes[0].burns, BigDecimal::one());
+        assert_eq!(changes[0].transfers, 0);
+
+        let token_collections =
+            TokenCollections::from_iter([(TOKEN.to_string(), COLLECTION.to_string())]);
+        let changes = CollectionStatChange::from_transaction(&txn, &token_collections);
+        assert_eq!(changes[0].transfers, 1);
+        assert_eq!(
+            CollectionStatChange::get_transferred_tokens(&[user_txn(11, vec![transfer])]),
+            AHashSet::from_iter([TOKEN.to_string()])
+        );
+    }
+}
@@ -0,0 +1,431 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+// This is required because a diesel macro makes clippy sad
+#![allow(clippy::extra_unused_lifetimes)]
+#![allow(clippy::unused_unit)]
+
+use crate::{
+    db::{
+        common::models::token_v2_models::v2_token_utils::ResourceReference,
+        postgres::models::token_models::token_utils::{
+            CollectionDataIdType, TokenDataIdType, NAME_LENGTH,
+        },
+    },
+    schema::marketplace_activities,
+    utils::util::{parse_transaction_timestamp, standardize_address, truncate_str},
+};
+use aptos_protos::transaction::v1::{transaction::TxnData, Event, Transaction};
+use bigdecimal::{BigDecimal, One};
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// A marketplace contract whose events are resolved into `marketplace_activities`. The contract
+/// is expected to emit the events of the Aptos example marketplace (`<address>::events`).
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarketplaceConfig {
+    /// Recorded in the `marketplace` column
+    pub name: String,
+    pub contract_address: String,
+}
+
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(transaction_version, event_index))]
+#[diesel(table_name = marketplace_activities)]
+pub struct MarketplaceActivity {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub marketplace: String,
+    pub contract_address: String,
+    pub activity_type: String,
+    pub offer_or_listing_id: String,
+    pub collection_id: String,
+    pub creator_address: String,
+    pub collection_name: String,
+    pub token_data_id: Option<String>,
+    pub token_name: Option<String>,
+    pub token_standard: String,
+    pub price: BigDecimal,
+    pub token_amount: BigDecimal,
+    pub buyer: Option<String>,
+    pub seller: Option<String>,
+    pub event_type: String,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+}
+
+#[derive(Debug, Deserialize)]
+struct OptionalObject {
+    vec: Vec<ResourceReference>,
+}
+
+impl OptionalObject {
+    fn get_address(&self) -> Option<String> {
+        self.vec
+            .first()
+            .map(|object| object.get_reference_address())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenMetadata {
+    creator_address: String,
+    collection_name: String,
+    collection: OptionalObject,
+    token_name: String,
+    token: OptionalObject,
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectionMetadata {
+    creator_address: String,
+    collection_name: String,
+    collection: OptionalObject,
+}
+
+/// Fields of all marketplace events, each event only has some of them. Amounts are u64 and so
+/// come as strings.
+#[derive(Debug, Deserialize)]
+struct MarketplaceEventData {
+    listing: Option<String>,
+    token_offer: Option<String>,
+    collection_offer: Option<String>,
+    price: Option<String>,
+    new_bid: Option<String>,
+    token_amount: Option<String>,
+    remaining_token_amount: Option<String>,
+    seller: Option<String>,
+    purchaser: Option<String>,
+    new_bidder: Option<String>,
+    token_metadata: Option<TokenMetadata>,
+    collection_metadata: Option<CollectionMetadata>,
+}
+
+/// Collection and token an activity is about. Token v2 events carry the object addresses, for
+/// token v1 the ids are derived from creator, collection and token name.
+struct ResolvedMetadata {
+    collection_id: String,
+    creator_address: String,
+    collection_name: String,
+    token_data_id: Option<String>,
+    token_name: Option<String>,
+    token_standard: &'static str,
+}
+
+impl From<&TokenMetadata> for ResolvedMetadata {
+    fn from(metadata: &TokenMetadata) -> Self {
+        let creator_address = standardize_address(&metadata.creator_address);
+        let (collection_id, token_data_id, token_standard) = match (
+            metadata.collection.get_address(),
+            metadata.token.get_address(),
+        ) {
+            (Some(collection_id), Some(token_data_id)) => (collection_id, token_data_id, "v2"),
+            _ => {
+                let token_data_id_struct = TokenDataIdType::new(
+                    creator_address.clone(),
+                    metadata.collection_name.clone(),
+                    metadata.token_name.clone(),
+                );
+                (
+                    token_data_id_struct.get_collection_id(),
+                    token_data_id_struct.to_id(),
+                    "v1",
+                )
+            },
+        };
+        Self {
+            collection_id,
+            creator_address,
+            collection_name: truncate_str(&metadata.collection_name, NAME_LENGTH),
+            token_data_id: Some(token_data_id),
+            token_name: Some(truncate_str(&metadata.token_name, NAME_LENGTH)),
+            token_standard,
+        }
+    }
+}
+
+impl From<&CollectionMetadata> for ResolvedMetadata {
+    fn from(metadata: &CollectionMetadata) -> Self {
+        let creator_address = standardize_address(&metadata.creator_address);
+        let (collection_id, token_standard) = match metadata.collection.get_address() {
+            Some(collection_id) => (collection_id, "v2"),
+            None => (
+                CollectionDataIdType::new(
+                    creator_address.clone(),
+                    metadata.collection_name.clone(),
+                )
+                .to_id(),
+                "v1",
+            ),
+        };
+        Self {
+            collection_id,
+            creator_address,
+            collection_name: truncate_str(&metadata.collection_name, NAME_LENGTH),
+            token_data_id: None,
+            token_name: None,
+            token_standard,
+        }
+    }
+}
+
+fn parse_amount(amount: &Option<String>) -> Option<BigDecimal> {
+    amount.as_deref().and_then(|a| BigDecimal::from_str(a).ok())
+}
+
+impl MarketplaceActivity {
+    pub fn from_transaction(txn: &Transaction, marketplaces: &[MarketplaceConfig]) -> Vec<Self> {
+        let Some(TxnData::User(user_txn)) = txn.txn_data.as_ref() else {
+            return vec![];
+        };
+        let txn_version = txn.version as i64;
+        let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
+        let mut activities = vec![];
+        for (index, event) in user_txn.events.iter().enumerate() {
+            match Self::from_event(
+                event,
+                index as i64,
+                txn_version,
+                txn_timestamp,
+                marketplaces,
+            ) {
+                Ok(Some(activity)) => activities.push(activity),
+                Ok(None) => {},
+                Err(e) => tracing::warn!(
+                    transaction_version = txn_version,
+                    event_index = index,
+                    event_type = %event.type_str,
+                    error = ?e,
+                    "Failed to parse marketplace event",
+                ),
+            }
+        }
+        activities
+    }
+
+    fn from_event(
+        event: &Event,
+        event_index: i64,
+        txn_version: i64,
+        txn_timestamp: chrono::NaiveDateTime,
+        marketplaces: &[MarketplaceConfig],
+    ) -> anyhow::Result<Option<Self>> {
+        let mut parts = event.type_str.splitn(3, "::");
+        let (Some(address), Some("events"), Some(event_name)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Ok(None);
+        };
+        let contract_address = standardize_address(address);
+        let Some(marketplace) = marketplaces
+            .iter()
+            .find(|m| standardize_address(&m.contract_address) == contract_address)
+        else {
+            return Ok(None);
+        };
+        let activity_type = match event_name {
+            "ListingPlacedEvent" => "listing_placed",
+            "ListingCanceledEvent" => "listing_canceled",
+            "ListingFilledEvent" => "listing_filled",
+            "TokenOfferPlacedEvent" => "token_offer_placed",
+            "TokenOfferCanceledEvent" => "token_offer_canceled",
+            "TokenOfferFilledEvent" => "token_offer_filled",
+            "CollectionOfferPlacedEvent" => "collection_offer_placed",
+            "CollectionOfferCanceledEvent" => "collection_offer_canceled",
+            "CollectionOfferFilledEvent" => "collection_offer_filled",
+            "AuctionBidEvent" => "auction_bid",
+            _ => return Ok(None),
+        };
+
+        let data: MarketplaceEventData = serde_json::from_str(&event.data)?;
+        let metadata = match (&data.token_metadata, &data.collection_metadata) {
+            (Some(token_metadata), _) => ResolvedMetadata::from(token_metadata),
+            (None, Some(collection_metadata)) => ResolvedMetadata::from(collection_metadata),
+            (None, None) => anyhow::bail!("Event has neither token nor collection metadata"),
+        };
+        let offer_or_listing_id = data
+            .listing
+            .as_deref()
+            .or(data.token_offer.as_deref())
+            .or(data.collection_offer.as_deref())
+            .map(standardize_address)
+            .ok_or_else(|| anyhow::anyhow!("Event has no listing or offer id"))?;
+        let (price, buyer) = if activity_type == "auction_bid" {
+            (parse_amount(&data.new_bid), data.new_bidder.as_deref())
+        } else {
+            (parse_amount(&data.price), data.purchaser.as_deref())
+        };
+        let price = price.ok_or_else(|| anyhow::anyhow!("Event has no price"))?;
+        // Collection offers are for a number of tokens, everything else is for one
+        let token_amount = match activity_type {
+            "collection_offer_placed" => parse_amount(&data.token_amount),
+            "collection_offer_canceled" => parse_amount(&data.remaining_token_amount),
+            _ => Some(BigDecimal::one()),
+        }
+        .unwrap_or_else(BigDecimal::one);
+
+        Ok(Some(Self {
+            transaction_version: txn_version,
+            event_index,
+            marketplace: marketplace.name.clone(),
+            contract_address,
+            activity_type: activity_type.to_string(),
+            offer_or_listing_id,
+            collection_id: metadata.collection_id,
+            creator_address: metadata.creator_address,
+            collection_name: metadata.collection_name,
+            token_data_id: metadata.token_data_id,
+            token_name: metadata.token_name,
+            token_standard: metadata.token_standard.to_string(),
+            price,
+            token_amount,
+            buyer: buyer.map(standardize_address),
+            seller: data.seller.as_deref().map(standardize_address),
+            event_type: event.type_str.clone(),
+            transaction_timestamp: txn_timestamp,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::{transaction::v1::UserTransaction, util::timestamp::Timestamp};
+
+    const CONTRACT_ADDRESS: &str =
+        "0x6de37368e31dff4580b211295198159ee6f98b42ffa93c5683bb955ca1be67e0";
+
+    fn marketplaces() -> Vec<MarketplaceConfig> {
+        vec![MarketplaceConfig {
+            name: "example_v2_marketplace".to_string(),
+            contract_address: CONTRACT_ADDRESS.to_string(),
+        }]
+    }
+
+    fn user_txn(version: u64, events: Vec<(&str, &str)>) -> Transaction {
+        Transaction {
+            version,
+            timestamp: Some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 0,
+            }),
+            txn_data: Some(TxnData::User(UserTransaction {
+                events: events
+                    .into_iter()
+                    .map(|(type_str, data)| Event {
+                        type_str: type_str.to_string(),
+                        data: data.to_string(),
+                        ..Event::default()
+                    })
+                    .collect(),
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn test_token_v2_listing() {
+        let txn = user_txn(
+            1_000,
+            vec![
+                (
+                    "0x1::object::TransferEvent",
+                    r#"{"from": "0xa", "object": "0xb", "to": "0xc"}"#,
+                ),
+                (
+                    &format!("{CONTRACT_ADDRESS}::events::ListingPlacedEvent"),
+                    r#"{
+                    "listing": "0x4d1b3a6e8c2f0a9d7e5b1c3f8a2d6e0b9c7f1a3e5d8b2c4f6a0e9d7b5c3a1f2e",
+                    "price": "150000000",
+                    "seller": "0xf0e1d2c3b4a5968778695a4b3c2d1e0ff0e1d2c3b4a5968778695a4b3c2d1e0f",
+                    "token_metadata": {
+                        "collection": {"vec": [{"inner": "0x2a1b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f809"}]},
+                        "collection_name": "Aptos Monkeys",
+                        "creator_address": "0x9a8b7c6d5e4f30211a2b3c4d5e6f70819a8b7c6d5e4f30211a2b3c4d5e6f7081",
+                        "property_version": {"vec": []},
+                        "token": {"vec": [{"inner": "0x7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a29180f"}]},
+                        "token_name": "Aptos Monkey #1024"
+                    },
+                    "type": "fixed price"
+                }"#,
+                ),
+            ],
+        );
+        let activities = MarketplaceActivity::from_transaction(&txn, &marketplaces());
+        assert_eq!(activities.len(), 1);
+        let listing = &activities[0];
+        assert_eq!(listing.event_index, 1);
+        assert_eq!(listing.activity_type, "listing_placed");
+        assert_eq!(listing.marketplace, "example_v2_marketplace");
+        assert_eq!(listing.token_standard, "v2");
+        assert_eq!(
+            listing.collection_id,
+            "0x2a1b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7f809"
+        );
+        assert_eq!(
+            listing.token_data_id.as_deref(),
+            Some("0x7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a29180f7e6d5c4b3a29180f")
+        );
+        assert_eq!(listing.token_name.as_deref(), Some("Aptos Monkey #1024"));
+        assert_eq!(listing.price, BigDecimal::from(150_000_000));
+        assert_eq!(listing.token_amount, BigDecimal::one());
+        assert_eq!(listing.buyer, None);
+        assert_eq!(
+            listing.seller.as_deref(),
+            Some("0xf0e1d2c3b4a5968778695a4b3c2d1e0ff0e1d2c3b4a5968778695a4b3c2d1e0f")
+        );
+    }
+
+    #[test]
+    fn test_token_v1_sale() {
+        let txn = user_txn(
+            2_000,
+            vec![(
+                &format!("{CONTRACT_ADDRESS}::events::ListingFilledEvent"),
+                r#"{
+                "commission": "3000000",
+                "listing": "0x4d1b3a6e8c2f0a9d7e5b1c3f8a2d6e0b9c7f1a3e5d8b2c4f6a0e9d7b5c3a1f2e",
+                "price": "150000000",
+                "purchaser": "0x1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d",
+                "royalties": "7500000",
+                "seller": "0xf0e1d2c3b4a5968778695a4b3c2d1e0ff0e1d2c3b4a5968778695a4b3c2d1e0f",
+                "token_metadata": {
+                    "collection": {"vec": []},
+                    "collection_name": "Aptos Monkeys",
+                    "creator_address": "0x9a8b7c6d5e4f30211a2b3c4d5e6f70819a8b7c6d5e4f30211a2b3c4d5e6f7081",
+                    "property_version": {"vec": ["0"]},
+                    "token": {"vec": []},
+                    "token_name": "Aptos Monkey #1024"
+                },
+                "type": "fixed price"
+            }"#,
+            )],
+        );
+        let activities = MarketplaceActivity::from_transaction(&txn, &marketplaces());
+        assert_eq!(activities.len(), 1);
+        let sale = &activities[0];
+        let token_data_id_struct = TokenDataIdType::new(
+            "0x9a8b7c6d5e4f30211a2b3c4d5e6f70819a8b7c6d5e4f30211a2b3c4d5e6f7081".to_string(),
+            "Aptos Monkeys".to_string(),
+            "Aptos Monkey #1024".to_string(),
+        );
+        assert_eq!(sale.activity_type, "listing_filled");
+        assert_eq!(sale.token_standard, "v1");
+        assert_eq!(sale.collection_id, token_data_id_struct.get_collection_id());
+        assert_eq!(sale.token_data_id, Some(token_data_id_struct.to_id()));
+        assert_eq!(sale.price, BigDecimal::from(150_000_000));
+        assert_eq!(
+            sale.buyer.as_deref(),
+            Some("0x1c2d3e4f5a6b7c8d9e0f1a2b3c4d5e6f7a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d")
+        );
+        assert_eq!(
+            sale.seller.as_deref(),
+            Some("0xf0e1d2c3b4a5968778695a4b3c2d1e0ff0e1d2c3b4a5968778695a4b3c2d1e0f")
+        );
+
+        // Not a configured marketplace
+        assert!(MarketplaceActivity::from_transaction(&txn, &[]).is_empty());
+    }
+}
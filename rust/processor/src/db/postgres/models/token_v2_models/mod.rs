@@ -1,6 +1,9 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod collection_creator_cache;
+pub mod collection_stats;
+pub mod marketplace_activities;
 pub mod v1_token_royalty;
 pub mod v2_collections;
 pub mod v2_token_activities;
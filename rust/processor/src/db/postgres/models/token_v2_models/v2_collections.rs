@@ -18,6 +18,7 @@ use crate::{
                 token_utils::{CollectionDataIdType, TokenWriteSet},
                 tokens::TableHandleToOwner,
             },
+            token_v2_models::collection_creator_cache::CollectionCreatorCache,
         },
     },
     schema::{collections_v2, current_collections_v2},
@@ -220,17 +221,40 @@ impl CollectionV2 {
             let maybe_creator_address = table_handle_to_owner
                 .get(&standardize_address(&table_handle))
                 .map(|table_metadata| table_metadata.get_owner_address());
+            let creator_cache = CollectionCreatorCache::global();
             let mut creator_address = match maybe_creator_address {
-                Some(ca) => ca,
+                Some(ca) => {
+                    // The stream is the source of truth, replace whatever was cached
+                    if let Some(cache) = creator_cache {
+                        cache.insert(table_handle.clone(), ca.clone());
+                    }
+                    ca
+                },
                 None => {
-                    match Self::get_collection_creator_for_v1(
-                        conn,
-                        &table_handle,
-                        query_retries,
-                        query_retry_delay_ms,
-                    )
-                    .await
-                    .context(format!(
+                    let lookup = match creator_cache {
+                        Some(cache) => {
+                            cache
+                                .get_or_try_fetch(&table_handle, || {
+                                    Self::get_collection_creator_for_v1(
+                                        conn,
+                                        &table_handle,
+                                        query_retries,
+                                        query_retry_delay_ms,
+                                    )
+                                })
+                                .await
+                        },
+                        None => {
+                            Self::get_collection_creator_for_v1(
+                                conn,
+                                &table_handle,
+                                query_retries,
+                                query_retry_delay_ms,
+                            )
+                            .await
+                        },
+                    };
+                    match lookup.context(format!(
                         "Failed to get collection creator for table handle {}, txn version {}",
                         table_handle, txn_version
                     )) {
@@ -3,3 +3,4 @@
 
 pub mod signatures;
 pub mod user_transactions;
+pub mod vm_status;
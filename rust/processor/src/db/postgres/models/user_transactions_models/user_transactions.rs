@@ -7,18 +7,20 @@
 #![allow(clippy::extra_unused_lifetimes)]
 #![allow(clippy::unused_unit)]
 
-use super::signatures::Signature;
+use super::{signatures::Signature, vm_status::VmStatusFailure};
 use crate::{
     schema::user_transactions,
     utils::util::{
         get_entry_function_contract_address_from_user_request,
         get_entry_function_from_user_request, get_entry_function_function_name_from_user_request,
-        get_entry_function_module_name_from_user_request, parse_timestamp, standardize_address,
-        u64_to_bigdecimal,
+        get_entry_function_module_name_from_user_request, get_script_payload_from_user_request,
+        parse_timestamp, standardize_address, u64_to_bigdecimal,
     },
 };
 use aptos_protos::{
-    transaction::v1::{UserTransaction as UserTransactionPB, UserTransactionRequest},
+    transaction::v1::{
+        TransactionInfo, UserTransaction as UserTransactionPB, UserTransactionRequest,
+    },
     util::timestamp::Timestamp,
 };
 use bigdecimal::BigDecimal;
@@ -43,11 +45,20 @@ pub struct UserTransaction {
     pub entry_function_contract_address: Option<String>,
     pub entry_function_module_name: Option<String>,
     pub entry_function_function_name: Option<String>,
+    /// Script and arguments of script transactions, None for other payloads
+    pub script_payload: Option<serde_json::Value>,
+    /// None if the transaction info is missing
+    pub success: Option<bool>,
+    pub vm_status: Option<String>,
+    /// Parsed from `vm_status` for failed transactions, see `VmStatusFailure`
+    pub abort_code: Option<BigDecimal>,
+    pub abort_location: Option<String>,
 }
 
 impl UserTransaction {
     pub fn from_transaction(
         txn: &UserTransactionPB,
+        transaction_info: Option<&TransactionInfo>,
         timestamp: &Timestamp,
         block_height: i64,
         epoch: i64,
@@ -57,6 +68,10 @@ impl UserTransaction {
             .request
             .as_ref()
             .expect("Sends is not present in user txn");
+        let failure = match transaction_info {
+            Some(info) if !info.success => VmStatusFailure::parse(&info.vm_status),
+            _ => VmStatusFailure::default(),
+        };
         (
             Self {
                 version,
@@ -96,6 +111,11 @@ impl UserTransaction {
                     get_entry_function_function_name_from_user_request(user_request)
                         .unwrap_or_default(),
                 ),
+                script_payload: get_script_payload_from_user_request(user_request, version),
+                success: transaction_info.map(|info| info.success),
+                vm_status: transaction_info.map(|info| info.vm_status.clone()),
+                abort_code: failure.abort_code.map(u64_to_bigdecimal),
+                abort_location: failure.abort_location,
             },
             Self::get_signatures(user_request, version, block_height),
         )
@@ -0,0 +1,116 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Where and with what code a failed transaction aborted, parsed from the VM status the node
+//! explains it with, e.g. `Move abort in 0x1::coin: EINSUFFICIENT_BALANCE(0x10006): Not enough
+//! coins to complete transaction`.
+
+use crate::utils::util::standardize_address;
+
+/// The failure in a VM status. Both are None for successful transactions and for failures that
+/// don't happen in Move code, e.g. running out of gas.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct VmStatusFailure {
+    /// Abort code of a `MoveAbort`
+    pub abort_code: Option<u64>,
+    /// Module of a `MoveAbort` (`script` for aborts in a script), or function of an
+    /// `ExecutionFailure`, with the address standardized, e.g. `0x0...01::coin`
+    pub abort_location: Option<String>,
+}
+
+impl VmStatusFailure {
+    pub fn parse(vm_status: &str) -> Self {
+        if let Some(rest) = vm_status.strip_prefix("Move abort in ") {
+            // `Move abort in {location}: {reason}({code}): {description}`, or
+            // `Move abort in {location}: {code}` when the module has no error descriptions
+            let (location, detail) = rest.split_once(": ").unwrap_or((rest, ""));
+            let code = match detail.split_once('(') {
+                Some((_, after_reason)) => after_reason.split(')').next().unwrap_or_default(),
+                None => detail,
+            };
+            Self {
+                abort_code: parse_hex_code(code),
+                abort_location: Some(standardize_location(location)),
+            }
+        } else if let Some(rest) = vm_status.strip_prefix("Move abort") {
+            // Aborts in scripts have no module, e.g. `Move abort: code 0x1`
+            Self {
+                abort_code: rest.split_whitespace().find_map(parse_hex_code),
+                abort_location: Some("script".to_string()),
+            }
+        } else if let Some(rest) = vm_status.strip_prefix("Execution failed in ") {
+            // `Execution failed in {function} at code offset {offset}`
+            let function = rest.split(" at code offset").next().unwrap_or(rest);
+            Self {
+                abort_code: None,
+                abort_location: Some(standardize_location(function)),
+            }
+        } else {
+            Self::default()
+        }
+    }
+}
+
+fn parse_hex_code(code: &str) -> Option<u64> {
+    let code = code
+        .trim()
+        .trim_end_matches(|c: char| !c.is_ascii_hexdigit());
+    u64::from_str_radix(code.strip_prefix("0x")?, 16).ok()
+}
+
+/// Standardizes the address of `0x1::coin` or `0x1::coin::transfer`, leaving anything that
+/// doesn't start with an address as it is.
+fn standardize_location(location: &str) -> String {
+    match location.split_once("::") {
+        Some((address, path)) if address.starts_with("0x") => {
+            format!("{}::{}", standardize_address(address), path)
+        },
+        _ => location.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COIN: &str = "0x0000000000000000000000000000000000000000000000000000000000000001::coin";
+
+    #[test]
+    fn test_move_abort() {
+        let failure = VmStatusFailure::parse(
+            "Move abort in 0x1::coin: EINSUFFICIENT_BALANCE(0x10006): Not enough coins to \
+             complete transaction",
+        );
+        assert_eq!(failure.abort_code, Some(0x10006));
+        assert_eq!(failure.abort_location.as_deref(), Some(COIN));
+
+        // Without error descriptions in the module
+        let failure = VmStatusFailure::parse("Move abort in 0x1::coin: 0x10006");
+        assert_eq!(failure.abort_code, Some(0x10006));
+        assert_eq!(failure.abort_location.as_deref(), Some(COIN));
+
+        let failure = VmStatusFailure::parse("Move abort: code 0x2a");
+        assert_eq!(failure.abort_code, Some(42));
+        assert_eq!(failure.abort_location.as_deref(), Some("script"));
+    }
+
+    #[test]
+    fn test_execution_failure() {
+        let failure =
+            VmStatusFailure::parse("Execution failed in 0x1::coin::transfer at code offset 12");
+        assert_eq!(failure.abort_code, None);
+        assert_eq!(failure.abort_location, Some(format!("{}::transfer", COIN)));
+    }
+
+    #[test]
+    fn test_other_statuses() {
+        assert_eq!(
+            VmStatusFailure::parse("Executed successfully"),
+            VmStatusFailure::default()
+        );
+        assert_eq!(
+            VmStatusFailure::parse("Out of gas"),
+            VmStatusFailure::default()
+        );
+    }
+}
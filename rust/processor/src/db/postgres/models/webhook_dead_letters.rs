@@ -0,0 +1,21 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::schema::webhook_dead_letters;
+use field_count::FieldCount;
+use serde::{Deserialize, Serialize};
+
+/// An event that couldn't be delivered to the webhook after all retries, kept with the
+/// CloudEvents envelope so it can be redelivered later instead of blocking the processor.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize)]
+#[diesel(primary_key(transaction_version, event_index))]
+#[diesel(table_name = webhook_dead_letters)]
+pub struct WebhookDeadLetter {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub url: String,
+    pub cloud_event: serde_json::Value,
+    pub error: String,
+}
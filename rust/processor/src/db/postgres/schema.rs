@@ -1,5 +1,17 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    account_sequence_numbers (transaction_version) {
+        transaction_version -> Int8,
+        #[max_length = 66]
+        account_address -> Varchar,
+        sequence_number -> Int8,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+        block_height -> Nullable<Int8>,
+    }
+}
+
 diesel::table! {
     account_transactions (account_address, transaction_version) {
         transaction_version -> Int8,
@@ -9,6 +21,25 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    allowance_activities (transaction_version, event_index) {
+        transaction_version -> Int8,
+        event_index -> Int8,
+        #[max_length = 66]
+        owner_address -> Varchar,
+        #[max_length = 66]
+        spender_address -> Varchar,
+        #[max_length = 1000]
+        asset_type -> Varchar,
+        #[max_length = 50]
+        activity_type -> Varchar,
+        amount -> Numeric,
+        is_unlimited -> Bool,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     ans_lookup (transaction_version, write_set_change_index) {
         transaction_version -> Int8,
@@ -98,6 +129,42 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    block_end_transactions (version) {
+        version -> Int8,
+        block_height -> Int8,
+        epoch -> Int8,
+        #[max_length = 50]
+        transaction_type -> Varchar,
+        timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    block_gas_stats (block_height) {
+        block_height -> Int8,
+        first_version -> Int8,
+        last_version -> Int8,
+        block_timestamp -> Nullable<Timestamp>,
+        transaction_count -> Int8,
+        total_gas_used -> Numeric,
+        total_fee_octas -> Numeric,
+        total_gas_unit_price -> Numeric,
+        avg_gas_unit_price -> Nullable<Numeric>,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    block_gas_stats_parts (block_height, first_version) {
+        block_height -> Int8,
+        first_version -> Int8,
+        last_version -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     block_metadata_transactions (version) {
         version -> Int8,
@@ -194,6 +261,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    coin_to_fa_mapping (coin_type_hash) {
+        #[max_length = 64]
+        coin_type_hash -> Varchar,
+        #[max_length = 5000]
+        coin_type -> Varchar,
+        #[max_length = 66]
+        fa_metadata_address -> Varchar,
+        transaction_version -> Int8,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     collection_datas (collection_data_id_hash, transaction_version) {
         #[max_length = 64]
@@ -218,6 +299,31 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    collection_stat_changes (collection_id, transaction_version) {
+        #[max_length = 66]
+        collection_id -> Varchar,
+        transaction_version -> Int8,
+        mints -> Numeric,
+        burns -> Numeric,
+        transfers -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    collection_stats (collection_id) {
+        #[max_length = 66]
+        collection_id -> Varchar,
+        current_supply -> Numeric,
+        total_mints -> Numeric,
+        total_burns -> Numeric,
+        total_transfers -> Int8,
+        last_activity_version -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     collections_v2 (transaction_version, write_set_change_index) {
         transaction_version -> Int8,
@@ -246,6 +352,35 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    current_account_sequence_numbers (account_address) {
+        #[max_length = 66]
+        account_address -> Varchar,
+        sequence_number -> Int8,
+        last_transaction_version -> Int8,
+        last_transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    current_allowances (owner_address, spender_address, asset_type) {
+        #[max_length = 66]
+        owner_address -> Varchar,
+        #[max_length = 66]
+        spender_address -> Varchar,
+        #[max_length = 1000]
+        asset_type -> Varchar,
+        amount -> Numeric,
+        is_unlimited -> Bool,
+        approval_transaction_version -> Int8,
+        last_transaction_version -> Int8,
+        last_transaction_timestamp -> Timestamp,
+        num_activities -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     current_ans_lookup (domain, subdomain) {
         #[max_length = 64]
@@ -483,6 +618,36 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    current_fungible_asset_supply (asset_type) {
+        #[max_length = 1000]
+        asset_type -> Varchar,
+        supply -> Numeric,
+        last_transaction_version -> Int8,
+        last_transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    current_move_resources (address, resource_type_hash) {
+        #[max_length = 66]
+        address -> Varchar,
+        #[max_length = 64]
+        resource_type_hash -> Varchar,
+        resource_type -> Text,
+        module -> Text,
+        name -> Text,
+        generic_type_params -> Nullable<Jsonb>,
+        data -> Nullable<Jsonb>,
+        is_deleted -> Bool,
+        #[max_length = 66]
+        state_key_hash -> Varchar,
+        last_transaction_version -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     current_objects (object_address) {
         #[max_length = 66]
@@ -780,6 +945,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    failed_events (processor, transaction_version, event_index) {
+        #[max_length = 100]
+        processor -> Varchar,
+        transaction_version -> Int8,
+        event_index -> Int8,
+        #[sql_name = "type"]
+        type_ -> Text,
+        data -> Text,
+        error -> Text,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     fungible_asset_activities (transaction_version, event_index) {
         transaction_version -> Int8,
@@ -858,6 +1037,83 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    fungible_asset_metadata_history (asset_type, transaction_version) {
+        #[max_length = 1000]
+        asset_type -> Varchar,
+        transaction_version -> Int8,
+        transaction_timestamp -> Timestamp,
+        #[max_length = 32]
+        name -> Varchar,
+        #[max_length = 10]
+        symbol -> Varchar,
+        decimals -> Int4,
+        #[max_length = 512]
+        icon_uri -> Nullable<Varchar>,
+        #[max_length = 512]
+        project_uri -> Nullable<Varchar>,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    gap_detector_status (processor) {
+        #[max_length = 100]
+        processor -> Varchar,
+        next_version_to_process -> Int8,
+        pending_batches -> Jsonb,
+        last_updated -> Timestamp,
+    }
+}
+
+diesel::table! {
+    governance_proposal_resolutions (proposal_id) {
+        proposal_id -> Int8,
+        yes_votes -> Numeric,
+        no_votes -> Numeric,
+        resolved_early -> Bool,
+        transaction_version -> Int8,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    governance_proposals (proposal_id) {
+        proposal_id -> Int8,
+        #[max_length = 66]
+        proposer_address -> Varchar,
+        #[max_length = 66]
+        stake_pool_address -> Varchar,
+        #[max_length = 200]
+        execution_hash -> Varchar,
+        metadata_location -> Nullable<Text>,
+        metadata_hash -> Nullable<Text>,
+        min_vote_threshold -> Numeric,
+        early_resolution_vote_threshold -> Nullable<Numeric>,
+        expiration_timestamp -> Timestamp,
+        transaction_version -> Int8,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    governance_votes (transaction_version, event_index) {
+        transaction_version -> Int8,
+        event_index -> Int8,
+        proposal_id -> Int8,
+        #[max_length = 66]
+        voter_address -> Varchar,
+        #[max_length = 66]
+        stake_pool_address -> Varchar,
+        num_votes -> Numeric,
+        should_pass -> Bool,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     indexer_status (db) {
         #[max_length = 50]
@@ -873,6 +1129,42 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    marketplace_activities (transaction_version, event_index) {
+        transaction_version -> Int8,
+        event_index -> Int8,
+        #[max_length = 100]
+        marketplace -> Varchar,
+        #[max_length = 66]
+        contract_address -> Varchar,
+        #[max_length = 50]
+        activity_type -> Varchar,
+        #[max_length = 66]
+        offer_or_listing_id -> Varchar,
+        #[max_length = 66]
+        collection_id -> Varchar,
+        #[max_length = 66]
+        creator_address -> Varchar,
+        #[max_length = 128]
+        collection_name -> Varchar,
+        #[max_length = 66]
+        token_data_id -> Nullable<Varchar>,
+        #[max_length = 128]
+        token_name -> Nullable<Varchar>,
+        #[max_length = 10]
+        token_standard -> Varchar,
+        price -> Numeric,
+        token_amount -> Numeric,
+        #[max_length = 66]
+        buyer -> Nullable<Varchar>,
+        #[max_length = 66]
+        seller -> Nullable<Varchar>,
+        event_type -> Text,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     move_modules (transaction_version, write_set_change_index) {
         transaction_version -> Int8,
@@ -941,6 +1233,57 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    outbox (id) {
+        id -> Int8,
+        #[max_length = 100]
+        table_name -> Varchar,
+        #[max_length = 10]
+        operation -> Varchar,
+        row_key -> Jsonb,
+        payload -> Jsonb,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    parquet_upload_checkpoints (processor, table_name, start_version) {
+        #[max_length = 100]
+        processor -> Varchar,
+        #[max_length = 100]
+        table_name -> Varchar,
+        start_version -> Int8,
+        end_version -> Int8,
+        end_version_struct_count -> Int8,
+        #[max_length = 1000]
+        file_path -> Varchar,
+        #[max_length = 20]
+        status -> Varchar,
+        last_updated -> Timestamp,
+    }
+}
+
+diesel::table! {
+    processor_leases (processor) {
+        #[max_length = 100]
+        processor -> Varchar,
+        #[max_length = 100]
+        holder_id -> Varchar,
+        fencing_token -> Int8,
+        expires_at -> Timestamp,
+        last_updated -> Timestamp,
+    }
+}
+
+diesel::table! {
+    processor_schema_versions (processor) {
+        #[max_length = 100]
+        processor -> Varchar,
+        schema_version -> Int8,
+        last_updated -> Timestamp,
+    }
+}
+
 diesel::table! {
     processor_status (processor) {
         #[max_length = 100]
@@ -951,6 +1294,18 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    processor_write_ahead_log (processor) {
+        #[max_length = 100]
+        processor -> Varchar,
+        start_version -> Int8,
+        end_version -> Int8,
+        #[max_length = 10]
+        state -> Varchar,
+        last_updated -> Timestamp,
+    }
+}
+
 diesel::table! {
     proposal_votes (transaction_version, proposal_id, voter_address) {
         transaction_version -> Int8,
@@ -1248,6 +1603,21 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    typed_events (transaction_version, event_index) {
+        transaction_version -> Int8,
+        event_index -> Int8,
+        #[sql_name = "type"]
+        type_ -> Text,
+        amount -> Nullable<Numeric>,
+        #[max_length = 66]
+        from_address -> Nullable<Varchar>,
+        #[max_length = 66]
+        to_address -> Nullable<Varchar>,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     user_transactions (version) {
         version -> Int8,
@@ -1271,6 +1641,23 @@ diesel::table! {
         entry_function_module_name -> Nullable<Varchar>,
         #[max_length = 255]
         entry_function_function_name -> Nullable<Varchar>,
+        script_payload -> Nullable<Jsonb>,
+        success -> Nullable<Bool>,
+        vm_status -> Nullable<Text>,
+        abort_code -> Nullable<Numeric>,
+        #[max_length = 1000]
+        abort_location -> Nullable<Varchar>,
+    }
+}
+
+diesel::table! {
+    webhook_dead_letters (transaction_version, event_index) {
+        transaction_version -> Int8,
+        event_index -> Int8,
+        url -> Text,
+        cloud_event -> Jsonb,
+        error -> Text,
+        inserted_at -> Timestamp,
     }
 }
 
@@ -1300,19 +1687,29 @@ diesel::table! {
 }
 
 diesel::allow_tables_to_appear_in_same_query!(
+    account_sequence_numbers,
     account_transactions,
+    allowance_activities,
     ans_lookup,
     ans_lookup_v2,
     ans_primary_name,
     ans_primary_name_v2,
     backfill_processor_status,
+    block_end_transactions,
+    block_gas_stats,
+    block_gas_stats_parts,
     block_metadata_transactions,
     coin_activities,
     coin_balances,
     coin_infos,
     coin_supply,
+    coin_to_fa_mapping,
     collection_datas,
+    collection_stat_changes,
+    collection_stats,
     collections_v2,
+    current_account_sequence_numbers,
+    current_allowances,
     current_ans_lookup,
     current_ans_lookup_v2,
     current_ans_primary_name,
@@ -1325,6 +1722,8 @@ diesel::allow_tables_to_appear_in_same_query!(
     current_delegator_balances,
     current_fungible_asset_balances,
     current_fungible_asset_balances_legacy,
+    current_fungible_asset_supply,
+    current_move_resources,
     current_objects,
     current_staking_pool_voter,
     current_table_items,
@@ -1341,16 +1740,28 @@ diesel::allow_tables_to_appear_in_same_query!(
     delegator_balances,
     event_size_info,
     events,
+    failed_events,
     fungible_asset_activities,
     fungible_asset_balances,
     fungible_asset_metadata,
+    fungible_asset_metadata_history,
+    gap_detector_status,
+    governance_proposal_resolutions,
+    governance_proposals,
+    governance_votes,
     indexer_status,
     ledger_infos,
+    marketplace_activities,
     move_modules,
     move_resources,
     nft_points,
     objects,
+    outbox,
+    parquet_upload_checkpoints,
+    processor_leases,
+    processor_schema_versions,
     processor_status,
+    processor_write_ahead_log,
     proposal_votes,
     signatures,
     spam_assets,
@@ -1365,7 +1776,9 @@ diesel::allow_tables_to_appear_in_same_query!(
     tokens,
     transaction_size_info,
     transactions,
+    typed_events,
     user_transactions,
+    webhook_dead_letters,
     write_set_changes,
     write_set_size_info,
 );
@@ -59,6 +59,36 @@ impl DefaultGapDetector {
         }
     }
 
+    /// Rebuilds a detector from a persisted snapshot. The stored `next_version_to_process` is
+    /// the source of truth; pending batches that are already behind it are dropped.
+    pub fn from_snapshot(
+        next_version_to_process: u64,
+        pending_batches: Vec<DefaultProcessingResult>,
+    ) -> Self {
+        let seen_versions = pending_batches
+            .into_iter()
+            .filter(|batch| batch.start_version > next_version_to_process)
+            .map(|batch| (batch.start_version, batch))
+            .collect();
+        Self {
+            next_version_to_process,
+            seen_versions,
+            last_success_batch: None,
+        }
+    }
+
+    pub fn next_version_to_process(&self) -> u64 {
+        self.next_version_to_process
+    }
+
+    /// Batches that have been processed but are not yet contiguous with `next_version_to_process`.
+    pub fn pending_batches(&self) -> Vec<DefaultProcessingResult> {
+        let mut batches: Vec<DefaultProcessingResult> =
+            self.seen_versions.values().cloned().collect();
+        batches.sort_by_key(|batch| batch.start_version);
+        batches
+    }
+
     fn update_prev_batch(&mut self, result: DefaultProcessingResult) {
         let mut new_prev_batch = result;
         while let Some(next_version) = self.seen_versions.remove(&(new_prev_batch.end_version + 1))
@@ -89,6 +119,8 @@ mod test {
                 last_transaction_timestamp: None,
                 processing_duration_in_secs: 0.0,
                 db_insertion_duration_in_secs: 0.0,
+                num_events: None,
+                num_write_set_changes: None,
             };
             let default_gap_detector_result = default_gap_detector
                 .process_versions(ProcessingResult::DefaultProcessingResult(result))
@@ -112,6 +144,8 @@ mod test {
                     last_transaction_timestamp: None,
                     processing_duration_in_secs: 0.0,
                     db_insertion_duration_in_secs: 0.0,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             ))
             .unwrap();
@@ -140,4 +174,48 @@ mod test {
             199 + (DEFAULT_GAP_DETECTION_BATCH_SIZE - 1) * 100
         );
     }
+
+    #[tokio::test]
+    async fn restore_gap_after_restart_test() {
+        let batch = |start_version: u64, end_version: u64| DefaultProcessingResult {
+            start_version,
+            end_version,
+            last_transaction_timestamp: None,
+            processing_duration_in_secs: 0.0,
+            db_insertion_duration_in_secs: 0.0,
+            num_events: None,
+            num_write_set_changes: None,
+        };
+        let mut default_gap_detector = DefaultGapDetector::new(0);
+        default_gap_detector
+            .process_versions(ProcessingResult::DefaultProcessingResult(batch(0, 99)))
+            .unwrap();
+        // Leave [100, 199] outstanding
+        default_gap_detector
+            .process_versions(ProcessingResult::DefaultProcessingResult(batch(200, 299)))
+            .unwrap();
+
+        // Simulate a restart by rebuilding the detector from its persisted state
+        let mut restored_gap_detector = DefaultGapDetector::from_snapshot(
+            default_gap_detector.next_version_to_process(),
+            default_gap_detector.pending_batches(),
+        );
+        assert_eq!(restored_gap_detector.next_version_to_process(), 100);
+        assert_eq!(
+            restored_gap_detector.pending_batches(),
+            vec![batch(200, 299)]
+        );
+
+        // Filling the gap resolves the pending batch that was carried over
+        let result = restored_gap_detector
+            .process_versions(ProcessingResult::DefaultProcessingResult(batch(100, 199)))
+            .unwrap();
+        let result = match result {
+            GapDetectorResult::DefaultGapDetectorResult(res) => res,
+            _ => panic!("Invalid result type"),
+        };
+        assert_eq!(result.num_gaps, 0);
+        assert_eq!(result.next_version_to_process, 300);
+        assert_eq!(result.last_success_batch, Some(batch(200, 299)));
+    }
 }
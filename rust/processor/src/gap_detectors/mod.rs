@@ -1,25 +1,38 @@
 use crate::{
     bq_analytics::ParquetProcessingResult,
+    db::postgres::models::gap_detector_status::{GapDetectorStatus, GapDetectorStatusQuery},
     gap_detectors::{
         gap_detector::{DefaultGapDetector, DefaultGapDetectorResult},
         parquet_gap_detector::{ParquetFileGapDetectorInner, ParquetFileGapDetectorResult},
     },
     processors::{DefaultProcessingResult, Processor, ProcessorTrait},
-    utils::counters::{PARQUET_PROCESSOR_DATA_GAP_COUNT, PROCESSOR_DATA_GAP_COUNT},
+    schema::gap_detector_status,
+    utils::{
+        counters::{PARQUET_PROCESSOR_DATA_GAP_COUNT, PROCESSOR_DATA_GAP_COUNT},
+        database::{execute_with_better_error, processor_status_key, ArcDbPool},
+        in_flight_versions::InFlightVersions,
+        progress_lease::LeasedProgressStorage,
+        shutdown,
+    },
     worker::PROCESSOR_SERVICE_TYPE,
 };
-use anyhow::Result;
+use anyhow::{Context, Result};
+use aptos_protos::util::timestamp::Timestamp;
+use diesel::{pg::upsert::excluded, ExpressionMethods};
 use enum_dispatch::enum_dispatch;
-use kanal::AsyncReceiver;
-use std::sync::{Arc, Mutex};
+use kanal::{AsyncReceiver, ReceiveError};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 pub mod gap_detector;
 pub mod parquet_gap_detector;
 
 // Size of a gap (in txn version) before gap detected
 pub const DEFAULT_GAP_DETECTION_BATCH_SIZE: u64 = 500;
-// Number of seconds between each processor status update
-const UPDATE_PROCESSOR_STATUS_SECS: u64 = 1;
+// Default number of seconds between each processor status update
+pub const DEFAULT_PROGRESS_COMMIT_INTERVAL_SECS: u64 = 1;
 
 #[enum_dispatch(GapDetectorTrait)]
 #[derive(Clone)]
@@ -56,11 +69,152 @@ pub enum ProcessingResult {
     ParquetProcessingResult(ParquetProcessingResult),
 }
 
+/// Loads the persisted state of the default gap detector, if any. This decides where
+/// processing resumes from, so `db_pool` should be the primary rather than a read replica.
+pub async fn load_default_gap_detector(
+    db_pool: ArcDbPool,
+    processor_name: &str,
+    starting_version: u64,
+) -> Result<DefaultGapDetector> {
+    let mut conn = db_pool.get().await?;
+    let status = GapDetectorStatusQuery::get_by_processor(processor_name, &mut conn).await?;
+    match status {
+        // The stored state is only valid if it lines up with where the stream restarts from
+        Some(status) if status.next_version_to_process as u64 == starting_version => {
+            let pending_batches: Vec<DefaultProcessingResult> =
+                serde_json::from_value(status.pending_batches)
+                    .context("Failed to deserialize pending gap detector batches")?;
+            tracing::info!(
+                processor_name,
+                next_version_to_process = starting_version,
+                num_gaps = pending_batches.len(),
+                "[Parser] Restored gap detector state from db",
+            );
+            Ok(DefaultGapDetector::from_snapshot(
+                starting_version,
+                pending_batches,
+            ))
+        },
+        _ => Ok(DefaultGapDetector::new(starting_version)),
+    }
+}
+
+/// Persists the state of the default gap detector so that pending gaps survive a restart.
+async fn persist_default_gap_detector(
+    db_pool: ArcDbPool,
+    processor_name: &str,
+    gap_detector: &DefaultGapDetector,
+) -> Result<()> {
+    let status = GapDetectorStatus {
+        processor: processor_status_key(processor_name),
+        next_version_to_process: gap_detector.next_version_to_process() as i64,
+        pending_batches: serde_json::to_value(gap_detector.pending_batches())?,
+    };
+    execute_with_better_error(
+        db_pool,
+        diesel::insert_into(gap_detector_status::table)
+            .values(&status)
+            .on_conflict(gap_detector_status::processor)
+            .do_update()
+            .set((
+                gap_detector_status::next_version_to_process
+                    .eq(excluded(gap_detector_status::next_version_to_process)),
+                gap_detector_status::pending_batches
+                    .eq(excluded(gap_detector_status::pending_batches)),
+                gap_detector_status::last_updated.eq(excluded(gap_detector_status::last_updated)),
+            )),
+        None,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Decides when the last successful version is written to `processor_status`: once
+/// `interval_secs` have passed since the last write, or once `interval_versions` versions have
+/// been processed since then, whichever comes first. Anything not yet written is reprocessed
+/// after a crash, which is safe since processing is idempotent.
+pub struct ProgressCommitter {
+    interval: Duration,
+    interval_versions: Option<u64>,
+    last_commit_time: Instant,
+    last_committed_version: u64,
+    // Progress is only written while this is held, if set
+    leased_progress: Option<Arc<LeasedProgressStorage>>,
+}
+
+impl ProgressCommitter {
+    pub fn new(interval_secs: u64, interval_versions: Option<u64>, starting_version: u64) -> Self {
+        Self {
+            interval: Duration::from_secs(interval_secs),
+            interval_versions,
+            last_commit_time: Instant::now(),
+            // Nothing before the starting version is processed by this run
+            last_committed_version: starting_version.saturating_sub(1),
+            leased_progress: None,
+        }
+    }
+
+    pub fn with_leased_progress(
+        mut self,
+        leased_progress: Option<Arc<LeasedProgressStorage>>,
+    ) -> Self {
+        self.leased_progress = leased_progress;
+        self
+    }
+
+    pub fn leased_progress(&self) -> Option<&LeasedProgressStorage> {
+        self.leased_progress.as_deref()
+    }
+
+    pub fn is_due(&self, version: u64) -> bool {
+        self.last_commit_time.elapsed() >= self.interval
+            || self.interval_versions.is_some_and(|interval_versions| {
+                version.saturating_sub(self.last_committed_version) >= interval_versions
+            })
+    }
+
+    pub fn record_commit(&mut self, version: u64) {
+        self.last_commit_time = Instant::now();
+        self.last_committed_version = version;
+    }
+}
+
+/// Writes `version` to `processor_status` and persists the gap detector if enabled. Panics
+/// instead if the progress lease has been lost, since another process may be writing it.
+async fn commit_progress(
+    processor: &Processor,
+    gap_detector: &GapDetector,
+    persist_gap_detector_state: bool,
+    in_flight_versions: &InFlightVersions,
+    leased_progress: Option<&LeasedProgressStorage>,
+    (version, last_transaction_timestamp): (u64, Option<Timestamp>),
+) {
+    processor
+        .update_last_processed_version(version, last_transaction_timestamp, leased_progress)
+        .await
+        .expect("[Parser] Failed to commit progress");
+    in_flight_versions.record_committed(version);
+    if persist_gap_detector_state {
+        if let GapDetector::DefaultGapDetector(default_gap_detector) = gap_detector {
+            persist_default_gap_detector(
+                processor.get_pool(),
+                processor.name(),
+                default_gap_detector,
+            )
+            .await
+            .unwrap();
+        }
+    }
+}
+
 pub async fn create_gap_detector_status_tracker_loop(
     mut gap_detector: GapDetector,
     gap_detector_receiver: AsyncReceiver<ProcessingResult>,
     processor: Processor,
     gap_detection_batch_size: u64,
+    persist_gap_detector_state: bool,
+    mut progress_committer: ProgressCommitter,
+    in_flight_versions: Arc<InFlightVersions>,
 ) {
     let processor_name = processor.name();
     tracing::info!(
@@ -69,9 +223,18 @@ pub async fn create_gap_detector_status_tracker_loop(
         "[Parser] Starting gap detector task",
     );
 
-    let mut last_update_time = std::time::Instant::now();
+    // Latest successful version not yet written to `processor_status`
+    let mut pending_progress: Option<(u64, Option<Timestamp>)> = None;
+    let mut flushed = shutdown::flushed_signal();
     loop {
-        match gap_detector_receiver.recv().await {
+        let received = tokio::select! {
+            received = gap_detector_receiver.recv() => received,
+            // On shutdown, take the results of what was flushed and finish as if closed
+            _ = flushed.triggered() => gap_detector_receiver
+                .try_recv()
+                .and_then(|result| result.ok_or(ReceiveError::SendClosed)),
+        };
+        match received {
             Ok(ProcessingResult::DefaultProcessingResult(result)) => {
                 match gap_detector
                     .process_versions(ProcessingResult::DefaultProcessingResult(result))
@@ -92,17 +255,22 @@ pub async fn create_gap_detector_status_tracker_loop(
                                     // We don't panic as everything downstream will panic if it doesn't work/receive
                                 }
                                 if let Some(res_last_success_batch) = res.last_success_batch {
-                                    if last_update_time.elapsed().as_secs()
-                                        >= UPDATE_PROCESSOR_STATUS_SECS
-                                    {
-                                        processor
-                                            .update_last_processed_version(
-                                                res_last_success_batch.end_version,
-                                                res_last_success_batch.last_transaction_timestamp,
-                                            )
-                                            .await
-                                            .unwrap();
-                                        last_update_time = std::time::Instant::now();
+                                    let version = res_last_success_batch.end_version;
+                                    pending_progress = Some((
+                                        version,
+                                        res_last_success_batch.last_transaction_timestamp,
+                                    ));
+                                    if progress_committer.is_due(version) {
+                                        commit_progress(
+                                            &processor,
+                                            &gap_detector,
+                                            persist_gap_detector_state,
+                                            &in_flight_versions,
+                                            progress_committer.leased_progress(),
+                                            pending_progress.take().unwrap(),
+                                        )
+                                        .await;
+                                        progress_committer.record_commit(version);
                                     }
                                 }
                             },
@@ -149,22 +317,26 @@ pub async fn create_gap_detector_status_tracker_loop(
                                     // We don't panic as everything downstream will panic if it doesn't work/receive
                                 }
 
-                                if last_update_time.elapsed().as_secs()
-                                    >= UPDATE_PROCESSOR_STATUS_SECS
-                                {
+                                pending_progress = Some((
+                                    res.last_success_version,
+                                    res.last_transaction_timestamp,
+                                ));
+                                if progress_committer.is_due(res.last_success_version) {
                                     tracing::info!(
                                         last_processed_version = res.last_success_version,
                                         processor_name,
                                         "Updating last processed version"
                                     );
-                                    processor
-                                        .update_last_processed_version(
-                                            res.last_success_version,
-                                            res.last_transaction_timestamp,
-                                        )
-                                        .await
-                                        .unwrap();
-                                    last_update_time = std::time::Instant::now();
+                                    commit_progress(
+                                        &processor,
+                                        &gap_detector,
+                                        persist_gap_detector_state,
+                                        &in_flight_versions,
+                                        progress_committer.leased_progress(),
+                                        pending_progress.take().unwrap(),
+                                    )
+                                    .await;
+                                    progress_committer.record_commit(res.last_success_version);
                                 } else {
                                     tracing::info!("Not Updating last processed version");
                                 }
@@ -192,8 +364,46 @@ pub async fn create_gap_detector_status_tracker_loop(
                     error = ?e,
                     "[Parser] Gap detector channel has been closed",
                 );
+                // Don't lose progress made since the last commit
+                if let Some(progress) = pending_progress.take() {
+                    tracing::info!(
+                        processor_name,
+                        last_processed_version = progress.0,
+                        "[Parser] Flushing last processed version",
+                    );
+                    commit_progress(
+                        &processor,
+                        &gap_detector,
+                        persist_gap_detector_state,
+                        &in_flight_versions,
+                        progress_committer.leased_progress(),
+                        progress,
+                    )
+                    .await;
+                }
                 return;
             },
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_committed_by_versions_or_time() {
+        let mut committer = ProgressCommitter::new(3600, Some(1_000), 100);
+        assert!(!committer.is_due(1_098));
+        assert!(committer.is_due(1_099));
+        committer.record_commit(1_099);
+        assert!(!committer.is_due(2_000));
+        assert!(committer.is_due(2_099));
+
+        // Without a version interval only time counts, and an interval of 0 commits every batch
+        let committer = ProgressCommitter::new(0, None, 0);
+        assert!(committer.is_due(1));
+        let committer = ProgressCommitter::new(3600, None, 0);
+        assert!(!committer.is_due(u64::MAX));
+    }
+}
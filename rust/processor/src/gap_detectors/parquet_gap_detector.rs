@@ -72,7 +72,8 @@ impl ParquetFileGapDetectorInner {
     /// If a version is fully processed, it removes the version from the version counters and adds it to the `seen_versions`.
     /// For tables other than transactions, the latest version to process may not always be the most recent transaction version
     /// since this value is updated based on the minimum of the maximum versions of the latest table files per processor
-    /// that have been uploaded to GCS. Therefore, when the processor restarts, some duplicate rows may be generated, which is acceptable.
+    /// that have been uploaded to GCS. Therefore, when the processor restarts, tables that were ahead replay structs they already uploaded.
+    /// The parquet handlers drop those based on their upload checkpoints and acknowledge them here as if they were just uploaded.
     /// The function also ensures that the current version starts checking from the `next_version_to_process`
     /// value stored in the database. While there might be potential performance improvements,
     /// the current implementation prioritizes data integrity.
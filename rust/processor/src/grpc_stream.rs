@@ -1,26 +1,32 @@
 use crate::utils::{
+    block_height::BlockHeightTracker,
+    channel_byte_limiter::ChannelByteLimiter,
     counters::{
-        ProcessorStep, FETCHER_THREAD_CHANNEL_SIZE, LATEST_PROCESSED_VERSION,
-        NUM_TRANSACTIONS_FILTERED_OUT_COUNT, NUM_TRANSACTIONS_PROCESSED_COUNT,
-        PROCESSED_BYTES_COUNT, TRANSACTION_UNIX_TIMESTAMP,
+        chain_id_label, ProcessorStep, FETCHER_THREAD_CHANNEL_SIZE, GRPC_EMPTY_BATCH_COUNT,
+        LATEST_PROCESSED_VERSION, NUM_TRANSACTIONS_FILTERED_OUT_COUNT,
+        NUM_TRANSACTIONS_PROCESSED_COUNT, PROCESSED_BYTES_COUNT, TRANSACTION_UNIX_TIMESTAMP,
     },
-    util::{timestamp_to_iso, timestamp_to_unixtime},
+    heartbeat::StreamTip,
+    in_flight_versions::InFlightVersions,
+    tip_lag::TipLagThrottle,
+    transaction_decoder::{get_transactions, DecodedTransactionsResponse, TransactionExtensions},
+    transaction_fields::TransactionFields,
+    transaction_tee::TransactionTee,
+    util::{timestamp_to_iso, timestamp_to_unixtime, transaction_timestamp},
 };
 use aptos_moving_average::MovingAverage;
 use aptos_protos::{
-    indexer::v1::{raw_data_client::RawDataClient, GetTransactionsRequest, TransactionsResponse},
-    transaction::v1::Transaction,
-    util::timestamp::Timestamp,
+    indexer::v1::GetTransactionsRequest, transaction::v1::Transaction, util::timestamp::Timestamp,
 };
 use bigdecimal::Zero;
 use futures_util::StreamExt;
 use itertools::Itertools;
 use kanal::AsyncSender;
 use prost::Message;
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 use tokio::time::timeout;
 use tonic::{Response, Streaming};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use url::Url;
 
 /// GRPC request metadata key for the token ID.
@@ -45,6 +51,9 @@ pub struct TransactionsPBResponse {
     pub start_txn_timestamp: Option<Timestamp>,
     pub end_txn_timestamp: Option<Timestamp>,
     pub size_in_bytes: u64,
+    /// What the registered `TransactionDecoder` kept from the transactions, which can include
+    /// ones the filter dropped
+    pub extensions: Arc<TransactionExtensions>,
 }
 
 pub fn grpc_request_builder(
@@ -79,7 +88,7 @@ pub async fn get_stream(
     ending_version: Option<u64>,
     auth_token: String,
     processor_name: String,
-) -> Response<Streaming<TransactionsResponse>> {
+) -> Response<Streaming<DecodedTransactionsResponse>> {
     info!(
         processor_name = processor_name,
         service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
@@ -121,11 +130,7 @@ pub async fn get_stream(
     // Retry this connection a few times before giving up
     let mut connect_retries = 0;
     let connect_res = loop {
-        let res = timeout(
-            indexer_grpc_reconnection_timeout_secs,
-            RawDataClient::connect(channel.clone()),
-        )
-        .await;
+        let res = timeout(indexer_grpc_reconnection_timeout_secs, channel.connect()).await;
         match res {
             Ok(client) => break Ok(client),
             Err(e) => {
@@ -149,7 +154,7 @@ pub async fn get_stream(
     .expect("[Parser] Timeout connecting to GRPC server");
 
     let mut rpc_client = match connect_res {
-        Ok(client) => client
+        Ok(connection) => tonic::client::Grpc::new(connection)
             .accept_compressed(tonic::codec::CompressionEncoding::Gzip)
             .accept_compressed(tonic::codec::CompressionEncoding::Zstd)
             .send_compressed(tonic::codec::CompressionEncoding::Zstd)
@@ -190,7 +195,7 @@ pub async fn get_stream(
                 auth_token.clone(),
                 processor_name.clone(),
             );
-            rpc_client.get_transactions(request).await
+            get_transactions(&mut rpc_client, request).await
         })
         .await;
         match timeout_res {
@@ -271,7 +276,10 @@ pub async fn get_chain_id(
     );
 
     match resp_stream.next().await {
-        Some(Ok(r)) => r.chain_id.expect("[Parser] Chain Id doesn't exist."),
+        Some(Ok(r)) => r
+            .response
+            .chain_id
+            .expect("[Parser] Chain Id doesn't exist."),
         Some(Err(rpc_error)) => {
             error!(
                 processor_name = processor_name,
@@ -301,9 +309,10 @@ pub async fn get_chain_id(
 /// There could be several special scenarios:
 /// 1. If we lose the connection, we will try reconnecting X times within Y seconds before crashing.
 /// 2. If we specified an end version and we hit that, we will stop fetching, but we will make sure that
-///    all existing transactions are processed
+///    all existing transactions are processed. Transactions past the end version are dropped, in case
+///    the server sends more than requested.
 pub async fn create_fetcher_loop(
-    txn_sender: AsyncSender<TransactionsPBResponse>,
+    txn_sender: AsyncSender<Arc<TransactionsPBResponse>>,
     indexer_grpc_data_service_address: Url,
     indexer_grpc_http2_ping_interval: Duration,
     indexer_grpc_http2_ping_timeout: Duration,
@@ -314,8 +323,21 @@ pub async fn create_fetcher_loop(
     auth_token: String,
     processor_name: String,
     transaction_filter: crate::transaction_filter::TransactionFilter,
+    // Fields the processors read, the others are dropped before the transactions are buffered
+    transaction_fields: TransactionFields,
     // The number of transactions per protobuf batch
     pb_channel_txn_chunk_size: usize,
+    channel_byte_limiter: Arc<ChannelByteLimiter>,
+    // Not set when multiplexing, since each processor tracks its own versions
+    in_flight_versions: Option<Arc<InFlightVersions>>,
+    // Only set when the heartbeat is on
+    stream_tip: Option<Arc<StreamTip>>,
+    // Only set with `compute_block_heights`
+    mut block_heights: Option<BlockHeightTracker>,
+    // Only set if the embedding application registered one
+    transaction_tee: Option<&'static TransactionTee>,
+    // Only set with `tip_lag`
+    mut tip_lag: Option<TipLagThrottle>,
 ) {
     info!(
         processor_name = processor_name,
@@ -368,13 +390,42 @@ pub async fn create_fetcher_loop(
             // Received datastream response
             Ok(response) => {
                 match response {
-                    Some(Ok(mut r)) => {
+                    Some(Ok(DecodedTransactionsResponse {
+                        response: mut r,
+                        extensions,
+                    })) => {
                         reconnection_retries = 0;
-                        let start_version = r.transactions.as_slice().first().unwrap().version;
-                        let start_txn_timestamp =
-                            r.transactions.as_slice().first().unwrap().timestamp;
-                        let end_version = r.transactions.as_slice().last().unwrap().version;
-                        let end_txn_timestamp = r.transactions.as_slice().last().unwrap().timestamp;
+                        if let Some(ending_version) = request_ending_version {
+                            r.transactions.retain(|txn| txn.version <= ending_version);
+                        }
+                        // Nothing to process or advance past, e.g. a keep-alive from upstream
+                        let (Some(first_txn), Some(last_txn)) =
+                            (r.transactions.first(), r.transactions.last())
+                        else {
+                            warn!(
+                                processor_name = processor_name,
+                                service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+                                stream_address = indexer_grpc_data_service_address.to_string(),
+                                connection_id,
+                                next_version_to_fetch,
+                                "[Parser] Received empty batch from GRPC stream, skipping"
+                            );
+                            GRPC_EMPTY_BATCH_COUNT
+                                .with_label_values(&[&processor_name])
+                                .inc();
+                            continue;
+                        };
+                        let start_version = first_txn.version;
+                        let start_txn_timestamp = first_txn.timestamp;
+                        let end_version = last_txn.version;
+                        let end_txn_timestamp = last_txn.timestamp;
+
+                        // Holds the stream back until the batch is far enough behind the tip
+                        if let Some(tip_lag) = &mut tip_lag {
+                            tip_lag
+                                .wait(&processor_name, end_version, end_txn_timestamp.as_ref())
+                                .await;
+                        }
 
                         next_version_to_fetch = end_version + 1;
 
@@ -386,8 +437,19 @@ pub async fn create_fetcher_loop(
 
                         let num_txns = r.transactions.len();
 
+                        // Before filtering, since the block metadata transactions may be dropped
+                        if let Some(block_heights) = &mut block_heights {
+                            for txn in r.transactions.iter_mut() {
+                                block_heights.set_block_height(txn);
+                            }
+                        }
+
                         // Filter out the txns we don't care about
-                        r.transactions.retain(|txn| transaction_filter.include(txn));
+                        transaction_filter.retain(&processor_name, &mut r.transactions);
+                        // Before the fields the processors don't read are stripped
+                        if let Some(transaction_tee) = transaction_tee {
+                            transaction_tee.send(&processor_name, &r.transactions).await;
+                        }
 
                         let num_txn_post_filter = r.transactions.len();
                         let num_filtered_txns = num_txns - num_txn_post_filter;
@@ -431,25 +493,66 @@ pub async fn create_fetcher_loop(
                             panic!("[Parser] Received batch with gap from GRPC stream");
                         }
                         last_fetched_version = end_version as i64;
+                        if let Some(in_flight_versions) = &in_flight_versions {
+                            in_flight_versions
+                                .record_fetched(end_version, start_txn_timestamp.as_ref());
+                        }
+                        if let Some(stream_tip) = &stream_tip {
+                            stream_tip.record(end_version);
+                        }
 
                         LATEST_PROCESSED_VERSION
-                            .with_label_values(&[&processor_name, step, label, "-"])
+                            .with_label_values(&[
+                                &processor_name,
+                                step,
+                                label,
+                                "-",
+                                chain_id_label(),
+                            ])
                             .set(end_version as i64);
-                        TRANSACTION_UNIX_TIMESTAMP
-                            .with_label_values(&[&processor_name, step, label, "-"])
-                            .set(
-                                start_txn_timestamp
-                                    .as_ref()
-                                    .map(timestamp_to_unixtime)
-                                    .unwrap_or_default(),
-                            );
+                        if let Some(start_txn_timestamp) =
+                            transaction_timestamp(start_txn_timestamp.as_ref())
+                        {
+                            TRANSACTION_UNIX_TIMESTAMP
+                                .with_label_values(&[
+                                    &processor_name,
+                                    step,
+                                    label,
+                                    "-",
+                                    chain_id_label(),
+                                ])
+                                .set(timestamp_to_unixtime(start_txn_timestamp));
+                        }
                         PROCESSED_BYTES_COUNT
-                            .with_label_values(&[&processor_name, step, label, "-"])
+                            .with_label_values(&[
+                                &processor_name,
+                                step,
+                                label,
+                                "-",
+                                chain_id_label(),
+                            ])
                             .inc_by(size_in_bytes);
                         NUM_TRANSACTIONS_PROCESSED_COUNT
-                            .with_label_values(&[&processor_name, step, label, "-"])
+                            .with_label_values(&[
+                                &processor_name,
+                                step,
+                                label,
+                                "-",
+                                chain_id_label(),
+                            ])
                             .inc_by(end_version - start_version + 1);
 
+                        // Buffered and processed at the stripped size, while the metrics above
+                        // count the bytes received
+                        let size_in_bytes = if transaction_fields.is_all() {
+                            size_in_bytes
+                        } else {
+                            for txn in r.transactions.iter_mut() {
+                                transaction_fields.strip(txn);
+                            }
+                            r.encoded_len() as u64
+                        };
+
                         let txn_channel_send_latency = std::time::Instant::now();
 
                         //potentially break txn_pb into many `TransactionsPBResponse` that are each `pb_channel_txn_chunk_size` txns max in size
@@ -463,9 +566,11 @@ pub async fn create_fetcher_loop(
                                 start_txn_timestamp,
                                 end_txn_timestamp,
                                 size_in_bytes,
+                                extensions: Arc::new(extensions),
                             };
 
-                            match txn_sender.send(txn_pb).await {
+                            channel_byte_limiter.acquire(size_in_bytes).await;
+                            match txn_sender.send(Arc::new(txn_pb)).await {
                                 Ok(()) => {},
                                 Err(e) => {
                                     error!(
@@ -481,6 +586,7 @@ pub async fn create_fetcher_loop(
                         } else {
                             // We are breaking down a big batch into small batches; this involves an iterator
                             let average_size_in_bytes = size_in_bytes / num_txns as u64;
+                            let extensions = Arc::new(extensions);
 
                             let pb_txn_chunks: Vec<Vec<Transaction>> = r
                                 .transactions
@@ -500,9 +606,11 @@ pub async fn create_fetcher_loop(
                                     start_txn_timestamp,
                                     end_txn_timestamp,
                                     size_in_bytes,
+                                    extensions: extensions.clone(),
                                 };
 
-                                match txn_sender.send(txn_pb).await {
+                                channel_byte_limiter.acquire(size_in_bytes).await;
+                                match txn_sender.send(Arc::new(txn_pb)).await {
                                     Ok(()) => {},
                                     Err(e) => {
                                         error!(
@@ -691,3 +799,116 @@ pub async fn create_fetcher_loop(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction_filter::TransactionFilter;
+    use aptos_protos::indexer::v1::{
+        raw_data_server::{RawData, RawDataServer},
+        TransactionsResponse,
+    };
+    use futures::Stream;
+    use std::pin::Pin;
+    use tonic::{
+        transport::{server::TcpIncoming, Server},
+        Request, Status,
+    };
+
+    /// Sends the given batches of versions, ignoring the requested range.
+    struct MockRawData {
+        batches: Vec<Vec<u64>>,
+    }
+
+    #[tonic::async_trait]
+    impl RawData for MockRawData {
+        type GetTransactionsStream =
+            Pin<Box<dyn Stream<Item = Result<TransactionsResponse, Status>> + Send>>;
+
+        async fn get_transactions(
+            &self,
+            _request: Request<GetTransactionsRequest>,
+        ) -> Result<Response<Self::GetTransactionsStream>, Status> {
+            let responses = self
+                .batches
+                .iter()
+                .map(|versions| {
+                    let transactions = versions
+                        .iter()
+                        .map(|&version| Transaction {
+                            version,
+                            ..Transaction::default()
+                        })
+                        .collect();
+                    Ok(TransactionsResponse {
+                        transactions,
+                        chain_id: Some(1),
+                        ..TransactionsResponse::default()
+                    })
+                })
+                .collect::<Vec<_>>();
+            Ok(Response::new(Box::pin(futures::stream::iter(responses))))
+        }
+    }
+
+    /// Runs the fetcher from version 0 to `ending_version` against a server sending `batches`,
+    /// and returns the versions it passes on in order.
+    async fn fetch_versions(batches: Vec<Vec<u64>>, ending_version: u64) -> Vec<u64> {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let incoming = TcpIncoming::from_listener(listener, true, None).unwrap();
+        let server = Server::builder()
+            .add_service(RawDataServer::new(MockRawData { batches }))
+            .serve_with_incoming(incoming);
+        tokio::spawn(server);
+
+        let (sender, receiver) = kanal::bounded_async(10);
+        let channel_byte_limiter = Arc::new(ChannelByteLimiter::new("test".to_string(), None));
+        tokio::spawn(create_fetcher_loop(
+            sender,
+            format!("http://{}", address).parse().unwrap(),
+            Duration::from_secs(30),
+            Duration::from_secs(10),
+            Duration::from_secs(5),
+            Duration::from_secs(5),
+            0,
+            Some(ending_version),
+            String::new(),
+            "test".to_string(),
+            TransactionFilter::default(),
+            TransactionFields::all(),
+            100,
+            channel_byte_limiter,
+            None,
+            None,
+            None,
+            None,
+            None,
+        ));
+
+        // The channel is closed once the ending version is reached
+        let mut versions = vec![];
+        while let Ok(response) = receiver.recv().await {
+            versions.extend(response.transactions.iter().map(|txn| txn.version));
+            assert_eq!(Some(&response.end_version), versions.last());
+        }
+        versions
+    }
+
+    #[tokio::test]
+    async fn test_no_versions_past_ending_version() {
+        let versions = (0..20).collect::<Vec<_>>();
+        let batches = versions.chunks(5).map(|batch| batch.to_vec()).collect();
+        // The second batch is cut off at the ending version
+        assert_eq!(
+            fetch_versions(batches, 7).await,
+            (0..=7).collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_empty_batches_are_skipped() {
+        let batches = vec![vec![], vec![0, 1, 2], vec![], vec![3, 4]];
+        assert_eq!(fetch_versions(batches, 4).await, [0, 1, 2, 3, 4]);
+    }
+}
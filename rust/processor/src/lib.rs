@@ -19,13 +19,19 @@ extern crate parquet_derive;
 pub use config::IndexerGrpcProcessorConfig;
 
 pub mod bq_analytics;
+pub mod column_backfill;
 mod config;
 pub mod db;
 pub mod gap_detectors;
 pub mod grpc_stream;
+pub mod multiplexer;
+pub mod parallel_fetch;
+pub mod parquet_file_stream;
 pub mod processors;
+pub mod replay;
 #[path = "db/postgres/schema.rs"]
 pub mod schema;
+pub mod self_test;
 pub mod transaction_filter;
 pub mod utils;
 pub mod worker;
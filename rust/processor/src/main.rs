@@ -3,7 +3,7 @@
 
 use anyhow::Result;
 use clap::Parser;
-use processor::IndexerGrpcProcessorConfig;
+use processor::{utils::live_status::enable_tail, IndexerGrpcProcessorConfig};
 use server_framework::ServerArgs;
 
 #[cfg(unix)]
@@ -12,6 +12,15 @@ static ALLOC: jemallocator::Jemalloc = jemallocator::Jemalloc;
 
 const RUNTIME_WORKER_MULTIPLIER: usize = 2;
 
+#[derive(Parser)]
+struct ProcessorArgs {
+    #[clap(flatten)]
+    server_args: ServerArgs,
+    /// Print a one-line summary of every processed batch to stdout. For local development.
+    #[clap(long)]
+    tail: bool,
+}
+
 fn main() -> Result<()> {
     let num_cpus = num_cpus::get();
     let worker_threads = (num_cpus * RUNTIME_WORKER_MULTIPLIER).max(16);
@@ -28,8 +37,12 @@ fn main() -> Result<()> {
         .build()
         .unwrap()
         .block_on(async {
-            let args = ServerArgs::parse();
-            args.run::<IndexerGrpcProcessorConfig>(tokio::runtime::Handle::current())
+            let args = ProcessorArgs::parse();
+            if args.tail {
+                enable_tail();
+            }
+            args.server_args
+                .run::<IndexerGrpcProcessorConfig>(tokio::runtime::Handle::current())
                 .await
         })
 }
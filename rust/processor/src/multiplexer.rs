@@ -0,0 +1,209 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Runs several processors in one process off a single stream.
+//!
+//! The fetcher writes into one channel as usual. The multiplexer takes each batch off that
+//! channel and hands the same `Arc` to every processor's own bounded buffer, so the transactions
+//! are fetched and held in memory once no matter how many processors there are. Each processor
+//! then runs its usual tasks, gap detector and `processor_status` row against its buffer.
+//!
+//! Processors advance independently as long as their buffer has room: a fast processor can get
+//! up to `multiplexed_buffer_size` batches ahead of a slow one. Once the slow processor's buffer
+//! is full the multiplexer waits on it, which stops it from draining the fetcher channel and in
+//! turn stops the fetcher. Without this, the gap between them and the memory held for the slow
+//! one would grow without bound.
+
+use crate::{
+    grpc_stream::TransactionsPBResponse,
+    utils::{
+        channel_byte_limiter::ChannelByteLimiter, counters::MULTIPLEXED_BUFFER_SIZE,
+        in_flight_versions::InFlightVersions,
+    },
+    worker::PROCESSOR_SERVICE_TYPE,
+};
+use kanal::{AsyncReceiver, AsyncSender};
+use std::{sync::Arc, time::Duration};
+use tracing::{error, info};
+
+/// A processor fed by the multiplexer, through its own bounded buffer.
+pub struct MultiplexedProcessor {
+    pub processor_name: &'static str,
+    // Batches ending before this version are not sent to the processor
+    pub starting_version: u64,
+    pub sender: AsyncSender<Arc<TransactionsPBResponse>>,
+    pub in_flight_versions: Arc<InFlightVersions>,
+}
+
+/// Forwards every batch from the fetcher channel to each processor that still needs it, until
+/// the fetcher closes the channel.
+pub async fn create_multiplexer_loop(
+    receiver: AsyncReceiver<Arc<TransactionsPBResponse>>,
+    channel_byte_limiter: Arc<ChannelByteLimiter>,
+    processors: Vec<MultiplexedProcessor>,
+) {
+    while let Ok(batch) = receiver.recv().await {
+        // The fetcher channel's byte budget only covers that channel; the buffers are bounded by
+        // number of batches
+        channel_byte_limiter.release(batch.size_in_bytes);
+        for processor in &processors {
+            if batch.end_version < processor.starting_version {
+                continue;
+            }
+            processor
+                .in_flight_versions
+                .record_fetched(batch.end_version, batch.start_txn_timestamp.as_ref());
+            if let Err(e) = processor.sender.send(batch.clone()).await {
+                error!(
+                    processor_name = processor.processor_name,
+                    error = ?e,
+                    "[Parser] Error sending transactions to multiplexed processor buffer."
+                );
+                panic!("[Parser] Error sending transactions to multiplexed processor buffer.")
+            }
+            MULTIPLEXED_BUFFER_SIZE
+                .with_label_values(&[processor.processor_name])
+                .set(processor.sender.len() as i64);
+        }
+    }
+
+    info!(
+        service_type = PROCESSOR_SERVICE_TYPE,
+        "[Parser] Fetcher channel closed, waiting for multiplexed processors to drain.",
+    );
+    // Same as the fetchers, let the processors finish before the buffers close
+    for processor in &processors {
+        while !processor.sender.is_empty() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+}
+
+/// Takes a batch out of the shared `Arc`, dropping any transactions before `starting_version`.
+/// Only the last processor to take a batch gets it without a copy.
+pub fn take_batch_from_version(
+    batch: Arc<TransactionsPBResponse>,
+    starting_version: u64,
+) -> TransactionsPBResponse {
+    if batch.start_version >= starting_version {
+        return Arc::try_unwrap(batch).unwrap_or_else(|batch| batch.as_ref().clone());
+    }
+    let transactions: Vec<_> = batch
+        .transactions
+        .iter()
+        .filter(|txn| txn.version >= starting_version)
+        .cloned()
+        .collect();
+    TransactionsPBResponse {
+        start_txn_timestamp: transactions.first().and_then(|txn| txn.timestamp),
+        transactions,
+        chain_id: batch.chain_id,
+        start_version: starting_version,
+        end_version: batch.end_version,
+        end_txn_timestamp: batch.end_txn_timestamp,
+        size_in_bytes: batch.size_in_bytes,
+        extensions: batch.extensions.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::{transaction::v1::Transaction, util::timestamp::Timestamp};
+
+    fn batch(start_version: u64, end_version: u64) -> TransactionsPBResponse {
+        let transactions: Vec<Transaction> = (start_version..=end_version)
+            .map(|version| Transaction {
+                version,
+                timestamp: Some(Timestamp {
+                    seconds: version as i64,
+                    nanos: 0,
+                }),
+                ..Default::default()
+            })
+            .collect();
+        TransactionsPBResponse {
+            start_txn_timestamp: transactions.first().and_then(|txn| txn.timestamp),
+            end_txn_timestamp: transactions.last().and_then(|txn| txn.timestamp),
+            transactions,
+            chain_id: 1,
+            start_version,
+            end_version,
+            size_in_bytes: 0,
+            extensions: Arc::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_two_processors_advance_independently() {
+        let (fetcher_sender, fetcher_receiver) = kanal::bounded_async(10);
+        let (fast_sender, fast_receiver) = kanal::bounded_async(2);
+        let (slow_sender, slow_receiver) = kanal::bounded_async(2);
+        let processors = vec![
+            MultiplexedProcessor {
+                processor_name: "fast_processor",
+                starting_version: 0,
+                sender: fast_sender,
+                in_flight_versions: Arc::new(InFlightVersions::new("fast_processor".to_string())),
+            },
+            MultiplexedProcessor {
+                processor_name: "slow_processor",
+                starting_version: 0,
+                sender: slow_sender,
+                in_flight_versions: Arc::new(InFlightVersions::new("slow_processor".to_string())),
+            },
+        ];
+        let multiplexer = tokio::spawn(create_multiplexer_loop(
+            fetcher_receiver,
+            Arc::new(ChannelByteLimiter::new(
+                "multiplexer_test".to_string(),
+                None,
+            )),
+            processors,
+        ));
+        for start_version in [0, 10, 20, 30] {
+            fetcher_sender
+                .send(Arc::new(batch(start_version, start_version + 9)))
+                .await
+                .unwrap();
+        }
+
+        // The slow processor hasn't read anything, so once its buffer is full the multiplexer
+        // waits on it and the fast one stops getting batches too
+        for expected_start_version in [0, 10, 20] {
+            let batch = fast_receiver.recv().await.unwrap();
+            assert_eq!(batch.start_version, expected_start_version);
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(fast_receiver.is_empty());
+
+        // Once the slow processor catches up, both get every batch
+        for expected_start_version in [0, 10, 20, 30] {
+            let slow_batch = slow_receiver.recv().await.unwrap();
+            assert_eq!(slow_batch.start_version, expected_start_version);
+        }
+        let fast_batch = fast_receiver.recv().await.unwrap();
+        assert_eq!(fast_batch.start_version, 30);
+
+        drop(fetcher_sender);
+        multiplexer.await.unwrap();
+    }
+
+    #[test]
+    fn test_take_batch_from_version() {
+        let shared = Arc::new(batch(100, 109));
+        let other_processor = shared.clone();
+
+        let trimmed = take_batch_from_version(shared, 105);
+        assert_eq!(trimmed.start_version, 105);
+        assert_eq!(trimmed.end_version, 109);
+        assert_eq!(trimmed.transactions.len(), 5);
+        assert_eq!(trimmed.transactions[0].version, 105);
+        assert_eq!(trimmed.start_txn_timestamp.unwrap().seconds, 105);
+
+        // The last one to take it gets the batch as is
+        let untouched = take_batch_from_version(other_processor, 100);
+        assert_eq!(untouched.start_version, 100);
+        assert_eq!(untouched.transactions.len(), 10);
+    }
+}
@@ -0,0 +1,273 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Fetches a bounded version range over several GRPC streams at once, for deep backfills where a
+//! single stream is the bottleneck.
+//!
+//! The range is cut into chunks of `chunk_versions` versions, dealt out round robin to
+//! `num_streams` fetchers that each fetch their chunks one after the other on a stream of their
+//! own. The merger forwards the chunks to the fetcher channel in version order, draining chunk k
+//! from fetcher k % `num_streams` before moving on to chunk k + 1.
+//!
+//! Each chunk is fetched into a bounded channel of its own, and a fetcher only moves on to its
+//! next chunk once the merger has drained the previous one. So at most `num_streams` chunks are
+//! in flight, each holding at most `CHUNK_BUFFER_SIZE` batches, however far ahead the faster
+//! streams get.
+
+use crate::{
+    grpc_stream::TransactionsPBResponse,
+    utils::{channel_byte_limiter::ChannelByteLimiter, heartbeat::StreamTip},
+};
+use anyhow::{bail, Result};
+use kanal::{AsyncReceiver, AsyncSender};
+use serde::{Deserialize, Serialize};
+use std::{future::Future, sync::Arc, time::Duration};
+use tracing::{error, info};
+
+/// Batches buffered per chunk until the merger gets to it
+pub const CHUNK_BUFFER_SIZE: usize = 50;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ParallelFetchConfig {
+    /// GRPC streams to fetch on at once
+    pub num_streams: usize,
+    /// Versions each stream fetches before moving on to its next chunk
+    #[serde(default = "ParallelFetchConfig::default_chunk_versions")]
+    pub chunk_versions: u64,
+}
+
+impl ParallelFetchConfig {
+    pub const fn default_chunk_versions() -> u64 {
+        100_000
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.num_streams == 0 {
+            bail!("parallel_fetch num_streams must be greater than 0");
+        }
+        if self.chunk_versions == 0 {
+            bail!("parallel_fetch chunk_versions must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// `[starting_version, ending_version]` cut into inclusive ranges of `chunk_versions` versions.
+fn chunks(
+    starting_version: u64,
+    ending_version: u64,
+    chunk_versions: u64,
+) -> impl Iterator<Item = (u64, u64)> + Clone {
+    (starting_version..=ending_version)
+        .step_by(chunk_versions as usize)
+        .map(move |chunk_start| {
+            (
+                chunk_start,
+                chunk_start
+                    .saturating_add(chunk_versions - 1)
+                    .min(ending_version),
+            )
+        })
+}
+
+/// Fetches `[starting_version, ending_version]` into `txn_sender` in version order, with
+/// `fetch_chunk` fetching each chunk into the channel it's given. Like the fetcher loop, it
+/// returns once the channel has been drained.
+///
+/// `fetch_chunk` is given a byte limiter of its own that never blocks, since a chunk can't wait
+/// for room that only the earlier chunks will free up. Batches are charged to
+/// `channel_byte_limiter` as they're merged instead.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_parallel_fetcher_loop<F, Fut>(
+    txn_sender: AsyncSender<Arc<TransactionsPBResponse>>,
+    channel_byte_limiter: Arc<ChannelByteLimiter>,
+    processor_name: String,
+    starting_version: u64,
+    ending_version: u64,
+    config: ParallelFetchConfig,
+    // Only set when the heartbeat is on
+    stream_tip: Option<Arc<StreamTip>>,
+    fetch_chunk: F,
+) where
+    F: Fn(u64, u64, AsyncSender<Arc<TransactionsPBResponse>>, Arc<ChannelByteLimiter>) -> Fut
+        + Clone
+        + Send
+        + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let chunks = chunks(starting_version, ending_version, config.chunk_versions);
+    let chunk_byte_limiter = Arc::new(ChannelByteLimiter::new(processor_name.clone(), None));
+    info!(
+        processor_name,
+        start_version = starting_version,
+        end_version = ending_version,
+        num_streams = config.num_streams,
+        chunk_versions = config.chunk_versions,
+        "[Parser] Fetching over parallel GRPC streams"
+    );
+
+    // Each fetcher hands over the channel of its next chunk as it starts fetching it
+    let mut chunk_receivers: Vec<AsyncReceiver<AsyncReceiver<Arc<TransactionsPBResponse>>>> =
+        vec![];
+    for stream_index in 0..config.num_streams {
+        let (sender, receiver) = kanal::bounded_async(1);
+        chunk_receivers.push(receiver);
+        let chunks = chunks
+            .clone()
+            .skip(stream_index)
+            .step_by(config.num_streams);
+        let fetch_chunk = fetch_chunk.clone();
+        let chunk_byte_limiter = chunk_byte_limiter.clone();
+        tokio::spawn(async move {
+            for (chunk_start, chunk_end) in chunks {
+                let (chunk_sender, chunk_receiver) = kanal::bounded_async(CHUNK_BUFFER_SIZE);
+                if sender.send(chunk_receiver).await.is_err() {
+                    return;
+                }
+                fetch_chunk(
+                    chunk_start,
+                    chunk_end,
+                    chunk_sender,
+                    chunk_byte_limiter.clone(),
+                )
+                .await;
+            }
+        });
+    }
+
+    for (chunk_index, (chunk_start, chunk_end)) in chunks.enumerate() {
+        let chunk_receiver = chunk_receivers[chunk_index % config.num_streams]
+            .recv()
+            .await
+            .expect("[Parser] Parallel fetcher stopped before fetching all its chunks");
+        let mut last_version = None;
+        while let Ok(batch) = chunk_receiver.recv().await {
+            chunk_byte_limiter.release(batch.size_in_bytes);
+            last_version = Some(batch.end_version);
+            if let Some(stream_tip) = &stream_tip {
+                stream_tip.record(batch.end_version);
+            }
+            channel_byte_limiter.acquire(batch.size_in_bytes).await;
+            if let Err(e) = txn_sender.send(batch).await {
+                error!(
+                    processor_name,
+                    error = ?e,
+                    "[Parser] Error sending GRPC response to channel."
+                );
+                panic!("[Parser] Error sending GRPC response to channel.")
+            }
+        }
+        if last_version != Some(chunk_end) {
+            error!(
+                processor_name,
+                chunk_start,
+                chunk_end,
+                last_version,
+                "[Parser] Parallel fetch chunk ended before its last version"
+            );
+            panic!("[Parser] Parallel fetch chunk ended before its last version");
+        }
+    }
+
+    // Wait for the fetched transactions to finish processing before closing the channel
+    while !txn_sender.is_empty() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    info!(
+        processor_name,
+        end_version = ending_version,
+        "[Parser] Reached ending version on parallel GRPC streams"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::Transaction;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_chunks() {
+        assert_eq!(
+            chunks(10, 34, 10).collect::<Vec<_>>(),
+            [(10, 19), (20, 29), (30, 34)]
+        );
+        assert_eq!(chunks(5, 5, 10).collect::<Vec<_>>(), [(5, 5)]);
+    }
+
+    #[tokio::test]
+    async fn test_merged_in_version_order() {
+        let num_streams = 3;
+        let in_flight_chunks = Arc::new(AtomicUsize::new(0));
+        let max_in_flight_chunks = Arc::new(AtomicUsize::new(0));
+        // Later chunks are fetched faster, so they're ready before the earlier ones are merged
+        let fetch_chunk = {
+            let in_flight_chunks = in_flight_chunks.clone();
+            let max_in_flight_chunks = max_in_flight_chunks.clone();
+            move |start: u64,
+                  end: u64,
+                  sender: AsyncSender<Arc<TransactionsPBResponse>>,
+                  _: Arc<ChannelByteLimiter>| {
+                let in_flight_chunks = in_flight_chunks.clone();
+                let max_in_flight_chunks = max_in_flight_chunks.clone();
+                async move {
+                    let in_flight = in_flight_chunks.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight_chunks.fetch_max(in_flight, Ordering::SeqCst);
+                    for batch_start in (start..=end).step_by(3) {
+                        let batch_end = (batch_start + 2).min(end);
+                        tokio::time::sleep(Duration::from_millis(20u64.saturating_sub(start)))
+                            .await;
+                        let batch = TransactionsPBResponse {
+                            transactions: (batch_start..=batch_end)
+                                .map(|version| Transaction {
+                                    version,
+                                    ..Transaction::default()
+                                })
+                                .collect(),
+                            chain_id: 1,
+                            start_version: batch_start,
+                            end_version: batch_end,
+                            start_txn_timestamp: None,
+                            end_txn_timestamp: None,
+                            size_in_bytes: 0,
+                            extensions: Arc::default(),
+                        };
+                        sender.send(Arc::new(batch)).await.unwrap();
+                    }
+                    // Like the fetcher loop, done once the chunk's been drained
+                    while !sender.is_empty() {
+                        tokio::time::sleep(Duration::from_millis(1)).await;
+                    }
+                    in_flight_chunks.fetch_sub(1, Ordering::SeqCst);
+                }
+            }
+        };
+
+        let (sender, receiver) = kanal::bounded_async(100);
+        let channel_byte_limiter = Arc::new(ChannelByteLimiter::new("test".to_string(), None));
+        let merger = tokio::spawn(create_parallel_fetcher_loop(
+            sender,
+            channel_byte_limiter,
+            "test_merged_in_version_order".to_string(),
+            1,
+            30,
+            ParallelFetchConfig {
+                num_streams,
+                chunk_versions: 4,
+            },
+            None,
+            fetch_chunk,
+        ));
+
+        let mut versions = vec![];
+        while let Ok(batch) = receiver.recv().await {
+            assert_eq!(batch.start_version, versions.len() as u64 + 1);
+            versions.extend(batch.transactions.iter().map(|txn| txn.version));
+            assert_eq!(Some(&batch.end_version), versions.last());
+        }
+        merger.await.unwrap();
+        assert_eq!(versions, (1..=30).collect::<Vec<_>>());
+        assert!(max_in_flight_chunks.load(Ordering::SeqCst) <= num_streams);
+    }
+}
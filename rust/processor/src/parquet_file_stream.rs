@@ -0,0 +1,478 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reads raw transactions from local Parquet files instead of the GRPC stream. This is meant for
+//! reprocessing from a cold archive. Batches are sent to the same channel as the GRPC fetcher so
+//! the rest of the worker doesn't know the difference.
+
+use crate::{
+    grpc_stream::TransactionsPBResponse,
+    transaction_filter::TransactionFilter,
+    utils::{
+        block_height::BlockHeightTracker,
+        channel_byte_limiter::ChannelByteLimiter,
+        counters::{
+            chain_id_label, ProcessorStep, FETCHER_THREAD_CHANNEL_SIZE, LATEST_PROCESSED_VERSION,
+            NUM_TRANSACTIONS_FILTERED_OUT_COUNT, NUM_TRANSACTIONS_PROCESSED_COUNT,
+            PARQUET_FILE_DECODE_ERROR_COUNT, PROCESSED_BYTES_COUNT, TRANSACTION_UNIX_TIMESTAMP,
+        },
+        in_flight_versions::InFlightVersions,
+        transaction_decoder::{decode_transaction, DecodedTransaction, TransactionExtensions},
+        util::{timestamp_to_unixtime, transaction_timestamp},
+    },
+};
+use anyhow::{anyhow, bail, Context, Result};
+use aptos_protos::transaction::v1::Transaction;
+use kanal::AsyncSender;
+use parquet::{
+    file::reader::{FileReader, SerializedFileReader},
+    record::Field,
+};
+use prost::Message;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::File,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{error, info};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParquetFileSourceConfig {
+    /// A Parquet file, or a directory of Parquet files whose names sort in version order.
+    pub path: PathBuf,
+    /// Column holding the protobuf encoded `Transaction`.
+    #[serde(default = "ParquetFileSourceConfig::default_column_name")]
+    pub column_name: String,
+    /// There's no stream to ask for the chain id, so it has to be provided.
+    pub chain_id: u64,
+}
+
+impl ParquetFileSourceConfig {
+    pub fn default_column_name() -> String {
+        "transaction".to_string()
+    }
+
+    /// Lists the files to read, in order.
+    fn list_files(&self) -> Result<Vec<PathBuf>> {
+        if !self.path.is_dir() {
+            return Ok(vec![self.path.clone()]);
+        }
+        let mut files = std::fs::read_dir(&self.path)
+            .with_context(|| format!("Failed to read directory {:?}", self.path))?
+            .map(|entry| entry.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        files.retain(|path| path.extension().is_some_and(|ext| ext == "parquet"));
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Opens a file and parses its footer, once for all of its row groups.
+fn open_file(path: &Path) -> Result<Arc<SerializedFileReader<File>>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {:?}", path))?;
+    let reader = SerializedFileReader::new(file)
+        .with_context(|| format!("Failed to read the metadata of {:?}", path))?;
+    Ok(Arc::new(reader))
+}
+
+/// Reads one row group. Rows that fail to decode are returned as errors so the caller can log
+/// them without giving up on the rest of the file.
+fn read_row_group(
+    reader: &SerializedFileReader<File>,
+    row_group_index: usize,
+    column_name: &str,
+) -> Result<Vec<Result<DecodedTransaction>>> {
+    let row_group = reader.get_row_group(row_group_index)?;
+    let rows = row_group
+        .get_row_iter(None)?
+        .map(|row| {
+            let row = row?;
+            let bytes = row
+                .get_column_iter()
+                .find_map(|(name, field)| match field {
+                    Field::Bytes(bytes) if name == column_name => Some(bytes.data()),
+                    _ => None,
+                })
+                .with_context(|| format!("Row has no binary column {}", column_name))?;
+            decode_transaction(bytes)
+        })
+        .collect();
+    Ok(rows)
+}
+
+/// Sends a batch covering `[start_version, end_version]`, less what the filter drops.
+#[allow(clippy::too_many_arguments)]
+async fn send_batch(
+    txn_sender: &AsyncSender<Arc<TransactionsPBResponse>>,
+    channel_byte_limiter: &ChannelByteLimiter,
+    in_flight_versions: Option<&InFlightVersions>,
+    transaction_filter: &TransactionFilter,
+    processor_name: &str,
+    chain_id: u64,
+    start_version: u64,
+    end_version: u64,
+    mut transactions: Vec<Transaction>,
+    extensions: TransactionExtensions,
+) -> Result<()> {
+    let step = ProcessorStep::ReceivedTxnsFromGrpc.get_step();
+    let label = ProcessorStep::ReceivedTxnsFromGrpc.get_label();
+    let start_txn_timestamp = transactions.first().and_then(|t| t.timestamp);
+    let end_txn_timestamp = transactions.last().and_then(|t| t.timestamp);
+    let size_in_bytes = transactions.iter().map(|t| t.encoded_len() as u64).sum();
+
+    let num_txns = transactions.len();
+    transaction_filter.retain(processor_name, &mut transactions);
+    let num_filtered_txns = num_txns - transactions.len();
+
+    LATEST_PROCESSED_VERSION
+        .with_label_values(&[processor_name, step, label, "-", chain_id_label()])
+        .set(end_version as i64);
+    if let Some(start_txn_timestamp) = transaction_timestamp(start_txn_timestamp.as_ref()) {
+        TRANSACTION_UNIX_TIMESTAMP
+            .with_label_values(&[processor_name, step, label, "-", chain_id_label()])
+            .set(timestamp_to_unixtime(start_txn_timestamp));
+    }
+    PROCESSED_BYTES_COUNT
+        .with_label_values(&[processor_name, step, label, "-", chain_id_label()])
+        .inc_by(size_in_bytes);
+    NUM_TRANSACTIONS_PROCESSED_COUNT
+        .with_label_values(&[processor_name, step, label, "-", chain_id_label()])
+        .inc_by(end_version - start_version + 1);
+
+    if let Some(in_flight_versions) = in_flight_versions {
+        in_flight_versions.record_fetched(end_version, start_txn_timestamp.as_ref());
+    }
+    channel_byte_limiter.acquire(size_in_bytes).await;
+    txn_sender
+        .send(Arc::new(TransactionsPBResponse {
+            transactions,
+            chain_id,
+            start_version,
+            end_version,
+            start_txn_timestamp,
+            end_txn_timestamp,
+            size_in_bytes,
+            extensions: Arc::new(extensions),
+        }))
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "Error sending Parquet file transactions to channel: {:?}",
+                e
+            )
+        })?;
+
+    FETCHER_THREAD_CHANNEL_SIZE
+        .with_label_values(&[processor_name])
+        .set(txn_sender.len() as i64);
+    NUM_TRANSACTIONS_FILTERED_OUT_COUNT
+        .with_label_values(&[processor_name])
+        .inc_by(num_filtered_txns as u64);
+    Ok(())
+}
+
+/// Counterpart of `create_fetcher_loop` that reads from local Parquet files. Transactions before
+/// `starting_version` are skipped and reading stops after `request_ending_version` or when the
+/// files run out, once the channel has drained. A version missing from the files stops the
+/// fetcher, like a gap in the stream would.
+#[allow(clippy::too_many_arguments)]
+pub async fn create_parquet_file_fetcher_loop(
+    txn_sender: AsyncSender<Arc<TransactionsPBResponse>>,
+    config: ParquetFileSourceConfig,
+    starting_version: u64,
+    request_ending_version: Option<u64>,
+    processor_name: String,
+    transaction_filter: TransactionFilter,
+    // The number of transactions per protobuf batch
+    pb_channel_txn_chunk_size: usize,
+    channel_byte_limiter: Arc<ChannelByteLimiter>,
+    // Not set when multiplexing, since each processor tracks its own versions
+    in_flight_versions: Option<Arc<InFlightVersions>>,
+    // Only set with `compute_block_heights`
+    block_heights: Option<BlockHeightTracker>,
+) {
+    if let Err(e) = read_parquet_files(
+        &txn_sender,
+        &config,
+        starting_version,
+        request_ending_version,
+        &processor_name,
+        &transaction_filter,
+        pb_channel_txn_chunk_size,
+        &channel_byte_limiter,
+        in_flight_versions.as_deref(),
+        block_heights,
+    )
+    .await
+    {
+        error!(
+            processor_name = processor_name,
+            source_path = ?config.path,
+            error = ?e,
+            "[Parser] Error reading transactions from Parquet files."
+        );
+        panic!(
+            "[Parser] Error reading transactions from Parquet files: {:#}",
+            e
+        );
+    }
+
+    // Wait for the fetched transactions to finish processing before closing the channel
+    while !txn_sender.is_empty() {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn read_parquet_files(
+    txn_sender: &AsyncSender<Arc<TransactionsPBResponse>>,
+    config: &ParquetFileSourceConfig,
+    starting_version: u64,
+    request_ending_version: Option<u64>,
+    processor_name: &str,
+    transaction_filter: &TransactionFilter,
+    pb_channel_txn_chunk_size: usize,
+    channel_byte_limiter: &ChannelByteLimiter,
+    in_flight_versions: Option<&InFlightVersions>,
+    mut block_heights: Option<BlockHeightTracker>,
+) -> Result<()> {
+    let files = config.list_files()?;
+    info!(
+        processor_name = processor_name,
+        service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+        source_path = ?config.path,
+        num_files = files.len(),
+        start_version = starting_version,
+        end_version = request_ending_version,
+        "[Parser] Reading transactions from Parquet files",
+    );
+
+    let mut next_version_to_fetch = starting_version;
+    let mut batch: Vec<Transaction> = Vec::with_capacity(pb_channel_txn_chunk_size);
+    let mut batch_extensions = TransactionExtensions::new();
+    let mut batch_start_version = starting_version;
+
+    'files: for file in files {
+        let file_for_open = file.clone();
+        let reader = tokio::task::spawn_blocking(move || open_file(&file_for_open))
+            .await
+            .context("Parquet reader task panicked")??;
+
+        for row_group_index in 0..reader.metadata().num_row_groups() {
+            let reader = reader.clone();
+            let column_name = config.column_name.clone();
+            let rows = tokio::task::spawn_blocking(move || {
+                read_row_group(&reader, row_group_index, &column_name)
+            })
+            .await
+            .context("Parquet reader task panicked")?
+            .with_context(|| {
+                format!("Failed to read row group {} of {:?}", row_group_index, file)
+            })?;
+
+            for (row_index, row) in rows.into_iter().enumerate() {
+                let DecodedTransaction {
+                    transaction: mut txn,
+                    extension,
+                } = match row {
+                    Ok(decoded) => decoded,
+                    Err(e) => {
+                        // Fails with the missing version below, unless it's a duplicate
+                        error!(
+                            processor_name = processor_name,
+                            file = ?file,
+                            row_group_index,
+                            row_index,
+                            error = ?e,
+                            "[Parser] Skipping row that failed to decode"
+                        );
+                        PARQUET_FILE_DECODE_ERROR_COUNT
+                            .with_label_values(&[processor_name])
+                            .inc();
+                        continue;
+                    },
+                };
+                // Before the requested range, or a duplicate of something already sent
+                if txn.version < next_version_to_fetch {
+                    continue;
+                }
+                if request_ending_version.is_some_and(|end| txn.version > end) {
+                    break 'files;
+                }
+                if txn.version > next_version_to_fetch {
+                    bail!(
+                        "Versions {} to {} are missing, the next row of {:?} is version {}",
+                        next_version_to_fetch,
+                        txn.version - 1,
+                        file,
+                        txn.version
+                    );
+                }
+                next_version_to_fetch = txn.version + 1;
+                if let Some(block_heights) = &mut block_heights {
+                    block_heights.set_block_height(&mut txn);
+                }
+                if let Some(extension) = extension {
+                    batch_extensions.insert(txn.version, extension);
+                }
+                batch.push(txn);
+
+                if batch.len() >= pb_channel_txn_chunk_size {
+                    send_batch(
+                        txn_sender,
+                        channel_byte_limiter,
+                        in_flight_versions,
+                        transaction_filter,
+                        processor_name,
+                        config.chain_id,
+                        batch_start_version,
+                        next_version_to_fetch - 1,
+                        std::mem::take(&mut batch),
+                        std::mem::take(&mut batch_extensions),
+                    )
+                    .await?;
+                    batch_start_version = next_version_to_fetch;
+                }
+            }
+        }
+    }
+    if !batch.is_empty() {
+        send_batch(
+            txn_sender,
+            channel_byte_limiter,
+            in_flight_versions,
+            transaction_filter,
+            processor_name,
+            config.chain_id,
+            batch_start_version,
+            next_version_to_fetch - 1,
+            batch,
+            batch_extensions,
+        )
+        .await?;
+    }
+    if let Some(end) = request_ending_version.filter(|end| next_version_to_fetch <= *end) {
+        bail!(
+            "Versions {} to {} are missing, the files ran out",
+            next_version_to_fetch,
+            end
+        );
+    }
+
+    info!(
+        processor_name = processor_name,
+        service_type = crate::worker::PROCESSOR_SERVICE_TYPE,
+        next_version_to_fetch,
+        "[Parser] Finished reading Parquet files.",
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use parquet::{
+        data_type::{ByteArray, ByteArrayType},
+        file::writer::SerializedFileWriter,
+        schema::parser::parse_message_type,
+    };
+
+    /// Writes one row group per slice of versions
+    fn write_parquet_file(path: &Path, row_groups: &[&[u64]]) {
+        let schema = Arc::new(
+            parse_message_type("message schema { REQUIRED BYTE_ARRAY transaction; }").unwrap(),
+        );
+        let mut writer =
+            SerializedFileWriter::new(File::create(path).unwrap(), schema, Default::default())
+                .unwrap();
+        for versions in row_groups {
+            let values = versions
+                .iter()
+                .map(|version| {
+                    ByteArray::from(
+                        Transaction {
+                            version: *version,
+                            ..Transaction::default()
+                        }
+                        .encode_to_vec(),
+                    )
+                })
+                .collect::<Vec<_>>();
+            let mut row_group = writer.next_row_group().unwrap();
+            let mut column = row_group.next_column().unwrap().unwrap();
+            column
+                .typed::<ByteArrayType>()
+                .write_batch(&values, None, None)
+                .unwrap();
+            column.close().unwrap();
+            row_group.close().unwrap();
+        }
+        writer.close().unwrap();
+    }
+
+    async fn read_file(
+        file_name: &str,
+        row_groups: &[&[u64]],
+        starting_version: u64,
+        request_ending_version: Option<u64>,
+    ) -> (Result<()>, Vec<Vec<u64>>) {
+        let path = std::env::temp_dir().join(format!("{}_{}", std::process::id(), file_name));
+        write_parquet_file(&path, row_groups);
+        let config = ParquetFileSourceConfig {
+            path: path.clone(),
+            column_name: ParquetFileSourceConfig::default_column_name(),
+            chain_id: 1,
+        };
+        let (sender, receiver) = kanal::bounded_async(10);
+        let result = read_parquet_files(
+            &sender,
+            &config,
+            starting_version,
+            request_ending_version,
+            "parquet_file_stream_test",
+            &TransactionFilter::default(),
+            2,
+            &ChannelByteLimiter::new("parquet_file_stream_test".to_string(), None),
+            None,
+            None,
+        )
+        .await;
+        std::fs::remove_file(path).unwrap();
+        let mut batches = vec![];
+        while let Ok(Some(batch)) = receiver.try_recv() {
+            batches.push(batch.transactions.iter().map(|t| t.version).collect());
+        }
+        (result, batches)
+    }
+
+    #[tokio::test]
+    async fn test_reads_row_groups_in_order() {
+        let (result, batches) =
+            read_file("in_order.parquet", &[&[0, 1, 2], &[2, 3, 4]], 1, Some(4)).await;
+        result.unwrap();
+        assert_eq!(batches, vec![vec![1, 2], vec![3, 4]]);
+    }
+
+    #[tokio::test]
+    async fn test_fails_on_missing_version() {
+        let (result, batches) = read_file("gap.parquet", &[&[0, 1], &[3, 4]], 0, None).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Versions 2 to 2 are missing"));
+        // What came before the gap is still processed
+        assert_eq!(batches, vec![vec![0, 1]]);
+    }
+
+    #[tokio::test]
+    async fn test_fails_when_files_end_early() {
+        let (result, _) = read_file("end_early.parquet", &[&[0, 1]], 0, Some(3)).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .starts_with("Versions 2 to 3 are missing"));
+    }
+}
@@ -0,0 +1,198 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
+use crate::{
+    db::postgres::models::account_sequence_number_models::account_sequence_numbers::{
+        AccountSequenceNumber, CurrentAccountSequenceNumber,
+    },
+    gap_detectors::ProcessingResult,
+    schema,
+    utils::database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+};
+use ahash::AHashMap;
+use anyhow::bail;
+use aptos_protos::transaction::v1::Transaction;
+use async_trait::async_trait;
+use diesel::{
+    pg::{upsert::excluded, Pg},
+    query_builder::QueryFragment,
+    ExpressionMethods,
+};
+use std::{fmt::Debug, sync::Arc};
+use tracing::error;
+
+/// Records the sender and sequence number of every user transaction, plus the latest sequence
+/// number per account. A cheaper alternative to the account transactions processor for spotting
+/// stuck accounts and nonce gaps.
+pub struct AccountSequenceNumberProcessor {
+    connection_pool: ArcDbPool,
+    per_table_chunk_sizes: AHashMap<String, usize>,
+}
+
+impl AccountSequenceNumberProcessor {
+    pub fn new(connection_pool: ArcDbPool, per_table_chunk_sizes: AHashMap<String, usize>) -> Self {
+        Self {
+            connection_pool,
+            per_table_chunk_sizes,
+        }
+    }
+}
+
+impl Debug for AccountSequenceNumberProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = &self.connection_pool.state();
+        write!(
+            f,
+            "AccountSequenceNumberProcessor {{ connections: {:?}  idle_connections: {:?} }}",
+            state.connections, state.idle_connections
+        )
+    }
+}
+
+async fn insert_to_db(
+    conn: ArcDbPool,
+    name: &'static str,
+    start_version: u64,
+    end_version: u64,
+    account_sequence_numbers: &[AccountSequenceNumber],
+    current_account_sequence_numbers: &[CurrentAccountSequenceNumber],
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<(), diesel::result::Error> {
+    tracing::trace!(
+        name = name,
+        start_version = start_version,
+        end_version = end_version,
+        "Inserting to db",
+    );
+    let asn = execute_in_chunks(
+        conn.clone(),
+        insert_account_sequence_numbers_query,
+        account_sequence_numbers,
+        get_config_table_chunk_size::<AccountSequenceNumber>(
+            "account_sequence_numbers",
+            per_table_chunk_sizes,
+        ),
+    );
+    let casn = execute_in_chunks(
+        conn,
+        insert_current_account_sequence_numbers_query,
+        current_account_sequence_numbers,
+        get_config_table_chunk_size::<CurrentAccountSequenceNumber>(
+            "current_account_sequence_numbers",
+            per_table_chunk_sizes,
+        ),
+    );
+    let (asn_res, casn_res) = tokio::join!(asn, casn);
+    for res in [asn_res, casn_res] {
+        res?;
+    }
+    Ok(())
+}
+
+pub fn insert_account_sequence_numbers_query(
+    items_to_insert: Vec<AccountSequenceNumber>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::account_sequence_numbers::dsl::*;
+
+    (
+        diesel::insert_into(schema::account_sequence_numbers::table)
+            .values(items_to_insert)
+            .on_conflict(transaction_version)
+            .do_nothing(),
+        None,
+    )
+}
+
+pub fn insert_current_account_sequence_numbers_query(
+    items_to_insert: Vec<CurrentAccountSequenceNumber>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::current_account_sequence_numbers::dsl::*;
+
+    (
+        diesel::insert_into(schema::current_account_sequence_numbers::table)
+            .values(items_to_insert)
+            .on_conflict(account_address)
+            .do_update()
+            .set((
+                sequence_number.eq(excluded(sequence_number)),
+                last_transaction_version.eq(excluded(last_transaction_version)),
+                last_transaction_timestamp.eq(excluded(last_transaction_timestamp)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        Some(" WHERE current_account_sequence_numbers.last_transaction_version <= excluded.last_transaction_version "),
+    )
+}
+
+#[async_trait]
+impl ProcessorTrait for AccountSequenceNumberProcessor {
+    fn name(&self) -> &'static str {
+        ProcessorName::AccountSequenceNumberProcessor.into()
+    }
+
+    async fn process_transactions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        start_version: u64,
+        end_version: u64,
+        _db_chain_id: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        let processing_start = std::time::Instant::now();
+        let last_transaction_timestamp = transactions.last().unwrap().timestamp;
+
+        let account_sequence_numbers = transactions
+            .iter()
+            .filter_map(AccountSequenceNumber::from_transaction)
+            .collect::<Vec<_>>();
+        let current_account_sequence_numbers =
+            CurrentAccountSequenceNumber::from_history(&account_sequence_numbers);
+
+        let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
+        let db_insertion_start = std::time::Instant::now();
+        let tx_result = insert_to_db(
+            self.get_pool(),
+            self.name(),
+            start_version,
+            end_version,
+            &account_sequence_numbers,
+            &current_account_sequence_numbers,
+            &self.per_table_chunk_sizes,
+        )
+        .await;
+
+        let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
+        match tx_result {
+            Ok(_) => Ok(ProcessingResult::DefaultProcessingResult(
+                DefaultProcessingResult {
+                    start_version,
+                    end_version,
+                    processing_duration_in_secs,
+                    db_insertion_duration_in_secs,
+                    last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
+                },
+            )),
+            Err(e) => {
+                error!(
+                    start_version = start_version,
+                    end_version = end_version,
+                    processor_name = self.name(),
+                    error = ?e,
+                    "[Parser] Error inserting transactions to db",
+                );
+                bail!(e)
+            },
+        }
+    }
+
+    fn connection_pool(&self) -> &ArcDbPool {
+        &self.connection_pool
+    }
+}
@@ -9,7 +9,10 @@ use crate::{
     },
     gap_detectors::ProcessingResult,
     schema,
-    utils::database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+    utils::{
+        database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+        rayon_pool::RayonPool,
+    },
 };
 use ahash::AHashMap;
 use anyhow::bail;
@@ -17,19 +20,25 @@ use aptos_protos::transaction::v1::Transaction;
 use async_trait::async_trait;
 use diesel::{pg::Pg, query_builder::QueryFragment};
 use rayon::prelude::*;
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 pub struct AccountTransactionsProcessor {
     connection_pool: ArcDbPool,
     per_table_chunk_sizes: AHashMap<String, usize>,
+    rayon_pool: RayonPool,
 }
 
 impl AccountTransactionsProcessor {
-    pub fn new(connection_pool: ArcDbPool, per_table_chunk_sizes: AHashMap<String, usize>) -> Self {
+    pub fn new(
+        connection_pool: ArcDbPool,
+        per_table_chunk_sizes: AHashMap<String, usize>,
+        rayon_pool: RayonPool,
+    ) -> Self {
         Self {
             connection_pool,
             per_table_chunk_sizes,
+            rayon_pool,
         }
     }
 }
@@ -97,7 +106,7 @@ impl ProcessorTrait for AccountTransactionsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _db_chain_id: Option<u64>,
@@ -105,20 +114,24 @@ impl ProcessorTrait for AccountTransactionsProcessor {
         let processing_start = std::time::Instant::now();
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
 
-        let account_transactions: Vec<_> = transactions
-            .into_par_iter()
-            .map(|txn| {
-                let transaction_version = txn.version as i64;
-                let accounts = RawAccountTransaction::get_accounts(&txn);
-                accounts
-                    .into_iter()
-                    .map(|account_address| AccountTransaction {
-                        transaction_version,
-                        account_address,
+        let account_transactions: Vec<_> = self
+            .rayon_pool
+            .install(|| {
+                transactions
+                    .par_iter()
+                    .map(|txn| {
+                        let transaction_version = txn.version as i64;
+                        let accounts = RawAccountTransaction::get_accounts(txn);
+                        accounts
+                            .into_iter()
+                            .map(|account_address| AccountTransaction {
+                                transaction_version,
+                                account_address,
+                            })
+                            .collect()
                     })
-                    .collect()
+                    .collect::<Vec<Vec<_>>>()
             })
-            .collect::<Vec<Vec<_>>>()
             .into_iter()
             .flatten()
             .collect();
@@ -144,6 +157,8 @@ impl ProcessorTrait for AccountTransactionsProcessor {
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             )),
             Err(err) => {
@@ -0,0 +1,176 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
+use crate::{
+    db::postgres::models::allowance_models::allowances::{
+        allowance_keys, refresh_current_allowances, AllowanceActivity,
+    },
+    gap_detectors::ProcessingResult,
+    schema,
+    utils::{
+        database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+        util::standardize_address,
+    },
+};
+use ahash::{AHashMap, AHashSet};
+use anyhow::bail;
+use aptos_protos::transaction::v1::Transaction;
+use async_trait::async_trait;
+use diesel::{pg::Pg, query_builder::QueryFragment};
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
+use tracing::error;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AllowanceProcessorConfig {
+    /// Addresses of the modules whose `Approval` and `TransferFrom` events are recorded
+    pub contract_addresses: Vec<String>,
+}
+
+/// Records `Approval` and `TransferFrom` events of coins and fungible assets, plus the allowance
+/// each spender has left per owner and asset.
+pub struct AllowanceProcessor {
+    connection_pool: ArcDbPool,
+    /// Standardized `AllowanceProcessorConfig::contract_addresses`
+    contract_addresses: AHashSet<String>,
+    per_table_chunk_sizes: AHashMap<String, usize>,
+}
+
+impl AllowanceProcessor {
+    pub fn new(
+        connection_pool: ArcDbPool,
+        config: AllowanceProcessorConfig,
+        per_table_chunk_sizes: AHashMap<String, usize>,
+    ) -> Self {
+        Self {
+            connection_pool,
+            contract_addresses: config
+                .contract_addresses
+                .iter()
+                .map(|address| standardize_address(address))
+                .collect(),
+            per_table_chunk_sizes,
+        }
+    }
+}
+
+impl Debug for AllowanceProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = &self.connection_pool.state();
+        write!(
+            f,
+            "AllowanceProcessor {{ connections: {:?}  idle_connections: {:?} }}",
+            state.connections, state.idle_connections
+        )
+    }
+}
+
+async fn insert_to_db(
+    conn: ArcDbPool,
+    name: &'static str,
+    start_version: u64,
+    end_version: u64,
+    allowance_activities: &[AllowanceActivity],
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<(), diesel::result::Error> {
+    tracing::trace!(
+        name = name,
+        start_version = start_version,
+        end_version = end_version,
+        "Inserting to db",
+    );
+    execute_in_chunks(
+        conn.clone(),
+        insert_allowance_activities_query,
+        allowance_activities,
+        get_config_table_chunk_size::<AllowanceActivity>(
+            "allowance_activities",
+            per_table_chunk_sizes,
+        ),
+    )
+    .await?;
+    // The current allowances are computed from the activities, so they go in after
+    refresh_current_allowances(conn, &allowance_keys(allowance_activities)).await?;
+    Ok(())
+}
+
+pub fn insert_allowance_activities_query(
+    items_to_insert: Vec<AllowanceActivity>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::allowance_activities::dsl::*;
+
+    (
+        diesel::insert_into(schema::allowance_activities::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_nothing(),
+        None,
+    )
+}
+
+#[async_trait]
+impl ProcessorTrait for AllowanceProcessor {
+    fn name(&self) -> &'static str {
+        ProcessorName::AllowanceProcessor.into()
+    }
+
+    async fn process_transactions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        start_version: u64,
+        end_version: u64,
+        _db_chain_id: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        let processing_start = std::time::Instant::now();
+        let last_transaction_timestamp = transactions.last().unwrap().timestamp;
+
+        let allowance_activities =
+            AllowanceActivity::from_transactions(&transactions, &self.contract_addresses);
+
+        let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
+        let db_insertion_start = std::time::Instant::now();
+        let tx_result = insert_to_db(
+            self.get_pool(),
+            self.name(),
+            start_version,
+            end_version,
+            &allowance_activities,
+            &self.per_table_chunk_sizes,
+        )
+        .await;
+
+        let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
+        match tx_result {
+            Ok(_) => Ok(ProcessingResult::DefaultProcessingResult(
+                DefaultProcessingResult {
+                    start_version,
+                    end_version,
+                    processing_duration_in_secs,
+                    db_insertion_duration_in_secs,
+                    last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
+                },
+            )),
+            Err(e) => {
+                error!(
+                    start_version = start_version,
+                    end_version = end_version,
+                    processor_name = self.name(),
+                    error = ?e,
+                    "[Parser] Error inserting transactions to db",
+                );
+                bail!(e)
+            },
+        }
+    }
+
+    fn connection_pool(&self) -> &ArcDbPool {
+        &self.connection_pool
+    }
+}
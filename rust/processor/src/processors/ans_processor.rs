@@ -27,7 +27,7 @@ use crate::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
         database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
         table_flags::TableFlags,
-        util::standardize_address,
+        util::{parse_transaction_timestamp, standardize_address},
     },
 };
 use ahash::AHashMap;
@@ -36,13 +36,14 @@ use aptos_protos::transaction::v1::{
     transaction::TxnData, write_set_change::Change as WriteSetChange, Transaction,
 };
 use async_trait::async_trait;
+use chrono::SubsecRound;
 use diesel::{
     pg::{upsert::excluded, Pg},
     query_builder::QueryFragment,
     ExpressionMethods,
 };
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -378,7 +379,7 @@ impl ProcessorTrait for AnsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _db_chain_id: Option<u64>,
@@ -481,6 +482,8 @@ impl ProcessorTrait for AnsProcessor {
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             )),
             Err(e) => {
@@ -544,13 +547,10 @@ pub fn parse_ans(
             .info
             .as_ref()
             .expect("Transaction info doesn't exist!");
-        let timestamp = transaction
-            .timestamp
-            .as_ref()
-            .expect("Transaction timestamp doesn't exist!");
-        #[allow(deprecated)]
-        let block_timestamp = chrono::NaiveDateTime::from_timestamp_opt(timestamp.seconds, 0)
-            .expect("Txn Timestamp is invalid!");
+        // Stored to the second
+        let block_timestamp =
+            parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version)
+                .trunc_subsecs(0);
 
         // Extracts from user transactions. Other transactions won't have any ANS changes
 
@@ -0,0 +1,241 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
+use crate::{
+    db::postgres::models::block_gas_stats_models::block_gas_stats::BlockGasStats,
+    gap_detectors::ProcessingResult,
+    schema::{self, block_gas_stats_parts},
+    utils::database::{
+        execute_with_better_error_conn, get_config_table_chunk_size, ArcDbPool, DbPoolConnection,
+        MyDbConnection,
+    },
+};
+use ahash::AHashMap;
+use anyhow::bail;
+use aptos_protos::transaction::v1::Transaction;
+use async_trait::async_trait;
+use diesel::{
+    dsl::sql,
+    pg::{upsert::excluded, Pg},
+    query_builder::QueryFragment,
+    sql_types::{BigInt, Nullable, Timestamp},
+    ExpressionMethods,
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection, RunQueryDsl};
+use std::{fmt::Debug, sync::Arc};
+use tracing::error;
+
+/// Writes per block gas totals of user transactions, for gas analytics.
+pub struct BlockGasStatsProcessor {
+    connection_pool: ArcDbPool,
+    per_table_chunk_sizes: AHashMap<String, usize>,
+    /// First version this run processes. A range that begins mid-block can't see the start of
+    /// its first block.
+    starting_version: u64,
+}
+
+impl BlockGasStatsProcessor {
+    pub fn new(
+        connection_pool: ArcDbPool,
+        per_table_chunk_sizes: AHashMap<String, usize>,
+        starting_version: u64,
+    ) -> Self {
+        Self {
+            connection_pool,
+            per_table_chunk_sizes,
+            starting_version,
+        }
+    }
+}
+
+impl Debug for BlockGasStatsProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = &self.connection_pool.state();
+        write!(
+            f,
+            "BlockGasStatsProcessor {{ connections: {:?}  idle_connections: {:?} }}",
+            state.connections, state.idle_connections
+        )
+    }
+}
+
+async fn insert_to_db(
+    conn: &mut DbPoolConnection<'_>,
+    name: &'static str,
+    start_version: u64,
+    end_version: u64,
+    block_gas_stats: Vec<BlockGasStats>,
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<(), diesel::result::Error> {
+    tracing::trace!(
+        name = name,
+        start_version = start_version,
+        end_version = end_version,
+        "Inserting to db",
+    );
+    if block_gas_stats.is_empty() {
+        return Ok(());
+    }
+    let chunk_size =
+        get_config_table_chunk_size::<BlockGasStats>("block_gas_stats", per_table_chunk_sizes);
+    conn.transaction(|conn| {
+        async move {
+            for chunk in block_gas_stats.chunks(chunk_size) {
+                insert_new_parts(conn, chunk).await?;
+            }
+            Ok::<_, diesel::result::Error>(())
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+/// Adds the parts of blocks that weren't added before, so a batch that's processed again isn't
+/// counted twice.
+async fn insert_new_parts(
+    conn: &mut MyDbConnection,
+    block_gas_stats: &[BlockGasStats],
+) -> Result<(), diesel::result::Error> {
+    let parts = block_gas_stats
+        .iter()
+        .map(|stats| {
+            (
+                block_gas_stats_parts::block_height.eq(stats.block_height),
+                block_gas_stats_parts::first_version.eq(stats.first_version),
+                block_gas_stats_parts::last_version.eq(stats.last_version),
+            )
+        })
+        .collect::<Vec<_>>();
+    let new_parts: Vec<(i64, i64)> = diesel::insert_into(block_gas_stats_parts::table)
+        .values(parts)
+        .on_conflict((
+            block_gas_stats_parts::block_height,
+            block_gas_stats_parts::first_version,
+        ))
+        .do_nothing()
+        .returning((
+            block_gas_stats_parts::block_height,
+            block_gas_stats_parts::first_version,
+        ))
+        .get_results(conn)
+        .await?;
+    let new_stats = block_gas_stats
+        .iter()
+        .filter(|stats| new_parts.contains(&(stats.block_height, stats.first_version)))
+        .cloned()
+        .collect::<Vec<_>>();
+    if new_stats.is_empty() {
+        return Ok(());
+    }
+    let (query, additional_where_clause) = insert_block_gas_stats_query(new_stats);
+    execute_with_better_error_conn(conn, query, additional_where_clause).await?;
+    Ok(())
+}
+
+/// Adds the stats to those already written for the block by other batches, in whatever order
+/// the batches are processed.
+pub fn insert_block_gas_stats_query(
+    items_to_insert: Vec<BlockGasStats>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::block_gas_stats::dsl::*;
+
+    (
+        diesel::insert_into(schema::block_gas_stats::table)
+            .values(items_to_insert)
+            .on_conflict(block_height)
+            .do_update()
+            .set((
+                first_version.eq(sql::<BigInt>(
+                    "LEAST(block_gas_stats.first_version, excluded.first_version)",
+                )),
+                last_version.eq(sql::<BigInt>(
+                    "GREATEST(block_gas_stats.last_version, excluded.last_version)",
+                )),
+                block_timestamp.eq(sql::<Nullable<Timestamp>>(
+                    "COALESCE(block_gas_stats.block_timestamp, excluded.block_timestamp)",
+                )),
+                transaction_count.eq(transaction_count + excluded(transaction_count)),
+                total_gas_used.eq(total_gas_used + excluded(total_gas_used)),
+                total_fee_octas.eq(total_fee_octas + excluded(total_fee_octas)),
+                total_gas_unit_price.eq(total_gas_unit_price + excluded(total_gas_unit_price)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        None,
+    )
+}
+
+#[async_trait]
+impl ProcessorTrait for BlockGasStatsProcessor {
+    fn name(&self) -> &'static str {
+        ProcessorName::BlockGasStatsProcessor.into()
+    }
+
+    async fn process_transactions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        start_version: u64,
+        end_version: u64,
+        _db_chain_id: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        let processing_start = std::time::Instant::now();
+        let last_transaction_timestamp = transactions.last().unwrap().timestamp;
+
+        let mut block_gas_stats = BlockGasStats::from_transactions(&transactions);
+        let mut conn = self.get_conn().await;
+        // If the range begins mid-block the first block is incomplete, so it's skipped. Unless
+        // this run resumes a previous one, which already wrote the start of the block.
+        if start_version == self.starting_version
+            && block_gas_stats
+                .first()
+                .is_some_and(|stats| !stats.has_block_start())
+            && !BlockGasStats::exists(block_gas_stats[0].block_height, &mut conn).await?
+        {
+            block_gas_stats.remove(0);
+        }
+
+        let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
+        let db_insertion_start = std::time::Instant::now();
+        let tx_result = insert_to_db(
+            &mut conn,
+            self.name(),
+            start_version,
+            end_version,
+            block_gas_stats,
+            &self.per_table_chunk_sizes,
+        )
+        .await;
+
+        let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
+        match tx_result {
+            Ok(_) => Ok(ProcessingResult::DefaultProcessingResult(
+                DefaultProcessingResult {
+                    start_version,
+                    end_version,
+                    processing_duration_in_secs,
+                    db_insertion_duration_in_secs,
+                    last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
+                },
+            )),
+            Err(e) => {
+                error!(
+                    start_version = start_version,
+                    end_version = end_version,
+                    processor_name = self.name(),
+                    error = ?e,
+                    "[Parser] Error inserting transactions to db",
+                );
+                bail!(e)
+            },
+        }
+    }
+
+    fn connection_pool(&self) -> &ArcDbPool {
+        &self.connection_pool
+    }
+}
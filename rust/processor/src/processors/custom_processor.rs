@@ -0,0 +1,151 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lets downstream crates plug in their own processors without adding them to `ProcessorConfig`.
+//! A `ProcessorFactory` is registered under a type name at startup, and a config with
+//! `type: custom_processor` and a matching `custom_type` is built through it.
+
+use super::ProcessorTrait;
+use crate::{
+    gap_detectors::ProcessingResult,
+    utils::{
+        database::ArcDbPool, table_flags::TableFlags, transaction_decoder::TransactionExtensions,
+    },
+};
+use ahash::AHashMap;
+use aptos_protos::transaction::v1::Transaction;
+use async_trait::async_trait;
+use kanal::AsyncSender;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::sync::{Arc, RwLock};
+
+static PROCESSOR_FACTORIES: Lazy<RwLock<AHashMap<String, Arc<dyn ProcessorFactory>>>> =
+    Lazy::new(|| RwLock::new(AHashMap::new()));
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomProcessorConfig {
+    /// Name the factory was registered under. It's also the processor name, which is what
+    /// progress is tracked by in `processor_status`.
+    #[serde(deserialize_with = "deserialize_static_str")]
+    pub custom_type: &'static str,
+    /// Passed as is to the factory
+    #[serde(default)]
+    pub params: serde_json::Value,
+}
+
+// The processor name has to be `&'static str` like the built-in ones. The config is only
+// deserialized once at startup, so leaking it is fine.
+fn deserialize_static_str<'de, D>(deserializer: D) -> Result<&'static str, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let name = String::deserialize(deserializer)?;
+    Ok(Box::leak(name.into_boxed_str()))
+}
+
+/// Everything the built-in processors are constructed with
+pub struct CustomProcessorArgs {
+    pub db_pool: ArcDbPool,
+    pub per_table_chunk_sizes: AHashMap<String, usize>,
+    pub deprecated_tables: TableFlags,
+    pub gap_detector_sender: Option<AsyncSender<ProcessingResult>>,
+}
+
+/// Builds a processor that isn't defined in this crate from the opaque `params` of its config.
+pub trait ProcessorFactory: Send + Sync {
+    fn build(
+        &self,
+        params: &serde_json::Value,
+        args: CustomProcessorArgs,
+    ) -> anyhow::Result<Box<dyn ProcessorTrait>>;
+}
+
+/// Registers a factory for configs with the given `custom_type`. This must happen before the
+/// processor is started. Returns false if a factory was already registered under that name.
+pub fn register_processor_factory(
+    custom_type: &str,
+    factory: impl ProcessorFactory + 'static,
+) -> bool {
+    let mut factories = PROCESSOR_FACTORIES.write().unwrap();
+    if factories.contains_key(custom_type) {
+        return false;
+    }
+    factories.insert(custom_type.to_string(), Arc::new(factory));
+    true
+}
+
+pub fn get_processor_factory(custom_type: &str) -> Option<Arc<dyn ProcessorFactory>> {
+    PROCESSOR_FACTORIES
+        .read()
+        .unwrap()
+        .get(custom_type)
+        .cloned()
+}
+
+/// Wraps a processor built by a registered factory so it can be dispatched like the built-in
+/// ones. The name always comes from the config so that progress is read and written under the
+/// same key.
+#[derive(Debug)]
+pub struct CustomProcessor {
+    name: &'static str,
+    inner: Box<dyn ProcessorTrait>,
+}
+
+impl CustomProcessor {
+    pub fn new(config: &CustomProcessorConfig, args: CustomProcessorArgs) -> anyhow::Result<Self> {
+        let factory = get_processor_factory(config.custom_type).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No processor factory registered for custom type {}",
+                config.custom_type
+            )
+        })?;
+        Ok(Self {
+            name: config.custom_type,
+            inner: factory.build(&config.params, args)?,
+        })
+    }
+}
+
+#[async_trait]
+impl ProcessorTrait for CustomProcessor {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    async fn process_transactions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        start_version: u64,
+        end_version: u64,
+        db_chain_id: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        self.inner
+            .process_transactions(transactions, start_version, end_version, db_chain_id)
+            .await
+    }
+
+    async fn process_transactions_with_extensions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        extensions: Arc<TransactionExtensions>,
+        start_version: u64,
+        end_version: u64,
+        db_chain_id: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        self.inner
+            .process_transactions_with_extensions(
+                transactions,
+                extensions,
+                start_version,
+                end_version,
+                db_chain_id,
+            )
+            .await
+    }
+
+    fn connection_pool(&self) -> &ArcDbPool {
+        self.inner.connection_pool()
+    }
+}
@@ -13,16 +13,19 @@ use crate::{
             raw_table_metadata::{RawTableMetadata, TableMetadataConvertible},
         },
         postgres::models::default_models::{
+            block_end_transactions::BlockEndTransaction,
             block_metadata_transactions::BlockMetadataTransactionModel,
+            move_resources::CurrentMoveResource,
             move_tables::{CurrentTableItem, TableItem, TableMetadata},
         },
     },
     gap_detectors::ProcessingResult,
     schema,
     utils::{
-        counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
+        counters::{TransactionTypeTimer, PROCESSOR_UNKNOWN_TYPE_COUNT},
         database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
         table_flags::TableFlags,
+        util::{count_events_and_write_set_changes, parse_transaction_timestamp},
     },
 };
 use ahash::AHashMap;
@@ -36,11 +39,22 @@ use diesel::{
     query_builder::QueryFragment,
     ExpressionMethods,
 };
-use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
 use tokio::join;
 use tracing::error;
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DefaultProcessorConfig {
+    /// If set, the latest state of every resource is kept in `current_move_resources`.
+    #[serde(default)]
+    pub current_move_resources: bool,
+}
+
 pub struct DefaultProcessor {
     connection_pool: ArcDbPool,
+    config: DefaultProcessorConfig,
     per_table_chunk_sizes: AHashMap<String, usize>,
     deprecated_tables: TableFlags,
 }
@@ -48,11 +62,13 @@ pub struct DefaultProcessor {
 impl DefaultProcessor {
     pub fn new(
         connection_pool: ArcDbPool,
+        config: DefaultProcessorConfig,
         per_table_chunk_sizes: AHashMap<String, usize>,
         deprecated_tables: TableFlags,
     ) -> Self {
         Self {
             connection_pool,
+            config,
             per_table_chunk_sizes,
             deprecated_tables,
         }
@@ -76,6 +92,8 @@ async fn insert_to_db(
     start_version: u64,
     end_version: u64,
     block_metadata_transactions: &[BlockMetadataTransactionModel],
+    block_end_transactions: &[BlockEndTransaction],
+    current_move_resources: &[CurrentMoveResource],
     (table_items, current_table_items, table_metadata): (
         &[TableItem],
         &[CurrentTableItem],
@@ -100,6 +118,26 @@ async fn insert_to_db(
         ),
     );
 
+    let bet_res = execute_in_chunks(
+        conn.clone(),
+        insert_block_end_transactions_query,
+        block_end_transactions,
+        get_config_table_chunk_size::<BlockEndTransaction>(
+            "block_end_transactions",
+            per_table_chunk_sizes,
+        ),
+    );
+
+    let cmr_res = execute_in_chunks(
+        conn.clone(),
+        insert_current_move_resources_query,
+        current_move_resources,
+        get_config_table_chunk_size::<CurrentMoveResource>(
+            "current_move_resources",
+            per_table_chunk_sizes,
+        ),
+    );
+
     let ti_res = execute_in_chunks(
         conn.clone(),
         insert_table_items_query,
@@ -124,9 +162,10 @@ async fn insert_to_db(
         get_config_table_chunk_size::<TableMetadata>("table_metadatas", per_table_chunk_sizes),
     );
 
-    let (bmt_res, ti_res, cti_res, tm_res) = join!(bmt_res, ti_res, cti_res, tm_res);
+    let (bmt_res, bet_res, cmr_res, ti_res, cti_res, tm_res) =
+        join!(bmt_res, bet_res, cmr_res, ti_res, cti_res, tm_res);
 
-    for res in [bmt_res, ti_res, cti_res, tm_res] {
+    for res in [bmt_res, bet_res, cmr_res, ti_res, cti_res, tm_res] {
         res?;
     }
 
@@ -150,6 +189,47 @@ pub fn insert_block_metadata_transactions_query(
     )
 }
 
+pub fn insert_block_end_transactions_query(
+    items_to_insert: Vec<BlockEndTransaction>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::block_end_transactions::dsl::*;
+
+    (
+        diesel::insert_into(schema::block_end_transactions::table)
+            .values(items_to_insert)
+            .on_conflict(version)
+            .do_nothing(),
+        None,
+    )
+}
+
+pub fn insert_current_move_resources_query(
+    items_to_insert: Vec<CurrentMoveResource>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::current_move_resources::dsl::*;
+
+    (
+        diesel::insert_into(schema::current_move_resources::table)
+            .values(items_to_insert)
+            .on_conflict((address, resource_type_hash))
+            .do_update()
+            .set((
+                data.eq(excluded(data)),
+                is_deleted.eq(excluded(is_deleted)),
+                state_key_hash.eq(excluded(state_key_hash)),
+                last_transaction_version.eq(excluded(last_transaction_version)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        Some(" WHERE current_move_resources.last_transaction_version <= excluded.last_transaction_version "),
+    )
+}
+
 pub fn insert_table_items_query(
     items_to_insert: Vec<TableItem>,
 ) -> (
@@ -217,20 +297,30 @@ impl ProcessorTrait for DefaultProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
     ) -> anyhow::Result<ProcessingResult> {
         let processing_start = std::time::Instant::now();
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
+        let (num_events, num_write_set_changes) = count_events_and_write_set_changes(&transactions);
+        let mut block_end_transactions: Vec<BlockEndTransaction> = transactions
+            .iter()
+            .filter_map(BlockEndTransaction::from_transaction)
+            .collect();
+        let mut current_move_resources = if self.config.current_move_resources {
+            CurrentMoveResource::from_transactions(&transactions)
+        } else {
+            vec![]
+        };
 
         let (
             raw_block_metadata_transactions,
             raw_table_items,
             raw_current_table_items,
             raw_table_metadata,
-        ) = tokio::task::spawn_blocking(move || process_transactions(transactions))
+        ) = tokio::task::spawn_blocking(move || process_transactions(&transactions))
             .await
             .expect("Failed to spawn_blocking for TransactionModel::from_transactions");
 
@@ -262,6 +352,12 @@ impl ProcessorTrait for DefaultProcessor {
         if flags.contains(TableFlags::TABLE_METADATAS) {
             postgres_table_metadata.clear();
         }
+        if flags.contains(TableFlags::BLOCK_END_TRANSACTIONS) {
+            block_end_transactions.clear();
+        }
+        if flags.contains(TableFlags::CURRENT_MOVE_RESOURCES) {
+            current_move_resources.clear();
+        }
 
         let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
         let db_insertion_start = std::time::Instant::now();
@@ -272,6 +368,8 @@ impl ProcessorTrait for DefaultProcessor {
             start_version,
             end_version,
             &postgres_block_metadata_transactions,
+            &block_end_transactions,
+            &current_move_resources,
             (
                 &postgres_table_items,
                 &postgres_current_table_items,
@@ -285,6 +383,8 @@ impl ProcessorTrait for DefaultProcessor {
         // make it faster.
         tokio::task::spawn(async move {
             drop(postgres_block_metadata_transactions);
+            drop(block_end_transactions);
+            drop(current_move_resources);
             drop(postgres_table_items);
             drop(postgres_current_table_items);
             drop(postgres_table_metadata);
@@ -299,6 +399,8 @@ impl ProcessorTrait for DefaultProcessor {
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp,
+                    num_events: Some(num_events),
+                    num_write_set_changes: Some(num_write_set_changes),
                 },
             )),
             Err(e) => {
@@ -340,7 +442,7 @@ impl ProcessorTrait for DefaultProcessor {
 /// * `Vec<RawCurrentTableItem>` - A vector of current table items, sorted by primary key.
 /// * `Vec<RawTableMetadata>` - A vector of table metadata, sorted by primary key.
 pub fn process_transactions(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
 ) -> (
     Vec<RawBlockMetadataTransactionModel>,
     Vec<RawTableItem>,
@@ -352,23 +454,17 @@ pub fn process_transactions(
     let mut current_table_items = AHashMap::new();
     let mut table_metadata = AHashMap::new();
 
+    let mut timer = TransactionTypeTimer::new(ProcessorName::DefaultProcessor.into());
     for transaction in transactions {
+        timer.start(transaction);
         let version = transaction.version as i64;
         let block_height = transaction.block_height as i64;
         let epoch = transaction.epoch as i64;
-        let timestamp = transaction
-            .timestamp
-            .as_ref()
-            .expect("Transaction timestamp doesn't exist!");
+        let block_timestamp = parse_transaction_timestamp(transaction.timestamp.as_ref(), version);
         let transaction_info = transaction
             .info
             .as_ref()
             .expect("Transaction info doesn't exist!");
-
-        #[allow(deprecated)]
-        let block_timestamp =
-            chrono::NaiveDateTime::from_timestamp_opt(timestamp.seconds, timestamp.nanos as u32)
-                .expect("Txn Timestamp is invalid!");
         let txn_data = match transaction.txn_data.as_ref() {
             Some(txn_data) => txn_data,
             None => {
@@ -382,15 +478,25 @@ pub fn process_transactions(
                 continue;
             },
         };
-        if let TxnData::BlockMetadata(block_metadata_txn) = txn_data {
-            let bmt = RawBlockMetadataTransactionModel::from_bmt_transaction(
-                block_metadata_txn,
-                version,
-                block_height,
-                epoch,
-                timestamp,
-            );
-            block_metadata_transactions.push(bmt);
+        // Exhaustive so that new transaction types have to be considered here
+        match txn_data {
+            TxnData::BlockMetadata(block_metadata_txn) => {
+                let timestamp = transaction
+                    .timestamp
+                    .as_ref()
+                    .expect("Block metadata transaction timestamp doesn't exist!");
+                let bmt = RawBlockMetadataTransactionModel::from_bmt_transaction(
+                    block_metadata_txn,
+                    version,
+                    block_height,
+                    epoch,
+                    timestamp,
+                );
+                block_metadata_transactions.push(bmt);
+            },
+            // Handled by `BlockEndTransaction` in the processor since this is shared with the SDK
+            TxnData::StateCheckpoint(_) | TxnData::BlockEpilogue(_) => {},
+            TxnData::Genesis(_) | TxnData::User(_) | TxnData::Validator(_) => {},
         }
 
         for (index, wsc) in transaction_info.changes.iter().enumerate() {
@@ -433,6 +539,7 @@ pub fn process_transactions(
             };
         }
     }
+    timer.finish();
 
     // Getting list of values and sorting by pk in order to avoid postgres deadlock since we're doing multi threaded db writes
     let mut current_table_items = current_table_items
@@ -3,38 +3,266 @@
 
 use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
 use crate::{
-    db::postgres::models::events_models::events::EventModel,
+    column_backfill::ColumnBackfill,
+    db::{
+        clickhouse::models::events::ClickhouseEvent,
+        postgres::models::{
+            events_models::{
+                events::EventModel,
+                typed_events::{TypedEvent, TypedEventFields},
+            },
+            outbox::Outbox,
+        },
+    },
     gap_detectors::ProcessingResult,
     schema,
     utils::{
-        counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+        clickhouse_sink::{ClickhouseConfig, ClickhouseSink},
+        counters::{TransactionTypeTimer, PROCESSOR_UNKNOWN_TYPE_COUNT},
+        database::{
+            execute_in_chunks, execute_in_chunks_with_conflict_strategy,
+            execute_in_chunks_with_outbox, execute_with_better_error, get_config_table_chunk_size,
+            get_config_table_conflict_strategy, prefixed_table_name, ArcDbPool, ConflictStrategy,
+        },
+        util::debug_assert_standardized_address,
+        webhook::{insert_webhook_dead_letters, WebhookConfig, WebhookSink},
     },
 };
-use ahash::AHashMap;
-use anyhow::bail;
-use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+use ahash::{AHashMap, AHashSet};
+use anyhow::{bail, Context};
+use aptos_protos::transaction::v1::{transaction::TxnData, Event as EventPB, Transaction};
 use async_trait::async_trait;
 use diesel::{
     pg::{upsert::excluded, Pg},
     query_builder::QueryFragment,
+    sql_query,
+    sql_types::BigInt,
     ExpressionMethods,
 };
-use std::fmt::Debug;
-use tracing::error;
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
+use tracing::{error, info};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventsProcessorConfig {
+    /// If set, `events` is a partitioned table with one partition per this many versions, e.g.
+    /// `10000000`. Partitions are created as the processor reaches them.
+    #[serde(default)]
+    pub partition_interval: Option<u64>,
+    /// If set, events are also posted to this webhook as CloudEvents.
+    #[serde(default)]
+    pub webhook: Option<WebhookConfig>,
+    /// If set, a batch is parsed and inserted this many events at a time instead of all at
+    /// once, so a transaction with a huge number of events doesn't need them all in memory.
+    #[serde(default)]
+    pub max_events_per_insert: Option<usize>,
+    /// If set, each event also gets a row in `outbox`, written in the same DB transaction, for
+    /// change data capture.
+    #[serde(default)]
+    pub outbox: bool,
+    /// Event types whose fields are also written to `typed_events` as typed columns, with
+    /// where in the event data each column comes from.
+    #[serde(default)]
+    pub typed_events: AHashMap<String, TypedEventFields>,
+    /// If set, events are also written to this ClickHouse server for analytics.
+    #[serde(default)]
+    pub clickhouse: Option<ClickhouseConfig>,
+}
 
 pub struct EventsProcessor {
     connection_pool: ArcDbPool,
     per_table_chunk_sizes: AHashMap<String, usize>,
+    per_table_conflict_strategies: AHashMap<String, ConflictStrategy>,
+    partition_interval: Option<u64>,
+    // Start versions of partitions this processor already made sure exist
+    created_partitions: Mutex<AHashSet<u64>>,
+    webhook: Option<WebhookSink>,
+    max_events_per_insert: Option<usize>,
+    outbox: bool,
+    typed_events: AHashMap<String, TypedEventFields>,
+    clickhouse: Option<ClickhouseSink>,
 }
 
 impl EventsProcessor {
-    pub fn new(connection_pool: ArcDbPool, per_table_chunk_sizes: AHashMap<String, usize>) -> Self {
+    pub fn new(
+        connection_pool: ArcDbPool,
+        config: EventsProcessorConfig,
+        per_table_chunk_sizes: AHashMap<String, usize>,
+        per_table_conflict_strategies: AHashMap<String, ConflictStrategy>,
+    ) -> Self {
         Self {
             connection_pool,
             per_table_chunk_sizes,
+            per_table_conflict_strategies,
+            partition_interval: config.partition_interval,
+            created_partitions: Mutex::new(AHashSet::new()),
+            webhook: config
+                .webhook
+                .map(|webhook| WebhookSink::new(webhook).expect("Failed to create webhook sink")),
+            // 0 would never insert anything
+            max_events_per_insert: config.max_events_per_insert.map(|n| n.max(1)),
+            outbox: config.outbox,
+            typed_events: config.typed_events,
+            clickhouse: config.clickhouse.map(ClickhouseSink::new),
         }
     }
+
+    /// Typed columns of the events of the types configured in `typed_events`.
+    fn typed_events(&self, events: &[EventModel]) -> Vec<TypedEvent> {
+        events
+            .iter()
+            .filter_map(|event| {
+                let fields = self.typed_events.get(&event.type_)?;
+                Some(TypedEvent::from_event(event, fields))
+            })
+            .collect()
+    }
+
+    /// Inserts part of a batch and delivers it to ClickHouse and the webhook.
+    async fn insert_sub_batch(
+        &self,
+        start_version: u64,
+        end_version: u64,
+        events: &[EventModel],
+    ) -> anyhow::Result<()> {
+        insert_to_db(
+            self.get_pool(),
+            self.name(),
+            start_version,
+            end_version,
+            events,
+            &self.typed_events(events),
+            &self.per_table_chunk_sizes,
+            &self.per_table_conflict_strategies,
+            self.outbox,
+        )
+        .await
+        .context("Failed to insert events")?;
+        self.write_to_clickhouse(events).await?;
+        self.deliver_to_webhook(events).await
+    }
+
+    /// Parses and inserts the batch `max_events_per_insert` events at a time, splitting
+    /// transactions with more events than that across inserts. Returns the time spent parsing
+    /// and inserting, and the number of events.
+    async fn process_in_sub_batches(
+        &self,
+        transactions: &[Transaction],
+        start_version: u64,
+        end_version: u64,
+        max_events_per_insert: usize,
+    ) -> anyhow::Result<(f64, f64, u64)> {
+        let processing_start = std::time::Instant::now();
+        let mut db_insertion_duration = std::time::Duration::ZERO;
+        let mut events = Vec::with_capacity(max_events_per_insert);
+        let mut num_events = 0;
+        let mut timer = TransactionTypeTimer::new(self.name());
+        for txn in transactions {
+            timer.start(txn);
+            for event in transaction_events(txn) {
+                events.push(event);
+                num_events += 1;
+                if events.len() == max_events_per_insert {
+                    timer.pause();
+                    let db_insertion_start = std::time::Instant::now();
+                    self.insert_sub_batch(start_version, end_version, &events)
+                        .await?;
+                    db_insertion_duration += db_insertion_start.elapsed();
+                    events.clear();
+                    timer.resume();
+                }
+            }
+        }
+        timer.finish();
+        if !events.is_empty() {
+            let db_insertion_start = std::time::Instant::now();
+            self.insert_sub_batch(start_version, end_version, &events)
+                .await?;
+            db_insertion_duration += db_insertion_start.elapsed();
+        }
+        let processing_duration = processing_start.elapsed() - db_insertion_duration;
+        Ok((
+            processing_duration.as_secs_f64(),
+            db_insertion_duration.as_secs_f64(),
+            num_events,
+        ))
+    }
+
+    /// Writes the events to ClickHouse if configured. The batch fails if they can't be written,
+    /// so progress doesn't get ahead of ClickHouse.
+    async fn write_to_clickhouse(&self, events: &[EventModel]) -> anyhow::Result<()> {
+        let Some(clickhouse) = &self.clickhouse else {
+            return Ok(());
+        };
+        let rows = events.iter().map(ClickhouseEvent::from).collect::<Vec<_>>();
+        clickhouse.insert(&rows).await
+    }
+
+    /// Sends the events to the webhook if configured. Events it can't take are dead-lettered, and
+    /// the batch fails if even that doesn't work, so no event is skipped.
+    async fn deliver_to_webhook(&self, events: &[EventModel]) -> anyhow::Result<()> {
+        let Some(webhook) = &self.webhook else {
+            return Ok(());
+        };
+        let dead_letters = webhook.deliver(events).await?;
+        insert_webhook_dead_letters(self.get_pool(), &dead_letters, &self.per_table_chunk_sizes)
+            .await
+            .context("Failed to insert webhook dead letters")?;
+        Ok(())
+    }
+
+    /// Creates the partitions `[start_version, end_version]` will be inserted into, if they
+    /// don't exist yet. Postgres then routes each row to its partition.
+    async fn create_partitions(&self, start_version: u64, end_version: u64) -> anyhow::Result<()> {
+        let Some(partition_interval) = self.partition_interval else {
+            return Ok(());
+        };
+        for partition_start in partition_starts(start_version, end_version, partition_interval) {
+            if self
+                .created_partitions
+                .lock()
+                .unwrap()
+                .contains(&partition_start)
+            {
+                continue;
+            }
+            execute_with_better_error(
+                self.get_pool(),
+                sql_query("SELECT create_events_partition($1, $2)")
+                    .bind::<BigInt, _>(partition_start as i64)
+                    .bind::<BigInt, _>((partition_start + partition_interval) as i64),
+                None,
+            )
+            .await
+            .with_context(|| format!("Failed to create events partition {}", partition_start))?;
+            self.created_partitions
+                .lock()
+                .unwrap()
+                .insert(partition_start);
+        }
+        Ok(())
+    }
+}
+
+/// Turns `events` into a partitioned table if it isn't one yet. This only works while the table
+/// is empty, so it's meant to be run on startup, right after the migrations.
+pub async fn partition_events_table(pool: ArcDbPool) -> anyhow::Result<()> {
+    execute_with_better_error(pool, sql_query("SELECT partition_events_table()"), None)
+        .await
+        .context("Failed to partition the events table")?;
+    info!("[Parser] events is partitioned by transaction_version");
+    Ok(())
+}
+
+/// Start versions of the partitions that `[start_version, end_version]` falls into
+fn partition_starts(start_version: u64, end_version: u64, partition_interval: u64) -> Vec<u64> {
+    (start_version / partition_interval..=end_version / partition_interval)
+        .map(|i| i * partition_interval)
+        .collect()
 }
 
 impl Debug for EventsProcessor {
@@ -54,7 +282,10 @@ async fn insert_to_db(
     start_version: u64,
     end_version: u64,
     events: &[EventModel],
+    typed_events: &[TypedEvent],
     per_table_chunk_sizes: &AHashMap<String, usize>,
+    per_table_conflict_strategies: &AHashMap<String, ConflictStrategy>,
+    outbox: bool,
 ) -> Result<(), diesel::result::Error> {
     tracing::trace!(
         name = name,
@@ -62,14 +293,89 @@ async fn insert_to_db(
         end_version = end_version,
         "Inserting to db",
     );
+    // Events never change, so reprocessing only needs to skip them
+    let conflict_strategy = get_config_table_conflict_strategy(
+        "events",
+        per_table_conflict_strategies,
+        ConflictStrategy::DoNothing,
+    );
+    let chunk_size = get_config_table_chunk_size::<EventModel>("events", per_table_chunk_sizes);
+    if outbox {
+        execute_in_chunks_with_outbox(
+            conn.clone(),
+            conflict_strategy,
+            insert_events_do_nothing_query,
+            insert_events_query,
+            events_outbox,
+            events,
+            chunk_size,
+        )
+        .await?;
+    } else {
+        execute_in_chunks_with_conflict_strategy(
+            conn.clone(),
+            conflict_strategy,
+            insert_events_do_nothing_query,
+            insert_events_query,
+            events,
+            chunk_size,
+        )
+        .await?;
+    }
     execute_in_chunks(
         conn,
-        insert_events_query,
-        events,
-        get_config_table_chunk_size::<EventModel>("events", per_table_chunk_sizes),
+        insert_typed_events_query,
+        typed_events,
+        get_config_table_chunk_size::<TypedEvent>("typed_events", per_table_chunk_sizes),
+    )
+    .await
+}
+
+/// Outbox rows of the events, keyed like `events`.
+fn events_outbox(events: &[EventModel]) -> Vec<Outbox> {
+    let table_name = prefixed_table_name("events");
+    events
+        .iter()
+        .map(|event| {
+            let row_key = serde_json::json!({
+                "transaction_version": event.transaction_version,
+                "event_index": event.event_index,
+            });
+            Outbox::insert(&table_name, row_key, event)
+        })
+        .collect()
+}
+
+fn insert_events_do_nothing_query(
+    items_to_insert: Vec<EventModel>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::events::dsl::*;
+    (
+        diesel::insert_into(schema::events::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_nothing(),
+        None,
+    )
+}
+
+fn insert_typed_events_query(
+    items_to_insert: Vec<TypedEvent>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::typed_events::dsl::*;
+    (
+        diesel::insert_into(schema::typed_events::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_nothing(),
+        None,
     )
-    .await?;
-    Ok(())
 }
 
 fn insert_events_query(
@@ -100,7 +406,7 @@ impl ProcessorTrait for EventsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -108,32 +414,66 @@ impl ProcessorTrait for EventsProcessor {
         let processing_start = std::time::Instant::now();
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
 
-        let events = process_transactions(transactions);
+        if let Some(max_events_per_insert) = self.max_events_per_insert {
+            self.create_partitions(start_version, end_version).await?;
+            let (processing_duration_in_secs, db_insertion_duration_in_secs, num_events) = self
+                .process_in_sub_batches(
+                    &transactions,
+                    start_version,
+                    end_version,
+                    max_events_per_insert,
+                )
+                .await?;
+            return Ok(ProcessingResult::DefaultProcessingResult(
+                DefaultProcessingResult {
+                    start_version,
+                    end_version,
+                    processing_duration_in_secs,
+                    db_insertion_duration_in_secs,
+                    last_transaction_timestamp,
+                    num_events: Some(num_events),
+                    num_write_set_changes: None,
+                },
+            ));
+        }
+
+        let events = process_transactions(&transactions);
+        let typed_events = self.typed_events(&events);
 
         let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
         let db_insertion_start = std::time::Instant::now();
 
+        self.create_partitions(start_version, end_version).await?;
         let tx_result = insert_to_db(
             self.get_pool(),
             self.name(),
             start_version,
             end_version,
             &events,
+            &typed_events,
             &self.per_table_chunk_sizes,
+            &self.per_table_conflict_strategies,
+            self.outbox,
         )
         .await;
 
         let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
         match tx_result {
-            Ok(_) => Ok(ProcessingResult::DefaultProcessingResult(
-                DefaultProcessingResult {
-                    start_version,
-                    end_version,
-                    processing_duration_in_secs,
-                    db_insertion_duration_in_secs,
-                    last_transaction_timestamp,
-                },
-            )),
+            Ok(_) => {
+                self.write_to_clickhouse(&events).await?;
+                self.deliver_to_webhook(&events).await?;
+                Ok(ProcessingResult::DefaultProcessingResult(
+                    DefaultProcessingResult {
+                        start_version,
+                        end_version,
+                        processing_duration_in_secs,
+                        db_insertion_duration_in_secs,
+                        last_transaction_timestamp,
+                        num_events: Some(events.len() as u64),
+                        num_write_set_changes: None,
+                    },
+                ))
+            },
             Err(e) => {
                 error!(
                     start_version = start_version,
@@ -152,35 +492,155 @@ impl ProcessorTrait for EventsProcessor {
     }
 }
 
-pub fn process_transactions(transactions: Vec<Transaction>) -> Vec<EventModel> {
+pub fn process_transactions(transactions: &[Transaction]) -> Vec<EventModel> {
     let mut events = vec![];
-    for txn in &transactions {
-        let txn_version = txn.version as i64;
-        let block_height = txn.block_height as i64;
-        let txn_data = match txn.txn_data.as_ref() {
-            Some(data) => data,
-            None => {
-                tracing::warn!(
-                    transaction_version = txn_version,
-                    "Transaction data doesn't exist"
-                );
-                PROCESSOR_UNKNOWN_TYPE_COUNT
-                    .with_label_values(&["EventsProcessor"])
-                    .inc();
-                continue;
-            },
-        };
-        let default = vec![];
-        let raw_events = match txn_data {
-            TxnData::BlockMetadata(tx_inner) => &tx_inner.events,
-            TxnData::Genesis(tx_inner) => &tx_inner.events,
-            TxnData::User(tx_inner) => &tx_inner.events,
-            TxnData::Validator(tx_inner) => &tx_inner.events,
-            _ => &default,
-        };
-
-        let txn_events = EventModel::from_events(raw_events, txn_version, block_height);
-        events.extend(txn_events);
+    let mut timer = TransactionTypeTimer::new(ProcessorName::EventsProcessor.into());
+    for txn in transactions {
+        timer.start(txn);
+        events.extend(transaction_events(txn));
     }
+    timer.finish();
     events
 }
+
+/// `indexed_type` of an event, keyed like `events`.
+#[derive(Debug, Serialize)]
+pub struct EventIndexedType {
+    pub transaction_version: i64,
+    pub event_index: i64,
+    pub indexed_type: String,
+}
+
+/// Recomputes `indexed_type` of the events already in `events`, e.g. after a change to how it's
+/// derived from the event type.
+pub fn indexed_type_backfill(
+) -> ColumnBackfill<impl Fn(&[Transaction]) -> Vec<EventIndexedType> + Send + Sync> {
+    ColumnBackfill::new(
+        "events",
+        &[("transaction_version", "BIGINT"), ("event_index", "BIGINT")],
+        ("indexed_type", "VARCHAR(300)"),
+        |transactions: &[Transaction]| {
+            transactions
+                .iter()
+                .flat_map(transaction_events)
+                .map(|event| EventIndexedType {
+                    transaction_version: event.transaction_version,
+                    event_index: event.event_index,
+                    indexed_type: event.indexed_type,
+                })
+                .collect()
+        },
+    )
+}
+
+/// The events of a transaction, converted one at a time as they're iterated.
+fn transaction_events(txn: &Transaction) -> impl Iterator<Item = EventModel> + '_ {
+    let txn_version = txn.version as i64;
+    let block_height = txn.block_height as i64;
+    let raw_events: &[EventPB] = match txn.txn_data.as_ref() {
+        Some(TxnData::BlockMetadata(tx_inner)) => &tx_inner.events,
+        Some(TxnData::Genesis(tx_inner)) => &tx_inner.events,
+        Some(TxnData::User(tx_inner)) => &tx_inner.events,
+        Some(TxnData::Validator(tx_inner)) => &tx_inner.events,
+        Some(_) => &[],
+        None => {
+            tracing::warn!(
+                transaction_version = txn_version,
+                "Transaction data doesn't exist"
+            );
+            PROCESSOR_UNKNOWN_TYPE_COUNT
+                .with_label_values(&["EventsProcessor"])
+                .inc();
+            &[]
+        },
+    };
+    raw_events.iter().enumerate().map(move |(index, event)| {
+        let event = EventModel::from_event(event, txn_version, block_height, index as i64);
+        debug_assert_standardized_address(&event.account_address);
+        event
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batch_spanning_partition_boundary() {
+        let interval = 10_000_000;
+        assert_eq!(partition_starts(0, 999, interval), vec![0]);
+        assert_eq!(
+            partition_starts(9_999_500, 10_000_499, interval),
+            vec![0, 10_000_000]
+        );
+        // Last version of a partition and first version of the next one
+        assert_eq!(partition_starts(9_999_999, 9_999_999, interval), vec![0]);
+        assert_eq!(
+            partition_starts(10_000_000, 10_000_000, interval),
+            vec![10_000_000]
+        );
+        // A batch larger than the interval needs every partition in between
+        assert_eq!(partition_starts(5, 25, 10), vec![0, 10, 20]);
+    }
+
+    fn event(transaction_version: i64, event_index: i64) -> EventModel {
+        EventModel {
+            sequence_number: 0,
+            creation_number: 0,
+            account_address: "0x1".to_string(),
+            transaction_version,
+            transaction_block_height: 0,
+            type_: "0x1::coin::DepositEvent".to_string(),
+            data: serde_json::Value::Null,
+            event_index,
+            indexed_type: "0x1::coin::DepositEvent".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_reprocessing_events_skips_existing_rows() {
+        let strategy = get_config_table_conflict_strategy(
+            "events",
+            &AHashMap::new(),
+            ConflictStrategy::DoNothing,
+        );
+        assert_eq!(strategy, ConflictStrategy::DoNothing);
+
+        // The same event written twice, as when a range is reprocessed
+        let (query, where_clause) = insert_events_do_nothing_query(vec![event(1, 0), event(1, 0)]);
+        let sql = diesel::debug_query::<Pg, _>(&query).to_string();
+        assert!(sql.contains(r#"ON CONFLICT ("transaction_version", "event_index") DO NOTHING"#));
+        assert!(where_clause.is_none());
+
+        let overrides = [("events".to_string(), ConflictStrategy::DoUpdate)]
+            .into_iter()
+            .collect();
+        assert_eq!(
+            get_config_table_conflict_strategy("events", &overrides, ConflictStrategy::DoNothing),
+            ConflictStrategy::DoUpdate
+        );
+    }
+
+    #[test]
+    fn test_outbox_rows_match_events() {
+        let events = vec![event(5, 0), event(5, 1), event(6, 0)];
+        let outbox = events_outbox(&events);
+        assert_eq!(outbox.len(), events.len());
+        for (row, event) in outbox.iter().zip(&events) {
+            assert_eq!(row.table_name, "events");
+            assert_eq!(row.operation, "insert");
+            assert_eq!(
+                row.row_key,
+                serde_json::json!({
+                    "transaction_version": event.transaction_version,
+                    "event_index": event.event_index,
+                })
+            );
+            let payload: EventModel = serde_json::from_value(row.payload.clone()).unwrap();
+            assert_eq!(payload.transaction_version, event.transaction_version);
+            assert_eq!(payload.event_index, event.event_index);
+            assert_eq!(payload.type_, event.type_);
+            assert_eq!(payload.data, event.data);
+        }
+    }
+}
@@ -1,8 +1,16 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
+use super::{
+    parquet_processors::{ParquetProcessorTrait, ParquetSinkConfig},
+    DefaultProcessingResult, ProcessorName, ProcessorTrait,
+};
 use crate::{
+    bq_analytics::{
+        create_parquet_handler_loop,
+        generic_parquet_processor::{merge_struct_counts, NamedTable, ParquetDataGeneric},
+        ParquetProcessingResult,
+    },
     db::{
         common::models::{
             fungible_asset_models::{
@@ -24,9 +32,15 @@ use crate::{
                 ObjectAggregatedData, ObjectAggregatedDataMapping, ObjectWithMetadata,
             },
         },
+        parquet::models::fungible_asset_models::{
+            parquet_v2_fungible_asset_activities::FungibleAssetActivity as ParquetFungibleAssetActivity,
+            parquet_v2_fungible_asset_balances::FungibleAssetBalance as ParquetFungibleAssetBalance,
+        },
         postgres::models::{
             coin_models::coin_supply::CoinSupply,
             fungible_asset_models::{
+                coin_to_fa_mapping::CoinToFaMapping,
+                current_fungible_asset_supply::CurrentFungibleAssetSupply,
                 v2_fungible_asset_activities::{EventToCoinType, FungibleAssetActivity},
                 v2_fungible_asset_balances::{
                     CurrentFungibleAssetBalance, CurrentUnifiedFungibleAssetBalance,
@@ -34,6 +48,7 @@ use crate::{
                 },
                 v2_fungible_asset_utils::FeeStatement,
                 v2_fungible_metadata::FungibleAssetMetadataModel,
+                v2_fungible_metadata_history::FungibleAssetMetadataHistory,
             },
             resources::{FromWriteResource, V2FungibleAssetResource},
         },
@@ -41,43 +56,179 @@ use crate::{
     gap_detectors::ProcessingResult,
     schema,
     utils::{
-        counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
+        counters::{PROCESSOR_UNKNOWN_TYPE_COUNT, SUPPLY_MISMATCH_COUNT},
         database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+        rayon_pool::RayonPool,
         table_flags::TableFlags,
-        util::{get_entry_function_from_user_request, standardize_address},
+        util::{
+            debug_assert_standardized_address, get_entry_function_from_user_request,
+            parse_transaction_timestamp, standardize_address, ParseContext,
+        },
     },
 };
 use ahash::AHashMap;
-use anyhow::bail;
+use anyhow::{anyhow, bail, Context};
 use aptos_protos::transaction::v1::{transaction::TxnData, write_set_change::Change, Transaction};
 use async_trait::async_trait;
-use chrono::NaiveDateTime;
+use chrono::{NaiveDateTime, SubsecRound};
 use diesel::{
     pg::{upsert::excluded, Pg},
     query_builder::QueryFragment,
     ExpressionMethods,
 };
+use kanal::AsyncSender;
 use rayon::prelude::*;
-use std::fmt::Debug;
-use tracing::error;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
+use tracing::{error, warn};
+
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FungibleAssetProcessorConfig {
+    /// If set, activities and balances are also written to Parquet ("dual sink" mode).
+    #[serde(default)]
+    pub parquet_sink: Option<ParquetSinkConfig>,
+    /// If set, tracks the supply of each fungible asset in `current_fungible_asset_supply` and
+    /// checks every supply change against the deposits and withdrawals of the asset.
+    #[serde(default)]
+    pub reconcile_supply: bool,
+}
+
+/// Senders to the parquet handlers used in dual sink mode.
+///
+/// Postgres writes happen synchronously in `process_transactions`, so by the time structs
+/// are sent to the parquet handlers the batch is already in Postgres. The processor then
+/// reports a `ParquetProcessingResult`, which means progress is tracked by the parquet gap
+/// detector and only advances once the Parquet upload has succeeded too.
+struct FungibleAssetParquetSink {
+    fungible_asset_activities_sender: AsyncSender<ParquetDataGeneric<ParquetFungibleAssetActivity>>,
+    fungible_asset_balances_sender: AsyncSender<ParquetDataGeneric<ParquetFungibleAssetBalance>>,
+}
+
+impl FungibleAssetParquetSink {
+    /// Both tables are reported in one result, since their structs are counted together
+    fn table_names() -> String {
+        [
+            ParquetFungibleAssetActivity::TABLE_NAME,
+            ParquetFungibleAssetBalance::TABLE_NAME,
+        ]
+        .join(",")
+    }
+
+    /// Sends the batch to the parquet handlers and returns the struct count per version,
+    /// which the parquet gap detector needs to know when a version is fully uploaded.
+    async fn send(
+        &self,
+        raw_fungible_asset_activities: Vec<RawFungibleAssetActivity>,
+        raw_fungible_asset_balances: Vec<RawFungibleAssetBalance>,
+    ) -> anyhow::Result<AHashMap<i64, i64>> {
+        let mut activity_struct_count: AHashMap<i64, i64> = AHashMap::new();
+        for activity in &raw_fungible_asset_activities {
+            *activity_struct_count
+                .entry(activity.transaction_version)
+                .or_insert(0) += 1;
+        }
+        let mut balance_struct_count: AHashMap<i64, i64> = AHashMap::new();
+        for balance in &raw_fungible_asset_balances {
+            *balance_struct_count
+                .entry(balance.transaction_version)
+                .or_insert(0) += 1;
+        }
+        let transaction_version_to_struct_count =
+            merge_struct_counts([&activity_struct_count, &balance_struct_count]);
+
+        self.fungible_asset_activities_sender
+            .send(ParquetDataGeneric {
+                data: raw_fungible_asset_activities
+                    .into_iter()
+                    .map(ParquetFungibleAssetActivity::from_raw)
+                    .collect(),
+                transaction_version_to_struct_count: activity_struct_count,
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to send to parquet manager: {}", e))?;
+        self.fungible_asset_balances_sender
+            .send(ParquetDataGeneric {
+                data: raw_fungible_asset_balances
+                    .into_iter()
+                    .map(ParquetFungibleAssetBalance::from_raw)
+                    .collect(),
+                transaction_version_to_struct_count: balance_struct_count,
+            })
+            .await
+            .map_err(|e| anyhow!("Failed to send to parquet manager: {}", e))?;
+
+        Ok(transaction_version_to_struct_count)
+    }
+}
 
 pub struct FungibleAssetProcessor {
     connection_pool: ArcDbPool,
     per_table_chunk_sizes: AHashMap<String, usize>,
     deprecated_tables: TableFlags,
+    parquet_sink: Option<FungibleAssetParquetSink>,
+    reconcile_supply: bool,
+    rayon_pool: RayonPool,
 }
 
 impl FungibleAssetProcessor {
     pub fn new(
         connection_pool: ArcDbPool,
+        config: FungibleAssetProcessorConfig,
         per_table_chunk_sizes: AHashMap<String, usize>,
         deprecated_tables: TableFlags,
-    ) -> Self {
-        Self {
+        gap_detector_sender: Option<AsyncSender<ProcessingResult>>,
+        rayon_pool: RayonPool,
+    ) -> anyhow::Result<Self> {
+        let parquet_sink = match config.parquet_sink {
+            Some(parquet_config) => {
+                let gap_detector_sender =
+                    gap_detector_sender.context("Dual sink mode requires a gap detector sender")?;
+                parquet_config
+                    .set_google_credentials(parquet_config.google_application_credentials.clone());
+
+                Some(FungibleAssetParquetSink {
+                    fungible_asset_activities_sender: create_parquet_handler_loop::<
+                        ParquetFungibleAssetActivity,
+                    >(
+                        gap_detector_sender.clone(),
+                        ProcessorName::FungibleAssetProcessor.into(),
+                        parquet_config.bucket_name.clone(),
+                        parquet_config.bucket_root.clone(),
+                        parquet_config.parquet_handler_response_channel_size,
+                        parquet_config.max_buffer_size,
+                        parquet_config.parquet_upload_interval_in_secs(),
+                        parquet_config.gcs_upload,
+                        connection_pool.clone(),
+                        parquet_config.compression,
+                    ),
+                    fungible_asset_balances_sender: create_parquet_handler_loop::<
+                        ParquetFungibleAssetBalance,
+                    >(
+                        gap_detector_sender.clone(),
+                        ProcessorName::FungibleAssetProcessor.into(),
+                        parquet_config.bucket_name.clone(),
+                        parquet_config.bucket_root.clone(),
+                        parquet_config.parquet_handler_response_channel_size,
+                        parquet_config.max_buffer_size,
+                        parquet_config.parquet_upload_interval_in_secs(),
+                        parquet_config.gcs_upload,
+                        connection_pool.clone(),
+                        parquet_config.compression,
+                    ),
+                })
+            },
+            None => None,
+        };
+
+        Ok(Self {
             connection_pool,
             per_table_chunk_sizes,
             deprecated_tables,
-        }
+            parquet_sink,
+            reconcile_supply: config.reconcile_supply,
+            rayon_pool,
+        })
     }
 }
 
@@ -106,6 +257,9 @@ async fn insert_to_db(
         &[CurrentUnifiedFungibleAssetBalance],
     ),
     coin_supply: &[CoinSupply],
+    fungible_asset_metadata_history: &[FungibleAssetMetadataHistory],
+    current_fungible_asset_supply: &[CurrentFungibleAssetSupply],
+    coin_to_fa_mappings: &[CoinToFaMapping],
     per_table_chunk_sizes: &AHashMap<String, usize>,
 ) -> Result<(), diesel::result::Error> {
     tracing::trace!(
@@ -170,15 +324,50 @@ async fn insert_to_db(
         ),
     );
     let cs = execute_in_chunks(
-        conn,
+        conn.clone(),
         insert_coin_supply_query,
         coin_supply,
         get_config_table_chunk_size::<CoinSupply>("coin_supply", per_table_chunk_sizes),
     );
-    let (faa_res, fam_res, fab_res, cfab_res, cufab1_res, cufab2_res, cs_res) =
-        tokio::join!(faa, fam, fab, cfab, cufab_v1, cufab_v2, cs);
+    let famh = execute_in_chunks(
+        conn.clone(),
+        insert_fungible_asset_metadata_history_query,
+        fungible_asset_metadata_history,
+        get_config_table_chunk_size::<FungibleAssetMetadataHistory>(
+            "fungible_asset_metadata_history",
+            per_table_chunk_sizes,
+        ),
+    );
+    let cfas = execute_in_chunks(
+        conn.clone(),
+        insert_current_fungible_asset_supply_query,
+        current_fungible_asset_supply,
+        get_config_table_chunk_size::<CurrentFungibleAssetSupply>(
+            "current_fungible_asset_supply",
+            per_table_chunk_sizes,
+        ),
+    );
+    let ctfm = execute_in_chunks(
+        conn,
+        insert_coin_to_fa_mapping_query,
+        coin_to_fa_mappings,
+        get_config_table_chunk_size::<CoinToFaMapping>("coin_to_fa_mapping", per_table_chunk_sizes),
+    );
+    let (
+        faa_res,
+        fam_res,
+        fab_res,
+        cfab_res,
+        cufab1_res,
+        cufab2_res,
+        cs_res,
+        famh_res,
+        cfas_res,
+        ctfm_res,
+    ) = tokio::join!(faa, fam, fab, cfab, cufab_v1, cufab_v2, cs, famh, cfas, ctfm);
     for res in [
-        faa_res, fam_res, fab_res, cfab_res, cufab1_res, cufab2_res, cs_res,
+        faa_res, fam_res, fab_res, cfab_res, cufab1_res, cufab2_res, cs_res, famh_res, cfas_res,
+        ctfm_res,
     ] {
         res?;
     }
@@ -239,6 +428,46 @@ pub fn insert_fungible_asset_metadata_query(
     )
 }
 
+pub fn insert_fungible_asset_metadata_history_query(
+    items_to_insert: Vec<FungibleAssetMetadataHistory>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::fungible_asset_metadata_history::dsl::*;
+
+    (
+        diesel::insert_into(schema::fungible_asset_metadata_history::table)
+            .values(items_to_insert)
+            .on_conflict((asset_type, transaction_version))
+            .do_nothing(),
+        None,
+    )
+}
+
+pub fn insert_current_fungible_asset_supply_query(
+    items_to_insert: Vec<CurrentFungibleAssetSupply>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::current_fungible_asset_supply::dsl::*;
+
+    (
+        diesel::insert_into(schema::current_fungible_asset_supply::table)
+            .values(items_to_insert)
+            .on_conflict(asset_type)
+            .do_update()
+            .set((
+                supply.eq(excluded(supply)),
+                last_transaction_version.eq(excluded(last_transaction_version)),
+                last_transaction_timestamp.eq(excluded(last_transaction_timestamp)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        Some(" WHERE current_fungible_asset_supply.last_transaction_version <= excluded.last_transaction_version "),
+    )
+}
+
 pub fn insert_fungible_asset_balances_query(
     items_to_insert: Vec<FungibleAssetBalance>,
 ) -> (
@@ -362,6 +591,32 @@ pub fn insert_coin_supply_query(
     )
 }
 
+pub fn insert_coin_to_fa_mapping_query(
+    items_to_insert: Vec<CoinToFaMapping>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::coin_to_fa_mapping::dsl::*;
+
+    // A pairing never changes, but batches can be processed out of order, so the earliest
+    // sighting wins
+    (
+        diesel::insert_into(schema::coin_to_fa_mapping::table)
+            .values(items_to_insert)
+            .on_conflict(coin_type_hash)
+            .do_update()
+            .set((
+                coin_type.eq(excluded(coin_type)),
+                fa_metadata_address.eq(excluded(fa_metadata_address)),
+                transaction_version.eq(excluded(transaction_version)),
+                transaction_timestamp.eq(excluded(transaction_timestamp)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        Some(" WHERE excluded.transaction_version < coin_to_fa_mapping.transaction_version "),
+    )
+}
+
 #[async_trait]
 impl ProcessorTrait for FungibleAssetProcessor {
     fn name(&self) -> &'static str {
@@ -370,7 +625,7 @@ impl ProcessorTrait for FungibleAssetProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -385,7 +640,50 @@ impl ProcessorTrait for FungibleAssetProcessor {
             raw_current_fungible_asset_balances,
             raw_current_unified_fungible_asset_balances,
             mut coin_supply,
-        ) = parse_v2_coin(&transactions).await;
+            raw_fungible_asset_metadata_writes,
+        ) = parse_v2_coin_with_metadata_writes(&transactions, &self.rayon_pool).await;
+
+        let fungible_asset_metadata_history = if self
+            .deprecated_tables
+            .contains(TableFlags::FUNGIBLE_ASSET_METADATA_HISTORY)
+        {
+            vec![]
+        } else {
+            self.get_fungible_asset_metadata_history(
+                &raw_fungible_asset_metadata_writes,
+                start_version as i64,
+            )
+            .await?
+        };
+
+        let current_fungible_asset_supply = if self.reconcile_supply {
+            self.reconcile_fungible_asset_supply(
+                &transactions,
+                &raw_fungible_asset_activities,
+                start_version as i64,
+            )
+            .await?
+        } else {
+            vec![]
+        };
+
+        let coin_to_fa_mappings = if self
+            .deprecated_tables
+            .contains(TableFlags::COIN_TO_FA_MAPPING)
+        {
+            vec![]
+        } else {
+            CoinToFaMapping::from_transactions(&transactions)?
+        };
+
+        // Keep a copy of the append-only tables for the parquet sink before they're converted
+        let parquet_data = self.parquet_sink.as_ref().map(|parquet_sink| {
+            (
+                parquet_sink,
+                raw_fungible_asset_activities.clone(),
+                raw_fungible_asset_balances.clone(),
+            )
+        });
 
         let postgres_fungible_asset_activities: Vec<FungibleAssetActivity> =
             raw_fungible_asset_activities
@@ -466,21 +764,40 @@ impl ProcessorTrait for FungibleAssetProcessor {
             &postgres_current_fungible_asset_balances,
             (&coin_balance, &fa_balance),
             &coin_supply,
+            &fungible_asset_metadata_history,
+            &current_fungible_asset_supply,
+            &coin_to_fa_mappings,
             &self.per_table_chunk_sizes,
         )
         .await;
         let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
-        match tx_result {
-            Ok(_) => Ok(ProcessingResult::DefaultProcessingResult(
+        match (tx_result, parquet_data) {
+            (Ok(_), Some((parquet_sink, raw_activities, raw_balances))) => {
+                let transaction_version_to_struct_count =
+                    parquet_sink.send(raw_activities, raw_balances).await?;
+                Ok(ProcessingResult::ParquetProcessingResult(
+                    ParquetProcessingResult {
+                        start_version: start_version as i64,
+                        end_version: end_version as i64,
+                        last_transaction_timestamp,
+                        txn_version_to_struct_count: Some(transaction_version_to_struct_count),
+                        parquet_processed_structs: None,
+                        table_name: FungibleAssetParquetSink::table_names(),
+                    },
+                ))
+            },
+            (Ok(_), None) => Ok(ProcessingResult::DefaultProcessingResult(
                 DefaultProcessingResult {
                     start_version,
                     end_version,
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             )),
-            Err(err) => {
+            (Err(err), _) => {
                 error!(
                     start_version = start_version,
                     end_version = end_version,
@@ -498,10 +815,84 @@ impl ProcessorTrait for FungibleAssetProcessor {
     }
 }
 
+impl FungibleAssetProcessor {
+    /// Keeps only the metadata writes that actually changed a tracked field, comparing against
+    /// the latest history row from before this batch.
+    async fn get_fungible_asset_metadata_history(
+        &self,
+        metadata_writes: &[RawFungibleAssetMetadataModel],
+        start_version: i64,
+    ) -> anyhow::Result<Vec<FungibleAssetMetadataHistory>> {
+        if metadata_writes.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut asset_types = metadata_writes
+            .iter()
+            .map(|write| write.asset_type.clone())
+            .collect::<Vec<_>>();
+        asset_types.sort();
+        asset_types.dedup();
+        let mut conn = self.get_conn().await;
+        let previous = FungibleAssetMetadataHistory::get_latest_before_version(
+            &asset_types,
+            start_version,
+            &mut conn,
+        )
+        .await?;
+        Ok(FungibleAssetMetadataHistory::from_writes(
+            metadata_writes,
+            previous,
+        ))
+    }
+
+    /// Checks the supply changes in this batch against the deposits and withdrawals of each
+    /// asset, starting from the supply stored before this batch, and returns the latest supply
+    /// of each asset. Mismatches are logged and counted but don't fail the batch.
+    async fn reconcile_fungible_asset_supply(
+        &self,
+        transactions: &[Transaction],
+        activities: &[RawFungibleAssetActivity],
+        start_version: i64,
+    ) -> anyhow::Result<Vec<CurrentFungibleAssetSupply>> {
+        let supply_writes = CurrentFungibleAssetSupply::from_transactions(transactions);
+        if supply_writes.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut asset_types = supply_writes
+            .iter()
+            .map(|write| write.asset_type.clone())
+            .collect::<Vec<_>>();
+        asset_types.sort();
+        asset_types.dedup();
+        let mut conn = self.get_conn().await;
+        let previous =
+            CurrentFungibleAssetSupply::get_before_version(&asset_types, start_version, &mut conn)
+                .await?;
+        for mismatch in CurrentFungibleAssetSupply::reconcile(&supply_writes, activities, previous)
+        {
+            warn!(
+                processor_name = self.name(),
+                asset_type = mismatch.asset_type,
+                transaction_version = mismatch.transaction_version,
+                expected_supply = %mismatch.expected_supply,
+                supply = %mismatch.supply,
+                "[Parser] Fungible asset supply doesn't match deposits and withdrawals",
+            );
+            SUPPLY_MISMATCH_COUNT
+                .with_label_values(&[&mismatch.asset_type])
+                .inc();
+        }
+        Ok(CurrentFungibleAssetSupply::latest_by_asset_type(
+            supply_writes,
+        ))
+    }
+}
+
 /// TODO: After the migration is complete, we can move this to common models folder
 /// V2 coin is called fungible assets and this flow includes all data from V1 in coin_processor
 pub async fn parse_v2_coin(
     transactions: &[Transaction],
+    rayon_pool: &RayonPool,
 ) -> (
     Vec<RawFungibleAssetActivity>,
     Vec<RawFungibleAssetMetadataModel>,
@@ -509,6 +900,40 @@ pub async fn parse_v2_coin(
     Vec<RawCurrentFungibleAssetBalance>,
     Vec<RawCurrentUnifiedFungibleAssetBalance>,
     Vec<CoinSupply>,
+) {
+    let (
+        fungible_asset_activities,
+        fungible_asset_metadata,
+        fungible_asset_balances,
+        current_fungible_asset_balances,
+        current_unified_fungible_asset_balances,
+        all_coin_supply,
+        _,
+    ) = parse_v2_coin_with_metadata_writes(transactions, rayon_pool).await;
+    (
+        fungible_asset_activities,
+        fungible_asset_metadata,
+        fungible_asset_balances,
+        current_fungible_asset_balances,
+        current_unified_fungible_asset_balances,
+        all_coin_supply,
+    )
+}
+
+/// Same as `parse_v2_coin`, but also returns every metadata write in version order (the last one
+/// per asset within each transaction) rather than only the latest per asset in the batch. This
+/// is what the metadata history is built from.
+pub async fn parse_v2_coin_with_metadata_writes(
+    transactions: &[Transaction],
+    rayon_pool: &RayonPool,
+) -> (
+    Vec<RawFungibleAssetActivity>,
+    Vec<RawFungibleAssetMetadataModel>,
+    Vec<RawFungibleAssetBalance>,
+    Vec<RawCurrentFungibleAssetBalance>,
+    Vec<RawCurrentUnifiedFungibleAssetBalance>,
+    Vec<CoinSupply>,
+    Vec<RawFungibleAssetMetadataModel>,
 ) {
     let mut fungible_asset_activities = vec![];
     let mut fungible_asset_balances = vec![];
@@ -516,309 +941,325 @@ pub async fn parse_v2_coin(
     let mut current_fungible_asset_balances: CurrentFungibleAssetMapping = AHashMap::new();
     let mut fungible_asset_metadata: FungibleAssetMetadataMapping = AHashMap::new();
 
-    let data: Vec<_> = transactions
-        .par_iter()
-        .map(|txn| {
-            let mut fungible_asset_activities = vec![];
-            let mut fungible_asset_balances = vec![];
-            let mut all_coin_supply = vec![];
-            let mut current_fungible_asset_balances: CurrentFungibleAssetMapping = AHashMap::new();
-            let mut fungible_asset_metadata: FungibleAssetMetadataMapping = AHashMap::new();
-
-            // Get Metadata for fungible assets by object address
-            let mut fungible_asset_object_helper: ObjectAggregatedDataMapping = AHashMap::new();
-
-            let txn_version = txn.version as i64;
-            let block_height = txn.block_height as i64;
-            if txn.txn_data.is_none() {
-                tracing::warn!(
-                    transaction_version = txn_version,
-                    "Transaction data doesn't exist"
-                );
-                PROCESSOR_UNKNOWN_TYPE_COUNT
-                    .with_label_values(&["FungibleAssetProcessor"])
-                    .inc();
-                return (vec![], vec![], vec![], AHashMap::new(), AHashMap::new());
-            }
-            let txn_data = txn.txn_data.as_ref().unwrap();
-            let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
-            let txn_timestamp = txn
-                .timestamp
-                .as_ref()
-                .expect("Transaction timestamp doesn't exist!")
-                .seconds;
-            #[allow(deprecated)]
-            let txn_timestamp = NaiveDateTime::from_timestamp_opt(txn_timestamp, 0)
-                .expect("Txn Timestamp is invalid!");
-            let txn_epoch = txn.epoch as i64;
-
-            let default = vec![];
-            let (events, user_request, entry_function_id_str) = match txn_data {
-                TxnData::BlockMetadata(tx_inner) => (&tx_inner.events, None, None),
-                TxnData::Validator(tx_inner) => (&tx_inner.events, None, None),
-                TxnData::Genesis(tx_inner) => (&tx_inner.events, None, None),
-                TxnData::User(tx_inner) => {
-                    let user_request = tx_inner
-                        .request
-                        .as_ref()
-                        .expect("Sends is not present in user txn");
-                    let entry_function_id_str = get_entry_function_from_user_request(user_request);
-                    (&tx_inner.events, Some(user_request), entry_function_id_str)
-                },
-                _ => (&default, None, None),
-            };
-
-            // This is because v1 events (deposit/withdraw) don't have coin type so the only way is to match
-            // the event to the resource using the event guid
-            let mut event_to_v1_coin_type: EventToCoinType = AHashMap::new();
-
-            // Loop 1: to get all object addresses
-            // Need to do a first pass to get all the object addresses and insert them into the helper
-            for wsc in transaction_info.changes.iter() {
-                if let Change::WriteResource(wr) = wsc.change.as_ref().unwrap() {
-                    if let Some(object) = ObjectWithMetadata::from_write_resource(wr).unwrap() {
-                        fungible_asset_object_helper.insert(
-                            standardize_address(&wr.address.to_string()),
-                            ObjectAggregatedData {
-                                object,
-                                ..ObjectAggregatedData::default()
-                            },
-                        );
-                    }
+    let data: Vec<_> = rayon_pool.install(|| {
+        transactions
+            .par_iter()
+            .map(|txn| {
+                let mut fungible_asset_activities = vec![];
+                let mut fungible_asset_balances = vec![];
+                let mut all_coin_supply = vec![];
+                let mut current_fungible_asset_balances: CurrentFungibleAssetMapping =
+                    AHashMap::new();
+                let mut fungible_asset_metadata: FungibleAssetMetadataMapping = AHashMap::new();
+
+                // Get Metadata for fungible assets by object address
+                let mut fungible_asset_object_helper: ObjectAggregatedDataMapping = AHashMap::new();
+
+                let txn_version = txn.version as i64;
+                let block_height = txn.block_height as i64;
+                if txn.txn_data.is_none() {
+                    tracing::warn!(
+                        transaction_version = txn_version,
+                        "Transaction data doesn't exist"
+                    );
+                    PROCESSOR_UNKNOWN_TYPE_COUNT
+                        .with_label_values(&["FungibleAssetProcessor"])
+                        .inc();
+                    return (vec![], vec![], vec![], AHashMap::new(), AHashMap::new());
                 }
-            }
-            // Loop 2: Get the metadata relevant to parse v1 coin and v2 fungible asset.
-            // As an optimization, we also handle v1 balances in the process
-            for (index, wsc) in transaction_info.changes.iter().enumerate() {
-                if let Change::WriteResource(write_resource) = wsc.change.as_ref().unwrap() {
-                    if let Some((balance, current_balance, event_to_coin)) =
-                        RawFungibleAssetBalance::get_v1_from_write_resource(
-                            write_resource,
-                            index as i64,
-                            txn_version,
-                            txn_timestamp,
-                        )
-                        .unwrap()
-                    {
-                        fungible_asset_balances.push(balance);
-                        current_fungible_asset_balances
-                            .insert(current_balance.storage_id.clone(), current_balance.clone());
-                        event_to_v1_coin_type.extend(event_to_coin);
+                let txn_data = txn.txn_data.as_ref().unwrap();
+                let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
+                // Stored to the second
+                let txn_timestamp =
+                    parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version)
+                        .trunc_subsecs(0);
+                let txn_epoch = txn.epoch as i64;
+
+                let default = vec![];
+                let (events, user_request, entry_function_id_str) = match txn_data {
+                    TxnData::BlockMetadata(tx_inner) => (&tx_inner.events, None, None),
+                    TxnData::Validator(tx_inner) => (&tx_inner.events, None, None),
+                    TxnData::Genesis(tx_inner) => (&tx_inner.events, None, None),
+                    TxnData::User(tx_inner) => {
+                        let user_request = tx_inner
+                            .request
+                            .as_ref()
+                            .expect("Sends is not present in user txn");
+                        let entry_function_id_str =
+                            get_entry_function_from_user_request(user_request);
+                        (&tx_inner.events, Some(user_request), entry_function_id_str)
+                    },
+                    _ => (&default, None, None),
+                };
+
+                // This is because v1 events (deposit/withdraw) don't have coin type so the only way is to match
+                // the event to the resource using the event guid
+                let mut event_to_v1_coin_type: EventToCoinType = AHashMap::new();
+
+                // Loop 1: to get all object addresses
+                // Need to do a first pass to get all the object addresses and insert them into the helper
+                for wsc in transaction_info.changes.iter() {
+                    if let Change::WriteResource(wr) = wsc.change.as_ref().unwrap() {
+                        if let Some(object) = ObjectWithMetadata::from_write_resource(wr).unwrap() {
+                            fungible_asset_object_helper.insert(
+                                standardize_address(&wr.address.to_string()),
+                                ObjectAggregatedData {
+                                    object,
+                                    ..ObjectAggregatedData::default()
+                                },
+                            );
+                        }
                     }
-                    // Fill the v2 fungible_asset_object_helper. This is used to track which objects exist at each object address.
-                    // The data will be used to reconstruct the full data in Loop 4.
-                    let address = standardize_address(&write_resource.address.to_string());
-                    if let Some(aggregated_data) = fungible_asset_object_helper.get_mut(&address) {
-                        if let Some(v2_fungible_asset_resource) =
-                            V2FungibleAssetResource::from_write_resource(write_resource).unwrap()
+                }
+                // Loop 2: Get the metadata relevant to parse v1 coin and v2 fungible asset.
+                // As an optimization, we also handle v1 balances in the process
+                for (index, wsc) in transaction_info.changes.iter().enumerate() {
+                    if let Change::WriteResource(write_resource) = wsc.change.as_ref().unwrap() {
+                        if let Some((balance, current_balance, event_to_coin)) =
+                            RawFungibleAssetBalance::get_v1_from_write_resource(
+                                write_resource,
+                                index as i64,
+                                txn_version,
+                                txn_timestamp,
+                            )
+                            .unwrap()
                         {
-                            match v2_fungible_asset_resource {
-                                V2FungibleAssetResource::FungibleAssetMetadata(
-                                    fungible_asset_metadata,
-                                ) => {
-                                    aggregated_data.fungible_asset_metadata =
-                                        Some(fungible_asset_metadata);
-                                },
-                                V2FungibleAssetResource::FungibleAssetStore(
-                                    fungible_asset_store,
-                                ) => {
-                                    aggregated_data.fungible_asset_store =
-                                        Some(fungible_asset_store);
-                                },
-                                V2FungibleAssetResource::FungibleAssetSupply(
-                                    fungible_asset_supply,
-                                ) => {
-                                    aggregated_data.fungible_asset_supply =
-                                        Some(fungible_asset_supply);
-                                },
-                                V2FungibleAssetResource::ConcurrentFungibleAssetSupply(
-                                    concurrent_fungible_asset_supply,
-                                ) => {
-                                    aggregated_data.concurrent_fungible_asset_supply =
-                                        Some(concurrent_fungible_asset_supply);
-                                },
-                                V2FungibleAssetResource::ConcurrentFungibleAssetBalance(
-                                    concurrent_fungible_asset_balance,
-                                ) => {
-                                    aggregated_data.concurrent_fungible_asset_balance =
-                                        Some(concurrent_fungible_asset_balance);
-                                },
+                            fungible_asset_balances.push(balance);
+                            current_fungible_asset_balances.insert(
+                                current_balance.storage_id.clone(),
+                                current_balance.clone(),
+                            );
+                            event_to_v1_coin_type.extend(event_to_coin);
+                        }
+                        // Fill the v2 fungible_asset_object_helper. This is used to track which objects exist at each object address.
+                        // The data will be used to reconstruct the full data in Loop 4.
+                        let address = standardize_address(&write_resource.address.to_string());
+                        if let Some(aggregated_data) =
+                            fungible_asset_object_helper.get_mut(&address)
+                        {
+                            if let Some(v2_fungible_asset_resource) =
+                                V2FungibleAssetResource::from_write_resource(write_resource)
+                                    .parse_context(
+                                        txn_version,
+                                        index as i64,
+                                        &write_resource.type_str,
+                                    )
+                                    .unwrap()
+                            {
+                                match v2_fungible_asset_resource {
+                                    V2FungibleAssetResource::FungibleAssetMetadata(
+                                        fungible_asset_metadata,
+                                    ) => {
+                                        aggregated_data.fungible_asset_metadata =
+                                            Some(fungible_asset_metadata);
+                                    },
+                                    V2FungibleAssetResource::FungibleAssetStore(
+                                        fungible_asset_store,
+                                    ) => {
+                                        aggregated_data.fungible_asset_store =
+                                            Some(fungible_asset_store);
+                                    },
+                                    V2FungibleAssetResource::FungibleAssetSupply(
+                                        fungible_asset_supply,
+                                    ) => {
+                                        aggregated_data.fungible_asset_supply =
+                                            Some(fungible_asset_supply);
+                                    },
+                                    V2FungibleAssetResource::ConcurrentFungibleAssetSupply(
+                                        concurrent_fungible_asset_supply,
+                                    ) => {
+                                        aggregated_data.concurrent_fungible_asset_supply =
+                                            Some(concurrent_fungible_asset_supply);
+                                    },
+                                    V2FungibleAssetResource::ConcurrentFungibleAssetBalance(
+                                        concurrent_fungible_asset_balance,
+                                    ) => {
+                                        aggregated_data.concurrent_fungible_asset_balance =
+                                            Some(concurrent_fungible_asset_balance);
+                                    },
+                                }
                             }
                         }
-                    }
-                } else if let Change::DeleteResource(delete_resource) = wsc.change.as_ref().unwrap()
-                {
-                    if let Some((balance, current_balance, event_to_coin)) =
-                        RawFungibleAssetBalance::get_v1_from_delete_resource(
-                            delete_resource,
-                            index as i64,
-                            txn_version,
-                            txn_timestamp,
-                        )
-                        .unwrap()
+                    } else if let Change::DeleteResource(delete_resource) =
+                        wsc.change.as_ref().unwrap()
                     {
-                        fungible_asset_balances.push(balance);
-                        current_fungible_asset_balances
-                            .insert(current_balance.storage_id.clone(), current_balance.clone());
-                        event_to_v1_coin_type.extend(event_to_coin);
+                        if let Some((balance, current_balance, event_to_coin)) =
+                            RawFungibleAssetBalance::get_v1_from_delete_resource(
+                                delete_resource,
+                                index as i64,
+                                txn_version,
+                                txn_timestamp,
+                            )
+                            .unwrap()
+                        {
+                            fungible_asset_balances.push(balance);
+                            current_fungible_asset_balances.insert(
+                                current_balance.storage_id.clone(),
+                                current_balance.clone(),
+                            );
+                            event_to_v1_coin_type.extend(event_to_coin);
+                        }
                     }
                 }
-            }
-
-            // The artificial gas event, only need for v1
-            if let Some(req) = user_request {
-                let fee_statement = events.iter().find_map(|event| {
-                    let event_type = event.type_str.as_str();
-                    FeeStatement::from_event(event_type, &event.data, txn_version)
-                });
-                let gas_event = RawFungibleAssetActivity::get_gas_event(
-                    transaction_info,
-                    req,
-                    &entry_function_id_str,
-                    txn_version,
-                    txn_timestamp,
-                    block_height,
-                    fee_statement,
-                );
-                fungible_asset_activities.push(gas_event);
-            }
-
-            // Loop 3 to handle events and collect additional metadata from events for v2
-            for (index, event) in events.iter().enumerate() {
-                if let Some(v1_activity) = RawFungibleAssetActivity::get_v1_from_event(
-                    event,
-                    txn_version,
-                    block_height,
-                    txn_timestamp,
-                    &entry_function_id_str,
-                    &event_to_v1_coin_type,
-                    index as i64,
-                )
-                .unwrap_or_else(|e| {
-                    tracing::error!(
+
+                // The artificial gas event, only need for v1
+                if let Some(req) = user_request {
+                    let fee_statement = events.iter().find_map(|event| {
+                        let event_type = event.type_str.as_str();
+                        FeeStatement::from_event(event_type, &event.data, txn_version)
+                    });
+                    let gas_event = RawFungibleAssetActivity::get_gas_event(
+                        transaction_info,
+                        req,
+                        &entry_function_id_str,
+                        txn_version,
+                        txn_timestamp,
+                        block_height,
+                        fee_statement,
+                    );
+                    fungible_asset_activities.push(gas_event);
+                }
+
+                // Loop 3 to handle events and collect additional metadata from events for v2
+                for (index, event) in events.iter().enumerate() {
+                    if let Some(v1_activity) = RawFungibleAssetActivity::get_v1_from_event(
+                        event,
+                        txn_version,
+                        block_height,
+                        txn_timestamp,
+                        &entry_function_id_str,
+                        &event_to_v1_coin_type,
+                        index as i64,
+                    )
+                    .unwrap_or_else(|e| {
+                        tracing::error!(
                         transaction_version = txn_version,
                         index = index,
                         error = ?e,
                         "[Parser] error parsing fungible asset activity v1");
-                    panic!("[Parser] error parsing fungible asset activity v1");
-                }) {
-                    fungible_asset_activities.push(v1_activity);
-                }
-                if let Some(v2_activity) = RawFungibleAssetActivity::get_v2_from_event(
-                    event,
-                    txn_version,
-                    block_height,
-                    txn_timestamp,
-                    index as i64,
-                    &entry_function_id_str,
-                    &fungible_asset_object_helper,
-                )
-                .unwrap_or_else(|e| {
-                    tracing::error!(
+                        panic!("[Parser] error parsing fungible asset activity v1");
+                    }) {
+                        fungible_asset_activities.push(v1_activity);
+                    }
+                    if let Some(v2_activity) = RawFungibleAssetActivity::get_v2_from_event(
+                        event,
+                        txn_version,
+                        block_height,
+                        txn_timestamp,
+                        index as i64,
+                        &entry_function_id_str,
+                        &fungible_asset_object_helper,
+                    )
+                    .unwrap_or_else(|e| {
+                        tracing::error!(
                         transaction_version = txn_version,
                         index = index,
                         error = ?e,
                         "[Parser] error parsing fungible asset activity v2");
-                    panic!("[Parser] error parsing fungible asset activity v2");
-                }) {
-                    fungible_asset_activities.push(v2_activity);
+                        panic!("[Parser] error parsing fungible asset activity v2");
+                    }) {
+                        fungible_asset_activities.push(v2_activity);
+                    }
                 }
-            }
-
-            // Loop 4 to handle write set changes for metadata, balance, and v1 supply
-            for (index, wsc) in transaction_info.changes.iter().enumerate() {
-                match wsc.change.as_ref().unwrap() {
-                    Change::WriteResource(write_resource) => {
-                        if let Some(fa_metadata) =
-                            RawFungibleAssetMetadataModel::get_v1_from_write_resource(
-                                write_resource,
-                                index as i64,
-                                txn_version,
-                                txn_timestamp,
-                            )
-                            .unwrap_or_else(|e| {
-                                tracing::error!(
+
+                // Loop 4 to handle write set changes for metadata, balance, and v1 supply
+                for (index, wsc) in transaction_info.changes.iter().enumerate() {
+                    match wsc.change.as_ref().unwrap() {
+                        Change::WriteResource(write_resource) => {
+                            if let Some(fa_metadata) =
+                                RawFungibleAssetMetadataModel::get_v1_from_write_resource(
+                                    write_resource,
+                                    index as i64,
+                                    txn_version,
+                                    txn_timestamp,
+                                )
+                                .unwrap_or_else(|e| {
+                                    tracing::error!(
                                     transaction_version = txn_version,
                                     index = index,
                                     error = ?e,
                                     "[Parser] error parsing fungible metadata v1");
-                                panic!("[Parser] error parsing fungible metadata v1");
-                            })
-                        {
-                            fungible_asset_metadata
-                                .insert(fa_metadata.asset_type.clone(), fa_metadata);
-                        }
-                        if let Some(fa_metadata) =
-                            RawFungibleAssetMetadataModel::get_v2_from_write_resource(
-                                write_resource,
-                                txn_version,
-                                txn_timestamp,
-                                &fungible_asset_object_helper,
-                            )
-                            .unwrap_or_else(|e| {
-                                tracing::error!(
+                                    panic!("[Parser] error parsing fungible metadata v1");
+                                })
+                            {
+                                fungible_asset_metadata
+                                    .insert(fa_metadata.asset_type.clone(), fa_metadata);
+                            }
+                            if let Some(fa_metadata) =
+                                RawFungibleAssetMetadataModel::get_v2_from_write_resource(
+                                    write_resource,
+                                    txn_version,
+                                    txn_timestamp,
+                                    &fungible_asset_object_helper,
+                                )
+                                .unwrap_or_else(|e| {
+                                    tracing::error!(
                                     transaction_version = txn_version,
                                     index = index,
                                     error = ?e,
                                     "[Parser] error parsing fungible metadata v2");
-                                panic!("[Parser] error parsing fungible metadata v2");
-                            })
-                        {
-                            fungible_asset_metadata
-                                .insert(fa_metadata.asset_type.clone(), fa_metadata);
-                        }
-                        if let Some((balance, curr_balance)) =
-                            RawFungibleAssetBalance::get_v2_from_write_resource(
-                                write_resource,
-                                index as i64,
-                                txn_version,
-                                txn_timestamp,
-                                &fungible_asset_object_helper,
-                            )
-                            .unwrap_or_else(|e| {
-                                tracing::error!(
+                                    panic!("[Parser] error parsing fungible metadata v2");
+                                })
+                            {
+                                fungible_asset_metadata
+                                    .insert(fa_metadata.asset_type.clone(), fa_metadata);
+                            }
+                            if let Some((balance, curr_balance)) =
+                                RawFungibleAssetBalance::get_v2_from_write_resource(
+                                    write_resource,
+                                    index as i64,
+                                    txn_version,
+                                    txn_timestamp,
+                                    &fungible_asset_object_helper,
+                                )
+                                .unwrap_or_else(|e| {
+                                    tracing::error!(
                                     transaction_version = txn_version,
                                     index = index,
                                     error = ?e,
                                     "[Parser] error parsing fungible balance v2");
-                                panic!("[Parser] error parsing fungible balance v2");
-                            })
-                        {
-                            fungible_asset_balances.push(balance);
-                            current_fungible_asset_balances
-                                .insert(curr_balance.storage_id.clone(), curr_balance);
-                        }
-                    },
-                    Change::WriteTableItem(table_item) => {
-                        if let Some(coin_supply) = CoinSupply::from_write_table_item(
-                            table_item,
-                            txn_version,
-                            txn_timestamp,
-                            txn_epoch,
-                        )
-                        .unwrap()
-                        {
-                            all_coin_supply.push(coin_supply);
-                        }
-                    },
-                    _ => {},
+                                    panic!("[Parser] error parsing fungible balance v2");
+                                })
+                            {
+                                fungible_asset_balances.push(balance);
+                                current_fungible_asset_balances
+                                    .insert(curr_balance.storage_id.clone(), curr_balance);
+                            }
+                        },
+                        Change::WriteTableItem(table_item) => {
+                            if let Some(coin_supply) = CoinSupply::from_write_table_item(
+                                table_item,
+                                txn_version,
+                                txn_timestamp,
+                                txn_epoch,
+                            )
+                            .unwrap()
+                            {
+                                all_coin_supply.push(coin_supply);
+                            }
+                        },
+                        _ => {},
+                    }
                 }
-            }
-            (
-                fungible_asset_activities,
-                fungible_asset_balances,
-                all_coin_supply,
-                current_fungible_asset_balances,
-                fungible_asset_metadata,
-            )
-        })
-        .collect();
+                (
+                    fungible_asset_activities,
+                    fungible_asset_balances,
+                    all_coin_supply,
+                    current_fungible_asset_balances,
+                    fungible_asset_metadata,
+                )
+            })
+            .collect()
+    });
 
+    let mut fungible_asset_metadata_writes = vec![];
     for (faa, fab, acs, cfab, fam) in data {
         fungible_asset_activities.extend(faa);
         fungible_asset_balances.extend(fab);
         all_coin_supply.extend(acs);
         current_fungible_asset_balances.extend(cfab);
+        // Transactions are still in version order here
+        fungible_asset_metadata_writes.extend(fam.values().cloned());
         fungible_asset_metadata.extend(fam);
     }
 
@@ -835,6 +1276,18 @@ pub async fn parse_v2_coin(
     fungible_asset_metadata.sort_by(|a, b| a.asset_type.cmp(&b.asset_type));
     current_fungible_asset_balances.sort_by(|a, b| a.storage_id.cmp(&b.storage_id));
 
+    for owner_address in fungible_asset_activities
+        .iter()
+        .filter_map(|activity| activity.owner_address.as_deref())
+        .chain(
+            current_fungible_asset_balances
+                .iter()
+                .map(|balance| balance.owner_address.as_str()),
+        )
+    {
+        debug_assert_standardized_address(owner_address);
+    }
+
     // Process the unified balance
     let current_unified_fungible_asset_balances = current_fungible_asset_balances
         .iter()
@@ -847,5 +1300,99 @@ pub async fn parse_v2_coin(
         current_fungible_asset_balances,
         current_unified_fungible_asset_balances,
         all_coin_supply,
+        fungible_asset_metadata_writes,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bigdecimal::BigDecimal;
+
+    fn activity(transaction_version: i64, event_index: i64) -> RawFungibleAssetActivity {
+        RawFungibleAssetActivity {
+            transaction_version,
+            event_index,
+            owner_address: Some("0x1".to_string()),
+            storage_id: "0x2".to_string(),
+            asset_type: Some("0xa".to_string()),
+            is_frozen: None,
+            amount: Some(BigDecimal::from(100)),
+            event_type: "0x1::fungible_asset::Deposit".to_string(),
+            is_gas_fee: false,
+            gas_fee_payer_address: None,
+            is_transaction_success: true,
+            entry_function_id_str: None,
+            block_height: 1,
+            token_standard: "v2".to_string(),
+            transaction_timestamp: NaiveDateTime::default(),
+            storage_refund_amount: BigDecimal::from(0),
+        }
+    }
+
+    fn balance(transaction_version: i64, write_set_change_index: i64) -> RawFungibleAssetBalance {
+        RawFungibleAssetBalance {
+            transaction_version,
+            write_set_change_index,
+            storage_id: "0x2".to_string(),
+            owner_address: "0x1".to_string(),
+            asset_type: "0xa".to_string(),
+            is_primary: true,
+            is_frozen: false,
+            amount: BigDecimal::from(100),
+            transaction_timestamp: NaiveDateTime::default(),
+            token_standard: "v2".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parquet_sink_sends_both_tables() {
+        let (activities_sender, activities_receiver) = kanal::bounded_async(1);
+        let (balances_sender, balances_receiver) = kanal::bounded_async(1);
+        let parquet_sink = FungibleAssetParquetSink {
+            fungible_asset_activities_sender: activities_sender,
+            fungible_asset_balances_sender: balances_sender,
+        };
+
+        let activities = vec![activity(1, 0), activity(1, 1), activity(2, 0)];
+        let balances = vec![balance(1, 0), balance(3, 0)];
+        let transaction_version_to_struct_count =
+            parquet_sink.send(activities, balances).await.unwrap();
+
+        assert_eq!(transaction_version_to_struct_count.len(), 3);
+        assert_eq!(transaction_version_to_struct_count[&1], 3);
+        assert_eq!(transaction_version_to_struct_count[&2], 1);
+        assert_eq!(transaction_version_to_struct_count[&3], 1);
+        // Each table carries its own counts, which its uploads are checked against
+        let activities = activities_receiver.recv().await.unwrap();
+        assert_eq!(
+            activities.transaction_version_to_struct_count,
+            [(1, 2), (2, 1)].into_iter().collect()
+        );
+        assert_eq!(
+            activities
+                .data
+                .iter()
+                .map(|activity| (activity.txn_version, activity.event_index))
+                .collect::<Vec<_>>(),
+            vec![(1, 0), (1, 1), (2, 0)]
+        );
+        let balances = balances_receiver.recv().await.unwrap();
+        assert_eq!(
+            balances.transaction_version_to_struct_count,
+            [(1, 1), (3, 1)].into_iter().collect()
+        );
+        assert_eq!(
+            balances
+                .data
+                .iter()
+                .map(|balance| balance.txn_version)
+                .collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(
+            FungibleAssetParquetSink::table_names(),
+            "fungible_asset_activities,fungible_asset_balances"
+        );
+    }
+}
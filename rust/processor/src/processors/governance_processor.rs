@@ -0,0 +1,215 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
+use crate::{
+    db::postgres::models::governance_models::governance_proposals::{
+        GovernanceChanges, GovernanceProposal, GovernanceProposalResolution, GovernanceVote,
+    },
+    gap_detectors::ProcessingResult,
+    schema,
+    utils::database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+};
+use ahash::AHashMap;
+use anyhow::bail;
+use aptos_protos::transaction::v1::Transaction;
+use async_trait::async_trait;
+use diesel::{
+    pg::{upsert::excluded, Pg},
+    query_builder::QueryFragment,
+    ExpressionMethods,
+};
+use std::{fmt::Debug, sync::Arc};
+use tracing::error;
+
+/// Indexes on-chain governance: proposals as created, every vote, and the final tally of the
+/// proposals that get resolved.
+pub struct GovernanceProcessor {
+    connection_pool: ArcDbPool,
+    per_table_chunk_sizes: AHashMap<String, usize>,
+}
+
+impl GovernanceProcessor {
+    pub fn new(connection_pool: ArcDbPool, per_table_chunk_sizes: AHashMap<String, usize>) -> Self {
+        Self {
+            connection_pool,
+            per_table_chunk_sizes,
+        }
+    }
+}
+
+impl Debug for GovernanceProcessor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let state = &self.connection_pool.state();
+        write!(
+            f,
+            "GovernanceProcessor {{ connections: {:?}  idle_connections: {:?} }}",
+            state.connections, state.idle_connections
+        )
+    }
+}
+
+async fn insert_to_db(
+    conn: ArcDbPool,
+    name: &'static str,
+    start_version: u64,
+    end_version: u64,
+    changes: &GovernanceChanges,
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<(), diesel::result::Error> {
+    tracing::trace!(
+        name = name,
+        start_version = start_version,
+        end_version = end_version,
+        "Inserting to db",
+    );
+    let proposals = execute_in_chunks(
+        conn.clone(),
+        insert_governance_proposals_query,
+        &changes.proposals,
+        get_config_table_chunk_size::<GovernanceProposal>(
+            "governance_proposals",
+            per_table_chunk_sizes,
+        ),
+    );
+    let votes = execute_in_chunks(
+        conn.clone(),
+        insert_governance_votes_query,
+        &changes.votes,
+        get_config_table_chunk_size::<GovernanceVote>("governance_votes", per_table_chunk_sizes),
+    );
+    let resolutions = execute_in_chunks(
+        conn,
+        insert_governance_proposal_resolutions_query,
+        &changes.resolutions,
+        get_config_table_chunk_size::<GovernanceProposalResolution>(
+            "governance_proposal_resolutions",
+            per_table_chunk_sizes,
+        ),
+    );
+    let (proposals_res, votes_res, resolutions_res) = tokio::join!(proposals, votes, resolutions);
+    for res in [proposals_res, votes_res, resolutions_res] {
+        res?;
+    }
+    Ok(())
+}
+
+pub fn insert_governance_proposals_query(
+    items_to_insert: Vec<GovernanceProposal>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::governance_proposals::dsl::*;
+
+    (
+        diesel::insert_into(schema::governance_proposals::table)
+            .values(items_to_insert)
+            .on_conflict(proposal_id)
+            .do_nothing(),
+        None,
+    )
+}
+
+pub fn insert_governance_votes_query(
+    items_to_insert: Vec<GovernanceVote>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::governance_votes::dsl::*;
+
+    (
+        diesel::insert_into(schema::governance_votes::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_nothing(),
+        None,
+    )
+}
+
+/// Keeps the last resolution of multi-step proposals, whatever order the steps are processed in
+pub fn insert_governance_proposal_resolutions_query(
+    items_to_insert: Vec<GovernanceProposalResolution>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::governance_proposal_resolutions::dsl::*;
+
+    (
+        diesel::insert_into(schema::governance_proposal_resolutions::table)
+            .values(items_to_insert)
+            .on_conflict(proposal_id)
+            .do_update()
+            .set((
+                yes_votes.eq(excluded(yes_votes)),
+                no_votes.eq(excluded(no_votes)),
+                resolved_early.eq(excluded(resolved_early)),
+                transaction_version.eq(excluded(transaction_version)),
+                transaction_timestamp.eq(excluded(transaction_timestamp)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        Some(" WHERE governance_proposal_resolutions.transaction_version <= excluded.transaction_version "),
+    )
+}
+
+#[async_trait]
+impl ProcessorTrait for GovernanceProcessor {
+    fn name(&self) -> &'static str {
+        ProcessorName::GovernanceProcessor.into()
+    }
+
+    async fn process_transactions(
+        &self,
+        transactions: Arc<Vec<Transaction>>,
+        start_version: u64,
+        end_version: u64,
+        _db_chain_id: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        let processing_start = std::time::Instant::now();
+        let last_transaction_timestamp = transactions.last().unwrap().timestamp;
+
+        let changes = GovernanceChanges::from_transactions(&transactions)?;
+
+        let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
+        let db_insertion_start = std::time::Instant::now();
+        let tx_result = insert_to_db(
+            self.get_pool(),
+            self.name(),
+            start_version,
+            end_version,
+            &changes,
+            &self.per_table_chunk_sizes,
+        )
+        .await;
+        let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
+        match tx_result {
+            Ok(_) => Ok(ProcessingResult::DefaultProcessingResult(
+                DefaultProcessingResult {
+                    start_version,
+                    end_version,
+                    processing_duration_in_secs,
+                    db_insertion_duration_in_secs,
+                    last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
+                },
+            )),
+            Err(e) => {
+                error!(
+                    start_version = start_version,
+                    end_version = end_version,
+                    processor_name = self.name(),
+                    error = ?e,
+                    "[Parser] Error inserting transactions to db",
+                );
+                bail!(e)
+            },
+        }
+    }
+
+    fn connection_pool(&self) -> &ArcDbPool {
+        &self.connection_pool
+    }
+}
@@ -4,11 +4,16 @@
 // Note: For enum_dispatch to work nicely, it is easiest to have the trait and the enum
 // in the same file (ProcessorTrait and Processor).
 
+pub mod account_sequence_number_processor;
 pub mod account_transactions_processor;
+pub mod allowance_processor;
 pub mod ans_processor;
+pub mod block_gas_stats_processor;
+pub mod custom_processor;
 pub mod default_processor;
 pub mod events_processor;
 pub mod fungible_asset_processor;
+pub mod governance_processor;
 pub mod monitoring_processor;
 pub mod nft_metadata_processor;
 pub mod objects_processor;
@@ -19,20 +24,26 @@ pub mod transaction_metadata_processor;
 pub mod user_transaction_processor;
 
 use self::{
+    account_sequence_number_processor::AccountSequenceNumberProcessor,
     account_transactions_processor::AccountTransactionsProcessor,
+    allowance_processor::{AllowanceProcessor, AllowanceProcessorConfig},
     ans_processor::{AnsProcessor, AnsProcessorConfig},
-    default_processor::DefaultProcessor,
-    events_processor::EventsProcessor,
-    fungible_asset_processor::FungibleAssetProcessor,
+    block_gas_stats_processor::BlockGasStatsProcessor,
+    custom_processor::{CustomProcessor, CustomProcessorConfig},
+    default_processor::{DefaultProcessor, DefaultProcessorConfig},
+    events_processor::{EventsProcessor, EventsProcessorConfig},
+    fungible_asset_processor::{FungibleAssetProcessor, FungibleAssetProcessorConfig},
+    governance_processor::GovernanceProcessor,
     monitoring_processor::MonitoringProcessor,
     nft_metadata_processor::{NftMetadataProcessor, NftMetadataProcessorConfig},
     objects_processor::{ObjectsProcessor, ObjectsProcessorConfig},
     stake_processor::{StakeProcessor, StakeProcessorConfig},
     token_v2_processor::{TokenV2Processor, TokenV2ProcessorConfig},
     transaction_metadata_processor::TransactionMetadataProcessor,
-    user_transaction_processor::UserTransactionProcessor,
+    user_transaction_processor::{UserTransactionProcessor, UserTransactionProcessorConfig},
 };
 use crate::{
+    bq_analytics::generic_parquet_processor::ParquetCompressionConfig,
     db::postgres::models::processor_status::ProcessorStatus,
     gap_detectors::ProcessingResult,
     processors::parquet_processors::{
@@ -56,16 +67,30 @@ use crate::{
     schema::processor_status,
     utils::{
         counters::{GOT_CONNECTION_COUNT, UNABLE_TO_GET_CONNECTION_COUNT},
-        database::{execute_with_better_error, ArcDbPool, DbPoolConnection},
+        database::{
+            execute_with_better_error, execute_with_better_error_conn, processor_status_key,
+            ArcDbPool, DbPoolConnection,
+        },
+        progress_lease::LeasedProgressStorage,
+        schema_version::{
+            SchemaVersion, BASE_SCHEMA_VERSION, TOKEN_V2_COLLECTION_STATS_SCHEMA_VERSION,
+        },
+        transaction_decoder::TransactionExtensions,
+        transaction_fields::TransactionFields,
         util::parse_timestamp,
     },
 };
 use aptos_protos::transaction::v1::Transaction as ProtoTransaction;
 use async_trait::async_trait;
-use diesel::{pg::upsert::excluded, ExpressionMethods};
+use diesel::{
+    pg::{upsert::excluded, Pg},
+    query_builder::QueryFragment,
+    ExpressionMethods,
+};
+use diesel_async::{scoped_futures::ScopedFutureExt, AsyncConnection};
 use enum_dispatch::enum_dispatch;
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct DefaultProcessingResult {
@@ -74,6 +99,12 @@ pub struct DefaultProcessingResult {
     pub last_transaction_timestamp: Option<aptos_protos::util::timestamp::Timestamp>,
     pub processing_duration_in_secs: f64,
     pub db_insertion_duration_in_secs: f64,
+    /// Events processed in the batch, for processors that count them
+    #[serde(default)]
+    pub num_events: Option<u64>,
+    /// Write set changes processed in the batch, for processors that count them
+    #[serde(default)]
+    pub num_write_set_changes: Option<u64>,
 }
 
 /// Base trait for all processors
@@ -82,15 +113,30 @@ pub struct DefaultProcessingResult {
 pub trait ProcessorTrait: Send + Sync + Debug {
     fn name(&self) -> &'static str;
 
-    /// Process all transactions including writing to the database
+    /// Process all transactions including writing to the database. The batch is shared, so that
+    /// it can be retried without copying it.
     async fn process_transactions(
         &self,
-        transactions: Vec<ProtoTransaction>,
+        transactions: Arc<Vec<ProtoTransaction>>,
         start_version: u64,
         end_version: u64,
         db_chain_id: Option<u64>,
     ) -> anyhow::Result<ProcessingResult>;
 
+    /// Same as `process_transactions`, with what the registered `TransactionDecoder` kept from
+    /// the transactions. Only processors that read the extensions need to implement it.
+    async fn process_transactions_with_extensions(
+        &self,
+        transactions: Arc<Vec<ProtoTransaction>>,
+        _extensions: Arc<TransactionExtensions>,
+        start_version: u64,
+        end_version: u64,
+        db_chain_id: Option<u64>,
+    ) -> anyhow::Result<ProcessingResult> {
+        self.process_transactions(transactions, start_version, end_version, db_chain_id)
+            .await
+    }
+
     /// Gets a reference to the connection pool
     /// This is used by the `get_conn()` helper below
     fn connection_pool(&self) -> &ArcDbPool;
@@ -130,38 +176,65 @@ pub trait ProcessorTrait: Send + Sync + Debug {
     }
 
     /// Store last processed version from database. We can assume that all previously processed
-    /// versions are successful because any gap would cause the processor to panic
+    /// versions are successful because any gap would cause the processor to panic. With a
+    /// progress lease, it's only written while the lease is still ours, see
+    /// `LeasedProgressStorage::lock_for_commit`.
     async fn update_last_processed_version(
         &self,
         version: u64,
         last_transaction_timestamp: Option<aptos_protos::util::timestamp::Timestamp>,
+        leased_progress: Option<&LeasedProgressStorage>,
     ) -> anyhow::Result<()> {
         let timestamp = last_transaction_timestamp.map(|t| parse_timestamp(&t, version as i64));
         let status = ProcessorStatus {
-            processor: self.name().to_string(),
+            processor: processor_status_key(self.name()),
             last_success_version: version as i64,
             last_transaction_timestamp: timestamp,
         };
-        execute_with_better_error(
-            self.get_pool(),
-            diesel::insert_into(processor_status::table)
-                .values(&status)
-                .on_conflict(processor_status::processor)
-                .do_update()
-                .set((
-                    processor_status::last_success_version
-                        .eq(excluded(processor_status::last_success_version)),
-                    processor_status::last_updated.eq(excluded(processor_status::last_updated)),
-                    processor_status::last_transaction_timestamp
-                        .eq(excluded(processor_status::last_transaction_timestamp)),
-                )),
-            Some(" WHERE processor_status.last_success_version <= EXCLUDED.last_success_version "),
-        )
-        .await?;
-        Ok(())
+        let Some(leased_progress) = leased_progress else {
+            let (query, additional_where_clause) = update_processor_status_query(status);
+            execute_with_better_error(self.get_pool(), query, additional_where_clause).await?;
+            return Ok(());
+        };
+        let processor_name = self.name();
+        let mut conn = self.get_conn().await;
+        conn.transaction(|conn| {
+            async move {
+                leased_progress
+                    .lock_for_commit(conn, processor_name)
+                    .await?;
+                let (query, additional_where_clause) = update_processor_status_query(status);
+                execute_with_better_error_conn(conn, query, additional_where_clause).await?;
+                Ok::<_, anyhow::Error>(())
+            }
+            .scope_boxed()
+        })
+        .await
     }
 }
 
+fn update_processor_status_query(
+    status: ProcessorStatus,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    (
+        diesel::insert_into(processor_status::table)
+            .values(status)
+            .on_conflict(processor_status::processor)
+            .do_update()
+            .set((
+                processor_status::last_success_version
+                    .eq(excluded(processor_status::last_success_version)),
+                processor_status::last_updated.eq(excluded(processor_status::last_updated)),
+                processor_status::last_transaction_timestamp
+                    .eq(excluded(processor_status::last_transaction_timestamp)),
+            )),
+        Some(" WHERE processor_status.last_success_version <= EXCLUDED.last_success_version "),
+    )
+}
+
 /// This enum captures the configs for all the different processors that are defined.
 ///
 /// The configs for each processor should only contain configuration specific to that
@@ -195,18 +268,23 @@ pub trait ProcessorTrait: Send + Sync + Debug {
     strum(serialize_all = "snake_case")
 )]
 pub enum ProcessorConfig {
+    AccountSequenceNumberProcessor,
     AccountTransactionsProcessor,
+    AllowanceProcessor(AllowanceProcessorConfig),
     AnsProcessor(AnsProcessorConfig),
-    DefaultProcessor,
-    EventsProcessor,
-    FungibleAssetProcessor,
+    BlockGasStatsProcessor,
+    CustomProcessor(CustomProcessorConfig),
+    DefaultProcessor(DefaultProcessorConfig),
+    EventsProcessor(EventsProcessorConfig),
+    FungibleAssetProcessor(FungibleAssetProcessorConfig),
+    GovernanceProcessor,
     MonitoringProcessor,
     NftMetadataProcessor(NftMetadataProcessorConfig),
     ObjectsProcessor(ObjectsProcessorConfig),
     StakeProcessor(StakeProcessorConfig),
     TokenV2Processor(TokenV2ProcessorConfig),
     TransactionMetadataProcessor,
-    UserTransactionProcessor,
+    UserTransactionProcessor(UserTransactionProcessorConfig),
     ParquetDefaultProcessor(ParquetDefaultProcessorConfig),
     ParquetFungibleAssetActivitiesProcessor(ParquetFungibleAssetActivitiesProcessorConfig),
     ParquetFungibleAssetProcessor(ParquetFungibleAssetProcessorConfig),
@@ -221,10 +299,18 @@ impl ProcessorConfig {
     /// Get the name of the processor config as a static str. This is a convenience
     /// method to access the derived functionality implemented by strum::IntoStaticStr.
     pub fn name(&self) -> &'static str {
+        if let ProcessorConfig::CustomProcessor(config) = self {
+            return config.custom_type;
+        }
         self.into()
     }
 
+    /// Whether the processor writes to Parquet, either exclusively or alongside Postgres.
+    /// These processors report progress through the parquet gap detector.
     pub fn is_parquet_processor(&self) -> bool {
+        if let ProcessorConfig::FungibleAssetProcessor(config) = self {
+            return config.parquet_sink.is_some();
+        }
         matches!(
             self,
             ProcessorConfig::ParquetDefaultProcessor(_)
@@ -237,6 +323,47 @@ impl ProcessorConfig {
                 | ProcessorConfig::ParquetUserTransactionsProcessor(_)
         )
     }
+
+    /// Compression of the Parquet files the processor writes, if it writes any.
+    pub fn parquet_compression(&self) -> Option<ParquetCompressionConfig> {
+        match self {
+            ProcessorConfig::FungibleAssetProcessor(config) => config
+                .parquet_sink
+                .as_ref()
+                .map(|parquet_sink| parquet_sink.compression),
+            ProcessorConfig::ParquetDefaultProcessor(config) => Some(config.compression),
+            ProcessorConfig::ParquetFungibleAssetProcessor(config) => Some(config.compression),
+            ProcessorConfig::ParquetTransactionMetadataProcessor(config) => {
+                Some(config.compression)
+            },
+            ProcessorConfig::ParquetAnsProcessor(config) => Some(config.compression),
+            ProcessorConfig::ParquetEventsProcessor(config) => Some(config.compression),
+            ProcessorConfig::ParquetTokenV2Processor(config) => Some(config.compression),
+            ProcessorConfig::ParquetFungibleAssetActivitiesProcessor(config) => {
+                Some(config.compression)
+            },
+            ProcessorConfig::ParquetUserTransactionsProcessor(config) => Some(config.compression),
+            _ => None,
+        }
+    }
+
+    /// The parts of each transaction the processor reads, see `strip_unused_transaction_fields`.
+    pub fn transaction_fields(&self) -> TransactionFields {
+        match self {
+            ProcessorConfig::EventsProcessor(_) => TransactionFields::EVENTS,
+            _ => TransactionFields::all(),
+        }
+    }
+
+    /// Version of the tables the processor writes, checked against `processor_schema_versions`
+    /// at startup. Add a new one along with the migration whenever a change to its models needs
+    /// one.
+    pub fn schema_version(&self) -> SchemaVersion {
+        match self {
+            ProcessorConfig::TokenV2Processor(_) => TOKEN_V2_COLLECTION_STATS_SCHEMA_VERSION,
+            _ => BASE_SCHEMA_VERSION,
+        }
+    }
 }
 
 /// This enum contains all the processors defined in this crate.
@@ -259,11 +386,16 @@ impl ProcessorConfig {
     )
 )]
 pub enum Processor {
+    AccountSequenceNumberProcessor,
     AccountTransactionsProcessor,
+    AllowanceProcessor,
     AnsProcessor,
+    BlockGasStatsProcessor,
+    CustomProcessor,
     DefaultProcessor,
     EventsProcessor,
     FungibleAssetProcessor,
+    GovernanceProcessor,
     MonitoringProcessor,
     NftMetadataProcessor,
     ObjectsProcessor,
@@ -22,7 +22,7 @@ use crate::{
     gap_detectors::ProcessingResult,
     utils::{
         database::{ArcDbPool, DbPoolConnection},
-        util::{parse_timestamp, remove_null_bytes, standardize_address},
+        util::{parse_transaction_timestamp, remove_null_bytes, standardize_address},
     },
     IndexerGrpcProcessorConfig,
 };
@@ -35,6 +35,7 @@ use google_cloud_pubsub::client::{Client, ClientConfig};
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::Debug,
+    sync::Arc,
     time::{SystemTime, UNIX_EPOCH},
 };
 use tracing::{error, info};
@@ -99,7 +100,7 @@ impl ProcessorTrait for NftMetadataProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         db_chain_id: Option<u64>,
@@ -191,6 +192,8 @@ impl ProcessorTrait for NftMetadataProcessor {
                 processing_duration_in_secs,
                 db_insertion_duration_in_secs,
                 last_transaction_timestamp,
+                num_events: None,
+                num_write_set_changes: None,
             },
         ))
     }
@@ -237,7 +240,7 @@ async fn parse_v2_token(
 
     for txn in transactions {
         let txn_version = txn.version as i64;
-        let txn_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
+        let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
         let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
 
         let mut token_v2_metadata_helper: ObjectAggregatedDataMapping = AHashMap::new();
@@ -23,7 +23,7 @@ use crate::{
     utils::{
         database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool, DbContext},
         table_flags::TableFlags,
-        util::{parse_timestamp, standardize_address},
+        util::{parse_transaction_timestamp, standardize_address},
     },
     IndexerGrpcProcessorConfig,
 };
@@ -37,7 +37,7 @@ use diesel::{
     ExpressionMethods,
 };
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -170,7 +170,7 @@ impl ProcessorTrait for ObjectsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -188,7 +188,7 @@ impl ProcessorTrait for ObjectsProcessor {
         };
 
         let (mut raw_all_objects, raw_all_current_objects) =
-            process_objects(transactions, &mut Some(db_connection)).await;
+            process_objects(&transactions, &mut Some(db_connection)).await;
 
         if self.deprecated_tables.contains(TableFlags::OBJECTS) {
             raw_all_objects.clear();
@@ -223,6 +223,8 @@ impl ProcessorTrait for ObjectsProcessor {
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             )),
             Err(e) => {
@@ -244,7 +246,7 @@ impl ProcessorTrait for ObjectsProcessor {
 }
 
 pub async fn process_objects(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
     db_context: &mut Option<DbContext<'_>>,
 ) -> (Vec<RawObject>, Vec<RawCurrentObject>) {
     // Moving object handling here because we need a single object
@@ -253,7 +255,7 @@ pub async fn process_objects(
     let mut all_current_objects = AHashMap::new();
     let mut object_metadata_helper: ObjectAggregatedDataMapping = AHashMap::new();
 
-    for txn in &transactions {
+    for txn in transactions {
         let txn_version = txn.version as i64;
         let changes = &txn
             .info
@@ -266,7 +268,7 @@ pub async fn process_objects(
             })
             .changes;
 
-        let txn_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
+        let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
 
         // First pass to get all the object cores
         for wsc in changes.iter() {
@@ -1,3 +1,7 @@
+use crate::bq_analytics::{
+    gcs_handler::GcsUploadConfig, generic_parquet_processor::ParquetCompressionConfig,
+};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 
 pub mod parquet_ans_processor;
@@ -20,3 +24,25 @@ pub trait ParquetProcessorTrait {
         }
     }
 }
+
+/// Parquet settings for processors that can write to Parquet in addition to Postgres.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ParquetSinkConfig {
+    pub google_application_credentials: Option<String>,
+    pub bucket_name: String,
+    pub bucket_root: String,
+    pub parquet_handler_response_channel_size: usize,
+    pub max_buffer_size: usize,
+    pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
+}
+
+impl ParquetProcessorTrait for ParquetSinkConfig {
+    fn parquet_upload_interval_in_secs(&self) -> Duration {
+        Duration::from_secs(self.parquet_upload_interval)
+    }
+}
@@ -4,7 +4,9 @@
 use super::ParquetProcessorTrait;
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{ParquetCompressionConfig, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::postgres::models::ans_models::{
@@ -13,7 +15,10 @@ use crate::{
     },
     gap_detectors::ProcessingResult,
     processors::{ProcessorName, ProcessorTrait},
-    utils::{counters::PROCESSOR_UNKNOWN_TYPE_COUNT, database::ArcDbPool},
+    utils::{
+        counters::PROCESSOR_UNKNOWN_TYPE_COUNT, database::ArcDbPool,
+        util::parse_transaction_timestamp,
+    },
 };
 use ahash::AHashMap;
 use anyhow::anyhow;
@@ -21,9 +26,10 @@ use aptos_protos::transaction::v1::{
     transaction::TxnData, write_set_change::Change as WriteSetChange, Transaction,
 };
 use async_trait::async_trait;
+use chrono::SubsecRound;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -38,6 +44,10 @@ pub struct ParquetAnsProcessorConfig {
     pub ans_v1_name_records_table_handle: String,
     pub ans_v2_contract_address: String,
     pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
 }
 
 impl ParquetProcessorTrait for ParquetAnsProcessorConfig {
@@ -68,6 +78,9 @@ impl ParquetAnsProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         Self {
@@ -96,7 +109,7 @@ impl ProcessorTrait for ParquetAnsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _db_chain_id: Option<u64>,
@@ -113,6 +126,7 @@ impl ProcessorTrait for ParquetAnsProcessor {
 
         let ans_primary_name_v2_parquet_data = ParquetDataGeneric {
             data: all_ans_primary_names_v2,
+            transaction_version_to_struct_count: transaction_version_to_struct_count.clone(),
         };
 
         self.ans_primary_name_v2_sender
@@ -164,13 +178,10 @@ fn parse_ans(
             .info
             .as_ref()
             .expect("Transaction info doesn't exist!");
-        let timestamp = transaction
-            .timestamp
-            .as_ref()
-            .expect("Transaction timestamp doesn't exist!");
-        #[allow(deprecated)]
-        let block_timestamp = chrono::NaiveDateTime::from_timestamp_opt(timestamp.seconds, 0)
-            .expect("Txn Timestamp is invalid!");
+        // Stored to the second
+        let block_timestamp =
+            parse_transaction_timestamp(transaction.timestamp.as_ref(), txn_version)
+                .trunc_subsecs(0);
         // Extracts from user transactions. Other transactions won't have any ANS changes
         if let TxnData::User(user_txn) = txn_data {
             // Parse V2 ANS Events. We only care about the following events:
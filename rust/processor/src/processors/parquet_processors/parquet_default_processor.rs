@@ -3,7 +3,11 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{
+            merge_struct_counts, ParquetCompressionConfig, ParquetDataGeneric,
+        },
         ParquetProcessingResult,
     },
     db::parquet::models::default_models::{
@@ -15,7 +19,7 @@ use crate::{
     },
     gap_detectors::ProcessingResult,
     processors::{parquet_processors::ParquetProcessorTrait, ProcessorName, ProcessorTrait},
-    utils::database::ArcDbPool,
+    utils::{database::ArcDbPool, util::transaction_content_hash},
 };
 use ahash::AHashMap;
 use anyhow::anyhow;
@@ -25,6 +29,7 @@ use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{Debug, Formatter, Result},
+    sync::Arc,
     time::Duration,
 };
 
@@ -37,6 +42,13 @@ pub struct ParquetDefaultProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
+    /// Fills `content_hash` in `transactions`, see `transaction_content_hash`
+    #[serde(default)]
+    pub compute_content_hash: bool,
 }
 impl ParquetProcessorTrait for ParquetDefaultProcessorConfig {
     fn parquet_upload_interval_in_secs(&self) -> Duration {
@@ -51,6 +63,7 @@ pub struct ParquetDefaultProcessor {
     wsc_sender: AsyncSender<ParquetDataGeneric<WriteSetChangeModel>>,
     table_item_sender: AsyncSender<ParquetDataGeneric<TableItem>>,
     move_module_sender: AsyncSender<ParquetDataGeneric<MoveModule>>,
+    compute_content_hash: bool,
 }
 
 // TODO: Since each table item has different size allocated, the pace of being backfilled to PQ varies a lot.
@@ -71,6 +84,9 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         let move_resource_sender = create_parquet_handler_loop::<MoveResource>(
@@ -81,6 +97,9 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         let wsc_sender = create_parquet_handler_loop::<WriteSetChangeModel>(
@@ -91,6 +110,9 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         let table_item_sender = create_parquet_handler_loop::<TableItem>(
@@ -101,6 +123,9 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
         let move_module_sender = create_parquet_handler_loop::<MoveModule>(
             new_gap_detector_sender.clone(),
@@ -110,6 +135,9 @@ impl ParquetDefaultProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         Self {
@@ -119,6 +147,7 @@ impl ParquetDefaultProcessor {
             wsc_sender,
             table_item_sender,
             move_module_sender,
+            compute_content_hash: config.compute_content_hash,
         }
     }
 }
@@ -145,52 +174,61 @@ impl ProcessorTrait for ParquetDefaultProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
     ) -> anyhow::Result<ProcessingResult> {
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
+        let compute_content_hash = self.compute_content_hash;
 
-        let (
-            (move_resources, write_set_changes, transactions, table_items, move_modules),
-            transaction_version_to_struct_count,
-        ) = tokio::task::spawn_blocking(move || process_transactions_parquet(transactions))
+        let (mr_parquet_data, wsc_parquet_data, t_parquet_data, ti_parquet_data, mm_parquet_data) =
+            tokio::task::spawn_blocking(move || {
+                let content_hashes = compute_content_hash.then(|| {
+                    transactions
+                        .iter()
+                        .map(|txn| (txn.version as i64, transaction_content_hash(txn)))
+                        .collect::<AHashMap<_, _>>()
+                });
+                let mut parquet_data = process_transactions_parquet(&transactions);
+                if let Some(mut content_hashes) = content_hashes {
+                    for txn in parquet_data.2.data.iter_mut() {
+                        txn.content_hash = content_hashes.remove(&txn.txn_version);
+                    }
+                }
+                parquet_data
+            })
             .await
             .expect("Failed to spawn_blocking for TransactionModel::from_transactions");
 
-        let mr_parquet_data = ParquetDataGeneric {
-            data: move_resources,
-        };
+        let transaction_version_to_struct_count = merge_struct_counts([
+            &mr_parquet_data.transaction_version_to_struct_count,
+            &wsc_parquet_data.transaction_version_to_struct_count,
+            &t_parquet_data.transaction_version_to_struct_count,
+            &ti_parquet_data.transaction_version_to_struct_count,
+            &mm_parquet_data.transaction_version_to_struct_count,
+        ]);
 
         self.move_resource_sender
             .send(mr_parquet_data)
             .await
             .map_err(|e| anyhow!("Failed to send to parquet manager: {}", e))?;
 
-        let wsc_parquet_data = ParquetDataGeneric {
-            data: write_set_changes,
-        };
         self.wsc_sender
             .send(wsc_parquet_data)
             .await
             .map_err(|e| anyhow!("Failed to send to parquet manager: {}", e))?;
 
-        let t_parquet_data = ParquetDataGeneric { data: transactions };
         self.transaction_sender
             .send(t_parquet_data)
             .await
             .map_err(|e| anyhow!("Failed to send to parquet manager: {}", e))?;
 
-        let ti_parquet_data = ParquetDataGeneric { data: table_items };
-
         self.table_item_sender
             .send(ti_parquet_data)
             .await
             .map_err(|e| anyhow!("Failed to send to parquet manager: {}", e))?;
 
-        let mm_parquet_data = ParquetDataGeneric { data: move_modules };
-
         self.move_module_sender
             .send(mm_parquet_data)
             .await
@@ -213,65 +251,68 @@ impl ProcessorTrait for ParquetDefaultProcessor {
     }
 }
 
-// TODO: Remove transaction_version_to_struct_count after migration
+/// Parses the structs of each table, along with how many the transactions made per version.
 pub fn process_transactions_parquet(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
 ) -> (
-    (
-        Vec<MoveResource>,
-        Vec<WriteSetChangeModel>,
-        Vec<TransactionModel>,
-        Vec<TableItem>,
-        Vec<MoveModule>,
-    ),
-    AHashMap<i64, i64>,
+    ParquetDataGeneric<MoveResource>,
+    ParquetDataGeneric<WriteSetChangeModel>,
+    ParquetDataGeneric<TransactionModel>,
+    ParquetDataGeneric<TableItem>,
+    ParquetDataGeneric<MoveModule>,
 ) {
-    let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
+    let mut txn_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
+    let mut wsc_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
     let (txns, write_set_changes, wsc_details) = TransactionModel::from_transactions(
-        &transactions,
-        &mut transaction_version_to_struct_count,
+        transactions,
+        &mut txn_version_to_struct_count,
+        &mut wsc_version_to_struct_count,
     );
 
-    let mut move_modules = vec![];
-    let mut move_resources = vec![];
-    let mut table_items = vec![];
+    let mut move_modules = ParquetDataGeneric::default();
+    let mut move_resources = ParquetDataGeneric::default();
+    let mut table_items = ParquetDataGeneric::default();
 
     for detail in wsc_details {
         match detail {
             WriteSetChangeDetail::Module(module) => {
-                let txn_version = module.txn_version;
-                move_modules.push(module);
-                transaction_version_to_struct_count
-                    .entry(txn_version)
+                move_modules
+                    .transaction_version_to_struct_count
+                    .entry(module.txn_version)
                     .and_modify(|e| *e += 1)
                     .or_insert(1);
+                move_modules.data.push(module);
             },
             WriteSetChangeDetail::Resource(resource) => {
-                transaction_version_to_struct_count
+                move_resources
+                    .transaction_version_to_struct_count
                     .entry(resource.txn_version)
                     .and_modify(|e| *e += 1)
                     .or_insert(1);
-                move_resources.push(resource);
+                move_resources.data.push(resource);
             },
             WriteSetChangeDetail::Table(item, _current_item, _) => {
-                let txn_version = item.txn_version;
-                transaction_version_to_struct_count
-                    .entry(txn_version)
+                table_items
+                    .transaction_version_to_struct_count
+                    .entry(item.txn_version)
                     .and_modify(|e| *e += 1)
                     .or_insert(1);
-                table_items.push(item);
+                table_items.data.push(item);
             },
         }
     }
 
     (
-        (
-            move_resources,
-            write_set_changes,
-            txns,
-            table_items,
-            move_modules,
-        ),
-        transaction_version_to_struct_count,
+        move_resources,
+        ParquetDataGeneric {
+            data: write_set_changes,
+            transaction_version_to_struct_count: wsc_version_to_struct_count,
+        },
+        ParquetDataGeneric {
+            data: txns,
+            transaction_version_to_struct_count: txn_version_to_struct_count,
+        },
+        table_items,
+        move_modules,
     )
 }
@@ -3,13 +3,18 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{ParquetCompressionConfig, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::parquet::models::event_models::parquet_events::{Event, ParquetEventModel},
     gap_detectors::ProcessingResult,
     processors::{parquet_processors::ParquetProcessorTrait, ProcessorName, ProcessorTrait},
-    utils::{counters::PROCESSOR_UNKNOWN_TYPE_COUNT, database::ArcDbPool, util::parse_timestamp},
+    utils::{
+        counters::PROCESSOR_UNKNOWN_TYPE_COUNT, database::ArcDbPool,
+        util::parse_transaction_timestamp,
+    },
 };
 use ahash::AHashMap;
 use anyhow::Context;
@@ -17,7 +22,7 @@ use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
 use async_trait::async_trait;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 use tracing::warn;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -28,6 +33,10 @@ pub struct ParquetEventsProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
 }
 
 impl ParquetProcessorTrait for ParquetEventsProcessorConfig {
@@ -57,6 +66,9 @@ impl ParquetEventsProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         Self {
@@ -84,7 +96,7 @@ impl ProcessorTrait for ParquetEventsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -92,9 +104,12 @@ impl ProcessorTrait for ParquetEventsProcessor {
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
 
         let (transaction_version_to_struct_count, events) =
-            process_transactions_parquet(transactions);
+            process_transactions_parquet(&transactions);
 
-        let event_parquet_data = ParquetDataGeneric { data: events };
+        let event_parquet_data = ParquetDataGeneric {
+            data: events,
+            transaction_version_to_struct_count: transaction_version_to_struct_count.clone(),
+        };
 
         self.event_sender
             .send(event_parquet_data)
@@ -119,15 +134,15 @@ impl ProcessorTrait for ParquetEventsProcessor {
 }
 
 pub fn process_transactions_parquet(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
 ) -> (AHashMap<i64, i64>, Vec<Event>) {
     let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
 
     let mut events = vec![];
-    for txn in &transactions {
+    for txn in transactions {
         let txn_version = txn.version as i64;
         let block_height = txn.block_height as i64;
-        let block_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
+        let block_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
         let size_info = match txn.size_info.as_ref() {
             Some(size_info) => size_info,
             None => {
@@ -4,7 +4,9 @@
 use super::ParquetProcessorTrait;
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{ParquetCompressionConfig, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::{
@@ -30,17 +32,20 @@ use crate::{
     processors::{ProcessorName, ProcessorTrait},
     utils::{
         database::ArcDbPool,
-        util::{get_entry_function_from_user_request, standardize_address},
+        util::{
+            get_entry_function_from_user_request, parse_transaction_timestamp, standardize_address,
+            ParseContext,
+        },
     },
 };
 use ahash::AHashMap;
 use anyhow::anyhow;
 use aptos_protos::transaction::v1::{transaction::TxnData, write_set_change::Change, Transaction};
 use async_trait::async_trait;
-use chrono::NaiveDateTime;
+use chrono::SubsecRound;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -51,6 +56,10 @@ pub struct ParquetFungibleAssetActivitiesProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
 }
 
 impl ParquetProcessorTrait for ParquetFungibleAssetActivitiesProcessorConfig {
@@ -82,6 +91,9 @@ impl ParquetFungibleAssetActivitiesProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         Self {
@@ -109,7 +121,7 @@ impl ProcessorTrait for ParquetFungibleAssetActivitiesProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -127,6 +139,7 @@ impl ProcessorTrait for ParquetFungibleAssetActivitiesProcessor {
 
         let parquet_fungible_asset_activities = ParquetDataGeneric {
             data: parquet_fungible_asset_activities,
+            transaction_version_to_struct_count: transaction_version_to_struct_count.clone(),
         };
 
         self.fungible_asset_activities_sender
@@ -176,14 +189,9 @@ async fn parse_activities(
             }
             let txn_data = txn.txn_data.as_ref().unwrap();
             let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
-            let txn_timestamp = txn
-                .timestamp
-                .as_ref()
-                .expect("Transaction timestamp doesn't exist!")
-                .seconds;
-            #[allow(deprecated)]
-            let txn_timestamp = NaiveDateTime::from_timestamp_opt(txn_timestamp, 0)
-                .expect("Txn Timestamp is invalid!");
+            // Stored to the second
+            let txn_timestamp =
+                parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version).trunc_subsecs(0);
 
             let default = vec![];
             let (events, user_request, entry_function_id_str) = match txn_data {
@@ -239,7 +247,9 @@ async fn parse_activities(
                     let address = standardize_address(&write_resource.address.to_string());
                     if let Some(aggregated_data) = fungible_asset_object_helper.get_mut(&address) {
                         if let Some(v2_fungible_asset_resource) =
-                            V2FungibleAssetResource::from_write_resource(write_resource).unwrap()
+                            V2FungibleAssetResource::from_write_resource(write_resource)
+                                .parse_context(txn_version, index as i64, &write_resource.type_str)
+                                .unwrap()
                         {
                             match v2_fungible_asset_resource {
                                 V2FungibleAssetResource::FungibleAssetMetadata(
@@ -4,7 +4,11 @@
 use super::ParquetProcessorTrait;
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{
+            merge_struct_counts, ParquetCompressionConfig, ParquetDataGeneric,
+        },
         ParquetProcessingResult,
     },
     db::{
@@ -23,16 +27,21 @@ use crate::{
     },
     gap_detectors::ProcessingResult,
     processors::{ProcessorName, ProcessorTrait},
-    utils::{database::ArcDbPool, util::standardize_address},
+    utils::{
+        database::ArcDbPool,
+        util::{
+            debug_assert_standardized_address, parse_transaction_timestamp, standardize_address,
+        },
+    },
 };
 use ahash::AHashMap;
 use anyhow::anyhow;
 use aptos_protos::transaction::v1::{write_set_change::Change, Transaction};
 use async_trait::async_trait;
-use chrono::NaiveDateTime;
+use chrono::SubsecRound;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -43,6 +52,10 @@ pub struct ParquetFungibleAssetProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
 }
 
 impl ParquetProcessorTrait for ParquetFungibleAssetProcessorConfig {
@@ -73,6 +86,9 @@ impl ParquetFungibleAssetProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         let fungible_asset_balances_sender = create_parquet_handler_loop::<FungibleAssetBalance>(
@@ -83,6 +99,9 @@ impl ParquetFungibleAssetProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         Self {
@@ -112,18 +131,28 @@ impl ProcessorTrait for ParquetFungibleAssetProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
     ) -> anyhow::Result<ProcessingResult> {
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
-        let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
+        let mut balance_struct_count: AHashMap<i64, i64> = AHashMap::new();
+        let mut coin_supply_struct_count: AHashMap<i64, i64> = AHashMap::new();
 
-        let (raw_fungible_asset_balances, coin_supply) =
-            parse_v2_coin(&transactions, &mut transaction_version_to_struct_count).await;
+        let (raw_fungible_asset_balances, coin_supply) = parse_v2_coin(
+            &transactions,
+            &mut balance_struct_count,
+            &mut coin_supply_struct_count,
+        )
+        .await;
+        let transaction_version_to_struct_count =
+            merge_struct_counts([&balance_struct_count, &coin_supply_struct_count]);
 
-        let parquet_coin_supply = ParquetDataGeneric { data: coin_supply };
+        let parquet_coin_supply = ParquetDataGeneric {
+            data: coin_supply,
+            transaction_version_to_struct_count: coin_supply_struct_count,
+        };
 
         self.coin_supply_sender
             .send(parquet_coin_supply)
@@ -137,6 +166,7 @@ impl ProcessorTrait for ParquetFungibleAssetProcessor {
 
         let parquet_fungible_asset_balances = ParquetDataGeneric {
             data: fungible_asset_balances,
+            transaction_version_to_struct_count: balance_struct_count,
         };
 
         self.fungible_asset_balances_sender
@@ -163,7 +193,8 @@ impl ProcessorTrait for ParquetFungibleAssetProcessor {
 
 async fn parse_v2_coin(
     transactions: &[Transaction],
-    transaction_version_to_struct_count: &mut AHashMap<i64, i64>,
+    balance_struct_count: &mut AHashMap<i64, i64>,
+    coin_supply_struct_count: &mut AHashMap<i64, i64>,
 ) -> (Vec<RawFungibleAssetBalance>, Vec<CoinSupply>) {
     let mut fungible_asset_balances = vec![];
     let mut all_coin_supply = vec![];
@@ -174,14 +205,9 @@ async fn parse_v2_coin(
     for txn in transactions {
         let txn_version = txn.version as i64;
         let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
-        let txn_timestamp = txn
-            .timestamp
-            .as_ref()
-            .expect("Transaction timestamp doesn't exist!")
-            .seconds;
-        #[allow(deprecated)]
+        // Stored to the second
         let txn_timestamp =
-            NaiveDateTime::from_timestamp_opt(txn_timestamp, 0).expect("Txn Timestamp is invalid!");
+            parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version).trunc_subsecs(0);
 
         // First loop to get all objects
         // Need to do a first pass to get all the objects
@@ -210,7 +236,7 @@ async fn parse_v2_coin(
                 .unwrap()
                 {
                     fungible_asset_balances.push(balance);
-                    transaction_version_to_struct_count
+                    balance_struct_count
                         .entry(txn_version)
                         .and_modify(|e| *e += 1)
                         .or_insert(1);
@@ -225,7 +251,7 @@ async fn parse_v2_coin(
                 .unwrap()
                 {
                     fungible_asset_balances.push(balance);
-                    transaction_version_to_struct_count
+                    balance_struct_count
                         .entry(txn_version)
                         .and_modify(|e| *e += 1)
                         .or_insert(1);
@@ -253,7 +279,7 @@ async fn parse_v2_coin(
                         panic!("[Parser] error parsing fungible balance v2");
                     }) {
                         fungible_asset_balances.push(balance);
-                        transaction_version_to_struct_count
+                        balance_struct_count
                             .entry(txn_version)
                             .and_modify(|e| *e += 1)
                             .or_insert(1);
@@ -265,7 +291,7 @@ async fn parse_v2_coin(
                             .unwrap()
                     {
                         all_coin_supply.push(coin_supply);
-                        transaction_version_to_struct_count
+                        coin_supply_struct_count
                             .entry(txn_version)
                             .and_modify(|e| *e += 1)
                             .or_insert(1);
@@ -276,5 +302,9 @@ async fn parse_v2_coin(
         }
     }
 
+    for balance in &fungible_asset_balances {
+        debug_assert_standardized_address(&balance.owner_address);
+    }
+
     (fungible_asset_balances, all_coin_supply)
 }
@@ -4,7 +4,11 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{
+            merge_struct_counts, ParquetCompressionConfig, ParquetDataGeneric,
+        },
         ParquetProcessingResult,
     },
     db::{
@@ -36,7 +40,7 @@ use crate::{
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
         database::{ArcDbPool, DbContext},
-        util::{parse_timestamp, standardize_address},
+        util::{parse_transaction_timestamp, standardize_address},
     },
 };
 use ahash::{AHashMap, AHashSet};
@@ -45,7 +49,7 @@ use aptos_protos::transaction::v1::{transaction::TxnData, write_set_change::Chan
 use async_trait::async_trait;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -56,6 +60,10 @@ pub struct ParquetTokenV2ProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
 }
 impl ParquetProcessorTrait for ParquetTokenV2ProcessorConfig {
     fn parquet_upload_interval_in_secs(&self) -> Duration {
@@ -85,6 +93,9 @@ impl ParquetTokenV2Processor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         let v2_token_ownerships_sender = create_parquet_handler_loop::<TokenOwnershipV2>(
@@ -95,6 +106,9 @@ impl ParquetTokenV2Processor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
 
         Self {
@@ -124,13 +138,14 @@ impl ProcessorTrait for ParquetTokenV2Processor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
     ) -> anyhow::Result<ProcessingResult> {
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
-        let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
+        let mut token_data_struct_count: AHashMap<i64, i64> = AHashMap::new();
+        let mut token_ownership_struct_count: AHashMap<i64, i64> = AHashMap::new();
 
         let table_handle_to_owner =
             TableMetadataForToken::get_table_handle_to_owner_from_transactions(&transactions);
@@ -139,9 +154,12 @@ impl ProcessorTrait for ParquetTokenV2Processor {
             &transactions,
             &table_handle_to_owner,
             &mut None,
-            &mut transaction_version_to_struct_count,
+            &mut token_data_struct_count,
+            &mut token_ownership_struct_count,
         )
         .await;
+        let transaction_version_to_struct_count =
+            merge_struct_counts([&token_data_struct_count, &token_ownership_struct_count]);
 
         let parquet_token_datas_v2: Vec<TokenDataV2> = raw_token_datas_v2
             .into_iter()
@@ -150,6 +168,7 @@ impl ProcessorTrait for ParquetTokenV2Processor {
 
         let token_data_v2_parquet_data = ParquetDataGeneric {
             data: parquet_token_datas_v2,
+            transaction_version_to_struct_count: token_data_struct_count,
         };
 
         self.v2_token_datas_sender
@@ -164,6 +183,7 @@ impl ProcessorTrait for ParquetTokenV2Processor {
 
         let token_ownerships_v2_parquet_data = ParquetDataGeneric {
             data: parquet_token_ownerships_v2,
+            transaction_version_to_struct_count: token_ownership_struct_count,
         };
 
         self.v2_token_ownerships_sender
@@ -192,7 +212,8 @@ async fn parse_v2_token(
     transactions: &[Transaction],
     table_handle_to_owner: &TableHandleToOwner,
     db_context: &mut Option<DbContext<'_>>,
-    transaction_version_to_struct_count: &mut AHashMap<i64, i64>,
+    token_data_struct_count: &mut AHashMap<i64, i64>,
+    token_ownership_struct_count: &mut AHashMap<i64, i64>,
 ) -> (Vec<RawTokenDataV2>, Vec<RawTokenOwnershipV2>) {
     // Token V2 and V1 combined
     let mut token_datas_v2 = vec![];
@@ -222,7 +243,7 @@ async fn parse_v2_token(
             },
         };
         let txn_version = txn.version as i64;
-        let txn_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
+        let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
         let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
 
         if let TxnData::User(user_txn) = txn_data {
@@ -343,7 +364,7 @@ async fn parse_v2_token(
                             .unwrap()
                         {
                             token_datas_v2.push(raw_token_data);
-                            transaction_version_to_struct_count
+                            token_data_struct_count
                                 .entry(txn_version)
                                 .and_modify(|e| *e += 1)
                                 .or_insert(1);
@@ -369,7 +390,7 @@ async fn parse_v2_token(
                                     },
                                 );
                             }
-                            transaction_version_to_struct_count
+                            token_ownership_struct_count
                                 .entry(txn_version)
                                 .and_modify(|e| *e += 1)
                                 .or_insert(1);
@@ -387,7 +408,7 @@ async fn parse_v2_token(
                             .unwrap()
                         {
                             token_ownerships_v2.push(token_ownership);
-                            transaction_version_to_struct_count
+                            token_ownership_struct_count
                                 .entry(txn_version)
                                 .and_modify(|e| *e += 1)
                                 .or_insert(1);
@@ -438,10 +459,14 @@ async fn parse_v2_token(
                                     },
                                 );
                             }
-                            transaction_version_to_struct_count
+                            token_ownership_struct_count
+                                .entry(txn_version)
+                                .and_modify(|e| *e += ownerships.len() as i64)
+                                .or_insert(ownerships.len() as i64);
+                            token_data_struct_count
                                 .entry(txn_version)
-                                .and_modify(|e| *e += ownerships.len() as i64 + 1)
-                                .or_insert(ownerships.len() as i64 + 1);
+                                .and_modify(|e| *e += 1)
+                                .or_insert(1);
                             token_ownerships_v2.append(&mut ownerships);
                             token_datas_v2.push(raw_token_data);
                         }
@@ -461,7 +486,7 @@ async fn parse_v2_token(
                             .unwrap()
                         {
                             token_ownerships_v2.push(nft_ownership);
-                            transaction_version_to_struct_count
+                            token_ownership_struct_count
                                 .entry(txn_version)
                                 .and_modify(|e| *e += 1)
                                 .or_insert(1);
@@ -490,7 +515,7 @@ async fn parse_v2_token(
                             .unwrap()
                         {
                             token_ownerships_v2.push(nft_ownership);
-                            transaction_version_to_struct_count
+                            token_ownership_struct_count
                                 .entry(txn_version)
                                 .and_modify(|e| *e += 1)
                                 .or_insert(1);
@@ -3,13 +3,15 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{ParquetCompressionConfig, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::parquet::models::transaction_metadata_model::parquet_write_set_size_info::WriteSetSize,
     gap_detectors::ProcessingResult,
     processors::{parquet_processors::ParquetProcessorTrait, ProcessorName, ProcessorTrait},
-    utils::{database::ArcDbPool, util::parse_timestamp},
+    utils::{database::ArcDbPool, util::parse_transaction_timestamp},
 };
 use ahash::AHashMap;
 use anyhow::Context;
@@ -17,7 +19,7 @@ use aptos_protos::transaction::v1::Transaction;
 use async_trait::async_trait;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 use tracing::warn;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -29,6 +31,10 @@ pub struct ParquetTransactionMetadataProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
 }
 
 impl ParquetProcessorTrait for ParquetTransactionMetadataProcessorConfig {
@@ -58,6 +64,9 @@ impl ParquetTransactionMetadataProcessor {
             config.parquet_handler_response_channel_size,
             config.max_buffer_size,
             config.parquet_upload_interval_in_secs(),
+            config.gcs_upload,
+            connection_pool.clone(),
+            config.compression,
         );
         Self {
             connection_pool,
@@ -84,7 +93,7 @@ impl ProcessorTrait for ParquetTransactionMetadataProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -93,10 +102,11 @@ impl ProcessorTrait for ParquetTransactionMetadataProcessor {
         let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
 
         let write_set_sizes =
-            process_transaction(transactions, &mut transaction_version_to_struct_count);
+            process_transaction(&transactions, &mut transaction_version_to_struct_count);
 
         let write_set_size_info_parquet_data = ParquetDataGeneric {
             data: write_set_sizes,
+            transaction_version_to_struct_count: transaction_version_to_struct_count.clone(),
         };
 
         self.write_set_size_info_sender
@@ -122,14 +132,14 @@ impl ProcessorTrait for ParquetTransactionMetadataProcessor {
 }
 
 pub fn process_transaction(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
     transaction_version_to_struct_count: &mut AHashMap<i64, i64>,
 ) -> Vec<WriteSetSize> {
     let mut write_set_sizes = vec![];
 
     for txn in transactions {
         let txn_version = txn.version as i64;
-        let block_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
+        let block_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
         let size_info = match txn.size_info.as_ref() {
             Some(size_info) => size_info,
             None => {
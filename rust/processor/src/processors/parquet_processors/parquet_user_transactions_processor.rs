@@ -3,7 +3,9 @@
 
 use crate::{
     bq_analytics::{
-        create_parquet_handler_loop, generic_parquet_processor::ParquetDataGeneric,
+        create_parquet_handler_loop,
+        gcs_handler::GcsUploadConfig,
+        generic_parquet_processor::{ParquetCompressionConfig, ParquetDataGeneric},
         ParquetProcessingResult,
     },
     db::{
@@ -20,7 +22,7 @@ use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
 use async_trait::async_trait;
 use kanal::AsyncSender;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Debug, time::Duration};
+use std::{fmt::Debug, sync::Arc, time::Duration};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ParquetUserTransactionsProcessorConfig {
@@ -30,6 +32,10 @@ pub struct ParquetUserTransactionsProcessorConfig {
     pub parquet_handler_response_channel_size: usize,
     pub max_buffer_size: usize,
     pub parquet_upload_interval: u64,
+    #[serde(default)]
+    pub gcs_upload: GcsUploadConfig,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
 }
 
 impl ParquetProcessorTrait for ParquetUserTransactionsProcessorConfig {
@@ -60,6 +66,9 @@ impl ParquetUserTransactionsProcessor {
                 config.parquet_handler_response_channel_size,
                 config.max_buffer_size,
                 config.parquet_upload_interval_in_secs(),
+                config.gcs_upload,
+                connection_pool.clone(),
+                config.compression,
             );
 
         Self {
@@ -80,11 +89,11 @@ impl Debug for ParquetUserTransactionsProcessor {
 }
 
 pub async fn process_transactions(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
 ) -> (Vec<UserTransaction>, AHashMap<i64, i64>) {
     let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
     let mut user_transactions = vec![];
-    for txn in &transactions {
+    for txn in transactions {
         let txn_version = txn.version as i64;
         let block_height = txn.block_height as i64;
         let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
@@ -134,17 +143,18 @@ impl ProcessorTrait for ParquetUserTransactionsProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
     ) -> anyhow::Result<ProcessingResult> {
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
         let (user_transactions, transaction_version_to_struct_count) =
-            process_transactions(transactions).await;
+            process_transactions(&transactions).await;
 
         let user_transaction_parquet_data = ParquetDataGeneric {
             data: user_transactions,
+            transaction_version_to_struct_count: transaction_version_to_struct_count.clone(),
         };
 
         self.user_transactions_sender
@@ -37,7 +37,7 @@ use crate::{
     schema,
     utils::{
         database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool, DbPoolConnection},
-        util::{parse_timestamp, standardize_address},
+        util::{parse_transaction_timestamp, standardize_address},
     },
     IndexerGrpcProcessorConfig,
 };
@@ -51,7 +51,7 @@ use diesel::{
     ExpressionMethods,
 };
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -454,7 +454,7 @@ pub async fn parse_stake_data(
         // Currently only delegator voting follows this paradigm
         // TODO: refactor all the other staking code to follow this paradigm
         let txn_version = txn.version as i64;
-        let txn_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
+        let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
         let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
         // adding some metadata for subsequent parsing
         for wsc in &transaction_info.changes {
@@ -596,7 +596,7 @@ impl ProcessorTrait for StakeProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -696,6 +696,8 @@ impl ProcessorTrait for StakeProcessor {
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             )),
             Err(e) => {
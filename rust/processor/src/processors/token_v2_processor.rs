@@ -30,6 +30,7 @@ use crate::{
                 },
                 v2_token_utils::{
                     Burn, BurnEvent, Mint, MintEvent, TokenV2Burned, TokenV2Minted, TransferEvent,
+                    DEFAULT_OWNER_ADDRESS,
                 },
             },
         },
@@ -41,6 +42,12 @@ use crate::{
                 tokens::{CurrentTokenPendingClaimPK, TableHandleToOwner, TableMetadataForToken},
             },
             token_v2_models::{
+                collection_creator_cache::CollectionCreatorCache,
+                collection_stats::{
+                    get_token_collections, refresh_collection_stats, CollectionStatChange,
+                    TokenCollections,
+                },
+                marketplace_activities::{MarketplaceActivity, MarketplaceConfig},
                 v1_token_royalty::CurrentTokenRoyaltyV1,
                 v2_collections::{CollectionV2, CurrentCollectionV2, CurrentCollectionV2PK},
                 v2_token_activities::TokenActivityV2,
@@ -55,8 +62,12 @@ use crate::{
     utils::{
         counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
         database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool, DbContext},
+        feature_flags::FeatureFlags,
         table_flags::TableFlags,
-        util::{get_entry_function_from_user_request, parse_timestamp, standardize_address},
+        util::{
+            debug_assert_standardized_address, get_entry_function_from_user_request,
+            parse_transaction_timestamp, standardize_address,
+        },
     },
     IndexerGrpcProcessorConfig,
 };
@@ -70,7 +81,7 @@ use diesel::{
     ExpressionMethods,
 };
 use serde::{Deserialize, Serialize};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -80,8 +91,35 @@ pub struct TokenV2ProcessorConfig {
     pub query_retries: u32,
     #[serde(default = "IndexerGrpcProcessorConfig::default_query_retry_delay_ms")]
     pub query_retry_delay_ms: u64,
+    // Number of v1 collection creators cached across processing tasks. 0 disables the cache
+    #[serde(default = "TokenV2ProcessorConfig::default_collection_creator_cache_capacity")]
+    pub collection_creator_cache_capacity: usize,
+    /// Marketplaces whose listing, offer and sale events are resolved into
+    /// `marketplace_activities`. Empty (the default) skips marketplace events entirely.
+    #[serde(default)]
+    pub marketplaces: Vec<MarketplaceConfig>,
+    /// Parsing changes still being rolled out, e.g. `TRIM_TOKEN_URIS`.
+    #[serde(default)]
+    pub feature_flags: FeatureFlags,
+    /// Keeps each collection's supply and mint, burn and transfer counts in `collection_stats`.
+    #[serde(default)]
+    pub collection_stats: bool,
+}
+
+impl TokenV2ProcessorConfig {
+    pub const fn default_collection_creator_cache_capacity() -> usize {
+        10_000
+    }
+
+    pub fn feature_enabled(&self, name: &str) -> bool {
+        self.feature_flags.is_enabled(name)
+    }
 }
 
+/// Trims whitespace around token URIs. Some creators write them with stray spaces or newlines,
+/// which breaks fetching the metadata they point to.
+pub const TRIM_TOKEN_URIS: &str = "trim_token_uris";
+
 pub struct TokenV2Processor {
     connection_pool: ArcDbPool,
     config: TokenV2ProcessorConfig,
@@ -96,6 +134,7 @@ impl TokenV2Processor {
         per_table_chunk_sizes: AHashMap<String, usize>,
         deprecated_tables: TableFlags,
     ) -> Self {
+        CollectionCreatorCache::init_global(config.collection_creator_cache_capacity);
         Self {
             connection_pool,
             config,
@@ -138,6 +177,8 @@ async fn insert_to_db(
     current_token_v2_metadata: &[CurrentTokenV2Metadata],
     current_token_royalties_v1: &[CurrentTokenRoyaltyV1],
     current_token_claims: &[CurrentTokenPendingClaim],
+    marketplace_activities: &[MarketplaceActivity],
+    collection_stat_changes: &[CollectionStatChange],
     per_table_chunk_sizes: &AHashMap<String, usize>,
 ) -> Result<(), diesel::result::Error> {
     tracing::trace!(
@@ -241,7 +282,7 @@ async fn insert_to_db(
         ),
     );
     let ctc_v1 = execute_in_chunks(
-        conn,
+        conn.clone(),
         insert_current_token_claims_query,
         current_token_claims,
         get_config_table_chunk_size::<CurrentTokenPendingClaim>(
@@ -249,6 +290,31 @@ async fn insert_to_db(
             per_table_chunk_sizes,
         ),
     );
+    let ma = execute_in_chunks(
+        conn.clone(),
+        insert_marketplace_activities_query,
+        marketplace_activities,
+        get_config_table_chunk_size::<MarketplaceActivity>(
+            "marketplace_activities",
+            per_table_chunk_sizes,
+        ),
+    );
+    let cs = async {
+        execute_in_chunks(
+            conn.clone(),
+            insert_collection_stat_changes_query,
+            collection_stat_changes,
+            get_config_table_chunk_size::<CollectionStatChange>(
+                "collection_stat_changes",
+                per_table_chunk_sizes,
+            ),
+        )
+        .await?;
+        // The stats are computed from the changes, so they go in after
+        refresh_collection_stats(conn, collection_stat_changes)
+            .await
+            .map(|_| ())
+    };
 
     let (
         coll_v2_res,
@@ -263,9 +329,11 @@ async fn insert_to_db(
         ct_v2_res,
         ctr_v1_res,
         ctc_v1_res,
+        ma_res,
+        cs_res,
     ) = tokio::join!(
         coll_v2, td_v2, to_v2, cc_v2, ctd_v2, cdtd_v2, cto_v2, cdto_v2, ta_v2, ct_v2, ctr_v1,
-        ctc_v1
+        ctc_v1, ma, cs
     );
 
     for res in [
@@ -281,6 +349,8 @@ async fn insert_to_db(
         ct_v2_res,
         ctr_v1_res,
         ctc_v1_res,
+        ma_res,
+        cs_res,
     ] {
         res?;
     }
@@ -519,6 +589,40 @@ pub fn insert_token_activities_v2_query(
     )
 }
 
+pub fn insert_marketplace_activities_query(
+    items_to_insert: Vec<MarketplaceActivity>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::marketplace_activities::dsl::*;
+
+    (
+        diesel::insert_into(schema::marketplace_activities::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_nothing(),
+        None,
+    )
+}
+
+pub fn insert_collection_stat_changes_query(
+    items_to_insert: Vec<CollectionStatChange>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::collection_stat_changes::dsl::*;
+
+    (
+        diesel::insert_into(schema::collection_stat_changes::table)
+            .values(items_to_insert)
+            .on_conflict((collection_id, transaction_version))
+            .do_nothing(),
+        None,
+    )
+}
+
 pub fn insert_current_token_v2_metadatas_query(
     items_to_insert: Vec<CurrentTokenV2Metadata>,
 ) -> (
@@ -605,7 +709,7 @@ impl ProcessorTrait for TokenV2Processor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -673,12 +777,13 @@ impl ProcessorTrait for TokenV2Processor {
             .map(TokenDataV2::from_raw)
             .collect();
 
-        let postgres_current_token_datas_v2: Vec<CurrentTokenDataV2> = raw_current_token_datas_v2
-            .into_iter()
-            .map(CurrentTokenDataV2::from_raw)
-            .collect();
+        let mut postgres_current_token_datas_v2: Vec<CurrentTokenDataV2> =
+            raw_current_token_datas_v2
+                .into_iter()
+                .map(CurrentTokenDataV2::from_raw)
+                .collect();
 
-        let postgres_current_deleted_token_datas_v2: Vec<CurrentTokenDataV2> =
+        let mut postgres_current_deleted_token_datas_v2: Vec<CurrentTokenDataV2> =
             raw_current_deleted_token_datas_v2
                 .into_iter()
                 .map(CurrentTokenDataV2::from_raw)
@@ -701,6 +806,56 @@ impl ProcessorTrait for TokenV2Processor {
                 .map(CurrentTokenOwnershipV2::from_raw)
                 .collect();
 
+        apply_feature_flags(
+            &self.config,
+            postgres_token_datas_v2
+                .iter_mut()
+                .map(|token_data| &mut token_data.token_uri)
+                .chain(
+                    postgres_current_token_datas_v2
+                        .iter_mut()
+                        .chain(postgres_current_deleted_token_datas_v2.iter_mut())
+                        .map(|token_data| &mut token_data.token_uri),
+                ),
+        );
+
+        let marketplace_activities: Vec<MarketplaceActivity> =
+            if self.config.marketplaces.is_empty() {
+                vec![]
+            } else {
+                transactions
+                    .iter()
+                    .flat_map(|txn| {
+                        MarketplaceActivity::from_transaction(txn, &self.config.marketplaces)
+                    })
+                    .collect()
+            };
+
+        let collection_stat_changes: Vec<CollectionStatChange> = if self.config.collection_stats {
+            // Transferred tokens not in this batch were indexed by an earlier one
+            let mut token_collections: TokenCollections = postgres_current_token_datas_v2
+                .iter()
+                .map(|token_data| {
+                    (
+                        token_data.token_data_id.clone(),
+                        token_data.collection_id.clone(),
+                    )
+                })
+                .collect();
+            let other_tokens = CollectionStatChange::get_transferred_tokens(&transactions)
+                .into_iter()
+                .filter(|token_data_id| !token_collections.contains_key(token_data_id))
+                .collect();
+            token_collections
+                .extend(get_token_collections(&mut self.get_conn().await, other_tokens).await?);
+            transactions
+                .iter()
+                .flat_map(|txn| CollectionStatChange::from_transaction(txn, &token_collections))
+                .collect()
+        } else {
+            vec![]
+        };
+
         let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
         let db_insertion_start = std::time::Instant::now();
 
@@ -744,6 +899,8 @@ impl ProcessorTrait for TokenV2Processor {
             &postgres_current_token_v2_metadata,
             &postgres_current_token_royalties_v1,
             &postgres_current_token_claims,
+            &marketplace_activities,
+            &collection_stat_changes,
             &self.per_table_chunk_sizes,
         )
         .await;
@@ -757,6 +914,8 @@ impl ProcessorTrait for TokenV2Processor {
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             )),
             Err(e) => {
@@ -777,6 +936,21 @@ impl ProcessorTrait for TokenV2Processor {
     }
 }
 
+/// Applies the parsing changes enabled in `feature_flags` to the parsed token URIs.
+fn apply_feature_flags<'a>(
+    config: &TokenV2ProcessorConfig,
+    token_uris: impl Iterator<Item = &'a mut String>,
+) {
+    if config.feature_enabled(TRIM_TOKEN_URIS) {
+        for token_uri in token_uris {
+            let trimmed = token_uri.trim();
+            if trimmed.len() != token_uri.len() {
+                *token_uri = trimmed.to_string();
+            }
+        }
+    }
+}
+
 pub async fn parse_v2_token_for_parquet(
     transactions: &[Transaction],
     table_handle_to_owner: &TableHandleToOwner,
@@ -868,7 +1042,7 @@ pub async fn parse_v2_token(
             },
         };
         let txn_version = txn.version as i64;
-        let txn_timestamp = parse_timestamp(txn.timestamp.as_ref().unwrap(), txn_version);
+        let txn_timestamp = parse_transaction_timestamp(txn.timestamp.as_ref(), txn_version);
         let transaction_info = txn.info.as_ref().expect("Transaction info doesn't exist!");
 
         if let TxnData::User(user_txn) = txn_data {
@@ -1402,6 +1576,20 @@ pub async fn parse_v2_token(
     current_token_royalties_v1.sort();
     all_current_token_claims.sort();
 
+    // Burned tokens whose previous owner can't be found are recorded under a placeholder owner
+    for owner_address in token_ownerships_v2
+        .iter()
+        .filter_map(|ownership| ownership.owner_address.as_deref())
+        .chain(
+            current_token_ownerships_v2
+                .iter()
+                .map(|ownership| ownership.owner_address.as_str()),
+        )
+        .filter(|owner_address| *owner_address != DEFAULT_OWNER_ADDRESS)
+    {
+        debug_assert_standardized_address(owner_address);
+    }
+
     (
         collections_v2,
         token_datas_v2,
@@ -1417,3 +1605,36 @@ pub async fn parse_v2_token(
         all_current_token_claims,
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed_token_uris(config: serde_json::Value) -> Vec<String> {
+        let config: TokenV2ProcessorConfig = serde_json::from_value(config).unwrap();
+        let mut token_uris = vec![
+            " ipfs://bafybeigdyrzt/1.json\n".to_string(),
+            "https://example.com/2.json".to_string(),
+        ];
+        apply_feature_flags(&config, token_uris.iter_mut());
+        token_uris
+    }
+
+    #[test]
+    fn test_trim_token_uris_flag() {
+        // Off by default and when set to false, the URIs are kept as they are on chain
+        let untrimmed = [
+            " ipfs://bafybeigdyrzt/1.json\n",
+            "https://example.com/2.json",
+        ];
+        assert_eq!(parsed_token_uris(serde_json::json!({})), untrimmed);
+        let config = serde_json::json!({"feature_flags": {"trim_token_uris": false}});
+        assert_eq!(parsed_token_uris(config), untrimmed);
+
+        let config = serde_json::json!({
+            "feature_flags": {"trim_token_uris": true, "not_a_flag_yet": true},
+        });
+        let trimmed = ["ipfs://bafybeigdyrzt/1.json", "https://example.com/2.json"];
+        assert_eq!(parsed_token_uris(config), trimmed);
+    }
+}
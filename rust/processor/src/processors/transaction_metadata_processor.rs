@@ -16,7 +16,7 @@ use anyhow::bail;
 use aptos_protos::transaction::v1::Transaction;
 use async_trait::async_trait;
 use diesel::{pg::Pg, query_builder::QueryFragment};
-use std::fmt::Debug;
+use std::{fmt::Debug, sync::Arc};
 use tracing::{error, warn};
 
 pub struct TransactionMetadataProcessor {
@@ -145,7 +145,7 @@ impl ProcessorTrait for TransactionMetadataProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -154,7 +154,7 @@ impl ProcessorTrait for TransactionMetadataProcessor {
         let mut transaction_sizes = vec![];
         let mut event_sizes = vec![];
         let mut write_set_sizes = vec![];
-        for txn in &transactions {
+        for txn in transactions.iter() {
             let txn_version = txn.version as i64;
             let size_info = match txn.size_info.as_ref() {
                 Some(size_info) => size_info,
@@ -206,6 +206,8 @@ impl ProcessorTrait for TransactionMetadataProcessor {
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp: transactions.last().unwrap().timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             )),
             Err(e) => {
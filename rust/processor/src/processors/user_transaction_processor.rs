@@ -3,18 +3,25 @@
 
 use super::{DefaultProcessingResult, ProcessorName, ProcessorTrait};
 use crate::{
-    db::postgres::models::user_transactions_models::{
-        signatures::Signature, user_transactions::UserTransactionModel,
+    db::postgres::models::{
+        default_models::transactions::Transaction as TransactionModel,
+        user_transactions_models::{
+            signatures::Signature, user_transactions::UserTransactionModel,
+        },
     },
     gap_detectors::ProcessingResult,
     schema,
     utils::{
-        counters::PROCESSOR_UNKNOWN_TYPE_COUNT,
-        database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+        counters::{TransactionTypeTimer, PROCESSOR_UNKNOWN_TYPE_COUNT},
+        database::{
+            execute_in_chunks, execute_in_chunks_with_conflict_strategy,
+            get_config_table_chunk_size, get_config_table_conflict_strategy, ArcDbPool,
+            ConflictStrategy,
+        },
         table_flags::TableFlags,
     },
 };
-use ahash::AHashMap;
+use ahash::{AHashMap, AHashSet};
 use anyhow::bail;
 use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
 use async_trait::async_trait;
@@ -23,24 +30,41 @@ use diesel::{
     query_builder::QueryFragment,
     ExpressionMethods,
 };
-use std::fmt::Debug;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc};
 use tracing::error;
 
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserTransactionProcessorConfig {
+    /// If set, each version is also written to `transactions`, versions already there aren't
+    /// written again, and a batch that's entirely written skips the inserts. Makes reprocessing
+    /// overlapping ranges cheap.
+    #[serde(default)]
+    pub skip_existing_versions: bool,
+}
+
 pub struct UserTransactionProcessor {
     connection_pool: ArcDbPool,
+    config: UserTransactionProcessorConfig,
     per_table_chunk_sizes: AHashMap<String, usize>,
+    per_table_conflict_strategies: AHashMap<String, ConflictStrategy>,
     deprecated_tables: TableFlags,
 }
 
 impl UserTransactionProcessor {
     pub fn new(
         connection_pool: ArcDbPool,
+        config: UserTransactionProcessorConfig,
         per_table_chunk_sizes: AHashMap<String, usize>,
+        per_table_conflict_strategies: AHashMap<String, ConflictStrategy>,
         deprecated_tables: TableFlags,
     ) -> Self {
         Self {
             connection_pool,
+            config,
             per_table_chunk_sizes,
+            per_table_conflict_strategies,
             deprecated_tables,
         }
     }
@@ -64,7 +88,10 @@ async fn insert_to_db(
     end_version: u64,
     user_transactions: &[UserTransactionModel],
     signatures: &[Signature],
+    transactions: &[TransactionModel],
     per_table_chunk_sizes: &AHashMap<String, usize>,
+    per_table_conflict_strategies: &AHashMap<String, ConflictStrategy>,
+    skip_existing_versions: bool,
 ) -> Result<(), diesel::result::Error> {
     tracing::trace!(
         name = name,
@@ -73,8 +100,15 @@ async fn insert_to_db(
         "Inserting to db",
     );
 
-    let ut = execute_in_chunks(
+    let ut = execute_in_chunks_with_conflict_strategy(
         conn.clone(),
+        // Transactions never change once committed
+        get_config_table_conflict_strategy(
+            "user_transactions",
+            per_table_conflict_strategies,
+            ConflictStrategy::DoNothing,
+        ),
+        insert_user_transactions_do_nothing_query,
         insert_user_transactions_query,
         user_transactions,
         get_config_table_chunk_size::<UserTransactionModel>(
@@ -83,12 +117,27 @@ async fn insert_to_db(
         ),
     );
     let is = execute_in_chunks(
-        conn,
+        conn.clone(),
         insert_signatures_query,
         signatures,
         get_config_table_chunk_size::<Signature>("signatures", per_table_chunk_sizes),
     );
 
+    if skip_existing_versions {
+        // A version in `transactions` is taken to be fully written, so it has to be inserted
+        // last
+        let (ut_res, is_res) = futures::join!(ut, is);
+        for res in [ut_res, is_res] {
+            res?;
+        }
+        return execute_in_chunks(
+            conn,
+            insert_transactions_query,
+            transactions,
+            get_config_table_chunk_size::<TransactionModel>("transactions", per_table_chunk_sizes),
+        )
+        .await;
+    }
     let (ut_res, is_res) = futures::join!(ut, is);
     for res in [ut_res, is_res] {
         res?;
@@ -96,6 +145,34 @@ async fn insert_to_db(
     Ok(())
 }
 
+/// Drops the rows of the versions that are already written.
+pub fn drop_existing_versions(
+    user_transactions: &mut Vec<UserTransactionModel>,
+    signatures: &mut Vec<Signature>,
+    transactions: &mut Vec<TransactionModel>,
+    existing_versions: &AHashSet<i64>,
+) {
+    user_transactions.retain(|txn| !existing_versions.contains(&txn.version));
+    signatures.retain(|sig| !existing_versions.contains(&sig.transaction_version));
+    transactions.retain(|txn| !existing_versions.contains(&txn.version));
+}
+
+pub fn insert_user_transactions_do_nothing_query(
+    items_to_insert: Vec<UserTransactionModel>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::user_transactions::dsl::*;
+    (
+        diesel::insert_into(schema::user_transactions::table)
+            .values(items_to_insert)
+            .on_conflict(version)
+            .do_nothing(),
+        None,
+    )
+}
+
 pub fn insert_user_transactions_query(
     items_to_insert: Vec<UserTransactionModel>,
 ) -> (
@@ -112,12 +189,33 @@ pub fn insert_user_transactions_query(
                 entry_function_contract_address.eq(excluded(entry_function_contract_address)),
                 entry_function_module_name.eq(excluded(entry_function_module_name)),
                 entry_function_function_name.eq(excluded(entry_function_function_name)),
+                script_payload.eq(excluded(script_payload)),
+                success.eq(excluded(success)),
+                vm_status.eq(excluded(vm_status)),
+                abort_code.eq(excluded(abort_code)),
+                abort_location.eq(excluded(abort_location)),
                 inserted_at.eq(excluded(inserted_at)),
             )),
         None,
     )
 }
 
+pub fn insert_transactions_query(
+    items_to_insert: Vec<TransactionModel>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::transactions::dsl::*;
+    (
+        diesel::insert_into(schema::transactions::table)
+            .values(items_to_insert)
+            .on_conflict(version)
+            .do_nothing(),
+        None,
+    )
+}
+
 pub fn insert_signatures_query(
     items_to_insert: Vec<Signature>,
 ) -> (
@@ -147,7 +245,7 @@ impl ProcessorTrait for UserTransactionProcessor {
 
     async fn process_transactions(
         &self,
-        transactions: Vec<Transaction>,
+        transactions: Arc<Vec<Transaction>>,
         start_version: u64,
         end_version: u64,
         _: Option<u64>,
@@ -155,8 +253,31 @@ impl ProcessorTrait for UserTransactionProcessor {
         let processing_start = std::time::Instant::now();
         let last_transaction_timestamp = transactions.last().unwrap().timestamp;
 
-        let (user_transactions, signatures) =
-            user_transaction_parse(transactions, self.deprecated_tables);
+        let mut transaction_models: Vec<TransactionModel> = if self.config.skip_existing_versions {
+            transactions
+                .iter()
+                .filter_map(TransactionModel::from_transaction)
+                .collect()
+        } else {
+            vec![]
+        };
+        let (mut user_transactions, mut signatures) =
+            user_transaction_parse(&transactions, self.deprecated_tables);
+        if self.config.skip_existing_versions {
+            let mut conn = self.get_conn().await;
+            let existing_versions = TransactionModel::existing_versions(
+                start_version as i64,
+                end_version as i64,
+                &mut conn,
+            )
+            .await?;
+            drop_existing_versions(
+                &mut user_transactions,
+                &mut signatures,
+                &mut transaction_models,
+                &existing_versions,
+            );
+        }
 
         let processing_duration_in_secs = processing_start.elapsed().as_secs_f64();
         let db_insertion_start = std::time::Instant::now();
@@ -168,7 +289,10 @@ impl ProcessorTrait for UserTransactionProcessor {
             end_version,
             &user_transactions,
             &signatures,
+            &transaction_models,
             &self.per_table_chunk_sizes,
+            &self.per_table_conflict_strategies,
+            self.config.skip_existing_versions,
         )
         .await;
         let db_insertion_duration_in_secs = db_insertion_start.elapsed().as_secs_f64();
@@ -180,6 +304,8 @@ impl ProcessorTrait for UserTransactionProcessor {
                     processing_duration_in_secs,
                     db_insertion_duration_in_secs,
                     last_transaction_timestamp,
+                    num_events: None,
+                    num_write_set_changes: None,
                 },
             )),
             Err(e) => {
@@ -202,12 +328,14 @@ impl ProcessorTrait for UserTransactionProcessor {
 
 /// Helper function to parse user transactions and signatures from the transaction data.
 pub fn user_transaction_parse(
-    transactions: Vec<Transaction>,
+    transactions: &[Transaction],
     deprecated_tables: TableFlags,
 ) -> (Vec<UserTransactionModel>, Vec<Signature>) {
     let mut signatures = vec![];
     let mut user_transactions = vec![];
+    let mut timer = TransactionTypeTimer::new(ProcessorName::UserTransactionProcessor.into());
     for txn in transactions {
+        timer.start(txn);
         let txn_version = txn.version as i64;
         let block_height = txn.block_height as i64;
         let txn_data = match txn.txn_data.as_ref() {
@@ -226,6 +354,7 @@ pub fn user_transaction_parse(
         if let TxnData::User(inner) = txn_data {
             let (user_transaction, sigs) = UserTransactionModel::from_transaction(
                 inner,
+                txn.info.as_ref(),
                 txn.timestamp.as_ref().unwrap(),
                 block_height,
                 txn.epoch as i64,
@@ -235,6 +364,7 @@ pub fn user_transaction_parse(
             user_transactions.push(user_transaction);
         }
     }
+    timer.finish();
 
     if deprecated_tables.contains(TableFlags::SIGNATURES) {
         signatures.clear();
@@ -242,3 +372,119 @@ pub fn user_transaction_parse(
 
     (user_transactions, signatures)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::{
+        transaction::v1::{
+            signature::Signature as SignatureEnum, transaction_payload::Payload, Ed25519Signature,
+            MoveScriptBytecode, MoveType, MoveTypes, ScriptPayload,
+            Signature as TransactionSignature, TransactionInfo, TransactionPayload,
+            UserTransaction, UserTransactionRequest,
+        },
+        util::timestamp::Timestamp,
+    };
+
+    fn user_transaction(version: u64) -> Transaction {
+        let request = UserTransactionRequest {
+            sender: "0x1".to_string(),
+            expiration_timestamp_secs: Some(Timestamp::default()),
+            signature: Some(TransactionSignature {
+                signature: Some(SignatureEnum::Ed25519(Ed25519Signature::default())),
+                ..TransactionSignature::default()
+            }),
+            ..UserTransactionRequest::default()
+        };
+        Transaction {
+            version,
+            timestamp: Some(Timestamp::default()),
+            txn_data: Some(TxnData::User(UserTransaction {
+                request: Some(request),
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn test_reprocessing_written_versions_inserts_nothing() {
+        let transactions: Vec<Transaction> = (100..105)
+            .map(|version| Transaction {
+                info: Some(TransactionInfo {
+                    success: true,
+                    ..TransactionInfo::default()
+                }),
+                ..user_transaction(version)
+            })
+            .collect();
+        let mut transaction_models: Vec<TransactionModel> = transactions
+            .iter()
+            .filter_map(TransactionModel::from_transaction)
+            .collect();
+        let (mut user_transactions, mut signatures) =
+            user_transaction_parse(&transactions, TableFlags::empty());
+        assert_eq!(transaction_models.len(), 5);
+        assert_eq!(signatures.len(), 5);
+
+        // An overlapping backfill, where the first versions were written by another run
+        drop_existing_versions(
+            &mut user_transactions,
+            &mut signatures,
+            &mut transaction_models,
+            &[100, 101, 102].into_iter().collect(),
+        );
+        let versions: Vec<i64> = transaction_models.iter().map(|txn| txn.version).collect();
+        assert_eq!(versions, vec![103, 104]);
+        assert_eq!(user_transactions.len(), 2);
+        assert_eq!(signatures.len(), 2);
+
+        // Fully written, so the batch skips the inserts
+        drop_existing_versions(
+            &mut user_transactions,
+            &mut signatures,
+            &mut transaction_models,
+            &(100..105).collect(),
+        );
+        assert!(user_transactions.is_empty());
+        assert!(signatures.is_empty());
+        assert!(transaction_models.is_empty());
+    }
+
+    #[test]
+    fn test_script_payload() {
+        let mut transaction = user_transaction(100);
+        let script = ScriptPayload {
+            code: Some(MoveScriptBytecode {
+                bytecode: vec![0xa1, 0x1c, 0xeb, 0x0b],
+                ..MoveScriptBytecode::default()
+            }),
+            type_arguments: vec![MoveType {
+                r#type: MoveTypes::U64 as i32,
+                content: None,
+            }],
+            arguments: vec!["\"100\"".to_string(), "true".to_string()],
+        };
+        if let Some(TxnData::User(user_transaction)) = transaction.txn_data.as_mut() {
+            user_transaction.request.as_mut().unwrap().payload = Some(TransactionPayload {
+                payload: Some(Payload::ScriptPayload(script)),
+                ..TransactionPayload::default()
+            });
+        }
+        let (user_transactions, _) =
+            user_transaction_parse(&[transaction, user_transaction(101)], TableFlags::empty());
+
+        let script_payload = user_transactions[0].script_payload.as_ref().unwrap();
+        assert!(!script_payload["code"].is_null());
+        assert_eq!(
+            script_payload["type_arguments"].as_array().unwrap().len(),
+            1
+        );
+        assert_eq!(
+            script_payload["arguments"],
+            serde_json::json!(["100", true])
+        );
+        // Not a script
+        assert_eq!(user_transactions[1].script_payload, None);
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Reprocessing a version range on demand, e.g. once a parser bug that affected it is fixed,
+//! without restarting the processor from there. Replays are queued on `POST /replay` and run one
+//! at a time on a stream of their own, through the same processor logic as live batches. Inserts
+//! are idempotent, so rows already written are left as they are or rewritten the same, and the
+//! processor's progress isn't touched.
+
+use crate::{
+    grpc_stream::TransactionsPBResponse,
+    processors::{Processor, ProcessorTrait},
+    utils::channel_byte_limiter::ChannelByteLimiter,
+};
+use anyhow::{bail, Result};
+use kanal::{AsyncReceiver, AsyncSender};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+/// Replays kept in `GET /replay`, the oldest finished ones are dropped past this.
+const MAX_FINISHED_REPLAYS: usize = 100;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReplayState {
+    Queued,
+    Running,
+    Done,
+    Failed,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Replay {
+    pub id: u64,
+    pub start_version: u64,
+    pub end_version: u64,
+    pub state: ReplayState,
+    pub num_transactions_processed: u64,
+    pub error: Option<String>,
+}
+
+/// The transactions of one replay, as streamed by the task `fetch` starts in `run_next`.
+pub struct ReplayStream {
+    pub receiver: AsyncReceiver<Arc<TransactionsPBResponse>>,
+    pub channel_byte_limiter: Arc<ChannelByteLimiter>,
+    pub fetcher_task: JoinHandle<()>,
+}
+
+/// Replays waiting to run, running and recently finished.
+pub struct ReplayQueue {
+    replays: Mutex<Vec<Replay>>,
+    sender: AsyncSender<u64>,
+    receiver: AsyncReceiver<u64>,
+}
+
+impl Default for ReplayQueue {
+    fn default() -> Self {
+        let (sender, receiver) = kanal::unbounded_async();
+        Self {
+            replays: Mutex::new(vec![]),
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl ReplayQueue {
+    /// Queues a replay of `start_version` to `end_version` inclusive.
+    pub fn enqueue(&self, start_version: u64, end_version: u64) -> Result<Replay> {
+        if start_version > end_version {
+            bail!(
+                "start_version {} is after end_version {}",
+                start_version,
+                end_version
+            );
+        }
+        let mut replays = self.replays.lock().unwrap();
+        let replay = Replay {
+            id: replays.last().map_or(1, |replay| replay.id + 1),
+            start_version,
+            end_version,
+            state: ReplayState::Queued,
+            num_transactions_processed: 0,
+            error: None,
+        };
+        replays.push(replay.clone());
+        let num_finished = replays
+            .iter()
+            .filter(|replay| matches!(replay.state, ReplayState::Done | ReplayState::Failed))
+            .count();
+        if num_finished > MAX_FINISHED_REPLAYS {
+            let oldest_finished = replays
+                .iter()
+                .position(|replay| matches!(replay.state, ReplayState::Done | ReplayState::Failed))
+                .unwrap();
+            replays.remove(oldest_finished);
+        }
+        // The receiver lives as long as the queue, so this can't fail
+        self.sender.as_sync().send(replay.id)?;
+        Ok(replay)
+    }
+
+    pub fn replays(&self) -> Vec<Replay> {
+        self.replays.lock().unwrap().clone()
+    }
+
+    fn update(&self, id: u64, f: impl FnOnce(&mut Replay)) -> Option<Replay> {
+        let mut replays = self.replays.lock().unwrap();
+        let replay = replays.iter_mut().find(|replay| replay.id == id)?;
+        f(replay);
+        Some(replay.clone())
+    }
+
+    /// Runs the queued replays one at a time as they come in, forever.
+    pub async fn run<F>(&self, processor: &Processor, chain_id: u64, fetch: F)
+    where
+        F: Fn(u64, u64) -> ReplayStream,
+    {
+        loop {
+            self.run_next(processor, chain_id, &fetch).await;
+        }
+    }
+
+    /// Waits for the next queued replay and runs it through `processor`. `fetch` starts streaming
+    /// the transactions of a range, closing the channel once it has sent the last of them.
+    /// Returns the replay once it's done or failed.
+    pub async fn run_next<F>(&self, processor: &Processor, chain_id: u64, fetch: &F) -> Replay
+    where
+        F: Fn(u64, u64) -> ReplayStream,
+    {
+        let processor_name = processor.name();
+        let id = self
+            .receiver
+            .recv()
+            .await
+            .expect("[Replay] Queue is never closed");
+        let replay = self
+            .update(id, |replay| replay.state = ReplayState::Running)
+            .unwrap();
+        info!(
+            processor_name = processor_name,
+            replay_id = id,
+            start_version = replay.start_version,
+            end_version = replay.end_version,
+            "[Replay] Reprocessing versions"
+        );
+
+        let stream = fetch(replay.start_version, replay.end_version);
+        let mut next_version = replay.start_version;
+        let res = loop {
+            let Ok(transactions_pb) = stream.receiver.recv().await else {
+                break if next_version > replay.end_version {
+                    Ok(())
+                } else {
+                    Err(anyhow::anyhow!(
+                        "Stream ended before version {}",
+                        next_version
+                    ))
+                };
+            };
+            stream
+                .channel_byte_limiter
+                .release(transactions_pb.size_in_bytes);
+            let transactions_pb = Arc::unwrap_or_clone(transactions_pb);
+            let num_transactions = transactions_pb.transactions.len() as u64;
+            // Skipped like in the worker, as everything in the batch may have been filtered out
+            if num_transactions > 0 {
+                let res = processor
+                    .process_transactions_with_extensions(
+                        Arc::new(transactions_pb.transactions),
+                        transactions_pb.extensions,
+                        transactions_pb.start_version,
+                        transactions_pb.end_version,
+                        Some(chain_id),
+                    )
+                    .await;
+                if let Err(e) = res {
+                    break Err(e);
+                }
+            }
+            next_version = transactions_pb.end_version + 1;
+            self.update(id, |replay| {
+                replay.num_transactions_processed += num_transactions;
+            });
+        };
+        // The fetcher panics once nothing receives what it fetched, so it's stopped before the
+        // channel is dropped
+        stream.fetcher_task.abort();
+        while let Ok(Some(transactions_pb)) = stream.receiver.try_recv() {
+            stream
+                .channel_byte_limiter
+                .release(transactions_pb.size_in_bytes);
+        }
+
+        let replay = self
+            .update(id, |replay| match &res {
+                Ok(()) => replay.state = ReplayState::Done,
+                Err(e) => {
+                    replay.state = ReplayState::Failed;
+                    replay.error = Some(format!("{:#}", e));
+                },
+            })
+            .unwrap();
+        match res {
+            Ok(()) => info!(
+                processor_name = processor_name,
+                replay_id = id,
+                start_version = replay.start_version,
+                end_version = replay.end_version,
+                num_transactions_processed = replay.num_transactions_processed,
+                "[Replay] Finished reprocessing versions"
+            ),
+            Err(e) => error!(
+                processor_name = processor_name,
+                replay_id = id,
+                start_version = replay.start_version,
+                end_version = replay.end_version,
+                error = ?e,
+                "[Replay] Failed to reprocess versions"
+            ),
+        }
+        replay
+    }
+}
+
+impl server_framework::ReplayHandler for ReplayQueue {
+    fn enqueue(&self, start_version: u64, end_version: u64) -> Result<serde_json::Value> {
+        Ok(serde_json::to_value(
+            self.enqueue(start_version, end_version)?,
+        )?)
+    }
+
+    fn status(&self) -> serde_json::Value {
+        serde_json::to_value(self.replays()).unwrap_or_default()
+    }
+}
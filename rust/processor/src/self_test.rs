@@ -0,0 +1,301 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pre-flight check that parses a bundled set of known transactions and compares the rows each
+//! table would get against committed golden JSON, the same files the diff tests use. The
+//! processor refuses to start if anything diverges, so a behavior change can't reach production
+//! by accident.
+
+use crate::{
+    db::{
+        common::models::default_models::{
+            raw_block_metadata_transactions::BlockMetadataTransactionConvertible,
+            raw_current_table_items::CurrentTableItemConvertible,
+            raw_table_items::TableItemConvertible, raw_table_metadata::TableMetadataConvertible,
+        },
+        postgres::models::default_models::{
+            block_end_transactions::BlockEndTransaction,
+            block_metadata_transactions::BlockMetadataTransactionModel,
+            move_tables::{CurrentTableItem, TableItem, TableMetadata},
+        },
+    },
+    processors::{
+        default_processor, events_processor, user_transaction_processor, ProcessorConfig,
+    },
+    utils::table_flags::TableFlags,
+};
+use anyhow::{bail, Context, Result};
+use aptos_protos::transaction::v1::Transaction;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// Columns filled in by the DB rather than the parser.
+const DB_ONLY_FIELDS: [&str; 1] = ["inserted_at"];
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SelfTestConfig {
+    /// Directory of transactions in the `testing-transactions` JSON format, one per file. Each
+    /// file is a case named after its file stem.
+    pub transactions_dir: PathBuf,
+    /// Golden output laid out as `<processor name>/<case>/<table>.json`, like
+    /// `sdk_expected_db_output_files`. Cases without a directory for this processor are skipped,
+    /// and only tables with a golden file are compared.
+    pub golden_dir: PathBuf,
+}
+
+/// A field whose parsed value differs from the golden file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub case: String,
+    pub table: String,
+    /// None if the tables have a different number of rows.
+    pub row: Option<usize>,
+    pub field: Option<String>,
+    pub expected: Value,
+    pub actual: Value,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "case {} table {}", self.case, self.table)?;
+        if let Some(row) = self.row {
+            write!(f, " row {}", row)?;
+        }
+        if let Some(field) = &self.field {
+            write!(f, " field {}", field)?;
+        }
+        write!(f, ": expected {}, got {}", self.expected, self.actual)
+    }
+}
+
+impl SelfTestConfig {
+    /// Runs every case for this processor and fails with all the divergences found.
+    pub fn run(&self, processor_config: &ProcessorConfig) -> Result<()> {
+        let processor_name = processor_config.name();
+        let mut cases_checked = 0;
+        let mut divergences = vec![];
+        for (case, transaction) in self.load_transactions()? {
+            let case_dir = self.golden_dir.join(processor_name).join(&case);
+            if !case_dir.is_dir() {
+                continue;
+            }
+            let tables = parse_tables(processor_config, vec![transaction])?;
+            for (table, actual) in tables {
+                let golden_path = case_dir.join(format!("{}.json", table));
+                if !golden_path.exists() {
+                    continue;
+                }
+                let expected = read_json(&golden_path)?;
+                divergences.extend(diff_table(&case, table, &expected, &actual));
+            }
+            cases_checked += 1;
+        }
+        if cases_checked == 0 {
+            bail!(
+                "[Self Test] No golden files for {} in {:?}",
+                processor_name,
+                self.golden_dir
+            );
+        }
+        if !divergences.is_empty() {
+            let report = divergences
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join("\n");
+            bail!(
+                "[Self Test] {} output diverged from the golden files:\n{}",
+                processor_name,
+                report
+            );
+        }
+        info!(
+            processor_name,
+            cases_checked, "[Self Test] Output matches the golden files"
+        );
+        Ok(())
+    }
+
+    /// Transactions by case name, in name order.
+    fn load_transactions(&self) -> Result<BTreeMap<String, Transaction>> {
+        let mut transactions = BTreeMap::new();
+        let entries = std::fs::read_dir(&self.transactions_dir)
+            .with_context(|| format!("Failed to read directory {:?}", self.transactions_dir))?;
+        for entry in entries {
+            let path = entry?.path();
+            if !path.extension().is_some_and(|ext| ext == "json") {
+                continue;
+            }
+            let case = path.file_stem().unwrap().to_string_lossy().to_string();
+            let transaction = serde_json::from_value(read_json(&path)?)
+                .with_context(|| format!("Failed to parse transaction {:?}", path))?;
+            transactions.insert(case, transaction);
+        }
+        Ok(transactions)
+    }
+}
+
+fn read_json(path: &Path) -> Result<Value> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    serde_json::from_str(&content).with_context(|| format!("Failed to parse JSON at {:?}", path))
+}
+
+fn to_rows<T: Serialize>(rows: &[T]) -> Result<Value> {
+    Ok(serde_json::to_value(rows)?)
+}
+
+/// The rows each table would get, for the processors whose parsing doesn't need the DB.
+fn parse_tables(
+    processor_config: &ProcessorConfig,
+    transactions: Vec<Transaction>,
+) -> Result<BTreeMap<&'static str, Value>> {
+    let mut tables = BTreeMap::new();
+    match processor_config {
+        ProcessorConfig::DefaultProcessor(_) => {
+            let block_end_transactions: Vec<BlockEndTransaction> = transactions
+                .iter()
+                .filter_map(BlockEndTransaction::from_transaction)
+                .collect();
+            let (block_metadata_transactions, table_items, current_table_items, table_metadata) =
+                default_processor::process_transactions(&transactions);
+            let block_metadata_transactions: Vec<BlockMetadataTransactionModel> =
+                block_metadata_transactions
+                    .into_iter()
+                    .map(BlockMetadataTransactionModel::from_raw)
+                    .collect();
+            let table_items: Vec<TableItem> = table_items.iter().map(TableItem::from_raw).collect();
+            let current_table_items: Vec<CurrentTableItem> = current_table_items
+                .iter()
+                .map(CurrentTableItem::from_raw)
+                .collect();
+            let table_metadata: Vec<TableMetadata> =
+                table_metadata.iter().map(TableMetadata::from_raw).collect();
+            tables.insert(
+                "block_metadata_transactions",
+                to_rows(&block_metadata_transactions)?,
+            );
+            tables.insert("block_end_transactions", to_rows(&block_end_transactions)?);
+            tables.insert("table_items", to_rows(&table_items)?);
+            tables.insert("current_table_items", to_rows(&current_table_items)?);
+            tables.insert("table_metadatas", to_rows(&table_metadata)?);
+        },
+        ProcessorConfig::EventsProcessor(_) => {
+            let events = events_processor::process_transactions(&transactions);
+            tables.insert("events", to_rows(&events)?);
+        },
+        ProcessorConfig::UserTransactionProcessor(_) => {
+            let (user_transactions, signatures) =
+                user_transaction_processor::user_transaction_parse(
+                    &transactions,
+                    TableFlags::empty(),
+                );
+            tables.insert("user_transactions", to_rows(&user_transactions)?);
+            tables.insert("signatures", to_rows(&signatures)?);
+        },
+        _ => bail!(
+            "[Self Test] Not supported for {}, its parsing needs the DB",
+            processor_config.name()
+        ),
+    }
+    Ok(tables)
+}
+
+fn strip_db_only_fields(row: &Value) -> Map<String, Value> {
+    let mut row = row.as_object().cloned().unwrap_or_default();
+    for field in DB_ONLY_FIELDS {
+        row.remove(field);
+    }
+    row
+}
+
+/// Compares a table row by row and field by field. Rows are compared in order, so golden files
+/// must list them in the order the processor emits them.
+fn diff_table(case: &str, table: &str, expected: &Value, actual: &Value) -> Vec<Divergence> {
+    let empty = vec![];
+    let expected_rows = expected.as_array().unwrap_or(&empty);
+    let actual_rows = actual.as_array().unwrap_or(&empty);
+    let mut divergences = vec![];
+    if expected_rows.len() != actual_rows.len() {
+        divergences.push(Divergence {
+            case: case.to_string(),
+            table: table.to_string(),
+            row: None,
+            field: None,
+            expected: format!("{} rows", expected_rows.len()).into(),
+            actual: format!("{} rows", actual_rows.len()).into(),
+        });
+    }
+    for (row, (expected_row, actual_row)) in expected_rows.iter().zip(actual_rows).enumerate() {
+        let expected_row = strip_db_only_fields(expected_row);
+        let actual_row = strip_db_only_fields(actual_row);
+        let mut fields = expected_row
+            .keys()
+            .chain(actual_row.keys())
+            .collect::<Vec<_>>();
+        fields.sort();
+        fields.dedup();
+        for field in fields {
+            let expected_value = expected_row.get(field).cloned().unwrap_or(Value::Null);
+            let actual_value = actual_row.get(field).cloned().unwrap_or(Value::Null);
+            if expected_value != actual_value {
+                divergences.push(Divergence {
+                    case: case.to_string(),
+                    table: table.to_string(),
+                    row: Some(row),
+                    field: Some(field.clone()),
+                    expected: expected_value,
+                    actual: actual_value,
+                });
+            }
+        }
+    }
+    divergences
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_table_reports_diverged_fields() {
+        let expected = json!([
+            {"transaction_version": 1, "event_index": 0, "inserted_at": "2024-01-01T00:00:00"},
+            {"transaction_version": 1, "event_index": 1, "type_": "0x1::a::B"},
+        ]);
+        let actual = json!([
+            {"transaction_version": 1, "event_index": 0},
+            {"transaction_version": 1, "event_index": 1, "type_": "0x1::a::C"},
+        ]);
+        assert_eq!(
+            diff_table("case", "events", &expected, &actual),
+            vec![Divergence {
+                case: "case".to_string(),
+                table: "events".to_string(),
+                row: Some(1),
+                field: Some("type_".to_string()),
+                expected: json!("0x1::a::B"),
+                actual: json!("0x1::a::C"),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_diff_table_reports_row_count() {
+        let expected = json!([{"transaction_version": 1}]);
+        let divergences = diff_table("case", "events", &expected, &json!([]));
+        assert_eq!(divergences.len(), 1);
+        assert_eq!(
+            divergences[0].to_string(),
+            "case case table events: expected \"1 rows\", got \"0 rows\""
+        );
+    }
+}
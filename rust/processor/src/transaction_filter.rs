@@ -1,9 +1,59 @@
+use crate::utils::{
+    counters::{TRANSACTION_FILTER_EVALUATED_COUNT, TRANSACTION_FILTER_MATCHED_COUNT},
+    util::standardize_address,
+};
 use aptos_protos::transaction::v1::{
     transaction::{TransactionType, TxnData},
     transaction_payload::Payload,
     Transaction,
 };
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// Inclusive numeric range. Either bound can be left out to leave that side open.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct NumericRange {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl NumericRange {
+    pub fn contains(&self, value: u64) -> bool {
+        self.min.is_none_or(|min| value >= min) && self.max.is_none_or(|max| value <= max)
+    }
+}
+
+/// Kinds of user transaction payloads, for `payload_types`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadKind {
+    EntryFunction,
+    Script,
+    Multisig,
+    WriteSet,
+}
+
+impl PayloadKind {
+    fn of(payload: &Payload) -> Self {
+        match payload {
+            Payload::EntryFunctionPayload(_) => PayloadKind::EntryFunction,
+            Payload::ScriptPayload(_) => PayloadKind::Script,
+            Payload::MultisigPayload(_) => PayloadKind::Multisig,
+            Payload::WriteSetPayload(_) => PayloadKind::WriteSet,
+        }
+    }
+}
+
+fn deserialize_standardized_addresses<'de, D>(
+    deserializer: D,
+) -> Result<Option<ahash::HashSet<String>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let addresses = Option::<Vec<String>>::deserialize(deserializer)?;
+    Ok(addresses.map(|addresses| addresses.iter().map(|a| standardize_address(a)).collect()))
+}
 
 /// Allows filtering transactions based on various criteria
 /// The criteria are combined with `AND`
@@ -13,26 +63,160 @@ use serde::{Deserialize, Serialize};
 #[serde(deny_unknown_fields)]
 #[serde(default)]
 pub struct TransactionFilter {
+    // Label of the filter's evaluated and matched counters, `default` if unset
+    name: Option<String>,
     // Only allow transactions from these contract addresses
     focus_contract_addresses: Option<ahash::HashSet<String>>,
     // Skip transactions from these sender addresses
     skip_sender_addresses: Option<ahash::HashSet<String>>,
+    // Only allow user transactions from these sender addresses, standardized on load
+    #[serde(deserialize_with = "deserialize_standardized_addresses")]
+    sender_in: Option<ahash::HashSet<String>>,
     // Skip all transactions that aren't user transactions
     focus_user_transactions: bool,
+    // Only allow transactions whose gas used is within this range
+    gas_used: Option<NumericRange>,
+    // Only allow user transactions whose gas unit price is within this range
+    gas_unit_price: Option<NumericRange>,
+    // Only allow user transactions with these kinds of payload, e.g. `[script]`
+    payload_types: Option<ahash::HashSet<PayloadKind>>,
+    // Only allow this fraction of transactions, e.g. `0.01` for 1%, picked by version so the same
+    // versions are kept on every run
+    sample_fraction: Option<f64>,
+}
+
+/// Versions are sampled in buckets of this many, so fractions are rounded to 1 in a million.
+const SAMPLE_BUCKETS: u64 = 1_000_000;
+
+/// A hash of the version that doesn't change between runs or builds, unlike `DefaultHasher`.
+/// This is the finalizer of SplitMix64, which spreads consecutive versions evenly over buckets.
+fn version_hash(version: u64) -> u64 {
+    let mut hash = version.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    hash ^ (hash >> 31)
 }
 
 impl TransactionFilter {
     pub fn new(
         focus_contract_addresses: Option<ahash::HashSet<String>>,
         skip_sender_addresses: Option<ahash::HashSet<String>>,
+        sender_in: Option<ahash::HashSet<String>>,
         focus_user_transactions: bool,
+        gas_used: Option<NumericRange>,
+        gas_unit_price: Option<NumericRange>,
+        payload_types: Option<ahash::HashSet<PayloadKind>>,
+        sample_fraction: Option<f64>,
     ) -> Self {
         // TODO: normalize addresses
         Self {
+            name: None,
             focus_contract_addresses,
             skip_sender_addresses,
+            sender_in: sender_in.map(|addresses| {
+                addresses
+                    .iter()
+                    .map(|address| standardize_address(address))
+                    .collect()
+            }),
             focus_user_transactions,
+            gas_used,
+            gas_unit_price,
+            payload_types,
+            sample_fraction,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or("default")
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let Some(sample_fraction) = self.sample_fraction {
+            if !(sample_fraction > 0.0 && sample_fraction <= 1.0) {
+                anyhow::bail!(
+                    "sample_fraction must be above 0 and at most 1, got {}",
+                    sample_fraction
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the version is in the sample. Doesn't depend on anything but the version, so
+    /// reruns and other processors with the same fraction sample the same versions.
+    pub fn is_sampled(&self, version: u64) -> bool {
+        let Some(sample_fraction) = self.sample_fraction else {
+            return true;
+        };
+        let num_sampled_buckets = (sample_fraction * SAMPLE_BUCKETS as f64).round() as u64;
+        version_hash(version) % SAMPLE_BUCKETS < num_sampled_buckets
+    }
+
+    /// Transactions without the gas field being filtered on (e.g. no gas unit price outside of
+    /// user transactions) don't match
+    fn gas_matches(&self, transaction: &Transaction) -> bool {
+        if let Some(gas_used) = &self.gas_used {
+            match transaction.info.as_ref() {
+                Some(info) if gas_used.contains(info.gas_used) => {},
+                _ => return false,
+            }
         }
+        if let Some(gas_unit_price) = &self.gas_unit_price {
+            match transaction.txn_data.as_ref() {
+                Some(TxnData::User(user_transaction))
+                    if user_transaction
+                        .request
+                        .as_ref()
+                        .is_some_and(|utr| gas_unit_price.contains(utr.gas_unit_price)) => {},
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Transactions without a sender (anything but user transactions) don't match
+    fn sender_matches(&self, transaction: &Transaction) -> bool {
+        let Some(sender_in) = &self.sender_in else {
+            return true;
+        };
+        match transaction.txn_data.as_ref() {
+            Some(TxnData::User(user_transaction)) => user_transaction
+                .request
+                .as_ref()
+                .is_some_and(|utr| sender_in.contains(&standardize_address(&utr.sender))),
+            _ => false,
+        }
+    }
+
+    /// Transactions without a payload (anything but user transactions) don't match
+    fn payload_type_matches(&self, transaction: &Transaction) -> bool {
+        let Some(payload_types) = &self.payload_types else {
+            return true;
+        };
+        match transaction.txn_data.as_ref() {
+            Some(TxnData::User(user_transaction)) => user_transaction
+                .request
+                .as_ref()
+                .and_then(|utr| utr.payload.as_ref())
+                .and_then(|payload| payload.payload.as_ref())
+                .is_some_and(|payload| payload_types.contains(&PayloadKind::of(payload))),
+            _ => false,
+        }
+    }
+
+    /// Keeps the transactions that should be included, counting how many the filter was
+    /// evaluated on and how many it matched.
+    pub fn retain(&self, processor_name: &str, transactions: &mut Vec<Transaction>) {
+        let num_evaluated = transactions.len();
+        transactions.retain(|txn| self.include(txn));
+        let labels = [processor_name, self.name()];
+        TRANSACTION_FILTER_EVALUATED_COUNT
+            .with_label_values(&labels)
+            .inc_by(num_evaluated as u64);
+        TRANSACTION_FILTER_MATCHED_COUNT
+            .with_label_values(&labels)
+            .inc_by(transactions.len() as u64);
     }
 
     /// Returns true if the transaction should be included
@@ -44,6 +228,14 @@ impl TransactionFilter {
             return false;
         }
 
+        if !self.is_sampled(transaction.version)
+            || !self.gas_matches(transaction)
+            || !self.sender_matches(transaction)
+            || !self.payload_type_matches(transaction)
+        {
+            return false;
+        }
+
         // If it's not a user transaction, we can skip the rest of the checks
         if !is_user_txn {
             return true;
@@ -78,3 +270,276 @@ impl TransactionFilter {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{
+        EntryFunctionPayload, ScriptPayload, TransactionInfo, TransactionPayload, UserTransaction,
+        UserTransactionRequest,
+    };
+
+    fn user_txn(gas_used: u64, gas_unit_price: u64) -> Transaction {
+        Transaction {
+            r#type: TransactionType::User as i32,
+            info: Some(TransactionInfo {
+                gas_used,
+                ..TransactionInfo::default()
+            }),
+            txn_data: Some(TxnData::User(UserTransaction {
+                request: Some(UserTransactionRequest {
+                    gas_unit_price,
+                    ..UserTransactionRequest::default()
+                }),
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    fn block_metadata_txn(gas_used: u64) -> Transaction {
+        Transaction {
+            r#type: TransactionType::BlockMetadata as i32,
+            info: Some(TransactionInfo {
+                gas_used,
+                ..TransactionInfo::default()
+            }),
+            ..Transaction::default()
+        }
+    }
+
+    fn range(min: Option<u64>, max: Option<u64>) -> Option<NumericRange> {
+        Some(NumericRange { min, max })
+    }
+
+    #[test]
+    fn test_gas_used_range_boundaries() {
+        let filter = TransactionFilter::new(
+            None,
+            None,
+            None,
+            false,
+            range(Some(10), Some(20)),
+            None,
+            None,
+            None,
+        );
+        assert!(!filter.include(&user_txn(9, 100)));
+        assert!(filter.include(&user_txn(10, 100)));
+        assert!(filter.include(&user_txn(20, 100)));
+        assert!(!filter.include(&user_txn(21, 100)));
+        // gas_used is on the transaction info, so it applies to every transaction type
+        assert!(filter.include(&block_metadata_txn(15)));
+        assert!(!filter.include(&block_metadata_txn(21)));
+    }
+
+    #[test]
+    fn test_gas_unit_price_open_ended_range() {
+        let filter = TransactionFilter::new(
+            None,
+            None,
+            None,
+            false,
+            None,
+            range(Some(150), None),
+            None,
+            None,
+        );
+        assert!(!filter.include(&user_txn(10, 149)));
+        assert!(filter.include(&user_txn(10, 150)));
+        assert!(filter.include(&user_txn(10, u64::MAX)));
+
+        let filter = TransactionFilter::new(
+            None,
+            None,
+            None,
+            false,
+            None,
+            range(None, Some(150)),
+            None,
+            None,
+        );
+        assert!(filter.include(&user_txn(10, 0)));
+        assert!(filter.include(&user_txn(10, 150)));
+        assert!(!filter.include(&user_txn(10, 151)));
+    }
+
+    #[test]
+    fn test_gas_unit_price_without_gas_field_does_not_match() {
+        let filter =
+            TransactionFilter::new(None, None, None, false, None, range(None, None), None, None);
+        assert!(filter.include(&user_txn(10, 100)));
+        assert!(!filter.include(&block_metadata_txn(10)));
+    }
+
+    #[test]
+    fn test_gas_ranges_combine_with_other_criteria() {
+        let skip_sender_addresses = Some(["0x7".to_string()].into_iter().collect());
+        let filter = TransactionFilter::new(
+            None,
+            skip_sender_addresses,
+            None,
+            true,
+            range(Some(10), None),
+            range(Some(100), Some(200)),
+            None,
+            None,
+        );
+        assert!(filter.include(&user_txn(10, 100)));
+        assert!(!filter.include(&user_txn(9, 100)));
+        assert!(!filter.include(&user_txn(10, 201)));
+        assert!(!filter.include(&block_metadata_txn(10)));
+
+        let mut skipped_sender_txn = user_txn(10, 100);
+        if let Some(TxnData::User(user_transaction)) = skipped_sender_txn.txn_data.as_mut() {
+            user_transaction.request.as_mut().unwrap().sender = "0x7".to_string();
+        }
+        assert!(!filter.include(&skipped_sender_txn));
+    }
+
+    fn user_txn_from(sender: &str) -> Transaction {
+        let mut txn = user_txn(10, 100);
+        if let Some(TxnData::User(user_transaction)) = txn.txn_data.as_mut() {
+            user_transaction.request.as_mut().unwrap().sender = sender.to_string();
+        }
+        txn
+    }
+
+    fn sender_in(addresses: &[&str]) -> Option<ahash::HashSet<String>> {
+        Some(
+            addresses
+                .iter()
+                .map(|address| address.to_string())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_sender_in_membership() {
+        let filter = TransactionFilter::new(
+            None,
+            None,
+            sender_in(&["0xa", "0xb"]),
+            false,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(filter.include(&user_txn_from("0xa")));
+        assert!(filter.include(&user_txn_from("0xb")));
+        assert!(!filter.include(&user_txn_from("0xc")));
+        // Only user transactions have a sender
+        assert!(!filter.include(&block_metadata_txn(10)));
+    }
+
+    #[test]
+    fn test_sender_in_standardizes_addresses() {
+        let full_address = "0x000000000000000000000000000000000000000000000000000000000000000a";
+        let filter = TransactionFilter::new(
+            None,
+            None,
+            sender_in(&["0x0A"]),
+            false,
+            None,
+            None,
+            None,
+            None,
+        );
+        assert!(filter.include(&user_txn_from(full_address)));
+        assert!(filter.include(&user_txn_from("0xa")));
+        assert!(filter.include(&user_txn_from("0x00a")));
+        assert!(!filter.include(&user_txn_from("0xa0")));
+
+        // Addresses from the config are standardized too
+        let filter: TransactionFilter =
+            serde_json::from_str(r#"{"sender_in": ["0x000a"]}"#).unwrap();
+        assert!(filter.include(&user_txn_from(full_address)));
+        assert!(!filter.include(&user_txn_from("0xb")));
+    }
+
+    fn txn_with_payload(payload: Payload) -> Transaction {
+        let mut txn = user_txn(10, 100);
+        if let Some(TxnData::User(user_transaction)) = txn.txn_data.as_mut() {
+            user_transaction.request.as_mut().unwrap().payload = Some(TransactionPayload {
+                payload: Some(payload),
+                ..TransactionPayload::default()
+            });
+        }
+        txn
+    }
+
+    #[test]
+    fn test_payload_types_match_script_transactions() {
+        let filter: TransactionFilter =
+            serde_json::from_str(r#"{"payload_types": ["script"]}"#).unwrap();
+        let script_txn = txn_with_payload(Payload::ScriptPayload(ScriptPayload::default()));
+        let entry_function_txn = txn_with_payload(Payload::EntryFunctionPayload(
+            EntryFunctionPayload::default(),
+        ));
+        assert!(filter.include(&script_txn));
+        assert!(!filter.include(&entry_function_txn));
+        // Without a payload there's nothing to match
+        assert!(!filter.include(&user_txn(10, 100)));
+        assert!(!filter.include(&block_metadata_txn(10)));
+    }
+
+    /// Versions kept by the filter, out of the first `num_versions`.
+    fn sampled_versions(filter: &TransactionFilter, num_versions: u64) -> Vec<u64> {
+        (0..num_versions)
+            .filter(|&version| {
+                filter.include(&Transaction {
+                    version,
+                    ..block_metadata_txn(10)
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_sampling_is_deterministic() {
+        let config = r#"{"sample_fraction": 0.01}"#;
+        let first_run: TransactionFilter = serde_json::from_str(config).unwrap();
+        let second_run: TransactionFilter = serde_json::from_str(config).unwrap();
+        let sampled = sampled_versions(&first_run, 100_000);
+        assert_eq!(sampled, sampled_versions(&second_run, 100_000));
+        // Roughly 1% of the versions, spread out rather than the first ones
+        assert!((900..=1_100).contains(&sampled.len()), "{}", sampled.len());
+        assert!(sampled.last().unwrap() > &90_000);
+
+        let filter = TransactionFilter::new(None, None, None, false, None, None, None, Some(1.0));
+        assert_eq!(sampled_versions(&filter, 1_000).len(), 1_000);
+        assert!(
+            serde_json::from_str::<TransactionFilter>(r#"{"sample_fraction": 0}"#)
+                .unwrap()
+                .validate()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_retain_counts_matches() {
+        let filter: TransactionFilter =
+            serde_json::from_str(r#"{"name": "test_senders", "sender_in": ["0x1"]}"#).unwrap();
+        let mut transactions = vec![
+            user_txn_from("0x1"),
+            user_txn_from("0x2"),
+            user_txn_from("0x1"),
+        ];
+        filter.retain("test_retain_processor", &mut transactions);
+        assert_eq!(transactions.len(), 2);
+        let labels = ["test_retain_processor", "test_senders"];
+        assert_eq!(
+            TRANSACTION_FILTER_EVALUATED_COUNT
+                .with_label_values(&labels)
+                .get(),
+            3
+        );
+        assert_eq!(
+            TRANSACTION_FILTER_MATCHED_COUNT
+                .with_label_values(&labels)
+                .get(),
+            2
+        );
+    }
+}
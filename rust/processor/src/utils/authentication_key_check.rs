@@ -0,0 +1,358 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Opt-in check, with `check_authentication_keys`, that each user transaction's sender
+//! signature is consistent with the sender's authentication key. This is not signature
+//! verification: the stream doesn't carry the BCS bytes a signature is over, so the signature
+//! itself can't be checked. What is checked is that the signature is well formed and that its
+//! public keys hash to the authentication key in the sender's `0x1::account::Account` as written
+//! by the transaction. A mismatch means the transaction was altered after it was executed, e.g.
+//! upstream of the indexer. Only Ed25519 and MultiEd25519 sender signatures are checked.
+
+use crate::utils::{
+    counters::AUTHENTICATION_KEY_MISMATCH_COUNT,
+    util::{sha3_256, standardize_address, standardize_address_from_bytes},
+};
+use aptos_protos::transaction::v1::{
+    account_signature::Signature as AccountSignatureEnum, signature::Signature as SignatureEnum,
+    transaction::TxnData, write_set_change::Change, Ed25519Signature, MultiEd25519Signature,
+    Transaction,
+};
+use serde::Deserialize;
+use tracing::error;
+
+const ED25519_SCHEME: u8 = 0;
+const MULTI_ED25519_SCHEME: u8 = 1;
+const ED25519_PUBLIC_KEY_LENGTH: usize = 32;
+const ED25519_SIGNATURE_LENGTH: usize = 64;
+const ACCOUNT_RESOURCE_TYPE: &str = "0x1::account::Account";
+
+#[derive(Deserialize)]
+struct AccountResource {
+    authentication_key: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthenticationKeyCheck {
+    Matched,
+    Mismatched(String),
+    /// Another signature scheme, or the transaction doesn't show the sender's authentication key
+    Skipped,
+}
+
+/// Sender's signature, if it's one of the schemes checked
+enum SenderSignature<'a> {
+    Ed25519(&'a Ed25519Signature),
+    MultiEd25519(&'a MultiEd25519Signature),
+}
+
+impl SenderSignature<'_> {
+    fn type_(&self) -> &'static str {
+        match self {
+            SenderSignature::Ed25519(_) => "ed25519_signature",
+            SenderSignature::MultiEd25519(_) => "multi_ed25519_signature",
+        }
+    }
+
+    /// Authentication key of the public keys, or why the signature is malformed
+    fn authentication_key(&self) -> Result<[u8; 32], String> {
+        match self {
+            SenderSignature::Ed25519(sig) => {
+                check_public_key_length(&sig.public_key)?;
+                check_signature_length(&sig.signature)?;
+                let mut bytes = sig.public_key.clone();
+                bytes.push(ED25519_SCHEME);
+                Ok(sha3_256(&bytes))
+            },
+            SenderSignature::MultiEd25519(sig) => {
+                let num_signatures = sig.signatures.len();
+                if num_signatures != sig.public_key_indices.len() {
+                    return Err(format!(
+                        "{} signatures for {} public key indices",
+                        num_signatures,
+                        sig.public_key_indices.len()
+                    ));
+                }
+                if (num_signatures as u32) < sig.threshold {
+                    return Err(format!(
+                        "{} signatures below the threshold of {}",
+                        num_signatures, sig.threshold
+                    ));
+                }
+                if !sig.public_key_indices.windows(2).all(|w| w[0] < w[1])
+                    || sig
+                        .public_key_indices
+                        .last()
+                        .is_some_and(|index| *index as usize >= sig.public_keys.len())
+                {
+                    return Err("Public key indices out of order or out of range".to_string());
+                }
+                for signature in &sig.signatures {
+                    check_signature_length(signature)?;
+                }
+                let mut bytes = vec![];
+                for public_key in &sig.public_keys {
+                    check_public_key_length(public_key)?;
+                    bytes.extend_from_slice(public_key);
+                }
+                bytes.push(sig.threshold as u8);
+                bytes.push(MULTI_ED25519_SCHEME);
+                Ok(sha3_256(&bytes))
+            },
+        }
+    }
+}
+
+fn check_public_key_length(public_key: &[u8]) -> Result<(), String> {
+    if public_key.len() != ED25519_PUBLIC_KEY_LENGTH {
+        return Err(format!("{} byte public key", public_key.len()));
+    }
+    Ok(())
+}
+
+fn check_signature_length(signature: &[u8]) -> Result<(), String> {
+    if signature.len() != ED25519_SIGNATURE_LENGTH {
+        return Err(format!("{} byte signature", signature.len()));
+    }
+    Ok(())
+}
+
+/// Authentication key of the sender as written by the transaction. Every transaction with a
+/// sequence number bumps it, so writes the sender's account, but one rotating the key writes the
+/// new key, not the one it was signed with.
+fn written_authentication_key(txn: &Transaction, sender: &str) -> Option<String> {
+    let info = txn.info.as_ref()?;
+    info.changes
+        .iter()
+        .find_map(|change| match change.change.as_ref()? {
+            Change::WriteResource(resource)
+                if resource.type_str == ACCOUNT_RESOURCE_TYPE
+                    && standardize_address(&resource.address) == sender =>
+            {
+                serde_json::from_str::<AccountResource>(&resource.data)
+                    .ok()
+                    .map(|account| account.authentication_key)
+            },
+            _ => None,
+        })
+}
+
+fn rotates_key(txn: &Transaction) -> bool {
+    let Some(TxnData::User(user_txn)) = txn.txn_data.as_ref() else {
+        return false;
+    };
+    user_txn.events.iter().any(|event| {
+        event.type_str == "0x1::account::KeyRotation"
+            || event.type_str == "0x1::account::KeyRotationEvent"
+    })
+}
+
+fn sender_signature(txn: &Transaction) -> Option<(String, SenderSignature<'_>)> {
+    let Some(TxnData::User(user_txn)) = txn.txn_data.as_ref() else {
+        return None;
+    };
+    let request = user_txn.request.as_ref()?;
+    let account_signature = match request.signature.as_ref()?.signature.as_ref()? {
+        SignatureEnum::Ed25519(sig) => {
+            return Some((request.sender.clone(), SenderSignature::Ed25519(sig)))
+        },
+        SignatureEnum::MultiEd25519(sig) => {
+            return Some((request.sender.clone(), SenderSignature::MultiEd25519(sig)))
+        },
+        SignatureEnum::MultiAgent(sig) => sig.sender.as_ref()?,
+        SignatureEnum::FeePayer(sig) => sig.sender.as_ref()?,
+        SignatureEnum::SingleSender(sig) => sig.sender.as_ref()?,
+    };
+    let signature = match account_signature.signature.as_ref()? {
+        AccountSignatureEnum::Ed25519(sig) => SenderSignature::Ed25519(sig),
+        AccountSignatureEnum::MultiEd25519(sig) => SenderSignature::MultiEd25519(sig),
+        _ => return None,
+    };
+    Some((request.sender.clone(), signature))
+}
+
+pub fn check_transaction(txn: &Transaction) -> AuthenticationKeyCheck {
+    let Some((sender, signature)) = sender_signature(txn) else {
+        return AuthenticationKeyCheck::Skipped;
+    };
+    if rotates_key(txn) {
+        return AuthenticationKeyCheck::Skipped;
+    }
+    let Some(expected) = written_authentication_key(txn, &standardize_address(&sender)) else {
+        return AuthenticationKeyCheck::Skipped;
+    };
+    match signature
+        .authentication_key()
+        .map(|key| standardize_address_from_bytes(&key))
+    {
+        Ok(actual) if actual == standardize_address(&expected) => AuthenticationKeyCheck::Matched,
+        Ok(actual) => AuthenticationKeyCheck::Mismatched(format!(
+            "{} signed with the keys of authentication key {}, the sender's is {}",
+            signature.type_(),
+            actual,
+            expected
+        )),
+        Err(reason) => AuthenticationKeyCheck::Mismatched(format!(
+            "Malformed {}: {}",
+            signature.type_(),
+            reason
+        )),
+    }
+}
+
+/// Checks the user transactions of a batch, counting and logging each mismatch.
+pub fn check_transactions(processor_name: &str, transactions: &[Transaction]) {
+    for txn in transactions {
+        if let AuthenticationKeyCheck::Mismatched(reason) = check_transaction(txn) {
+            let signature_type = sender_signature(txn)
+                .map(|(_, signature)| signature.type_())
+                .unwrap_or_default();
+            AUTHENTICATION_KEY_MISMATCH_COUNT
+                .with_label_values(&[processor_name, signature_type])
+                .inc();
+            error!(
+                processor_name,
+                transaction_version = txn.version,
+                reason,
+                "[Parser] Transaction signature doesn't match the sender's authentication key",
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{
+        Event, Signature, TransactionInfo, UserTransaction, UserTransactionRequest, WriteResource,
+        WriteSetChange,
+    };
+
+    const SENDER: &str = "0x00000000000000000000000000000000000000000000000000000000000000aa";
+
+    fn transaction(signature: SignatureEnum, authentication_key: &str) -> Transaction {
+        let account = WriteResource {
+            address: SENDER.to_string(),
+            type_str: ACCOUNT_RESOURCE_TYPE.to_string(),
+            data: serde_json::json!({
+                "authentication_key": authentication_key,
+                "sequence_number": "1",
+            })
+            .to_string(),
+            ..WriteResource::default()
+        };
+        Transaction {
+            version: 1,
+            info: Some(TransactionInfo {
+                changes: vec![WriteSetChange {
+                    change: Some(Change::WriteResource(account)),
+                    ..WriteSetChange::default()
+                }],
+                ..TransactionInfo::default()
+            }),
+            txn_data: Some(TxnData::User(UserTransaction {
+                request: Some(UserTransactionRequest {
+                    sender: SENDER.to_string(),
+                    signature: Some(Signature {
+                        signature: Some(signature),
+                        ..Signature::default()
+                    }),
+                    ..UserTransactionRequest::default()
+                }),
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    fn ed25519(public_key: u8) -> Ed25519Signature {
+        Ed25519Signature {
+            public_key: vec![public_key; 32],
+            signature: vec![0xab; 64],
+        }
+    }
+
+    fn ed25519_authentication_key(public_key: u8) -> String {
+        let mut bytes = vec![public_key; 32];
+        bytes.push(ED25519_SCHEME);
+        standardize_address_from_bytes(&sha3_256(&bytes))
+    }
+
+    #[test]
+    fn test_ed25519() {
+        let txn = transaction(
+            SignatureEnum::Ed25519(ed25519(1)),
+            &ed25519_authentication_key(1),
+        );
+        assert_eq!(check_transaction(&txn), AuthenticationKeyCheck::Matched);
+
+        // Signed by someone else's key
+        let tampered = transaction(
+            SignatureEnum::Ed25519(ed25519(2)),
+            &ed25519_authentication_key(1),
+        );
+        assert!(matches!(
+            check_transaction(&tampered),
+            AuthenticationKeyCheck::Mismatched(_)
+        ));
+
+        let mut truncated = ed25519(1);
+        truncated.signature.truncate(32);
+        let truncated = transaction(
+            SignatureEnum::Ed25519(truncated),
+            &ed25519_authentication_key(1),
+        );
+        assert!(matches!(
+            check_transaction(&truncated),
+            AuthenticationKeyCheck::Mismatched(_)
+        ));
+    }
+
+    #[test]
+    fn test_multi_ed25519() {
+        let signature = MultiEd25519Signature {
+            public_keys: vec![vec![1; 32], vec![2; 32], vec![3; 32]],
+            signatures: vec![vec![0xab; 64], vec![0xcd; 64]],
+            threshold: 2,
+            public_key_indices: vec![0, 2],
+        };
+        let mut bytes = signature.public_keys.concat();
+        bytes.extend([2, MULTI_ED25519_SCHEME]);
+        let authentication_key = standardize_address_from_bytes(&sha3_256(&bytes));
+
+        let txn = transaction(
+            SignatureEnum::MultiEd25519(signature.clone()),
+            &authentication_key,
+        );
+        assert_eq!(check_transaction(&txn), AuthenticationKeyCheck::Matched);
+
+        let below_threshold = MultiEd25519Signature {
+            signatures: vec![vec![0xab; 64]],
+            public_key_indices: vec![0],
+            ..signature
+        };
+        let txn = transaction(
+            SignatureEnum::MultiEd25519(below_threshold),
+            &authentication_key,
+        );
+        assert!(matches!(
+            check_transaction(&txn),
+            AuthenticationKeyCheck::Mismatched(_)
+        ));
+    }
+
+    #[test]
+    fn test_key_rotation_skipped() {
+        let mut txn = transaction(
+            SignatureEnum::Ed25519(ed25519(2)),
+            &ed25519_authentication_key(1),
+        );
+        if let Some(TxnData::User(user_txn)) = txn.txn_data.as_mut() {
+            user_txn.events.push(Event {
+                type_str: "0x1::account::KeyRotation".to_string(),
+                ..Event::default()
+            });
+        }
+        assert_eq!(check_transaction(&txn), AuthenticationKeyCheck::Skipped);
+    }
+}
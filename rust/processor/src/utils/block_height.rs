@@ -0,0 +1,132 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Block heights for transactions the stream didn't set one for, e.g. when read from Parquet
+//! files written without it. Each block starts with a block metadata transaction whose
+//! `NewBlockEvent` has the block's height, and every transaction up to the next one is in that
+//! block. With `compute_block_heights`, the fetcher fills in `block_height` this way before the
+//! processors see it. A height the stream did set is always kept.
+
+use crate::utils::util::deserialize_from_string;
+use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+use serde::Deserialize;
+
+const NEW_BLOCK_EVENT_TYPE: &str = "0x1::block::NewBlockEvent";
+
+#[derive(Deserialize)]
+struct NewBlockEvent {
+    #[serde(deserialize_with = "deserialize_from_string")]
+    height: u64,
+}
+
+/// Tracks the height of the block the fetcher is in. Transactions must be passed in version
+/// order, including the ones the transaction filter drops afterwards.
+#[derive(Debug, Default)]
+pub struct BlockHeightTracker {
+    current_block_height: Option<u64>,
+}
+
+impl BlockHeightTracker {
+    pub fn set_block_height(&mut self, txn: &mut Transaction) {
+        // Only genesis is at height 0, so 0 on anything else means the stream didn't set it
+        if txn.block_height != 0 || matches!(txn.txn_data, Some(TxnData::Genesis(_))) {
+            self.current_block_height = Some(txn.block_height);
+            return;
+        }
+        if let Some(TxnData::BlockMetadata(block_metadata)) = txn.txn_data.as_ref() {
+            let height = block_metadata
+                .events
+                .iter()
+                .filter(|event| event.type_str == NEW_BLOCK_EVENT_TYPE)
+                .find_map(|event| serde_json::from_str::<NewBlockEvent>(&event.data).ok())
+                .map(|event| event.height);
+            // Every block has the event, but count on from the previous block if it's missing
+            self.current_block_height =
+                height.or_else(|| self.current_block_height.map(|height| height + 1));
+        }
+        // Starting partway through a block, its transactions are left as they are until the
+        // next block starts
+        if let Some(height) = self.current_block_height {
+            txn.block_height = height;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{
+        BlockMetadataTransaction, Event, UserTransaction, ValidatorTransaction,
+    };
+
+    fn block_metadata_txn(version: u64, height: u64) -> Transaction {
+        let new_block_event = Event {
+            type_str: NEW_BLOCK_EVENT_TYPE.to_string(),
+            data: serde_json::json!({"height": height.to_string(), "round": "1"}).to_string(),
+            ..Event::default()
+        };
+        Transaction {
+            version,
+            txn_data: Some(TxnData::BlockMetadata(BlockMetadataTransaction {
+                events: vec![new_block_event],
+                ..BlockMetadataTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    fn user_txn(version: u64) -> Transaction {
+        Transaction {
+            version,
+            txn_data: Some(TxnData::User(UserTransaction::default())),
+            ..Transaction::default()
+        }
+    }
+
+    fn block_heights(tracker: &mut BlockHeightTracker, mut txns: Vec<Transaction>) -> Vec<u64> {
+        txns.iter_mut()
+            .map(|txn| {
+                tracker.set_block_height(txn);
+                txn.block_height
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_block_boundary() {
+        let txns = vec![
+            block_metadata_txn(10, 5),
+            user_txn(11),
+            Transaction {
+                version: 12,
+                txn_data: Some(TxnData::Validator(ValidatorTransaction::default())),
+                ..Transaction::default()
+            },
+            block_metadata_txn(13, 6),
+            user_txn(14),
+        ];
+        let mut tracker = BlockHeightTracker::default();
+        assert_eq!(block_heights(&mut tracker, txns), vec![5, 5, 5, 6, 6]);
+    }
+
+    #[test]
+    fn test_start_mid_block() {
+        let txns = vec![user_txn(11), block_metadata_txn(12, 6), user_txn(13)];
+        let mut tracker = BlockHeightTracker::default();
+        assert_eq!(block_heights(&mut tracker, txns), vec![0, 6, 6]);
+    }
+
+    #[test]
+    fn test_stream_heights_kept() {
+        let mut first = user_txn(11);
+        first.block_height = 5;
+        // Heights the stream set win over the event and the previous block
+        let mut block_metadata = block_metadata_txn(12, 7);
+        block_metadata.block_height = 6;
+        let mut last = user_txn(14);
+        last.block_height = 6;
+        let txns = vec![first, block_metadata, user_txn(13), last];
+        let mut tracker = BlockHeightTracker::default();
+        assert_eq!(block_heights(&mut tracker, txns), vec![5, 6, 6, 6]);
+    }
+}
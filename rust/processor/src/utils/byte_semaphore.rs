@@ -0,0 +1,52 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Bytes of transactions that fit under an optional limit, one semaphore permit per byte. Shared
+/// by `ChannelByteLimiter` and `ProcessingByteBudget`. Without a limit everything fits at once.
+pub struct ByteSemaphore {
+    max_bytes: Option<u64>,
+    semaphore: Arc<Semaphore>,
+}
+
+impl ByteSemaphore {
+    pub fn new(max_bytes: Option<u64>) -> Self {
+        let permits = max_bytes.map_or(0, |max| max.min(Semaphore::MAX_PERMITS as u64) as usize);
+        Self {
+            max_bytes,
+            semaphore: Arc::new(Semaphore::new(permits)),
+        }
+    }
+
+    /// A single batch larger than the limit would never fit, so it's charged as the whole limit
+    /// and goes through once nothing else holds any bytes.
+    fn permits_for(max_bytes: u64, size_in_bytes: u64) -> u32 {
+        size_in_bytes
+            .min(max_bytes)
+            .min(Semaphore::MAX_PERMITS as u64)
+            .min(u32::MAX as u64) as u32
+    }
+
+    /// Waits until `size_in_bytes` fit under the limit. They're returned when the permit is
+    /// dropped, or by `release` if it's forgotten. None without a limit.
+    pub async fn acquire(&self, size_in_bytes: u64) -> Option<OwnedSemaphorePermit> {
+        let max_bytes = self.max_bytes?;
+        Some(
+            self.semaphore
+                .clone()
+                .acquire_many_owned(Self::permits_for(max_bytes, size_in_bytes))
+                .await
+                .expect("Byte semaphore should never be closed"),
+        )
+    }
+
+    /// Returns the bytes of a forgotten permit of `size_in_bytes`.
+    pub fn release(&self, size_in_bytes: u64) {
+        if let Some(max_bytes) = self.max_bytes {
+            self.semaphore
+                .add_permits(Self::permits_for(max_bytes, size_in_bytes) as usize);
+        }
+    }
+}
@@ -0,0 +1,105 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::{
+    byte_semaphore::ByteSemaphore, counters::FETCHER_THREAD_CHANNEL_BUFFERED_BYTES,
+};
+
+/// Bounds the total bytes of transactions sitting in the fetcher channel. The channel itself is
+/// bounded by number of batches, which isn't enough during deep backfills where batches can be
+/// large. When the limit is hit, the fetcher stops pulling from the stream until the processor
+/// tasks have drained enough of the channel.
+pub struct ChannelByteLimiter {
+    processor_name: String,
+    bytes: ByteSemaphore,
+}
+
+impl ChannelByteLimiter {
+    pub fn new(processor_name: String, max_bytes: Option<u64>) -> Self {
+        Self {
+            processor_name,
+            bytes: ByteSemaphore::new(max_bytes),
+        }
+    }
+
+    /// Called by the fetcher before sending a batch. Waits until there's room if a limit is set.
+    /// A batch larger than the limit goes through once the channel is empty.
+    pub async fn acquire(&self, size_in_bytes: u64) {
+        // Taking the batch off the channel releases its bytes, on another task
+        if let Some(permit) = self.bytes.acquire(size_in_bytes).await {
+            permit.forget();
+        }
+        FETCHER_THREAD_CHANNEL_BUFFERED_BYTES
+            .with_label_values(&[&self.processor_name])
+            .add(size_in_bytes as i64);
+    }
+
+    /// Called by the processor tasks once a batch has been taken off the channel.
+    pub fn release(&self, size_in_bytes: u64) {
+        self.bytes.release(size_in_bytes);
+        FETCHER_THREAD_CHANNEL_BUFFERED_BYTES
+            .with_label_values(&[&self.processor_name])
+            .sub(size_in_bytes as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Whether `acquire` is still waiting after a while.
+    async fn blocks(limiter: &ChannelByteLimiter, size_in_bytes: u64) -> bool {
+        tokio::time::timeout(Duration::from_millis(50), limiter.acquire(size_in_bytes))
+            .await
+            .is_err()
+    }
+
+    fn buffered_bytes(processor_name: &str) -> i64 {
+        FETCHER_THREAD_CHANNEL_BUFFERED_BYTES
+            .with_label_values(&[processor_name])
+            .get()
+    }
+
+    #[tokio::test]
+    async fn test_waits_for_release() {
+        let limiter = ChannelByteLimiter::new("limiter_release_test".to_string(), Some(100));
+        limiter.acquire(60).await;
+        limiter.acquire(40).await;
+        assert!(blocks(&limiter, 30).await);
+        // Not enough freed yet
+        limiter.release(20);
+        assert!(blocks(&limiter, 30).await);
+        limiter.release(40);
+        limiter.acquire(30).await;
+        limiter.release(30);
+        limiter.release(40);
+        assert_eq!(buffered_bytes("limiter_release_test"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_batch_waits_for_empty_channel() {
+        let limiter = ChannelByteLimiter::new("limiter_oversized_test".to_string(), Some(100));
+        limiter.acquire(10).await;
+        assert!(blocks(&limiter, 1_000).await);
+        limiter.release(10);
+        limiter.acquire(1_000).await;
+        assert_eq!(buffered_bytes("limiter_oversized_test"), 1_000);
+        // Nothing else fits alongside it
+        assert!(blocks(&limiter, 1).await);
+        limiter.release(1_000);
+        assert_eq!(buffered_bytes("limiter_oversized_test"), 0);
+    }
+
+    #[tokio::test]
+    async fn test_no_limit_never_blocks() {
+        let limiter = ChannelByteLimiter::new("limiter_unlimited_test".to_string(), None);
+        for _ in 0..10 {
+            assert!(!blocks(&limiter, u64::MAX / 100).await);
+        }
+        for _ in 0..10 {
+            limiter.release(u64::MAX / 100);
+        }
+        assert_eq!(buffered_bytes("limiter_unlimited_test"), 0);
+    }
+}
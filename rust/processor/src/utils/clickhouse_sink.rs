@@ -0,0 +1,133 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    db::clickhouse::ClickhouseTable,
+    utils::retry::{Backoff, Jitter},
+};
+use ahash::AHashSet;
+use anyhow::{Context, Result};
+use clickhouse::Client;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use url::Url;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClickhouseConfig {
+    /// HTTP interface of the server, e.g. `http://localhost:8123`.
+    pub url: Url,
+    #[serde(default = "ClickhouseConfig::default_database")]
+    pub database: String,
+    #[serde(default)]
+    pub user: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Retries of a failed insert before the batch fails. The delay doubles after each retry.
+    #[serde(default = "ClickhouseConfig::default_max_retries")]
+    pub max_retries: usize,
+    #[serde(default = "ClickhouseConfig::default_initial_retry_delay_ms")]
+    pub initial_retry_delay_ms: u64,
+    #[serde(default)]
+    pub retry_jitter: Jitter,
+}
+
+impl ClickhouseConfig {
+    pub fn default_database() -> String {
+        "default".to_string()
+    }
+
+    pub const fn default_max_retries() -> usize {
+        3
+    }
+
+    pub const fn default_initial_retry_delay_ms() -> u64 {
+        500
+    }
+}
+
+/// Writes processor output to ClickHouse alongside Postgres. Inserts are async inserts, which
+/// the server buffers and merges into larger parts, but each one only returns once its rows are
+/// flushed, so a batch that counts as processed is in ClickHouse. Reprocessing a batch writes its
+/// rows again, which the `ReplacingMergeTree` tables deduplicate.
+pub struct ClickhouseSink {
+    config: ClickhouseConfig,
+    client: Client,
+    // Tables this sink already made sure exist
+    created_tables: Mutex<AHashSet<&'static str>>,
+}
+
+impl ClickhouseSink {
+    pub fn new(config: ClickhouseConfig) -> Self {
+        let mut client = Client::default()
+            .with_url(config.url.as_str())
+            .with_database(&config.database)
+            .with_option("async_insert", "1")
+            .with_option("wait_for_async_insert", "1");
+        if let Some(user) = &config.user {
+            client = client.with_user(user);
+        }
+        if let Some(password) = &config.password {
+            client = client.with_password(password);
+        }
+        Self {
+            config,
+            client,
+            created_tables: Mutex::new(AHashSet::new()),
+        }
+    }
+
+    async fn create_table<T: ClickhouseTable>(&self) -> Result<()> {
+        if self.created_tables.lock().unwrap().contains(T::TABLE_NAME) {
+            return Ok(());
+        }
+        self.client
+            .query(&T::create_table_query())
+            .execute()
+            .await
+            .with_context(|| format!("Failed to create ClickHouse table {}", T::TABLE_NAME))?;
+        self.created_tables.lock().unwrap().insert(T::TABLE_NAME);
+        Ok(())
+    }
+
+    /// Inserts the rows, retrying the whole insert if it fails. An insert that failed partway may
+    /// have written some rows, which the retry writes again and merges deduplicate.
+    pub async fn insert<T: ClickhouseTable>(&self, rows: &[T]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        self.create_table::<T>().await?;
+        let mut retry_count = 0;
+        let mut backoff =
+            Backoff::new(self.config.initial_retry_delay_ms, self.config.retry_jitter);
+        loop {
+            match self.try_insert(rows).await {
+                Ok(()) => return Ok(()),
+                Err(e) if retry_count >= self.config.max_retries => {
+                    return Err(e).with_context(|| {
+                        format!("Failed to insert into ClickHouse table {}", T::TABLE_NAME)
+                    });
+                },
+                Err(e) => {
+                    tracing::debug!(
+                        retry_count,
+                        table = T::TABLE_NAME,
+                        error = ?e,
+                        "Retrying ClickHouse insert",
+                    );
+                },
+            }
+            retry_count += 1;
+            backoff.sleep().await;
+        }
+    }
+
+    async fn try_insert<T: ClickhouseTable>(&self, rows: &[T]) -> Result<()> {
+        let mut insert = self.client.insert(T::TABLE_NAME)?;
+        for row in rows {
+            insert.write(row).await?;
+        }
+        insert.end().await?;
+        Ok(())
+    }
+}
@@ -1,11 +1,53 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use once_cell::sync::Lazy;
+use aptos_protos::transaction::v1::{Transaction, TransactionType};
+use once_cell::sync::{Lazy, OnceCell};
 use prometheus::{
-    register_gauge_vec, register_histogram_vec, register_int_counter, register_int_counter_vec,
-    register_int_gauge_vec, GaugeVec, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec,
+    register_counter_vec, register_gauge_vec, register_histogram_vec, register_int_counter,
+    register_int_counter_vec, register_int_gauge, register_int_gauge_vec, CounterVec, GaugeVec,
+    HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
 };
+use server_framework::trace_id::current_trace_id;
+use std::time::{Duration, Instant};
+
+/// Optional namespace prepended to every metric name, e.g. `dapp_a` turns
+/// `indexer_processor_errors` into `dapp_a_indexer_processor_errors`.
+static METRICS_PREFIX: OnceCell<Option<String>> = OnceCell::new();
+
+/// Sets the metrics namespace prefix. Metrics are registered lazily on first use, so this has
+/// to be called before any metric is touched; afterwards it fails so names stay consistent.
+/// Setting the same prefix again is fine, e.g. for several processors in one process.
+pub fn set_metrics_prefix(prefix: Option<String>) -> anyhow::Result<()> {
+    if METRICS_PREFIX.get() == Some(&prefix) {
+        return Ok(());
+    }
+    METRICS_PREFIX
+        .set(prefix)
+        .map_err(|_| anyhow::anyhow!("Metrics prefix must be set before any metric is used"))
+}
+
+fn metric_name(name: &str) -> String {
+    match METRICS_PREFIX.get_or_init(|| None) {
+        Some(prefix) => format!("{}_{}", prefix, name),
+        None => name.to_string(),
+    }
+}
+
+/// Value of the `chain_id` label on the main processor metrics, so processors on different
+/// networks can be scraped into one Prometheus. Set once the chain id has been checked, which is
+/// before any transaction is processed.
+static CHAIN_ID_LABEL: OnceCell<String> = OnceCell::new();
+
+/// Sets the `chain_id` label. Only the first chain id counts, which only matters if several
+/// workers for different chains share a process, as in tests.
+pub fn set_chain_id_label(chain_id: u64) {
+    let _ = CHAIN_ID_LABEL.set(chain_id.to_string());
+}
+
+pub fn chain_id_label() -> &'static str {
+    CHAIN_ID_LABEL.get().map(String::as_str).unwrap_or_default()
+}
 
 pub enum ProcessorStep {
     ReceivedTxnsFromGrpc,
@@ -42,9 +84,9 @@ impl ProcessorStep {
 /// Data latency when processor receives transactions.
 pub static PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS: Lazy<GaugeVec> = Lazy::new(|| {
     register_gauge_vec!(
-        "indexer_processor_data_receive_latency_in_secs",
+        metric_name("indexer_processor_data_receive_latency_in_secs"),
         "Data latency when processor receives transactions",
-        &["request_token", "processor_name"]
+        &["request_token", "processor_name", "chain_id"]
     )
     .unwrap()
 });
@@ -52,9 +94,9 @@ pub static PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS: Lazy<GaugeVec> = Lazy::new(|
 /// Data latency when processor finishes processing transactions.
 pub static PROCESSOR_DATA_PROCESSED_LATENCY_IN_SECS: Lazy<GaugeVec> = Lazy::new(|| {
     register_gauge_vec!(
-        "indexer_processor_data_processed_latency_in_secs",
+        metric_name("indexer_processor_data_processed_latency_in_secs"),
         "Data latency when processor finishes processing transactions",
-        &["request_token", "processor_name"]
+        &["request_token", "processor_name", "chain_id"]
     )
     .unwrap()
 });
@@ -62,9 +104,9 @@ pub static PROCESSOR_DATA_PROCESSED_LATENCY_IN_SECS: Lazy<GaugeVec> = Lazy::new(
 /// Number of times a given processor has been invoked
 pub static PROCESSOR_INVOCATIONS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
-        "indexer_processor_invocation_count",
+        metric_name("indexer_processor_invocation_count"),
         "Number of times a given processor has been invoked",
-        &["processor_name"]
+        &["processor_name", "chain_id"]
     )
     .unwrap()
 });
@@ -72,8 +114,18 @@ pub static PROCESSOR_INVOCATIONS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
 /// Number of times any given processor has raised an error
 pub static PROCESSOR_ERRORS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
-        "indexer_processor_errors",
+        metric_name("indexer_processor_errors"),
         "Number of times any given processor has raised an error",
+        &["processor_name", "chain_id"]
+    )
+    .unwrap()
+});
+
+/// Number of batches failed for taking longer than `batch_processing_timeout_secs`
+pub static BATCH_PROCESSING_TIMEOUT_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_batch_processing_timeout_count"),
+        "Number of batches that timed out while processing",
         &["processor_name"]
     )
     .unwrap()
@@ -82,9 +134,9 @@ pub static PROCESSOR_ERRORS_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
 /// Number of times any given processor has completed successfully
 pub static PROCESSOR_SUCCESSES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
-        "indexer_processor_success_count",
+        metric_name("indexer_processor_success_count"),
         "Number of times a given processor has completed successfully",
-        &["processor_name"]
+        &["processor_name", "chain_id"]
     )
     .unwrap()
 });
@@ -92,7 +144,7 @@ pub static PROCESSOR_SUCCESSES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
 /// Number of times the connection pool has timed out when trying to get a connection
 pub static UNABLE_TO_GET_CONNECTION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
     register_int_counter!(
-        "indexer_connection_pool_err",
+        metric_name("indexer_connection_pool_err"),
         "Number of times the connection pool has timed out when trying to get a connection"
     )
     .unwrap()
@@ -101,7 +153,7 @@ pub static UNABLE_TO_GET_CONNECTION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
 /// Number of times the connection pool got a connection
 pub static GOT_CONNECTION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
     register_int_counter!(
-        "indexer_connection_pool_ok",
+        metric_name("indexer_connection_pool_ok"),
         "Number of times the connection pool got a connection"
     )
     .unwrap()
@@ -111,7 +163,7 @@ pub static GOT_CONNECTION_COUNT: Lazy<IntCounter> = Lazy::new(|| {
 /// Number of times the indexer has been unable to fetch a transaction. Ideally zero.
 pub static UNABLE_TO_FETCH_TRANSACTION: Lazy<IntCounter> = Lazy::new(|| {
     register_int_counter!(
-        "indexer_unable_to_fetch_transaction_count",
+        metric_name("indexer_unable_to_fetch_transaction_count"),
         "Number of times the indexer has been unable to fetch a transaction"
     )
     .unwrap()
@@ -121,7 +173,7 @@ pub static UNABLE_TO_FETCH_TRANSACTION: Lazy<IntCounter> = Lazy::new(|| {
 /// Number of times the indexer has been able to fetch a transaction
 pub static FETCHED_TRANSACTION: Lazy<IntCounter> = Lazy::new(|| {
     register_int_counter!(
-        "indexer_fetched_transaction_count",
+        metric_name("indexer_fetched_transaction_count"),
         "Number of times the indexer has been able to fetch a transaction"
     )
     .unwrap()
@@ -130,9 +182,65 @@ pub static FETCHED_TRANSACTION: Lazy<IntCounter> = Lazy::new(|| {
 /// Max version processed
 pub static LATEST_PROCESSED_VERSION: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
-        "indexer_processor_latest_version",
+        metric_name("indexer_processor_latest_version"),
         "Latest version a processor has fully consumed",
-        &["processor_name", "step", "message", "task_index"]
+        &[
+            "processor_name",
+            "step",
+            "message",
+            "task_index",
+            "chain_id"
+        ]
+    )
+    .unwrap()
+});
+
+/// Number of heartbeats emitted, see `heartbeat`
+pub static HEARTBEAT_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_heartbeat_count"),
+        "Number of heartbeats emitted, whether or not transactions are flowing",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Latest version received from the stream as of the last heartbeat
+pub static HEARTBEAT_TIP_VERSION: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        metric_name("indexer_processor_heartbeat_tip_version"),
+        "Last version the fetcher received from the stream as of the last heartbeat, not the chain tip",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Versions between the chain tip and the last batch fetched, with `tip_lag` in versions
+pub static TIP_LAG_VERSIONS: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        metric_name("indexer_processor_tip_lag_versions"),
+        "Versions between the chain tip and the last batch fetched",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Age of the last batch fetched when it was let through, with `tip_lag` in seconds
+pub static TIP_LAG_SECS: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        metric_name("indexer_processor_tip_lag_secs"),
+        "Age of the last batch fetched when it was let through",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Time since the last batch was received, as of the last heartbeat
+pub static HEARTBEAT_SECS_SINCE_LAST_BATCH: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        metric_name("indexer_processor_heartbeat_secs_since_last_batch"),
+        "Seconds since the last batch was received from the stream, as of the last heartbeat",
+        &["processor_name"]
     )
     .unwrap()
 });
@@ -140,9 +248,15 @@ pub static LATEST_PROCESSED_VERSION: Lazy<IntGaugeVec> = Lazy::new(|| {
 /// Count of bytes processed.
 pub static PROCESSED_BYTES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
-        "indexer_processor_processed_bytes_count",
+        metric_name("indexer_processor_processed_bytes_count"),
         "Count of bytes processed",
-        &["processor_name", "step", "message", "task_index"]
+        &[
+            "processor_name",
+            "step",
+            "message",
+            "task_index",
+            "chain_id"
+        ]
     )
     .unwrap()
 });
@@ -150,19 +264,45 @@ pub static PROCESSED_BYTES_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
 /// The amount of time that a task spent waiting for a protobuf bundle of transactions
 pub static PB_CHANNEL_FETCH_WAIT_TIME_SECS: Lazy<GaugeVec> = Lazy::new(|| {
     register_gauge_vec!(
-        "indexer_processor_pb_channel_fetch_wait_time_secs",
+        metric_name("indexer_processor_pb_channel_fetch_wait_time_secs"),
         "Count of bytes processed",
         &["processor_name", "task_index"]
     )
     .unwrap()
 });
 
+/// Count of events processed, for processors that report them.
+pub static NUM_EVENTS_PROCESSED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_num_events_processed_count"),
+        "Number of events processed",
+        &["processor_name", "chain_id"]
+    )
+    .unwrap()
+});
+
+/// Count of write set changes processed, for processors that report them.
+pub static NUM_WRITE_SET_CHANGES_PROCESSED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_num_write_set_changes_processed_count"),
+        "Number of write set changes processed",
+        &["processor_name", "chain_id"]
+    )
+    .unwrap()
+});
+
 /// Count of transactions processed.
 pub static NUM_TRANSACTIONS_PROCESSED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
-        "indexer_processor_num_transactions_processed_count",
+        metric_name("indexer_processor_num_transactions_processed_count"),
         "Number of transactions processed",
-        &["processor_name", "step", "message", "task_index"]
+        &[
+            "processor_name",
+            "step",
+            "message",
+            "task_index",
+            "chain_id"
+        ]
     )
     .unwrap()
 });
@@ -170,37 +310,144 @@ pub static NUM_TRANSACTIONS_PROCESSED_COUNT: Lazy<IntCounterVec> = Lazy::new(||
 /// Count of transactions filtered out
 pub static NUM_TRANSACTIONS_FILTERED_OUT_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
-        "indexer_processor_num_transactions_filtered_out_count",
+        metric_name("indexer_processor_num_transactions_filtered_out_count"),
         "Number of transactions filtered out",
         &["processor_name"]
     )
     .unwrap()
 });
 
+/// Count of transactions the transaction filter was evaluated on
+pub static TRANSACTION_FILTER_EVALUATED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_transaction_filter_evaluated_count"),
+        "Number of transactions the transaction filter was evaluated on",
+        &["processor_name", "filter_name"]
+    )
+    .unwrap()
+});
+
+/// Count of transactions the transaction filter matched, i.e. kept
+pub static TRANSACTION_FILTER_MATCHED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_transaction_filter_matched_count"),
+        "Number of transactions the transaction filter matched",
+        &["processor_name", "filter_name"]
+    )
+    .unwrap()
+});
+
+/// Count of matched transactions sent to the transaction tee
+pub static TRANSACTION_TEE_SENT_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_transaction_tee_sent_count"),
+        "Number of transactions sent to the transaction tee",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Count of matched transactions dropped because the transaction tee's channel was full
+pub static TRANSACTION_TEE_DROPPED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_transaction_tee_dropped_count"),
+        "Number of transactions dropped by the transaction tee",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Count of batches from GRPC without any transactions, which are skipped
+pub static GRPC_EMPTY_BATCH_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_grpc_empty_batch_count"),
+        "Number of empty batches received from GRPC",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
 /// Size of the channel containing transactions fetched from GRPC, waiting to be processed
 pub static FETCHER_THREAD_CHANNEL_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
-        "indexer_processor_fetcher_thread_channel_size",
+        metric_name("indexer_processor_fetcher_thread_channel_size"),
         "Size of the fetcher thread channel",
         &["processor_name"]
     )
     .unwrap()
 });
 
+/// Number of rows in Parquet source files that could not be decoded into a transaction
+pub static PARQUET_FILE_DECODE_ERROR_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_parquet_file_decode_error_count"),
+        "Number of rows in Parquet source files that failed to decode",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// 1 while the DB circuit breaker is open or half open, 0 when it's closed
+pub static DB_CIRCUIT_BREAKER_OPEN: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        metric_name("indexer_processor_db_circuit_breaker_open"),
+        "Whether DB writes are paused by the circuit breaker"
+    )
+    .unwrap()
+});
+
+/// Bytes of transactions currently being processed by the processor tasks
+pub static IN_FLIGHT_PROCESSING_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        metric_name("indexer_processor_in_flight_processing_bytes"),
+        "Bytes of transactions being processed across the processor tasks",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Bytes of transactions currently buffered in the fetcher thread channel
+pub static FETCHER_THREAD_CHANNEL_BUFFERED_BYTES: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        metric_name("indexer_processor_fetcher_thread_channel_buffered_bytes"),
+        "Bytes of transactions buffered in the fetcher thread channel",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
 /// Overall processing time for a single batch of transactions (per task)
 pub static SINGLE_BATCH_PROCESSING_TIME_IN_SECS: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
-        "indexer_processor_single_batch_processing_time_in_secs",
+        metric_name("indexer_processor_single_batch_processing_time_in_secs"),
         "Time taken to process a single batch of transactions",
         &["processor_name", "task_index"]
     )
     .unwrap()
 });
 
+/// Attaches the trace ID of the current span to the slow batch's bucket of
+/// `SINGLE_BATCH_PROCESSING_TIME_IN_SECS` as an OpenMetrics exemplar, so the batch's logs can be
+/// looked up from a latency spike. Does nothing outside of a span with a trace ID.
+pub fn record_slow_batch_exemplar(processor_name: &str, task_index: &str, processing_time: f64) {
+    let Some(trace_id) = current_trace_id() else {
+        return;
+    };
+    server_framework::open_metrics::record_exemplar(
+        &metric_name("indexer_processor_single_batch_processing_time_in_secs"),
+        &[
+            ("processor_name", processor_name),
+            ("task_index", task_index),
+        ],
+        processing_time,
+        &[("trace_id", &trace_id)],
+    );
+}
+
 /// Parsing time for a single batch of transactions
 pub static SINGLE_BATCH_PARSING_TIME_IN_SECS: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
-        "indexer_processor_single_batch_parsing_time_in_secs",
+        metric_name("indexer_processor_single_batch_parsing_time_in_secs"),
         "Time taken to parse a single batch of transactions",
         &["processor_name", "task_index"]
     )
@@ -210,7 +457,7 @@ pub static SINGLE_BATCH_PARSING_TIME_IN_SECS: Lazy<HistogramVec> = Lazy::new(||
 /// DB insertion time for a single batch of transactions
 pub static SINGLE_BATCH_DB_INSERTION_TIME_IN_SECS: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
-        "indexer_processor_single_batch_db_insertion_time_in_secs",
+        metric_name("indexer_processor_single_batch_db_insertion_time_in_secs"),
         "Time taken to insert to DB for a single batch of transactions",
         &["processor_name", "task_index"]
     )
@@ -220,25 +467,64 @@ pub static SINGLE_BATCH_DB_INSERTION_TIME_IN_SECS: Lazy<HistogramVec> = Lazy::ne
 /// Transaction timestamp in unixtime
 pub static TRANSACTION_UNIX_TIMESTAMP: Lazy<GaugeVec> = Lazy::new(|| {
     register_gauge_vec!(
-        "indexer_processor_transaction_unix_timestamp",
+        metric_name("indexer_processor_transaction_unix_timestamp"),
         "Transaction timestamp in unixtime",
-        &["processor_name", "step", "message", "task_index"]
+        &[
+            "processor_name",
+            "step",
+            "message",
+            "task_index",
+            "chain_id"
+        ]
+    )
+    .unwrap()
+});
+
+/// Time taken by each migration that ran on startup
+pub static MIGRATION_DURATION_IN_SECS: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        metric_name("indexer_processor_migration_duration_in_secs"),
+        "Time taken by each migration that ran on startup",
+        &["migration_name"]
+    )
+    .unwrap()
+});
+
+/// Number of batches waiting in each processor's buffer when multiplexing
+pub static MULTIPLEXED_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register_int_gauge_vec!(
+        metric_name("indexer_processor_multiplexed_buffer_size"),
+        "Number of batches waiting in each processor's buffer when multiplexing",
+        &["processor_name"]
+    )
+    .unwrap()
+});
+
+/// Timestamp of the oldest transaction that has been fetched but whose progress hasn't been
+/// committed yet, in unixtime. 0 when nothing is in flight.
+pub static OLDEST_UNCOMMITTED_TRANSACTION_UNIX_TIMESTAMP: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        metric_name("indexer_processor_oldest_uncommitted_transaction_unix_timestamp"),
+        "Timestamp of the oldest fetched transaction whose progress isn't committed yet, in unixtime",
+        &["processor_name"]
     )
     .unwrap()
 });
 
 /// Data gap warnings
 pub static PROCESSOR_DATA_GAP_COUNT: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!("indexer_processor_data_gap_count", "Data gap count", &[
-        "processor_name"
-    ])
+    register_int_gauge_vec!(
+        metric_name("indexer_processor_data_gap_count"),
+        "Data gap count",
+        &["processor_name"]
+    )
     .unwrap()
 });
 
 /// Data gap warnings for parquet
 pub static PARQUET_PROCESSOR_DATA_GAP_COUNT: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
-        "indexer_parquet_processor_data_gap_count",
+        metric_name("indexer_parquet_processor_data_gap_count"),
         "Data gap count",
         &["processor_name"]
     )
@@ -248,9 +534,9 @@ pub static PARQUET_PROCESSOR_DATA_GAP_COUNT: Lazy<IntGaugeVec> = Lazy::new(|| {
 /// GRPC latency.
 pub static GRPC_LATENCY_BY_PROCESSOR_IN_SECS: Lazy<HistogramVec> = Lazy::new(|| {
     register_histogram_vec!(
-        "indexer_processor_grpc_latency_in_secs",
+        metric_name("indexer_processor_grpc_latency_in_secs"),
         "GRPC latency observed by processor",
-        &["processor_name", "task_index"]
+        &["processor_name", "task_index", "chain_id"]
     )
     .unwrap()
 });
@@ -258,7 +544,7 @@ pub static GRPC_LATENCY_BY_PROCESSOR_IN_SECS: Lazy<HistogramVec> = Lazy::new(||
 /// Processor unknown type count.
 pub static PROCESSOR_UNKNOWN_TYPE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     register_int_counter_vec!(
-        "indexer_processor_unknown_type_count",
+        metric_name("indexer_processor_unknown_type_count"),
         "Processor unknown type count, e.g., comptaibility issues",
         &["model_name"]
     )
@@ -267,17 +553,18 @@ pub static PROCESSOR_UNKNOWN_TYPE_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
 
 /// Parquet struct size
 pub static PARQUET_STRUCT_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
-    register_int_gauge_vec!("indexer_parquet_struct_size", "Parquet struct size", &[
-        "processor_name",
-        "parquet_type"
-    ])
+    register_int_gauge_vec!(
+        metric_name("indexer_parquet_struct_size"),
+        "Parquet struct size",
+        &["processor_name", "parquet_type"]
+    )
     .unwrap()
 });
 
 /// Parquet handler buffer size
 pub static PARQUET_HANDLER_CURRENT_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
-        "indexer_parquet_handler_buffer_size",
+        metric_name("indexer_parquet_handler_buffer_size"),
         "Parquet handler buffer size",
         &["processor_name", "parquet_type"]
     )
@@ -287,7 +574,7 @@ pub static PARQUET_HANDLER_CURRENT_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(||
 /// Size of the parquet file
 pub static PARQUET_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
-        "indexer_parquet_size",
+        metric_name("indexer_parquet_size"),
         "Size of Parquet buffer to upload",
         &["processor_name", "parquet_type"]
     )
@@ -297,9 +584,199 @@ pub static PARQUET_BUFFER_SIZE: Lazy<IntGaugeVec> = Lazy::new(|| {
 /// Size of parquet buffer after upload
 pub static PARQUET_BUFFER_SIZE_AFTER_UPLOAD: Lazy<IntGaugeVec> = Lazy::new(|| {
     register_int_gauge_vec!(
-        "indexer_parquet_size_after_upload",
+        metric_name("indexer_parquet_size_after_upload"),
         "Size of Parquet buffer after upload",
         &["parquet_type"]
     )
     .unwrap()
 });
+
+/// Lookups of the v1 collection creator cache, by result (hit or miss)
+pub static COLLECTION_CREATOR_CACHE_LOOKUP_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_collection_creator_cache_lookup_count"),
+        "Lookups of the v1 collection creator cache, by result",
+        &["result"]
+    )
+    .unwrap()
+});
+
+/// Number of supply changes that don't match the deposits and withdrawals of the asset
+pub static SUPPLY_MISMATCH_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_supply_mismatch_count"),
+        "Number of fungible asset supply changes that don't match balance changes",
+        &["asset_type"]
+    )
+    .unwrap()
+});
+
+/// Number of rows deleted for falling out of their table's retention
+pub static ROWS_PRUNED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_rows_pruned_count"),
+        "Number of rows pruned by retention",
+        &["processor_name", "table_name"]
+    )
+    .unwrap()
+});
+
+/// Number of user transactions whose signature doesn't match the sender's authentication key,
+/// with `check_authentication_keys`
+pub static AUTHENTICATION_KEY_MISMATCH_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_authentication_key_mismatch_count"),
+        "Number of user transactions whose signature doesn't match the sender's authentication key",
+        &["processor_name", "signature_type"]
+    )
+    .unwrap()
+});
+
+/// Time spent parsing transactions of each type. Divide by
+/// `indexer_processor_transaction_type_processed_count` for the average per transaction.
+pub static TRANSACTION_TYPE_PROCESSING_TIME_IN_SECS: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        metric_name("indexer_processor_transaction_type_processing_time_in_secs"),
+        "Time taken to parse transactions, by transaction type",
+        &["processor_name", "transaction_type"]
+    )
+    .unwrap()
+});
+
+/// Number of transactions parsed of each type
+pub static TRANSACTION_TYPE_PROCESSED_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        metric_name("indexer_processor_transaction_type_processed_count"),
+        "Number of transactions parsed, by transaction type",
+        &["processor_name", "transaction_type"]
+    )
+    .unwrap()
+});
+
+/// Times each transaction of a batch by its type. Call `start` as each transaction is reached,
+/// which ends the previous one, and `finish` after the last. Anything done in between that isn't
+/// parsing, like inserting part of the batch, goes between `pause` and `resume`. Times are added
+/// up per type and only reported to the metrics in `finish`, so the cost per transaction is
+/// reading the clock.
+pub struct TransactionTypeTimer {
+    processor_name: &'static str,
+    current: Option<(TransactionType, Instant)>,
+    paused_at: Option<Instant>,
+    // A batch usually has a few types, so a list is cheaper than a map
+    totals: Vec<(TransactionType, Duration, u64)>,
+}
+
+impl TransactionTypeTimer {
+    pub fn new(processor_name: &'static str) -> Self {
+        Self {
+            processor_name,
+            current: None,
+            paused_at: None,
+            totals: vec![],
+        }
+    }
+
+    pub fn start(&mut self, transaction: &Transaction) {
+        let now = Instant::now();
+        self.stop(now);
+        // Unknown values would otherwise grow the label set
+        let transaction_type =
+            TransactionType::try_from(transaction.r#type).unwrap_or(TransactionType::Unspecified);
+        self.current = Some((transaction_type, now));
+    }
+
+    pub fn pause(&mut self) {
+        self.paused_at = Some(Instant::now());
+    }
+
+    /// Leaves the time since `pause` out of the current transaction.
+    pub fn resume(&mut self) {
+        if let (Some((_, start)), Some(paused_at)) = (&mut self.current, self.paused_at.take()) {
+            *start += paused_at.elapsed();
+        }
+    }
+
+    fn stop(&mut self, now: Instant) {
+        let Some((transaction_type, start)) = self.current.take() else {
+            return;
+        };
+        let elapsed = now - start;
+        match self
+            .totals
+            .iter_mut()
+            .find(|(t, ..)| *t == transaction_type)
+        {
+            Some((_, total, count)) => {
+                *total += elapsed;
+                *count += 1;
+            },
+            None => self.totals.push((transaction_type, elapsed, 1)),
+        }
+    }
+
+    pub fn finish(mut self) {
+        self.stop(Instant::now());
+        for (transaction_type, total, count) in self.totals {
+            let labels = [self.processor_name, transaction_type.as_str_name()];
+            TRANSACTION_TYPE_PROCESSING_TIME_IN_SECS
+                .with_label_values(&labels)
+                .inc_by(total.as_secs_f64());
+            TRANSACTION_TYPE_PROCESSED_COUNT
+                .with_label_values(&labels)
+                .inc_by(count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(transaction_type: i32) -> Transaction {
+        Transaction {
+            r#type: transaction_type,
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn test_transaction_type_timer_counts_per_type() {
+        let mut timer = TransactionTypeTimer::new("test_processor");
+        for transaction_type in [
+            TransactionType::BlockMetadata as i32,
+            TransactionType::User as i32,
+            TransactionType::User as i32,
+            // Not a known type
+            1000,
+        ] {
+            timer.start(&transaction(transaction_type));
+        }
+        timer.stop(Instant::now());
+        let counts = timer
+            .totals
+            .iter()
+            .map(|(t, _, count)| (*t, *count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            vec![
+                (TransactionType::BlockMetadata, 1),
+                (TransactionType::User, 2),
+                (TransactionType::Unspecified, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_transaction_type_timer_leaves_out_pauses() {
+        let mut timer = TransactionTypeTimer::new("test_processor");
+        timer.start(&transaction(TransactionType::User as i32));
+        timer.pause();
+        std::thread::sleep(Duration::from_millis(200));
+        timer.resume();
+        timer.stop(Instant::now());
+        let (_, total, count) = timer.totals[0];
+        assert_eq!(count, 1);
+        assert!(total < Duration::from_millis(200));
+    }
+}
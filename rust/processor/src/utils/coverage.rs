@@ -0,0 +1,202 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Which versions a processor has actually written to a table, as contiguous ranges, for
+//! auditing. `processor_status` only has the high-water mark, and the gap detector only knows
+//! about the versions it saw since the last restart, so holes left by a backfill or a botched
+//! replay only show up in the tables themselves. Served on `/coverage`.
+
+use crate::{
+    db::postgres::models::processor_status::ProcessorStatusQuery,
+    utils::database::{table_name_in_db, ArcDbPool},
+};
+use anyhow::{bail, Context, Result};
+use diesel::{sql_query, sql_types::BigInt, QueryableByName};
+use diesel_async::RunQueryDsl;
+use serde::Serialize;
+
+/// Tables whose coverage can be queried, with their version column. A version only shows up as
+/// covered if it wrote at least one row, so holes are only meaningful for tables with a row for
+/// every version, like `transactions`. For the others, a hole may just be versions without such
+/// rows, e.g. transactions without events.
+pub const COVERAGE_TABLES: [(&str, &str); 12] = [
+    ("account_transactions", "transaction_version"),
+    ("block_metadata_transactions", "version"),
+    ("coin_activities", "transaction_version"),
+    ("events", "transaction_version"),
+    ("fungible_asset_activities", "transaction_version"),
+    ("move_resources", "transaction_version"),
+    ("signatures", "transaction_version"),
+    ("table_items", "transaction_version"),
+    ("token_activities_v2", "transaction_version"),
+    ("transactions", "version"),
+    ("user_transactions", "version"),
+    ("write_set_changes", "transaction_version"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, QueryableByName, Serialize)]
+pub struct VersionRange {
+    #[diesel(sql_type = BigInt)]
+    pub start_version: i64,
+    #[diesel(sql_type = BigInt)]
+    pub end_version: i64,
+}
+
+/// Most versions a single request can scan, so it can't read a whole table by accident
+pub const MAX_COVERAGE_VERSIONS: i64 = 10_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Coverage {
+    pub processor: String,
+    pub table: String,
+    pub last_success_version: i64,
+    pub start_version: i64,
+    /// The requested end version, or `last_success_version` if that's lower
+    pub end_version: i64,
+    /// Contiguous ranges of versions with rows, in order
+    pub covered: Vec<VersionRange>,
+    /// Versions without rows between `start_version` and `end_version`
+    pub holes: Vec<VersionRange>,
+}
+
+/// Contiguous runs of versions, found by numbering the distinct versions in order: within a run,
+/// the version minus its number is the same.
+const COVERED_RANGES_QUERY: &str = "
+SELECT MIN(version) AS start_version, MAX(version) AS end_version
+FROM (
+    SELECT version, version - ROW_NUMBER() OVER (ORDER BY version) AS run
+    FROM (
+        SELECT DISTINCT {column} AS version FROM \"{table}\" WHERE {column} BETWEEN $1 AND $2
+    ) AS versions
+) AS numbered
+GROUP BY run
+ORDER BY start_version
+";
+
+/// Versions of `processor_name` in `table` from `start_version` to `end_version`, inclusive, but
+/// no further than its `last_success_version`: the versions after it may still be in flight. The
+/// range can be at most `MAX_COVERAGE_VERSIONS` long.
+pub async fn get_coverage(
+    pool: ArcDbPool,
+    processor_name: &str,
+    table: &str,
+    start_version: i64,
+    end_version: i64,
+) -> Result<Coverage> {
+    let Some((_, column)) = COVERAGE_TABLES.iter().find(|(t, _)| *t == table) else {
+        bail!(
+            "Coverage isn't supported for {}, only for {:?}",
+            table,
+            COVERAGE_TABLES.map(|(table, _)| table)
+        );
+    };
+    if start_version < 0 || end_version < start_version {
+        bail!("Invalid version range {} to {}", start_version, end_version);
+    }
+    if end_version - start_version >= MAX_COVERAGE_VERSIONS {
+        bail!(
+            "Version range {} to {} is longer than {} versions",
+            start_version,
+            end_version,
+            MAX_COVERAGE_VERSIONS
+        );
+    }
+    let mut conn = pool.get().await?;
+    let last_success_version = ProcessorStatusQuery::get_by_processor(processor_name, &mut conn)
+        .await?
+        .map(|status| status.last_success_version)
+        .with_context(|| format!("{} hasn't committed any versions", processor_name))?;
+    let end_version = end_version.min(last_success_version);
+    let query = COVERED_RANGES_QUERY
+        .replace("{column}", column)
+        .replace("{table}", &table_name_in_db(table));
+    let covered = sql_query(query)
+        .bind::<BigInt, _>(start_version)
+        .bind::<BigInt, _>(end_version)
+        .load::<VersionRange>(&mut conn)
+        .await
+        .with_context(|| format!("Failed to query the coverage of {}", table))?;
+    Ok(Coverage {
+        processor: processor_name.to_string(),
+        table: table.to_string(),
+        last_success_version,
+        start_version,
+        end_version,
+        holes: holes(&covered, start_version, end_version),
+        covered,
+    })
+}
+
+/// The versions from `start_version` to `end_version` missing from `covered`, which is within
+/// them.
+fn holes(covered: &[VersionRange], start_version: i64, end_version: i64) -> Vec<VersionRange> {
+    let mut holes = vec![];
+    let mut next_version = start_version;
+    for range in covered {
+        if range.start_version > next_version {
+            holes.push(VersionRange {
+                start_version: next_version,
+                end_version: range.start_version - 1,
+            });
+        }
+        next_version = range.end_version + 1;
+    }
+    if next_version <= end_version {
+        holes.push(VersionRange {
+            start_version: next_version,
+            end_version,
+        });
+    }
+    holes
+}
+
+/// Serves `/coverage`, for the processor that registered it unless another one writing to the
+/// same database is asked for.
+pub struct CoverageQuery {
+    pub processor_name: &'static str,
+    pub pool: ArcDbPool,
+}
+
+#[async_trait::async_trait]
+impl server_framework::CoverageHandler for CoverageQuery {
+    async fn coverage(
+        &self,
+        processor: Option<String>,
+        table: String,
+        start_version: u64,
+        end_version: u64,
+    ) -> Result<serde_json::Value> {
+        let processor = processor.as_deref().unwrap_or(self.processor_name);
+        let coverage = get_coverage(
+            self.pool.clone(),
+            processor,
+            &table,
+            start_version as i64,
+            end_version as i64,
+        )
+        .await?;
+        Ok(serde_json::to_value(coverage)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start_version: i64, end_version: i64) -> VersionRange {
+        VersionRange {
+            start_version,
+            end_version,
+        }
+    }
+
+    #[test]
+    fn test_holes() {
+        assert_eq!(holes(&[], 0, 10), [range(0, 10)]);
+        assert_eq!(holes(&[range(0, 10)], 0, 10), []);
+        assert_eq!(
+            holes(&[range(2, 3), range(7, 8), range(10, 10)], 0, 12),
+            [range(0, 1), range(4, 6), range(9, 9), range(11, 12)]
+        );
+    }
+}
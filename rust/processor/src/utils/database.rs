@@ -1,22 +1,40 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
-use crate::utils::util::remove_null_bytes;
+use crate::{
+    db::postgres::models::outbox::Outbox,
+    schema,
+    utils::{
+        counters::MIGRATION_DURATION_IN_SECS,
+        db_circuit_breaker::{db_circuit_breaker, is_db_unhealthy_error, DbCircuitBreaker},
+        util::remove_null_bytes,
+    },
+};
 use ahash::AHashMap;
+use anyhow::{bail, Context};
 use diesel::{
-    query_builder::{AstPass, Query, QueryFragment},
-    ConnectionResult, QueryResult,
+    migration::MigrationSource,
+    pg::{upsert::excluded, Pg, PgQueryBuilder},
+    query_builder::{AstPass, Query, QueryBuilder, QueryFragment},
+    sql_query, ConnectionError, ConnectionResult, ExpressionMethods, QueryResult,
 };
 use diesel_async::{
     pooled_connection::{
         bb8::{Pool, PooledConnection},
         AsyncDieselConnectionManager, ManagerConfig, PoolError,
     },
-    AsyncPgConnection, RunQueryDsl,
+    scoped_futures::ScopedFutureExt,
+    AsyncConnection, AsyncPgConnection, RunQueryDsl,
 };
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
 use futures_util::{future::BoxFuture, FutureExt};
-use std::sync::Arc;
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 pub type Backend = diesel::pg::Pg;
 
@@ -29,6 +47,213 @@ pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("src/db/postgres/mi
 
 pub const DEFAULT_MAX_POOL_SIZE: u32 = 150;
 
+/// Settings applied to every connection of a pool as it's opened.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DbConnectionConfig {
+    /// Idle time before TCP keepalives are sent, so proxies like PgBouncer don't drop idle pooled
+    /// connections. 0 leaves the driver's default of 2 hours. Ignored if the connection string
+    /// sets `keepalives_idle`.
+    #[serde(default = "DbConnectionConfig::default_tcp_keepalive_secs")]
+    pub tcp_keepalive_secs: u64,
+    /// `statement_timeout`, so a stalled statement fails instead of hanging. 0 leaves the
+    /// server's setting, which is no timeout unless it's been changed.
+    #[serde(default)]
+    pub statement_timeout_ms: u64,
+    /// `idle_in_transaction_session_timeout`, so a connection left in an open transaction doesn't
+    /// hold its locks forever. 0 leaves the server's setting.
+    #[serde(default = "DbConnectionConfig::default_idle_in_transaction_session_timeout_ms")]
+    pub idle_in_transaction_session_timeout_ms: u64,
+}
+
+impl DbConnectionConfig {
+    pub const fn default_tcp_keepalive_secs() -> u64 {
+        60
+    }
+
+    pub const fn default_idle_in_transaction_session_timeout_ms() -> u64 {
+        60_000
+    }
+
+    /// Adds the keepalive to the connection string, unless it already sets one.
+    fn apply_to_url(&self, database_url: &str) -> String {
+        let mut url = url::Url::parse(database_url).expect("Could not parse database url");
+        if self.tcp_keepalive_secs == 0 || url.query_pairs().any(|(k, _)| k == "keepalives_idle") {
+            return database_url.to_string();
+        }
+        url.query_pairs_mut()
+            .append_pair("keepalives", "1")
+            .append_pair("keepalives_idle", &self.tcp_keepalive_secs.to_string());
+        url.to_string()
+    }
+
+    /// Sets the session timeouts on a newly opened connection.
+    async fn apply_to_connection(&self, conn: &mut MyDbConnection) -> QueryResult<()> {
+        let timeouts = [
+            ("statement_timeout", self.statement_timeout_ms),
+            (
+                "idle_in_transaction_session_timeout",
+                self.idle_in_transaction_session_timeout_ms,
+            ),
+        ];
+        for (setting, timeout_ms) in timeouts {
+            if timeout_ms > 0 {
+                sql_query(format!("SET {} = {}", setting, timeout_ms))
+                    .execute(conn)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for DbConnectionConfig {
+    fn default() -> Self {
+        Self {
+            tcp_keepalive_secs: Self::default_tcp_keepalive_secs(),
+            statement_timeout_ms: 0,
+            idle_in_transaction_session_timeout_ms:
+                Self::default_idle_in_transaction_session_timeout_ms(),
+        }
+    }
+}
+
+/// Statements slower than this are logged with their table and row count.
+static SLOW_QUERY_THRESHOLD: OnceCell<Duration> = OnceCell::new();
+
+/// High enough that nothing is logged unless the DB is degraded.
+pub const DEFAULT_SLOW_QUERY_THRESHOLD_MS: u64 = 10_000;
+
+/// Sets the slow query threshold. Only the first call has an effect.
+pub fn set_slow_query_threshold(threshold: Duration) {
+    let _ = SLOW_QUERY_THRESHOLD.set(threshold);
+}
+
+fn slow_query_threshold() -> Duration {
+    *SLOW_QUERY_THRESHOLD.get_or_init(|| Duration::from_millis(DEFAULT_SLOW_QUERY_THRESHOLD_MS))
+}
+
+/// Prepended to the names of `PREFIXABLE_TABLES`, see `set_table_prefix`.
+static TABLE_PREFIX: OnceCell<Option<String>> = OnceCell::new();
+
+/// Tables that `table_prefix` applies to. `schema.rs` is generated with fixed names, so the
+/// prefix is applied by rewriting the SQL of every statement that goes through
+/// `execute_with_better_error`. Reads through the generated schema aren't rewritten, which is
+/// why only tables that processors never read back are supported.
+pub const PREFIXABLE_TABLES: [&str; 3] = ["events", "signatures", "user_transactions"];
+
+/// Sets the table name prefix, e.g. `dappa_` to write `dappa_events`. Like the metrics prefix
+/// it's process wide, so it has to be set before anything is written.
+pub fn set_table_prefix(prefix: Option<String>) -> anyhow::Result<()> {
+    if let Some(prefix) = &prefix {
+        if !prefix
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+        {
+            bail!(
+                "table_prefix {:?} can only have lowercase letters, digits and underscores",
+                prefix
+            );
+        }
+    }
+    if TABLE_PREFIX.get() == Some(&prefix) {
+        return Ok(());
+    }
+    TABLE_PREFIX
+        .set(prefix)
+        .map_err(|_| anyhow::anyhow!("Table prefix must be set before anything is written"))
+}
+
+fn table_prefix() -> Option<&'static str> {
+    TABLE_PREFIX.get_or_init(|| None).as_deref()
+}
+
+/// Name of the processor's row in `processor_status` and `gap_detector_status`. These tables
+/// are shared between instances, so with a table prefix the row is prefixed instead.
+pub fn processor_status_key(processor_name: &str) -> String {
+    format!("{}{}", table_prefix().unwrap_or_default(), processor_name)
+}
+
+/// Name to read one of `PREFIXABLE_TABLES` by in raw SQL, since reads through the generated
+/// schema aren't prefixed.
+pub fn prefixed_table_name(table: &str) -> String {
+    debug_assert!(PREFIXABLE_TABLES.contains(&table));
+    format!("{}{}", table_prefix().unwrap_or_default(), table)
+}
+
+/// Name of any table in raw SQL, prefixed if it's one of `PREFIXABLE_TABLES`.
+pub fn table_name_in_db(table: &str) -> String {
+    if PREFIXABLE_TABLES.contains(&table) {
+        prefixed_table_name(table)
+    } else {
+        table.to_string()
+    }
+}
+
+/// Replaces every quoted reference to a prefixable table. Diesel quotes identifiers and binds
+/// values, so only identifiers can match, and no column is named after a prefixable table.
+fn prefix_table_names(sql: &str, prefix: &str) -> String {
+    PREFIXABLE_TABLES
+        .iter()
+        .fold(sql.to_string(), |sql, table| {
+            sql.replace(
+                &format!("\"{}\"", table),
+                &format!("\"{}{}\"", prefix, table),
+            )
+        })
+}
+
+/// Creates the prefixed tables from the migrated ones, which serve as templates and stay
+/// empty. Columns, defaults, constraints and indexes are copied. Tables that already exist are
+/// left alone, so a migration that changes one of `PREFIXABLE_TABLES` has to be applied to the
+/// prefixed tables by hand.
+pub async fn create_prefixed_tables(pool: ArcDbPool) -> anyhow::Result<()> {
+    let Some(prefix) = table_prefix() else {
+        return Ok(());
+    };
+    for table in PREFIXABLE_TABLES {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS \"{prefix}{table}\" (LIKE \"{table}\" INCLUDING ALL)"
+        );
+        // Run directly, since going through `execute_with_better_error` would prefix the
+        // template table as well
+        let conn = &mut pool.get().await?;
+        sql_query(query)
+            .execute(conn)
+            .await
+            .with_context(|| format!("Failed to create table {}{}", prefix, table))?;
+    }
+    Ok(())
+}
+
+/// Rows inserted or updated since the process started, by unprefixed table name, for the
+/// progress report.
+static ROWS_WRITTEN: Lazy<Mutex<BTreeMap<String, u64>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+pub fn rows_written_by_table() -> BTreeMap<String, u64> {
+    ROWS_WRITTEN.lock().unwrap().clone()
+}
+
+fn record_rows_written(table_name: &str, num_rows: usize) {
+    if table_name == "unknown" || num_rows == 0 {
+        return;
+    }
+    *ROWS_WRITTEN
+        .lock()
+        .unwrap()
+        .entry(table_name.to_string())
+        .or_default() += num_rows as u64;
+}
+
+/// Gets the table an `INSERT INTO "table" ...` statement writes to, for logging.
+fn insert_table_name(query: &str) -> &str {
+    query
+        .strip_prefix("INSERT INTO ")
+        .and_then(|rest| rest.split_whitespace().next())
+        .map(|table| table.trim_matches('"'))
+        .unwrap_or("unknown")
+}
+
 #[derive(QueryId)]
 /// Using this will append a where clause at the end of the string upsert function
 ///
@@ -106,16 +331,32 @@ fn parse_and_clean_db_url(url: &str) -> (String, Option<String>) {
 pub async fn new_db_pool(
     database_url: &str,
     max_pool_size: Option<u32>,
+    connection_config: &DbConnectionConfig,
 ) -> Result<ArcDbPool, PoolError> {
-    let (_url, cert_path) = parse_and_clean_db_url(database_url);
+    let database_url = connection_config.apply_to_url(database_url);
+    let (_url, cert_path) = parse_and_clean_db_url(&database_url);
+    let has_cert = cert_path.is_some();
 
-    let config = if cert_path.is_some() {
-        let mut config = ManagerConfig::<MyDbConnection>::default();
-        config.custom_setup = Box::new(|conn| Box::pin(establish_connection(conn)));
-        AsyncDieselConnectionManager::<MyDbConnection>::new_with_config(database_url, config)
-    } else {
-        AsyncDieselConnectionManager::<MyDbConnection>::new(database_url)
-    };
+    let mut config = ManagerConfig::<MyDbConnection>::default();
+    let connection_config = connection_config.clone();
+    config.custom_setup = Box::new(move |url| {
+        let connection_config = connection_config.clone();
+        async move {
+            let mut conn = if has_cert {
+                establish_connection(url).await?
+            } else {
+                AsyncPgConnection::establish(url).await?
+            };
+            connection_config
+                .apply_to_connection(&mut conn)
+                .await
+                .map_err(ConnectionError::CouldntSetupConfiguration)?;
+            Ok(conn)
+        }
+        .boxed()
+    });
+    let config =
+        AsyncDieselConnectionManager::<MyDbConnection>::new_with_config(database_url, config);
     let pool = Pool::builder()
         .max_size(max_pool_size.unwrap_or(DEFAULT_MAX_POOL_SIZE))
         .build(config)
@@ -123,6 +364,23 @@ pub async fn new_db_pool(
     Ok(Arc::new(pool))
 }
 
+/// Creates the pool used for read-only queries that can tolerate replication lag. If no
+/// read replica is configured, this is the primary pool.
+///
+/// Reads that decide where to resume from (e.g. `last_success_version`) must keep using the
+/// primary, otherwise a lagging replica could rewind the processor.
+pub async fn new_read_only_db_pool(
+    primary_pool: &ArcDbPool,
+    read_replica_url: Option<&str>,
+    max_pool_size: Option<u32>,
+    connection_config: &DbConnectionConfig,
+) -> Result<ArcDbPool, PoolError> {
+    match read_replica_url {
+        Some(url) => new_db_pool(url, max_pool_size, connection_config).await,
+        None => Ok(primary_pool.clone()),
+    }
+}
+
 pub async fn execute_in_chunks<U, T>(
     conn: ArcDbPool,
     build_query: fn(Vec<T>) -> (U, Option<&'static str>),
@@ -139,6 +397,9 @@ where
             let conn = conn.clone();
             let items = chunk.to_vec();
             tokio::spawn(async move {
+                if let Some(breaker) = db_circuit_breaker() {
+                    return execute_with_circuit_breaker(breaker, conn, build_query, items).await;
+                }
                 let (query, additional_where_clause) = build_query(items.clone());
                 execute_or_retry_cleaned(conn, build_query, items, query, additional_where_clause)
                     .await
@@ -157,9 +418,22 @@ where
 }
 
 pub async fn execute_with_better_error<U>(
+    pool: ArcDbPool,
+    query: U,
+    additional_where_clause: Option<&'static str>,
+) -> QueryResult<usize>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send,
+{
+    execute_and_log_if_slow(pool, query, additional_where_clause, None).await
+}
+
+/// `num_rows` is what gets logged for slow statements, falling back to the affected rows.
+async fn execute_and_log_if_slow<U>(
     pool: ArcDbPool,
     query: U,
     mut additional_where_clause: Option<&'static str>,
+    num_rows: Option<usize>,
 ) -> QueryResult<usize>
 where
     U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send,
@@ -183,9 +457,21 @@ where
             Box::new(e.to_string()),
         )
     })?;
+    let query_start = std::time::Instant::now();
     let res = final_query.execute(conn).await;
-    if let Err(ref e) = res {
-        tracing::warn!("Error running query: {:?}\n{:?}", e, debug_string);
+    let query_duration = query_start.elapsed();
+    if query_duration > slow_query_threshold() {
+        tracing::warn!(
+            table_name = insert_table_name(&debug_string),
+            num_rows = num_rows.or(res.as_ref().ok().copied()),
+            duration_in_secs = query_duration.as_secs_f64(),
+            "Slow query",
+        );
+    }
+    match res {
+        // The original query isn't prefixed, so tables are counted the same with any prefix
+        Ok(num_rows) => record_rows_written(insert_table_name(&original_query), num_rows),
+        Err(ref e) => tracing::warn!("Error running query: {:?}\n{:?}", e, debug_string),
     }
     res
 }
@@ -204,6 +490,253 @@ pub fn get_config_table_chunk_size<T: field_count::FieldCount>(
         .unwrap_or_else(|| MAX_DIESEL_PARAM_SIZE / T::field_count())
 }
 
+/// How an insert handles rows that already exist, e.g. when a range is reprocessed. Immutable
+/// tables only need `DoNothing`, which is cheaper; `DoUpdate` rewrites the row, which is how
+/// newly added columns get backfilled.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictStrategy {
+    DoNothing,
+    DoUpdate,
+}
+
+/// Gets the conflict strategy configured for a table, falling back to the processor's default.
+pub fn get_config_table_conflict_strategy(
+    table_name: &str,
+    per_table_conflict_strategies: &AHashMap<String, ConflictStrategy>,
+    default: ConflictStrategy,
+) -> ConflictStrategy {
+    per_table_conflict_strategies
+        .get(table_name)
+        .copied()
+        .unwrap_or(default)
+}
+
+/// Same as `execute_in_chunks`, but picks between the two queries of a table based on the
+/// conflict strategy.
+pub async fn execute_in_chunks_with_conflict_strategy<U1, U2, T>(
+    conn: ArcDbPool,
+    conflict_strategy: ConflictStrategy,
+    build_do_nothing_query: fn(Vec<T>) -> (U1, Option<&'static str>),
+    build_do_update_query: fn(Vec<T>) -> (U2, Option<&'static str>),
+    items_to_insert: &[T],
+    chunk_size: usize,
+) -> Result<(), diesel::result::Error>
+where
+    U1: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    U2: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + 'static,
+{
+    match conflict_strategy {
+        ConflictStrategy::DoNothing => {
+            execute_in_chunks(conn, build_do_nothing_query, items_to_insert, chunk_size).await
+        },
+        ConflictStrategy::DoUpdate => {
+            execute_in_chunks(conn, build_do_update_query, items_to_insert, chunk_size).await
+        },
+    }
+}
+
+/// Same as `execute_in_chunks_with_conflict_strategy`, but each chunk is written in one DB
+/// transaction together with its `outbox` rows, so a CDC connector reading `outbox` sees exactly
+/// the rows that were committed. Outbox rows that already exist are handled with the same
+/// conflict strategy as the table.
+pub async fn execute_in_chunks_with_outbox<U1, U2, T>(
+    conn: ArcDbPool,
+    conflict_strategy: ConflictStrategy,
+    build_do_nothing_query: fn(Vec<T>) -> (U1, Option<&'static str>),
+    build_do_update_query: fn(Vec<T>) -> (U2, Option<&'static str>),
+    build_outbox: fn(&[T]) -> Vec<Outbox>,
+    items_to_insert: &[T],
+    chunk_size: usize,
+) -> Result<(), diesel::result::Error>
+where
+    U1: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    U2: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + 'static,
+{
+    match conflict_strategy {
+        ConflictStrategy::DoNothing => {
+            execute_in_chunks_with_outbox_query(
+                conn,
+                conflict_strategy,
+                build_do_nothing_query,
+                build_outbox,
+                items_to_insert,
+                chunk_size,
+            )
+            .await
+        },
+        ConflictStrategy::DoUpdate => {
+            execute_in_chunks_with_outbox_query(
+                conn,
+                conflict_strategy,
+                build_do_update_query,
+                build_outbox,
+                items_to_insert,
+                chunk_size,
+            )
+            .await
+        },
+    }
+}
+
+async fn execute_in_chunks_with_outbox_query<U, T>(
+    conn: ArcDbPool,
+    conflict_strategy: ConflictStrategy,
+    build_query: fn(Vec<T>) -> (U, Option<&'static str>),
+    build_outbox: fn(&[T]) -> Vec<Outbox>,
+    items_to_insert: &[T],
+    chunk_size: usize,
+) -> Result<(), diesel::result::Error>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + 'static,
+{
+    let tasks = items_to_insert
+        .chunks(chunk_size)
+        .map(|chunk| {
+            let conn = conn.clone();
+            let items = chunk.to_vec();
+            tokio::spawn(async move {
+                let write = || {
+                    execute_chunk_with_outbox_or_retry_cleaned(
+                        conn.clone(),
+                        conflict_strategy,
+                        build_query,
+                        build_outbox,
+                        items.clone(),
+                    )
+                };
+                match db_circuit_breaker() {
+                    Some(breaker) => retry_while_db_unhealthy(breaker, write).await,
+                    None => write().await,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let results = futures_util::future::try_join_all(tasks)
+        .await
+        .expect("Task panicked executing in chunks");
+    for res in results {
+        res?
+    }
+
+    Ok(())
+}
+
+async fn execute_chunk_with_outbox_or_retry_cleaned<U, T>(
+    pool: ArcDbPool,
+    conflict_strategy: ConflictStrategy,
+    build_query: fn(Vec<T>) -> (U, Option<&'static str>),
+    build_outbox: fn(&[T]) -> Vec<Outbox>,
+    items: Vec<T>,
+) -> Result<(), diesel::result::Error>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + 'static,
+{
+    let res = execute_chunk_with_outbox(
+        pool.clone(),
+        conflict_strategy,
+        build_query,
+        build_outbox,
+        items.clone(),
+    )
+    .await;
+    if res.is_ok() {
+        return res;
+    }
+    // Retried with cleaned data, like `execute_or_retry_cleaned`
+    let cleaned_items = clean_data_for_db(items, true);
+    execute_chunk_with_outbox(
+        pool,
+        conflict_strategy,
+        build_query,
+        build_outbox,
+        cleaned_items,
+    )
+    .await
+}
+
+async fn execute_chunk_with_outbox<U, T>(
+    pool: ArcDbPool,
+    conflict_strategy: ConflictStrategy,
+    build_query: fn(Vec<T>) -> (U, Option<&'static str>),
+    build_outbox: fn(&[T]) -> Vec<Outbox>,
+    items: Vec<T>,
+) -> Result<(), diesel::result::Error>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send + 'static,
+    T: Send + 'static,
+{
+    let outbox = build_outbox(&items);
+    let conn = &mut pool.get().await.map_err(|e| {
+        tracing::warn!("Error getting connection from pool: {:?}", e);
+        diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UnableToSendCommand,
+            Box::new(e.to_string()),
+        )
+    })?;
+    conn.transaction(|conn| {
+        async move {
+            let (query, additional_where_clause) = build_query(items);
+            execute_with_better_error_conn(conn, query, additional_where_clause).await?;
+            match conflict_strategy {
+                ConflictStrategy::DoNothing => {
+                    let (query, additional_where_clause) = insert_outbox_do_nothing_query(outbox);
+                    execute_with_better_error_conn(conn, query, additional_where_clause).await?;
+                },
+                ConflictStrategy::DoUpdate => {
+                    let (query, additional_where_clause) = insert_outbox_query(outbox);
+                    execute_with_better_error_conn(conn, query, additional_where_clause).await?;
+                },
+            }
+            Ok::<_, diesel::result::Error>(())
+        }
+        .scope_boxed()
+    })
+    .await
+}
+
+fn insert_outbox_do_nothing_query(
+    items_to_insert: Vec<Outbox>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::outbox::dsl::*;
+    (
+        diesel::insert_into(schema::outbox::table)
+            .values(items_to_insert)
+            .on_conflict((table_name, row_key))
+            .do_nothing(),
+        None,
+    )
+}
+
+fn insert_outbox_query(
+    items_to_insert: Vec<Outbox>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::outbox::dsl::*;
+    (
+        diesel::insert_into(schema::outbox::table)
+            .values(items_to_insert)
+            .on_conflict((table_name, row_key))
+            .do_update()
+            .set((
+                operation.eq(excluded(operation)),
+                payload.eq(excluded(payload)),
+                inserted_at.eq(excluded(inserted_at)),
+            )),
+        None,
+    )
+}
+
 pub async fn execute_with_better_error_conn<U>(
     conn: &mut MyDbConnection,
     query: U,
@@ -225,8 +758,10 @@ where
     let debug_string = diesel::debug_query::<Backend, _>(&final_query).to_string();
     tracing::debug!("Executing query: {:?}", debug_string);
     let res = final_query.execute(conn).await;
-    if let Err(ref e) = res {
-        tracing::warn!("Error running query: {:?}\n{:?}", e, debug_string);
+    match res {
+        // The original query isn't prefixed, so tables are counted the same with any prefix
+        Ok(num_rows) => record_rows_written(insert_table_name(&original_query), num_rows),
+        Err(ref e) => tracing::warn!("Error running query: {:?}\n{:?}", e, debug_string),
     }
     res
 }
@@ -242,13 +777,21 @@ where
     U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send,
     T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone,
 {
-    match execute_with_better_error(conn.clone(), query, additional_where_clause).await {
+    let num_rows = items.len();
+    match execute_and_log_if_slow(conn.clone(), query, additional_where_clause, Some(num_rows))
+        .await
+    {
         Ok(_) => {},
         Err(_) => {
             let cleaned_items = clean_data_for_db(items, true);
             let (cleaned_query, additional_where_clause) = build_query(cleaned_items);
-            match execute_with_better_error(conn.clone(), cleaned_query, additional_where_clause)
-                .await
+            match execute_and_log_if_slow(
+                conn.clone(),
+                cleaned_query,
+                additional_where_clause,
+                Some(num_rows),
+            )
+            .await
             {
                 Ok(_) => {},
                 Err(e) => {
@@ -260,9 +803,130 @@ where
     Ok(())
 }
 
+/// Retries the chunk for as long as the DB is unhealthy, waiting whenever the breaker is open.
+async fn execute_with_circuit_breaker<U, T>(
+    breaker: &DbCircuitBreaker,
+    conn: ArcDbPool,
+    build_query: fn(Vec<T>) -> (U, Option<&'static str>),
+    items: Vec<T>,
+) -> Result<(), diesel::result::Error>
+where
+    U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send,
+    T: serde::Serialize + for<'de> serde::Deserialize<'de> + Clone,
+{
+    retry_while_db_unhealthy(breaker, || {
+        let (query, additional_where_clause) = build_query(items.clone());
+        execute_or_retry_cleaned(
+            conn.clone(),
+            build_query,
+            items.clone(),
+            query,
+            additional_where_clause,
+        )
+    })
+    .await
+}
+
+/// Runs `write` until it succeeds or fails for a reason other than the DB being unhealthy,
+/// waiting whenever the breaker is open.
+async fn retry_while_db_unhealthy<F, Fut>(
+    breaker: &DbCircuitBreaker,
+    mut write: F,
+) -> Result<(), diesel::result::Error>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Result<(), diesel::result::Error>>,
+{
+    loop {
+        // Dropped on every way out of the iteration, so a trial that fails on its rows rather
+        // than on the DB doesn't keep the breaker half open forever
+        let _trial = breaker.wait_until_writable().await;
+        match write().await {
+            Ok(()) => {
+                breaker.record_success();
+                return Ok(());
+            },
+            Err(e) if is_db_unhealthy_error(&e) => breaker.record_failure(Instant::now()),
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Which migrations have run, served on `/migrations` so a slow migration doesn't look like a
+/// hung processor.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct MigrationStatus {
+    pub applied: Vec<String>,
+    pub running: Option<String>,
+    pub pending: Vec<String>,
+}
+
+/// Runs the pending migrations one at a time, logging and timing each of them.
 pub fn run_pending_migrations<DB: diesel::backend::Backend>(conn: &mut impl MigrationHarness<DB>) {
-    conn.run_pending_migrations(MIGRATIONS)
-        .expect("[Parser] Migrations failed!");
+    run_pending_migrations_with_status(conn, &Mutex::default())
+}
+
+/// Same as `run_pending_migrations`, keeping `status` up to date as they run.
+pub fn run_pending_migrations_with_status<DB: diesel::backend::Backend>(
+    conn: &mut impl MigrationHarness<DB>,
+    status: &Mutex<MigrationStatus>,
+) {
+    let applied_versions: HashSet<String> = conn
+        .applied_migrations()
+        .expect("[Parser] Failed to get applied migrations")
+        .iter()
+        .map(|version| version.to_string())
+        .collect();
+    let pending_migrations = conn
+        .pending_migrations(MIGRATIONS)
+        .expect("[Parser] Failed to get pending migrations");
+    {
+        let mut status = status.lock().unwrap();
+        status.applied = MigrationSource::<DB>::migrations(&MIGRATIONS)
+            .expect("[Parser] Failed to list migrations")
+            .iter()
+            .filter(|migration| applied_versions.contains(&migration.name().version().to_string()))
+            .map(|migration| migration.name().to_string())
+            .collect();
+        status.pending = pending_migrations
+            .iter()
+            .map(|migration| migration.name().to_string())
+            .collect();
+    }
+    tracing::info!(
+        num_pending_migrations = pending_migrations.len(),
+        "[Parser] Running pending migrations"
+    );
+
+    for migration in pending_migrations {
+        let migration_name = migration.name().to_string();
+        tracing::info!(
+            migration_name = migration_name,
+            "[Parser] Running migration"
+        );
+        {
+            let mut status = status.lock().unwrap();
+            status.pending.retain(|name| name != &migration_name);
+            status.running = Some(migration_name.clone());
+        }
+
+        let migration_start = std::time::Instant::now();
+        conn.run_migration(&migration)
+            .expect("[Parser] Migrations failed!");
+        let duration_in_secs = migration_start.elapsed().as_secs_f64();
+
+        MIGRATION_DURATION_IN_SECS
+            .with_label_values(&[&migration_name])
+            .set(duration_in_secs);
+        tracing::info!(
+            migration_name = migration_name,
+            duration_in_secs,
+            "[Parser] Finished migration"
+        );
+        let mut status = status.lock().unwrap();
+        status.running = None;
+        status.applied.push(migration_name);
+    }
 }
 
 /// Section below is required to modify the query.
@@ -283,6 +947,32 @@ where
         }
         Ok(())
     }
+
+    /// Every statement is executed through this wrapper, so this is where `table_prefix` is
+    /// applied. Only the SQL changes, the binds are collected through `walk_ast` as usual.
+    fn to_sql(&self, out: &mut PgQueryBuilder, backend: &Backend) -> QueryResult<()> {
+        match table_prefix() {
+            Some(prefix) => {
+                let mut query_builder = PgQueryBuilder::default();
+                WithoutTablePrefix(self).to_sql(&mut query_builder, backend)?;
+                out.push_sql(&prefix_table_names(&query_builder.finish(), prefix));
+                Ok(())
+            },
+            None => WithoutTablePrefix(self).to_sql(out, backend),
+        }
+    }
+}
+
+/// The default `to_sql`, which goes through `walk_ast`.
+struct WithoutTablePrefix<'a, T>(&'a UpsertFilterLatestTransactionQuery<T>);
+
+impl<T> QueryFragment<Backend> for WithoutTablePrefix<'_, T>
+where
+    T: QueryFragment<Backend>,
+{
+    fn walk_ast<'b>(&'b self, out: AstPass<'_, 'b, Backend>) -> QueryResult<()> {
+        self.0.walk_ast(out)
+    }
 }
 
 pub struct DbContext<'a> {
@@ -290,3 +980,81 @@ pub struct DbContext<'a> {
     pub query_retries: u32,
     pub query_retry_delay_ms: u64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_table_name() {
+        assert_eq!(
+            insert_table_name(
+                "INSERT INTO \"events\" (\"sequence_number\", \"creation_number\") VALUES ($1, $2)"
+            ),
+            "events"
+        );
+        assert_eq!(
+            insert_table_name("SELECT 1 FROM events WHERE 1=0"),
+            "unknown"
+        );
+    }
+
+    #[test]
+    fn test_prefix_table_names() {
+        let query = UpsertFilterLatestTransactionQuery {
+            query: diesel::insert_into(crate::schema::events::table)
+                .values((
+                    crate::schema::events::transaction_version.eq(1),
+                    crate::schema::events::event_index.eq(0),
+                ))
+                .on_conflict((
+                    crate::schema::events::transaction_version,
+                    crate::schema::events::event_index,
+                ))
+                .do_nothing(),
+            where_clause: None,
+        };
+        let mut query_builder = PgQueryBuilder::default();
+        WithoutTablePrefix(&query)
+            .to_sql(&mut query_builder, &Backend::default())
+            .unwrap();
+        let sql = query_builder.finish();
+        assert_eq!(
+            prefix_table_names(&sql, "dappa_"),
+            r#"INSERT INTO "dappa_events" ("transaction_version", "event_index") VALUES ($1, $2) ON CONFLICT ("transaction_version", "event_index") DO NOTHING"#
+        );
+
+        // Tables that aren't prefixable are left alone
+        let sql = r#"INSERT INTO "processor_status" ("processor") VALUES ($1)"#;
+        assert_eq!(prefix_table_names(sql, "dappa_"), sql);
+        let sql = r#"UPDATE "user_transactions" SET "sender" = 'events' WHERE "user_transactions"."version" = $1"#;
+        assert_eq!(
+            prefix_table_names(sql, "dappa_"),
+            r#"UPDATE "dappa_user_transactions" SET "sender" = 'events' WHERE "dappa_user_transactions"."version" = $1"#
+        );
+    }
+
+    #[test]
+    fn test_set_table_prefix_rejects_identifiers_that_need_quoting() {
+        assert!(set_table_prefix(Some("dapp\"a_".to_string())).is_err());
+        assert!(set_table_prefix(Some("DappA_".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_keepalive_is_added_to_url() {
+        let config = DbConnectionConfig::default();
+        assert_eq!(
+            config.apply_to_url("postgresql://postgres:@localhost:5432/db"),
+            "postgresql://postgres:@localhost:5432/db?keepalives=1&keepalives_idle=60"
+        );
+        // A keepalive in the connection string wins
+        let url = "postgresql://postgres:@localhost:5432/db?keepalives_idle=10";
+        assert_eq!(config.apply_to_url(url), url);
+        let config = DbConnectionConfig {
+            tcp_keepalive_secs: 0,
+            ..DbConnectionConfig::default()
+        };
+        let url = "postgresql://postgres:@localhost:5432/db?sslmode=require";
+        assert_eq!(config.apply_to_url(url), url);
+    }
+}
@@ -0,0 +1,259 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Backs off DB writes while the DB looks unhealthy. After `failure_threshold` consecutive
+//! failed inserts the breaker opens and writes wait, with the wait doubling each time it opens
+//! again. Once the wait is over it's half open: a single write goes through to test the DB,
+//! closing the breaker if it succeeds and opening it again if the DB looks unhealthy. If it
+//! fails for any other reason the next write makes the trial. Failed writes are retried rather
+//! than failing the batch, so progress doesn't advance past them while it's open.
+
+use crate::utils::counters::DB_CIRCUIT_BREAKER_OPEN;
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// How often writes waiting on a half open breaker check whether the trial write is done.
+const HALF_OPEN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DbCircuitBreakerConfig {
+    #[serde(default = "DbCircuitBreakerConfig::default_failure_threshold")]
+    pub failure_threshold: u32,
+    #[serde(default = "DbCircuitBreakerConfig::default_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    #[serde(default = "DbCircuitBreakerConfig::default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl DbCircuitBreakerConfig {
+    pub const fn default_failure_threshold() -> u32 {
+        5
+    }
+
+    pub const fn default_initial_backoff_ms() -> u64 {
+        1_000
+    }
+
+    pub const fn default_max_backoff_ms() -> u64 {
+        60_000
+    }
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    /// Times opened since it was last closed, for the backoff
+    times_opened: u32,
+    /// Set while open or half open
+    open_until: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+pub struct DbCircuitBreaker {
+    config: DbCircuitBreakerConfig,
+    state: Mutex<BreakerState>,
+}
+
+/// Held by the write testing a half open breaker. If it's dropped before the outcome is
+/// recorded, e.g. because the write failed for a reason that doesn't count against the DB, the
+/// next write makes the trial instead.
+pub struct TrialWrite<'a> {
+    breaker: &'a DbCircuitBreaker,
+}
+
+impl Drop for TrialWrite<'_> {
+    fn drop(&mut self) {
+        // Recording the outcome already ended the trial, and no other trial can start until the
+        // breaker is open again past its wait
+        self.breaker.state.lock().unwrap().trial_in_flight = false;
+    }
+}
+
+static DB_CIRCUIT_BREAKER: OnceCell<DbCircuitBreaker> = OnceCell::new();
+
+/// Enables the breaker for every insert in the process. Only the first call has an effect.
+pub fn set_db_circuit_breaker(config: DbCircuitBreakerConfig) {
+    let _ = DB_CIRCUIT_BREAKER.set(DbCircuitBreaker::new(config));
+}
+
+/// None unless `set_db_circuit_breaker` was called.
+pub fn db_circuit_breaker() -> Option<&'static DbCircuitBreaker> {
+    DB_CIRCUIT_BREAKER.get()
+}
+
+/// Whether the error says more about the DB than about the rows being written, e.g. no
+/// connection could be had. Other errors don't count towards opening the breaker.
+pub fn is_db_unhealthy_error(error: &diesel::result::Error) -> bool {
+    matches!(
+        error,
+        diesel::result::Error::DatabaseError(
+            diesel::result::DatabaseErrorKind::UnableToSendCommand
+                | diesel::result::DatabaseErrorKind::ClosedConnection,
+            _
+        )
+    )
+}
+
+impl DbCircuitBreaker {
+    pub fn new(config: DbCircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// How long to wait before trying to write, or Ok to write now. Past the open period the
+    /// first caller gets to make the trial write.
+    fn wait_time(&self, now: Instant) -> Result<Option<TrialWrite<'_>>, Duration> {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            None => Ok(None),
+            Some(open_until) if now < open_until => Err(open_until - now),
+            Some(_) if state.trial_in_flight => Err(HALF_OPEN_POLL_INTERVAL),
+            Some(_) => {
+                state.trial_in_flight = true;
+                info!("[Parser] DB circuit breaker half open, trying a write");
+                Ok(Some(TrialWrite { breaker: self }))
+            },
+        }
+    }
+
+    /// Waits until the breaker lets a write through. The trial write of a half open breaker
+    /// gets a `TrialWrite`, to hold until its outcome is recorded.
+    pub async fn wait_until_writable(&self) -> Option<TrialWrite<'_>> {
+        loop {
+            match self.wait_time(Instant::now()) {
+                Ok(trial) => return trial,
+                Err(wait_time) => tokio::time::sleep(wait_time).await,
+            }
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if state.open_until.is_some() {
+            info!("[Parser] DB circuit breaker closed");
+        }
+        *state = BreakerState::default();
+        DB_CIRCUIT_BREAKER_OPEN.set(0);
+    }
+
+    pub fn record_failure(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        let trial_failed = state.trial_in_flight;
+        let threshold_reached = state.open_until.is_none()
+            && state.consecutive_failures >= self.config.failure_threshold;
+        if !trial_failed && !threshold_reached {
+            return;
+        }
+        let backoff = Duration::from_millis(
+            self.config
+                .initial_backoff_ms
+                .saturating_mul(1 << state.times_opened.min(32))
+                .min(self.config.max_backoff_ms),
+        );
+        state.times_opened += 1;
+        state.open_until = Some(now + backoff);
+        state.trial_in_flight = false;
+        DB_CIRCUIT_BREAKER_OPEN.set(1);
+        warn!(
+            consecutive_failures = state.consecutive_failures,
+            backoff_ms = backoff.as_millis() as u64,
+            "[Parser] DB circuit breaker open, pausing writes"
+        );
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state.lock().unwrap().open_until.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> DbCircuitBreaker {
+        DbCircuitBreaker::new(DbCircuitBreakerConfig {
+            failure_threshold: 3,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 3_000,
+        })
+    }
+
+    #[test]
+    fn test_failures_open_and_success_closes() {
+        let breaker = breaker();
+        let start = Instant::now();
+        breaker.record_failure(start);
+        breaker.record_failure(start);
+        assert!(!breaker.is_open());
+        assert!(matches!(breaker.wait_time(start), Ok(None)));
+        breaker.record_failure(start);
+        assert!(breaker.is_open());
+        assert_eq!(breaker.wait_time(start).err(), Some(Duration::from_secs(1)));
+
+        // Half open: one trial write, the others keep waiting. The trial fails, so it opens
+        // again for twice as long
+        let after_backoff = start + Duration::from_secs(1);
+        let trial = breaker.wait_time(after_backoff).unwrap();
+        assert!(trial.is_some());
+        assert_eq!(
+            breaker.wait_time(after_backoff).err(),
+            Some(HALF_OPEN_POLL_INTERVAL)
+        );
+        breaker.record_failure(after_backoff);
+        drop(trial);
+        assert_eq!(
+            breaker.wait_time(after_backoff).err(),
+            Some(Duration::from_secs(2))
+        );
+
+        // Capped at max_backoff_ms
+        let after_backoff = after_backoff + Duration::from_secs(2);
+        let trial = breaker.wait_time(after_backoff).unwrap();
+        assert!(trial.is_some());
+        breaker.record_failure(after_backoff);
+        drop(trial);
+        assert_eq!(
+            breaker.wait_time(after_backoff).err(),
+            Some(Duration::from_secs(3))
+        );
+
+        let after_backoff = after_backoff + Duration::from_secs(3);
+        let trial = breaker.wait_time(after_backoff).unwrap();
+        assert!(trial.is_some());
+        breaker.record_success();
+        drop(trial);
+        assert!(!breaker.is_open());
+        assert!(matches!(breaker.wait_time(after_backoff), Ok(None)));
+        // Counting starts over
+        breaker.record_failure(after_backoff);
+        assert!(!breaker.is_open());
+    }
+
+    #[test]
+    fn test_trial_ending_without_outcome_lets_next_write_try() {
+        let breaker = breaker();
+        let start = Instant::now();
+        for _ in 0..3 {
+            breaker.record_failure(start);
+        }
+        let after_backoff = start + Duration::from_secs(1);
+        let trial = breaker.wait_time(after_backoff).unwrap();
+        assert!(trial.is_some());
+        assert!(breaker.wait_time(after_backoff).is_err());
+
+        // The trial write failed on its rows rather than on the DB
+        drop(trial);
+        assert!(breaker.is_open());
+        let trial = breaker.wait_time(after_backoff).unwrap();
+        assert!(trial.is_some());
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Retries failed batches until too many fail within a rolling window, then exits so the
+//! orchestrator restarts the processor. Without it, the first processing error panics. With it,
+//! a transient error, e.g. the DB failing over, doesn't cost a restart, while a processor that
+//! keeps failing still gets one instead of limping along.
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tracing::error;
+
+/// Exit code once the error budget is spent, apart from the 12 of a panic
+pub const ERROR_BUDGET_EXIT_CODE: i32 = 13;
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct ErrorBudgetConfig {
+    /// Processing errors within `window_secs` that make the processor exit
+    pub max_errors: usize,
+    pub window_secs: u64,
+    /// Wait before retrying a failed batch
+    #[serde(default = "ErrorBudgetConfig::default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+}
+
+impl ErrorBudgetConfig {
+    pub const fn default_retry_delay_ms() -> u64 {
+        1000
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.max_errors == 0 {
+            bail!("error_budget max_errors must be greater than 0");
+        }
+        if self.window_secs == 0 {
+            bail!("error_budget window_secs must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// Processing errors of the last window, shared by all the processing tasks.
+#[derive(Debug)]
+pub struct ErrorBudget {
+    max_errors: usize,
+    window: Duration,
+    pub retry_delay: Duration,
+    errors: Mutex<VecDeque<Instant>>,
+}
+
+impl ErrorBudget {
+    pub fn new(config: &ErrorBudgetConfig) -> Self {
+        Self {
+            max_errors: config.max_errors,
+            window: Duration::from_secs(config.window_secs),
+            retry_delay: Duration::from_millis(config.retry_delay_ms),
+            errors: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Records a processing error, exiting with `ERROR_BUDGET_EXIT_CODE` once the budget is spent.
+    pub fn record_error(&self, processor_name: &str) {
+        let errors_in_window = self.record_error_at(Instant::now());
+        if errors_in_window >= self.max_errors {
+            error!(
+                processor_name,
+                errors_in_window,
+                window_in_secs = self.window.as_secs(),
+                "[Parser] Error budget spent, exiting"
+            );
+            std::process::exit(ERROR_BUDGET_EXIT_CODE);
+        }
+    }
+
+    /// Records a processing error at `now` and returns the errors within the window up to it.
+    fn record_error_at(&self, now: Instant) -> usize {
+        let mut errors = self.errors.lock().unwrap();
+        while errors
+            .front()
+            .is_some_and(|error| now.duration_since(*error) >= self.window)
+        {
+            errors.pop_front();
+        }
+        errors.push_back(now);
+        errors.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn budget(max_errors: usize) -> ErrorBudget {
+        ErrorBudget::new(&ErrorBudgetConfig {
+            max_errors,
+            window_secs: 60,
+            retry_delay_ms: 0,
+        })
+    }
+
+    #[test]
+    fn test_rolling_window() {
+        let budget = budget(3);
+        let start = Instant::now();
+        assert_eq!(budget.record_error_at(start), 1);
+        assert_eq!(budget.record_error_at(start + Duration::from_secs(30)), 2);
+        // The first error is out of the window by now
+        assert_eq!(budget.record_error_at(start + Duration::from_secs(60)), 2);
+        assert_eq!(budget.record_error_at(start + Duration::from_secs(70)), 3);
+    }
+
+    #[test]
+    fn test_exits_once_spent() {
+        // Exiting ends the test binary, so it's run again to exit in a child process
+        if std::env::var("ERROR_BUDGET_SPEND").is_ok() {
+            let budget = budget(2);
+            budget.record_error("test_exits_once_spent");
+            budget.record_error("test_exits_once_spent");
+            return;
+        }
+        let status = Command::new(std::env::current_exe().unwrap())
+            .args([
+                "--exact",
+                "utils::error_budget::tests::test_exits_once_spent",
+            ])
+            .env("ERROR_BUDGET_SPEND", "1")
+            .status()
+            .unwrap();
+        assert_eq!(status.code(), Some(ERROR_BUDGET_EXIT_CODE));
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    db::postgres::models::failed_events::FailedEvent,
+    schema,
+    utils::database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+};
+use ahash::AHashMap;
+use aptos_protos::transaction::v1::Event as EventPB;
+use diesel::{pg::Pg, query_builder::QueryFragment};
+
+/// Wraps event parsing so that, when enabled, events that fail to parse are collected for the
+/// `failed_events` dead-letter table instead of failing the batch. When disabled, parse errors
+/// are returned as before.
+pub struct FailedEventCollector {
+    processor_name: &'static str,
+    enabled: bool,
+    failed_events: Vec<FailedEvent>,
+}
+
+impl FailedEventCollector {
+    pub fn new(processor_name: &'static str, enabled: bool) -> Self {
+        Self {
+            processor_name,
+            enabled,
+            failed_events: vec![],
+        }
+    }
+
+    /// Runs `parse` on the event. On error, records the event and returns `Ok(None)` if the
+    /// dead-letter table is enabled, otherwise returns the error.
+    pub fn parse<T>(
+        &mut self,
+        event: &EventPB,
+        txn_version: i64,
+        event_index: i64,
+        parse: impl FnOnce(&EventPB) -> anyhow::Result<Option<T>>,
+    ) -> anyhow::Result<Option<T>> {
+        match parse(event) {
+            Ok(parsed) => Ok(parsed),
+            Err(e) if self.enabled => {
+                tracing::warn!(
+                    processor_name = self.processor_name,
+                    transaction_version = txn_version,
+                    event_index,
+                    event_type = event.type_str,
+                    error = ?e,
+                    "Failed to parse event, routing to failed_events",
+                );
+                self.failed_events.push(FailedEvent::from_event(
+                    self.processor_name,
+                    event,
+                    txn_version,
+                    event_index,
+                    &e,
+                ));
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn into_failed_events(self) -> Vec<FailedEvent> {
+        self.failed_events
+    }
+}
+
+pub async fn insert_failed_events(
+    conn: ArcDbPool,
+    failed_events: &[FailedEvent],
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<(), diesel::result::Error> {
+    execute_in_chunks(
+        conn,
+        insert_failed_events_query,
+        failed_events,
+        get_config_table_chunk_size::<FailedEvent>("failed_events", per_table_chunk_sizes),
+    )
+    .await
+}
+
+fn insert_failed_events_query(
+    items_to_insert: Vec<FailedEvent>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::failed_events::dsl::*;
+    (
+        diesel::insert_into(schema::failed_events::table)
+            .values(items_to_insert)
+            .on_conflict((processor, transaction_version, event_index))
+            .do_nothing(),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn malformed_event() -> EventPB {
+        EventPB {
+            type_str: "0xcafe::poke::Poke".to_string(),
+            data: "{\"user\": \"0x1\", \"times\": ".to_string(),
+            ..EventPB::default()
+        }
+    }
+
+    fn parse_times(event: &EventPB) -> anyhow::Result<Option<u64>> {
+        let data: serde_json::Value = serde_json::from_str(&event.data)?;
+        Ok(data["times"].as_u64())
+    }
+
+    #[test]
+    fn test_malformed_event_routed_to_dead_letter() {
+        let mut collector = FailedEventCollector::new("test_processor", true);
+        let parsed = collector
+            .parse(&malformed_event(), 100, 2, parse_times)
+            .unwrap();
+        assert!(parsed.is_none());
+
+        let failed_events = collector.into_failed_events();
+        assert_eq!(failed_events.len(), 1);
+        assert_eq!(failed_events[0].processor, "test_processor");
+        assert_eq!(failed_events[0].transaction_version, 100);
+        assert_eq!(failed_events[0].event_index, 2);
+        assert_eq!(failed_events[0].type_, "0xcafe::poke::Poke");
+        assert_eq!(failed_events[0].data, malformed_event().data);
+        assert!(!failed_events[0].error.is_empty());
+    }
+
+    #[test]
+    fn test_malformed_event_errors_when_disabled() {
+        let mut collector = FailedEventCollector::new("test_processor", false);
+        assert!(collector
+            .parse(&malformed_event(), 100, 2, parse_times)
+            .is_err());
+        assert!(collector.into_failed_events().is_empty());
+    }
+}
@@ -0,0 +1,22 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Switches for parsing logic that's still being rolled out, set in a processor's config, e.g.
+//! `feature_flags: {trim_token_uris: true}`. A risky change ships turned off, is turned on for
+//! one deployment by changing its config, and turned back off the same way if the output looks
+//! wrong, without a new build. Flags the processor doesn't know are ignored, so a flag can be set
+//! ahead of the build that reads it.
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct FeatureFlags(BTreeMap<String, bool>);
+
+impl FeatureFlags {
+    /// Off unless set to `true`.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.0.get(name).copied().unwrap_or(false)
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::counters::{
+    HEARTBEAT_COUNT, HEARTBEAT_SECS_SINCE_LAST_BATCH, HEARTBEAT_TIP_VERSION,
+};
+use std::{sync::Mutex, time::Duration};
+use tokio::time::Instant;
+use tracing::info;
+
+/// Last version the fetcher received from the stream and when it arrived. While catching up
+/// that's how far the fetcher has got, not the chain tip.
+pub struct StreamTip {
+    // (version, received at). The version is None until the first batch
+    latest: Mutex<(Option<u64>, Instant)>,
+}
+
+impl Default for StreamTip {
+    fn default() -> Self {
+        Self {
+            latest: Mutex::new((None, Instant::now())),
+        }
+    }
+}
+
+impl StreamTip {
+    pub fn record(&self, version: u64) {
+        *self.latest.lock().unwrap() = (Some(version), Instant::now());
+    }
+
+    /// The last fetched version, and how long ago its batch arrived (or since startup).
+    pub fn get(&self) -> (Option<u64>, Duration) {
+        let (version, received_at) = *self.latest.lock().unwrap();
+        (version, received_at.elapsed())
+    }
+}
+
+/// Emits a heartbeat every `interval` whether or not transactions are flowing, reporting the last
+/// fetched version and the seconds since its batch arrived. It runs as a task of its own, so it
+/// keeps beating while the fetcher or the processing tasks hang; a growing time since the last
+/// batch is what shows the stream stalled. Only reads `tip`, so it never affects the fetcher or
+/// its response timeout.
+pub async fn run_heartbeat(processor_name: &'static str, interval: Duration, tip: &StreamTip) {
+    let mut ticker = tokio::time::interval(interval);
+    // The first tick completes immediately
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let (last_fetched_version, since_last_batch) = tip.get();
+        HEARTBEAT_COUNT.with_label_values(&[processor_name]).inc();
+        HEARTBEAT_SECS_SINCE_LAST_BATCH
+            .with_label_values(&[processor_name])
+            .set(since_last_batch.as_secs_f64());
+        if let Some(last_fetched_version) = last_fetched_version {
+            HEARTBEAT_TIP_VERSION
+                .with_label_values(&[processor_name])
+                .set(last_fetched_version as i64);
+        }
+        info!(
+            processor_name,
+            last_fetched_version,
+            secs_since_last_batch = since_last_batch.as_secs_f64(),
+            "[Parser] Heartbeat"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn test_stream_tip() {
+        let tip = StreamTip::default();
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert_eq!(tip.get(), (None, Duration::from_secs(3)));
+        tip.record(10);
+        tokio::time::advance(Duration::from_secs(2)).await;
+        tip.record(20);
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(tip.get(), (Some(20), Duration::from_secs(1)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_run_heartbeat() {
+        const PROCESSOR_NAME: &str = "heartbeat_test_processor";
+        let tip = StreamTip::default();
+        tip.record(7);
+        // Beats at 10s and 20s, with no batch since the one at 0s
+        let _ = tokio::time::timeout(
+            Duration::from_secs(25),
+            run_heartbeat(PROCESSOR_NAME, Duration::from_secs(10), &tip),
+        )
+        .await;
+        assert_eq!(
+            HEARTBEAT_COUNT.with_label_values(&[PROCESSOR_NAME]).get(),
+            2
+        );
+        assert_eq!(
+            HEARTBEAT_TIP_VERSION
+                .with_label_values(&[PROCESSOR_NAME])
+                .get(),
+            7
+        );
+        let since_last_batch = HEARTBEAT_SECS_SINCE_LAST_BATCH
+            .with_label_values(&[PROCESSOR_NAME])
+            .get();
+        assert!((20.0..21.0).contains(&since_last_batch));
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::{
+    counters::OLDEST_UNCOMMITTED_TRANSACTION_UNIX_TIMESTAMP,
+    util::{timestamp_to_unixtime, transaction_timestamp},
+};
+use aptos_protos::util::timestamp::Timestamp;
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// Tracks batches from the moment the fetcher pulls them off the stream until the gap detector
+/// commits progress past them, and exports the timestamp of the oldest one. Unlike the per-step
+/// latency metrics, this is the worst case across everything in the pipeline, including batches
+/// waiting in the channel or stuck behind a gap.
+pub struct InFlightVersions {
+    processor_name: String,
+    // Batch end version -> timestamp of its first transaction. Versions and timestamps increase
+    // together, so the first entry is always the oldest.
+    batches: Mutex<BTreeMap<u64, f64>>,
+}
+
+impl InFlightVersions {
+    pub fn new(processor_name: String) -> Self {
+        Self {
+            processor_name,
+            batches: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Called by the fetcher for every batch it receives, before it's sent to the channel.
+    /// Genesis has no timestamp, so it isn't tracked rather than reporting the Unix epoch.
+    pub fn record_fetched(&self, end_version: u64, start_txn_timestamp: Option<&Timestamp>) {
+        let Some(timestamp) = transaction_timestamp(start_txn_timestamp).map(timestamp_to_unixtime)
+        else {
+            return;
+        };
+        let mut batches = self.batches.lock().unwrap();
+        batches.entry(end_version).or_insert(timestamp);
+        self.update_gauge(&batches);
+    }
+
+    /// Called once `processor_status` has been updated to `version`. Everything up to it is out
+    /// of the pipeline, and the gauge goes back to 0 if nothing else is.
+    pub fn record_committed(&self, version: u64) {
+        let mut batches = self.batches.lock().unwrap();
+        *batches = batches.split_off(&(version + 1));
+        self.update_gauge(&batches);
+    }
+
+    pub fn oldest_timestamp(&self) -> Option<f64> {
+        self.batches.lock().unwrap().values().next().copied()
+    }
+
+    fn update_gauge(&self, batches: &BTreeMap<u64, f64>) {
+        OLDEST_UNCOMMITTED_TRANSACTION_UNIX_TIMESTAMP
+            .with_label_values(&[&self.processor_name])
+            .set(batches.values().next().copied().unwrap_or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timestamp(seconds: i64) -> Timestamp {
+        Timestamp { seconds, nanos: 0 }
+    }
+
+    #[test]
+    fn test_oldest_uncommitted_resets_when_drained() {
+        let in_flight = InFlightVersions::new("test_processor".to_string());
+        in_flight.record_fetched(99, Some(&timestamp(100)));
+        in_flight.record_fetched(199, Some(&timestamp(200)));
+        in_flight.record_fetched(299, Some(&timestamp(300)));
+        assert_eq!(in_flight.oldest_timestamp(), Some(100.0));
+
+        // Progress only moves past whole batches
+        in_flight.record_committed(150);
+        assert_eq!(in_flight.oldest_timestamp(), Some(200.0));
+        in_flight.record_committed(199);
+        assert_eq!(in_flight.oldest_timestamp(), Some(300.0));
+
+        in_flight.record_committed(299);
+        assert_eq!(in_flight.oldest_timestamp(), None);
+        assert_eq!(
+            OLDEST_UNCOMMITTED_TRANSACTION_UNIX_TIMESTAMP
+                .with_label_values(&["test_processor"])
+                .get(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn test_genesis_isnt_tracked() {
+        let in_flight = InFlightVersions::new("test_genesis_isnt_tracked".to_string());
+        in_flight.record_fetched(0, Some(&timestamp(0)));
+        in_flight.record_fetched(9, None);
+        assert_eq!(in_flight.oldest_timestamp(), None);
+        in_flight.record_fetched(99, Some(&timestamp(100)));
+        assert_eq!(in_flight.oldest_timestamp(), Some(100.0));
+    }
+}
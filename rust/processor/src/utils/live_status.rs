@@ -0,0 +1,145 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::progress_report::ProcessedRanges;
+use aptos_moving_average::MovingAverage;
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Mutex,
+};
+
+static TAIL_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turns on `--tail` mode: a one-line summary of every processed batch is printed to stdout,
+/// separately from the JSON tracing logs. Meant for local development only.
+pub fn enable_tail() {
+    TAIL_ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_tail_enabled() -> bool {
+    TAIL_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Bounds on the reported TPS. Batches that finish within a few milliseconds of each other
+/// would otherwise report millions of TPS.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+#[serde(default)]
+pub struct TpsReportingConfig {
+    /// TPS is averaged over at least this long.
+    pub min_elapsed_millis: u64,
+    /// Reported TPS is capped at this, with a warning.
+    pub max_tps: f64,
+}
+
+impl Default for TpsReportingConfig {
+    fn default() -> Self {
+        Self {
+            min_elapsed_millis: 100,
+            max_tps: 1_000_000.0,
+        }
+    }
+}
+
+impl TpsReportingConfig {
+    pub fn moving_average(&self, window_millis: u64) -> MovingAverage {
+        MovingAverage::new(window_millis).with_limits(self.min_elapsed_millis, Some(self.max_tps))
+    }
+
+    /// TPS of a single batch.
+    pub fn tps(&self, num_transactions: f64, elapsed_secs: f64) -> f64 {
+        let min_elapsed_secs = self.min_elapsed_millis as f64 / 1000.0;
+        (num_transactions / elapsed_secs.max(min_elapsed_secs)).min(self.max_tps)
+    }
+}
+
+/// Live TPS, lag and progress of the processor, published by the processor tasks after every
+/// batch and served as JSON on the server framework's `/status` endpoint. Readers only touch
+/// atomics so the endpoint never contends with processing.
+pub struct LiveProcessorStatus {
+    processor_name: &'static str,
+    // Shared across tasks so TPS reflects all concurrent tasks, not just one
+    ma: Mutex<MovingAverage>,
+    // f64 values are stored as bits
+    tps: AtomicU64,
+    lag_in_secs: AtomicU64,
+    last_processed_version: AtomicU64,
+    // For the progress report, which needs every range rather than just the last version
+    processed_ranges: ProcessedRanges,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LiveProcessorStatusSnapshot {
+    pub processor_name: &'static str,
+    pub tps: f64,
+    pub lag_in_secs: f64,
+    pub last_processed_version: u64,
+}
+
+impl LiveProcessorStatus {
+    pub fn new(processor_name: &'static str, tps_reporting: &TpsReportingConfig) -> Self {
+        Self {
+            processor_name,
+            ma: Mutex::new(tps_reporting.moving_average(3000)),
+            tps: AtomicU64::new(0f64.to_bits()),
+            lag_in_secs: AtomicU64::new(0f64.to_bits()),
+            last_processed_version: AtomicU64::new(0),
+            processed_ranges: ProcessedRanges::default(),
+        }
+    }
+
+    /// `lag_in_secs` is None for batches without a timestamp, i.e. genesis, keeping the last lag.
+    pub fn record_batch(
+        &self,
+        num_processed: u64,
+        last_processed_version: u64,
+        lag_in_secs: Option<f64>,
+    ) {
+        let tps = {
+            let mut ma = self.ma.lock().unwrap();
+            ma.tick_now(num_processed);
+            ma.avg()
+        };
+        self.tps.store(tps.to_bits(), Ordering::Relaxed);
+        if let Some(lag_in_secs) = lag_in_secs {
+            self.lag_in_secs
+                .store(lag_in_secs.to_bits(), Ordering::Relaxed);
+        }
+        // Batches from concurrent tasks can finish out of order
+        self.last_processed_version
+            .fetch_max(last_processed_version, Ordering::Relaxed);
+    }
+
+    /// Records the versions a batch covered, including the ones filtered out of it.
+    pub fn record_batch_range(&self, start_version: u64, end_version: u64) {
+        self.processed_ranges.record(start_version, end_version);
+    }
+
+    pub fn processed_ranges(&self) -> &ProcessedRanges {
+        &self.processed_ranges
+    }
+
+    /// Prints the batch summary line for `--tail` mode, using the TPS averaged across all tasks.
+    pub fn print_tail_line(&self, first_version: u64, last_version: u64) {
+        let snapshot = self.snapshot();
+        println!(
+            "[{}] versions {}-{} | {} txns | {:.1} tps | lag {:.2}s",
+            snapshot.processor_name,
+            first_version,
+            last_version,
+            last_version - first_version + 1,
+            snapshot.tps,
+            snapshot.lag_in_secs,
+        );
+    }
+
+    pub fn snapshot(&self) -> LiveProcessorStatusSnapshot {
+        LiveProcessorStatusSnapshot {
+            processor_name: self.processor_name,
+            tps: f64::from_bits(self.tps.load(Ordering::Relaxed)),
+            lag_in_secs: f64::from_bits(self.lag_in_secs.load(Ordering::Relaxed)),
+            last_processed_version: self.last_processed_version.load(Ordering::Relaxed),
+        }
+    }
+}
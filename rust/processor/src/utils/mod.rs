@@ -1,7 +1,36 @@
 // Copyright © Aptos Foundation
 // SPDX-License-Identifier: Apache-2.0
 
+pub mod authentication_key_check;
+pub mod block_height;
+pub mod byte_semaphore;
+pub mod channel_byte_limiter;
+pub mod clickhouse_sink;
 pub mod counters;
+pub mod coverage;
 pub mod database;
+pub mod db_circuit_breaker;
+pub mod error_budget;
+pub mod failed_events;
+pub mod feature_flags;
+pub mod heartbeat;
+pub mod in_flight_versions;
+pub mod live_status;
+pub mod processing_byte_budget;
+pub mod progress_lease;
+pub mod progress_report;
+pub mod rayon_pool;
+pub mod retention;
+pub mod retry;
+pub mod schema_drift;
+pub mod schema_version;
+pub mod shutdown;
 pub mod table_flags;
+pub mod timestamp_to_version;
+pub mod tip_lag;
+pub mod transaction_decoder;
+pub mod transaction_fields;
+pub mod transaction_tee;
 pub mod util;
+pub mod webhook;
+pub mod write_ahead_log;
@@ -0,0 +1,84 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::utils::{byte_semaphore::ByteSemaphore, counters::IN_FLIGHT_PROCESSING_BYTES};
+use tokio::sync::OwnedSemaphorePermit;
+
+/// Bounds the total bytes of transactions being processed at once across the processor tasks.
+/// Parsing a batch takes memory in proportion to its size, so capping the number of tasks alone
+/// lets a few huge batches use far more RAM than many small ones.
+pub struct ProcessingByteBudget {
+    processor_name: String,
+    bytes: ByteSemaphore,
+}
+
+/// Held while a batch is processed, returns its bytes to the budget when dropped.
+pub struct ProcessingBytesPermit {
+    processor_name: String,
+    size_in_bytes: u64,
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl ProcessingByteBudget {
+    pub fn new(processor_name: String, max_bytes: Option<u64>) -> Self {
+        Self {
+            processor_name,
+            bytes: ByteSemaphore::new(max_bytes),
+        }
+    }
+
+    /// Called by a processor task before processing a batch. Waits until there's room in the
+    /// budget if one is set. A batch larger than the budget goes through once nothing else is
+    /// being processed.
+    pub async fn acquire(&self, size_in_bytes: u64) -> ProcessingBytesPermit {
+        let permit = self.bytes.acquire(size_in_bytes).await;
+        IN_FLIGHT_PROCESSING_BYTES
+            .with_label_values(&[&self.processor_name])
+            .add(size_in_bytes as i64);
+        ProcessingBytesPermit {
+            processor_name: self.processor_name.clone(),
+            size_in_bytes,
+            _permit: permit,
+        }
+    }
+}
+
+impl Drop for ProcessingBytesPermit {
+    fn drop(&mut self) {
+        IN_FLIGHT_PROCESSING_BYTES
+            .with_label_values(&[&self.processor_name])
+            .sub(self.size_in_bytes as i64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_waits_for_bytes_to_be_released() {
+        let budget = ProcessingByteBudget::new("test".to_string(), Some(100));
+        let small = budget.acquire(40).await;
+        let _also_small = budget.acquire(40).await;
+        // Doesn't fit until the first batch is done
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), budget.acquire(40))
+                .await
+                .is_err()
+        );
+        drop(small);
+        let _third = budget.acquire(40).await;
+
+        // Larger than the whole budget, only goes through alone
+        let oversized = ProcessingByteBudget::new("test".to_string(), Some(100));
+        let huge = oversized.acquire(1_000).await;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), oversized.acquire(1))
+                .await
+                .is_err()
+        );
+        drop(huge);
+        let _small = oversized.acquire(1).await;
+    }
+}
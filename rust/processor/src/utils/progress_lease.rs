@@ -0,0 +1,353 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! A lease on a processor's progress, for running a primary and a hot standby. Only the process
+//! holding the lease processes transactions and commits progress. The standby waits for the lease
+//! and, once it has it, resumes from `processor_status` where the primary left off.
+
+use crate::utils::database::{processor_status_key, ArcDbPool, MyDbConnection};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use diesel::{
+    sql_query,
+    sql_types::{BigInt, Double, Text},
+    QueryableByName,
+};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProgressLeaseConfig {
+    /// Identifies this process as the lease holder, must differ between the primary and standby.
+    pub holder_id: String,
+    #[serde(default = "ProgressLeaseConfig::default_lease_duration_secs")]
+    pub lease_duration_secs: u64,
+    /// Must be well below `lease_duration_secs` so a slow renewal doesn't lose the lease.
+    #[serde(default = "ProgressLeaseConfig::default_renew_interval_secs")]
+    pub renew_interval_secs: u64,
+}
+
+impl ProgressLeaseConfig {
+    pub const fn default_lease_duration_secs() -> u64 {
+        30
+    }
+
+    pub const fn default_renew_interval_secs() -> u64 {
+        10
+    }
+}
+
+/// Where the lease is kept. It has to be shared by every process that can hold the lease.
+#[async_trait]
+pub trait LeaseStore: Send + Sync {
+    /// Takes the lease if it's free, expired or already held by `holder_id`. Returns the new
+    /// fencing token, which changes whenever the lease is taken, or None if someone else holds it.
+    async fn try_acquire(&self, holder_id: &str, duration: Duration) -> Result<Option<i64>>;
+
+    /// Extends the lease. False if it has expired or been taken since `fencing_token` was issued.
+    async fn renew(&self, holder_id: &str, fencing_token: i64, duration: Duration) -> Result<bool>;
+}
+
+#[derive(QueryableByName)]
+struct FencingToken {
+    #[diesel(sql_type = BigInt)]
+    fencing_token: i64,
+}
+
+/// Keeps the lease in the `processor_leases` table of the processor's own DB.
+pub struct PostgresLeaseStore {
+    pool: ArcDbPool,
+    processor: String,
+}
+
+impl PostgresLeaseStore {
+    pub fn new(pool: ArcDbPool, processor_name: &str) -> Self {
+        Self {
+            pool,
+            processor: processor_status_key(processor_name),
+        }
+    }
+}
+
+#[async_trait]
+impl LeaseStore for PostgresLeaseStore {
+    async fn try_acquire(&self, holder_id: &str, duration: Duration) -> Result<Option<i64>> {
+        let conn = &mut self.pool.get().await?;
+        let mut rows: Vec<FencingToken> = sql_query(
+            "INSERT INTO processor_leases (processor, holder_id, fencing_token, expires_at) \
+             VALUES ($1, $2, 1, NOW() + make_interval(secs => $3)) \
+             ON CONFLICT (processor) DO UPDATE SET \
+               holder_id = EXCLUDED.holder_id, \
+               fencing_token = processor_leases.fencing_token + 1, \
+               expires_at = EXCLUDED.expires_at, \
+               last_updated = NOW() \
+             WHERE processor_leases.expires_at < NOW() \
+               OR processor_leases.holder_id = EXCLUDED.holder_id \
+             RETURNING fencing_token",
+        )
+        .bind::<Text, _>(&self.processor)
+        .bind::<Text, _>(holder_id)
+        .bind::<Double, _>(duration.as_secs_f64())
+        .get_results(conn)
+        .await?;
+        Ok(rows.pop().map(|row| row.fencing_token))
+    }
+
+    async fn renew(&self, holder_id: &str, fencing_token: i64, duration: Duration) -> Result<bool> {
+        let conn = &mut self.pool.get().await?;
+        let updated = sql_query(
+            "UPDATE processor_leases \
+             SET expires_at = NOW() + make_interval(secs => $4), last_updated = NOW() \
+             WHERE processor = $1 AND holder_id = $2 AND fencing_token = $3 \
+               AND expires_at >= NOW()",
+        )
+        .bind::<Text, _>(&self.processor)
+        .bind::<Text, _>(holder_id)
+        .bind::<BigInt, _>(fencing_token)
+        .bind::<Double, _>(duration.as_secs_f64())
+        .execute(conn)
+        .await?;
+        Ok(updated == 1)
+    }
+}
+
+struct LeaseState {
+    /// None until acquired, and once lost
+    fencing_token: Option<i64>,
+    /// Measured from before the last successful renewal was sent, so it's never later than the
+    /// expiry in the store
+    valid_until: Instant,
+}
+
+/// Guards processing and progress commits with a lease, see the module docs.
+pub struct LeasedProgressStorage {
+    store: Box<dyn LeaseStore>,
+    holder_id: String,
+    lease_duration: Duration,
+    renew_interval: Duration,
+    state: Mutex<LeaseState>,
+}
+
+impl LeasedProgressStorage {
+    pub fn new(store: Box<dyn LeaseStore>, config: &ProgressLeaseConfig) -> Self {
+        Self {
+            store,
+            holder_id: config.holder_id.clone(),
+            lease_duration: Duration::from_secs(config.lease_duration_secs),
+            renew_interval: Duration::from_secs(config.renew_interval_secs.max(1)),
+            state: Mutex::new(LeaseState {
+                fencing_token: None,
+                valid_until: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until the lease is ours. A standby spends its time here until the primary stops
+    /// renewing.
+    pub async fn acquire(&self) -> Result<()> {
+        loop {
+            let requested_at = Instant::now();
+            if let Some(fencing_token) = self
+                .store
+                .try_acquire(&self.holder_id, self.lease_duration)
+                .await?
+            {
+                *self.state.lock().unwrap() = LeaseState {
+                    fencing_token: Some(fencing_token),
+                    valid_until: requested_at + self.lease_duration,
+                };
+                info!(
+                    holder_id = self.holder_id,
+                    fencing_token, "[Parser] Acquired the progress lease"
+                );
+                return Ok(());
+            }
+            info!(
+                holder_id = self.holder_id,
+                "[Parser] Progress lease is held by another process, waiting"
+            );
+            tokio::time::sleep(self.renew_interval).await;
+        }
+    }
+
+    /// Extends the lease. A failed request is retried on the next renewal as long as the lease
+    /// hasn't run out in the meantime.
+    pub async fn renew(&self) -> Result<()> {
+        let Some(fencing_token) = self.state.lock().unwrap().fencing_token else {
+            bail!("Progress lease isn't held");
+        };
+        let requested_at = Instant::now();
+        match self
+            .store
+            .renew(&self.holder_id, fencing_token, self.lease_duration)
+            .await
+        {
+            Ok(true) => {
+                self.state.lock().unwrap().valid_until = requested_at + self.lease_duration;
+                Ok(())
+            },
+            Ok(false) => {
+                self.state.lock().unwrap().fencing_token = None;
+                bail!("Progress lease was taken over by another process")
+            },
+            Err(e) => {
+                warn!(
+                    holder_id = self.holder_id,
+                    error = ?e,
+                    "[Parser] Failed to renew the progress lease"
+                );
+                self.ensure_held()
+            },
+        }
+    }
+
+    pub fn is_held(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.fencing_token.is_some() && Instant::now() < state.valid_until
+    }
+
+    /// Called before processing a batch and before committing progress.
+    pub fn ensure_held(&self) -> Result<()> {
+        if !self.is_held() {
+            bail!("Progress lease for {} was lost", self.holder_id);
+        }
+        Ok(())
+    }
+
+    /// Locks the lease in `processor_leases` until the end of the transaction `conn` is in,
+    /// provided it's still held under our fencing token. A process that took the lease over has
+    /// a new token, so this fails instead, and one taking it over now waits for the lock. Progress
+    /// is committed in the same transaction, so only the holder ever writes it.
+    pub async fn lock_for_commit(
+        &self,
+        conn: &mut MyDbConnection,
+        processor_name: &str,
+    ) -> Result<()> {
+        self.ensure_held()?;
+        let Some(fencing_token) = self.state.lock().unwrap().fencing_token else {
+            bail!("Progress lease isn't held");
+        };
+        let rows: Vec<FencingToken> = sql_query(
+            "SELECT fencing_token FROM processor_leases \
+             WHERE processor = $1 AND holder_id = $2 AND fencing_token = $3 \
+             FOR SHARE",
+        )
+        .bind::<Text, _>(processor_status_key(processor_name))
+        .bind::<Text, _>(&self.holder_id)
+        .bind::<BigInt, _>(fencing_token)
+        .get_results(conn)
+        .await?;
+        if rows.is_empty() {
+            self.state.lock().unwrap().fencing_token = None;
+            bail!(
+                "Progress lease for {} was taken over by another process",
+                self.holder_id
+            );
+        }
+        Ok(())
+    }
+
+    /// Renews the lease until it's lost, then returns the error.
+    pub async fn run_renewal(self: Arc<Self>) -> anyhow::Error {
+        loop {
+            tokio::time::sleep(self.renew_interval).await;
+            if let Err(e) = self.renew().await {
+                return e;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A store whose holder can be changed behind the processes' backs.
+    #[derive(Default)]
+    struct InMemoryLeaseStore {
+        holder: Arc<Mutex<Option<(String, i64)>>>,
+        unreachable: Arc<Mutex<bool>>,
+    }
+
+    #[async_trait]
+    impl LeaseStore for InMemoryLeaseStore {
+        async fn try_acquire(&self, holder_id: &str, _duration: Duration) -> Result<Option<i64>> {
+            let mut holder = self.holder.lock().unwrap();
+            match holder.as_ref() {
+                Some((current, _)) if current != holder_id => Ok(None),
+                _ => {
+                    let fencing_token = holder.as_ref().map_or(1, |(_, token)| token + 1);
+                    *holder = Some((holder_id.to_string(), fencing_token));
+                    Ok(Some(fencing_token))
+                },
+            }
+        }
+
+        async fn renew(
+            &self,
+            holder_id: &str,
+            fencing_token: i64,
+            _duration: Duration,
+        ) -> Result<bool> {
+            if *self.unreachable.lock().unwrap() {
+                bail!("store unreachable");
+            }
+            Ok(self.holder.lock().unwrap().as_ref()
+                == Some(&(holder_id.to_string(), fencing_token)))
+        }
+    }
+
+    fn lease(store: &InMemoryLeaseStore, holder_id: &str) -> LeasedProgressStorage {
+        let store = InMemoryLeaseStore {
+            holder: store.holder.clone(),
+            unreachable: store.unreachable.clone(),
+        };
+        let config = ProgressLeaseConfig {
+            holder_id: holder_id.to_string(),
+            lease_duration_secs: 1,
+            renew_interval_secs: 1,
+        };
+        LeasedProgressStorage::new(Box::new(store), &config)
+    }
+
+    #[tokio::test]
+    async fn test_lease_taken_over() {
+        let store = InMemoryLeaseStore::default();
+        let primary = lease(&store, "primary");
+        let standby = lease(&store, "standby");
+        primary.acquire().await.unwrap();
+        assert!(primary.is_held());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), standby.acquire())
+                .await
+                .is_err()
+        );
+
+        // The store handed the lease to the standby, e.g. after the primary stalled past expiry
+        *store.holder.lock().unwrap() = None;
+        standby.acquire().await.unwrap();
+        assert!(primary.renew().await.is_err());
+        assert!(!primary.is_held());
+        assert!(primary.ensure_held().is_err());
+        assert!(standby.renew().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_lease_expires_when_store_is_unreachable() {
+        let store = InMemoryLeaseStore::default();
+        let primary = lease(&store, "primary");
+        primary.acquire().await.unwrap();
+        *store.unreachable.lock().unwrap() = true;
+        // Still valid until the lease would have expired
+        assert!(primary.renew().await.is_ok());
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(primary.renew().await.is_err());
+        assert!(!primary.is_held());
+    }
+}
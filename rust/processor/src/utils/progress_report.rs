@@ -0,0 +1,185 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! A JSON report written once a run with an `ending_version` finishes, or any run shuts down on a
+//! signal, so whatever started a backfill can check what it covered without scraping logs or
+//! metrics.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path, sync::Mutex};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ProgressReport {
+    pub starting_version: u64,
+    pub ending_version: Option<u64>,
+    pub wall_time_secs: f64,
+    /// One per processor, more than one when multiplexing
+    pub processors: Vec<ProcessorProgressReport>,
+    /// Rows inserted or updated by this run, by table. Rows left alone because they already
+    /// existed aren't counted.
+    pub rows_written: BTreeMap<String, u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ProcessorProgressReport {
+    pub processor_name: String,
+    pub starting_version: u64,
+    pub last_processed_version: Option<u64>,
+    pub num_versions_processed: u64,
+    /// Versions from `starting_version` up to the ending version that no batch covered. Empty
+    /// when the run processed everything it was asked to.
+    pub skipped_ranges: Vec<VersionRange>,
+}
+
+/// Inclusive range of versions.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct VersionRange {
+    pub start_version: u64,
+    pub end_version: u64,
+}
+
+impl ProgressReport {
+    /// Writes the report as pretty printed JSON, replacing the file if there's one.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Failed to write the progress report to {:?}", path))
+    }
+}
+
+/// Version ranges of the batches a processor has processed. Batches finish out of order across
+/// tasks, so adjacent ranges are merged as they come in to keep this small.
+#[derive(Default)]
+pub struct ProcessedRanges {
+    // Start version to end version, inclusive, with no two ranges overlapping or adjacent
+    ranges: Mutex<BTreeMap<u64, u64>>,
+}
+
+impl ProcessedRanges {
+    pub fn record(&self, start_version: u64, end_version: u64) {
+        let mut ranges = self.ranges.lock().unwrap();
+        let mut start_version = start_version;
+        let mut end_version = end_version;
+        // Merge with the range before, if it reaches this one
+        if let Some((&prev_start, &prev_end)) = ranges.range(..=start_version).next_back() {
+            if prev_end.saturating_add(1) >= start_version {
+                start_version = prev_start;
+                end_version = end_version.max(prev_end);
+                ranges.remove(&prev_start);
+            }
+        }
+        // And with the ranges after that this one reaches
+        while let Some((&next_start, &next_end)) = ranges.range(start_version..).next() {
+            if next_start > end_version.saturating_add(1) {
+                break;
+            }
+            end_version = end_version.max(next_end);
+            ranges.remove(&next_start);
+        }
+        ranges.insert(start_version, end_version);
+    }
+
+    /// The report of a processor that started at `starting_version` and was to stop at
+    /// `ending_version`, or at the last version processed if there's no end.
+    pub fn report(
+        &self,
+        processor_name: &str,
+        starting_version: u64,
+        ending_version: Option<u64>,
+    ) -> ProcessorProgressReport {
+        let ranges = self.ranges.lock().unwrap();
+        let last_processed_version = ranges.values().next_back().copied();
+        let num_versions_processed = ranges.iter().map(|(start, end)| end - start + 1).sum();
+        let mut skipped_ranges = vec![];
+        let mut next_version = starting_version;
+        for (&start, &end) in ranges.iter() {
+            if start > next_version {
+                skipped_ranges.push(VersionRange {
+                    start_version: next_version,
+                    end_version: start - 1,
+                });
+            }
+            next_version = next_version.max(end + 1);
+        }
+        if let Some(ending_version) = ending_version.or(last_processed_version) {
+            if ending_version >= next_version {
+                skipped_ranges.push(VersionRange {
+                    start_version: next_version,
+                    end_version: ending_version,
+                });
+            }
+        }
+        ProcessorProgressReport {
+            processor_name: processor_name.to_string(),
+            starting_version,
+            last_processed_version,
+            num_versions_processed,
+            skipped_ranges,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start_version: u64, end_version: u64) -> VersionRange {
+        VersionRange {
+            start_version,
+            end_version,
+        }
+    }
+
+    #[test]
+    fn test_out_of_order_batches_are_merged() {
+        let processed_ranges = ProcessedRanges::default();
+        processed_ranges.record(200, 299);
+        processed_ranges.record(0, 99);
+        processed_ranges.record(100, 199);
+        let report = processed_ranges.report("default_processor", 0, Some(299));
+        assert_eq!(report.last_processed_version, Some(299));
+        assert_eq!(report.num_versions_processed, 300);
+        assert!(report.skipped_ranges.is_empty());
+        assert_eq!(processed_ranges.ranges.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_skipped_ranges() {
+        let processed_ranges = ProcessedRanges::default();
+        processed_ranges.record(110, 149);
+        processed_ranges.record(200, 249);
+        let report = processed_ranges.report("default_processor", 100, Some(299));
+        assert_eq!(report.num_versions_processed, 90);
+        assert_eq!(
+            report.skipped_ranges,
+            [range(100, 109), range(150, 199), range(250, 299)]
+        );
+
+        // Nothing processed at all
+        let report = ProcessedRanges::default().report("default_processor", 100, Some(199));
+        assert_eq!(report.last_processed_version, None);
+        assert_eq!(report.skipped_ranges, [range(100, 199)]);
+    }
+
+    #[test]
+    fn test_write() {
+        let processed_ranges = ProcessedRanges::default();
+        processed_ranges.record(0, 99);
+        let report = ProgressReport {
+            starting_version: 0,
+            ending_version: Some(199),
+            wall_time_secs: 1.5,
+            processors: vec![processed_ranges.report("events_processor", 0, Some(199))],
+            rows_written: BTreeMap::from([("events".to_string(), 42)]),
+        };
+        let path =
+            std::env::temp_dir().join(format!("progress_report_{}.json", std::process::id()));
+        report.write(&path).unwrap();
+        let written: ProgressReport =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written, report);
+        assert_eq!(written.processors[0].skipped_ranges, [range(100, 199)]);
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Transactions are parsed in parallel with rayon, by default on its global pool of one thread
+//! per core. On a host shared by several processors that lets one processor's parsing starve the
+//! others, so with `rayon_num_threads` each processor parses on a pool of its own with that many
+//! threads instead.
+
+use anyhow::{bail, Context, Result};
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use std::sync::Arc;
+
+/// Pool a processor parses on. Cloning it shares the pool, e.g. between the processor's tasks.
+#[derive(Clone, Debug, Default)]
+pub struct RayonPool {
+    // None parses on rayon's global pool
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl RayonPool {
+    /// Builds a pool of `num_threads` threads, or uses the global pool if it's not set.
+    pub fn new(num_threads: Option<usize>) -> Result<Self> {
+        let Some(num_threads) = num_threads else {
+            return Ok(Self::default());
+        };
+        if num_threads == 0 {
+            bail!("rayon_num_threads must be greater than 0");
+        }
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|index| format!("processor-rayon-{}", index))
+            .build()
+            .context("Failed to build the rayon thread pool")?;
+        Ok(Self {
+            pool: Some(Arc::new(pool)),
+        })
+    }
+
+    /// Runs `op` on this pool, so that the parallel iterators in it are too.
+    pub fn install<OP, R>(&self, op: OP) -> R
+    where
+        OP: FnOnce() -> R + Send,
+        R: Send,
+    {
+        match &self.pool {
+            Some(pool) => pool.install(op),
+            None => op(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::prelude::*;
+    use std::collections::HashSet;
+
+    /// Threads `pool` parses on, with enough work to spread over all of them.
+    fn parsing_threads(pool: &RayonPool) -> HashSet<std::thread::ThreadId> {
+        pool.install(|| {
+            (0..10_000)
+                .into_par_iter()
+                .map(|_| std::thread::current().id())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_each_processor_has_its_own_pool() {
+        assert!(RayonPool::new(Some(0)).is_err());
+        let small = RayonPool::new(Some(2)).unwrap();
+        let large = RayonPool::new(Some(3)).unwrap();
+        assert_eq!(small.install(rayon::current_num_threads), 2);
+        assert_eq!(large.install(rayon::current_num_threads), 3);
+
+        let (small_threads, large_threads) = (parsing_threads(&small), parsing_threads(&large));
+        assert!(small_threads.len() <= 2);
+        assert!(large_threads.len() <= 3);
+        assert!(small_threads.is_disjoint(&large_threads));
+        assert!(small.install(|| std::thread::current()
+            .name()
+            .is_some_and(|name| name.starts_with("processor-rayon-"))));
+
+        // Without a size, parsing stays on the global pool
+        assert_eq!(
+            RayonPool::new(None)
+                .unwrap()
+                .install(rayon::current_num_threads),
+            rayon::current_num_threads()
+        );
+    }
+}
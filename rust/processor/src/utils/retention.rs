@@ -0,0 +1,218 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Periodically deletes the rows of history tables that fall out of their retention, so tables
+//! like `events` don't grow without bound. Rows are deleted by version, oldest first, a range of
+//! `batch_versions` versions per statement so no statement holds its locks for long.
+
+use crate::{
+    db::postgres::models::processor_status::ProcessorStatusQuery,
+    utils::{
+        counters::ROWS_PRUNED_COUNT,
+        database::{table_name_in_db, ArcDbPool},
+    },
+};
+use ahash::AHashMap;
+use anyhow::{bail, Context, Result};
+use diesel::{
+    sql_query,
+    sql_types::{BigInt, Nullable, Timestamp},
+    QueryableByName,
+};
+use diesel_async::RunQueryDsl;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Tables that can be pruned, with their version column. Only append-only history tables, whose
+/// rows nothing else is derived from after they're written.
+pub const PRUNABLE_TABLES: [(&str, &str); 6] = [
+    ("events", "transaction_version"),
+    ("move_resources", "transaction_version"),
+    ("signatures", "transaction_version"),
+    ("table_items", "transaction_version"),
+    ("user_transactions", "version"),
+    ("write_set_changes", "transaction_version"),
+];
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct RetentionConfig {
+    /// Retention of each table to prune, e.g. `events: { days: 90 }`
+    pub tables: AHashMap<String, Retention>,
+    #[serde(default = "RetentionConfig::default_interval_secs")]
+    pub interval_secs: u64,
+    /// Versions deleted per statement
+    #[serde(default = "RetentionConfig::default_batch_versions")]
+    pub batch_versions: u64,
+}
+
+impl RetentionConfig {
+    pub const fn default_interval_secs() -> u64 {
+        3600
+    }
+
+    pub const fn default_batch_versions() -> u64 {
+        10_000
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        for table in self.tables.keys() {
+            if version_column(table).is_none() {
+                bail!(
+                    "retention isn't supported for {}, only for {:?}",
+                    table,
+                    PRUNABLE_TABLES.map(|(table, _)| table)
+                );
+            }
+        }
+        if self.batch_versions == 0 {
+            bail!("retention batch_versions must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Retention {
+    /// Keep transactions from the last `days` days, by the timestamps of the blocks in
+    /// `block_metadata_transactions`
+    Days(u64),
+    /// Keep the last `versions` versions the processor has processed
+    Versions(u64),
+}
+
+fn version_column(table: &str) -> Option<&'static str> {
+    PRUNABLE_TABLES
+        .iter()
+        .find(|(prunable_table, _)| *prunable_table == table)
+        .map(|(_, column)| *column)
+}
+
+#[derive(QueryableByName)]
+struct MinVersion {
+    #[diesel(sql_type = Nullable<BigInt>)]
+    version: Option<i64>,
+}
+
+/// First version that's kept, or None if nothing can be pruned yet.
+async fn retention_boundary(
+    pool: ArcDbPool,
+    processor_name: &str,
+    retention: Retention,
+) -> Result<Option<i64>> {
+    let mut conn = pool.get().await?;
+    match retention {
+        Retention::Versions(versions) => {
+            let last_success_version =
+                ProcessorStatusQuery::get_by_processor(processor_name, &mut conn)
+                    .await?
+                    .map(|status| status.last_success_version);
+            Ok(last_success_version
+                .map(|version| version + 1 - versions as i64)
+                .filter(|boundary| *boundary > 0))
+        },
+        Retention::Days(days) => {
+            let cutoff = chrono::Utc::now().naive_utc() - chrono::Duration::days(days as i64);
+            // Every transaction before the first block at or after the cutoff is in an older block
+            let first_kept = sql_query(
+                "SELECT MIN(version) AS version FROM block_metadata_transactions \
+                 WHERE timestamp >= $1",
+            )
+            .bind::<Timestamp, _>(cutoff)
+            .get_result::<MinVersion>(&mut conn)
+            .await?;
+            if first_kept.version.is_none() {
+                warn!(
+                    processor_name,
+                    cutoff = cutoff.to_string(),
+                    "[Parser] No block in block_metadata_transactions since the retention cutoff, \
+                     not pruning"
+                );
+            }
+            Ok(first_kept.version)
+        },
+    }
+}
+
+/// Deletes the rows of `table` before `boundary`, in batches of `batch_versions` versions.
+/// Returns the number of rows deleted.
+pub async fn prune_table(
+    pool: ArcDbPool,
+    processor_name: &str,
+    table: &str,
+    boundary: i64,
+    batch_versions: u64,
+) -> Result<u64> {
+    let column = version_column(table).context("Table can't be pruned")?;
+    let table_name = table_name_in_db(table);
+    let mut conn = pool.get().await?;
+    let mut num_pruned = 0;
+    loop {
+        let oldest = sql_query(format!(
+            "SELECT MIN({}) AS version FROM \"{}\"",
+            column, table_name
+        ))
+        .get_result::<MinVersion>(&mut conn)
+        .await?
+        .version;
+        let Some(oldest) = oldest.filter(|oldest| *oldest < boundary) else {
+            return Ok(num_pruned);
+        };
+        let batch_end = boundary.min(oldest.saturating_add(batch_versions as i64));
+        let num_deleted = sql_query(format!(
+            "DELETE FROM \"{}\" WHERE {} >= $1 AND {} < $2",
+            table_name, column, column
+        ))
+        .bind::<BigInt, _>(oldest)
+        .bind::<BigInt, _>(batch_end)
+        .execute(&mut conn)
+        .await
+        .with_context(|| format!("Failed to prune {} before version {}", table, batch_end))?
+            as u64;
+        ROWS_PRUNED_COUNT
+            .with_label_values(&[processor_name, table])
+            .inc_by(num_deleted);
+        num_pruned += num_deleted;
+    }
+}
+
+/// Prunes every configured table once.
+pub async fn prune(pool: ArcDbPool, processor_name: &str, config: &RetentionConfig) -> Result<()> {
+    for (table, retention) in &config.tables {
+        let Some(boundary) = retention_boundary(pool.clone(), processor_name, *retention).await?
+        else {
+            continue;
+        };
+        let num_pruned = prune_table(
+            pool.clone(),
+            processor_name,
+            table,
+            boundary,
+            config.batch_versions,
+        )
+        .await?;
+        info!(
+            processor_name,
+            table, boundary, num_pruned, "[Parser] Pruned rows out of retention"
+        );
+    }
+    Ok(())
+}
+
+/// Prunes every `interval_secs`, starting right away. Failures are logged and retried on the
+/// next round.
+pub async fn run_pruning(processor_name: &'static str, pool: ArcDbPool, config: RetentionConfig) {
+    let mut ticker = tokio::time::interval(Duration::from_secs(config.interval_secs.max(1)));
+    loop {
+        ticker.tick().await;
+        if let Err(e) = prune(pool.clone(), processor_name, &config).await {
+            error!(
+                processor_name,
+                error = ?e,
+                "[Parser] Failed to prune tables, retrying on the next round"
+            );
+        }
+    }
+}
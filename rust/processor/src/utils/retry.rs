@@ -0,0 +1,131 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Delays between the attempts of a retry loop. With plain exponential backoff, processors that
+//! fail at the same moment, e.g. because the dependency went down, also retry at the same moments
+//! and hit the dependency all at once. Jitter spreads their retries out.
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Jitter {
+    /// Exactly the exponential delay
+    #[default]
+    None,
+    /// Anywhere between zero and the exponential delay
+    Full,
+    /// Between the initial delay and three times the previous delay, so delays grow about as
+    /// fast as without jitter but don't stay correlated between processors
+    Decorrelated,
+}
+
+/// Exponential backoff starting at `initial_delay_ms` and doubling up to `max_delay_ms`, with the
+/// given jitter on top. One per retry loop.
+pub struct Backoff {
+    initial_delay_ms: u64,
+    max_delay_ms: u64,
+    jitter: Jitter,
+    /// Exponential delay of the next retry, or the previous delay with decorrelated jitter
+    current_delay_ms: u64,
+}
+
+impl Backoff {
+    pub fn new(initial_delay_ms: u64, jitter: Jitter) -> Self {
+        Self {
+            initial_delay_ms,
+            max_delay_ms: u64::MAX,
+            jitter,
+            current_delay_ms: initial_delay_ms,
+        }
+    }
+
+    pub fn with_max_delay_ms(mut self, max_delay_ms: u64) -> Self {
+        self.max_delay_ms = max_delay_ms.max(self.initial_delay_ms);
+        self
+    }
+
+    /// How long to wait before the next retry.
+    pub fn next_delay(&mut self) -> Duration {
+        self.next_delay_with(&mut rand::thread_rng())
+    }
+
+    fn next_delay_with(&mut self, rng: &mut impl Rng) -> Duration {
+        let delay_ms = match self.jitter {
+            Jitter::None => self.current_delay_ms,
+            Jitter::Full => rng.gen_range(0..=self.current_delay_ms),
+            Jitter::Decorrelated => {
+                let upper_ms = self
+                    .current_delay_ms
+                    .saturating_mul(3)
+                    .min(self.max_delay_ms);
+                let delay_ms = rng.gen_range(self.initial_delay_ms..=upper_ms);
+                self.current_delay_ms = delay_ms;
+                return Duration::from_millis(delay_ms);
+            },
+        };
+        self.current_delay_ms = self
+            .current_delay_ms
+            .saturating_mul(2)
+            .min(self.max_delay_ms);
+        Duration::from_millis(delay_ms)
+    }
+
+    pub async fn sleep(&mut self) {
+        tokio::time::sleep(self.next_delay()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn delays_ms(backoff: &mut Backoff, rng: &mut StdRng, count: usize) -> Vec<u64> {
+        (0..count)
+            .map(|_| backoff.next_delay_with(rng).as_millis() as u64)
+            .collect()
+    }
+
+    #[test]
+    fn test_no_jitter_doubles_up_to_max() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut backoff = Backoff::new(100, Jitter::None).with_max_delay_ms(500);
+        let delays = delays_ms(&mut backoff, &mut rng, 5);
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+    }
+
+    #[test]
+    fn test_full_jitter_is_spread_below_the_exponential_delay() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let samples: Vec<u64> = (0..10_000)
+            .map(|_| {
+                let mut backoff = Backoff::new(1_000, Jitter::Full);
+                delays_ms(&mut backoff, &mut rng, 3)[2]
+            })
+            .collect();
+        // The third retry is drawn uniformly from 0..=4000
+        assert!(samples.iter().all(|&delay_ms| delay_ms <= 4_000));
+        let mean = samples.iter().sum::<u64>() as f64 / samples.len() as f64;
+        assert!((mean - 2_000.0).abs() < 100.0, "mean {}", mean);
+        let below_1000 = samples.iter().filter(|&&delay_ms| delay_ms < 1_000).count();
+        assert!((2_000..3_000).contains(&below_1000), "{}", below_1000);
+    }
+
+    #[test]
+    fn test_decorrelated_jitter_stays_between_initial_and_max() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let mut backoff = Backoff::new(100, Jitter::Decorrelated).with_max_delay_ms(10_000);
+        let samples = delays_ms(&mut backoff, &mut rng, 10_000);
+        assert!(samples
+            .iter()
+            .all(|&delay_ms| (100..=10_000).contains(&delay_ms)));
+        // Each delay is at most three times the previous one
+        assert!(samples.windows(2).all(|pair| pair[1] <= pair[0] * 3));
+        // Retries of different processors don't line up
+        let mut other = Backoff::new(100, Jitter::Decorrelated).with_max_delay_ms(10_000);
+        assert_ne!(samples[..5], delays_ms(&mut other, &mut rng, 5)[..]);
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Startup check that a table in the DB has the columns its diesel schema says it has. When a
+//! migration didn't run, or the DB was changed by hand, inserts would otherwise fail on the first
+//! batch that writes the table, with an error that doesn't say which columns are off.
+
+use crate::utils::database::{prefixed_table_name, ArcDbPool, Backend, PREFIXABLE_TABLES};
+use anyhow::{Context, Result};
+use diesel::{
+    debug_query, query_builder::QueryFragment, sql_query, sql_types::Text, QueryableByName, Table,
+};
+use diesel_async::RunQueryDsl;
+use serde::Serialize;
+use std::{collections::BTreeSet, fmt};
+
+/// Columns of a table that are only in the DB or only in the diesel schema.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+pub struct SchemaDrift {
+    pub table_name: String,
+    /// In the schema, so inserted, but missing in the DB
+    pub missing_in_db: Vec<String>,
+    /// In the DB but missing in the schema
+    pub missing_in_model: Vec<String>,
+}
+
+impl SchemaDrift {
+    /// The drift between the columns a model expects and the ones in the DB, None if they match.
+    pub fn compare(
+        table_name: &str,
+        model_columns: &[String],
+        db_columns: &[String],
+    ) -> Option<Self> {
+        let model_columns: BTreeSet<&String> = model_columns.iter().collect();
+        let db_columns: BTreeSet<&String> = db_columns.iter().collect();
+        let drift = Self {
+            table_name: table_name.to_string(),
+            missing_in_db: model_columns
+                .difference(&db_columns)
+                .map(|column| column.to_string())
+                .collect(),
+            missing_in_model: db_columns
+                .difference(&model_columns)
+                .map(|column| column.to_string())
+                .collect(),
+        };
+        (!drift.missing_in_db.is_empty() || !drift.missing_in_model.is_empty()).then_some(drift)
+    }
+}
+
+impl fmt::Display for SchemaDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Schema drift in table {}:", self.table_name)?;
+        for column in &self.missing_in_db {
+            write!(f, "\n  - {} (in the model, missing in the DB)", column)?;
+        }
+        for column in &self.missing_in_model {
+            write!(f, "\n  + {} (in the DB, missing in the model)", column)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SchemaDrift {}
+
+/// Name and columns of `T` as the diesel schema declares them, read off the SQL diesel generates
+/// for them, e.g. `"events"."sequence_number", "events"."type", ...`.
+pub fn model_columns<T>() -> (String, Vec<String>)
+where
+    T: Table,
+    T::AllColumns: QueryFragment<Backend>,
+{
+    let sql = debug_query::<Backend, _>(&T::all_columns()).to_string();
+    let mut table_name = String::new();
+    let columns = sql
+        .split(", ")
+        .filter_map(|column| {
+            let (table, column) = column.split_once("\".\"")?;
+            table_name = table.trim_matches('"').to_string();
+            Some(column.trim_matches('"').to_string())
+        })
+        .collect();
+    (table_name, columns)
+}
+
+#[derive(QueryableByName)]
+struct ColumnName {
+    #[diesel(sql_type = Text)]
+    column_name: String,
+}
+
+/// Columns of `table_name` in the current schema of the DB.
+pub async fn db_columns(db_pool: ArcDbPool, table_name: &str) -> Result<Vec<String>> {
+    let mut conn = db_pool.get().await?;
+    let columns: Vec<ColumnName> = sql_query(
+        "SELECT column_name::TEXT AS column_name FROM information_schema.columns \
+         WHERE table_schema = current_schema() AND table_name = $1",
+    )
+    .bind::<Text, _>(table_name)
+    .load(&mut conn)
+    .await?;
+    Ok(columns
+        .into_iter()
+        .map(|column| column.column_name)
+        .collect())
+}
+
+/// Fails with the `SchemaDrift` of `T` if the DB's columns for it differ from its diesel schema.
+/// For one of `PREFIXABLE_TABLES`, the prefixed table is checked if `table_prefix` is set.
+pub async fn check_schema_drift<T>(db_pool: ArcDbPool) -> Result<()>
+where
+    T: Table,
+    T::AllColumns: QueryFragment<Backend>,
+{
+    let (table_name, model_columns) = model_columns::<T>();
+    let table_name = if PREFIXABLE_TABLES.contains(&table_name.as_str()) {
+        prefixed_table_name(&table_name)
+    } else {
+        table_name
+    };
+    let db_columns = db_columns(db_pool, &table_name)
+        .await
+        .with_context(|| format!("Failed to read the columns of {}", table_name))?;
+    match SchemaDrift::compare(&table_name, &model_columns, &db_columns) {
+        Some(drift) => Err(drift.into()),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::events;
+
+    #[test]
+    fn test_model_columns() {
+        let (table_name, columns) = model_columns::<events::table>();
+        assert_eq!(table_name, "events");
+        assert_eq!(columns.len(), 10);
+        assert!(columns.contains(&"type".to_string()));
+        assert!(columns.contains(&"indexed_type".to_string()));
+    }
+
+    #[test]
+    fn test_compare() {
+        let columns =
+            |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+        let model = columns(&["data", "indexed_type", "type"]);
+        assert_eq!(SchemaDrift::compare("events", &model, &model), None);
+
+        let drift =
+            SchemaDrift::compare("events", &model, &columns(&["type", "data", "legacy"])).unwrap();
+        assert_eq!(drift.missing_in_db, ["indexed_type"]);
+        assert_eq!(drift.missing_in_model, ["legacy"]);
+        assert_eq!(
+            drift.to_string(),
+            "Schema drift in table events:\n  \
+             - indexed_type (in the model, missing in the DB)\n  \
+             + legacy (in the DB, missing in the model)"
+        );
+    }
+}
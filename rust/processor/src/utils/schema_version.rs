@@ -0,0 +1,171 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Startup guard against running a processor binary on tables its models don't match. Each
+//! processor declares the schema version of the tables it writes, see
+//! `ProcessorConfig::schema_version`, and the version it last started with is recorded in
+//! `processor_schema_versions`. Each version comes with the migration that introduced it, so a
+//! newer binary only starts once that migration is among the ones applied to the DB, and an older
+//! one, i.e. a downgrade, doesn't start at all, instead of writing wrong data or failing on its
+//! inserts.
+
+use crate::{
+    db::postgres::models::processor_schema_version::ProcessorSchemaVersion,
+    utils::database::ArcDbPool,
+};
+use anyhow::{bail, Result};
+use tracing::info;
+
+/// Version of the tables a processor writes, with the migration that brought them to it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SchemaVersion {
+    pub version: i64,
+    pub migration: &'static str,
+}
+
+/// Every processor's tables as of when schema versions were introduced
+pub const BASE_SCHEMA_VERSION: SchemaVersion = SchemaVersion {
+    version: 1,
+    migration: "2025-07-08-000000_processor_schema_versions",
+};
+
+/// `token_v2_processor` writing `collection_stat_changes` and `collection_stats`
+pub const TOKEN_V2_COLLECTION_STATS_SCHEMA_VERSION: SchemaVersion = SchemaVersion {
+    version: 2,
+    migration: "2025-07-01-000000_collection_stats",
+};
+
+/// What starting a processor means for its recorded schema version.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SchemaVersionCheck {
+    /// Nothing recorded yet, so the code's version is recorded
+    FirstStart,
+    Matching,
+    /// The code is newer and its migration has run, so the code's version is recorded
+    Upgrade {
+        from: i64,
+    },
+}
+
+impl SchemaVersionCheck {
+    /// Checks `code_version` against the `db_version` recorded and the names of the migrations
+    /// applied to the DB, failing if the processor mustn't start.
+    pub fn check(
+        processor_name: &str,
+        code_version: SchemaVersion,
+        db_version: Option<i64>,
+        applied_migrations: &[String],
+    ) -> Result<Self> {
+        let migration_applied = applied_migrations
+            .iter()
+            .any(|migration| migration == code_version.migration);
+        match db_version {
+            Some(db_version) if db_version > code_version.version => bail!(
+                "{} has schema version {}, older than the {} in the DB. Is this a downgrade?",
+                processor_name,
+                code_version.version,
+                db_version
+            ),
+            Some(db_version) if db_version == code_version.version => Ok(Self::Matching),
+            _ if !migration_applied => bail!(
+                "{} has schema version {}, but its migration {} hasn't been applied",
+                processor_name,
+                code_version.version,
+                code_version.migration
+            ),
+            None => Ok(Self::FirstStart),
+            Some(db_version) => Ok(Self::Upgrade { from: db_version }),
+        }
+    }
+}
+
+/// Checks the schema version of `processor_name` against the one recorded, and records it if
+/// the processor can start with it.
+pub async fn check_schema_version(
+    pool: ArcDbPool,
+    processor_name: &str,
+    code_version: SchemaVersion,
+    applied_migrations: &[String],
+) -> Result<SchemaVersionCheck> {
+    let mut conn = pool.get().await?;
+    let db_version = ProcessorSchemaVersion::get_by_processor(processor_name, &mut conn).await?;
+    let check =
+        SchemaVersionCheck::check(processor_name, code_version, db_version, applied_migrations)?;
+    if check != SchemaVersionCheck::Matching {
+        ProcessorSchemaVersion::upsert(processor_name, code_version.version, &mut conn).await?;
+        info!(
+            processor_name,
+            schema_version = code_version.version,
+            ?check,
+            "[Parser] Recorded the processor's schema version"
+        );
+    }
+    Ok(check)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::database::MIGRATIONS;
+    use diesel::{migration::MigrationSource, pg::Pg};
+
+    const V1: SchemaVersion = SchemaVersion {
+        version: 1,
+        migration: "v1",
+    };
+    const V2: SchemaVersion = SchemaVersion {
+        version: 2,
+        migration: "v2",
+    };
+    const V3: SchemaVersion = SchemaVersion {
+        version: 3,
+        migration: "v3",
+    };
+
+    fn applied(migrations: &[&str]) -> Vec<String> {
+        migrations.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_matching() {
+        assert_eq!(
+            SchemaVersionCheck::check("test", V2, Some(2), &applied(&["v1", "v2"])).unwrap(),
+            SchemaVersionCheck::Matching
+        );
+        assert_eq!(
+            SchemaVersionCheck::check("test", V1, None, &applied(&["v1"])).unwrap(),
+            SchemaVersionCheck::FirstStart
+        );
+        assert!(SchemaVersionCheck::check("test", V1, None, &[]).is_err());
+    }
+
+    #[test]
+    fn test_upgrade() {
+        assert_eq!(
+            SchemaVersionCheck::check("test", V3, Some(2), &applied(&["v2", "v3"])).unwrap(),
+            SchemaVersionCheck::Upgrade { from: 2 }
+        );
+        assert!(SchemaVersionCheck::check("test", V3, Some(2), &applied(&["v2"])).is_err());
+    }
+
+    #[test]
+    fn test_downgrade() {
+        assert!(SchemaVersionCheck::check("test", V2, Some(3), &applied(&["v2", "v3"])).is_err());
+    }
+
+    #[test]
+    fn test_schema_version_migrations_exist() {
+        let migrations = MigrationSource::<Pg>::migrations(&MIGRATIONS)
+            .unwrap()
+            .iter()
+            .map(|migration| migration.name().to_string())
+            .collect::<Vec<_>>();
+        let schema_versions = [
+            BASE_SCHEMA_VERSION,
+            TOKEN_V2_COLLECTION_STATS_SCHEMA_VERSION,
+        ];
+        for schema_version in schema_versions {
+            assert!(migrations.contains(&schema_version.migration.to_string()));
+        }
+    }
+}
@@ -0,0 +1,162 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Graceful shutdown on SIGINT or SIGTERM, in two phases. First the Parquet handlers upload what
+//! they have buffered. Once they're done, or out of time, the gap detectors take the results
+//! still queued, commit the progress they cover and finish, and the worker returns. Progress only
+//! ever covers what was uploaded, so data lost to a timed out flush is processed again on restart.
+
+use once_cell::sync::Lazy;
+use std::{sync::Mutex, time::Duration};
+use tokio::{
+    sync::{oneshot, watch},
+    task::JoinHandle,
+};
+use tracing::{error, info, warn};
+
+/// Receiving end of a shutdown phase. Cheap to clone, one per task.
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal {
+    receiver: watch::Receiver<bool>,
+}
+
+impl ShutdownSignal {
+    pub fn is_triggered(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once the phase is triggered, right away if it already was.
+    pub async fn triggered(&mut self) {
+        // Only fails if the trigger is dropped, and the process-wide ones never are
+        let _ = self.receiver.wait_for(|triggered| *triggered).await;
+    }
+}
+
+#[derive(Debug)]
+pub struct ShutdownTrigger {
+    sender: watch::Sender<bool>,
+}
+
+impl ShutdownTrigger {
+    pub fn new() -> Self {
+        Self {
+            sender: watch::Sender::new(false),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn signal(&self) -> ShutdownSignal {
+        ShutdownSignal {
+            receiver: self.sender.subscribe(),
+        }
+    }
+}
+
+impl Default for ShutdownTrigger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stop taking new work and flush buffers
+static SHUTDOWN: Lazy<ShutdownTrigger> = Lazy::new(ShutdownTrigger::new);
+/// Buffers are flushed, so commit progress and finish
+static FLUSHED: Lazy<ShutdownTrigger> = Lazy::new(ShutdownTrigger::new);
+static FLUSH_TASKS: Lazy<Mutex<Vec<JoinHandle<()>>>> = Lazy::new(Mutex::default);
+static PROGRESS_TASKS: Lazy<Mutex<Vec<JoinHandle<()>>>> = Lazy::new(Mutex::default);
+/// A registered task died. Nothing awaits them before shutdown, so this is how the worker finds
+/// out, instead of running on without them when panics don't exit the process
+static TASK_FAILED: Lazy<ShutdownTrigger> = Lazy::new(ShutdownTrigger::new);
+
+const PROGRESS_COMMIT_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub fn shutdown_signal() -> ShutdownSignal {
+    SHUTDOWN.signal()
+}
+
+pub fn flushed_signal() -> ShutdownSignal {
+    FLUSHED.signal()
+}
+
+/// Fires once a registered task has panicked.
+pub fn task_failed_signal() -> ShutdownSignal {
+    TASK_FAILED.signal()
+}
+
+/// A task that flushes its buffers once `shutdown_signal` fires and then finishes.
+pub fn register_flush_task(task: JoinHandle<()>) {
+    FLUSH_TASKS.lock().unwrap().push(watch_task(task));
+}
+
+/// A task that commits progress once `flushed_signal` fires and then finishes.
+pub fn register_progress_task(task: JoinHandle<()>) {
+    PROGRESS_TASKS.lock().unwrap().push(watch_task(task));
+}
+
+/// Same as `register_progress_task`, for a task that is also awaited elsewhere. The returned
+/// handle finishes along with the task, and fails if it panicked.
+pub fn register_awaited_progress_task(task: JoinHandle<()>) -> JoinHandle<()> {
+    let (finished_sender, finished_receiver) = oneshot::channel::<()>();
+    register_progress_task(tokio::spawn(async move {
+        let _ = finished_receiver.await;
+    }));
+    tokio::spawn(async move {
+        let result = task.await;
+        let _ = finished_sender.send(());
+        if let Err(e) = result {
+            if let Ok(panic) = e.try_into_panic() {
+                std::panic::resume_unwind(panic);
+            }
+        }
+    })
+}
+
+fn watch_task(task: JoinHandle<()>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        if let Err(e) = task.await {
+            error!(error = ?e, "[Parser] Background task died");
+            TASK_FAILED.trigger();
+        }
+    })
+}
+
+/// Triggers the shutdown on SIGINT or SIGTERM.
+pub async fn listen_for_signals() {
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .expect("Failed to listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => info!("[Parser] Received SIGINT, shutting down"),
+        _ = sigterm.recv() => info!("[Parser] Received SIGTERM, shutting down"),
+    }
+    SHUTDOWN.trigger();
+}
+
+/// Waits for the flush tasks, which time out their own uploads, then gives the progress tasks
+/// up to `PROGRESS_COMMIT_TIMEOUT` to commit what was uploaded.
+pub async fn wait_for_shutdown() {
+    let flush_tasks = std::mem::take(&mut *FLUSH_TASKS.lock().unwrap());
+    info!(
+        num_flush_tasks = flush_tasks.len(),
+        "[Parser] Waiting for buffers to be flushed"
+    );
+    futures::future::join_all(flush_tasks).await;
+    FLUSHED.trigger();
+
+    let progress_tasks = std::mem::take(&mut *PROGRESS_TASKS.lock().unwrap());
+    if tokio::time::timeout(
+        PROGRESS_COMMIT_TIMEOUT,
+        futures::future::join_all(progress_tasks),
+    )
+    .await
+    .is_err()
+    {
+        warn!(
+            timeout_secs = PROGRESS_COMMIT_TIMEOUT.as_secs(),
+            "[Parser] Timed out committing progress on shutdown, the uncommitted versions will be \
+             processed again"
+        );
+    }
+}
@@ -13,6 +13,8 @@ bitflags! {
         const MOVE_MODULES = 1 << 6;
         const CURRENT_TABLE_ITEMS = 1 << 7;
         const BLOCK_METADATA_TRANSACTIONS = 1 << 8;
+        const BLOCK_END_TRANSACTIONS = 1 << 9;
+        const CURRENT_MOVE_RESOURCES = 1 << 10;
 
         // Fungible Asset Processor: 11-20
         const FUNGIBLE_ASSET_BALANCES = 1 << 11;
@@ -21,6 +23,8 @@ bitflags! {
         const FUNGIBLE_ASSET_METADATA = 1 << 14;
         const CURRENT_UNIFIED_FUNGIBLE_ASSET_BALANCES = 1 << 15;
         const CURRENT_FUNGIBLE_ASSET_BALANCES_LEGACY = 1 << 16;
+        const FUNGIBLE_ASSET_METADATA_HISTORY = 1 << 17;
+        const COIN_TO_FA_MAPPING = 1 << 18;
 
         // Objects Processor: 21-30
         const OBJECTS = 1 << 21;
@@ -0,0 +1,224 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::future::Future;
+use tracing::info;
+use url::Url;
+
+#[derive(Debug, Deserialize)]
+struct LedgerInfoResponse {
+    chain_id: u64,
+    ledger_version: String,
+    ledger_timestamp: String,
+    oldest_ledger_version: String,
+}
+
+/// Resolves `starting_timestamp` to the first version at or after it, by binary searching
+/// transaction timestamps through the fullnode REST API.
+pub async fn resolve_starting_version(
+    fullnode_rest_api_url: &Url,
+    starting_timestamp: DateTime<Utc>,
+) -> Result<u64> {
+    let client = reqwest::Client::new();
+    let ledger_info = get_ledger_info(&client, fullnode_rest_api_url).await?;
+    let ledger_version: u64 = ledger_info.ledger_version.parse()?;
+    let ledger_timestamp: i64 = ledger_info.ledger_timestamp.parse()?;
+    let oldest_ledger_version: u64 = ledger_info.oldest_ledger_version.parse()?;
+
+    let target_timestamp = starting_timestamp.timestamp_micros();
+    if target_timestamp > ledger_timestamp {
+        bail!(
+            "starting_timestamp {} is after the latest transaction (version {}), can't resolve a starting version yet",
+            starting_timestamp,
+            ledger_version
+        );
+    }
+
+    let version = first_version_at_or_after(
+        oldest_ledger_version,
+        ledger_version,
+        target_timestamp,
+        |version| get_transaction_timestamp(&client, fullnode_rest_api_url, version),
+    )
+    .await?;
+    if version == oldest_ledger_version && oldest_ledger_version > 0 {
+        let oldest_timestamp =
+            get_transaction_timestamp(&client, fullnode_rest_api_url, oldest_ledger_version)
+                .await?;
+        if oldest_timestamp > target_timestamp {
+            bail!(
+                "starting_timestamp {} is before the oldest version {} kept by the fullnode",
+                starting_timestamp,
+                oldest_ledger_version
+            );
+        }
+    }
+
+    info!(
+        starting_timestamp = starting_timestamp.to_rfc3339(),
+        resolved_starting_version = version,
+        "[Parser] Resolved starting_timestamp to a starting version",
+    );
+    Ok(version)
+}
+
+/// The latest version on chain.
+pub async fn get_tip_version(fullnode_rest_api_url: &Url) -> Result<u64> {
+    let ledger_info = get_ledger_info(&reqwest::Client::new(), fullnode_rest_api_url).await?;
+    Ok(ledger_info.ledger_version.parse()?)
+}
+
+/// The latest version on chain, for `start_from_tip`. Fails if the fullnode isn't on `chain_id`,
+/// the stream's, since its tip would then be a version of another chain.
+pub async fn get_chain_tip_version(fullnode_rest_api_url: &Url, chain_id: u64) -> Result<u64> {
+    let ledger_info = get_ledger_info(&reqwest::Client::new(), fullnode_rest_api_url).await?;
+    if ledger_info.chain_id != chain_id {
+        bail!(
+            "The fullnode at {} is on chain {}, but the stream is on chain {}",
+            fullnode_rest_api_url,
+            ledger_info.chain_id,
+            chain_id
+        );
+    }
+    Ok(ledger_info.ledger_version.parse()?)
+}
+
+async fn get_ledger_info(
+    client: &reqwest::Client,
+    fullnode_rest_api_url: &Url,
+) -> Result<LedgerInfoResponse> {
+    client
+        .get(fullnode_rest_api_url.join("v1")?)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Failed to get ledger info from the fullnode")
+}
+
+/// Timestamp of a transaction in microseconds. Genesis has no timestamp, so it counts as 0.
+async fn get_transaction_timestamp(
+    client: &reqwest::Client,
+    fullnode_rest_api_url: &Url,
+    version: u64,
+) -> Result<i64> {
+    let transaction: serde_json::Value = client
+        .get(fullnode_rest_api_url.join(&format!("v1/transactions/by_version/{}", version))?)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .with_context(|| format!("Failed to get transaction {} from the fullnode", version))?;
+    match transaction.get("timestamp").and_then(|t| t.as_str()) {
+        Some(timestamp) => Ok(timestamp.parse()?),
+        None => Ok(0),
+    }
+}
+
+/// Binary searches `[low, high]` for the first version whose timestamp is at or after `target`.
+/// Timestamps never decrease with versions. Returns `high` if every timestamp is before `target`.
+async fn first_version_at_or_after<F, Fut>(
+    mut low: u64,
+    mut high: u64,
+    target: i64,
+    mut get_timestamp: F,
+) -> Result<u64>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = Result<i64>>,
+{
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if get_timestamp(mid).await? >= target {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(low)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    async fn search(timestamps: &[i64], target: i64) -> u64 {
+        first_version_at_or_after(0, timestamps.len() as u64 - 1, target, |version| {
+            let timestamp = timestamps[version as usize];
+            async move { Ok(timestamp) }
+        })
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_first_version_at_or_after() {
+        // Transactions in the same block share a timestamp
+        let timestamps = [0, 100, 100, 100, 250, 300, 300];
+        // Before genesis
+        assert_eq!(search(&timestamps, -5).await, 0);
+        assert_eq!(search(&timestamps, 0).await, 0);
+        assert_eq!(search(&timestamps, 1).await, 1);
+        // First of the block
+        assert_eq!(search(&timestamps, 100).await, 1);
+        assert_eq!(search(&timestamps, 101).await, 4);
+        assert_eq!(search(&timestamps, 300).await, 5);
+    }
+
+    /// A fullnode answering every request with the ledger info of `chain_id`.
+    async fn mock_fullnode(chain_id: u64) -> Url {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 4096];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                let body = serde_json::json!({
+                    "chain_id": chain_id,
+                    "ledger_version": "1234",
+                    "ledger_timestamp": "1700000000000000",
+                    "oldest_ledger_version": "0",
+                })
+                .to_string();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\n\
+                     content-length: {}\r\nconnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        url.parse().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_get_chain_tip_version() {
+        let fullnode_rest_api_url = mock_fullnode(2).await;
+        assert_eq!(get_tip_version(&fullnode_rest_api_url).await.unwrap(), 1234);
+        assert_eq!(
+            get_chain_tip_version(&fullnode_rest_api_url, 2)
+                .await
+                .unwrap(),
+            1234
+        );
+        // A fullnode on another network than the stream's
+        assert!(get_chain_tip_version(&fullnode_rest_api_url, 1)
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Keeps the fetcher a fixed distance behind the chain tip, for consumers that mustn't see data
+//! that recent. Batches closer to the tip than the target are held back until the tip has moved
+//! far enough ahead, which pauses the stream behind them.
+
+use crate::utils::{
+    counters::{TIP_LAG_SECS, TIP_LAG_VERSIONS},
+    timestamp_to_version::get_tip_version,
+};
+use anyhow::{Context, Result};
+use aptos_protos::util::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::{future::Future, time::Duration};
+use tracing::{info, warn};
+use url::Url;
+
+// How often the tip is checked again while a batch is held back
+const TIP_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TipLag {
+    /// Stay at least this many versions behind the latest version on chain, looked up through
+    /// `fullnode_rest_api_url`
+    Versions(u64),
+    /// Only fetch transactions at least this many seconds old, by their timestamps
+    Secs(u64),
+}
+
+#[derive(Clone, Debug)]
+pub struct TipLagThrottle {
+    lag: TipLag,
+    fullnode_rest_api_url: Option<Url>,
+    // Latest tip looked up, so a batch well behind it needs no lookup
+    known_tip: u64,
+}
+
+impl TipLagThrottle {
+    pub fn new(lag: TipLag, fullnode_rest_api_url: Option<Url>) -> Result<Self> {
+        if matches!(lag, TipLag::Versions(_)) {
+            fullnode_rest_api_url
+                .as_ref()
+                .context("tip_lag in versions requires fullnode_rest_api_url")?;
+        }
+        Ok(Self {
+            lag,
+            fullnode_rest_api_url,
+            known_tip: 0,
+        })
+    }
+
+    /// Waits until the batch ending at `end_version` is far enough behind the tip, and reports
+    /// how far behind it is.
+    pub async fn wait(
+        &mut self,
+        processor_name: &str,
+        end_version: u64,
+        end_txn_timestamp: Option<&Timestamp>,
+    ) {
+        match self.lag {
+            TipLag::Versions(lag_versions) => {
+                let fullnode_rest_api_url = self.fullnode_rest_api_url.clone().unwrap();
+                let lag = wait_for_versions_behind_tip(
+                    processor_name,
+                    end_version,
+                    lag_versions,
+                    &mut self.known_tip,
+                    TIP_POLL_INTERVAL,
+                    || get_tip_version(&fullnode_rest_api_url),
+                )
+                .await;
+                TIP_LAG_VERSIONS
+                    .with_label_values(&[processor_name])
+                    .set(lag as i64);
+            },
+            TipLag::Secs(lag_secs) => {
+                // Genesis has no timestamp, and is old enough
+                let Some(end_txn_timestamp) = end_txn_timestamp else {
+                    return;
+                };
+                let lag = wait_for_secs_behind_now(end_txn_timestamp, lag_secs).await;
+                TIP_LAG_SECS
+                    .with_label_values(&[processor_name])
+                    .set(lag.as_secs_f64());
+            },
+        }
+    }
+}
+
+/// Waits until the tip is at least `lag_versions` past `end_version`, and returns how far past it
+/// is. Failed tip lookups are retried, since holding back is always safe.
+async fn wait_for_versions_behind_tip<F, Fut>(
+    processor_name: &str,
+    end_version: u64,
+    lag_versions: u64,
+    known_tip: &mut u64,
+    poll_interval: Duration,
+    mut get_tip: F,
+) -> u64
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<u64>>,
+{
+    let target_tip = end_version.saturating_add(lag_versions);
+    let mut logged = false;
+    while *known_tip < target_tip {
+        match get_tip().await {
+            Ok(tip) => *known_tip = (*known_tip).max(tip),
+            Err(e) => warn!(
+                processor_name,
+                error = ?e,
+                "[Parser] Failed to look up the chain tip for tip_lag, retrying"
+            ),
+        }
+        if *known_tip >= target_tip {
+            break;
+        }
+        if !logged {
+            info!(
+                processor_name,
+                end_version,
+                tip_version = *known_tip,
+                lag_versions,
+                "[Parser] Holding back a batch too close to the chain tip"
+            );
+            logged = true;
+        }
+        tokio::time::sleep(poll_interval).await;
+    }
+    *known_tip - end_version
+}
+
+/// Waits until the transaction at `end_txn_timestamp` is at least `lag_secs` old, and returns how
+/// old it is.
+async fn wait_for_secs_behind_now(end_txn_timestamp: &Timestamp, lag_secs: u64) -> Duration {
+    let txn_time = Duration::new(
+        end_txn_timestamp.seconds.max(0) as u64,
+        end_txn_timestamp.nanos.max(0) as u32,
+    );
+    let now = || {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+    };
+    let ready_at = txn_time + Duration::from_secs(lag_secs);
+    if let Some(remaining) = ready_at.checked_sub(now()) {
+        tokio::time::sleep(remaining).await;
+    }
+    now().saturating_sub(txn_time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn test_stays_behind_tip() {
+        // The chain advances 10 versions every time the tip is looked up
+        let tip = Arc::new(AtomicU64::new(100));
+        let get_tip = || {
+            let tip = tip.clone();
+            async move { Ok::<_, anyhow::Error>(tip.fetch_add(10, Ordering::SeqCst)) }
+        };
+        let mut known_tip = 0;
+
+        // Far enough behind already
+        let lag = wait_for_versions_behind_tip(
+            "test_stays_behind_tip",
+            40,
+            50,
+            &mut known_tip,
+            Duration::from_millis(1),
+            get_tip,
+        )
+        .await;
+        assert_eq!(known_tip, 100);
+        assert_eq!(lag, 60);
+        // Behind the tip already known, so there's no lookup
+        let lag = wait_for_versions_behind_tip(
+            "test_stays_behind_tip",
+            45,
+            50,
+            &mut known_tip,
+            Duration::from_millis(1),
+            get_tip,
+        )
+        .await;
+        assert_eq!(lag, 55);
+        assert_eq!(tip.load(Ordering::SeqCst), 110);
+
+        // Held back until the tip reaches 140, four lookups later
+        let lag = wait_for_versions_behind_tip(
+            "test_stays_behind_tip",
+            90,
+            50,
+            &mut known_tip,
+            Duration::from_millis(1),
+            get_tip,
+        )
+        .await;
+        assert_eq!(known_tip, 140);
+        assert_eq!(lag, 50);
+        assert_eq!(tip.load(Ordering::SeqCst), 150);
+    }
+}
@@ -0,0 +1,290 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Lets networks whose transactions carry fields that aren't in `aptos_protos` decode them
+//! without forking the processors. All a processor needs from a transaction is the standard
+//! `Transaction` message, so a `TransactionDecoder` turns the bytes on the wire into one, along
+//! with an extension holding the extra fields. Extensions travel with their batch, and a custom
+//! processor reads them through `ProcessorTrait::process_transactions_with_extensions`. Once
+//! registered, the decoder is used for every transaction from the GRPC stream and from
+//! `parquet_file_source`.
+
+use ahash::AHashMap;
+use anyhow::Context;
+use aptos_protos::{
+    indexer::v1::{GetTransactionsRequest, TransactionsResponse},
+    transaction::v1::Transaction,
+};
+use once_cell::sync::OnceCell;
+use prost::{bytes::Buf, Message};
+use std::{any::Any, sync::Arc};
+use tonic::{
+    client::Grpc,
+    codec::{Codec, DecodeBuf, Decoder, ProstCodec},
+    codegen::http::uri::PathAndQuery,
+    transport::Channel,
+    Request, Response, Status, Streaming,
+};
+
+const GET_TRANSACTIONS_PATH: &str = "/aptos.indexer.v1.RawData/GetTransactions";
+
+static TRANSACTION_DECODER: OnceCell<Arc<dyn TransactionDecoder>> = OnceCell::new();
+
+/// Extra fields a decoder kept from a transaction, for a custom processor to downcast.
+pub type TransactionExtension = Arc<dyn Any + Send + Sync>;
+
+/// Extensions of a batch's transactions, by version.
+pub type TransactionExtensions = AHashMap<u64, TransactionExtension>;
+
+/// A transaction as the processors read it, along with the extra fields decoded from it.
+pub struct DecodedTransaction {
+    pub transaction: Transaction,
+    pub extension: Option<TransactionExtension>,
+}
+
+impl From<Transaction> for DecodedTransaction {
+    fn from(transaction: Transaction) -> Self {
+        Self {
+            transaction,
+            extension: None,
+        }
+    }
+}
+
+/// `TransactionsResponse` with the extensions of its transactions.
+#[derive(Default)]
+pub struct DecodedTransactionsResponse {
+    pub response: TransactionsResponse,
+    pub extensions: TransactionExtensions,
+}
+
+/// Decodes one protobuf encoded transaction, as sent by the stream.
+pub trait TransactionDecoder: Send + Sync {
+    fn decode(&self, bytes: &[u8]) -> anyhow::Result<DecodedTransaction>;
+}
+
+/// Registers the decoder for every transaction. This must happen before the processor is started.
+/// Returns false if a decoder was already registered.
+pub fn register_transaction_decoder(decoder: impl TransactionDecoder + 'static) -> bool {
+    TRANSACTION_DECODER.set(Arc::new(decoder)).is_ok()
+}
+
+pub fn get_transaction_decoder() -> Option<Arc<dyn TransactionDecoder>> {
+    TRANSACTION_DECODER.get().cloned()
+}
+
+/// Decodes with the registered decoder, or as a plain `Transaction` if there is none
+pub fn decode_transaction(bytes: &[u8]) -> anyhow::Result<DecodedTransaction> {
+    match TRANSACTION_DECODER.get() {
+        Some(decoder) => decoder.decode(bytes),
+        None => Ok(Transaction::decode(bytes)
+            .context("Failed to decode transaction")?
+            .into()),
+    }
+}
+
+/// `TransactionsResponse` with the transactions left encoded. Only the fields the stream reads
+/// are kept.
+#[derive(Clone, PartialEq, Message)]
+struct EncodedTransactionsResponse {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    transactions: Vec<Vec<u8>>,
+    #[prost(uint64, optional, tag = "2")]
+    chain_id: Option<u64>,
+}
+
+fn decode_response(
+    buf: impl Buf,
+    decoder: Option<&dyn TransactionDecoder>,
+) -> anyhow::Result<DecodedTransactionsResponse> {
+    let Some(decoder) = decoder else {
+        return Ok(DecodedTransactionsResponse {
+            response: TransactionsResponse::decode(buf)?,
+            extensions: TransactionExtensions::new(),
+        });
+    };
+    let response = EncodedTransactionsResponse::decode(buf)?;
+    let mut transactions = Vec::with_capacity(response.transactions.len());
+    let mut extensions = TransactionExtensions::new();
+    for bytes in &response.transactions {
+        let DecodedTransaction {
+            transaction,
+            extension,
+        } = decoder.decode(bytes)?;
+        if let Some(extension) = extension {
+            extensions.insert(transaction.version, extension);
+        }
+        transactions.push(transaction);
+    }
+    Ok(DecodedTransactionsResponse {
+        response: TransactionsResponse {
+            transactions,
+            chain_id: response.chain_id,
+            ..TransactionsResponse::default()
+        },
+        extensions,
+    })
+}
+
+/// Same as the generated `RawDataClient::get_transactions`, except that the transactions are
+/// decoded with the registered decoder if there is one. A transaction that fails to decode fails
+/// the stream like a malformed response would.
+pub async fn get_transactions(
+    client: &mut Grpc<Channel>,
+    request: Request<GetTransactionsRequest>,
+) -> Result<Response<Streaming<DecodedTransactionsResponse>>, Status> {
+    client
+        .ready()
+        .await
+        .map_err(|e| Status::unknown(format!("Service was not ready: {}", e)))?;
+    let path = PathAndQuery::from_static(GET_TRANSACTIONS_PATH);
+    let codec = TransactionsResponseCodec {
+        decoder: get_transaction_decoder(),
+    };
+    client.server_streaming(request, path, codec).await
+}
+
+struct TransactionsResponseCodec {
+    decoder: Option<Arc<dyn TransactionDecoder>>,
+}
+
+impl Codec for TransactionsResponseCodec {
+    type Decode = DecodedTransactionsResponse;
+    type Decoder = TransactionsResponseDecoder;
+    type Encode = GetTransactionsRequest;
+    type Encoder = <ProstCodec<GetTransactionsRequest, TransactionsResponse> as Codec>::Encoder;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        ProstCodec::<GetTransactionsRequest, TransactionsResponse>::default().encoder()
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        TransactionsResponseDecoder {
+            decoder: self.decoder.clone(),
+        }
+    }
+}
+
+struct TransactionsResponseDecoder {
+    decoder: Option<Arc<dyn TransactionDecoder>>,
+}
+
+impl Decoder for TransactionsResponseDecoder {
+    type Error = Status;
+    type Item = DecodedTransactionsResponse;
+
+    fn decode(&mut self, buf: &mut DecodeBuf<'_>) -> Result<Option<Self::Item>, Self::Error> {
+        decode_response(buf, self.decoder.as_deref())
+            .map(Some)
+            .map_err(|e| Status::internal(format!("Failed to decode transactions: {:#}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Extra field a private network appends to `Transaction`
+    #[derive(Clone, PartialEq, Message)]
+    struct TenantExtension {
+        #[prost(string, tag = "1000")]
+        tenant: String,
+    }
+
+    /// Keeps the tenant of every transaction it decodes as its extension
+    struct TenantDecoder;
+
+    impl TransactionDecoder for TenantDecoder {
+        fn decode(&self, bytes: &[u8]) -> anyhow::Result<DecodedTransaction> {
+            Ok(DecodedTransaction {
+                transaction: Transaction::decode(bytes)?,
+                extension: Some(Arc::new(TenantExtension::decode(bytes)?)),
+            })
+        }
+    }
+
+    fn extended_transaction(version: u64, tenant: &str) -> Vec<u8> {
+        let mut bytes = Transaction {
+            version,
+            ..Transaction::default()
+        }
+        .encode_to_vec();
+        TenantExtension {
+            tenant: tenant.to_string(),
+        }
+        .encode(&mut bytes)
+        .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn test_decode_response_with_custom_decoder() {
+        let response = EncodedTransactionsResponse {
+            transactions: vec![extended_transaction(7, "a"), extended_transaction(8, "b")],
+            chain_id: Some(4),
+        }
+        .encode_to_vec();
+        let DecodedTransactionsResponse {
+            response,
+            extensions,
+        } = decode_response(response.as_slice(), Some(&TenantDecoder)).unwrap();
+        assert_eq!(response.chain_id, Some(4));
+        assert_eq!(
+            response
+                .transactions
+                .iter()
+                .map(|txn| txn.version)
+                .collect::<Vec<_>>(),
+            vec![7, 8]
+        );
+        let tenant = |version| {
+            extensions[&version]
+                .downcast_ref::<TenantExtension>()
+                .map(|extension| extension.tenant.as_str())
+        };
+        assert_eq!((tenant(7), tenant(8)), (Some("a"), Some("b")));
+        // The extension is dropped from what the processors see
+        assert_eq!(
+            response.transactions[0].encode_to_vec(),
+            Transaction {
+                version: 7,
+                ..Transaction::default()
+            }
+            .encode_to_vec()
+        );
+    }
+
+    #[test]
+    fn test_encoded_response_matches_transactions_response() {
+        let response = TransactionsResponse {
+            transactions: vec![
+                Transaction {
+                    version: 1,
+                    epoch: 2,
+                    ..Transaction::default()
+                },
+                Transaction {
+                    version: 2,
+                    ..Transaction::default()
+                },
+            ],
+            chain_id: Some(1),
+            ..TransactionsResponse::default()
+        };
+        let encoded =
+            EncodedTransactionsResponse::decode(response.encode_to_vec().as_slice()).unwrap();
+        assert_eq!(encoded.chain_id, Some(1));
+        assert_eq!(
+            encoded
+                .transactions
+                .iter()
+                .map(|bytes| decode_transaction(bytes).unwrap().transaction)
+                .collect::<Vec<_>>(),
+            response.transactions
+        );
+        // Without a decoder, the response is decoded as is
+        let decoded = decode_response(response.encode_to_vec().as_slice(), None).unwrap();
+        assert_eq!(decoded.response, response);
+        assert!(decoded.extensions.is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parts of a transaction a processor reads. The rest can be dropped as soon as a batch comes off
+//! the stream, so it doesn't take up memory while the batch is buffered and processed. Version,
+//! block height, timestamp and the other scalar fields are always kept.
+
+use aptos_protos::transaction::v1::{transaction::TxnData, Transaction};
+use bitflags::bitflags;
+
+bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TransactionFields: u8 {
+        const EVENTS = 1 << 0;
+        /// The write set changes in the transaction info
+        const WRITE_SET_CHANGES = 1 << 1;
+        /// User transaction payloads and the genesis write set
+        const PAYLOAD = 1 << 2;
+        const SIGNATURE = 1 << 3;
+    }
+}
+
+impl TransactionFields {
+    /// Drops the fields that aren't in `self`.
+    pub fn strip(self, txn: &mut Transaction) {
+        if !self.contains(Self::WRITE_SET_CHANGES) {
+            if let Some(info) = txn.info.as_mut() {
+                info.changes = vec![];
+            }
+        }
+        let events = match txn.txn_data.as_mut() {
+            Some(TxnData::BlockMetadata(inner)) => Some(&mut inner.events),
+            Some(TxnData::Genesis(inner)) => {
+                if !self.contains(Self::PAYLOAD) {
+                    inner.payload = None;
+                }
+                Some(&mut inner.events)
+            },
+            Some(TxnData::User(inner)) => {
+                if let Some(request) = inner.request.as_mut() {
+                    if !self.contains(Self::PAYLOAD) {
+                        request.payload = None;
+                    }
+                    if !self.contains(Self::SIGNATURE) {
+                        request.signature = None;
+                    }
+                }
+                Some(&mut inner.events)
+            },
+            Some(TxnData::Validator(inner)) => Some(&mut inner.events),
+            _ => None,
+        };
+        if let Some(events) = events {
+            if !self.contains(Self::EVENTS) {
+                *events = vec![];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aptos_protos::transaction::v1::{
+        transaction_payload, write_set_change::Change, EntryFunctionPayload, Event,
+        TransactionInfo, TransactionPayload, UserTransaction, UserTransactionRequest,
+        WriteResource, WriteSetChange,
+    };
+    use prost::Message;
+
+    /// A user transaction with `num_changes` write resources of 1KB each and a few events.
+    fn transaction_with_large_write_set(num_changes: usize) -> Transaction {
+        let changes = (0..num_changes)
+            .map(|i| {
+                let write_resource = WriteResource {
+                    address: format!("0x{:064x}", i),
+                    type_str: "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>".to_string(),
+                    data: "x".repeat(1024),
+                    ..WriteResource::default()
+                };
+                WriteSetChange {
+                    change: Some(Change::WriteResource(write_resource)),
+                    ..WriteSetChange::default()
+                }
+            })
+            .collect();
+        let events = (0..3)
+            .map(|i| Event {
+                type_str: "0x1::coin::DepositEvent".to_string(),
+                data: format!(r#"{{"amount":"{}"}}"#, i),
+                ..Event::default()
+            })
+            .collect();
+        let payload = TransactionPayload {
+            payload: Some(transaction_payload::Payload::EntryFunctionPayload(
+                EntryFunctionPayload {
+                    arguments: vec!["0".repeat(4096)],
+                    ..EntryFunctionPayload::default()
+                },
+            )),
+            ..TransactionPayload::default()
+        };
+        let request = UserTransactionRequest {
+            sender: "0x1".to_string(),
+            payload: Some(payload),
+            ..UserTransactionRequest::default()
+        };
+        Transaction {
+            version: 1,
+            info: Some(TransactionInfo {
+                changes,
+                ..TransactionInfo::default()
+            }),
+            txn_data: Some(TxnData::User(UserTransaction {
+                request: Some(request),
+                events,
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    #[test]
+    fn test_events_only_drops_write_set_and_payload() {
+        let mut txn = transaction_with_large_write_set(1_000);
+        let size_before = txn.encoded_len();
+        let events_before = match &txn.txn_data {
+            Some(TxnData::User(inner)) => inner.events.clone(),
+            _ => unreachable!(),
+        };
+
+        TransactionFields::EVENTS.strip(&mut txn);
+
+        let size_after = txn.encoded_len();
+        assert!(
+            size_after * 100 < size_before,
+            "{} bytes before, {} after",
+            size_before,
+            size_after
+        );
+        assert!(txn.info.as_ref().unwrap().changes.is_empty());
+        let Some(TxnData::User(inner)) = &txn.txn_data else {
+            unreachable!();
+        };
+        assert_eq!(inner.events, events_before);
+        let request = inner.request.as_ref().unwrap();
+        assert_eq!(request.payload, None);
+        assert_eq!(request.sender, "0x1");
+        assert_eq!(txn.version, 1);
+    }
+
+    #[test]
+    fn test_all_fields_keeps_the_transaction() {
+        let mut txn = transaction_with_large_write_set(10);
+        let expected = txn.clone();
+        TransactionFields::all().strip(&mut txn);
+        assert_eq!(txn, expected);
+    }
+}
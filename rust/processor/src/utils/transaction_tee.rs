@@ -0,0 +1,159 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Sends the transactions that pass `transaction_filter` to a channel of the embedding
+//! application as well, e.g. to alert on them in real time without a second GRPC connection.
+//! The tee is registered in code before the worker starts, see `register_transaction_tee`, and
+//! only the main stream is teed, not replays.
+
+use crate::utils::counters::{TRANSACTION_TEE_DROPPED_COUNT, TRANSACTION_TEE_SENT_COUNT};
+use anyhow::Result;
+use aptos_protos::transaction::v1::Transaction;
+use kanal::AsyncSender;
+use once_cell::sync::OnceCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::warn;
+
+/// What to do when the consumer's channel is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TeeOverflow {
+    /// Drop the batch and count it in `indexer_processor_transaction_tee_dropped_count`, so a slow
+    /// consumer never holds up indexing.
+    Drop,
+    /// Wait for room, so the consumer sees every batch and paces the stream. Indexing stalls
+    /// for as long as the consumer does.
+    Block,
+}
+
+pub struct TransactionTee {
+    sender: AsyncSender<Vec<Transaction>>,
+    overflow: TeeOverflow,
+    // Set once the consumer drops its receiver, after which nothing more is sent
+    closed: AtomicBool,
+}
+
+impl TransactionTee {
+    pub fn new(sender: AsyncSender<Vec<Transaction>>, overflow: TeeOverflow) -> Self {
+        Self {
+            sender,
+            overflow,
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    /// Sends a copy of `transactions`, the batch left after filtering. Never fails, since the
+    /// consumer going away mustn't affect indexing.
+    pub async fn send(&self, processor_name: &str, transactions: &[Transaction]) {
+        if transactions.is_empty() || self.closed.load(Ordering::Relaxed) {
+            return;
+        }
+        let num_transactions = transactions.len() as u64;
+        let sent = match self.overflow {
+            TeeOverflow::Drop => self.sender.try_send(transactions.to_vec()),
+            TeeOverflow::Block => self.sender.send(transactions.to_vec()).await.map(|()| true),
+        };
+        match sent {
+            Ok(true) => TRANSACTION_TEE_SENT_COUNT
+                .with_label_values(&[processor_name])
+                .inc_by(num_transactions),
+            Ok(false) => TRANSACTION_TEE_DROPPED_COUNT
+                .with_label_values(&[processor_name])
+                .inc_by(num_transactions),
+            Err(e) => {
+                self.closed.store(true, Ordering::Relaxed);
+                warn!(
+                    processor_name,
+                    error = ?e,
+                    "[Parser] Transaction tee receiver is gone, no longer teeing transactions"
+                );
+            },
+        }
+    }
+}
+
+static TRANSACTION_TEE: OnceCell<TransactionTee> = OnceCell::new();
+
+/// Registers the tee for the worker's stream. It's process wide, so there's only one.
+pub fn register_transaction_tee(tee: TransactionTee) -> Result<()> {
+    TRANSACTION_TEE
+        .set(tee)
+        .map_err(|_| anyhow::anyhow!("A transaction tee is already registered"))
+}
+
+pub fn transaction_tee() -> Option<&'static TransactionTee> {
+    TRANSACTION_TEE.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn transactions(versions: std::ops::Range<u64>) -> Vec<Transaction> {
+        versions
+            .map(|version| Transaction {
+                version,
+                ..Transaction::default()
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_drop_when_full() {
+        let (sender, receiver) = kanal::bounded_async(1);
+        let tee = TransactionTee::new(sender, TeeOverflow::Drop);
+        tee.send("test_drop_when_full", &transactions(0..2)).await;
+        // The channel is full, so this is dropped rather than waited on
+        tokio::time::timeout(
+            Duration::from_millis(100),
+            tee.send("test_drop_when_full", &transactions(2..4)),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            TRANSACTION_TEE_DROPPED_COUNT
+                .with_label_values(&["test_drop_when_full"])
+                .get(),
+            2
+        );
+        assert_eq!(receiver.recv().await.unwrap(), transactions(0..2));
+        assert!(receiver.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_block_until_consumed() {
+        let (sender, receiver) = kanal::bounded_async(1);
+        let tee = std::sync::Arc::new(TransactionTee::new(sender, TeeOverflow::Block));
+        tee.send("test_block_until_consumed", &transactions(0..2))
+            .await;
+        let blocked = tokio::spawn({
+            let tee = tee.clone();
+            async move {
+                tee.send("test_block_until_consumed", &transactions(2..4))
+                    .await
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!blocked.is_finished());
+
+        // Consuming makes room, and nothing was dropped
+        assert_eq!(receiver.recv().await.unwrap(), transactions(0..2));
+        blocked.await.unwrap();
+        assert_eq!(receiver.recv().await.unwrap(), transactions(2..4));
+        assert_eq!(
+            TRANSACTION_TEE_SENT_COUNT
+                .with_label_values(&["test_block_until_consumed"])
+                .get(),
+            4
+        );
+    }
+
+    #[tokio::test]
+    async fn test_receiver_gone() {
+        let (sender, receiver) = kanal::bounded_async(1);
+        let tee = TransactionTee::new(sender, TeeOverflow::Block);
+        drop(receiver);
+        tee.send("test_receiver_gone", &transactions(0..2)).await;
+        assert!(tee.closed.load(Ordering::Relaxed));
+    }
+}
@@ -8,16 +8,18 @@ use crate::{
 };
 use aptos_protos::{
     transaction::v1::{
-        multisig_transaction_payload::Payload as MultisigPayloadType,
+        multisig_transaction_payload::Payload as MultisigPayloadType, transaction::TxnData,
         transaction_payload::Payload as PayloadType, write_set::WriteSet as WriteSetType,
         EntryFunctionId, EntryFunctionPayload, MoveScriptBytecode, MoveType, ScriptPayload,
-        TransactionPayload, UserTransactionRequest, WriteSet,
+        Transaction, TransactionPayload, UserTransactionRequest, WriteSet,
     },
     util::timestamp::Timestamp,
 };
 use bigdecimal::{BigDecimal, Signed, ToPrimitive, Zero};
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
+use prost::Message;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 use sha2::Digest;
@@ -74,13 +76,36 @@ pub struct MultisigPayloadClean {
     pub transaction_payload: Option<Value>,
 }
 
-/// Standardizes all addresses and table handles to be length 66 (0x-64 length hash)
+/// Canonical form of an address or table handle, the one every table stores so they can be
+/// joined: `0x` followed by 64 lowercase hex digits. The `0x` prefix is optional in the input,
+/// short addresses like `0x1` are zero-padded and extra leading zeros are dropped.
 pub fn standardize_address(handle: &str) -> String {
-    if let Some(handle) = handle.strip_prefix("0x") {
-        format!("0x{:0>64}", handle)
+    let hex = handle.strip_prefix("0x").unwrap_or(handle);
+    let hex = if hex.len() > 64 {
+        hex.trim_start_matches('0')
     } else {
-        format!("0x{:0>64}", handle)
-    }
+        hex
+    };
+    format!("0x{:0>64}", hex.to_ascii_lowercase())
+}
+
+/// Whether the address is already in the form `standardize_address` gives.
+pub fn is_standardized_address(address: &str) -> bool {
+    address.len() == 66
+        && address.starts_with("0x")
+        && address[2..]
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Panics in debug builds, and so in tests, if the address isn't standardized. Call before
+/// writing addresses that come from parsing, to catch paths that skip `standardize_address`.
+pub fn debug_assert_standardized_address(address: &str) {
+    debug_assert!(
+        is_standardized_address(address),
+        "Address {:?} isn't standardized",
+        address
+    );
 }
 
 /// Standardizes all addresses and table handles to be length 66 (0x-64 length hash) that takes in a slice.
@@ -107,6 +132,44 @@ pub fn sha3_256(buffer: &[u8]) -> [u8; 32] {
     output
 }
 
+/// Deterministic SHA-256 of a transaction's content as `0x` prefixed hex, for comparing indexer
+/// databases built from different streams. It's taken over the protobuf encoding of the whole
+/// transaction (version, epoch, block height, timestamp, type, `info` including the write set
+/// changes, and the type specific data including payload, events and signatures) with
+/// `size_info` cleared. `size_info` is computed by the transaction stream rather than the chain,
+/// so it can differ between environments serving the same transaction.
+pub fn transaction_content_hash(transaction: &Transaction) -> String {
+    let transaction = Transaction {
+        size_info: None,
+        ..transaction.clone()
+    };
+    format!(
+        "0x{}",
+        hex::encode(sha2::Sha256::digest(transaction.encode_to_vec()))
+    )
+}
+
+/// Events and write set changes in a batch, for `DefaultProcessingResult`. Only counts what the
+/// stream sent, so fields stripped for the processor count as empty.
+pub fn count_events_and_write_set_changes(transactions: &[Transaction]) -> (u64, u64) {
+    transactions
+        .iter()
+        .fold((0, 0), |(num_events, num_write_set_changes), txn| {
+            let events = match txn.txn_data.as_ref() {
+                Some(TxnData::BlockMetadata(tx_inner)) => tx_inner.events.len(),
+                Some(TxnData::Genesis(tx_inner)) => tx_inner.events.len(),
+                Some(TxnData::User(tx_inner)) => tx_inner.events.len(),
+                Some(TxnData::Validator(tx_inner)) => tx_inner.events.len(),
+                _ => 0,
+            };
+            let changes = txn.info.as_ref().map_or(0, |info| info.changes.len());
+            (
+                num_events + events as u64,
+                num_write_set_changes + changes as u64,
+            )
+        })
+}
+
 pub fn truncate_str(val: &str, max_chars: usize) -> String {
     let mut trunc = val.to_string();
     trunc.truncate(max_chars);
@@ -189,6 +252,24 @@ pub fn get_entry_function_function_name_from_user_request(
     })
 }
 
+/// The script and its arguments if the transaction runs a script, in the same format as script
+/// payloads in `get_clean_payload`. None for any other payload.
+pub fn get_script_payload_from_user_request(
+    user_request: &UserTransactionRequest,
+    version: i64,
+) -> Option<Value> {
+    match user_request.payload.as_ref()?.payload.as_ref()? {
+        PayloadType::ScriptPayload(inner) => {
+            let clean = get_clean_script_payload(inner, version);
+            Some(serde_json::to_value(clean).unwrap_or_else(|_| {
+                tracing::error!(version = version, "Unable to serialize payload into value");
+                panic!()
+            }))
+        },
+        _ => None,
+    }
+}
+
 pub fn get_payload_type(payload: &TransactionPayload) -> String {
     payload.r#type().as_str_name().to_string()
 }
@@ -343,6 +424,45 @@ pub fn parse_timestamp(ts: &Timestamp, version: i64) -> chrono::NaiveDateTime {
         .unwrap_or_else(|| panic!("Could not parse timestamp {:?} for version {}", ts, version))
 }
 
+/// Stored as the timestamp of transactions without one, see `set_missing_timestamp_sentinel`.
+static MISSING_TIMESTAMP_SENTINEL: OnceCell<NaiveDateTime> = OnceCell::new();
+
+/// Stored as the timestamp of transactions without one unless configured otherwise, as
+/// `0001-01-01T00:00:00` can't be mistaken for a real timestamp and fits Postgres and BigQuery.
+pub fn default_missing_timestamp_sentinel() -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(1, 1, 1)
+        .and_then(|date| date.and_hms_opt(0, 0, 0))
+        .expect("0001-01-01T00:00:00 is a valid timestamp")
+}
+
+/// Sets what's stored as the timestamp of transactions that don't have one. It's process-wide,
+/// so this fails if a different sentinel was already set or the default was already used.
+pub fn set_missing_timestamp_sentinel(sentinel: NaiveDateTime) -> anyhow::Result<()> {
+    let current = *MISSING_TIMESTAMP_SENTINEL.get_or_init(|| sentinel);
+    anyhow::ensure!(
+        current == sentinel,
+        "Missing timestamp sentinel is already {}, can't change it to {}",
+        current,
+        sentinel
+    );
+    Ok(())
+}
+
+/// The transaction's timestamp, or None if it doesn't have a real one. Genesis has either none or
+/// the Unix epoch.
+pub fn transaction_timestamp(timestamp: Option<&Timestamp>) -> Option<&Timestamp> {
+    timestamp.filter(|timestamp| timestamp.seconds != 0 || timestamp.nanos != 0)
+}
+
+/// Parses a transaction's timestamp to store. Transactions without one get the sentinel set with
+/// `set_missing_timestamp_sentinel`, or `default_missing_timestamp_sentinel` if there's none.
+pub fn parse_transaction_timestamp(timestamp: Option<&Timestamp>, version: i64) -> NaiveDateTime {
+    match transaction_timestamp(timestamp) {
+        Some(timestamp) => parse_timestamp(timestamp, version),
+        None => *MISSING_TIMESTAMP_SENTINEL.get_or_init(default_missing_timestamp_sentinel),
+    }
+}
+
 pub fn compute_nanos_since_epoch(datetime: NaiveDateTime) -> u64 {
     // The Unix epoch is 1970-01-01T00:00:00Z
     #[allow(deprecated)]
@@ -554,6 +674,26 @@ pub struct DerivedStringSnapshot {
     pub value: String,
 }
 
+/// Attaches where the data that failed to parse came from, so it can be found on chain. `index`
+/// is the event index or write set change index in the transaction.
+pub trait ParseContext<T> {
+    fn parse_context(self, txn_version: i64, index: i64, type_str: &str) -> anyhow::Result<T>;
+}
+
+impl<T, E> ParseContext<T> for Result<T, E>
+where
+    Self: anyhow::Context<T, E>,
+{
+    fn parse_context(self, txn_version: i64, index: i64, type_str: &str) -> anyhow::Result<T> {
+        anyhow::Context::with_context(self, || {
+            format!(
+                "Failed to parse {} at version {} index {}",
+                type_str, txn_version, index
+            )
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -580,6 +720,69 @@ mod tests {
         pub default_properties: serde_json::Value,
     }
 
+    fn content_hash_txn(gas_used: u64, transaction_bytes: u32) -> Transaction {
+        Transaction {
+            version: 100,
+            epoch: 2,
+            block_height: 10,
+            timestamp: Some(Timestamp {
+                seconds: 1649560602,
+                nanos: 5,
+            }),
+            info: Some(aptos_protos::transaction::v1::TransactionInfo {
+                gas_used,
+                success: true,
+                ..Default::default()
+            }),
+            size_info: Some(aptos_protos::transaction::v1::TransactionSizeInfo {
+                transaction_bytes,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_transaction_content_hash_is_deterministic() {
+        // Same transaction built twice, as two runs would decode it
+        let hash = transaction_content_hash(&content_hash_txn(500, 300));
+        assert_eq!(hash, transaction_content_hash(&content_hash_txn(500, 300)));
+        assert_eq!(hash.len(), 66);
+        // size_info is excluded
+        assert_eq!(hash, transaction_content_hash(&content_hash_txn(500, 999)));
+        // Chain data isn't
+        assert_ne!(hash, transaction_content_hash(&content_hash_txn(501, 300)));
+    }
+
+    #[test]
+    fn test_standardize_address() {
+        let one = "0x0000000000000000000000000000000000000000000000000000000000000001";
+        assert_eq!(standardize_address("0x1"), one);
+        assert_eq!(standardize_address("1"), one);
+        assert_eq!(standardize_address("0x0001"), one);
+        assert_eq!(standardize_address(one), one);
+        // Too many leading zeros
+        assert_eq!(standardize_address(&format!("0x00{}", &one[2..])), one);
+
+        let full = "0xa6cbdcdcd7e0bd4ffa6bfc8e1a7e3e85f2e7d59ea8c4eeeb80a3f19b6a7f9d40";
+        let uppercase = format!("0x{}", full[2..].to_uppercase());
+        assert_eq!(standardize_address(full), full);
+        assert_eq!(standardize_address(&uppercase), full);
+
+        assert!(is_standardized_address(one));
+        assert!(is_standardized_address(full));
+        assert!(!is_standardized_address("0x1"));
+        assert!(!is_standardized_address(&uppercase));
+        assert!(!is_standardized_address(&full[2..]));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "isn't standardized")]
+    fn test_debug_assert_standardized_address() {
+        debug_assert_standardized_address("0x1");
+    }
+
     #[test]
     fn test_parse_timestamp() {
         let ts = parse_timestamp(
@@ -599,6 +802,33 @@ mod tests {
         assert_eq!(ts3.and_utc().timestamp(), 1659386386);
     }
 
+    #[test]
+    fn test_transaction_timestamp() {
+        let genesis = Timestamp {
+            seconds: 0,
+            nanos: 0,
+        };
+        let timestamp = Timestamp {
+            seconds: 1649560602,
+            nanos: 0,
+        };
+        assert_eq!(transaction_timestamp(None), None);
+        assert_eq!(transaction_timestamp(Some(&genesis)), None);
+        assert_eq!(transaction_timestamp(Some(&timestamp)), Some(&timestamp));
+        assert_eq!(
+            parse_transaction_timestamp(Some(&timestamp), 1),
+            parse_timestamp(&timestamp, 1)
+        );
+        assert_eq!(
+            parse_transaction_timestamp(Some(&genesis), 0),
+            default_missing_timestamp_sentinel()
+        );
+        assert_eq!(default_missing_timestamp_sentinel().year(), 1);
+        // The sentinel was used, so it can't change anymore
+        assert!(set_missing_timestamp_sentinel(default_missing_timestamp_sentinel()).is_ok());
+        assert!(set_missing_timestamp_sentinel(NaiveDateTime::default()).is_err());
+    }
+
     #[test]
     fn test_deserialize_string_from_bcs() {
         let test_struct = TypeInfoMock {
@@ -717,6 +947,19 @@ mod tests {
         assert_eq!(d.default_properties["u64_property"], "72057594037927936");
     }
 
+    #[test]
+    fn test_parse_context() {
+        let err = serde_json::from_str::<AggregatorSnapshot>(r#"{"value": 1}"#)
+            .parse_context(1234, 5, "0x1::aggregator_v2::AggregatorSnapshot")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to parse 0x1::aggregator_v2::AggregatorSnapshot at version 1234 index 5"
+        );
+        // The parse error itself is kept
+        assert!(format!("{:?}", err).contains("invalid type"));
+    }
+
     #[test]
     fn test_empty_token_object_property_map() {
         let test_property_json = r#"{"data": []}"#;
@@ -0,0 +1,391 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+use crate::{
+    db::postgres::models::{
+        events_models::events::EventModel, webhook_dead_letters::WebhookDeadLetter,
+    },
+    schema,
+    utils::{
+        database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+        retry::{Backoff, Jitter},
+    },
+};
+use ahash::AHashMap;
+use anyhow::{Context, Result};
+use diesel::{pg::Pg, query_builder::QueryFragment};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+use url::Url;
+
+const CLOUD_EVENTS_CONTENT_TYPE: &str = "application/cloudevents+json";
+const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub url: Url,
+    /// Only events of these types are sent, e.g. `0x1::coin::CoinDeposit`. All events if empty.
+    #[serde(default)]
+    pub event_types: Vec<String>,
+    /// `source` attribute of the CloudEvents.
+    #[serde(default = "WebhookConfig::default_source")]
+    pub source: String,
+    #[serde(default = "WebhookConfig::default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// Retries before an event is dead-lettered. The delay doubles after each retry.
+    #[serde(default = "WebhookConfig::default_max_retries")]
+    pub max_retries: usize,
+    #[serde(default = "WebhookConfig::default_initial_retry_delay_ms")]
+    pub initial_retry_delay_ms: u64,
+    /// Randomizes the retry delays so many processors failing at once don't retry in lockstep.
+    #[serde(default)]
+    pub retry_jitter: Jitter,
+    /// If set, each request has an `X-Signature-256: sha256=<hex>` header with the HMAC-SHA256
+    /// of the body, so the receiver can check it came from this processor.
+    #[serde(default)]
+    pub signing_secret: Option<String>,
+}
+
+impl WebhookConfig {
+    pub fn default_source() -> String {
+        "aptos-indexer-processors/events_processor".to_string()
+    }
+
+    pub const fn default_timeout_secs() -> u64 {
+        10
+    }
+
+    pub const fn default_max_retries() -> usize {
+        3
+    }
+
+    pub const fn default_initial_retry_delay_ms() -> u64 {
+        500
+    }
+}
+
+/// Structured-mode CloudEvents 1.0 envelope for an event. The version and index are extension
+/// attributes, which must be lowercase alphanumeric.
+#[derive(Debug, Serialize)]
+pub struct CloudEvent<'a> {
+    pub specversion: &'static str,
+    pub id: String,
+    pub source: &'a str,
+    #[serde(rename = "type")]
+    pub type_: &'a str,
+    pub subject: &'a str,
+    pub datacontenttype: &'static str,
+    pub aptostransactionversion: i64,
+    pub aptoseventindex: i64,
+    pub data: &'a serde_json::Value,
+}
+
+impl<'a> CloudEvent<'a> {
+    pub fn from_event(event: &'a EventModel, source: &'a str) -> Self {
+        Self {
+            specversion: "1.0",
+            // Unique per source, and the same on redelivery so receivers can dedupe
+            id: format!("{}-{}", event.transaction_version, event.event_index),
+            source,
+            type_: &event.type_,
+            subject: &event.account_address,
+            datacontenttype: "application/json",
+            aptostransactionversion: event.transaction_version,
+            aptoseventindex: event.event_index,
+            data: &event.data,
+        }
+    }
+}
+
+/// Posts events to the webhook one at a time, in order. Delivery is at least once: a batch only
+/// counts as processed once each of its events was delivered or dead-lettered.
+pub struct WebhookSink {
+    config: WebhookConfig,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(config: WebhookConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .context("Failed to build webhook client")?;
+        Ok(Self { config, client })
+    }
+
+    fn is_matching(&self, event: &EventModel) -> bool {
+        self.config.event_types.is_empty() || self.config.event_types.contains(&event.type_)
+    }
+
+    /// Delivers the matching events and returns the ones that failed after all retries.
+    pub async fn deliver(&self, events: &[EventModel]) -> Result<Vec<WebhookDeadLetter>> {
+        let mut dead_letters = vec![];
+        for event in events.iter().filter(|event| self.is_matching(event)) {
+            let cloud_event = CloudEvent::from_event(event, &self.config.source);
+            let body = serde_json::to_vec(&cloud_event)?;
+            if let Err(e) = self.post_with_retries(&body).await {
+                tracing::warn!(
+                    transaction_version = event.transaction_version,
+                    event_index = event.event_index,
+                    url = self.config.url.as_str(),
+                    error = ?e,
+                    "Failed to deliver event to webhook, routing to webhook_dead_letters",
+                );
+                dead_letters.push(WebhookDeadLetter {
+                    transaction_version: event.transaction_version,
+                    event_index: event.event_index,
+                    url: self.config.url.to_string(),
+                    cloud_event: serde_json::to_value(&cloud_event)?,
+                    error: format!("{:#}", e),
+                });
+            }
+        }
+        Ok(dead_letters)
+    }
+
+    async fn post_with_retries(&self, body: &[u8]) -> Result<()> {
+        let mut retry_count = 0;
+        let mut backoff =
+            Backoff::new(self.config.initial_retry_delay_ms, self.config.retry_jitter);
+        loop {
+            match self.post(body).await {
+                Ok(()) => return Ok(()),
+                Err(e) if retry_count >= self.config.max_retries => return Err(e),
+                Err(e) => {
+                    tracing::debug!(retry_count, error = ?e, "Retrying webhook delivery");
+                },
+            }
+            retry_count += 1;
+            backoff.sleep().await;
+        }
+    }
+
+    async fn post(&self, body: &[u8]) -> Result<()> {
+        let mut request = self
+            .client
+            .post(self.config.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, CLOUD_EVENTS_CONTENT_TYPE)
+            .body(body.to_vec());
+        if let Some(signing_secret) = &self.config.signing_secret {
+            let signature = hmac_sha256(signing_secret.as_bytes(), body);
+            request = request.header(
+                SIGNATURE_HEADER,
+                format!("sha256={}", hex::encode(signature)),
+            );
+        }
+        request.send().await?.error_for_status()?;
+        Ok(())
+    }
+}
+
+/// HMAC-SHA256 as in RFC 2104.
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    const BLOCK_SIZE: usize = 64;
+    let mut key_block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        key_block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        key_block[..key.len()].copy_from_slice(key);
+    }
+    let inner = Sha256::new()
+        .chain_update(key_block.map(|b| b ^ 0x36))
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(key_block.map(|b| b ^ 0x5c))
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+pub async fn insert_webhook_dead_letters(
+    conn: ArcDbPool,
+    dead_letters: &[WebhookDeadLetter],
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<(), diesel::result::Error> {
+    execute_in_chunks(
+        conn,
+        insert_webhook_dead_letters_query,
+        dead_letters,
+        get_config_table_chunk_size::<WebhookDeadLetter>(
+            "webhook_dead_letters",
+            per_table_chunk_sizes,
+        ),
+    )
+    .await
+}
+
+fn insert_webhook_dead_letters_query(
+    items_to_insert: Vec<WebhookDeadLetter>,
+) -> (
+    impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send,
+    Option<&'static str>,
+) {
+    use schema::webhook_dead_letters::dsl::*;
+    (
+        diesel::insert_into(schema::webhook_dead_letters::table)
+            .values(items_to_insert)
+            .on_conflict((transaction_version, event_index))
+            .do_nothing(),
+        None,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    fn event() -> EventModel {
+        EventModel {
+            sequence_number: 0,
+            creation_number: 0,
+            account_address: "0xcafe".to_string(),
+            transaction_version: 100,
+            transaction_block_height: 10,
+            type_: "0xcafe::poke::Poke".to_string(),
+            data: serde_json::json!({"user": "0x1", "times": "3"}),
+            event_index: 2,
+            indexed_type: "0xcafe::poke::Poke".to_string(),
+        }
+    }
+
+    fn config(url: String) -> WebhookConfig {
+        WebhookConfig {
+            url: url.parse().unwrap(),
+            event_types: vec![],
+            source: WebhookConfig::default_source(),
+            timeout_secs: 5,
+            max_retries: 1,
+            initial_retry_delay_ms: 0,
+            retry_jitter: Jitter::None,
+            signing_secret: None,
+        }
+    }
+
+    /// Answers `num_requests` requests with `status` and returns them.
+    async fn mock_server(
+        status: &'static str,
+        num_requests: usize,
+    ) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut requests = vec![];
+            for _ in 0..num_requests {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![];
+                let mut buf = [0; 4096];
+                // Read until the whole body named by content-length has arrived
+                loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                    let text = String::from_utf8_lossy(&request).to_string();
+                    if let Some((head, body)) = text.split_once("\r\n\r\n") {
+                        let content_length = head
+                            .lines()
+                            .find_map(|line| {
+                                line.to_lowercase()
+                                    .strip_prefix("content-length: ")
+                                    .map(|v| v.parse::<usize>().unwrap())
+                            })
+                            .unwrap_or(0);
+                        if body.len() >= content_length {
+                            break;
+                        }
+                    }
+                }
+                stream
+                    .write_all(
+                        format!(
+                            "HTTP/1.1 {}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                            status
+                        )
+                        .as_bytes(),
+                    )
+                    .await
+                    .unwrap();
+                requests.push(String::from_utf8(request).unwrap());
+            }
+            requests
+        });
+        (url, server)
+    }
+
+    #[tokio::test]
+    async fn test_delivers_signed_cloud_event() {
+        let (url, server) = mock_server("200 OK", 1).await;
+        let sink = WebhookSink::new(WebhookConfig {
+            signing_secret: Some("secret".to_string()),
+            ..config(url)
+        })
+        .unwrap();
+        let dead_letters = sink.deliver(&[event()]).await.unwrap();
+        assert!(dead_letters.is_empty());
+
+        let request = server.await.unwrap().remove(0);
+        let (head, body) = request.split_once("\r\n\r\n").unwrap();
+        let head = head.to_lowercase();
+        assert!(head.starts_with("post /hook http/1.1"));
+        assert!(head.contains("content-type: application/cloudevents+json"));
+        assert!(head.contains(&format!(
+            "x-signature-256: sha256={}",
+            hex::encode(hmac_sha256(b"secret", body.as_bytes()))
+        )));
+        let cloud_event: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(
+            cloud_event,
+            serde_json::json!({
+                "specversion": "1.0",
+                "id": "100-2",
+                "source": "aptos-indexer-processors/events_processor",
+                "type": "0xcafe::poke::Poke",
+                "subject": "0xcafe",
+                "datacontenttype": "application/json",
+                "aptostransactionversion": 100,
+                "aptoseventindex": 2,
+                "data": {"user": "0x1", "times": "3"},
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dead_letters_after_retries() {
+        // The first attempt and one retry
+        let (url, server) = mock_server("500 Internal Server Error", 2).await;
+        let sink = WebhookSink::new(config(url.clone())).unwrap();
+        let dead_letters = sink.deliver(&[event()]).await.unwrap();
+        assert_eq!(server.await.unwrap().len(), 2);
+        assert_eq!(dead_letters.len(), 1);
+        assert_eq!(dead_letters[0].transaction_version, 100);
+        assert_eq!(dead_letters[0].event_index, 2);
+        assert_eq!(dead_letters[0].url, url);
+        assert_eq!(dead_letters[0].cloud_event["id"], "100-2");
+    }
+
+    #[tokio::test]
+    async fn test_skips_other_event_types() {
+        let sink = WebhookSink::new(WebhookConfig {
+            event_types: vec!["0x1::coin::CoinDeposit".to_string()],
+            // Nothing listens here, so sending anything would dead-letter it
+            ..config("http://127.0.0.1:1/hook".to_string())
+        })
+        .unwrap();
+        assert!(sink.deliver(&[event()]).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! With `write_ahead_log`, batches are written strictly in version order, and the batch being
+//! written is recorded before its first row goes to the DB. Progress in `processor_status` is
+//! committed lazily, so on its own a restart resumes from some version at or before the last
+//! batch written, reprocessing whatever came after. With the log, the restart resumes exactly
+//! where writing stopped: a batch still pending when the process died is written again from its
+//! start, completing it, and a batch that was written is never written again. Since batches are
+//! written one after another, everything before the logged batch is always written.
+
+use crate::utils::database::{processor_status_key, ArcDbPool};
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use diesel::{
+    sql_query,
+    sql_types::{BigInt, Text},
+    QueryableByName,
+};
+use diesel_async::RunQueryDsl;
+use std::sync::Mutex;
+use tokio::sync::watch;
+use tracing::info;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WalState {
+    /// Intent to write the batch, which may be partially written
+    Pending,
+    Written,
+}
+
+impl WalState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WalState::Pending => "pending",
+            WalState::Written => "written",
+        }
+    }
+
+    fn parse(state: &str) -> Result<Self> {
+        match state {
+            "pending" => Ok(WalState::Pending),
+            "written" => Ok(WalState::Written),
+            _ => bail!("Unknown write-ahead log state {}", state),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WalEntry {
+    pub start_version: u64,
+    pub end_version: u64,
+    pub state: WalState,
+}
+
+impl WalEntry {
+    /// Where processing resumes after a restart: at the start of a batch that may be partially
+    /// written, after one that was written.
+    pub fn resume_version(&self) -> u64 {
+        match self.state {
+            WalState::Pending => self.start_version,
+            WalState::Written => self.end_version + 1,
+        }
+    }
+}
+
+/// Where the log is kept, one entry per processor.
+#[async_trait]
+pub trait WalStore: Send + Sync {
+    async fn read(&self) -> Result<Option<WalEntry>>;
+
+    async fn write(&self, entry: WalEntry) -> Result<()>;
+}
+
+#[derive(QueryableByName)]
+struct WalRow {
+    #[diesel(sql_type = BigInt)]
+    start_version: i64,
+    #[diesel(sql_type = BigInt)]
+    end_version: i64,
+    #[diesel(sql_type = Text)]
+    state: String,
+}
+
+/// Keeps the log in the `processor_write_ahead_log` table of the processor's own DB.
+pub struct PostgresWalStore {
+    pool: ArcDbPool,
+    processor: String,
+}
+
+impl PostgresWalStore {
+    pub fn new(pool: ArcDbPool, processor_name: &str) -> Self {
+        Self {
+            pool,
+            processor: processor_status_key(processor_name),
+        }
+    }
+}
+
+#[async_trait]
+impl WalStore for PostgresWalStore {
+    async fn read(&self) -> Result<Option<WalEntry>> {
+        let conn = &mut self.pool.get().await?;
+        let mut rows: Vec<WalRow> = sql_query(
+            "SELECT start_version, end_version, state FROM processor_write_ahead_log \
+             WHERE processor = $1",
+        )
+        .bind::<Text, _>(&self.processor)
+        .get_results(conn)
+        .await?;
+        rows.pop()
+            .map(|row| {
+                Ok(WalEntry {
+                    start_version: row.start_version as u64,
+                    end_version: row.end_version as u64,
+                    state: WalState::parse(&row.state)?,
+                })
+            })
+            .transpose()
+    }
+
+    async fn write(&self, entry: WalEntry) -> Result<()> {
+        let conn = &mut self.pool.get().await?;
+        sql_query(
+            "INSERT INTO processor_write_ahead_log (processor, start_version, end_version, state) \
+             VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (processor) DO UPDATE SET \
+               start_version = EXCLUDED.start_version, \
+               end_version = EXCLUDED.end_version, \
+               state = EXCLUDED.state, \
+               last_updated = NOW()",
+        )
+        .bind::<Text, _>(&self.processor)
+        .bind::<BigInt, _>(entry.start_version as i64)
+        .bind::<BigInt, _>(entry.end_version as i64)
+        .bind::<Text, _>(entry.state.as_str())
+        .execute(conn)
+        .await?;
+        Ok(())
+    }
+}
+
+/// Orders the batch writes of one processor's tasks and logs each one, see the module docs.
+pub struct WriteAheadLog {
+    store: Box<dyn WalStore>,
+    // Start version of the next batch to write
+    next_version: watch::Sender<u64>,
+    // The batch being written, to check `complete` against
+    pending: Mutex<Option<(u64, u64)>>,
+}
+
+impl WriteAheadLog {
+    pub fn new(store: Box<dyn WalStore>, starting_version: u64) -> Self {
+        Self {
+            store,
+            next_version: watch::Sender::new(starting_version),
+            pending: Mutex::new(None),
+        }
+    }
+
+    /// The version to resume from, if the log has an entry. Called before anything is processed.
+    pub async fn recover(store: &dyn WalStore, processor_name: &str) -> Result<Option<u64>> {
+        let Some(entry) = store.read().await? else {
+            return Ok(None);
+        };
+        let resume_version = entry.resume_version();
+        match entry.state {
+            WalState::Pending => info!(
+                processor_name,
+                start_version = entry.start_version,
+                end_version = entry.end_version,
+                "[Parser] Batch was still being written, writing it again from its start"
+            ),
+            WalState::Written => info!(
+                processor_name,
+                start_version = entry.start_version,
+                end_version = entry.end_version,
+                "[Parser] Resuming after the last batch written"
+            ),
+        }
+        Ok(Some(resume_version))
+    }
+
+    /// Waits until every batch before `start_version` is written, then logs the intent to write
+    /// this one.
+    pub async fn begin(&self, start_version: u64, end_version: u64) -> Result<()> {
+        let mut next_version = self.next_version.subscribe();
+        // The sender lives as long as self, so this only returns once it's our turn
+        let _ = next_version
+            .wait_for(|next_version| *next_version == start_version)
+            .await;
+        self.store
+            .write(WalEntry {
+                start_version,
+                end_version,
+                state: WalState::Pending,
+            })
+            .await?;
+        *self.pending.lock().unwrap() = Some((start_version, end_version));
+        Ok(())
+    }
+
+    /// Logs the batch as written and lets the next one start.
+    pub async fn complete(&self, start_version: u64, end_version: u64) -> Result<()> {
+        if *self.pending.lock().unwrap() != Some((start_version, end_version)) {
+            bail!(
+                "Completing batch [{}, {}] that wasn't begun",
+                start_version,
+                end_version
+            );
+        }
+        self.store
+            .write(WalEntry {
+                start_version,
+                end_version,
+                state: WalState::Written,
+            })
+            .await?;
+        *self.pending.lock().unwrap() = None;
+        self.next_version.send_replace(end_version + 1);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Arc, time::Duration};
+
+    #[derive(Clone, Default)]
+    struct InMemoryWalStore {
+        entry: Arc<Mutex<Option<WalEntry>>>,
+        // Every entry written, in order
+        history: Arc<Mutex<Vec<WalEntry>>>,
+    }
+
+    #[async_trait]
+    impl WalStore for InMemoryWalStore {
+        async fn read(&self) -> Result<Option<WalEntry>> {
+            Ok(*self.entry.lock().unwrap())
+        }
+
+        async fn write(&self, entry: WalEntry) -> Result<()> {
+            *self.entry.lock().unwrap() = Some(entry);
+            self.history.lock().unwrap().push(entry);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_written_in_order() {
+        let store = InMemoryWalStore::default();
+        let wal = Arc::new(WriteAheadLog::new(Box::new(store.clone()), 0));
+
+        // The second batch is picked up first, but has to wait for the first
+        let second = tokio::spawn({
+            let wal = wal.clone();
+            async move {
+                wal.begin(10, 19).await.unwrap();
+                wal.complete(10, 19).await.unwrap();
+            }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(store.history.lock().unwrap().is_empty());
+        wal.begin(0, 9).await.unwrap();
+        wal.complete(0, 9).await.unwrap();
+        second.await.unwrap();
+
+        let history = store
+            .history
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|entry| (entry.start_version, entry.state))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            history,
+            vec![
+                (0, WalState::Pending),
+                (0, WalState::Written),
+                (10, WalState::Pending),
+                (10, WalState::Written),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recover_after_crash() {
+        let store = InMemoryWalStore::default();
+        assert_eq!(WriteAheadLog::recover(&store, "test").await.unwrap(), None);
+
+        let wal = WriteAheadLog::new(Box::new(store.clone()), 0);
+        wal.begin(0, 9).await.unwrap();
+        wal.complete(0, 9).await.unwrap();
+        wal.begin(10, 19).await.unwrap();
+        // Crashes partway through writing [10, 19], so it's written again from its start
+        drop(wal);
+        assert_eq!(
+            WriteAheadLog::recover(&store, "test").await.unwrap(),
+            Some(10)
+        );
+
+        let wal = WriteAheadLog::new(Box::new(store.clone()), 10);
+        wal.begin(10, 19).await.unwrap();
+        wal.complete(10, 19).await.unwrap();
+        // Crashes after writing [10, 19] but before committing progress, so it isn't rewritten
+        drop(wal);
+        assert_eq!(
+            WriteAheadLog::recover(&store, "test").await.unwrap(),
+            Some(20)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_complete_without_begin_fails() {
+        let wal = WriteAheadLog::new(Box::new(InMemoryWalStore::default()), 0);
+        assert!(wal.complete(0, 9).await.is_err());
+    }
+}
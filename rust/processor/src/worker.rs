@@ -2,19 +2,32 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use crate::{
+    bq_analytics::gcs_handler::{get_resume_version_from_gcs, ParquetResumeConfig},
+    column_backfill::ColumnBackfillTarget,
     config::IndexerGrpcHttp2Config,
     db::postgres::models::{ledger_info::LedgerInfo, processor_status::ProcessorStatusQuery},
     gap_detectors::{
         create_gap_detector_status_tracker_loop, gap_detector::DefaultGapDetector,
-        parquet_gap_detector::ParquetFileGapDetectorInner, GapDetector, ProcessingResult,
+        load_default_gap_detector, parquet_gap_detector::ParquetFileGapDetectorInner, GapDetector,
+        ProcessingResult, ProgressCommitter,
     },
     grpc_stream::TransactionsPBResponse,
+    multiplexer::{create_multiplexer_loop, take_batch_from_version, MultiplexedProcessor},
+    parallel_fetch::ParallelFetchConfig,
+    parquet_file_stream::ParquetFileSourceConfig,
     processors::{
+        account_sequence_number_processor::AccountSequenceNumberProcessor,
         account_transactions_processor::AccountTransactionsProcessor,
+        allowance_processor::AllowanceProcessor,
         ans_processor::AnsProcessor,
+        block_gas_stats_processor::BlockGasStatsProcessor,
+        custom_processor::{CustomProcessor, CustomProcessorArgs},
         default_processor::DefaultProcessor,
-        events_processor::EventsProcessor,
+        events_processor::{
+            indexed_type_backfill, partition_events_table, EventsProcessor, EventsProcessorConfig,
+        },
         fungible_asset_processor::FungibleAssetProcessor,
+        governance_processor::GovernanceProcessor,
         monitoring_processor::MonitoringProcessor,
         nft_metadata_processor::NftMetadataProcessor,
         objects_processor::ObjectsProcessor,
@@ -34,35 +47,69 @@ use crate::{
         user_transaction_processor::UserTransactionProcessor,
         DefaultProcessingResult, Processor, ProcessorConfig, ProcessorTrait,
     },
-    schema::ledger_infos,
+    replay::{ReplayQueue, ReplayStream},
+    schema::{events, ledger_infos},
     transaction_filter::TransactionFilter,
     utils::{
+        authentication_key_check,
+        block_height::BlockHeightTracker,
+        channel_byte_limiter::ChannelByteLimiter,
         counters::{
-            ProcessorStep, GRPC_LATENCY_BY_PROCESSOR_IN_SECS, LATEST_PROCESSED_VERSION,
-            NUM_TRANSACTIONS_PROCESSED_COUNT, PB_CHANNEL_FETCH_WAIT_TIME_SECS,
+            chain_id_label, record_slow_batch_exemplar, set_chain_id_label, ProcessorStep,
+            BATCH_PROCESSING_TIMEOUT_COUNT, GRPC_LATENCY_BY_PROCESSOR_IN_SECS,
+            LATEST_PROCESSED_VERSION, NUM_EVENTS_PROCESSED_COUNT, NUM_TRANSACTIONS_PROCESSED_COUNT,
+            NUM_WRITE_SET_CHANGES_PROCESSED_COUNT, PB_CHANNEL_FETCH_WAIT_TIME_SECS,
             PROCESSED_BYTES_COUNT, PROCESSOR_DATA_PROCESSED_LATENCY_IN_SECS,
             PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS, PROCESSOR_ERRORS_COUNT,
             PROCESSOR_INVOCATIONS_COUNT, PROCESSOR_SUCCESSES_COUNT,
             SINGLE_BATCH_DB_INSERTION_TIME_IN_SECS, SINGLE_BATCH_PARSING_TIME_IN_SECS,
             SINGLE_BATCH_PROCESSING_TIME_IN_SECS, TRANSACTION_UNIX_TIMESTAMP,
         },
+        coverage::CoverageQuery,
         database::{
-            execute_with_better_error_conn, new_db_pool, run_pending_migrations, ArcDbPool,
+            create_prefixed_tables, execute_with_better_error_conn, new_db_pool,
+            new_read_only_db_pool, rows_written_by_table, run_pending_migrations_with_status,
+            ArcDbPool, ConflictStrategy, DbConnectionConfig, MigrationStatus,
         },
+        error_budget::{ErrorBudget, ErrorBudgetConfig},
+        heartbeat::{run_heartbeat, StreamTip},
+        in_flight_versions::InFlightVersions,
+        live_status::{is_tail_enabled, LiveProcessorStatus, TpsReportingConfig},
+        processing_byte_budget::ProcessingByteBudget,
+        progress_lease::{LeasedProgressStorage, PostgresLeaseStore, ProgressLeaseConfig},
+        progress_report::ProgressReport,
+        rayon_pool::RayonPool,
+        retention::{run_pruning, RetentionConfig},
+        schema_drift::check_schema_drift,
+        schema_version::check_schema_version,
+        shutdown,
         table_flags::TableFlags,
-        util::{time_diff_since_pb_timestamp_in_secs, timestamp_to_iso, timestamp_to_unixtime},
+        tip_lag::TipLagThrottle,
+        transaction_fields::TransactionFields,
+        transaction_tee::transaction_tee,
+        util::{
+            time_diff_since_pb_timestamp_in_secs, timestamp_to_iso, timestamp_to_unixtime,
+            transaction_timestamp,
+        },
+        write_ahead_log::{PostgresWalStore, WriteAheadLog},
     },
 };
 use ahash::AHashMap;
-use anyhow::{Context, Result};
-use aptos_moving_average::MovingAverage;
+use anyhow::{bail, Context, Result};
+use aptos_protos::{transaction::v1::Transaction, util::timestamp::Timestamp};
+use google_cloud_storage::client::{Client as GCSClient, ClientConfig as GcsClientConfig};
 use kanal::AsyncSender;
+use prometheus::GaugeVec;
+use serde::{Deserialize, Serialize};
 use std::{
     collections::HashSet,
+    future::Future,
+    path::PathBuf,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 use tokio::task::JoinHandle;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, info_span, warn, Instrument};
 use url::Url;
 
 // this is how large the fetch queue should be. Each bucket should have a max of 80MB or so, so a batch
@@ -72,8 +119,75 @@ use url::Url;
 pub const BUFFER_SIZE: usize = 300;
 pub const PROCESSOR_SERVICE_TYPE: &str = "processor";
 
+/// What to do when the chain id from the stream doesn't match the one already in the DB, e.g.
+/// when a DB with testnet data is pointed at mainnet by mistake.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnChainMismatch {
+    #[default]
+    Panic,
+    /// Log the mismatch and stop the processor with a successful exit code
+    Halt,
+    /// Return a `ChainIdMismatchError` from `Worker::run`
+    Error,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ChainIdMismatchError {
+    pub existing_chain_id: i64,
+    pub grpc_chain_id: i64,
+}
+
+impl std::error::Error for ChainIdMismatchError {}
+
+impl std::fmt::Display for ChainIdMismatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[Parser] Wrong chain detected! Trying to index chain {} now but existing data is for chain {}",
+            self.grpc_chain_id, self.existing_chain_id
+        )
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum ChainIdCheck {
+    Matches,
+    NotStored,
+    Halt,
+}
+
+/// Compares the chain id from the stream with the one in `ledger_infos`, applying
+/// `on_chain_mismatch` if they differ.
+fn check_chain_id(
+    existing_chain_id: Option<i64>,
+    grpc_chain_id: i64,
+    on_chain_mismatch: OnChainMismatch,
+) -> std::result::Result<ChainIdCheck, ChainIdMismatchError> {
+    let existing_chain_id = match existing_chain_id {
+        Some(chain_id) if chain_id == grpc_chain_id => return Ok(ChainIdCheck::Matches),
+        Some(chain_id) => chain_id,
+        None => return Ok(ChainIdCheck::NotStored),
+    };
+    let mismatch = ChainIdMismatchError {
+        existing_chain_id,
+        grpc_chain_id,
+    };
+    match on_chain_mismatch {
+        OnChainMismatch::Panic => panic!("{}", mismatch),
+        OnChainMismatch::Halt => {
+            error!("{}. Halting since on_chain_mismatch is halt", mismatch);
+            Ok(ChainIdCheck::Halt)
+        },
+        OnChainMismatch::Error => Err(mismatch),
+    }
+}
+
+#[derive(Clone)]
 pub struct Worker {
     pub db_pool: ArcDbPool,
+    // Same as db_pool unless a read replica is configured
+    pub read_only_db_pool: ArcDbPool,
     pub processor_config: ProcessorConfig,
     pub postgres_connection_string: String,
     pub indexer_grpc_data_service_address: Url,
@@ -84,13 +198,50 @@ pub struct Worker {
     pub number_concurrent_processing_tasks: usize,
     pub gap_detection_batch_size: u64,
     pub parquet_gap_detection_batch_size: u64,
+    pub persist_gap_detector_state: bool,
+    pub progress_commit_interval_secs: u64,
+    pub progress_commit_interval_versions: Option<u64>,
     pub grpc_chain_id: Option<u64>,
     pub pb_channel_txn_chunk_size: usize,
     pub per_table_chunk_sizes: AHashMap<String, usize>,
+    pub per_table_conflict_strategies: AHashMap<String, ConflictStrategy>,
     pub enable_verbose_logging: Option<bool>,
     pub transaction_filter: TransactionFilter,
     pub grpc_response_item_timeout_in_secs: u64,
     pub deprecated_tables: TableFlags,
+    pub metrics_sample_rate: u64,
+    pub max_buffered_transaction_bytes: Option<u64>,
+    pub parquet_file_source: Option<ParquetFileSourceConfig>,
+    pub on_chain_mismatch: OnChainMismatch,
+    // Other processors to run off the same stream, see `multiplexer`
+    pub multiplexed_processor_configs: Vec<ProcessorConfig>,
+    pub multiplexed_buffer_size: usize,
+    // Resume a Parquet backfill from the files already in GCS
+    pub parquet_resume: Option<ParquetResumeConfig>,
+    pub tps_reporting: TpsReportingConfig,
+    pub heartbeat_interval_secs: Option<u64>,
+    pub max_in_flight_processing_bytes: Option<u64>,
+    pub progress_lease: Option<ProgressLeaseConfig>,
+    pub strip_unused_transaction_fields: bool,
+    pub slow_batch_exemplar_threshold_secs: Option<f64>,
+    pub enable_replays: bool,
+    pub progress_report_path: Option<PathBuf>,
+    pub compute_block_heights: bool,
+    pub check_authentication_keys: bool,
+    pub retention: Option<RetentionConfig>,
+    pub write_ahead_log: bool,
+    pub batch_processing_timeout_secs: Option<u64>,
+    pub tip_lag: Option<TipLagThrottle>,
+    pub parallel_fetch: Option<ParallelFetchConfig>,
+    pub error_budget: Option<Arc<ErrorBudget>>,
+    pub column_backfill: Option<ColumnBackfillTarget>,
+    pub rayon_num_threads: Option<usize>,
+    // Pool the processor parses on. Multiplexed processors each get one of their own
+    pub rayon_pool: RayonPool,
+    // Served on `/migrations` while they run
+    pub migration_status: Arc<Mutex<MigrationStatus>>,
+    // Set in `run` once the lease is acquired, if `progress_lease` is configured
+    pub leased_progress: Option<Arc<LeasedProgressStorage>>,
 }
 
 impl Worker {
@@ -98,6 +249,7 @@ impl Worker {
     pub async fn new(
         processor_config: ProcessorConfig,
         postgres_connection_string: String,
+        read_replica_connection_string: Option<String>,
         indexer_grpc_data_service_address: Url,
         grpc_http2_config: IndexerGrpcHttp2Config,
         auth_token: String,
@@ -107,13 +259,43 @@ impl Worker {
         db_pool_size: Option<u32>,
         gap_detection_batch_size: u64,
         parquet_gap_detection_batch_size: u64,
+        persist_gap_detector_state: bool,
+        progress_commit_interval_secs: u64,
+        progress_commit_interval_versions: Option<u64>,
         // The number of transactions per protobuf batch
         pb_channel_txn_chunk_size: usize,
         per_table_chunk_sizes: AHashMap<String, usize>,
+        per_table_conflict_strategies: AHashMap<String, ConflictStrategy>,
         enable_verbose_logging: Option<bool>,
         transaction_filter: TransactionFilter,
         grpc_response_item_timeout_in_secs: u64,
         deprecated_tables: HashSet<String>,
+        metrics_sample_rate: u64,
+        max_buffered_transaction_bytes: Option<u64>,
+        parquet_file_source: Option<ParquetFileSourceConfig>,
+        on_chain_mismatch: OnChainMismatch,
+        multiplexed_processor_configs: Vec<ProcessorConfig>,
+        multiplexed_buffer_size: usize,
+        parquet_resume: Option<ParquetResumeConfig>,
+        tps_reporting: TpsReportingConfig,
+        heartbeat_interval_secs: Option<u64>,
+        max_in_flight_processing_bytes: Option<u64>,
+        progress_lease: Option<ProgressLeaseConfig>,
+        strip_unused_transaction_fields: bool,
+        slow_batch_exemplar_threshold_secs: Option<f64>,
+        enable_replays: bool,
+        db_connection: DbConnectionConfig,
+        progress_report_path: Option<PathBuf>,
+        compute_block_heights: bool,
+        check_authentication_keys: bool,
+        retention: Option<RetentionConfig>,
+        write_ahead_log: bool,
+        batch_processing_timeout_secs: Option<u64>,
+        tip_lag: Option<TipLagThrottle>,
+        parallel_fetch: Option<ParallelFetchConfig>,
+        error_budget: Option<ErrorBudgetConfig>,
+        column_backfill: Option<ColumnBackfillTarget>,
+        rayon_num_threads: Option<usize>,
     ) -> Result<Self> {
         let processor_name = processor_config.name();
         info!(processor_name = processor_name, "[Parser] Kicking off");
@@ -123,9 +305,17 @@ impl Worker {
             service_type = PROCESSOR_SERVICE_TYPE,
             "[Parser] Creating connection pool"
         );
-        let conn_pool = new_db_pool(&postgres_connection_string, db_pool_size)
+        let conn_pool = new_db_pool(&postgres_connection_string, db_pool_size, &db_connection)
             .await
             .context("Failed to create connection pool")?;
+        let read_only_conn_pool = new_read_only_db_pool(
+            &conn_pool,
+            read_replica_connection_string.as_deref(),
+            db_pool_size,
+            &db_connection,
+        )
+        .await
+        .context("Failed to create read replica connection pool")?;
         info!(
             processor_name = processor_name,
             service_type = PROCESSOR_SERVICE_TYPE,
@@ -142,6 +332,7 @@ impl Worker {
 
         Ok(Self {
             db_pool: conn_pool,
+            read_only_db_pool: read_only_conn_pool,
             processor_config,
             postgres_connection_string,
             indexer_grpc_data_service_address,
@@ -152,13 +343,48 @@ impl Worker {
             number_concurrent_processing_tasks,
             gap_detection_batch_size,
             parquet_gap_detection_batch_size,
+            persist_gap_detector_state,
+            progress_commit_interval_secs,
+            progress_commit_interval_versions,
             grpc_chain_id: None,
             pb_channel_txn_chunk_size,
             per_table_chunk_sizes,
+            per_table_conflict_strategies,
             enable_verbose_logging,
             transaction_filter,
             grpc_response_item_timeout_in_secs,
             deprecated_tables: deprecated_tables_flags,
+            // A rate of 0 would never sample, so treat it as "every batch"
+            metrics_sample_rate: metrics_sample_rate.max(1),
+            max_buffered_transaction_bytes,
+            parquet_file_source,
+            on_chain_mismatch,
+            multiplexed_processor_configs,
+            multiplexed_buffer_size,
+            parquet_resume,
+            tps_reporting,
+            heartbeat_interval_secs,
+            max_in_flight_processing_bytes,
+            progress_lease,
+            strip_unused_transaction_fields,
+            slow_batch_exemplar_threshold_secs,
+            enable_replays,
+            progress_report_path,
+            compute_block_heights,
+            check_authentication_keys,
+            retention,
+            write_ahead_log,
+            batch_processing_timeout_secs,
+            tip_lag,
+            parallel_fetch,
+            error_budget: error_budget
+                .as_ref()
+                .map(|config| Arc::new(ErrorBudget::new(config))),
+            column_backfill,
+            rayon_num_threads,
+            rayon_pool: RayonPool::new(rayon_num_threads)?,
+            migration_status: Arc::default(),
+            leased_progress: None,
         })
     }
 
@@ -168,13 +394,29 @@ impl Worker {
     /// 3. Start a loop to consume from the buffer. We will have Y threads to process the transactions in parallel. (Y should be less than X for obvious reasons)
     ///   * Note that the batches will be sequential so we won't have problems with gaps
     /// 4. We will keep track of the last processed version and monitoring things like TPS
-    pub async fn run(&mut self) {
+    ///
+    /// With `multiplexed_processor_configs`, 3 and 4 happen once per processor, all fed by the
+    /// same fetcher through the `multiplexer`.
+    ///
+    /// Returns early on a chain id mismatch, depending on `on_chain_mismatch`.
+    pub async fn run(&mut self) -> Result<()> {
         let processor_name = self.processor_config.name();
         info!(
             processor_name = processor_name,
             service_type = PROCESSOR_SERVICE_TYPE,
             "[Parser] Running migrations"
         );
+        let migration_status = self.migration_status.clone();
+        server_framework::register_migrations_provider(move || {
+            let mut migrations = serde_json::json!({ "processor_name": processor_name });
+            if let Ok(serde_json::Value::Object(status)) =
+                serde_json::to_value(&*migration_status.lock().unwrap())
+            {
+                migrations.as_object_mut().unwrap().extend(status);
+            }
+            migrations
+        });
+        let run_start_time = std::time::Instant::now();
         let migration_time = std::time::Instant::now();
         self.run_migrations().await;
         info!(
@@ -183,49 +425,129 @@ impl Worker {
             duration_in_secs = migration_time.elapsed().as_secs_f64(),
             "[Parser] Finished migrations"
         );
-
-        let starting_version_from_db = self
-            .get_start_version()
+        // Scans whole tables, so it reads from the replica if there's one
+        server_framework::register_coverage_handler(Arc::new(CoverageQuery {
+            processor_name,
+            pool: self.read_only_db_pool.clone(),
+        }));
+        let processor_configs: Vec<ProcessorConfig> =
+            std::iter::once(self.processor_config.clone())
+                .chain(self.multiplexed_processor_configs.iter().cloned())
+                .collect();
+        // As read from the DB before running the pending ones, plus those that ran
+        let applied_migrations = self.migration_status.lock().unwrap().applied.clone();
+        for processor_config in &processor_configs {
+            check_schema_version(
+                self.db_pool.clone(),
+                processor_config.name(),
+                processor_config.schema_version(),
+                &applied_migrations,
+            )
             .await
-            .expect("[Parser] Database error when getting starting version")
-            .unwrap_or_else(|| {
-                info!(
-                    processor_name = processor_name,
-                    service_type = PROCESSOR_SERVICE_TYPE,
-                    "[Parser] No starting version from db so starting from version 0"
-                );
-                0
-            });
+            .context("[Parser] The processor's schema version doesn't match the DB")?;
+        }
+        if processor_configs.iter().any(|processor_config| {
+            matches!(
+                processor_config,
+                ProcessorConfig::EventsProcessor(EventsProcessorConfig {
+                    partition_interval: Some(_),
+                    ..
+                })
+            )
+        }) {
+            partition_events_table(self.db_pool.clone()).await?;
+        }
+        create_prefixed_tables(self.db_pool.clone())
+            .await
+            .expect("[Parser] Failed to create the prefixed tables");
+        if processor_configs
+            .iter()
+            .any(|processor_config| matches!(processor_config, ProcessorConfig::EventsProcessor(_)))
+        {
+            check_schema_drift::<events::table>(self.db_pool.clone())
+                .await
+                .context("[Parser] The events table doesn't match the events model")?;
+        }
 
-        let starting_version = self.starting_version.unwrap_or(starting_version_from_db);
+        // get the chain id
+        let chain_id = match &self.parquet_file_source {
+            Some(parquet_file_source) => parquet_file_source.chain_id,
+            None => {
+                crate::grpc_stream::get_chain_id(
+                    self.indexer_grpc_data_service_address.clone(),
+                    self.grpc_http2_config.grpc_http2_ping_interval_in_secs(),
+                    self.grpc_http2_config.grpc_http2_ping_timeout_in_secs(),
+                    self.grpc_http2_config.grpc_connection_timeout_secs(),
+                    self.auth_token.clone(),
+                    processor_name.to_string(),
+                )
+                .await
+            },
+        };
+        let checked_chain_id = self.check_or_update_chain_id(chain_id as i64).await?;
+        if checked_chain_id.is_none() {
+            return Ok(());
+        }
 
-        info!(
-            processor_name = processor_name,
-            service_type = PROCESSOR_SERVICE_TYPE,
-            stream_address = self.indexer_grpc_data_service_address.to_string(),
-            final_start_version = starting_version,
-            start_version_from_config = self.starting_version,
-            start_version_from_db = starting_version_from_db,
-            "[Parser] Building processor",
-        );
+        self.grpc_chain_id = Some(chain_id);
+        set_chain_id_label(chain_id);
 
-        let concurrent_tasks = self.number_concurrent_processing_tasks;
+        if let Some(column_backfill) = self.column_backfill {
+            return self.run_column_backfill(column_backfill).await;
+        }
 
-        // get the chain id
-        let chain_id = crate::grpc_stream::get_chain_id(
-            self.indexer_grpc_data_service_address.clone(),
-            self.grpc_http2_config.grpc_http2_ping_interval_in_secs(),
-            self.grpc_http2_config.grpc_http2_ping_timeout_in_secs(),
-            self.grpc_http2_config.grpc_connection_timeout_secs(),
-            self.auth_token.clone(),
-            processor_name.to_string(),
-        )
-        .await;
-        self.check_or_update_chain_id(chain_id as i64)
-            .await
-            .unwrap();
+        // `/replay` is served for one processor per process. Registered before anything is
+        // fetched, so another processor with replays fails to start instead of never replaying
+        let replay_queue = if self.enable_replays {
+            let replay_queue = Arc::new(ReplayQueue::default());
+            if !server_framework::register_replay_handler(replay_queue.clone()) {
+                bail!(
+                    "[Parser] /replay is already served by another processor in this process, \
+                     only one processor can set enable_replays"
+                );
+            }
+            Some(replay_queue)
+        } else {
+            None
+        };
 
-        self.grpc_chain_id = Some(chain_id);
+        // A standby waits here until the primary stops renewing the lease, and only then reads
+        // where to resume from
+        let mut lease_renewal_task = None;
+        if let Some(progress_lease) = &self.progress_lease {
+            let store = PostgresLeaseStore::new(self.db_pool.clone(), processor_name);
+            let leased_progress =
+                Arc::new(LeasedProgressStorage::new(Box::new(store), progress_lease));
+            leased_progress
+                .acquire()
+                .await
+                .context("Failed to acquire the progress lease")?;
+            self.leased_progress = Some(leased_progress.clone());
+            lease_renewal_task = Some(tokio::spawn(async move {
+                let e = leased_progress.run_renewal().await;
+                panic!("[Parser] Lost the progress lease: {:?}", e);
+            }));
+        }
+
+        // One pipeline per processor, each with its own progress. Without multiplexing this is
+        // just the configured processor
+        let mut pipelines = vec![];
+        for (index, processor_config) in processor_configs.into_iter().enumerate() {
+            let rayon_pool = if index == 0 {
+                self.rayon_pool.clone()
+            } else {
+                RayonPool::new(self.rayon_num_threads)?
+            };
+            let pipeline = self.for_processor(processor_config, rayon_pool);
+            let starting_version = pipeline.resolve_starting_version().await?;
+            pipelines.push((pipeline, starting_version));
+        }
+        // The stream has to start early enough for the processor that's furthest behind
+        let starting_version = pipelines
+            .iter()
+            .map(|(_, starting_version)| *starting_version)
+            .min()
+            .unwrap();
 
         let ending_version = self.ending_version;
         let indexer_grpc_data_service_address = self.indexer_grpc_data_service_address.clone();
@@ -236,16 +558,90 @@ impl Worker {
         let indexer_grpc_reconnection_timeout_secs =
             self.grpc_http2_config.grpc_connection_timeout_secs();
         let pb_channel_txn_chunk_size = self.pb_channel_txn_chunk_size;
+        let is_multiplexed = pipelines.len() > 1;
 
         // Create a transaction fetcher thread that will continuously fetch transactions from the GRPC stream
         // and write into a channel
         // TODO: change channel size based on number_concurrent_processing_tasks
-        let (tx, receiver) = kanal::bounded_async::<TransactionsPBResponse>(BUFFER_SIZE);
+        let (tx, receiver) = kanal::bounded_async::<Arc<TransactionsPBResponse>>(BUFFER_SIZE);
+        let channel_byte_limiter = Arc::new(ChannelByteLimiter::new(
+            processor_name.to_string(),
+            self.max_buffered_transaction_bytes,
+        ));
+        let fetcher_channel_byte_limiter = channel_byte_limiter.clone();
+        // Shared by all the processors' tasks, since they share the process's memory
+        let processing_byte_budget = Arc::new(ProcessingByteBudget::new(
+            processor_name.to_string(),
+            self.max_in_flight_processing_bytes,
+        ));
+        let in_flight_versions: Vec<Arc<InFlightVersions>> = pipelines
+            .iter()
+            .map(|(pipeline, _)| {
+                Arc::new(InFlightVersions::new(
+                    pipeline.processor_config.name().to_string(),
+                ))
+            })
+            .collect();
+        // When multiplexing, the multiplexer records versions for each processor instead
+        let fetcher_in_flight_versions = if is_multiplexed {
+            None
+        } else {
+            Some(in_flight_versions[0].clone())
+        };
+        let live_statuses: Vec<Arc<LiveProcessorStatus>> = pipelines
+            .iter()
+            .map(|(pipeline, _)| {
+                Arc::new(LiveProcessorStatus::new(
+                    pipeline.processor_config.name(),
+                    &self.tps_reporting,
+                ))
+            })
+            .collect();
+        let live_statuses_clone = live_statuses.clone();
+        server_framework::register_status_provider(move || {
+            match live_statuses_clone.as_slice() {
+                [live_status] => serde_json::to_value(live_status.snapshot()),
+                live_statuses => serde_json::to_value(
+                    live_statuses
+                        .iter()
+                        .map(|live_status| live_status.snapshot())
+                        .collect::<Vec<_>>(),
+                ),
+            }
+            .unwrap_or_default()
+        });
         let request_ending_version = self.ending_version;
         let auth_token = self.auth_token.clone();
         let transaction_filter = self.transaction_filter.clone();
+        // The fetcher drops everything none of the processors read
+        let transaction_fields = if self.strip_unused_transaction_fields {
+            pipelines
+                .iter()
+                .fold(TransactionFields::empty(), |fields, (pipeline, _)| {
+                    fields | pipeline.processor_config.transaction_fields()
+                })
+        } else {
+            TransactionFields::all()
+        };
         let grpc_response_item_timeout =
             std::time::Duration::from_secs(self.grpc_response_item_timeout_in_secs);
+        let parquet_file_source = self.parquet_file_source.clone();
+        let tip_lag = self.tip_lag.clone();
+        let parallel_fetch = self.parallel_fetch.clone();
+        // The heartbeat watches the stream, so there's none when reading Parquet files
+        let stream_tip = match (self.heartbeat_interval_secs, &parquet_file_source) {
+            (Some(_), None) => Some(Arc::new(StreamTip::default())),
+            _ => None,
+        };
+        let heartbeat_task = stream_tip.clone().map(|stream_tip| {
+            let interval =
+                std::time::Duration::from_secs(self.heartbeat_interval_secs.unwrap().max(1));
+            tokio::spawn(async move { run_heartbeat(processor_name, interval, &stream_tip).await })
+        });
+        let pruning_task = self.retention.clone().map(|retention| {
+            tokio::spawn(run_pruning(processor_name, self.db_pool.clone(), retention))
+        });
+        let block_heights = self.compute_block_heights.then(BlockHeightTracker::default);
         let fetcher_task = tokio::spawn(async move {
             info!(
                 processor_name = processor_name,
@@ -255,22 +651,411 @@ impl Worker {
                 "[Parser] Starting fetcher thread"
             );
 
-            crate::grpc_stream::create_fetcher_loop(
-                tx.clone(),
-                indexer_grpc_data_service_address.clone(),
-                indexer_grpc_http2_ping_interval,
-                indexer_grpc_http2_ping_timeout,
-                indexer_grpc_reconnection_timeout_secs,
-                grpc_response_item_timeout,
+            match parquet_file_source {
+                Some(parquet_file_source) => {
+                    crate::parquet_file_stream::create_parquet_file_fetcher_loop(
+                        tx.clone(),
+                        parquet_file_source,
+                        starting_version,
+                        request_ending_version,
+                        processor_name.to_string(),
+                        transaction_filter,
+                        pb_channel_txn_chunk_size,
+                        fetcher_channel_byte_limiter,
+                        fetcher_in_flight_versions,
+                        block_heights,
+                    )
+                    .await
+                },
+                None if parallel_fetch.is_some() => {
+                    // Each chunk is fetched on a stream of its own, through the usual fetcher
+                    // loop. Block heights and the tee need the transactions in order, so they're
+                    // rejected with it by the config, and the merger records the stream tip
+                    let fetch_chunk =
+                        move |chunk_start, chunk_end, chunk_sender, chunk_byte_limiter| {
+                            crate::grpc_stream::create_fetcher_loop(
+                                chunk_sender,
+                                indexer_grpc_data_service_address.clone(),
+                                indexer_grpc_http2_ping_interval,
+                                indexer_grpc_http2_ping_timeout,
+                                indexer_grpc_reconnection_timeout_secs,
+                                grpc_response_item_timeout,
+                                chunk_start,
+                                Some(chunk_end),
+                                auth_token.clone(),
+                                processor_name.to_string(),
+                                transaction_filter.clone(),
+                                transaction_fields,
+                                pb_channel_txn_chunk_size,
+                                chunk_byte_limiter,
+                                fetcher_in_flight_versions.clone(),
+                                None,
+                                None,
+                                None,
+                                tip_lag.clone(),
+                            )
+                        };
+                    crate::parallel_fetch::create_parallel_fetcher_loop(
+                        tx.clone(),
+                        fetcher_channel_byte_limiter,
+                        processor_name.to_string(),
+                        starting_version,
+                        request_ending_version.unwrap(),
+                        parallel_fetch.unwrap(),
+                        stream_tip,
+                        fetch_chunk,
+                    )
+                    .await
+                },
+                None => {
+                    crate::grpc_stream::create_fetcher_loop(
+                        tx.clone(),
+                        indexer_grpc_data_service_address.clone(),
+                        indexer_grpc_http2_ping_interval,
+                        indexer_grpc_http2_ping_timeout,
+                        indexer_grpc_reconnection_timeout_secs,
+                        grpc_response_item_timeout,
+                        starting_version,
+                        request_ending_version,
+                        auth_token.clone(),
+                        processor_name.to_string(),
+                        transaction_filter,
+                        transaction_fields,
+                        pb_channel_txn_chunk_size,
+                        fetcher_channel_byte_limiter,
+                        fetcher_in_flight_versions,
+                        stream_tip,
+                        block_heights,
+                        transaction_tee(),
+                        tip_lag,
+                    )
+                    .await
+                },
+            }
+        });
+
+        let mut processor_tasks = vec![fetcher_task];
+        processor_tasks.extend(lease_renewal_task);
+        if let Some(replay_queue) = replay_queue {
+            processor_tasks.push(self.start_replays(replay_queue)?);
+        }
+        if is_multiplexed {
+            // Each processor reads from its own bounded buffer, see `multiplexer` for how they
+            // are kept from drifting too far apart
+            let mut multiplexed_processors = vec![];
+            for (((pipeline, starting_version), in_flight_versions), live_status) in pipelines
+                .iter()
+                .zip(in_flight_versions)
+                .zip(live_statuses.clone())
+            {
+                let (sender, buffer_receiver) = kanal::bounded_async::<Arc<TransactionsPBResponse>>(
+                    self.multiplexed_buffer_size,
+                );
+                processor_tasks.extend(
+                    pipeline
+                        .start_processor_pipeline(
+                            *starting_version,
+                            buffer_receiver,
+                            None,
+                            processing_byte_budget.clone(),
+                            in_flight_versions.clone(),
+                            live_status,
+                        )
+                        .await?,
+                );
+                multiplexed_processors.push(MultiplexedProcessor {
+                    processor_name: pipeline.processor_config.name(),
+                    starting_version: *starting_version,
+                    sender,
+                    in_flight_versions,
+                });
+            }
+            processor_tasks.push(tokio::spawn(create_multiplexer_loop(
+                receiver,
+                channel_byte_limiter,
+                multiplexed_processors,
+            )));
+        } else {
+            let (pipeline, starting_version) = &pipelines[0];
+            processor_tasks.extend(
+                pipeline
+                    .start_processor_pipeline(
+                        *starting_version,
+                        receiver,
+                        Some(channel_byte_limiter),
+                        processing_byte_budget,
+                        in_flight_versions[0].clone(),
+                        live_statuses[0].clone(),
+                    )
+                    .await?,
+            );
+        }
+
+        // Await the processor tasks: this is forever, unless there's an ending version or the
+        // process is told to shut down
+        tokio::spawn(shutdown::listen_for_signals());
+        let mut shutdown_signal = shutdown::shutdown_signal();
+        // The tasks nothing else awaits are watched too. With `on_server_failure: continue` a
+        // panic doesn't exit the process, and the processor would otherwise run on without them
+        let mut task_failed_signal = shutdown::task_failed_signal();
+        let mut background_tasks: Vec<&mut JoinHandle<()>> = heartbeat_task
+            .iter_mut()
+            .chain(pruning_task.iter_mut())
+            .collect();
+        let shut_down = tokio::select! {
+            result = futures::future::try_join_all(processor_tasks) => {
+                result.context("[Processor] Processor tasks have died")?;
+                false
+            },
+            e = first_task_failure(&mut background_tasks) => {
+                return Err(e).context("[Processor] Background task has died");
+            },
+            _ = task_failed_signal.triggered() => {
+                bail!("[Processor] Background task has died");
+            },
+            _ = shutdown_signal.triggered() => {
+                shutdown::wait_for_shutdown().await;
+                info!(
+                    processor_name = processor_name,
+                    service_type = PROCESSOR_SERVICE_TYPE,
+                    "[Parser] Shut down after flushing buffers",
+                );
+                true
+            },
+        };
+        if let Some(heartbeat_task) = heartbeat_task {
+            heartbeat_task.abort();
+        }
+        if let Some(pruning_task) = pruning_task {
+            pruning_task.abort();
+        }
+        if let Some(progress_report_path) = &self.progress_report_path {
+            let report = ProgressReport {
                 starting_version,
-                request_ending_version,
-                auth_token.clone(),
-                processor_name.to_string(),
-                transaction_filter,
-                pb_channel_txn_chunk_size,
+                ending_version: self.ending_version,
+                wall_time_secs: run_start_time.elapsed().as_secs_f64(),
+                processors: pipelines
+                    .iter()
+                    .zip(&live_statuses)
+                    .map(|((pipeline, starting_version), live_status)| {
+                        // Versions after the last one processed before shutting down weren't
+                        // skipped, the run just didn't get to them
+                        live_status.processed_ranges().report(
+                            pipeline.processor_config.name(),
+                            *starting_version,
+                            if shut_down { None } else { self.ending_version },
+                        )
+                    })
+                    .collect(),
+                rows_written: rows_written_by_table(),
+            };
+            report.write(progress_report_path)?;
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                progress_report_path = ?progress_report_path,
+                "[Parser] Wrote the progress report"
+            );
+        }
+        Ok(())
+    }
+
+    /// A copy of this worker running `processor_config` instead, for multiplexing.
+    fn for_processor(&self, processor_config: ProcessorConfig, rayon_pool: RayonPool) -> Self {
+        Self {
+            processor_config,
+            rayon_pool,
+            multiplexed_processor_configs: vec![],
+            // The tables to resume from are the main processor's
+            parquet_resume: None,
+            ..self.clone()
+        }
+    }
+
+    /// Runs the replays queued on `/replay` of the main processor with a processor and stream of
+    /// their own, see `replay`.
+    fn start_replays(&self, replay_queue: Arc<ReplayQueue>) -> Result<JoinHandle<()>> {
+        let processor = build_processor(
+            &self.processor_config,
+            self.per_table_chunk_sizes.clone(),
+            self.per_table_conflict_strategies.clone(),
+            self.deprecated_tables,
+            self.db_pool.clone(),
+            None,
+            0,
+            self.rayon_pool.clone(),
+        )
+        .context("Failed to build the replay processor")?;
+        let chain_id = self
+            .grpc_chain_id
+            .expect("GRPC chain ID has not been fetched yet!");
+        let transaction_fields = if self.strip_unused_transaction_fields {
+            self.processor_config.transaction_fields()
+        } else {
+            TransactionFields::all()
+        };
+        // Kept apart from the main processor's fetcher metrics
+        let fetch = self.range_fetcher(
+            format!("{}_replay", self.processor_config.name()),
+            transaction_fields,
+        );
+        Ok(tokio::spawn(async move {
+            replay_queue.run(&processor, chain_id, fetch).await
+        }))
+    }
+
+    /// Starts streaming the transactions of a range on a stream of its own, for replays and
+    /// column backfills, closing the channel once it has sent the last of them.
+    fn range_fetcher(
+        &self,
+        fetcher_name: String,
+        transaction_fields: TransactionFields,
+    ) -> impl Fn(u64, u64) -> ReplayStream + Send + Sync + 'static {
+        let worker = self.clone();
+        move |start_version, end_version| {
+            let (sender, receiver) = kanal::bounded_async(BUFFER_SIZE);
+            let channel_byte_limiter = Arc::new(ChannelByteLimiter::new(
+                fetcher_name.clone(),
+                worker.max_buffered_transaction_bytes,
+            ));
+            let fetcher_task = tokio::spawn(crate::grpc_stream::create_fetcher_loop(
+                sender,
+                worker.indexer_grpc_data_service_address.clone(),
+                worker.grpc_http2_config.grpc_http2_ping_interval_in_secs(),
+                worker.grpc_http2_config.grpc_http2_ping_timeout_in_secs(),
+                worker.grpc_http2_config.grpc_connection_timeout_secs(),
+                std::time::Duration::from_secs(worker.grpc_response_item_timeout_in_secs),
+                start_version,
+                Some(end_version),
+                worker.auth_token.clone(),
+                fetcher_name.clone(),
+                worker.transaction_filter.clone(),
+                transaction_fields,
+                worker.pb_channel_txn_chunk_size,
+                channel_byte_limiter.clone(),
+                None,
+                None,
+                worker
+                    .compute_block_heights
+                    .then(BlockHeightTracker::default),
+                None,
+                None,
+            ));
+            ReplayStream {
+                receiver,
+                channel_byte_limiter,
+                fetcher_task,
+            }
+        }
+    }
+
+    /// Backfills a column over `[starting_version, ending_version]` instead of processing, see
+    /// `column_backfill`.
+    async fn run_column_backfill(&self, target: ColumnBackfillTarget) -> Result<()> {
+        let (Some(start_version), Some(end_version)) = (self.starting_version, self.ending_version)
+        else {
+            bail!("column_backfill requires starting_version and ending_version");
+        };
+        let fetch = self.range_fetcher(
+            format!("{}_column_backfill", self.processor_config.name()),
+            TransactionFields::all(),
+        );
+        let stream = fetch(start_version, end_version);
+        match target {
+            ColumnBackfillTarget::EventsIndexedType => {
+                indexed_type_backfill()
+                    .run(self.db_pool.clone(), stream, start_version, end_version)
+                    .await?
+            },
+        };
+        Ok(())
+    }
+
+    /// After the Parquet files already in GCS if resuming from them, otherwise the configured
+    /// starting version if any, otherwise where this processor left off.
+    async fn resolve_starting_version(&self) -> Result<u64> {
+        let processor_name = self.processor_config.name();
+        if let Some(parquet_resume) = &self.parquet_resume {
+            let gcs_config = GcsClientConfig::default()
+                .with_auth()
+                .await
+                .context("[Parser] Failed to create GCS client config")?;
+            let resume_version_from_gcs = get_resume_version_from_gcs(
+                &GCSClient::new(gcs_config),
+                parquet_resume,
+                self.starting_version.unwrap_or(0),
             )
             .await
-        });
+            .context("[Parser] Failed to list Parquet files in GCS")?;
+            if let Some(resume_version_from_gcs) = resume_version_from_gcs {
+                info!(
+                    processor_name = processor_name,
+                    service_type = PROCESSOR_SERVICE_TYPE,
+                    final_start_version = resume_version_from_gcs,
+                    bucket_name = parquet_resume.bucket_name,
+                    "[Parser] Resuming after the Parquet files in GCS",
+                );
+                return Ok(resume_version_from_gcs);
+            }
+            info!(
+                processor_name = processor_name,
+                service_type = PROCESSOR_SERVICE_TYPE,
+                bucket_name = parquet_resume.bucket_name,
+                "[Parser] No Parquet files in GCS to resume from",
+            );
+        }
+        let starting_version_from_db = self
+            .get_start_version()
+            .await
+            .context("[Parser] Database error when getting starting version")?
+            .unwrap_or_else(|| {
+                info!(
+                    processor_name = processor_name,
+                    service_type = PROCESSOR_SERVICE_TYPE,
+                    "[Parser] No starting version from db so starting from version 0"
+                );
+                0
+            });
+        // The last batch written can be past the last version committed
+        let starting_version_from_db = if self.write_ahead_log {
+            let store = PostgresWalStore::new(self.db_pool.clone(), processor_name);
+            WriteAheadLog::recover(&store, processor_name)
+                .await
+                .context("[Parser] Failed to read the write-ahead log")?
+                .map_or(starting_version_from_db, |resume_version| {
+                    resume_version.max(starting_version_from_db)
+                })
+        } else {
+            starting_version_from_db
+        };
+
+        let starting_version = self.starting_version.unwrap_or(starting_version_from_db);
+
+        info!(
+            processor_name = processor_name,
+            service_type = PROCESSOR_SERVICE_TYPE,
+            stream_address = self.indexer_grpc_data_service_address.to_string(),
+            final_start_version = starting_version,
+            start_version_from_config = self.starting_version,
+            start_version_from_db = starting_version_from_db,
+            "[Parser] Building processor",
+        );
+        Ok(starting_version)
+    }
+
+    /// Starts the gap detector and the processor tasks consuming from `receiver`. The byte
+    /// limiter is released as batches are taken off the channel, if it's the fetcher's channel.
+    async fn start_processor_pipeline(
+        &self,
+        starting_version: u64,
+        receiver: kanal::AsyncReceiver<Arc<TransactionsPBResponse>>,
+        channel_byte_limiter: Option<Arc<ChannelByteLimiter>>,
+        processing_byte_budget: Arc<ProcessingByteBudget>,
+        in_flight_versions: Arc<InFlightVersions>,
+        live_status: Arc<LiveProcessorStatus>,
+    ) -> Result<Vec<JoinHandle<()>>> {
+        let processor_name = self.processor_config.name();
+        let concurrent_tasks = self.number_concurrent_processing_tasks;
 
         // Create a gap detector task that will panic if there is a gap in the processing
         let (gap_detector_sender, gap_detector_receiver) =
@@ -288,26 +1073,45 @@ impl Worker {
         let processor = build_processor(
             &self.processor_config,
             self.per_table_chunk_sizes.clone(),
+            self.per_table_conflict_strategies.clone(),
             self.deprecated_tables,
             self.db_pool.clone(),
             maybe_gap_detector_sender,
-        );
+            starting_version,
+            self.rayon_pool.clone(),
+        )?;
 
         let gap_detector = if is_parquet_processor {
             GapDetector::ParquetFileGapDetector(Arc::new(Mutex::new(
                 ParquetFileGapDetectorInner::new(starting_version),
             )))
+        } else if self.persist_gap_detector_state {
+            GapDetector::DefaultGapDetector(
+                load_default_gap_detector(self.db_pool.clone(), processor_name, starting_version)
+                    .await
+                    .expect("[Parser] Failed to load gap detector state"),
+            )
         } else {
             GapDetector::DefaultGapDetector(DefaultGapDetector::new(starting_version))
         };
         let gap_detector_clone = gap_detector.clone();
+        let persist_gap_detector_state = self.persist_gap_detector_state;
+        let progress_committer = ProgressCommitter::new(
+            self.progress_commit_interval_secs,
+            self.progress_commit_interval_versions,
+            starting_version,
+        )
+        .with_leased_progress(self.leased_progress.clone());
 
-        tokio::spawn(async move {
+        let gap_detector_task = tokio::spawn(async move {
             create_gap_detector_status_tracker_loop(
                 gap_detector_clone,
                 gap_detector_receiver,
                 processor,
                 gap_detection_batch_size,
+                persist_gap_detector_state,
+                progress_committer,
+                in_flight_versions,
             )
             .await;
         });
@@ -327,16 +1131,25 @@ impl Worker {
             "[Parser] Spawning concurrent parallel processor tasks",
         );
 
-        let mut processor_tasks = vec![fetcher_task];
+        let write_ahead_log = self.write_ahead_log.then(|| {
+            let store = PostgresWalStore::new(self.db_pool.clone(), processor_name);
+            Arc::new(WriteAheadLog::new(Box::new(store), starting_version))
+        });
+        let mut processor_tasks = vec![];
         for task_index in 0..concurrent_tasks {
             let join_handle: JoinHandle<()> = self
                 .launch_processor_task(
                     task_index,
+                    starting_version,
                     receiver.clone(),
+                    channel_byte_limiter.clone(),
+                    processing_byte_budget.clone(),
+                    live_status.clone(),
                     gap_detector_sender.clone(),
                     gap_detector.clone(),
+                    write_ahead_log.clone(),
                 )
-                .await;
+                .await?;
             processor_tasks.push(join_handle);
         }
 
@@ -348,54 +1161,80 @@ impl Worker {
             "[Parser] Processor tasks spawned",
         );
 
-        // Await the processor tasks: this is forever
-        futures::future::try_join_all(processor_tasks)
-            .await
-            .expect("[Processor] Processor tasks have died");
+        // The gap detector flushes the last processed version once the processor tasks are done
+        // and drop their senders, or on shutdown. Parquet processors hold a sender of their own,
+        // so their gap detector only finishes on shutdown, once the parquet handlers have flushed.
+        if is_parquet_processor {
+            shutdown::register_progress_task(gap_detector_task);
+        } else {
+            processor_tasks.push(shutdown::register_awaited_progress_task(gap_detector_task));
+        }
+
+        Ok(processor_tasks)
     }
 
     async fn launch_processor_task(
         &self,
         task_index: usize,
-        receiver: kanal::AsyncReceiver<TransactionsPBResponse>,
+        starting_version: u64,
+        receiver: kanal::AsyncReceiver<Arc<TransactionsPBResponse>>,
+        channel_byte_limiter: Option<Arc<ChannelByteLimiter>>,
+        processing_byte_budget: Arc<ProcessingByteBudget>,
+        live_status: Arc<LiveProcessorStatus>,
         gap_detector_sender: AsyncSender<ProcessingResult>,
         mut gap_detector: GapDetector,
-    ) -> JoinHandle<()> {
+        write_ahead_log: Option<Arc<WriteAheadLog>>,
+    ) -> Result<JoinHandle<()>> {
         let processor_name = self.processor_config.name();
         let stream_address = self.indexer_grpc_data_service_address.to_string();
         let receiver_clone = receiver.clone();
         let auth_token = self.auth_token.clone();
+        let leased_progress = self.leased_progress.clone();
 
-        // Build the processor based on the config.
-        let processor = if self.processor_config.is_parquet_processor() {
+        // Build the processor based on the config. Shared with the task each batch is processed
+        // on when there's a timeout
+        let processor = Arc::new(if self.processor_config.is_parquet_processor() {
             build_processor(
                 &self.processor_config,
                 self.per_table_chunk_sizes.clone(),
+                self.per_table_conflict_strategies.clone(),
                 self.deprecated_tables,
                 self.db_pool.clone(),
                 Some(gap_detector_sender.clone()),
-            )
+                starting_version,
+                self.rayon_pool.clone(),
+            )?
         } else {
             build_processor(
                 &self.processor_config,
                 self.per_table_chunk_sizes.clone(),
+                self.per_table_conflict_strategies.clone(),
                 self.deprecated_tables,
                 self.db_pool.clone(),
                 None,
-            )
-        };
+                starting_version,
+                self.rayon_pool.clone(),
+            )?
+        });
 
         let concurrent_tasks = self.number_concurrent_processing_tasks;
+        let metrics_sample_rate = self.metrics_sample_rate;
+        let slow_batch_exemplar_threshold_secs = self.slow_batch_exemplar_threshold_secs;
+        let tps_reporting = self.tps_reporting.clone();
+        let check_authentication_keys = self.check_authentication_keys;
+        let batch_processing_timeout = self.batch_processing_timeout_secs.map(Duration::from_secs);
+        let error_budget = self.error_budget.clone();
 
         let chain_id = self
             .grpc_chain_id
             .expect("GRPC chain ID has not been fetched yet!");
 
-        tokio::spawn(async move {
+        Ok(tokio::spawn(async move {
             let task_index_str = task_index.to_string();
             let step = ProcessorStep::ProcessedBatch.get_step();
             let label = ProcessorStep::ProcessedBatch.get_label();
-            let mut ma = MovingAverage::new(3000);
+            let mut ma = tps_reporting.moving_average(3000);
+            let mut num_batches_processed: u64 = 0;
 
             loop {
                 let txn_channel_fetch_latency = std::time::Instant::now();
@@ -403,12 +1242,14 @@ impl Worker {
                     processor_name,
                     &stream_address,
                     receiver_clone.clone(),
+                    channel_byte_limiter.as_deref(),
+                    starting_version,
                     task_index,
                 )
                 .await
                 {
                     // Fetched transactions from channel
-                    Ok(transactions_pb) => {
+                    Ok(mut transactions_pb) => {
                         let size_in_bytes = transactions_pb.size_in_bytes as f64;
                         let first_txn_version = transactions_pb
                             .transactions
@@ -425,10 +1266,10 @@ impl Worker {
                         let start_txn_timestamp = transactions_pb.start_txn_timestamp;
                         let end_txn_timestamp = transactions_pb.end_txn_timestamp;
 
-                        let start_txn_timestamp_unix = start_txn_timestamp
-                            .as_ref()
-                            .map(timestamp_to_unixtime)
-                            .unwrap_or_default();
+                        // Genesis has no timestamp, so it's left out of the latency metrics
+                        let start_txn_timestamp_unix =
+                            transaction_timestamp(start_txn_timestamp.as_ref())
+                                .map(timestamp_to_unixtime);
                         let start_txn_timestamp_iso = start_txn_timestamp
                             .as_ref()
                             .map(timestamp_to_iso)
@@ -451,8 +1292,10 @@ impl Worker {
                             size_in_bytes,
                             task_index,
                             duration_in_secs = txn_channel_fetch_latency_sec,
-                            tps = (batch_last_txn_version as f64 - batch_first_txn_version as f64)
-                                / txn_channel_fetch_latency_sec,
+                            tps = tps_reporting.tps(
+                                batch_last_txn_version as f64 - batch_first_txn_version as f64,
+                                txn_channel_fetch_latency_sec
+                            ),
                             bytes_per_sec = size_in_bytes / txn_channel_fetch_latency_sec,
                             "[Parser][T#{}] Successfully fetched transactions from channel.",
                             task_index
@@ -475,23 +1318,109 @@ impl Worker {
                             );
                         }
 
+                        if check_authentication_keys {
+                            authentication_key_check::check_transactions(
+                                processor_name,
+                                &transactions_pb.transactions,
+                            );
+                        }
+
+                        if let Some(leased_progress) = &leased_progress {
+                            leased_progress
+                                .ensure_held()
+                                .expect("[Parser] Refusing to process transactions");
+                        }
+                        // Before taking any of the budget, which the batches ahead may need
+                        if let Some(write_ahead_log) = &write_ahead_log {
+                            write_ahead_log
+                                .begin(batch_first_txn_version, batch_last_txn_version)
+                                .await
+                                .expect("[Parser] Failed to log the batch in the write-ahead log");
+                        }
+                        let processing_bytes = processing_byte_budget
+                            .acquire(transactions_pb.size_in_bytes)
+                            .await;
                         let processing_time = std::time::Instant::now();
+                        // Every log line of the batch carries its trace ID, and so does its
+                        // exemplar if it's slow
+                        let batch_span = info_span!(
+                            "batch",
+                            trace_id = %format!("{:032x}", rand::random::<u128>()),
+                        );
 
-                        let res = do_processor(
-                            transactions_pb,
-                            &processor,
-                            chain_id,
-                            processor_name,
-                            &auth_token,
-                            false, // enable_verbose_logging
-                        )
-                        .await;
+                        // Shared by every attempt, so that retrying the batch doesn't copy it
+                        let transactions =
+                            Arc::new(std::mem::take(&mut transactions_pb.transactions));
+                        let res = loop {
+                            let processing = {
+                                let processor = processor.clone();
+                                let auth_token = auth_token.clone();
+                                let transactions_pb = transactions_pb.clone();
+                                let transactions = transactions.clone();
+                                async move {
+                                    do_processor(
+                                        transactions_pb,
+                                        transactions,
+                                        &processor,
+                                        chain_id,
+                                        processor_name,
+                                        &auth_token,
+                                        false, // enable_verbose_logging
+                                    )
+                                    .await
+                                }
+                            };
+                            let processing = processing.instrument(batch_span.clone());
+                            let res = match batch_processing_timeout {
+                                Some(timeout) => {
+                                    process_with_timeout(
+                                        processor_name,
+                                        batch_first_txn_version,
+                                        batch_last_txn_version,
+                                        timeout,
+                                        processing,
+                                    )
+                                    .await
+                                },
+                                None => processing.await,
+                            };
+                            match (res, &error_budget) {
+                                (Err(e), Some(error_budget)) => {
+                                    error!(
+                                        processor_name = processor_name,
+                                        stream_address = stream_address.as_str(),
+                                        error = ?e,
+                                        task_index,
+                                        start_version = batch_first_txn_version,
+                                        end_version = batch_last_txn_version,
+                                        "[Parser][T#{}] Error processing transactions, retrying",
+                                        task_index
+                                    );
+                                    PROCESSOR_ERRORS_COUNT
+                                        .with_label_values(&[processor_name, chain_id_label()])
+                                        .inc();
+                                    // Exits once too many batches have failed
+                                    error_budget.record_error(processor_name);
+                                    tokio::time::sleep(error_budget.retry_delay).await;
+                                },
+                                (res, ..) => break res,
+                            }
+                        };
+                        drop(processing_bytes);
 
                         let processing_result = match res {
                             Ok(versions) => {
                                 PROCESSOR_SUCCESSES_COUNT
-                                    .with_label_values(&[processor_name])
+                                    .with_label_values(&[processor_name, chain_id_label()])
                                     .inc();
+                                if let Some(write_ahead_log) = &write_ahead_log {
+                                    write_ahead_log
+                                        .complete(batch_first_txn_version, batch_last_txn_version)
+                                        .await
+                                        .expect(
+                                            "[Parser] Failed to log the batch in the write-ahead log",
+                                        );
+                                }
                                 versions
                             },
                             Err(e) => {
@@ -503,7 +1432,7 @@ impl Worker {
                                     "[Parser][T#{}] Error processing transactions", task_index
                                 );
                                 PROCESSOR_ERRORS_COUNT
-                                    .with_label_values(&[processor_name])
+                                    .with_label_values(&[processor_name, chain_id_label()])
                                     .inc();
                                 panic!(
                                     "[Parser][T#{}] Error processing '{:}' transactions: {:?}",
@@ -518,7 +1447,17 @@ impl Worker {
 
                                 // We've processed things: do some data and metrics
                                 ma.tick_now((last_txn_version - first_txn_version) + 1);
-                                let tps = ma.avg().ceil() as u64;
+                                let (tps, tps_clamped) = ma.clamped_avg();
+                                if tps_clamped {
+                                    warn!(
+                                        processor_name = processor_name,
+                                        task_index,
+                                        max_tps = tps_reporting.max_tps,
+                                        "[Parser][T#{}] Reported TPS capped at tps_reporting.max_tps",
+                                        task_index
+                                    );
+                                }
+                                let tps = tps.ceil() as u64;
 
                                 let num_processed = (last_txn_version - first_txn_version) + 1;
 
@@ -547,28 +1486,60 @@ impl Worker {
                                     label,
                                 );
 
+                                let lag_in_secs = transaction_timestamp(end_txn_timestamp.as_ref())
+                                    .map(time_diff_since_pb_timestamp_in_secs);
+                                live_status.record_batch(
+                                    num_processed,
+                                    last_txn_version,
+                                    lag_in_secs,
+                                );
+                                live_status.record_batch_range(
+                                    batch_first_txn_version,
+                                    batch_last_txn_version,
+                                );
+                                if is_tail_enabled() {
+                                    live_status
+                                        .print_tail_line(first_txn_version, last_txn_version);
+                                }
+
+                                // Gauges and histograms are sampled to keep the hot path cheap under load
+                                let should_sample_metrics =
+                                    num_batches_processed % metrics_sample_rate == 0;
+                                num_batches_processed += 1;
+
                                 // TODO: For these three, do an atomic thing, or ideally move to an async metrics collector!
-                                GRPC_LATENCY_BY_PROCESSOR_IN_SECS
-                                    .with_label_values(&[processor_name, &task_index_str])
-                                    .observe(time_diff_since_pb_timestamp_in_secs(
-                                        end_txn_timestamp.as_ref().unwrap(),
-                                    ));
-                                LATEST_PROCESSED_VERSION
-                                    .with_label_values(&[
-                                        processor_name,
-                                        step,
-                                        label,
-                                        &task_index_str,
-                                    ])
-                                    .set(last_txn_version as i64);
-                                TRANSACTION_UNIX_TIMESTAMP
-                                    .with_label_values(&[
-                                        processor_name,
-                                        step,
-                                        label,
-                                        &task_index_str,
-                                    ])
-                                    .set(start_txn_timestamp_unix);
+                                if should_sample_metrics {
+                                    if let Some(lag_in_secs) = lag_in_secs {
+                                        GRPC_LATENCY_BY_PROCESSOR_IN_SECS
+                                            .with_label_values(&[
+                                                processor_name,
+                                                &task_index_str,
+                                                chain_id_label(),
+                                            ])
+                                            .observe(lag_in_secs);
+                                    }
+                                    LATEST_PROCESSED_VERSION
+                                        .with_label_values(&[
+                                            processor_name,
+                                            step,
+                                            label,
+                                            &task_index_str,
+                                            chain_id_label(),
+                                        ])
+                                        .set(last_txn_version as i64);
+                                    if let Some(start_txn_timestamp_unix) = start_txn_timestamp_unix
+                                    {
+                                        TRANSACTION_UNIX_TIMESTAMP
+                                            .with_label_values(&[
+                                                processor_name,
+                                                step,
+                                                label,
+                                                &task_index_str,
+                                                chain_id_label(),
+                                            ])
+                                            .set(start_txn_timestamp_unix);
+                                    }
+                                }
 
                                 // Single batch metrics
                                 PROCESSED_BYTES_COUNT
@@ -577,6 +1548,7 @@ impl Worker {
                                         step,
                                         label,
                                         &task_index_str,
+                                        chain_id_label(),
                                     ])
                                     .inc_by(size_in_bytes as u64);
                                 NUM_TRANSACTIONS_PROCESSED_COUNT
@@ -585,18 +1557,33 @@ impl Worker {
                                         step,
                                         label,
                                         &task_index_str,
+                                        chain_id_label(),
                                     ])
                                     .inc_by(num_processed);
-
-                                SINGLE_BATCH_PROCESSING_TIME_IN_SECS
-                                    .with_label_values(&[processor_name, &task_index_str])
-                                    .observe(processing_time);
-                                SINGLE_BATCH_PARSING_TIME_IN_SECS
-                                    .with_label_values(&[processor_name, &task_index_str])
-                                    .observe(processing_result.processing_duration_in_secs);
-                                SINGLE_BATCH_DB_INSERTION_TIME_IN_SECS
-                                    .with_label_values(&[processor_name, &task_index_str])
-                                    .observe(processing_result.db_insertion_duration_in_secs);
+                                record_data_volume(processor_name, &processing_result);
+
+                                if should_sample_metrics {
+                                    SINGLE_BATCH_PROCESSING_TIME_IN_SECS
+                                        .with_label_values(&[processor_name, &task_index_str])
+                                        .observe(processing_time);
+                                    if slow_batch_exemplar_threshold_secs
+                                        .is_some_and(|threshold| processing_time >= threshold)
+                                    {
+                                        batch_span.in_scope(|| {
+                                            record_slow_batch_exemplar(
+                                                processor_name,
+                                                &task_index_str,
+                                                processing_time,
+                                            )
+                                        });
+                                    }
+                                    SINGLE_BATCH_PARSING_TIME_IN_SECS
+                                        .with_label_values(&[processor_name, &task_index_str])
+                                        .observe(processing_result.processing_duration_in_secs);
+                                    SINGLE_BATCH_DB_INSERTION_TIME_IN_SECS
+                                        .with_label_values(&[processor_name, &task_index_str])
+                                        .observe(processing_result.db_insertion_duration_in_secs);
+                                }
 
                                 gap_detector_sender
                                     .send(ProcessingResult::DefaultProcessingResult(
@@ -622,6 +1609,7 @@ impl Worker {
                                         step,
                                         label,
                                         &task_index_str,
+                                        chain_id_label(),
                                     ])
                                     .inc_by(num_processed);
 
@@ -652,7 +1640,7 @@ impl Worker {
                     },
                 }
             }
-        })
+        }))
     }
 
     // For the normal processor build we just use standard Diesel with the postgres
@@ -666,7 +1654,7 @@ impl Worker {
         info!("Running migrations: {:?}", self.postgres_connection_string);
         let mut conn =
             PgConnection::establish(&self.postgres_connection_string).expect("migrations failed!");
-        run_pending_migrations(&mut conn);
+        run_pending_migrations_with_status(&mut conn, &self.migration_status);
     }
 
     // If the libpq feature isn't enabled, we use diesel async instead. This is used by
@@ -683,19 +1671,21 @@ impl Worker {
             .dedicated_connection()
             .await
             .expect("[Parser] Failed to get connection");
+        let migration_status = self.migration_status.clone();
         // We use spawn_blocking since run_pending_migrations is a blocking function.
         tokio::task::spawn_blocking(move || {
             // This lets us use the connection like a normal diesel connection. See more:
             // https://docs.rs/diesel-async/latest/diesel_async/async_connection_wrapper/type.AsyncConnectionWrapper.html
             let mut conn: AsyncConnectionWrapper<diesel_async::AsyncPgConnection> =
                 AsyncConnectionWrapper::from(conn);
-            run_pending_migrations(&mut conn);
+            run_pending_migrations_with_status(&mut conn, &migration_status);
         })
         .await
         .expect("[Parser] Failed to run migrations");
     }
 
     /// Gets the start version for the processor. If not found, start from 0.
+    /// This always reads from the primary so replication lag can't rewind the processor.
     pub async fn get_start_version(&self) -> Result<Option<u64>> {
         let mut conn = self.db_pool.get().await?;
 
@@ -708,34 +1698,42 @@ impl Worker {
     }
 
     /// Verify the chain id from GRPC against the database.
-    pub async fn check_or_update_chain_id(&self, grpc_chain_id: i64) -> Result<u64> {
+    /// Like the start version, this reads from the primary: a lagging replica without the chain id
+    /// would otherwise let a processor index into another chain's database.
+    /// Returns `None` if the chain id doesn't match and `on_chain_mismatch` is `halt`.
+    pub async fn check_or_update_chain_id(&self, grpc_chain_id: i64) -> Result<Option<u64>> {
         let processor_name = self.processor_config.name();
         info!(
             processor_name = processor_name,
             "[Parser] Checking if chain id is correct"
         );
-        let mut conn = self.db_pool.get().await?;
-
-        let maybe_existing_chain_id = LedgerInfo::get(&mut conn).await?.map(|li| li.chain_id);
-
-        match maybe_existing_chain_id {
-            Some(chain_id) => {
-                anyhow::ensure!(chain_id == grpc_chain_id, "[Parser] Wrong chain detected! Trying to index chain {} now but existing data is for chain {}", grpc_chain_id, chain_id);
+        let maybe_existing_chain_id = LedgerInfo::get(&mut self.db_pool.get().await?)
+            .await?
+            .map(|li| li.chain_id);
+
+        let chain_id_check = check_chain_id(
+            maybe_existing_chain_id,
+            grpc_chain_id,
+            self.on_chain_mismatch,
+        )?;
+        match chain_id_check {
+            ChainIdCheck::Matches => {
                 info!(
                     processor_name = processor_name,
-                    chain_id = chain_id,
+                    chain_id = grpc_chain_id,
                     "[Parser] Chain id matches! Continue to index...",
                 );
-                Ok(chain_id as u64)
+                Ok(Some(grpc_chain_id as u64))
             },
-            None => {
+            ChainIdCheck::Halt => Ok(None),
+            ChainIdCheck::NotStored => {
                 info!(
                     processor_name = processor_name,
                     chain_id = grpc_chain_id,
                     "[Parser] Adding chain id to db, continue to index..."
                 );
                 execute_with_better_error_conn(
-                    &mut conn,
+                    &mut self.db_pool.get().await?,
                     diesel::insert_into(ledger_infos::table)
                         .values(LedgerInfo {
                             chain_id: grpc_chain_id,
@@ -745,16 +1743,32 @@ impl Worker {
                 )
                 .await
                 .context("[Parser] Error updating chain_id!")
-                .map(|_| grpc_chain_id as u64)
+                .map(|_| Some(grpc_chain_id as u64))
             },
         }
     }
 }
 
+/// Resolves with the error of the first task to die. Never resolves if they all keep running or
+/// finish cleanly.
+async fn first_task_failure(tasks: &mut [&mut JoinHandle<()>]) -> tokio::task::JoinError {
+    let mut tasks = tasks
+        .iter_mut()
+        .collect::<futures::stream::FuturesUnordered<_>>();
+    while let Some(result) = futures::StreamExt::next(&mut tasks).await {
+        if let Err(e) = result {
+            return e;
+        }
+    }
+    std::future::pending().await
+}
+
 async fn fetch_transactions(
     processor_name: &str,
     stream_address: &str,
-    receiver: kanal::AsyncReceiver<TransactionsPBResponse>,
+    receiver: kanal::AsyncReceiver<Arc<TransactionsPBResponse>>,
+    channel_byte_limiter: Option<&ChannelByteLimiter>,
+    starting_version: u64,
     task_index: usize,
 ) -> Result<TransactionsPBResponse> {
     let pb_channel_fetch_time = std::time::Instant::now();
@@ -765,7 +1779,12 @@ async fn fetch_transactions(
         .set(pb_channel_fetch_time.elapsed().as_secs_f64());
 
     match txn_pb_res {
-        Ok(txn_pb) => Ok(txn_pb),
+        Ok(txn_pb) => {
+            if let Some(channel_byte_limiter) = channel_byte_limiter {
+                channel_byte_limiter.release(txn_pb.size_in_bytes);
+            }
+            Ok(take_batch_from_version(txn_pb, starting_version))
+        },
         Err(_e) => {
             error!(
                 processor_name = processor_name,
@@ -782,8 +1801,62 @@ async fn fetch_transactions(
     }
 }
 
+/// Fails the batch if processing it takes longer than `timeout`. Processing runs on a task of its
+/// own so the timeout fires even if it never yields, e.g. looping on malformed data, though such a
+/// task can't be stopped and keeps its thread.
+async fn process_with_timeout(
+    processor_name: &'static str,
+    start_version: u64,
+    end_version: u64,
+    timeout: Duration,
+    processing: impl Future<Output = Result<ProcessingResult>> + Send + 'static,
+) -> Result<ProcessingResult> {
+    let mut processing = tokio::spawn(processing);
+    match tokio::time::timeout(timeout, &mut processing).await {
+        Ok(Ok(res)) => res,
+        // Only aborted below, so this is a panic
+        Ok(Err(e)) => std::panic::resume_unwind(e.into_panic()),
+        Err(_) => {
+            processing.abort();
+            BATCH_PROCESSING_TIMEOUT_COUNT
+                .with_label_values(&[processor_name])
+                .inc();
+            error!(
+                processor_name,
+                start_version,
+                end_version,
+                timeout_in_secs = timeout.as_secs_f64(),
+                "[Parser] Batch processing timed out"
+            );
+            bail!(
+                "Processing versions [{}, {}] timed out after {:?}",
+                start_version,
+                end_version,
+                timeout
+            )
+        },
+    }
+}
+
+/// Counts the events and write set changes of the batch, if the processor reported them.
+fn record_data_volume(processor_name: &str, processing_result: &DefaultProcessingResult) {
+    if let Some(num_events) = processing_result.num_events {
+        NUM_EVENTS_PROCESSED_COUNT
+            .with_label_values(&[processor_name, chain_id_label()])
+            .inc_by(num_events);
+    }
+    if let Some(num_write_set_changes) = processing_result.num_write_set_changes {
+        NUM_WRITE_SET_CHANGES_PROCESSED_COUNT
+            .with_label_values(&[processor_name, chain_id_label()])
+            .inc_by(num_write_set_changes);
+    }
+}
+
+/// Processes `transactions`, the batch taken out of `transactions_pb`, which only gives the
+/// versions and timestamps of the batch.
 pub async fn do_processor(
     transactions_pb: TransactionsPBResponse,
+    transactions: Arc<Vec<Transaction>>,
     processor: &Processor,
     db_chain_id: u64,
     processor_name: &str,
@@ -795,7 +1868,7 @@ pub async fn do_processor(
     let end_version = transactions_pb.end_version;
 
     // Fake this as it's possible we have filtered out all of the txns in this batch
-    if transactions_pb.transactions.is_empty() {
+    if transactions.is_empty() {
         return Ok(ProcessingResult::DefaultProcessingResult(
             DefaultProcessingResult {
                 start_version,
@@ -803,19 +1876,22 @@ pub async fn do_processor(
                 processing_duration_in_secs: 0.0,
                 db_insertion_duration_in_secs: 0.0,
                 last_transaction_timestamp: transactions_pb.end_txn_timestamp,
+                num_events: None,
+                num_write_set_changes: None,
             },
         ));
     }
 
     let txn_time = transactions_pb.start_txn_timestamp;
 
-    if let Some(ref t) = txn_time {
-        PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS
-            .with_label_values(&[auth_token, processor_name])
-            .set(time_diff_since_pb_timestamp_in_secs(t));
-    }
+    record_data_latency(
+        &PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS,
+        auth_token,
+        processor_name,
+        txn_time.as_ref(),
+    );
     PROCESSOR_INVOCATIONS_COUNT
-        .with_label_values(&[processor_name])
+        .with_label_values(&[processor_name, chain_id_label()])
         .inc();
 
     if enable_verbose_logging {
@@ -830,35 +1906,55 @@ pub async fn do_processor(
     }
 
     let processed_result = processor
-        .process_transactions(
-            transactions_pb.transactions,
+        .process_transactions_with_extensions(
+            transactions,
+            transactions_pb.extensions.clone(),
             start_version,
             end_version,
             Some(db_chain_id),
         )
         .await;
 
-    if let Some(ref t) = txn_time {
-        PROCESSOR_DATA_PROCESSED_LATENCY_IN_SECS
-            .with_label_values(&[auth_token, processor_name])
-            .set(time_diff_since_pb_timestamp_in_secs(t));
-    }
+    record_data_latency(
+        &PROCESSOR_DATA_PROCESSED_LATENCY_IN_SECS,
+        auth_token,
+        processor_name,
+        txn_time.as_ref(),
+    );
 
     processed_result
 }
 
+/// Sets a data latency gauge to the time since the batch's first transaction, unless it doesn't
+/// have a timestamp, i.e. genesis, which would be decades of latency.
+fn record_data_latency(
+    latency: &GaugeVec,
+    auth_token: &str,
+    processor_name: &str,
+    txn_timestamp: Option<&Timestamp>,
+) {
+    if let Some(txn_timestamp) = transaction_timestamp(txn_timestamp) {
+        latency
+            .with_label_values(&[auth_token, processor_name, chain_id_label()])
+            .set(time_diff_since_pb_timestamp_in_secs(txn_timestamp));
+    }
+}
+
 pub fn build_processor_for_testing(
     processor_config: ProcessorConfig,
     db_pool: ArcDbPool,
-) -> Processor {
+) -> Result<Processor> {
     let per_table_chunk_sizes = AHashMap::new();
     let deprecated_tables = TableFlags::empty();
     build_processor(
         &processor_config,
         per_table_chunk_sizes,
+        AHashMap::new(),
         deprecated_tables,
         db_pool,
         None,
+        0,
+        RayonPool::default(),
     )
 }
 
@@ -870,33 +1966,70 @@ pub fn build_processor_for_testing(
 pub fn build_processor(
     config: &ProcessorConfig,
     per_table_chunk_sizes: AHashMap<String, usize>,
+    per_table_conflict_strategies: AHashMap<String, ConflictStrategy>,
     deprecated_tables: TableFlags,
     db_pool: ArcDbPool,
-    gap_detector_sender: Option<AsyncSender<ProcessingResult>>, // Parquet only
-) -> Processor {
-    match config {
+    gap_detector_sender: Option<AsyncSender<ProcessingResult>>, // Parquet and dual sink only
+    starting_version: u64,
+    rayon_pool: RayonPool,
+) -> Result<Processor> {
+    Ok(match config {
+        ProcessorConfig::AccountSequenceNumberProcessor => Processor::from(
+            AccountSequenceNumberProcessor::new(db_pool, per_table_chunk_sizes),
+        ),
         ProcessorConfig::AccountTransactionsProcessor => Processor::from(
-            AccountTransactionsProcessor::new(db_pool, per_table_chunk_sizes),
+            AccountTransactionsProcessor::new(db_pool, per_table_chunk_sizes, rayon_pool),
         ),
+        ProcessorConfig::AllowanceProcessor(config) => Processor::from(AllowanceProcessor::new(
+            db_pool,
+            config.clone(),
+            per_table_chunk_sizes,
+        )),
         ProcessorConfig::AnsProcessor(config) => Processor::from(AnsProcessor::new(
             db_pool,
             config.clone(),
             per_table_chunk_sizes,
             deprecated_tables,
         )),
-        ProcessorConfig::DefaultProcessor => Processor::from(DefaultProcessor::new(
+        ProcessorConfig::BlockGasStatsProcessor => Processor::from(BlockGasStatsProcessor::new(
             db_pool,
             per_table_chunk_sizes,
-            deprecated_tables,
+            starting_version,
         )),
-        ProcessorConfig::EventsProcessor => {
-            Processor::from(EventsProcessor::new(db_pool, per_table_chunk_sizes))
-        },
-        ProcessorConfig::FungibleAssetProcessor => Processor::from(FungibleAssetProcessor::new(
+        ProcessorConfig::CustomProcessor(config) => Processor::from(
+            CustomProcessor::new(config, CustomProcessorArgs {
+                db_pool,
+                per_table_chunk_sizes,
+                deprecated_tables,
+                gap_detector_sender,
+            })
+            .context("Failed to build custom processor")?,
+        ),
+        ProcessorConfig::DefaultProcessor(config) => Processor::from(DefaultProcessor::new(
             db_pool,
+            config.clone(),
             per_table_chunk_sizes,
             deprecated_tables,
         )),
+        ProcessorConfig::EventsProcessor(config) => Processor::from(EventsProcessor::new(
+            db_pool,
+            config.clone(),
+            per_table_chunk_sizes,
+            per_table_conflict_strategies,
+        )),
+        ProcessorConfig::FungibleAssetProcessor(config) => {
+            Processor::from(FungibleAssetProcessor::new(
+                db_pool,
+                config.clone(),
+                per_table_chunk_sizes,
+                deprecated_tables,
+                gap_detector_sender,
+                rayon_pool,
+            )?)
+        },
+        ProcessorConfig::GovernanceProcessor => {
+            Processor::from(GovernanceProcessor::new(db_pool, per_table_chunk_sizes))
+        },
         ProcessorConfig::MonitoringProcessor => Processor::from(MonitoringProcessor::new(db_pool)),
         ProcessorConfig::NftMetadataProcessor(config) => {
             Processor::from(NftMetadataProcessor::new(db_pool, config.clone()))
@@ -921,9 +2054,15 @@ pub fn build_processor(
         ProcessorConfig::TransactionMetadataProcessor => Processor::from(
             TransactionMetadataProcessor::new(db_pool, per_table_chunk_sizes),
         ),
-        ProcessorConfig::UserTransactionProcessor => Processor::from(
-            UserTransactionProcessor::new(db_pool, per_table_chunk_sizes, deprecated_tables),
-        ),
+        ProcessorConfig::UserTransactionProcessor(config) => {
+            Processor::from(UserTransactionProcessor::new(
+                db_pool,
+                config.clone(),
+                per_table_chunk_sizes,
+                per_table_conflict_strategies,
+                deprecated_tables,
+            ))
+        },
         ProcessorConfig::ParquetDefaultProcessor(config) => {
             Processor::from(ParquetDefaultProcessor::new(
                 db_pool,
@@ -978,5 +2117,200 @@ pub fn build_processor(
                 gap_detector_sender.expect("Parquet processor requires a gap detector sender"),
             ))
         },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Chain id stored by a previous run against testnet, now pointed at mainnet
+    const EXISTING_CHAIN_ID: Option<i64> = Some(2);
+    const GRPC_CHAIN_ID: i64 = 1;
+
+    /// Takes `delay` to process any batch
+    #[derive(Debug)]
+    struct SlowProcessor {
+        delay: Duration,
+    }
+
+    #[async_trait::async_trait]
+    impl ProcessorTrait for SlowProcessor {
+        fn name(&self) -> &'static str {
+            "slow_processor"
+        }
+
+        async fn process_transactions(
+            &self,
+            _transactions: Arc<Vec<Transaction>>,
+            start_version: u64,
+            end_version: u64,
+            _: Option<u64>,
+        ) -> Result<ProcessingResult> {
+            tokio::time::sleep(self.delay).await;
+            Ok(ProcessingResult::DefaultProcessingResult(
+                DefaultProcessingResult {
+                    start_version,
+                    end_version,
+                    processing_duration_in_secs: self.delay.as_secs_f64(),
+                    db_insertion_duration_in_secs: 0.0,
+                    last_transaction_timestamp: None,
+                    num_events: None,
+                    num_write_set_changes: None,
+                },
+            ))
+        }
+
+        fn connection_pool(&self) -> &ArcDbPool {
+            unreachable!("The slow processor doesn't write anything")
+        }
+    }
+
+    async fn process_slowly(delay: Duration, timeout: Duration) -> Result<ProcessingResult> {
+        let processor = SlowProcessor { delay };
+        process_with_timeout("slow_processor", 10, 19, timeout, async move {
+            processor
+                .process_transactions(Arc::default(), 10, 19, None)
+                .await
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_batch_processing_timeout() {
+        let err = process_slowly(Duration::from_secs(60), Duration::from_millis(50))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Processing versions [10, 19] timed out after 50ms"
+        );
+        assert_eq!(
+            BATCH_PROCESSING_TIMEOUT_COUNT
+                .with_label_values(&["slow_processor"])
+                .get(),
+            1
+        );
+
+        // Within the timeout, the batch is processed as usual
+        assert!(
+            process_slowly(Duration::from_millis(10), Duration::from_secs(60))
+                .await
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn test_record_data_volume() {
+        let batch = |num_events, num_write_set_changes| DefaultProcessingResult {
+            start_version: 0,
+            end_version: 9,
+            last_transaction_timestamp: None,
+            processing_duration_in_secs: 0.0,
+            db_insertion_duration_in_secs: 0.0,
+            num_events,
+            num_write_set_changes,
+        };
+        let processor_name = "test_record_data_volume";
+        record_data_volume(processor_name, &batch(Some(12), Some(30)));
+        record_data_volume(processor_name, &batch(Some(3), None));
+        // Processors that don't count them report neither
+        record_data_volume(processor_name, &batch(None, None));
+        assert_eq!(
+            NUM_EVENTS_PROCESSED_COUNT
+                .with_label_values(&[processor_name, chain_id_label()])
+                .get(),
+            15
+        );
+        assert_eq!(
+            NUM_WRITE_SET_CHANGES_PROCESSED_COUNT
+                .with_label_values(&[processor_name, chain_id_label()])
+                .get(),
+            30
+        );
+    }
+
+    #[test]
+    fn test_no_latency_for_genesis() {
+        let processor_name = "test_no_latency_for_genesis";
+        let gauge = || {
+            PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS
+                .with_label_values(&["", processor_name, chain_id_label()])
+                .get()
+        };
+        // Genesis comes off the stream with a zeroed timestamp
+        let genesis = Timestamp {
+            seconds: 0,
+            nanos: 0,
+        };
+        record_data_latency(
+            &PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS,
+            "",
+            processor_name,
+            Some(&genesis),
+        );
+        record_data_latency(
+            &PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS,
+            "",
+            processor_name,
+            None,
+        );
+        assert_eq!(gauge(), 0.0);
+
+        let recent = Timestamp {
+            seconds: chrono::Utc::now().timestamp() - 5,
+            nanos: 0,
+        };
+        record_data_latency(
+            &PROCESSOR_DATA_RECEIVED_LATENCY_IN_SECS,
+            "",
+            processor_name,
+            Some(&recent),
+        );
+        let latency = gauge();
+        assert!(latency > 0.0 && latency < 60.0, "latency was {}", latency);
+    }
+
+    #[test]
+    #[should_panic(expected = "Wrong chain detected")]
+    fn test_chain_mismatch_panics() {
+        let _ = check_chain_id(EXISTING_CHAIN_ID, GRPC_CHAIN_ID, OnChainMismatch::Panic);
+    }
+
+    #[test]
+    fn test_chain_mismatch_halts() {
+        assert_eq!(
+            check_chain_id(EXISTING_CHAIN_ID, GRPC_CHAIN_ID, OnChainMismatch::Halt),
+            Ok(ChainIdCheck::Halt)
+        );
+    }
+
+    #[test]
+    fn test_chain_mismatch_returns_error() {
+        assert_eq!(
+            check_chain_id(EXISTING_CHAIN_ID, GRPC_CHAIN_ID, OnChainMismatch::Error),
+            Err(ChainIdMismatchError {
+                existing_chain_id: 2,
+                grpc_chain_id: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_chain_id_match_ignores_mode() {
+        for mode in [
+            OnChainMismatch::Panic,
+            OnChainMismatch::Halt,
+            OnChainMismatch::Error,
+        ] {
+            assert_eq!(
+                check_chain_id(Some(GRPC_CHAIN_ID), GRPC_CHAIN_ID, mode),
+                Ok(ChainIdCheck::Matches)
+            );
+            assert_eq!(
+                check_chain_id(None, GRPC_CHAIN_ID, mode),
+                Ok(ChainIdCheck::NotStored)
+            );
+        }
     }
 }
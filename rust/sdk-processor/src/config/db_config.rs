@@ -1,3 +1,4 @@
+use processor::bq_analytics::generic_parquet_processor::ParquetCompressionConfig;
 use serde::{Deserialize, Serialize};
 
 /// This enum captures the configs for all the different db storages that are defined.
@@ -64,4 +65,6 @@ pub struct ParquetConfig {
     pub bucket_name: String,
     #[serde(default)]
     pub bucket_root: String,
+    #[serde(default)]
+    pub compression: ParquetCompressionConfig,
 }
@@ -42,6 +42,9 @@ pub struct IndexerProcessorConfig {
     pub transaction_stream_config: TransactionStreamConfig,
     pub db_config: DbConfig,
     pub backfill_config: Option<BackfillConfig>,
+    /// Threads the extractors parse transactions on, instead of one per core
+    #[serde(default)]
+    pub rayon_num_threads: Option<usize>,
 }
 
 #[async_trait::async_trait]
@@ -1,9 +1,6 @@
 use crate::{
     config::db_config::DbConfig,
-    steps::common::{
-        gcs_uploader::{create_new_writer, GCSUploader},
-        parquet_buffer_step::ParquetBufferStep,
-    },
+    steps::common::{gcs_uploader::GCSUploader, parquet_buffer_step::ParquetBufferStep},
     utils::database::{new_db_pool, ArcDbPool},
 };
 use aptos_indexer_processor_sdk::utils::errors::ProcessorError;
@@ -12,6 +9,7 @@ use enum_dispatch::enum_dispatch;
 use google_cloud_storage::client::{Client as GCSClient, ClientConfig as GcsClientConfig};
 use parquet::schema::types::Type;
 use processor::{
+    bq_analytics::generic_parquet_processor::{create_new_writer, ParquetCompressionConfig},
     db::parquet::models::{
         account_transaction_models::parquet_account_transactions::AccountTransaction,
         ans_models::{
@@ -650,11 +648,14 @@ async fn initialize_parquet_buffer_step(
     bucket_name: String,
     bucket_root: String,
     processor_name: String,
+    compression: ParquetCompressionConfig,
 ) -> anyhow::Result<ParquetBufferStep> {
+    let compression = compression.compression()?;
     let parquet_type_to_writer = parquet_type_to_schemas
         .iter()
         .map(|(key, schema)| {
-            let writer = create_new_writer(schema.clone()).expect("Failed to create writer");
+            let writer =
+                create_new_writer(schema.clone(), compression).expect("Failed to create writer");
             (*key, writer)
         })
         .collect();
@@ -666,6 +667,7 @@ async fn initialize_parquet_buffer_step(
         bucket_name,
         bucket_root,
         processor_name,
+        compression,
     )?;
 
     let default_size_buffer_step = ParquetBufferStep::new(
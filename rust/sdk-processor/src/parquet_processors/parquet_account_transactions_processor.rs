@@ -31,6 +31,7 @@ use parquet::schema::types::Type;
 use processor::{
     bq_analytics::generic_parquet_processor::HasParquetSchema,
     db::parquet::models::account_transaction_models::parquet_account_transactions::AccountTransaction,
+    utils::rayon_pool::RayonPool,
 };
 use std::{collections::HashMap, sync::Arc};
 use tracing::{debug, info};
@@ -115,6 +116,7 @@ impl ProcessorTrait for ParquetAccountTransactionsProcessor {
         let backfill_table = set_backfill_table_flag(parquet_processor_config.backfill_table);
         let parquet_account_transactions_extractor = ParquetAccountTransactionsExtractor {
             opt_in_tables: backfill_table,
+            rayon_pool: RayonPool::new(self.config.rayon_num_threads)?,
         };
 
         let gcs_client =
@@ -135,6 +137,7 @@ impl ProcessorTrait for ParquetAccountTransactionsProcessor {
             parquet_db_config.bucket_name.clone(),
             parquet_db_config.bucket_root.clone(),
             self.name().to_string(),
+            parquet_db_config.compression,
         )
         .await
         .unwrap_or_else(|e| {
@@ -161,6 +161,7 @@ impl ProcessorTrait for ParquetAnsProcessor {
             parquet_db_config.bucket_name.clone(),
             parquet_db_config.bucket_root.clone(),
             self.name().to_string(),
+            parquet_db_config.compression,
         )
         .await
         .unwrap_or_else(|e| {
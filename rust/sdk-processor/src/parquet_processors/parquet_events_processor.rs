@@ -131,6 +131,7 @@ impl ProcessorTrait for ParquetEventsProcessor {
             parquet_db_config.bucket_name.clone(),
             parquet_db_config.bucket_root.clone(),
             self.name().to_string(),
+            parquet_db_config.compression,
         )
         .await
         .unwrap_or_else(|e| {
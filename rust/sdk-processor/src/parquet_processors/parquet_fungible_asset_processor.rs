@@ -37,6 +37,7 @@ use processor::{
         },
         parquet_v2_fungible_metadata::FungibleAssetMetadataModel,
     },
+    utils::rayon_pool::RayonPool,
 };
 use std::{collections::HashMap, sync::Arc};
 use tracing::{debug, info};
@@ -120,6 +121,7 @@ impl ProcessorTrait for ParquetFungibleAssetProcessor {
         let backfill_table = set_backfill_table_flag(parquet_processor_config.backfill_table);
         let parquet_fa_extractor = ParquetFungibleAssetExtractor {
             opt_in_tables: backfill_table,
+            rayon_pool: RayonPool::new(self.config.rayon_num_threads)?,
         };
 
         let gcs_client =
@@ -158,6 +160,7 @@ impl ProcessorTrait for ParquetFungibleAssetProcessor {
             parquet_db_config.bucket_name.clone(),
             parquet_db_config.bucket_root.clone(),
             self.name().to_string(),
+            parquet_db_config.compression,
         )
         .await
         .unwrap_or_else(|e| {
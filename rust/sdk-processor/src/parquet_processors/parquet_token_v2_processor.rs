@@ -171,6 +171,7 @@ impl ProcessorTrait for ParquetTokenV2Processor {
             parquet_db_config.bucket_name.clone(),
             parquet_db_config.bucket_root.clone(),
             self.name().to_string(),
+            parquet_db_config.compression,
         )
         .await
         .unwrap_or_else(|e| {
@@ -132,6 +132,7 @@ impl ProcessorTrait for ParquetTransactionMetadataProcessor {
             parquet_db_config.bucket_name.clone(),
             parquet_db_config.bucket_root.clone(),
             self.name().to_string(),
+            parquet_db_config.compression,
         )
         .await
         .unwrap_or_else(|e| {
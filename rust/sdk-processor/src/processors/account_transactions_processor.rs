@@ -22,6 +22,7 @@ use aptos_indexer_processor_sdk::{
     },
     traits::{processor_trait::ProcessorTrait, IntoRunnableStep},
 };
+use processor::utils::rayon_pool::RayonPool;
 use tracing::{debug, info};
 
 pub struct AccountTransactionsProcessor {
@@ -101,7 +102,9 @@ impl ProcessorTrait for AccountTransactionsProcessor {
             ..self.config.transaction_stream_config.clone()
         })
         .await?;
-        let acc_txns_extractor = AccountTransactionsExtractor {};
+        let acc_txns_extractor = AccountTransactionsExtractor {
+            rayon_pool: RayonPool::new(self.config.rayon_num_threads)?,
+        };
         let acc_txns_storer =
             AccountTransactionsStorer::new(self.db_pool.clone(), processor_config);
         let version_tracker = VersionTrackerStep::new(
@@ -22,6 +22,7 @@ use aptos_indexer_processor_sdk::{
     },
     traits::{processor_trait::ProcessorTrait, IntoRunnableStep},
 };
+use processor::utils::rayon_pool::RayonPool;
 use tracing::{debug, info};
 
 pub struct EventsProcessor {
@@ -101,7 +102,9 @@ impl ProcessorTrait for EventsProcessor {
             ..self.config.transaction_stream_config.clone()
         })
         .await?;
-        let events_extractor = EventsExtractor {};
+        let events_extractor = EventsExtractor {
+            rayon_pool: RayonPool::new(self.config.rayon_num_threads)?,
+        };
         let events_storer = EventsStorer::new(self.db_pool.clone(), processor_config);
         let version_tracker = VersionTrackerStep::new(
             get_processor_status_saver(self.db_pool.clone(), self.config.clone()),
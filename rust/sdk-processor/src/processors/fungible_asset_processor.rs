@@ -25,7 +25,7 @@ use aptos_indexer_processor_sdk::{
     },
     traits::{processor_trait::ProcessorTrait, IntoRunnableStep},
 };
-use processor::utils::table_flags::TableFlags;
+use processor::utils::{rayon_pool::RayonPool, table_flags::TableFlags};
 use tracing::{debug, info};
 
 pub struct FungibleAssetProcessor {
@@ -101,7 +101,9 @@ impl ProcessorTrait for FungibleAssetProcessor {
             ..self.config.transaction_stream_config.clone()
         })
         .await?;
-        let fa_extractor = FungibleAssetExtractor {};
+        let fa_extractor = FungibleAssetExtractor {
+            rayon_pool: RayonPool::new(self.config.rayon_num_threads)?,
+        };
         let fa_storer = FungibleAssetStorer::new(
             self.db_pool.clone(),
             processor_config.clone(),
@@ -5,15 +5,21 @@ use aptos_indexer_processor_sdk::{
     utils::errors::ProcessorError,
 };
 use async_trait::async_trait;
-use processor::db::{
-    common::models::account_transaction_models::raw_account_transactions::RawAccountTransaction,
-    postgres::models::account_transaction_models::account_transactions::AccountTransaction,
+use processor::{
+    db::{
+        common::models::account_transaction_models::raw_account_transactions::RawAccountTransaction,
+        postgres::models::account_transaction_models::account_transactions::AccountTransaction,
+    },
+    utils::rayon_pool::RayonPool,
 };
 use rayon::prelude::*;
 
 pub struct AccountTransactionsExtractor
 where
-    Self: Sized + Send + 'static, {}
+    Self: Sized + Send + 'static,
+{
+    pub rayon_pool: RayonPool,
+}
 
 #[async_trait]
 impl Processable for AccountTransactionsExtractor {
@@ -25,21 +31,25 @@ impl Processable for AccountTransactionsExtractor {
         &mut self,
         input: TransactionContext<Vec<Transaction>>,
     ) -> Result<Option<TransactionContext<Vec<AccountTransaction>>>, ProcessorError> {
-        let acc_txns: Vec<AccountTransaction> = input
-            .data
-            .into_par_iter()
-            .map(|txn| {
-                let transaction_version = txn.version as i64;
-                let accounts = RawAccountTransaction::get_accounts(&txn);
-                accounts
-                    .into_iter()
-                    .map(|account_address| AccountTransaction {
-                        transaction_version,
-                        account_address,
+        let acc_txns: Vec<AccountTransaction> = self
+            .rayon_pool
+            .install(|| {
+                input
+                    .data
+                    .into_par_iter()
+                    .map(|txn| {
+                        let transaction_version = txn.version as i64;
+                        let accounts = RawAccountTransaction::get_accounts(&txn);
+                        accounts
+                            .into_iter()
+                            .map(|account_address| AccountTransaction {
+                                transaction_version,
+                                account_address,
+                            })
+                            .collect()
                     })
-                    .collect()
+                    .collect::<Vec<Vec<AccountTransaction>>>()
             })
-            .collect::<Vec<Vec<AccountTransaction>>>()
             .into_iter()
             .flatten()
             .collect();
@@ -4,13 +4,12 @@ use aptos_indexer_processor_sdk::utils::errors::ProcessorError;
 use async_trait::async_trait;
 use google_cloud_storage::client::Client as GCSClient;
 use parquet::{
-    file::{properties::WriterProperties, writer::SerializedFileWriter},
-    record::RecordWriter,
+    basic::Compression, file::writer::SerializedFileWriter, record::RecordWriter,
     schema::types::Type,
 };
 use processor::bq_analytics::{
     gcs_handler::upload_parquet_to_gcs,
-    generic_parquet_processor::{GetTimeStamp, HasParquetSchema, HasVersion},
+    generic_parquet_processor::{create_new_writer, GetTimeStamp, HasParquetSchema, HasVersion},
 };
 use std::{collections::HashMap, path::PathBuf, sync::Arc};
 use tracing::{debug, error};
@@ -22,6 +21,7 @@ pub struct GCSUploader {
     pub bucket_name: String,
     pub bucket_root: String,
     pub processor_name: String,
+    compression: Compression,
 }
 
 #[async_trait]
@@ -52,15 +52,6 @@ impl Uploadable for GCSUploader {
     }
 }
 
-pub fn create_new_writer(schema: Arc<Type>) -> anyhow::Result<SerializedFileWriter<Vec<u8>>> {
-    let props = WriterProperties::builder()
-        .set_compression(parquet::basic::Compression::LZ4)
-        .build();
-    let props_arc = Arc::new(props);
-
-    SerializedFileWriter::new(Vec::new(), schema, props_arc).context("Failed to create new writer")
-}
-
 impl GCSUploader {
     pub fn new(
         gcs_client: Arc<GCSClient>,
@@ -69,6 +60,7 @@ impl GCSUploader {
         bucket_name: String,
         bucket_root: String,
         processor_name: String,
+        compression: Compression,
     ) -> anyhow::Result<Self> {
         Ok(Self {
             gcs_client,
@@ -77,6 +69,7 @@ impl GCSUploader {
             bucket_name,
             bucket_root,
             processor_name,
+            compression,
         })
     }
 
@@ -90,7 +83,7 @@ impl GCSUploader {
             .context("Parquet type not found in schemas")?
             .clone();
 
-        create_new_writer(schema)
+        create_new_writer(schema, self.compression)
     }
 
     /// # Context: Why we replace our writer
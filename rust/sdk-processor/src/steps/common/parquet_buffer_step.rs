@@ -293,7 +293,7 @@ mod tests {
     use crate::{
         config::db_config::ParquetConfig,
         steps::common::{
-            gcs_uploader::{create_new_writer, GCSUploader},
+            gcs_uploader::GCSUploader,
             parquet_buffer_step::{ParquetBufferStep, ParquetTypeEnum, ParquetTypeStructs},
         },
     };
@@ -304,7 +304,9 @@ mod tests {
     use google_cloud_storage::client::{Client as GCSClient, ClientConfig as GcsClientConfig};
     use parquet::schema::types::Type;
     use processor::{
-        bq_analytics::generic_parquet_processor::HasParquetSchema,
+        bq_analytics::generic_parquet_processor::{
+            create_new_writer, HasParquetSchema, ParquetCompressionConfig,
+        },
         db::parquet::models::default_models::parquet_move_resources::MoveResource,
     };
     use std::{collections::HashMap, sync::Arc, time::Duration};
@@ -394,10 +396,12 @@ mod tests {
                 .into_iter()
                 .collect();
 
+        let compression = db_config.compression.compression()?;
         let parquet_type_to_writer = parquet_type_to_schemas
             .iter()
             .map(|(key, schema)| {
-                let writer = create_new_writer(schema.clone()).expect("Failed to create writer");
+                let writer = create_new_writer(schema.clone(), compression)
+                    .expect("Failed to create writer");
                 (*key, writer)
             })
             .collect();
@@ -409,6 +413,7 @@ mod tests {
             db_config.bucket_name.clone(),
             db_config.bucket_root.clone(),
             "processor_name".to_string(),
+            compression,
         )
     }
 
@@ -419,6 +424,7 @@ mod tests {
             bucket_name: "bucket_name".to_string(),
             bucket_root: "bucket_root".to_string(),
             google_application_credentials: None,
+            compression: ParquetCompressionConfig::default(),
         }
     }
 }
@@ -58,7 +58,7 @@ impl Processable for DefaultExtractor {
             raw_table_items,
             raw_current_table_items,
             raw_table_metadata,
-        ) = process_transactions(transactions.data.clone());
+        ) = process_transactions(&transactions.data);
 
         let postgres_table_items: Vec<TableItem> =
             raw_table_items.iter().map(TableItem::from_raw).collect();
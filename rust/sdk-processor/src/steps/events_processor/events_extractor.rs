@@ -8,12 +8,16 @@ use aptos_indexer_processor_sdk::{
     utils::errors::ProcessorError,
 };
 use async_trait::async_trait;
+use processor::utils::rayon_pool::RayonPool;
 use rayon::prelude::*;
 use tracing::warn;
 
 pub struct EventsExtractor
 where
-    Self: Sized + Send + 'static, {}
+    Self: Sized + Send + 'static,
+{
+    pub rayon_pool: RayonPool,
+}
 
 #[async_trait]
 impl Processable for EventsExtractor {
@@ -31,42 +35,43 @@ impl Processable for EventsExtractor {
         //     step_name = self.name(),
         //     "Processing versions",
         // );
-        let events = item
-            .data
-            .par_iter()
-            .with_min_len(MIN_TRANSACTIONS_PER_RAYON_JOB)
-            .map(|txn| {
-                let mut events = vec![];
-                let txn_version = txn.version as i64;
-                let block_height = txn.block_height as i64;
-                let txn_data = match txn.txn_data.as_ref() {
-                    Some(data) => data,
-                    None => {
-                        warn!(
-                            transaction_version = txn_version,
-                            "Transaction data doesn't exist"
-                        );
-                        // PROCESSOR_UNKNOWN_TYPE_COUNT
-                        //     .with_label_values(&["EventsProcessor"])
-                        //     .inc();
-                        return vec![];
-                    },
-                };
-                let default = vec![];
-                let raw_events = match txn_data {
-                    TxnData::BlockMetadata(tx_inner) => &tx_inner.events,
-                    TxnData::Genesis(tx_inner) => &tx_inner.events,
-                    TxnData::User(tx_inner) => &tx_inner.events,
-                    TxnData::Validator(tx_inner) => &tx_inner.events,
-                    _ => &default,
-                };
+        let events = self.rayon_pool.install(|| {
+            item.data
+                .par_iter()
+                .with_min_len(MIN_TRANSACTIONS_PER_RAYON_JOB)
+                .map(|txn| {
+                    let mut events = vec![];
+                    let txn_version = txn.version as i64;
+                    let block_height = txn.block_height as i64;
+                    let txn_data = match txn.txn_data.as_ref() {
+                        Some(data) => data,
+                        None => {
+                            warn!(
+                                transaction_version = txn_version,
+                                "Transaction data doesn't exist"
+                            );
+                            // PROCESSOR_UNKNOWN_TYPE_COUNT
+                            //     .with_label_values(&["EventsProcessor"])
+                            //     .inc();
+                            return vec![];
+                        },
+                    };
+                    let default = vec![];
+                    let raw_events = match txn_data {
+                        TxnData::BlockMetadata(tx_inner) => &tx_inner.events,
+                        TxnData::Genesis(tx_inner) => &tx_inner.events,
+                        TxnData::User(tx_inner) => &tx_inner.events,
+                        TxnData::Validator(tx_inner) => &tx_inner.events,
+                        _ => &default,
+                    };
 
-                let txn_events = EventModel::from_events(raw_events, txn_version, block_height);
-                events.extend(txn_events);
-                events
-            })
-            .flatten()
-            .collect::<Vec<EventModel>>();
+                    let txn_events = EventModel::from_events(raw_events, txn_version, block_height);
+                    events.extend(txn_events);
+                    events
+                })
+                .flatten()
+                .collect::<Vec<EventModel>>()
+        });
         Ok(Some(TransactionContext {
             data: events,
             metadata: item.metadata,
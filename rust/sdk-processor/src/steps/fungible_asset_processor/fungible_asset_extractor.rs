@@ -28,12 +28,16 @@ use processor::{
         },
     },
     processors::fungible_asset_processor::parse_v2_coin,
+    utils::rayon_pool::RayonPool,
 };
 
 /// Extracts fungible asset events, metadata, balances, and v1 supply from transactions
 pub struct FungibleAssetExtractor
 where
-    Self: Sized + Send + 'static, {}
+    Self: Sized + Send + 'static,
+{
+    pub rayon_pool: RayonPool,
+}
 
 #[async_trait]
 impl Processable for FungibleAssetExtractor {
@@ -71,7 +75,7 @@ impl Processable for FungibleAssetExtractor {
             raw_current_fungible_asset_balances,
             raw_current_unified_fungible_asset_balances,
             coin_supply,
-        ) = parse_v2_coin(&transactions.data).await;
+        ) = parse_v2_coin(&transactions.data, &self.rayon_pool).await;
 
         let postgres_fungible_asset_activities: Vec<FungibleAssetActivity> =
             raw_fungible_asset_activities
@@ -71,7 +71,7 @@ impl Processable for ObjectsExtractor {
         };
 
         let (mut raw_all_objects, raw_all_current_objects) =
-            process_objects(transactions.data, &mut Some(db_connection)).await;
+            process_objects(&transactions.data, &mut Some(db_connection)).await;
 
         if self.deprecated_tables.contains(TableFlags::OBJECTS) {
             raw_all_objects.clear();
@@ -9,13 +9,13 @@ use aptos_indexer_processor_sdk::{
     utils::errors::ProcessorError,
 };
 use async_trait::async_trait;
-use chrono::NaiveDateTime;
+use chrono::SubsecRound;
 use processor::{
     db::{
         common::models::account_transaction_models::raw_account_transactions::RawAccountTransaction,
         parquet::models::account_transaction_models::parquet_account_transactions::AccountTransaction,
     },
-    utils::table_flags::TableFlags,
+    utils::{rayon_pool::RayonPool, table_flags::TableFlags, util::parse_transaction_timestamp},
 };
 use rayon::prelude::*;
 use std::collections::HashMap;
@@ -25,6 +25,7 @@ where
     Self: Processable + Send + Sized + 'static,
 {
     pub opt_in_tables: TableFlags,
+    pub rayon_pool: RayonPool,
 }
 
 type ParquetTypeMap = HashMap<ParquetTypeEnum, ParquetTypeStructs>;
@@ -39,31 +40,33 @@ impl Processable for ParquetAccountTransactionsExtractor {
         &mut self,
         transactions: TransactionContext<Self::Input>,
     ) -> anyhow::Result<Option<TransactionContext<ParquetTypeMap>>, ProcessorError> {
-        let acc_txns: Vec<AccountTransaction> = transactions
-            .data
-            .into_par_iter()
-            .map(|txn| {
-                let transaction_version = txn.version as i64;
-                let txn_timestamp = txn
-                    .timestamp
-                    .as_ref()
-                    .expect("Transaction timestamp doesn't exist!")
-                    .seconds;
-                #[allow(deprecated)]
-                let block_timestamp = NaiveDateTime::from_timestamp_opt(txn_timestamp, 0)
-                    .expect("Txn Timestamp is invalid!");
+        let acc_txns: Vec<AccountTransaction> = self
+            .rayon_pool
+            .install(|| {
+                transactions
+                    .data
+                    .into_par_iter()
+                    .map(|txn| {
+                        let transaction_version = txn.version as i64;
+                        // Stored to the second
+                        let block_timestamp = parse_transaction_timestamp(
+                            txn.timestamp.as_ref(),
+                            transaction_version,
+                        )
+                        .trunc_subsecs(0);
 
-                let accounts = RawAccountTransaction::get_accounts(&txn);
-                accounts
-                    .into_iter()
-                    .map(|account_address| AccountTransaction {
-                        txn_version: transaction_version,
-                        account_address,
-                        block_timestamp,
+                        let accounts = RawAccountTransaction::get_accounts(&txn);
+                        accounts
+                            .into_iter()
+                            .map(|account_address| AccountTransaction {
+                                txn_version: transaction_version,
+                                account_address,
+                                block_timestamp,
+                            })
+                            .collect()
                     })
-                    .collect()
+                    .collect::<Vec<Vec<AccountTransaction>>>()
             })
-            .collect::<Vec<Vec<AccountTransaction>>>()
             .into_iter()
             .flatten()
             .collect();
@@ -56,7 +56,7 @@ impl Processable for ParquetDefaultExtractor {
             raw_table_items,
             raw_current_table_items,
             raw_table_metadata,
-        ) = process_transactions(transactions.data.clone());
+        ) = process_transactions(&transactions.data);
 
         let parquet_table_items: Vec<TableItem> =
             raw_table_items.iter().map(TableItem::from_raw).collect();
@@ -75,23 +75,23 @@ impl Processable for ParquetDefaultExtractor {
             .collect();
 
         let (
-            (
-                move_resources,
-                write_set_changes,
-                parquet_transactions,
-                _parquet_table_items,
-                move_modules,
-            ),
-            _transaction_version_to_struct_count,
-        ) = process_transactions_parquet(transactions.data);
+            move_resources,
+            write_set_changes,
+            parquet_transactions,
+            _parquet_table_items,
+            move_modules,
+        ) = process_transactions_parquet(&transactions.data);
 
         // Print the size of each extracted data type
         debug!("Processed data sizes:");
-        debug!(" - MoveResources: {}", move_resources.len());
-        debug!(" - WriteSetChanges: {}", write_set_changes.len());
-        debug!(" - ParquetTransactions: {}", parquet_transactions.len());
+        debug!(" - MoveResources: {}", move_resources.data.len());
+        debug!(" - WriteSetChanges: {}", write_set_changes.data.len());
+        debug!(
+            " - ParquetTransactions: {}",
+            parquet_transactions.data.len()
+        );
         debug!(" - TableItems: {}", parquet_table_items.len());
-        debug!(" - MoveModules: {}", move_modules.len());
+        debug!(" - MoveModules: {}", move_modules.data.len());
         debug!(
             " - CurrentTableItems: {}",
             parquet_current_table_items.len()
@@ -109,17 +109,17 @@ impl Processable for ParquetDefaultExtractor {
             (
                 TableFlags::MOVE_RESOURCES,
                 ParquetTypeEnum::MoveResources,
-                ParquetTypeStructs::MoveResource(move_resources),
+                ParquetTypeStructs::MoveResource(move_resources.data),
             ),
             (
                 TableFlags::WRITE_SET_CHANGES,
                 ParquetTypeEnum::WriteSetChanges,
-                ParquetTypeStructs::WriteSetChange(write_set_changes),
+                ParquetTypeStructs::WriteSetChange(write_set_changes.data),
             ),
             (
                 TableFlags::TRANSACTIONS,
                 ParquetTypeEnum::Transactions,
-                ParquetTypeStructs::Transaction(parquet_transactions),
+                ParquetTypeStructs::Transaction(parquet_transactions.data),
             ),
             (
                 TableFlags::TABLE_ITEMS,
@@ -129,7 +129,7 @@ impl Processable for ParquetDefaultExtractor {
             (
                 TableFlags::MOVE_MODULES,
                 ParquetTypeEnum::MoveModules,
-                ParquetTypeStructs::MoveModule(move_modules),
+                ParquetTypeStructs::MoveModule(move_modules.data),
             ),
             (
                 TableFlags::CURRENT_TABLE_ITEMS,
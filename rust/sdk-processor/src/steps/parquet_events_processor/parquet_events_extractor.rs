@@ -35,7 +35,7 @@ impl Processable for ParquetEventsExtractor {
         &mut self,
         transactions: TransactionContext<Self::Input>,
     ) -> anyhow::Result<Option<TransactionContext<ParquetTypeMap>>, ProcessorError> {
-        let (_txn_ver_map, events) = process_transactions_parquet(transactions.data);
+        let (_txn_ver_map, events) = process_transactions_parquet(&transactions.data);
 
         let mut map: HashMap<ParquetTypeEnum, ParquetTypeStructs> = HashMap::new();
 
@@ -29,7 +29,7 @@ use processor::{
         },
     },
     processors::fungible_asset_processor::parse_v2_coin,
-    utils::table_flags::TableFlags,
+    utils::{rayon_pool::RayonPool, table_flags::TableFlags},
 };
 use std::collections::HashMap;
 use tracing::debug;
@@ -40,6 +40,7 @@ where
     Self: Processable + Send + Sized + 'static,
 {
     pub opt_in_tables: TableFlags,
+    pub rayon_pool: RayonPool,
 }
 
 type ParquetTypeMap = HashMap<ParquetTypeEnum, ParquetTypeStructs>;
@@ -61,7 +62,7 @@ impl Processable for ParquetFungibleAssetExtractor {
             raw_current_fungible_asset_balances,
             raw_current_unified_fungible_asset_balances,
             _raw_coin_supply,
-        ) = parse_v2_coin(&transactions.data).await;
+        ) = parse_v2_coin(&transactions.data, &self.rayon_pool).await;
 
         let parquet_fungible_asset_activities: Vec<FungibleAssetActivity> =
             raw_fungible_asset_activities
@@ -42,7 +42,7 @@ impl Processable for ParquetObjectsExtractor {
         transactions: TransactionContext<Self::Input>,
     ) -> anyhow::Result<Option<TransactionContext<ParquetTypeMap>>, ProcessorError> {
         let (raw_all_objects, raw_all_current_objects) =
-            process_objects(transactions.data, &mut None).await;
+            process_objects(&transactions.data, &mut None).await;
         let parquet_objects: Vec<Object> =
             raw_all_objects.into_iter().map(Object::from_raw).collect();
 
@@ -39,7 +39,7 @@ impl Processable for ParquetTransactionMetadataExtractor {
     ) -> anyhow::Result<Option<TransactionContext<ParquetTypeMap>>, ProcessorError> {
         let mut transaction_version_to_struct_count: AHashMap<i64, i64> = AHashMap::new();
         let write_set_size =
-            process_transaction(transactions.data, &mut transaction_version_to_struct_count);
+            process_transaction(&transactions.data, &mut transaction_version_to_struct_count);
 
         debug!("Processed data sizes:");
         debug!(" - WriteSetSize: {}", write_set_size.len());
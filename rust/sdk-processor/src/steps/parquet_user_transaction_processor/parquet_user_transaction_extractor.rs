@@ -36,7 +36,7 @@ impl Processable for ParquetUserTransactionExtractor {
         &mut self,
         transactions: TransactionContext<Self::Input>,
     ) -> anyhow::Result<Option<TransactionContext<ParquetTypeMap>>, ProcessorError> {
-        let (user_txns, _) = process_transactions(transactions.data.clone()).await;
+        let (user_txns, _) = process_transactions(&transactions.data).await;
 
         // Print the size of each extracted data type
         debug!("Processed data sizes:");
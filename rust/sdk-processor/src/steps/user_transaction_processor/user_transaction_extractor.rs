@@ -40,7 +40,7 @@ impl Processable for UserTransactionExtractor {
         ProcessorError,
     > {
         let (user_transactions, signatures) =
-            user_transaction_parse(item.data, self.deprecated_tables);
+            user_transaction_parse(&item.data, self.deprecated_tables);
         Ok(Some(TransactionContext {
             data: (user_transactions, signatures),
             metadata: item.metadata,
@@ -1,21 +1,137 @@
 // Copyright © Aptos Foundation
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 #[cfg(target_os = "linux")]
 use aptos_system_utils::profiling::start_cpu_profiling;
 use backtrace::Backtrace;
 use clap::Parser;
+use futures::{stream::FuturesUnordered, FutureExt, StreamExt};
 use prometheus::{Encoder, TextEncoder};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 #[cfg(target_os = "linux")]
 use std::convert::Infallible;
 // TODO: remove deprecated lint when new clippy nightly is released
 #[allow(deprecated)]
-use std::{fs::File, io::Read, panic::PanicInfo, path::PathBuf, process};
+use std::{
+    fs::File,
+    io::Read,
+    panic::{AssertUnwindSafe, PanicInfo},
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+};
 use tokio::runtime::Handle;
 use tracing::error;
-use tracing_subscriber::EnvFilter;
-use warp::{http::Response, Filter};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use warp::{http::Response, Filter, Reply};
+
+pub mod open_metrics;
+pub mod trace_id;
+
+type StatusProvider = Box<dyn Fn() -> serde_json::Value + Send + Sync>;
+
+static STATUS_PROVIDERS: Mutex<Vec<StatusProvider>> = Mutex::new(Vec::new());
+
+static MIGRATIONS_PROVIDERS: Mutex<Vec<StatusProvider>> = Mutex::new(Vec::new());
+
+static REPLAY_HANDLER: OnceLock<Arc<dyn ReplayHandler>> = OnceLock::new();
+
+static COVERAGE_HANDLER: OnceLock<Arc<dyn CoverageHandler>> = OnceLock::new();
+
+/// Whether the panic handler exits the process. Turned off when servers are isolated from each
+/// other's failures.
+static EXIT_ON_PANIC: AtomicBool = AtomicBool::new(true);
+
+/// Registers a function that produces the JSON served on `/status`, so services can expose
+/// their live state without requiring Prometheus. Each of several services in the process
+/// registers its own, see `provided_json`.
+pub fn register_status_provider(provider: impl Fn() -> serde_json::Value + Send + Sync + 'static) {
+    STATUS_PROVIDERS.lock().unwrap().push(Box::new(provider));
+}
+
+/// Registers a function that produces the JSON served on `/migrations`, e.g. which schema
+/// migrations have run and which are pending. Same semantics as `register_status_provider`.
+pub fn register_migrations_provider(
+    provider: impl Fn() -> serde_json::Value + Send + Sync + 'static,
+) {
+    MIGRATIONS_PROVIDERS
+        .lock()
+        .unwrap()
+        .push(Box::new(provider));
+}
+
+/// What the registered providers serve: a single provider's JSON as is, otherwise a list of
+/// every provider's, with the lists some of them serve flattened into it. None without any.
+fn provided_json(providers: &Mutex<Vec<StatusProvider>>) -> Option<serde_json::Value> {
+    let providers = providers.lock().unwrap();
+    match providers.as_slice() {
+        [] => None,
+        [provider] => Some(provider()),
+        providers => Some(serde_json::Value::Array(
+            providers
+                .iter()
+                .flat_map(|provider| match provider() {
+                    serde_json::Value::Array(values) => values,
+                    value => vec![value],
+                })
+                .collect(),
+        )),
+    }
+}
+
+/// Reprocessing of version ranges on demand, served on `/replay`.
+pub trait ReplayHandler: Send + Sync {
+    /// Queues a replay of `start_version` to `end_version` inclusive, returning the JSON to
+    /// reply with, or why it can't be queued.
+    fn enqueue(&self, start_version: u64, end_version: u64) -> Result<serde_json::Value>;
+
+    /// The JSON served on `GET /replay`, e.g. the queued, running and finished replays.
+    fn status(&self) -> serde_json::Value;
+}
+
+/// Registers what serves `/replay`: `POST` with `{"start_version": 1, "end_version": 2}` queues
+/// a replay and `GET` lists them. Only the first registration takes effect; returns false if a
+/// handler was already registered.
+pub fn register_replay_handler(handler: Arc<dyn ReplayHandler>) -> bool {
+    REPLAY_HANDLER.set(handler).is_ok()
+}
+
+#[derive(Deserialize)]
+struct ReplayRequest {
+    start_version: u64,
+    end_version: u64,
+}
+
+/// Which versions have been written to a table, served on `/coverage`.
+#[async_trait::async_trait]
+pub trait CoverageHandler: Send + Sync {
+    /// The JSON served on
+    /// `GET /coverage?table=<table>&start_version=<start>&end_version=<end>`, for `processor` if
+    /// given, or why it can't be computed.
+    async fn coverage(
+        &self,
+        processor: Option<String>,
+        table: String,
+        start_version: u64,
+        end_version: u64,
+    ) -> Result<serde_json::Value>;
+}
+
+/// Registers what serves `/coverage`. Same semantics as `register_replay_handler`.
+pub fn register_coverage_handler(handler: Arc<dyn CoverageHandler>) -> bool {
+    COVERAGE_HANDLER.set(handler).is_ok()
+}
+
+#[derive(Deserialize)]
+struct CoverageRequest {
+    processor: Option<String>,
+    table: String,
+    start_version: u64,
+    end_version: u64,
+}
 
 /// ServerArgs bootstraps a server with all common pieces. And then triggers the run method for
 /// the specific service.
@@ -45,9 +161,14 @@ where
     C: RunnableConfig,
 {
     let health_port = config.health_check_port;
+    let metrics_port = config.metrics_port;
+    let metrics_format = config.metrics_format;
+    if config.on_server_failure == OnServerFailure::Continue {
+        EXIT_ON_PANIC.store(false, Ordering::SeqCst);
+    }
     // Start liveness and readiness probes.
     let task_handler = handle.spawn(async move {
-        register_probes_and_metrics_handler(health_port).await;
+        register_probes_and_metrics_handler(health_port, metrics_port, metrics_format).await;
         anyhow::Ok(())
     });
     let main_task_handler = handle.spawn(async move { config.run().await });
@@ -66,8 +187,75 @@ pub struct GenericConfig<T> {
     // Shared configuration among all services.
     pub health_check_port: u16,
 
-    // Specific configuration for each service.
-    pub server_config: T,
+    // Serve `/metrics` on this port instead of `health_check_port`
+    #[serde(default)]
+    pub metrics_port: Option<u16>,
+
+    // Format of `/metrics` for scrapers that don't ask for OpenMetrics in their `Accept` header
+    #[serde(default)]
+    pub metrics_format: MetricsFormat,
+
+    // Specific configuration for each service. At least one of `server_config` and
+    // `server_configs` has to be set.
+    #[serde(default = "Option::default")]
+    pub server_config: Option<T>,
+
+    // Several services to run in the same process, e.g. one per processor
+    #[serde(default = "Vec::new")]
+    pub server_configs: Vec<ServerConfigEntry<T>>,
+
+    // What to do when one of several services fails
+    #[serde(default)]
+    pub on_server_failure: OnServerFailure,
+}
+
+#[derive(Deserialize, Debug, Serialize)]
+pub struct ServerConfigEntry<T> {
+    #[serde(default = "default_server_enabled")]
+    pub enabled: bool,
+    pub config: T,
+}
+
+const fn default_server_enabled() -> bool {
+    true
+}
+
+/// What happens to the other services when one of them returns an error or panics.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnServerFailure {
+    /// Exit the process, as with a single service.
+    #[default]
+    Exit,
+    /// Log the failure and keep the others running. The process only exits once all of them
+    /// have stopped. Panics no longer exit the process, so a service has to watch the tasks it
+    /// spawns and return an error when one of them dies, or it keeps running without it.
+    Continue,
+}
+
+/// Format of `/metrics`. Scrapers that ask for OpenMetrics in their `Accept` header get it either
+/// way.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetricsFormat {
+    /// The Prometheus text format, without exemplars.
+    #[default]
+    Text,
+    /// The OpenMetrics text format, with the exemplars recorded through
+    /// `open_metrics::record_exemplar`.
+    OpenMetrics,
+}
+
+impl<T> GenericConfig<T> {
+    /// `server_config` followed by every enabled entry of `server_configs`.
+    pub fn enabled_server_configs(&self) -> impl Iterator<Item = &T> {
+        self.server_config.iter().chain(
+            self.server_configs
+                .iter()
+                .filter(|entry| entry.enabled)
+                .map(|entry| &entry.config),
+        )
+    }
 }
 
 #[async_trait::async_trait]
@@ -75,12 +263,69 @@ impl<T> RunnableConfig for GenericConfig<T>
 where
     T: RunnableConfig,
 {
+    /// Runs every enabled service concurrently, each with its own `run`, once they're checked
+    /// to agree on the settings that apply to the whole process.
     async fn run(&self) -> Result<()> {
-        self.server_config.run().await
+        let mut server_configs = self.enabled_server_configs();
+        if let Some(first) = server_configs.next() {
+            let process_wide_settings = first.process_wide_settings();
+            for server_config in server_configs {
+                if server_config.process_wide_settings() != process_wide_settings {
+                    bail!(
+                        "{} and {} run in the same process, so they must have the same \
+                         process-wide settings, got {} and {}",
+                        first.get_server_name(),
+                        server_config.get_server_name(),
+                        process_wide_settings,
+                        server_config.process_wide_settings()
+                    );
+                }
+            }
+        }
+        let mut runs: FuturesUnordered<_> = self
+            .enabled_server_configs()
+            .map(|server_config| async move {
+                let res = AssertUnwindSafe(server_config.run()).catch_unwind().await;
+                (server_config.get_server_name(), res)
+            })
+            .collect();
+        let num_servers = runs.len();
+        if num_servers == 0 {
+            bail!("No enabled server config, set server_config or enable one in server_configs");
+        }
+
+        let mut num_failed = 0;
+        while let Some((server_name, res)) = runs.next().await {
+            let err = match res {
+                Ok(Ok(())) => continue,
+                Ok(Err(err)) => err,
+                Err(_) => anyhow::anyhow!("Panicked"),
+            };
+            match self.on_server_failure {
+                OnServerFailure::Exit => {
+                    return Err(err.context(format!("{} failed", server_name)));
+                },
+                OnServerFailure::Continue => {
+                    error!(
+                        server_name = server_name,
+                        error = ?err,
+                        "Server failed, the others keep running"
+                    );
+                    num_failed += 1;
+                },
+            }
+        }
+        if num_failed == num_servers {
+            bail!("All {} servers failed", num_servers);
+        }
+        Ok(())
     }
 
     fn get_server_name(&self) -> String {
-        self.server_config.get_server_name()
+        self.enabled_server_configs()
+            .next()
+            .map(|server_config| server_config.get_server_name())
+            .unwrap_or_default()
     }
 }
 
@@ -89,6 +334,12 @@ where
 pub trait RunnableConfig: DeserializeOwned + Send + Sync + 'static {
     async fn run(&self) -> Result<()>;
     fn get_server_name(&self) -> String;
+
+    /// Settings the service applies to the whole process rather than to itself, e.g. a metrics
+    /// prefix. Services run in the same process through `server_configs` must agree on them.
+    fn process_wide_settings(&self) -> serde_json::Value {
+        serde_json::Value::Null
+    }
 }
 
 /// Parse a yaml file into a struct.
@@ -134,8 +385,10 @@ fn handle_panic(panic_info: &PanicInfo<'_>) {
     // TODO / HACK ALARM: Write crash info synchronously via eprintln! to ensure it is written before the process exits which error! doesn't guarantee.
     // This is a workaround until https://github.com/aptos-labs/aptos-core/issues/2038 is resolved.
     eprintln!("{}", crash_info);
-    // Kill the process
-    process::exit(12);
+    // Kill the process, unless the other servers should keep running
+    if EXIT_ON_PANIC.load(Ordering::SeqCst) {
+        process::exit(12);
+    }
 }
 
 /// Set up logging for the server.
@@ -151,31 +404,139 @@ pub fn setup_logging() {
         .with_target(false)
         .with_thread_names(true)
         .with_env_filter(env_filter)
+        .finish()
+        .with(trace_id::TraceIdLayer)
         .init();
 }
 
-/// Register readiness and liveness probes and set up metrics endpoint.
-async fn register_probes_and_metrics_handler(port: u16) {
+/// Register readiness and liveness probes and set up metrics endpoint. Metrics are served on
+/// `metrics_port` if it's set, otherwise on the same port as the probes.
+async fn register_probes_and_metrics_handler(
+    port: u16,
+    metrics_port: Option<u16>,
+    metrics_format: MetricsFormat,
+) {
     let readiness = warp::path("readiness")
         .map(move || warp::reply::with_status("ready", warp::http::StatusCode::OK));
-    let metrics_endpoint = warp::path("metrics").map(|| {
-        // Metrics encoding.
-        let metrics = prometheus::gather();
-        let mut encode_buffer = vec![];
-        let encoder = TextEncoder::new();
-        // If metrics encoding fails, we want to panic and crash the process.
-        encoder
-            .encode(&metrics, &mut encode_buffer)
-            .context("Failed to encode metrics")
-            .unwrap();
-
-        Response::builder()
-            .header("Content-Type", "text/plain")
-            .body(encode_buffer)
+    let metrics_endpoint = warp::path("metrics")
+        .and(warp::header::optional::<String>("accept"))
+        .map(move |accept: Option<String>| {
+            // Metrics encoding.
+            let metrics = prometheus::gather();
+            let open_metrics_accepted =
+                accept.is_some_and(|accept| open_metrics::is_accepted(&accept));
+            if metrics_format == MetricsFormat::OpenMetrics || open_metrics_accepted {
+                return Response::builder()
+                    .header("Content-Type", open_metrics::CONTENT_TYPE)
+                    .body(open_metrics::encode(&metrics).into_bytes());
+            }
+            let mut encode_buffer = vec![];
+            let encoder = TextEncoder::new();
+            // If metrics encoding fails, we want to panic and crash the process.
+            encoder
+                .encode(&metrics, &mut encode_buffer)
+                .context("Failed to encode metrics")
+                .unwrap();
+
+            Response::builder()
+                .header("Content-Type", "text/plain")
+                .body(encode_buffer)
+        });
+
+    let status_endpoint = warp::path("status").map(|| match provided_json(&STATUS_PROVIDERS) {
+        Some(status) => {
+            warp::reply::with_status(warp::reply::json(&status), warp::http::StatusCode::OK)
+        },
+        None => warp::reply::with_status(
+            warp::reply::json(&serde_json::json!({ "error": "No status available" })),
+            warp::http::StatusCode::NOT_FOUND,
+        ),
     });
 
-    if cfg!(target_os = "linux") {
-        #[cfg(target_os = "linux")]
+    let migrations_endpoint =
+        warp::path("migrations").map(|| match provided_json(&MIGRATIONS_PROVIDERS) {
+            Some(migrations) => {
+                warp::reply::with_status(warp::reply::json(&migrations), warp::http::StatusCode::OK)
+            },
+            None => warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": "No migrations available" })),
+                warp::http::StatusCode::NOT_FOUND,
+            ),
+        });
+
+    let replay_status_endpoint = warp::path("replay")
+        .and(warp::get())
+        .map(|| match REPLAY_HANDLER.get() {
+            Some(handler) => warp::reply::with_status(
+                warp::reply::json(&handler.status()),
+                warp::http::StatusCode::OK,
+            ),
+            None => warp::reply::with_status(
+                warp::reply::json(&serde_json::json!({ "error": "Replays are not enabled" })),
+                warp::http::StatusCode::NOT_FOUND,
+            ),
+        });
+
+    let replay_endpoint = warp::path("replay")
+        .and(warp::post())
+        .and(warp::body::json())
+        .map(|request: ReplayRequest| {
+            let Some(handler) = REPLAY_HANDLER.get() else {
+                return warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "error": "Replays are not enabled" })),
+                    warp::http::StatusCode::NOT_FOUND,
+                );
+            };
+            match handler.enqueue(request.start_version, request.end_version) {
+                Ok(reply) => warp::reply::with_status(
+                    warp::reply::json(&reply),
+                    warp::http::StatusCode::ACCEPTED,
+                ),
+                Err(e) => warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "error": e.to_string() })),
+                    warp::http::StatusCode::BAD_REQUEST,
+                ),
+            }
+        });
+
+    let coverage_endpoint = warp::path("coverage")
+        .and(warp::get())
+        .and(warp::query::<CoverageRequest>())
+        .then(|request: CoverageRequest| async move {
+            let Some(handler) = COVERAGE_HANDLER.get() else {
+                return warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "error": "No coverage available" })),
+                    warp::http::StatusCode::NOT_FOUND,
+                );
+            };
+            let coverage = handler.coverage(
+                request.processor,
+                request.table,
+                request.start_version,
+                request.end_version,
+            );
+            match coverage.await {
+                Ok(reply) => {
+                    warp::reply::with_status(warp::reply::json(&reply), warp::http::StatusCode::OK)
+                },
+                Err(e) => warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "error": format!("{:#}", e) })),
+                    warp::http::StatusCode::BAD_REQUEST,
+                ),
+            }
+        });
+
+    let probes = readiness
+        .or(status_endpoint)
+        .or(migrations_endpoint)
+        .or(replay_status_endpoint)
+        .or(replay_endpoint)
+        .or(coverage_endpoint)
+        .map(|reply| Box::new(reply) as Box<dyn Reply>)
+        .boxed();
+
+    #[cfg(target_os = "linux")]
+    let probes = {
         let profilez = warp::path("profilez").and_then(|| async move {
             // TODO(grao): Consider make the parameters configurable.
             Ok::<_, Infallible>(match start_cpu_profiling(10, 99, false).await {
@@ -200,22 +561,36 @@ async fn register_probes_and_metrics_handler(port: u16) {
                 ),
             })
         });
-        #[cfg(target_os = "linux")]
-        warp::serve(readiness.or(metrics_endpoint).or(profilez))
-            .run(([0, 0, 0, 0], port))
-            .await;
-    } else {
-        warp::serve(readiness.or(metrics_endpoint))
-            .run(([0, 0, 0, 0], port))
-            .await;
+        probes
+            .or(profilez)
+            .map(|reply| Box::new(reply) as Box<dyn Reply>)
+            .boxed()
+    };
+
+    match metrics_port.filter(|metrics_port| *metrics_port != port) {
+        Some(metrics_port) => {
+            tokio::join!(
+                warp::serve(probes).run(([0, 0, 0, 0], port)),
+                warp::serve(metrics_endpoint).run(([0, 0, 0, 0], metrics_port)),
+            );
+        },
+        None => {
+            warp::serve(probes.or(metrics_endpoint))
+                .run(([0, 0, 0, 0], port))
+                .await;
+        },
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
+    use std::{io::Write, net::TcpListener, time::Duration};
     use tempfile::tempdir;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
 
     #[derive(Clone, Debug, Deserialize, Serialize)]
     #[serde(deny_unknown_fields)]
@@ -253,8 +628,240 @@ mod tests {
 
         let config = load::<GenericConfig<TestConfig>>(&file_path).unwrap();
         assert_eq!(config.health_check_port, 12345);
-        assert_eq!(config.server_config.test, 123);
-        assert_eq!(config.server_config.test_name, "test");
+        let server_config = config.server_config.unwrap();
+        assert_eq!(server_config.test, 123);
+        assert_eq!(server_config.test_name, "test");
+    }
+
+    #[test]
+    fn test_multiple_server_configs() {
+        let config = serde_yaml::from_str::<GenericConfig<TestConfig>>(
+            r#"
+            health_check_port: 12345
+            server_configs:
+                - config:
+                    test: 123
+                    test_name: "first"
+                - enabled: false
+                  config:
+                    test: 123
+                    test_name: "second"
+                - enabled: true
+                  config:
+                    test: 123
+                    test_name: "third"
+            on_server_failure: continue
+        "#,
+        )
+        .unwrap();
+        let server_names: Vec<String> = config
+            .enabled_server_configs()
+            .map(|server_config| server_config.get_server_name())
+            .collect();
+        assert_eq!(server_names, vec!["first", "third"]);
+        assert_eq!(config.on_server_failure, OnServerFailure::Continue);
+    }
+
+    static PREFIXED_RUNS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+    /// A service with a process-wide `prefix`, registering its name as its status.
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct PrefixedConfig {
+        name: String,
+        prefix: String,
+    }
+
+    #[async_trait::async_trait]
+    impl RunnableConfig for PrefixedConfig {
+        async fn run(&self) -> Result<()> {
+            let name = self.name.clone();
+            register_status_provider(move || serde_json::json!({ "name": name }));
+            PREFIXED_RUNS.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn get_server_name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn process_wide_settings(&self) -> serde_json::Value {
+            serde_json::json!({ "prefix": self.prefix })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_multiple_server_configs() {
+        let config = |second_prefix: &str| {
+            serde_yaml::from_str::<GenericConfig<PrefixedConfig>>(&format!(
+                r#"
+                health_check_port: 12345
+                server_configs:
+                    - config:
+                        name: "first"
+                        prefix: "a"
+                    - config:
+                        name: "second"
+                        prefix: "{}"
+                "#,
+                second_prefix
+            ))
+            .unwrap()
+        };
+
+        // Nothing runs if they disagree on a process-wide setting
+        assert!(config("b").run().await.is_err());
+        assert_eq!(PREFIXED_RUNS.load(Ordering::SeqCst), 0);
+        assert_eq!(provided_json(&STATUS_PROVIDERS), None);
+
+        config("a").run().await.unwrap();
+        assert_eq!(PREFIXED_RUNS.load(Ordering::SeqCst), 2);
+        let mut names = provided_json(&STATUS_PROVIDERS)
+            .unwrap()
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|status| status["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["first", "second"]);
+    }
+
+    /// Distinct ports nothing is listening on.
+    fn free_ports<const N: usize>() -> [u16; N] {
+        let listeners = [(); N].map(|_| TcpListener::bind("127.0.0.1:0").unwrap());
+        listeners.map(|listener| listener.local_addr().unwrap().port())
+    }
+
+    /// Status code of `GET path`, waiting for the server to come up.
+    async fn get_status(port: u16, path: &str) -> u16 {
+        let response = get(port, path, "").await;
+        response.split(' ').nth(1).unwrap().parse().unwrap()
+    }
+
+    /// Full response to `GET path` with the extra `headers`, waiting for the server to come up.
+    async fn get(port: u16, path: &str, headers: &str) -> String {
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}\r\n",
+            path, headers
+        );
+        send(port, &request).await
+    }
+
+    /// Full response to `request`, waiting for the server to come up.
+    async fn send(port: u16, request: &str) -> String {
+        let mut stream = loop {
+            match TcpStream::connect(("127.0.0.1", port)).await {
+                Ok(stream) => break stream,
+                Err(_) => tokio::time::sleep(Duration::from_millis(10)).await,
+            }
+        };
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_metrics_port() {
+        let [health_port, metrics_port, other_health_port] = free_ports();
+        tokio::spawn(register_probes_and_metrics_handler(
+            health_port,
+            Some(metrics_port),
+            MetricsFormat::Text,
+        ));
+        assert_eq!(get_status(health_port, "/readiness").await, 200);
+        assert_eq!(get_status(health_port, "/metrics").await, 404);
+        assert_eq!(get_status(metrics_port, "/metrics").await, 200);
+        assert_eq!(get_status(metrics_port, "/readiness").await, 404);
+
+        // Unset, both are on the health check port
+        tokio::spawn(register_probes_and_metrics_handler(
+            other_health_port,
+            None,
+            MetricsFormat::Text,
+        ));
+        assert_eq!(get_status(other_health_port, "/readiness").await, 200);
+        assert_eq!(get_status(other_health_port, "/metrics").await, 200);
+    }
+
+    #[tokio::test]
+    async fn test_open_metrics_negotiation() {
+        let [text_port, open_metrics_port] = free_ports();
+        tokio::spawn(register_probes_and_metrics_handler(
+            text_port,
+            None,
+            MetricsFormat::Text,
+        ));
+        let open_metrics_content_type = format!("content-type: {}", open_metrics::CONTENT_TYPE);
+        let response = get(text_port, "/metrics", "").await;
+        assert!(response.contains("content-type: text/plain\r\n"));
+        let accept = "Accept: application/openmetrics-text;version=1.0.0,text/plain;q=0.5\r\n";
+        let response = get(text_port, "/metrics", accept).await;
+        assert!(response.contains(&open_metrics_content_type));
+        assert!(response.ends_with("# EOF\n"));
+
+        // Configured, scrapers that don't ask for it get it too
+        tokio::spawn(register_probes_and_metrics_handler(
+            open_metrics_port,
+            None,
+            MetricsFormat::OpenMetrics,
+        ));
+        let response = get(open_metrics_port, "/metrics", "").await;
+        assert!(response.contains(&open_metrics_content_type));
+    }
+
+    /// Queues ranges that start at or before their end.
+    #[derive(Default)]
+    struct TestReplayHandler {
+        replays: std::sync::Mutex<Vec<(u64, u64)>>,
+    }
+
+    impl ReplayHandler for TestReplayHandler {
+        fn enqueue(&self, start_version: u64, end_version: u64) -> Result<serde_json::Value> {
+            if start_version > end_version {
+                bail!("start_version is after end_version");
+            }
+            let mut replays = self.replays.lock().unwrap();
+            replays.push((start_version, end_version));
+            Ok(serde_json::json!({ "id": replays.len() }))
+        }
+
+        fn status(&self) -> serde_json::Value {
+            serde_json::json!(*self.replays.lock().unwrap())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replay_endpoint() {
+        let [port] = free_ports();
+        tokio::spawn(register_probes_and_metrics_handler(
+            port,
+            None,
+            MetricsFormat::Text,
+        ));
+        let post = |body: &str| {
+            format!(
+                "POST /replay HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+                 Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            )
+        };
+        let request = post(r#"{"start_version": 10, "end_version": 20}"#);
+        assert!(send(port, &request).await.starts_with("HTTP/1.1 404"));
+
+        assert!(register_replay_handler(Arc::new(
+            TestReplayHandler::default()
+        )));
+        let response = send(port, &request).await;
+        assert!(response.starts_with("HTTP/1.1 202"));
+        assert!(response.ends_with(r#"{"id":1}"#));
+        let request = post(r#"{"start_version": 20, "end_version": 10}"#);
+        let response = send(port, &request).await;
+        assert!(response.starts_with("HTTP/1.1 400"));
+        assert!(response.ends_with(r#"{"error":"start_version is after end_version"}"#));
+        let response = get(port, "/replay", "").await;
+        assert!(response.ends_with("[[10,20]]"));
     }
 
     #[test]
@@ -0,0 +1,286 @@
+// Copyright © Aptos Foundation
+
+//! `/metrics` in the OpenMetrics text format, which unlike the Prometheus text format can carry
+//! exemplars: labels attached to a histogram bucket pointing at one of the observations in it,
+//! e.g. the batch behind a slow processing time.
+
+use prometheus::proto::{LabelPair, Metric, MetricFamily, MetricType};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// Labels of a series, sorted by name.
+type SeriesLabels = Vec<(String, String)>;
+
+struct Exemplar {
+    labels: Vec<(String, String)>,
+    value: f64,
+    timestamp_secs: f64,
+}
+
+/// Latest exemplar of each histogram series, by metric name and series labels.
+static EXEMPLARS: Mutex<BTreeMap<(String, SeriesLabels), Exemplar>> = Mutex::new(BTreeMap::new());
+
+/// Attaches `exemplar_labels` to the observation `value` of the histogram `name` with the series
+/// labels `labels`. Only the latest exemplar of each series is kept, and it's served on the
+/// bucket `value` falls into.
+pub fn record_exemplar(
+    name: &str,
+    labels: &[(&str, &str)],
+    value: f64,
+    exemplar_labels: &[(&str, &str)],
+) {
+    let timestamp_secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let exemplar = Exemplar {
+        labels: to_owned_labels(exemplar_labels),
+        value,
+        timestamp_secs,
+    };
+    let mut series_labels = to_owned_labels(labels);
+    series_labels.sort();
+    EXEMPLARS
+        .lock()
+        .unwrap()
+        .insert((name.to_string(), series_labels), exemplar);
+}
+
+fn to_owned_labels(labels: &[(&str, &str)]) -> Vec<(String, String)> {
+    labels
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect()
+}
+
+/// Whether a scraper's `Accept` header asks for OpenMetrics, as Prometheus does. It's preferred
+/// over the Prometheus text format wherever it's listed.
+pub fn is_accepted(accept: &str) -> bool {
+    accept.contains("application/openmetrics-text")
+}
+
+/// Encodes the gathered metrics along with the recorded exemplars. Counters whose name doesn't
+/// end in `_total` are served as `unknown`, since OpenMetrics would otherwise rename them.
+pub fn encode(metric_families: &[MetricFamily]) -> String {
+    let exemplars = EXEMPLARS.lock().unwrap();
+    let mut out = String::new();
+    for family in metric_families {
+        let name = family.get_name();
+        let (family_name, metric_type) = match family.get_field_type() {
+            MetricType::COUNTER => match name.strip_suffix("_total") {
+                Some(stripped) => (stripped, "counter"),
+                None => (name, "unknown"),
+            },
+            MetricType::GAUGE => (name, "gauge"),
+            MetricType::HISTOGRAM => (name, "histogram"),
+            MetricType::SUMMARY => (name, "summary"),
+            MetricType::UNTYPED => (name, "unknown"),
+        };
+        let help = family.get_help().replace('\\', r"\\").replace('\n', r"\n");
+        writeln!(out, "# TYPE {} {}", family_name, metric_type).unwrap();
+        writeln!(out, "# HELP {} {}", family_name, help).unwrap();
+        for metric in family.get_metric() {
+            let labels = metric.get_label();
+            match family.get_field_type() {
+                MetricType::COUNTER => {
+                    let value = metric.get_counter().get_value();
+                    write_sample(&mut out, name, labels, None, value);
+                },
+                MetricType::GAUGE => {
+                    let value = metric.get_gauge().get_value();
+                    write_sample(&mut out, name, labels, None, value);
+                },
+                MetricType::UNTYPED => {
+                    let value = metric.get_untyped().get_value();
+                    write_sample(&mut out, name, labels, None, value);
+                },
+                MetricType::HISTOGRAM => {
+                    let key = (name.to_string(), series_labels(metric));
+                    write_histogram(&mut out, name, metric, exemplars.get(&key));
+                },
+                MetricType::SUMMARY => {
+                    let summary = metric.get_summary();
+                    for quantile in summary.get_quantile() {
+                        let extra = ("quantile", format_value(quantile.get_quantile()));
+                        write_sample(&mut out, name, labels, Some(extra), quantile.get_value());
+                    }
+                    let count = summary.get_sample_count() as f64;
+                    write_sample(&mut out, &format!("{}_count", name), labels, None, count);
+                    let sum = summary.get_sample_sum();
+                    write_sample(&mut out, &format!("{}_sum", name), labels, None, sum);
+                },
+            }
+        }
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn series_labels(metric: &Metric) -> SeriesLabels {
+    let mut labels: SeriesLabels = metric
+        .get_label()
+        .iter()
+        .map(|label| (label.get_name().to_string(), label.get_value().to_string()))
+        .collect();
+    labels.sort();
+    labels
+}
+
+fn write_histogram(out: &mut String, name: &str, metric: &Metric, exemplar: Option<&Exemplar>) {
+    let histogram = metric.get_histogram();
+    let labels = metric.get_label();
+    let bucket_name = format!("{}_bucket", name);
+    // The exemplar goes on the first bucket that counts its observation
+    let exemplar_bucket = exemplar.map(|exemplar| {
+        histogram
+            .get_bucket()
+            .iter()
+            .position(|bucket| exemplar.value <= bucket.get_upper_bound())
+            .unwrap_or(histogram.get_bucket().len())
+    });
+    let inf_bucket = (f64::INFINITY, histogram.get_sample_count());
+    let buckets = histogram
+        .get_bucket()
+        .iter()
+        .map(|bucket| (bucket.get_upper_bound(), bucket.get_cumulative_count()))
+        .chain(std::iter::once(inf_bucket));
+    for (index, (upper_bound, count)) in buckets.enumerate() {
+        let le = ("le", format_value(upper_bound));
+        write_sample(out, &bucket_name, labels, Some(le), count as f64);
+        if exemplar_bucket == Some(index) {
+            let exemplar = exemplar.unwrap();
+            // Replace the newline of the bucket line with the exemplar
+            out.pop();
+            write!(out, " # ").unwrap();
+            let exemplar_labels = exemplar
+                .labels
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.clone()));
+            write_labels(out, exemplar_labels);
+            writeln!(
+                out,
+                " {} {:.3}",
+                format_value(exemplar.value),
+                exemplar.timestamp_secs
+            )
+            .unwrap();
+        }
+    }
+    let count = histogram.get_sample_count() as f64;
+    write_sample(out, &format!("{}_count", name), labels, None, count);
+    let sum = histogram.get_sample_sum();
+    write_sample(out, &format!("{}_sum", name), labels, None, sum);
+}
+
+fn write_sample(
+    out: &mut String,
+    name: &str,
+    labels: &[LabelPair],
+    extra_label: Option<(&str, String)>,
+    value: f64,
+) {
+    out.push_str(name);
+    let labels: Vec<_> = labels
+        .iter()
+        .map(|label| (label.get_name(), label.get_value().to_string()))
+        .chain(extra_label)
+        .collect();
+    if !labels.is_empty() {
+        write_labels(out, labels.into_iter());
+    }
+    writeln!(out, " {}", format_value(value)).unwrap();
+}
+
+fn write_labels<'a>(out: &mut String, labels: impl Iterator<Item = (&'a str, String)>) {
+    out.push('{');
+    for (index, (name, value)) in labels.enumerate() {
+        if index > 0 {
+            out.push(',');
+        }
+        let value = value
+            .replace('\\', r"\\")
+            .replace('"', r#"\""#)
+            .replace('\n', r"\n");
+        write!(out, "{}=\"{}\"", name, value).unwrap();
+    }
+    out.push('}');
+}
+
+fn format_value(value: f64) -> String {
+    if value == f64::INFINITY {
+        "+Inf".to_string()
+    } else if value == f64::NEG_INFINITY {
+        "-Inf".to_string()
+    } else if value.is_nan() {
+        "NaN".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry};
+
+    #[test]
+    fn test_encode_with_exemplar() {
+        let registry = Registry::new();
+        let opts =
+            HistogramOpts::new("test_batch_time_in_secs", "Batch time").buckets(vec![1.0, 5.0]);
+        let histogram = HistogramVec::new(opts, &["processor_name"]).unwrap();
+        registry.register(Box::new(histogram.clone())).unwrap();
+        let opts = Opts::new("test_batch_count", "Batches");
+        let counter = IntCounterVec::new(opts, &["processor_name"]).unwrap();
+        registry.register(Box::new(counter.clone())).unwrap();
+        let opts = Opts::new("test_errors_total", "Errors");
+        let total_counter = IntCounterVec::new(opts, &["processor_name"]).unwrap();
+        registry.register(Box::new(total_counter.clone())).unwrap();
+
+        histogram.with_label_values(&["default"]).observe(0.5);
+        histogram.with_label_values(&["default"]).observe(3.0);
+        counter.with_label_values(&["default"]).inc_by(2);
+        total_counter.with_label_values(&["default"]).inc();
+        let exemplar_labels = [("start_version", "100"), ("end_version", "199")];
+        record_exemplar(
+            "test_batch_time_in_secs",
+            &[("processor_name", "default")],
+            3.0,
+            &exemplar_labels,
+        );
+
+        let encoded = encode(&registry.gather());
+        let lines: Vec<&str> = encoded.lines().collect();
+        assert!(lines.contains(&"# TYPE test_batch_count unknown"));
+        assert!(lines.contains(&"test_batch_count{processor_name=\"default\"} 2"));
+        assert!(lines.contains(&"# TYPE test_errors counter"));
+        assert!(lines.contains(&"test_errors_total{processor_name=\"default\"} 1"));
+        assert!(lines.contains(&"# TYPE test_batch_time_in_secs histogram"));
+        assert!(lines
+            .contains(&"test_batch_time_in_secs_bucket{processor_name=\"default\",le=\"1\"} 1"));
+        assert!(lines
+            .contains(&"test_batch_time_in_secs_bucket{processor_name=\"default\",le=\"+Inf\"} 2"));
+        assert!(lines.contains(&"test_batch_time_in_secs_count{processor_name=\"default\"} 2"));
+        // The exemplar is on the bucket of 3.0 only
+        let exemplar_lines: Vec<&str> = lines
+            .iter()
+            .copied()
+            .filter(|line| line.contains(" # "))
+            .collect();
+        assert_eq!(exemplar_lines.len(), 1);
+        let prefix = "test_batch_time_in_secs_bucket{processor_name=\"default\",le=\"5\"} 2 # \
+                      {start_version=\"100\",end_version=\"199\"} 3 ";
+        assert!(
+            exemplar_lines[0].starts_with(prefix),
+            "{}",
+            exemplar_lines[0]
+        );
+        assert_eq!(lines.last(), Some(&"# EOF"));
+    }
+}
@@ -0,0 +1,100 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Trace IDs for joining logs and metrics exemplars of the same work, without a tracing backend.
+//! A span created with a `trace_id` field, e.g. `info_span!("batch", trace_id = %id)`, keeps that
+//! ID, and spans created inside it inherit it. The JSON logs already carry the fields of the
+//! current span, so the ID shows up on every log line emitted inside it.
+
+use std::fmt::Debug;
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id},
+    Subscriber,
+};
+use tracing_subscriber::{
+    layer::Context,
+    registry::{LookupSpan, Registry},
+    Layer,
+};
+
+const TRACE_ID_FIELD: &str = "trace_id";
+
+/// Trace ID of a span, kept in its extensions.
+#[derive(Clone)]
+struct TraceId(String);
+
+/// Records the trace ID of every span, see the module docs. Installed by `setup_logging`.
+pub struct TraceIdLayer;
+
+impl<S> Layer<S> for TraceIdLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = TraceIdVisitor::default();
+        attrs.record(&mut visitor);
+        let trace_id = visitor.0.map(TraceId).or_else(|| {
+            span.parent()
+                .and_then(|parent| parent.extensions().get::<TraceId>().cloned())
+        });
+        if let Some(trace_id) = trace_id {
+            span.extensions_mut().insert(trace_id);
+        }
+    }
+}
+
+#[derive(Default)]
+struct TraceIdVisitor(Option<String>);
+
+impl Visit for TraceIdVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == TRACE_ID_FIELD {
+            self.0 = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        if field.name() == TRACE_ID_FIELD {
+            self.0 = Some(format!("{:?}", value));
+        }
+    }
+}
+
+/// Trace ID of the current span. None outside of a span with one, or if `TraceIdLayer` isn't
+/// installed.
+pub fn current_trace_id() -> Option<String> {
+    tracing::Span::current()
+        .with_subscriber(|(id, dispatch)| {
+            let registry = dispatch.downcast_ref::<Registry>()?;
+            let span = registry.span(id)?;
+            let trace_id = span.extensions().get::<TraceId>()?.0.clone();
+            Some(trace_id)
+        })
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+    #[test]
+    fn test_current_trace_id_is_inherited() {
+        let subscriber = Registry::default().with(TraceIdLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(current_trace_id(), None);
+            let batch = tracing::info_span!("batch", trace_id = TRACE_ID);
+            let _batch = batch.enter();
+            assert_eq!(current_trace_id().as_deref(), Some(TRACE_ID));
+            let insert = tracing::info_span!("insert");
+            let _insert = insert.enter();
+            assert_eq!(current_trace_id().as_deref(), Some(TRACE_ID));
+        });
+    }
+}